@@ -0,0 +1,11 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_ARB_shading_language_420pack : enable
+
+// A single triangle covering the viewport, drawn without vertex buffers
+layout (location = 0) out vec2 o_uv;
+void main() {
+    o_uv = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+    // Far plane, behind anything the scene draws
+    gl_Position = vec4(o_uv * 2.0 - 1.0, 1.0, 1.0);
+}
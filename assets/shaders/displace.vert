@@ -0,0 +1,33 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_ARB_shading_language_420pack : enable
+
+layout (location = 0) in vec4 pos;
+layout (location = 1) in vec2 uv;
+layout (location = 2) in vec4 color;
+layout (location = 3) in vec3 normal;
+layout (location = 4) in vec4 tangent;
+
+layout(push_constant) uniform PushConstants {
+    mat4 pvm;
+    // Inverse transpose of the model matrix without its last column, zero when unlit.
+    // The fragment stage reads the tint pushed after it
+    mat3x4 normal;
+} pushConstants;
+
+// Everything leaves in clip space, the projection being linear the evaluation stage interpolates
+// and displaces there as it would in the mesh's own space, without the transforms
+layout (location = 0) out vec2 o_uv;
+// Read by the fragment shader when the device has no tessellation, the mesh drawn flat
+layout (location = 1) out vec4 o_color;
+layout (location = 2) out vec3 o_normal;
+// Clip space offset of a unit displacement along the normal
+layout (location = 3) out vec4 o_displacement;
+void main() {
+    vec3 direction = dot(normal, normal) > 0.0 ? normalize(normal) : vec3(0.0);
+    gl_Position = pushConstants.pvm * pos;
+    o_uv = uv;
+    o_color = color;
+    o_normal = mat3(pushConstants.normal) * normal;
+    o_displacement = pushConstants.pvm * vec4(direction, 0.0);
+}
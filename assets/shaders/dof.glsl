@@ -0,0 +1,38 @@
+#pragma once
+
+// Push constants of the depth of field passes, see `DofConstants` in dof.rs
+layout (push_constant) uniform DofConstants {
+    float near;
+    float far;
+    float focus_distance;
+    float aperture;
+    float focal_length;
+    float sensor_height;
+    float max_coc_pixels;
+    float viewport_height;
+    // -1 gathers the near field, 1 the far field
+    float side;
+} dof;
+
+// Bindings of the depth of field set, see `DOF_BINDINGS`
+layout (set = 0, binding = 0) uniform sampler2D sceneColor;
+layout (set = 0, binding = 1) uniform sampler2D sceneDepth;
+layout (set = 0, binding = 2) uniform sampler2D cocBuffer;
+layout (set = 0, binding = 3) uniform sampler2D nearField;
+layout (set = 0, binding = 4) uniform sampler2D farField;
+
+// Distance from the camera of a depth buffer value, `linearize_depth`
+float linearize_depth(float depth) {
+    return dof.near * dof.far / (dof.far - depth * (dof.far - dof.near));
+}
+
+// Circle of confusion diameter in pixels, negative in front of the focus plane, `coc_pixels`
+float coc_pixels(float distance) {
+    float focus = dof.focus_distance;
+    float focal = dof.focal_length;
+    if (distance <= 0.0 || focus <= focal) {
+        return 0.0;
+    }
+    float coc = focal * focal * (distance - focus) / (dof.aperture * distance * (focus - focal));
+    return clamp(coc / dof.sensor_height * dof.viewport_height, -dof.max_coc_pixels, dof.max_coc_pixels);
+}
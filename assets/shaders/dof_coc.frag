@@ -0,0 +1,13 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_ARB_shading_language_420pack : enable
+
+#include "dof.glsl"
+
+// Circle of confusion of every pixel, in pixels, from the depth the scene left
+layout (location = 0) out float coc;
+
+void main() {
+    float depth = texelFetch(sceneDepth, ivec2(gl_FragCoord.xy), 0).r;
+    coc = coc_pixels(linearize_depth(depth));
+}
@@ -0,0 +1,22 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_ARB_shading_language_420pack : enable
+
+#include "dof.glsl"
+
+// The far field behind the sharp pixels as their circle of confusion grows, so in focus edges
+// stay sharp, then the near field over them by its coverage
+layout (location = 0) in vec2 o_uv;
+
+layout (location = 0) out vec4 uFragColor;
+
+void main() {
+    ivec2 pixel = ivec2(gl_FragCoord.xy);
+    vec4 sharp = texelFetch(sceneColor, pixel, 0);
+    float coc = texelFetch(cocBuffer, pixel, 0).r;
+    vec4 far = texture(farField, o_uv);
+    vec4 near = texture(nearField, o_uv);
+    vec3 color = mix(sharp.rgb, far.rgb, smoothstep(1.0, 4.0, coc) * far.a);
+    color = mix(color, near.rgb, near.a);
+    uFragColor = vec4(color, sharp.a);
+}
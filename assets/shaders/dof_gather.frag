@@ -0,0 +1,39 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_ARB_shading_language_420pack : enable
+
+#include "dof.glsl"
+
+// Half resolution gather blur of one field, the pixels whose circle of confusion on that side of
+// the focus plane covers this one. The near field's coverage is kept in alpha to blend it over
+// the in focus pixels behind it, the far field never bleeds over what is in front of it.
+const int SAMPLES = 32;
+const float GOLDEN_ANGLE = 2.39996323;
+
+layout (location = 0) in vec2 o_uv;
+
+layout (location = 0) out vec4 field;
+
+void main() {
+    vec2 texel = 1.0 / vec2(textureSize(sceneColor, 0));
+    float radius = dof.max_coc_pixels * 0.5;
+    vec3 sum = vec3(0.0);
+    float weights = 0.0;
+    for (int i = 0; i < SAMPLES; i++) {
+        // Spiral filling the disc evenly, in full resolution pixels
+        float distance = sqrt((float(i) + 0.5) / float(SAMPLES)) * radius;
+        float angle = float(i) * GOLDEN_ANGLE;
+        vec2 uv = o_uv + vec2(cos(angle), sin(angle)) * distance * texel;
+        float coc = texture(cocBuffer, uv).r * dof.side;
+        // A sample spreads over half its circle of confusion, faded over a pixel
+        float weight = clamp(coc * 0.5 - distance + 1.0, 0.0, 1.0);
+        sum += texture(sceneColor, uv).rgb * weight;
+        weights += weight;
+    }
+    if (weights > 0.0) {
+        float coverage = dof.side < 0.0 ? clamp(2.0 * weights / float(SAMPLES), 0.0, 1.0) : 1.0;
+        field = vec4(sum / weights, coverage);
+    } else {
+        field = vec4(texture(sceneColor, o_uv).rgb, 0.0);
+    }
+}
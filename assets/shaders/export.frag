@@ -0,0 +1,57 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_ARB_shading_language_420pack : enable
+
+// The presented image, blitted into its own format, converted for an export. Same math as
+// color_space.rs, the target's format encodes sRGB or keeps half floats on write.
+layout (set = 1, binding = 0) uniform sampler2D samplerSource;
+
+layout (push_constant) uniform ExportConstants {
+    // SourceTransfer
+    uint transfer;
+} constants;
+
+const uint TRANSFER_LINEAR = 0;
+const uint TRANSFER_SRGB = 1;
+const uint TRANSFER_PQ = 2;
+
+const float HDR_REFERENCE_WHITE_NITS = 203.0;
+const float PQ_MAX_NITS = 10000.0;
+const float PQ_M1 = 2610.0 / 16384.0;
+const float PQ_M2 = 2523.0 / 4096.0 * 128.0;
+const float PQ_C1 = 3424.0 / 4096.0;
+const float PQ_C2 = 2413.0 / 4096.0 * 32.0;
+const float PQ_C3 = 2392.0 / 4096.0 * 32.0;
+
+const mat3 BT2020_TO_BT709 = mat3(
+    1.660491, -0.124550, -0.018151,
+    -0.587641, 1.132900, -0.100579,
+    -0.072850, -0.008349, 1.118730
+);
+
+layout (location = 0) in vec2 o_uv;
+
+layout (location = 0) out vec4 uFragColor;
+
+vec3 srgb_to_linear(vec3 encoded) {
+    vec3 low = encoded / 12.92;
+    vec3 high = pow((encoded + 0.055) / 1.055, vec3(2.4));
+    return mix(high, low, lessThanEqual(encoded, vec3(0.04045)));
+}
+
+vec3 pq_to_nits(vec3 signal) {
+    vec3 power = pow(max(signal, vec3(0.0)), vec3(1.0 / PQ_M2));
+    return pow(max(power - PQ_C1, vec3(0.0)) / (PQ_C2 - PQ_C3 * power), vec3(1.0 / PQ_M1)) * PQ_MAX_NITS;
+}
+
+void main() {
+    // One texel per fragment, the target has the extent of the source
+    vec4 source = texelFetch(samplerSource, ivec2(gl_FragCoord.xy), 0);
+    vec3 linear = source.rgb;
+    if (constants.transfer == TRANSFER_SRGB) {
+        linear = srgb_to_linear(source.rgb);
+    } else if (constants.transfer == TRANSFER_PQ) {
+        linear = BT2020_TO_BT709 * (pq_to_nits(source.rgb) / HDR_REFERENCE_WHITE_NITS);
+    }
+    uFragColor = vec4(linear, source.a);
+}
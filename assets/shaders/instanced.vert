@@ -0,0 +1,29 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_ARB_shading_language_420pack : enable
+
+layout (location = 0) in vec4 pos;
+layout (location = 1) in vec2 uv;
+layout (location = 2) in vec4 color;
+layout (location = 3) in vec3 normal;
+layout (location = 4) in vec4 tangent;
+// Per instance, read from binding 1 as four columns
+layout (location = 5) in mat4 instance;
+
+layout(push_constant) uniform PushConstants {
+    // Projection, view and the mesh transform shared by every instance
+    mat4 pvm;
+    // Inverse transpose of the mesh transform without its last column, zero when unlit.
+    // The fragment stage reads the tint pushed after it
+    mat3x4 normal;
+} pushConstants;
+
+layout (location = 0) out vec2 o_uv;
+layout (location = 1) out vec4 o_color;
+layout (location = 2) out vec3 o_normal;
+void main() {
+    o_uv = uv;
+    gl_Position = pushConstants.pvm * instance * pos;
+    o_color = color;
+    o_normal = mat3(pushConstants.normal) * transpose(inverse(mat3(instance))) * normal;
+}
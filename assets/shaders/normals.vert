@@ -0,0 +1,32 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_ARB_shading_language_420pack : enable
+
+layout (location = 0) in vec4 pos;
+layout (location = 1) in vec2 uv;
+layout (location = 2) in vec4 color;
+layout (location = 3) in vec3 normal;
+layout (location = 4) in vec4 tangent;
+
+layout(push_constant) uniform PushConstants {
+    mat4 pvm;
+    // Inverse transpose of the model matrix without its last column, zero when unlit.
+    // The fragment stage reads the tint pushed after it
+    mat3x4 normal;
+} pushConstants;
+
+// Length of the lines in the mesh's own units, see Material::normal_lines
+const float NORMAL_LENGTH = 0.1;
+
+// Clip space end of the vertex's normal line, its start is gl_Position
+layout (location = 0) out vec4 o_tip;
+// Read by the fragment shader when the device has no geometry stage, the mesh drawn as it is
+layout (location = 1) out vec4 o_color;
+layout (location = 2) out vec3 o_normal;
+void main() {
+    vec3 direction = dot(normal, normal) > 0.0 ? normalize(normal) : vec3(0.0);
+    gl_Position = pushConstants.pvm * pos;
+    o_tip = pushConstants.pvm * vec4(pos.xyz + direction * NORMAL_LENGTH, 1.0);
+    o_color = color;
+    o_normal = mat3(pushConstants.normal) * normal;
+}
@@ -2,19 +2,43 @@
 #extension GL_ARB_separate_shader_objects : enable
 #extension GL_ARB_shading_language_420pack : enable
 
-// layout (binding = 1) uniform sampler2D samplerColor;
+// The material's albedo, the window's texture for the default material, white until one is
+// loaded
+layout (set = 1, binding = 0) uniform sampler2D albedo;
 
-// layout (binding = 0) uniform UBO{
+// layout (set = 0, binding = 0) uniform UBO{
 //     mat4 transform;
 // } ubo;
 
+// Towards the light, above and in front of the origin in a Y up world
+const vec3 LIGHT_DIRECTION = normalize(vec3(0.4, 1.0, 0.6));
 
-// layout (location = 0) in vec2 o_uv;
+// Diffuse convolution of the environment, flat when no skybox is loaded, see `environment`.
+// Binding 4 is left for a prefiltered specular cubemap
+layout (set = 0, binding = 2) uniform samplerCube irradiance;
+layout (set = 0, binding = 3) uniform Environment {
+    // x scales the irradiance, yzw are unused
+    vec4 intensity;
+} environment;
+
+layout (location = 0) in vec2 o_uv;
 layout (location = 1) in vec4 o_color;
+layout (location = 2) in vec3 o_normal;
 
 layout (location = 0) out vec4 uFragColor;
 
+// Pushed after the vertex stage's transforms, see PushConstants
+layout(push_constant) uniform Tint {
+    layout(offset = 112) vec4 tint;
+} pushConstants;
+
 void main() {
-    // vec4 color = texture(samplerColor, o_uv);
-    uFragColor = o_color;
-}
\ No newline at end of file
+    vec4 color = texture(albedo, o_uv) * o_color;
+    vec3 light = vec3(1.0);
+    if (dot(o_normal, o_normal) > 0.0) {
+        vec3 normal = normalize(o_normal);
+        vec3 ambient = texture(irradiance, normal).rgb * environment.intensity.x;
+        light = max(vec3(dot(normal, LIGHT_DIRECTION)), ambient);
+    }
+    uFragColor = vec4(color.rgb * light, color.a) * pushConstants.tint;
+}
@@ -5,20 +5,27 @@
 layout (location = 0) in vec4 pos;
 layout (location = 1) in vec2 uv;
 layout (location = 2) in vec4 color;
+layout (location = 3) in vec3 normal;
+layout (location = 4) in vec4 tangent;
 
-// layout (binding = 0) uniform UBO{
+// layout (set = 0, binding = 0) uniform UBO{
 //     mat4 transform;
 // } ubo;
 
 layout(push_constant) uniform PushConstants {
     mat4 pvm;
+    // Inverse transpose of the model matrix without its last column, zero when unlit.
+    // The fragment stage reads the tint pushed after it
+    mat3x4 normal;
 } pushConstants;
 
 
-// layout (location = 0) out vec2 o_uv;
+layout (location = 0) out vec2 o_uv;
 layout (location = 1) out vec4 o_color;
+layout (location = 2) out vec3 o_normal;
 void main() {
-    // o_uv = uv;
+    o_uv = uv;
     gl_Position = pushConstants.pvm * pos;
     o_color = color;
-}
\ No newline at end of file
+    o_normal = mat3(pushConstants.normal) * normal;
+}
@@ -0,0 +1,30 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_ARB_shading_language_420pack : enable
+
+layout (set = 1, binding = 0) uniform sampler2DArray samplerLayers;
+
+// Towards the light, above and in front of the origin in a Y up world
+const vec3 LIGHT_DIRECTION = normalize(vec3(0.4, 1.0, 0.6));
+const float AMBIENT = 0.2;
+
+layout (location = 0) in vec2 o_uv;
+layout (location = 1) in vec4 o_color;
+layout (location = 2) in vec3 o_normal;
+layout (location = 3) flat in float o_layer;
+
+layout (location = 0) out vec4 uFragColor;
+
+// Pushed after the vertex stage's transforms, see PushConstants
+layout(push_constant) uniform Tint {
+    layout(offset = 112) vec4 tint;
+} pushConstants;
+
+void main() {
+    vec4 color = texture(samplerLayers, vec3(o_uv, o_layer)) * o_color;
+    float light = 1.0;
+    if (dot(o_normal, o_normal) > 0.0) {
+        light = max(dot(normalize(o_normal), LIGHT_DIRECTION), AMBIENT);
+    }
+    uFragColor = vec4(color.rgb * light, color.a) * pushConstants.tint;
+}
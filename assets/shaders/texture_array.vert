@@ -0,0 +1,29 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_ARB_shading_language_420pack : enable
+
+layout (location = 0) in vec4 pos;
+layout (location = 1) in vec2 uv;
+layout (location = 2) in vec4 color;
+layout (location = 3) in vec3 normal;
+layout (location = 4) in vec4 tangent;
+
+layout(push_constant) uniform PushConstants {
+    mat4 pvm;
+    // Inverse transpose of the model matrix without its last column, zero when unlit. Its last
+    // row is free, the layer of the texture array travels in normal[2][3]. The fragment stage
+    // reads the tint pushed after it
+    mat3x4 normal;
+} pushConstants;
+
+layout (location = 0) out vec2 o_uv;
+layout (location = 1) out vec4 o_color;
+layout (location = 2) out vec3 o_normal;
+layout (location = 3) flat out float o_layer;
+void main() {
+    o_uv = uv;
+    gl_Position = pushConstants.pvm * pos;
+    o_color = color;
+    o_normal = mat3(pushConstants.normal) * normal;
+    o_layer = pushConstants.normal[2][3];
+}
@@ -0,0 +1,43 @@
+//! Embeds the compiled shaders of `assets/bin` in the library, see `Shader::from_embedded`.
+//!
+//! Debug builds compile them at startup, so a release build embeds what the last debug run left
+//! there. Without any, the table is empty and every shader is read from disk.
+use std::{env, fs, path::Path};
+
+const COMPILED_SHADERS: &str = "assets/bin";
+
+fn main() {
+    println!("cargo:rerun-if-changed={COMPILED_SHADERS}");
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").expect("Set by cargo");
+    let mut binaries: Vec<(String, String)> =
+        fs::read_dir(Path::new(&manifest_dir).join(COMPILED_SHADERS))
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "spv"))
+            .filter_map(|path| {
+                let name = path.file_stem()?.to_str()?.to_string();
+                Some((name, path.to_str()?.to_string()))
+            })
+            .collect();
+    binaries.sort();
+    for (_, path) in &binaries {
+        println!("cargo:rerun-if-changed={path}");
+    }
+    if binaries.is_empty() && env::var("PROFILE").is_ok_and(|profile| profile == "release") {
+        println!(
+            "cargo:warning=No compiled shaders in {COMPILED_SHADERS} to embed, run a debug build \
+             first or ship them next to the binary"
+        );
+    }
+
+    let entries: String = binaries
+        .iter()
+        .map(|(name, path)| format!("    ({name:?}, include_bytes!({path:?})),\n"))
+        .collect();
+    let table = format!("&[\n{entries}]\n");
+    let out_dir = env::var("OUT_DIR").expect("Set by cargo");
+    fs::write(Path::new(&out_dir).join("embedded_shaders.rs"), table)
+        .expect("Failed to write the embedded shaders table");
+}
@@ -0,0 +1,89 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use glam::{Mat4, Vec3};
+use pulsar::{
+    app::{Application, UserEvent},
+    assets::TextureLibrary,
+    metrics,
+    model::Mesh,
+};
+use std::{error::Error, path::Path};
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    window::WindowId,
+};
+
+const PICTURE: &str = "assets/img/picture.png";
+
+fn cube_at(x: f32) -> Mesh {
+    let mut cube = Mesh::cube(0.5, [0.8, 0.4, 0.2, 1.0]);
+    cube.transform = Mat4::from_translation(Vec3::new(x, 0.0, 0.0));
+    cube
+}
+
+/// Adds the same cube three times, drawn from a single GPU copy. Images dropped on the window
+/// are loaded once whatever their path.
+struct Host {
+    app: Application,
+    added: bool,
+}
+
+impl ApplicationHandler<UserEvent> for Host {
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        self.app.user_event(event_loop, event);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        self.app.window_event(event_loop, window_id, event);
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.resumed(event_loop);
+        if self.added {
+            return;
+        }
+        let windows: Vec<WindowId> = self.app.window_manager.window_ids().collect();
+        for window_id in windows {
+            for x in [-1.0, 0.0, 1.0] {
+                self.app.add_mesh(window_id, cube_at(x));
+            }
+        }
+        self.added = true;
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.exiting(event_loop);
+        let stats = metrics::dedup_stats();
+        println!(
+            "{} buffers allocated, {} loads deduplicated saving {} bytes",
+            metrics::buffer_allocations(),
+            stats.hits,
+            stats.bytes_saved
+        );
+    }
+}
+
+// Load the same picture through two paths, then draw identical meshes
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut textures = TextureLibrary::default();
+    let first = textures.load(Path::new(PICTURE))?;
+    let again = textures.load(Path::new("./assets/img/../img/picture.png"))?;
+    println!(
+        "{PICTURE} loaded twice as {first:?} and {again:?}, {} texture held",
+        textures.len()
+    );
+
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut host = Host {
+        app: Application::new(&event_loop)?,
+        added: false,
+    };
+    event_loop.run_app(&mut host).map_err(Into::into)
+}
@@ -0,0 +1,23 @@
+#version 450
+
+// Layout of `pulsar::background::BackgroundConstants`
+layout(push_constant) uniform Background {
+    vec2 resolution;
+    vec2 cursor;
+    float time;
+    vec4 user[2];
+} background;
+
+layout (location = 0) in vec2 uv;
+layout (location = 0) out vec4 uFragColor;
+
+// A diagonal gradient drifting over time, brightened around the cursor by the first user value
+void main() {
+    float wave = 0.5 + 0.5 * sin(background.time + (uv.x + uv.y) * 3.0);
+    vec3 color = mix(vec3(0.05, 0.1, 0.3), vec3(0.6, 0.2, 0.5), wave);
+    if (background.cursor.x >= 0.0) {
+        float glow = 1.0 - clamp(distance(gl_FragCoord.xy, background.cursor) / 200.0, 0.0, 1.0);
+        color += glow * background.user[0].x * 0.3;
+    }
+    uFragColor = vec4(color, 1.0);
+}
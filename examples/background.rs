@@ -0,0 +1,80 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use pulsar::{
+    app::{Application, UserEvent},
+    background::BackgroundMode,
+};
+use std::{
+    collections::HashSet,
+    error::Error,
+    time::{Duration, Instant},
+};
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    window::WindowId,
+};
+
+const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+const FRAGMENT_SOURCE: &str = "examples/background.frag";
+
+/// Draws a drifting gradient behind the scene, glowing around the cursor as it pulses.
+struct Gradient {
+    app: Application,
+    start: Instant,
+    shaded: HashSet<WindowId>,
+}
+
+impl ApplicationHandler<UserEvent> for Gradient {
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        self.app.user_event(event_loop, event);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        self.app.window_event(event_loop, window_id, event);
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.resumed(event_loop);
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.about_to_wait(event_loop);
+        let pulse = 0.5 + 0.5 * (self.start.elapsed().as_secs_f32() * 4.0).sin();
+        let windows: Vec<WindowId> = self.app.window_manager.window_ids().collect();
+        for window_id in windows {
+            // Editing the source while this runs reloads it
+            if self.shaded.insert(window_id) {
+                let mode = BackgroundMode::Shader {
+                    fragment_source: FRAGMENT_SOURCE.into(),
+                };
+                self.app.set_background(window_id, mode);
+            }
+            let mut uniforms = [0.0; 8];
+            uniforms[0] = pulse;
+            self.app.set_background_uniforms(window_id, uniforms);
+        }
+        event_loop.set_control_flow(ControlFlow::WaitUntil(Instant::now() + FRAME_INTERVAL));
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.exiting(event_loop);
+    }
+}
+
+// Shade the demo scene's background
+fn main() -> Result<(), Box<dyn Error>> {
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut gradient = Gradient {
+        app: Application::new(&event_loop)?,
+        start: Instant::now(),
+        shaded: HashSet::new(),
+    };
+    event_loop.run_app(&mut gradient).map_err(Into::into)
+}
@@ -0,0 +1,52 @@
+use glam::{Mat4, Vec2, Vec3};
+use pulsar::{
+    camera::{Camera, CameraController, OrthographicProjection, PerspectiveProjection},
+    picking::Ray,
+    world::WorldConvention,
+};
+
+const VIEWPORT: Vec2 = Vec2::new(1200.0, 800.0);
+
+// Print the view of a camera looking at a point in each convention, then the steps of a dolly
+// toward the ground under a cursor
+fn main() {
+    for convention in [WorldConvention::YUp, WorldConvention::ZUp] {
+        let mut camera = camera(convention);
+        camera.look_at(Vec3::new(1.0, 2.0, -3.0));
+        camera.rotate_local(0.0, 0.0, std::f32::consts::FRAC_PI_6);
+        println!(
+            "{convention:?}: forward {} up {} right {}",
+            camera.forward(),
+            camera.up(),
+            camera.right()
+        );
+    }
+
+    let controller = CameraController::default();
+    let mut camera = camera(WorldConvention::YUp);
+    camera.set_position(Vec3::new(0.0, 3.0, 6.0));
+    camera.look_at(Vec3::ZERO);
+    let ray = Ray::from_cursor(
+        Vec2::new(300.0, 200.0),
+        VIEWPORT,
+        camera.perspective().projection_view,
+    );
+    let point = controller.dolly_target(&camera, &ray, None);
+    for _ in 0..5 {
+        controller.dolly_toward_cursor(&mut camera, 1.0, &ray, None);
+        println!(
+            "At {}, {} from {point}",
+            camera.position(),
+            camera.position().distance(point)
+        );
+    }
+}
+
+fn camera(convention: WorldConvention) -> Camera {
+    Camera::new(
+        Vec3::new(0.0, 0.0, 4.0),
+        convention,
+        OrthographicProjection::new(0.0, 1.0, 0.0, 1.0, -1.0, 1.0, Mat4::IDENTITY),
+        PerspectiveProjection::new(1.0, 1.5, 0.1, 100.0, Mat4::IDENTITY),
+    )
+}
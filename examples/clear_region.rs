@@ -0,0 +1,120 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use ash::vk;
+use pulsar::{
+    app::{Application, UserEvent},
+    color_space::ExportColorSpace,
+    custom_pass::{ClearTarget, CustomPassSlot},
+};
+use std::{error::Error, path::PathBuf};
+use winit::{
+    application::ApplicationHandler,
+    dpi::PhysicalSize,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    window::WindowId,
+};
+
+const MAGENTA: [f32; 4] = [1.0, 0.0, 1.0, 1.0];
+const CAPTURE: u32 = 64;
+
+/// Top left quarter of a target of `extent`.
+fn quadrant(extent: vk::Extent2D) -> vk::Rect2D {
+    vk::Rect2D {
+        offset: vk::Offset2D::default(),
+        extent: vk::Extent2D {
+            width: extent.width / 2,
+            height: extent.height / 2,
+        },
+    }
+}
+
+/// Clears the top left quadrant after the UI, then checks a capture shows exactly that quadrant.
+struct Host {
+    app: Application,
+    capture: PathBuf,
+    requested: bool,
+}
+
+impl Host {
+    /// Whether the capture is saved, and if so whether only the quadrant is magenta.
+    fn check_capture(&self) -> Option<Result<(), String>> {
+        let image = image::open(&self.capture).ok()?.into_rgba8();
+        let half = CAPTURE / 2;
+        let wrong = image.enumerate_pixels().find(|(x, y, pixel)| {
+            let inside = *x < half && *y < half;
+            (pixel.0 == [255, 0, 255, 255]) != inside
+        });
+        Some(match wrong {
+            Some((x, y, pixel)) => Err(format!("Pixel {x}, {y} is {:?}", pixel.0)),
+            None => Ok(()),
+        })
+    }
+}
+
+impl ApplicationHandler<UserEvent> for Host {
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        self.app.user_event(event_loop, event);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        self.app.window_event(event_loop, window_id, event);
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.resumed(event_loop);
+        if self.requested {
+            return;
+        }
+        let windows: Vec<WindowId> = self.app.window_manager.window_ids().collect();
+        for window_id in windows {
+            self.app.set_custom_pass(
+                window_id,
+                CustomPassSlot::AfterUi,
+                Box::new(|device, command_buffer, context| {
+                    let rect = quadrant(context.extent);
+                    context.clear_region(device, command_buffer, rect, ClearTarget::Color(MAGENTA));
+                }),
+            );
+            self.app.capture_screenshot_at(
+                window_id,
+                PhysicalSize::new(CAPTURE, CAPTURE),
+                self.capture.clone(),
+                ExportColorSpace::Srgb8,
+            );
+        }
+        self.requested = true;
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.about_to_wait(event_loop);
+        if let Some(result) = self.check_capture() {
+            match result {
+                Ok(()) => println!("Only the top left quadrant of the capture was cleared"),
+                Err(error) => eprintln!("{error}"),
+            }
+            let _ = std::fs::remove_file(&self.capture);
+            event_loop.exit();
+        }
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.exiting(event_loop);
+    }
+}
+
+// Clear a quadrant mid frame and read it back through a screenshot
+fn main() -> Result<(), Box<dyn Error>> {
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut host = Host {
+        app: Application::new(&event_loop)?,
+        capture: std::env::temp_dir().join("pulsar_clear_region.png"),
+        requested: false,
+    };
+    event_loop.run_app(&mut host).map_err(Into::into)
+}
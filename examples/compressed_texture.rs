@@ -0,0 +1,110 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use pulsar::{
+    app::{Application, UserEvent},
+    block_compression::{level_extent, BlockFormat, CompressedTexture, BLOCK_EDGE},
+    config::ApplicationOptions,
+    error::PulsarError,
+    texture::SamplerDesc,
+};
+use std::{collections::HashSet, error::Error};
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    window::WindowId,
+};
+
+/// Edge of the checkerboard loaded into the demo scene, its blocks alternate colors.
+const CHECKER_SIZE: u32 = 64;
+
+/// A BC1 block of `color0` and `color1`, each texel picking `index(texel)`.
+fn bc1_block(color0: u16, color1: u16, index: impl Fn(u32) -> u32) -> [u8; 8] {
+    let indices = (0..16).fold(0, |indices, texel| indices | index(texel) << (2 * texel));
+    let mut block = [0; 8];
+    block[..2].copy_from_slice(&color0.to_le_bytes());
+    block[2..4].copy_from_slice(&color1.to_le_bytes());
+    block[4..].copy_from_slice(&indices.to_le_bytes());
+    block
+}
+
+/// Red and blue blocks alternating on every level down to 1x1.
+fn checkerboard() -> Result<CompressedTexture, PulsarError> {
+    let levels = (0..CHECKER_SIZE.ilog2() + 1)
+        .map(|level| {
+            let (width, height) = level_extent(CHECKER_SIZE, CHECKER_SIZE, level);
+            let (columns, rows) = (width.div_ceil(BLOCK_EDGE), height.div_ceil(BLOCK_EDGE));
+            (0..rows * columns)
+                .flat_map(|block| {
+                    let color = match (block / columns + block % columns) % 2 {
+                        0 => 0xf800,
+                        _ => 0x001f,
+                    };
+                    bc1_block(color, color, |_| 0)
+                })
+                .collect()
+        })
+        .collect();
+    CompressedTexture::new(BlockFormat::Bc1, CHECKER_SIZE, CHECKER_SIZE, levels)
+}
+
+/// Loads the checkerboard into every window, uploaded as BC1 or decoded when unsupported.
+struct Compressed {
+    app: Application,
+    texture: CompressedTexture,
+    loaded: HashSet<WindowId>,
+}
+
+impl ApplicationHandler<UserEvent> for Compressed {
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        self.app.user_event(event_loop, event);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        self.app.window_event(event_loop, window_id, event);
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.resumed(event_loop);
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.about_to_wait(event_loop);
+        let windows: Vec<WindowId> = self.app.window_manager.window_ids().collect();
+        for window_id in windows {
+            if self.loaded.insert(window_id) {
+                self.app.load_compressed_texture(
+                    window_id,
+                    self.texture.clone(),
+                    SamplerDesc::nearest(),
+                );
+            }
+        }
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.exiting(event_loop);
+    }
+}
+
+// Show a BC1 checkerboard on the demo scene, decoded to RGBA8 where the device lacks BC support.
+fn main() -> Result<(), Box<dyn Error>> {
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut compressed = Compressed {
+        app: Application::with_options(
+            &event_loop,
+            ApplicationOptions {
+                demo_scene: Some(true),
+                ..Default::default()
+            },
+        )?,
+        texture: checkerboard()?,
+        loaded: HashSet::new(),
+    };
+    event_loop.run_app(&mut compressed).map_err(Into::into)
+}
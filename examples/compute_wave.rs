@@ -0,0 +1,78 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use pulsar::{
+    app::{Application, UserEvent},
+    compute::VertexDeformer,
+    model::Mesh,
+};
+use std::error::Error;
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    window::WindowId,
+};
+
+const AMPLITUDE: f32 = 0.25;
+const WAVELENGTH: f32 = 2.0;
+const SPEED: f32 = 0.5;
+const SUBDIVISIONS: u32 = 63;
+
+fn plane() -> Mesh {
+    Mesh::plane(6.0, 6.0, SUBDIVISIONS, [0.2, 0.5, 0.9, 1.0])
+}
+
+fn deformer() -> VertexDeformer {
+    VertexDeformer::wave(AMPLITUDE, WAVELENGTH, SPEED)
+}
+
+/// Adds the waving plane to every window once they exist.
+struct Viewer {
+    app: Application,
+    added: bool,
+}
+
+impl ApplicationHandler<UserEvent> for Viewer {
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        self.app.user_event(event_loop, event);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        self.app.window_event(event_loop, window_id, event);
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.resumed(event_loop);
+        if self.added {
+            return;
+        }
+        let windows: Vec<WindowId> = self.app.window_manager.window_ids().collect();
+        for window_id in windows {
+            self.app.add_deformed_mesh(window_id, plane(), deformer());
+        }
+        self.added = true;
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.about_to_wait(event_loop);
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.exiting(event_loop);
+    }
+}
+
+// Show a plane waving under the compute shader.
+fn main() -> Result<(), Box<dyn Error>> {
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut viewer = Viewer {
+        app: Application::new(&event_loop)?,
+        added: false,
+    };
+    event_loop.run_app(&mut viewer).map_err(Into::into)
+}
@@ -0,0 +1,27 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use pulsar::{
+    app::{Application, UserEvent},
+    config::{ApplicationOptions, PulsarConfig},
+};
+use std::error::Error;
+use winit::event_loop::EventLoop;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let config_path = PulsarConfig::default_path();
+    if !config_path.exists() {
+        PulsarConfig::write_default(&config_path)?;
+        println!("Wrote default configuration to {}", config_path.display());
+    }
+
+    // Code-specified values win over the file, edit the clear color or fps cap live
+    let options = ApplicationOptions {
+        config_path: Some(config_path),
+        title: Some("Pulsar configuration example".to_string()),
+        ..Default::default()
+    };
+
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut app = Application::with_options(&event_loop, options)?;
+    event_loop.run_app(&mut app).map_err(Into::into)
+}
@@ -0,0 +1,112 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use pulsar::{
+    app::{Application, UserEvent},
+    config::ApplicationOptions,
+    texture::{CubemapSource, SamplerDesc, CUBE_FACES},
+};
+use std::{collections::HashSet, error::Error};
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    window::WindowId,
+};
+
+const FACE_SIZE: u32 = 8;
+
+/// Texel `index` of `face`, telling both apart once sliced.
+fn texel(face: usize, index: u32) -> [u8; 4] {
+    [
+        face as u8 * 40,
+        (index % 256) as u8,
+        (index / 256) as u8,
+        255,
+    ]
+}
+
+/// `columns` by `rows` faces with `cells` holding the face at each column and row, the others
+/// black.
+fn layout(columns: u32, rows: u32, cells: [(u32, u32); CUBE_FACES]) -> Vec<u8> {
+    let (width, height) = (columns * FACE_SIZE, rows * FACE_SIZE);
+    let mut texels = vec![0; (width * height * 4) as usize];
+    for (face, (column, row)) in cells.into_iter().enumerate() {
+        for index in 0..FACE_SIZE * FACE_SIZE {
+            let x = column * FACE_SIZE + index % FACE_SIZE;
+            let y = row * FACE_SIZE + index / FACE_SIZE;
+            let start = ((y * width + x) * 4) as usize;
+            texels[start..start + 4].copy_from_slice(&texel(face, index));
+        }
+    }
+    texels
+}
+
+/// Loads the cubemap into every window's environment binding.
+struct Environment {
+    app: Application,
+    source: CubemapSource,
+    loaded: HashSet<WindowId>,
+}
+
+impl ApplicationHandler<UserEvent> for Environment {
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        self.app.user_event(event_loop, event);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        self.app.window_event(event_loop, window_id, event);
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.resumed(event_loop);
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.about_to_wait(event_loop);
+        let windows: Vec<WindowId> = self.app.window_manager.window_ids().collect();
+        for window_id in windows {
+            if self.loaded.insert(window_id) {
+                self.app
+                    .load_cubemap(window_id, self.source.clone(), SamplerDesc::default());
+            }
+        }
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.exiting(event_loop);
+    }
+}
+
+// Load a synthetic horizontal cross as the environment of the demo scene, whose ambient light
+// takes the tint of its irradiance.
+fn main() -> Result<(), Box<dyn Error>> {
+    let (columns, rows) = (4, 3);
+    let cells = [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (3, 1)];
+    let path = std::env::temp_dir().join("pulsar_cubemap_cross.png");
+    image::save_buffer(
+        &path,
+        &layout(columns, rows, cells),
+        columns * FACE_SIZE,
+        rows * FACE_SIZE,
+        image::ColorType::Rgba8,
+    )?;
+
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut environment = Environment {
+        app: Application::with_options(
+            &event_loop,
+            ApplicationOptions {
+                demo_scene: Some(true),
+                ..Default::default()
+            },
+        )?,
+        source: CubemapSource::Layout(path),
+        loaded: HashSet::new(),
+    };
+    event_loop.run_app(&mut environment).map_err(Into::into)
+}
@@ -0,0 +1,293 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use ash::{util::read_spv, vk};
+use glam::{Mat4, Vec3};
+use pulsar::{
+    app::{Application, UserEvent},
+    custom_pass::{CustomPassSlot, FrameContext},
+    model::Vertex,
+};
+use std::{error::Error, fs::File, mem};
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    window::WindowId,
+};
+
+/// Objects of the triangle drawn by the custom pass, created with the first frame's device.
+struct Overlay {
+    device: ash::Device,
+    layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    buffer: vk::Buffer,
+    memory: vk::DeviceMemory,
+}
+
+const TRIANGLE: [([f32; 2], [f32; 4]); 3] = [
+    ([0.0, 1.0], [1.0, 0.2, 0.2, 1.0]),
+    ([-0.9, -0.6], [0.2, 1.0, 0.2, 1.0]),
+    ([0.9, -0.6], [0.2, 0.2, 1.0, 1.0]),
+];
+
+impl Overlay {
+    fn new(device: &ash::Device, context: &FrameContext) -> Result<Self, Box<dyn Error>> {
+        let shader = |name: &str| -> Result<vk::ShaderModule, Box<dyn Error>> {
+            // Compiled by the renderer at startup in debug builds
+            let code = read_spv(&mut File::open(format!("assets/bin/{name}.spv"))?)?;
+            let info = vk::ShaderModuleCreateInfo::default().code(&code);
+            Ok(unsafe { device.create_shader_module(&info, None)? })
+        };
+        let vertex_shader = shader("vert")?;
+        let fragment_shader = shader("frag")?;
+
+        let push_constant_range = vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::VERTEX,
+            offset: 0,
+            size: mem::size_of::<[Mat4; 2]>() as u32,
+        };
+        let layout_info = vk::PipelineLayoutCreateInfo::default()
+            .push_constant_ranges(std::slice::from_ref(&push_constant_range));
+        let layout = unsafe { device.create_pipeline_layout(&layout_info, None)? };
+
+        let stages = [
+            vk::PipelineShaderStageCreateInfo::default()
+                .stage(vk::ShaderStageFlags::VERTEX)
+                .module(vertex_shader)
+                .name(c"main"),
+            vk::PipelineShaderStageCreateInfo::default()
+                .stage(vk::ShaderStageFlags::FRAGMENT)
+                .module(fragment_shader)
+                .name(c"main"),
+        ];
+        let bindings = [vk::VertexInputBindingDescription {
+            binding: 0,
+            stride: mem::size_of::<Vertex>() as u32,
+            input_rate: vk::VertexInputRate::VERTEX,
+        }];
+        let attribute = |location, format, offset: usize| vk::VertexInputAttributeDescription {
+            location,
+            binding: 0,
+            format,
+            offset: offset as u32,
+        };
+        let attributes = [
+            attribute(
+                0,
+                vk::Format::R32G32B32A32_SFLOAT,
+                mem::offset_of!(Vertex, pos),
+            ),
+            attribute(1, vk::Format::R32G32_SFLOAT, mem::offset_of!(Vertex, uv)),
+            attribute(
+                2,
+                vk::Format::R32G32B32A32_SFLOAT,
+                mem::offset_of!(Vertex, color),
+            ),
+            attribute(
+                3,
+                vk::Format::R32G32B32_SFLOAT,
+                mem::offset_of!(Vertex, normal),
+            ),
+            attribute(
+                4,
+                vk::Format::R32G32B32A32_SFLOAT,
+                mem::offset_of!(Vertex, tangent),
+            ),
+        ];
+        let vertex_input = vk::PipelineVertexInputStateCreateInfo::default()
+            .vertex_binding_descriptions(&bindings)
+            .vertex_attribute_descriptions(&attributes);
+        let input_assembly = vk::PipelineInputAssemblyStateCreateInfo::default()
+            .topology(vk::PrimitiveTopology::TRIANGLE_LIST);
+        let viewport = vk::PipelineViewportStateCreateInfo::default()
+            .viewport_count(1)
+            .scissor_count(1);
+        let rasterization = vk::PipelineRasterizationStateCreateInfo::default()
+            .polygon_mode(vk::PolygonMode::FILL)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .line_width(1.0);
+        let multisample = vk::PipelineMultisampleStateCreateInfo::default()
+            .rasterization_samples(context.samples);
+        // Drawn through the scene, without hiding what comes after
+        let depth = vk::PipelineDepthStencilStateCreateInfo::default();
+        let blend_attachments = [vk::PipelineColorBlendAttachmentState::default()
+            .color_write_mask(vk::ColorComponentFlags::RGBA)];
+        let blend =
+            vk::PipelineColorBlendStateCreateInfo::default().attachments(&blend_attachments);
+        let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+        let dynamic = vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_states);
+        let pipeline_info = vk::GraphicsPipelineCreateInfo::default()
+            .stages(&stages)
+            .vertex_input_state(&vertex_input)
+            .input_assembly_state(&input_assembly)
+            .viewport_state(&viewport)
+            .rasterization_state(&rasterization)
+            .multisample_state(&multisample)
+            .depth_stencil_state(&depth)
+            .color_blend_state(&blend)
+            .dynamic_state(&dynamic)
+            .layout(layout)
+            .render_pass(context.render_pass)
+            .subpass(0);
+        let pipeline = unsafe {
+            device
+                .create_graphics_pipelines(vk::PipelineCache::null(), &[pipeline_info], None)
+                .map_err(|(_, error)| error)?[0]
+        };
+        unsafe {
+            device.destroy_shader_module(vertex_shader, None);
+            device.destroy_shader_module(fragment_shader, None);
+        }
+
+        let vertices = TRIANGLE.map(|([x, y], color)| Vertex {
+            pos: [x, y, 0.0, 1.0],
+            uv: [0.0; 2],
+            color,
+            normal: [0.0; 3],
+            tangent: [1.0, 0.0, 0.0, 1.0],
+            joints: [0; 4],
+            weights: [0.0; 4],
+        });
+        let size = mem::size_of_val(&vertices) as u64;
+        let buffer_info = vk::BufferCreateInfo::default()
+            .size(size)
+            .usage(vk::BufferUsageFlags::VERTEX_BUFFER)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE);
+        let buffer = unsafe { device.create_buffer(&buffer_info, None)? };
+        let requirements = unsafe { device.get_buffer_memory_requirements(buffer) };
+        let host_visible =
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT;
+        let properties = &context.device_memory_properties;
+        let memory_type = (0..properties.memory_type_count)
+            .find(|&index| {
+                requirements.memory_type_bits & (1 << index) != 0
+                    && properties.memory_types[index as usize]
+                        .property_flags
+                        .contains(host_visible)
+            })
+            .ok_or("No host visible memory")?;
+        let allocate_info = vk::MemoryAllocateInfo::default()
+            .allocation_size(requirements.size)
+            .memory_type_index(memory_type);
+        let memory = unsafe {
+            let memory = device.allocate_memory(&allocate_info, None)?;
+            device.bind_buffer_memory(buffer, memory, 0)?;
+            let ptr = device.map_memory(memory, 0, size, vk::MemoryMapFlags::empty())?;
+            std::ptr::copy_nonoverlapping(vertices.as_ptr(), ptr.cast(), vertices.len());
+            device.unmap_memory(memory);
+            memory
+        };
+
+        Ok(Self {
+            device: device.clone(),
+            layout,
+            pipeline,
+            buffer,
+            memory,
+        })
+    }
+
+    /// Spin the triangle in front of the camera, turning once every 4 seconds at 60 fps.
+    fn record(&self, command_buffer: vk::CommandBuffer, context: &FrameContext) {
+        let angle = context.frame as f32 * std::f32::consts::TAU / 240.0;
+        let model = Mat4::from_translation(Vec3::new(0.0, 1.5, 0.0))
+            * Mat4::from_rotation_y(angle)
+            * Mat4::from_scale(Vec3::splat(0.5));
+        let constants = [context.perspective * model, Mat4::ZERO];
+        let bytes = unsafe {
+            std::slice::from_raw_parts(
+                constants.as_ptr().cast::<u8>(),
+                mem::size_of_val(&constants),
+            )
+        };
+        unsafe {
+            let device = &self.device;
+            device.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline,
+            );
+            device.cmd_push_constants(
+                command_buffer,
+                self.layout,
+                vk::ShaderStageFlags::VERTEX,
+                0,
+                bytes,
+            );
+            device.cmd_bind_vertex_buffers(command_buffer, 0, &[self.buffer], &[0]);
+            device.cmd_draw(command_buffer, 3, 1, 0, 0);
+        }
+    }
+}
+
+impl Drop for Overlay {
+    // The renderer drops its passes with the device idle
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_pipeline(self.pipeline, None);
+            self.device.destroy_pipeline_layout(self.layout, None);
+            self.device.destroy_buffer(self.buffer, None);
+            self.device.free_memory(self.memory, None);
+        }
+    }
+}
+
+/// Installs the overlay pass in every window it opens.
+struct Host {
+    app: Application,
+    installed: bool,
+}
+
+impl ApplicationHandler<UserEvent> for Host {
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        self.app.user_event(event_loop, event);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        self.app.window_event(event_loop, window_id, event);
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.resumed(event_loop);
+        if self.installed {
+            return;
+        }
+        let windows: Vec<WindowId> = self.app.window_manager.window_ids().collect();
+        for window_id in windows {
+            let mut overlay: Option<Overlay> = None;
+            self.app.set_custom_pass(
+                window_id,
+                CustomPassSlot::AfterMain,
+                Box::new(move |device, command_buffer, context| {
+                    if overlay.is_none() {
+                        overlay =
+                            Some(Overlay::new(device, context).expect("Overlay setup failed"));
+                    }
+                    if let Some(overlay) = &overlay {
+                        overlay.record(command_buffer, context);
+                    }
+                }),
+            );
+        }
+        self.installed = true;
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.exiting(event_loop);
+    }
+}
+
+// Draw a triangle with a pipeline built here through raw ash, inside the main render pass
+fn main() -> Result<(), Box<dyn Error>> {
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut host = Host {
+        app: Application::new(&event_loop)?,
+        installed: false,
+    };
+    event_loop.run_app(&mut host).map_err(Into::into)
+}
@@ -0,0 +1,82 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use glam::{Mat4, Vec3};
+use pulsar::{
+    app::{Application, UserEvent},
+    debug_lines::DebugLines,
+};
+use std::{
+    error::Error,
+    time::{Duration, Instant},
+};
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    window::WindowId,
+};
+
+const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+/// Draws the world axes, a box around the origin and the frustum of a camera circling it.
+struct Visualizer {
+    app: Application,
+    start: Instant,
+}
+
+fn circling_camera(seconds: f32) -> Mat4 {
+    let eye = Vec3::new(seconds.cos() * 4.0, 1.5, seconds.sin() * 4.0);
+    let projection = Mat4::perspective_rh(0.8, 16.0 / 9.0, 0.5, 2.5);
+    projection * Mat4::look_at_rh(eye, Vec3::ZERO, Vec3::Y)
+}
+
+fn scene_lines(seconds: f32) -> DebugLines {
+    let mut lines = DebugLines::new();
+    lines.add_axis_gizmo(Mat4::IDENTITY, 1.0);
+    lines.add_aabb(Vec3::splat(-0.5), Vec3::splat(0.5), [1.0, 1.0, 0.0, 1.0]);
+    lines.add_frustum(circling_camera(seconds), [0.0, 1.0, 1.0, 1.0]);
+    lines
+}
+
+impl ApplicationHandler<UserEvent> for Visualizer {
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        self.app.user_event(event_loop, event);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        self.app.window_event(event_loop, window_id, event);
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.resumed(event_loop);
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.about_to_wait(event_loop);
+        let lines = scene_lines(self.start.elapsed().as_secs_f32());
+        let windows: Vec<WindowId> = self.app.window_manager.window_ids().collect();
+        for window_id in windows {
+            self.app.set_debug_lines(window_id, lines.clone());
+        }
+        event_loop.set_control_flow(ControlFlow::WaitUntil(Instant::now() + FRAME_INTERVAL));
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.exiting(event_loop);
+    }
+}
+
+// Draw the axes, a box and a circling camera's frustum over the demo scene
+fn main() -> Result<(), Box<dyn Error>> {
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut visualizer = Visualizer {
+        app: Application::new(&event_loop)?,
+        start: Instant::now(),
+    };
+    event_loop.run_app(&mut visualizer).map_err(Into::into)
+}
@@ -0,0 +1,110 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use pulsar::{
+    app::{Application, UserEvent},
+    config::ApplicationOptions,
+    dof::{DepthOfFieldConfig, FocusMode},
+};
+use std::{
+    error::Error,
+    time::{Duration, Instant},
+};
+use winit::{
+    application::ApplicationHandler,
+    event::{ElementState, WindowEvent},
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    keyboard::{Key, ModifiersState},
+    window::WindowId,
+};
+
+/// How often the titles follow the focus distance.
+const READOUT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Toggles the depth of field of every window with F and shows its state in their titles.
+struct DepthOfField {
+    app: Application,
+    modifiers: ModifiersState,
+}
+
+impl DepthOfField {
+    fn toggle(&mut self) {
+        let mut config = self.app.graphics_config.write().unwrap();
+        config.depth_of_field.enabled = !config.depth_of_field.enabled;
+    }
+
+    fn update_titles(&self) {
+        let windows: Vec<WindowId> = self.app.window_manager.window_ids().collect();
+        for window_id in windows {
+            let title = match self.app.focus_distance(window_id) {
+                Some(distance) => format!("Depth of field on, focused at {distance:.2} (F)"),
+                None => "Depth of field off (F)".to_string(),
+            };
+            self.app.set_window_title(window_id, &title);
+        }
+    }
+}
+
+impl ApplicationHandler<UserEvent> for DepthOfField {
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        self.app.user_event(event_loop, event);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        match &event {
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers.state(),
+            // Ctrl+F is the fullscreen toggle
+            WindowEvent::KeyboardInput { event: key, .. }
+                if key.state == ElementState::Pressed
+                    && !key.repeat
+                    && self.modifiers.is_empty()
+                    && key.logical_key == Key::Character("f".into()) =>
+            {
+                self.toggle();
+            }
+            _ => {}
+        }
+        self.app.window_event(event_loop, window_id, event);
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.resumed(event_loop);
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.about_to_wait(event_loop);
+        self.update_titles();
+        event_loop.set_control_flow(ControlFlow::WaitUntil(Instant::now() + READOUT_INTERVAL));
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.exiting(event_loop);
+    }
+}
+
+// Show the demo scene through a wide open lens focused on whatever is under the cursor: F turns
+// the depth of field on and off, the title tells the distance it is focused at.
+fn main() -> Result<(), Box<dyn Error>> {
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut depth_of_field = DepthOfField {
+        app: Application::with_options(
+            &event_loop,
+            ApplicationOptions {
+                demo_scene: Some(true),
+                depth_of_field: Some(DepthOfFieldConfig {
+                    enabled: true,
+                    focus_mode: FocusMode::Cursor,
+                    aperture: 1.4,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        )?,
+        modifiers: ModifiersState::empty(),
+    };
+    event_loop.run_app(&mut depth_of_field).map_err(Into::into)
+}
@@ -0,0 +1,149 @@
+use pulsar::{
+    draw_order::{draw_order, DrawKey},
+    material::{DepthBias, Material},
+};
+
+/// Pipelines of the two materials, the shadow's sorting before the ground's.
+const GROUND_PIPELINE: u64 = 2;
+const SHADOW_PIPELINE: u64 = 1;
+/// Smallest resolvable depth difference of the engine's D16 attachment, one step of it.
+const DEPTH_UNIT: f32 = 1.0 / u16::MAX as f32;
+/// Depth both quads rasterize at, co-planar and on a step of the attachment.
+const PLANE_DEPTH: f32 = 16384.0 * DEPTH_UNIT;
+
+/// A quad covering the pixel under test.
+struct Quad {
+    name: &'static str,
+    key: DrawKey,
+    depth_bias: Option<DepthBias>,
+}
+
+/// Offset the rasterizer adds to a fragment of depth slope `slope`, clamped as the device does
+/// with the `depthBiasClamp` feature.
+fn bias_offset(bias: DepthBias, slope: f32) -> f32 {
+    let offset = slope * bias.slope_factor + DEPTH_UNIT * bias.constant_factor;
+    if bias.clamp > 0.0 {
+        offset.min(bias.clamp)
+    } else if bias.clamp < 0.0 {
+        offset.max(bias.clamp)
+    } else {
+        offset
+    }
+}
+
+/// `depth` as stored in the D16 attachment.
+fn quantize(depth: f32) -> f32 {
+    (depth / DEPTH_UNIT).round() * DEPTH_UNIT
+}
+
+/// Name of the quad left in the pixel after drawing `quads`, all of depth slope `slope`, in
+/// `order` against a cleared depth of 1, with the engine's `LESS_OR_EQUAL` test.
+fn resolve(quads: &[Quad], order: &[usize], slope: f32) -> &'static str {
+    let mut depth = 1.0;
+    let mut color = "clear";
+    for &index in order {
+        let quad = &quads[index];
+        let offset = quad.depth_bias.map_or(0.0, |bias| bias_offset(bias, slope));
+        let fragment = quantize(PLANE_DEPTH + offset);
+        if fragment <= depth {
+            depth = fragment;
+            color = quad.name;
+        }
+    }
+    color
+}
+
+/// The ground registered first, the blob shadow over it second.
+fn scene(shadow_sort_bias: i32, shadow_depth_bias: Option<DepthBias>) -> Vec<Quad> {
+    vec![
+        Quad {
+            name: "ground",
+            key: DrawKey {
+                sort_bias: 0,
+                state: GROUND_PIPELINE,
+                registered: 0,
+                blend_depth: None,
+            },
+            depth_bias: None,
+        },
+        Quad {
+            name: "shadow",
+            key: DrawKey {
+                sort_bias: shadow_sort_bias,
+                state: SHADOW_PIPELINE,
+                registered: 1,
+                blend_depth: None,
+            },
+            depth_bias: shadow_depth_bias,
+        },
+    ]
+}
+
+// Print the pixel co-planar quads leave, with the depth test run on the CPU. The same scene
+// rendered on a device needs a GPU and is not run here.
+fn main() {
+    let decal = Material::default().with_depth_bias(-1.0, -1.0).depth_bias;
+    for (sort_bias, depth_bias) in [(0, None), (1, None), (0, decal)] {
+        for sort_by_material in [false, true] {
+            let quads = scene(sort_bias, depth_bias);
+            let keys: Vec<DrawKey> = quads.iter().map(|quad| quad.key).collect();
+            let order = draw_order(&keys, sort_by_material);
+            println!(
+                "sort bias {sort_bias}, depth bias {}, sorted by material {sort_by_material}: {}",
+                depth_bias.is_some(),
+                resolve(&quads, &order, 0.0)
+            );
+        }
+    }
+    print_d16_precision();
+    print_blending();
+}
+
+/// The shadow drawn first with biases around a D16 step, on a surface facing the camera and one
+/// seen at an angle.
+fn print_d16_precision() {
+    for bias in [
+        DepthBias::new(-1.0, 0.0),
+        DepthBias::new(-0.25, 0.0),
+        DepthBias::new(0.0, -1.0),
+        DepthBias::new(0.0, -1.0).with_clamp(-0.25 * DEPTH_UNIT),
+    ] {
+        let quads = scene(0, Some(bias));
+        println!(
+            "{bias:?}: {} facing the camera, {} at an angle",
+            resolve(&quads, &[1, 0], 0.0),
+            resolve(&quads, &[1, 0], 4.0 * DEPTH_UNIT)
+        );
+    }
+}
+
+/// `color` with `alpha` over `destination`, as the blended pipeline's `SRC_ALPHA`,
+/// `ONE_MINUS_SRC_ALPHA` blend state computes it.
+fn over(destination: [f32; 3], color: [f32; 3], alpha: f32) -> [f32; 3] {
+    [0, 1, 2].map(|channel| color[channel] * alpha + destination[channel] * (1.0 - alpha))
+}
+
+/// Order of near glass, an opaque wall and far glass, and what a pixel behind both panes ends up
+/// as in that order and the other way around.
+fn print_blending() {
+    let key = |registered, blend_depth| DrawKey {
+        sort_bias: 0,
+        state: GROUND_PIPELINE,
+        registered,
+        blend_depth,
+    };
+    let keys = [key(0, Some(2.0)), key(1, None), key(2, Some(5.0))];
+    println!(
+        "Near glass, wall, far glass drawn in order {:?}",
+        draw_order(&keys, false)
+    );
+
+    // Half red glass behind half blue glass, over a white wall: back to front the blue glass
+    // tints what the red one let through, front to back the red one would cover the blue
+    let (wall, red, blue) = ([1.0; 3], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]);
+    println!(
+        "Back to front {:?}, front to back {:?}",
+        over(over(wall, red, 0.5), blue, 0.5),
+        over(over(wall, blue, 0.5), red, 0.5)
+    );
+}
@@ -0,0 +1,125 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use glam::Mat4;
+use pulsar::{
+    app::{Application, UserEvent},
+    model::{Mesh, MeshUpdate, Vertex},
+    MeshHandle,
+};
+use std::{
+    error::Error,
+    time::{Duration, Instant},
+};
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    window::WindowId,
+};
+
+const FRAME_INTERVAL: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// Adds a polygon to every window and replaces its vertices and indices with a polygon whose
+/// size pulses, every frame.
+struct Animator {
+    app: Application,
+    start: Instant,
+    next_frame: Instant,
+    polygons: Vec<(WindowId, MeshHandle)>,
+    added: bool,
+}
+
+/// Regular polygon of radius `extent`, a fan of `sides` triangles so the vertex count changes too.
+fn polygon(extent: f32, sides: usize) -> (Vec<Vertex>, Vec<u32>) {
+    let mut vertices = vec![vertex(0.0, 0.0)];
+    for side in 0..sides {
+        let angle = std::f32::consts::TAU * side as f32 / sides as f32;
+        vertices.push(vertex(angle.cos() * extent, angle.sin() * extent));
+    }
+    let indices = (0..sides as u32)
+        .flat_map(|side| [0, side + 1, (side + 1) % sides as u32 + 1])
+        .collect();
+    (vertices, indices)
+}
+
+fn vertex(x: f32, y: f32) -> Vertex {
+    Vertex {
+        pos: [x, y, 0.0, 1.0],
+        uv: [x * 0.5 + 0.5, y * 0.5 + 0.5],
+        color: [1.0, 0.5, 0.0, 1.0],
+        normal: [0.0, 0.0, 1.0],
+        tangent: [1.0, 0.0, 0.0, 1.0],
+        joints: [0; 4],
+        weights: [0.0; 4],
+    }
+}
+
+impl ApplicationHandler<UserEvent> for Animator {
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        self.app.user_event(event_loop, event);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        self.app.window_event(event_loop, window_id, event);
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.resumed(event_loop);
+        if self.added {
+            return;
+        }
+        let windows: Vec<WindowId> = self.app.window_manager.window_ids().collect();
+        for window_id in windows {
+            let (vertices, indices) = polygon(0.3, 4);
+            let mesh = Mesh {
+                vertices,
+                indices,
+                transform: Mat4::IDENTITY,
+                user_id: 0,
+            };
+            if let Some(polygon) = self.app.add_mesh(window_id, mesh) {
+                self.polygons.push((window_id, polygon));
+            }
+        }
+        self.added = true;
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.about_to_wait(event_loop);
+        if Instant::now() >= self.next_frame {
+            let seconds = self.start.elapsed().as_secs_f32();
+            let (vertices, indices) =
+                polygon(0.3 + 0.2 * seconds.sin(), 4 + (seconds as usize % 4) * 4);
+            for &(window_id, mesh) in &self.polygons {
+                self.app
+                    .update_mesh(window_id, mesh, MeshUpdate::Vertices(vertices.clone()));
+                self.app
+                    .update_mesh(window_id, mesh, MeshUpdate::Indices(indices.clone()));
+            }
+            self.next_frame += FRAME_INTERVAL;
+        }
+        event_loop.set_control_flow(ControlFlow::WaitUntil(self.next_frame));
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.exiting(event_loop);
+    }
+}
+
+// Show a pulsing polygon, updated in place every frame through the handle it was added with
+fn main() -> Result<(), Box<dyn Error>> {
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut animator = Animator {
+        app: Application::new(&event_loop)?,
+        start: Instant::now(),
+        next_frame: Instant::now(),
+        polygons: Vec::new(),
+        added: false,
+    };
+    event_loop.run_app(&mut animator).map_err(Into::into)
+}
@@ -0,0 +1,130 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use glam::Vec3;
+use pulsar::{
+    app::{Application, UserEvent},
+    config::ApplicationOptions,
+    environment::{Cubemap, CUBE_FACES},
+    texture::{CubemapSource, SamplerDesc},
+};
+use std::{
+    collections::HashMap,
+    error::Error,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    window::WindowId,
+};
+
+const SKY: Vec3 = Vec3::new(0.2, 0.45, 1.0);
+const GROUND: Vec3 = Vec3::new(0.6, 0.35, 0.1);
+const FACE_SIZE: u32 = 32;
+const STEP: Duration = Duration::from_secs(2);
+
+/// Skybox off, on, then on at twice the intensity.
+const STEPS: [(bool, f32); 3] = [(false, 1.0), (true, 1.0), (true, 2.0)];
+
+/// Steps every window of the demo scene through [`STEPS`].
+struct Lighting {
+    app: Application,
+    skybox: CubemapSource,
+    started: Instant,
+    steps: HashMap<WindowId, usize>,
+}
+
+impl ApplicationHandler<UserEvent> for Lighting {
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        self.app.user_event(event_loop, event);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        self.app.window_event(event_loop, window_id, event);
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.resumed(event_loop);
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.about_to_wait(event_loop);
+        let step = (self.started.elapsed().as_secs() / STEP.as_secs()) as usize % STEPS.len();
+        let windows: Vec<WindowId> = self.app.window_manager.window_ids().collect();
+        for window_id in windows {
+            if self.steps.insert(window_id, step) == Some(step) {
+                continue;
+            }
+            let (skybox, intensity) = STEPS[step];
+            if skybox {
+                self.app
+                    .load_cubemap(window_id, self.skybox.clone(), SamplerDesc::default());
+            } else {
+                self.app.clear_cubemap(window_id);
+            }
+            self.app.set_environment_intensity(window_id, intensity);
+            println!(
+                "Skybox {}, intensity {intensity}",
+                if skybox { "on" } else { "off" }
+            );
+        }
+        event_loop.set_control_flow(ControlFlow::WaitUntil(Instant::now() + STEP / 4));
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.exiting(event_loop);
+    }
+}
+
+/// Faces of a blue sky over a brown ground, saved next to each other in the temporary directory.
+fn write_skybox() -> Result<CubemapSource, Box<dyn Error>> {
+    let skybox = Cubemap::from_fn(
+        FACE_SIZE,
+        |direction| {
+            if direction.y > 0.0 {
+                SKY
+            } else {
+                GROUND
+            }
+        },
+    );
+    let mut paths: [PathBuf; CUBE_FACES] = Default::default();
+    for (face, (path, texels)) in paths.iter_mut().zip(skybox.to_rgba8(true)).enumerate() {
+        *path = std::env::temp_dir().join(format!("pulsar_environment_{face}.png"));
+        image::save_buffer(
+            &*path,
+            &texels,
+            FACE_SIZE,
+            FACE_SIZE,
+            image::ColorType::Rgba8,
+        )?;
+    }
+    Ok(CubemapSource::Faces(paths))
+}
+
+// Light the demo scene with a sky over ground skybox, then without, every two seconds: with the
+// skybox the faces turned up take a blue ambient tint and those turned down a brown one, without
+// it every face in shadow gets the same grey.
+fn main() -> Result<(), Box<dyn Error>> {
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut lighting = Lighting {
+        app: Application::with_options(
+            &event_loop,
+            ApplicationOptions {
+                demo_scene: Some(true),
+                ..Default::default()
+            },
+        )?,
+        skybox: write_skybox()?,
+        started: Instant::now(),
+        steps: HashMap::new(),
+    };
+    event_loop.run_app(&mut lighting).map_err(Into::into)
+}
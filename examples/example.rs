@@ -1,11 +1,18 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use pulsar::app::{Application, UserEvent};
+use pulsar::{
+    app::{Application, UserEvent},
+    config::ApplicationOptions,
+};
 use std::error::Error;
 use winit::event_loop::EventLoop;
 
 fn main() -> Result<(), Box<dyn Error>> {
+    let options = ApplicationOptions {
+        demo_scene: Some(true),
+        ..Default::default()
+    };
     let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
-    let mut app = Application::new(&event_loop)?;
+    let mut app = Application::with_options(&event_loop, options)?;
     event_loop.run_app(&mut app).map_err(Into::into)
 }
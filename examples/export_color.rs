@@ -0,0 +1,125 @@
+use ash::vk;
+use pulsar::color_space::{
+    self, f16_to_f32, f32_to_f16, linear_to_srgb, nits_to_pq, ExportColorSpace, SourceTransfer,
+    HDR_REFERENCE_WHITE_NITS,
+};
+use std::error::Error;
+
+/// Texels of each gradient, from black to its brightest.
+const STEPS: usize = 64;
+/// Brightest gradient value of the HDR sources, relative to SDR white.
+const HDR_PEAK: f32 = 4.0;
+
+/// A surface format, how to store one linear gray in it, and how bright its gradient goes.
+struct Source {
+    format: vk::SurfaceFormatKHR,
+    encode: fn(f32) -> Vec<u8>,
+    peak: f32,
+}
+
+fn surface(format: vk::Format, color_space: vk::ColorSpaceKHR) -> vk::SurfaceFormatKHR {
+    vk::SurfaceFormatKHR {
+        format,
+        color_space,
+    }
+}
+
+fn unorm8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+fn srgb8_gray(linear: f32) -> Vec<u8> {
+    let encoded = unorm8(linear_to_srgb(linear));
+    vec![encoded, encoded, encoded, 255]
+}
+
+fn pq10_gray(linear: f32) -> Vec<u8> {
+    let signal = (nits_to_pq(linear * HDR_REFERENCE_WHITE_NITS) * 1023.0).round() as u32;
+    (3 << 30 | signal << 20 | signal << 10 | signal)
+        .to_le_bytes()
+        .to_vec()
+}
+
+fn half_gray(linear: f32) -> Vec<u8> {
+    [linear, linear, linear, 1.0]
+        .iter()
+        .flat_map(|&channel| f32_to_f16(channel).to_le_bytes())
+        .collect()
+}
+
+fn sources() -> Vec<Source> {
+    let srgb = vk::ColorSpaceKHR::SRGB_NONLINEAR;
+    vec![
+        Source {
+            format: surface(vk::Format::B8G8R8A8_UNORM, srgb),
+            encode: srgb8_gray,
+            peak: 1.0,
+        },
+        Source {
+            format: surface(vk::Format::R8G8B8A8_SRGB, srgb),
+            encode: srgb8_gray,
+            peak: 1.0,
+        },
+        Source {
+            format: surface(
+                vk::Format::A2B10G10R10_UNORM_PACK32,
+                vk::ColorSpaceKHR::HDR10_ST2084_EXT,
+            ),
+            encode: pq10_gray,
+            peak: HDR_PEAK,
+        },
+        Source {
+            format: surface(
+                vk::Format::R16G16B16A16_SFLOAT,
+                vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT,
+            ),
+            encode: half_gray,
+            peak: HDR_PEAK,
+        },
+    ]
+}
+
+/// Linear gray of each exported texel.
+fn exported_grays(texels: &[u8], color_space: ExportColorSpace) -> Vec<f32> {
+    match color_space {
+        ExportColorSpace::Srgb8 => texels
+            .chunks_exact(4)
+            .map(|texel| color_space::srgb_to_linear(texel[0] as f32 / 255.0))
+            .collect(),
+        ExportColorSpace::Linear16F => texels
+            .chunks_exact(8)
+            .map(|texel| f16_to_f32(u16::from_le_bytes([texel[0], texel[1]])))
+            .collect(),
+    }
+}
+
+// Convert synthetic gradients of each surface format on the CPU and print where they end up. The
+// GPU pass runs the same math in export.frag, it needs a device and is not run here.
+fn main() -> Result<(), Box<dyn Error>> {
+    for source in sources() {
+        let texels: Vec<u8> = (0..STEPS)
+            .flat_map(|step| (source.encode)(source.peak * step as f32 / (STEPS - 1) as f32))
+            .collect();
+        for color_space in [ExportColorSpace::Srgb8, ExportColorSpace::Linear16F] {
+            let converted = color_space::convert(source.format, &texels, color_space)?;
+            let exported = exported_grays(&converted, color_space);
+            println!(
+                "{:?} {:?} to {color_space:?}: {STEPS} steps up to {}",
+                source.format.format,
+                SourceTransfer::of(source.format),
+                exported.last().copied().unwrap_or_default()
+            );
+        }
+    }
+
+    // What copying the texels as RGBA8 used to save for an HDR10 surface
+    let white = pq10_gray(1.0);
+    let copied: Vec<f32> = white.iter().map(|&byte| byte as f32 / 255.0).collect();
+    let hdr10 = surface(
+        vk::Format::A2B10G10R10_UNORM_PACK32,
+        vk::ColorSpaceKHR::HDR10_ST2084_EXT,
+    );
+    let converted = color_space::convert(hdr10, &white, ExportColorSpace::Srgb8)?;
+    println!("HDR10 SDR white copied as bytes: {copied:?}, converted: {converted:?}");
+    Ok(())
+}
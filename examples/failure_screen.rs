@@ -0,0 +1,94 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use pulsar::{
+    app::{Application, UserEvent},
+    config::ApplicationOptions,
+    failure::{Failure, FailureCategory},
+};
+use std::{
+    collections::HashSet,
+    error::Error,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+};
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    window::WindowId,
+};
+
+/// Calls failing before the retry callback reports the failure over.
+const FAILED_ATTEMPTS: u32 = 2;
+
+/// Retry callback counting its calls in `calls`, succeeding after `FAILED_ATTEMPTS`.
+fn flaky_retry(calls: Arc<AtomicU32>) -> Box<dyn FnMut() -> bool + Send> {
+    Box::new(move || calls.fetch_add(1, Ordering::Relaxed) >= FAILED_ATTEMPTS)
+}
+
+fn asset_failure() -> Failure {
+    Failure::new(
+        FailureCategory::Asset,
+        "Could not load the level",
+        "levels/forest.gltf: unexpected end of file.\nRetry once the download completes.",
+    )
+}
+
+/// Shows an application failure on every window, R retries until the callback gives in.
+struct Failing {
+    app: Application,
+    shown: HashSet<WindowId>,
+}
+
+impl ApplicationHandler<UserEvent> for Failing {
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        self.app.user_event(event_loop, event);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        self.app.window_event(event_loop, window_id, event);
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.resumed(event_loop);
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.about_to_wait(event_loop);
+        let windows: Vec<WindowId> = self.app.window_manager.window_ids().collect();
+        for window_id in windows {
+            if self.shown.insert(window_id) {
+                let calls = Arc::new(AtomicU32::new(0));
+                self.app
+                    .show_failure(window_id, asset_failure(), Some(flaky_retry(calls)));
+            }
+        }
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.exiting(event_loop);
+    }
+}
+
+// Show a failure over the demo scene, R retries until the callback gives in and Esc exits
+fn main() -> Result<(), Box<dyn Error>> {
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut failing = Failing {
+        app: Application::with_options(
+            &event_loop,
+            ApplicationOptions {
+                demo_scene: Some(true),
+                ..Default::default()
+            },
+        )?,
+        shown: HashSet::new(),
+    };
+    event_loop.run_app(&mut failing).map_err(Into::into)
+}
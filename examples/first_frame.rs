@@ -0,0 +1,145 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use pulsar::{
+    app::UserEvent,
+    config::WindowConfig,
+    display::DisplayEnvironment,
+    input_manager::EventStates,
+    renderer::{NullRenderer, RendererFactory, WindowRenderer},
+    window_manager::{WindowManager, FIRST_FRAME_TIMEOUT},
+};
+use std::{cell::RefCell, error::Error, rc::Rc, sync::Arc, thread};
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    window::{Window, WindowId},
+};
+
+type Created = Rc<RefCell<Vec<(Arc<Window>, Arc<EventStates>)>>>;
+
+/// Null renderers that never present, the host marks their first frame itself.
+struct SilentFactory {
+    created: Created,
+}
+
+impl RendererFactory for SilentFactory {
+    fn create_surface_renderer(
+        &mut self,
+        window: &Arc<Window>,
+        event_states: Arc<EventStates>,
+        display: DisplayEnvironment,
+    ) -> Result<Box<dyn WindowRenderer>, Box<dyn Error>> {
+        self.created
+            .borrow_mut()
+            .push((window.clone(), event_states));
+        Ok(Box::new(NullRenderer {
+            display: Some(display),
+            rendering: false,
+        }))
+    }
+}
+
+/// Opens hidden windows and plays the render thread's part by hand.
+struct Host {
+    window_manager: WindowManager,
+    created: Created,
+}
+
+impl Host {
+    fn visible(&self, index: usize) -> bool {
+        // Platforms that cannot tell count as shown
+        self.created.borrow()[index].0.is_visible().unwrap_or(true)
+    }
+
+    fn check_presented(&mut self, event_loop: &ActiveEventLoop) -> Result<(), String> {
+        let window_id = self
+            .window_manager
+            .create_window(event_loop, None)
+            .map_err(|error| error.to_string())?;
+        self.window_manager.about_to_wait(event_loop);
+        if self.window_manager.first_frame_presented(window_id)
+            || !self.window_manager.first_frames_presented().is_empty()
+            || self.visible(0)
+        {
+            return Err("The window was shown before its first frame".into());
+        }
+
+        let event_states = self.created.borrow()[0].1.clone();
+        event_states.mark_first_frame_presented();
+        if self.window_manager.first_frames_presented() != [window_id]
+            || !self.window_manager.first_frames_presented().is_empty()
+        {
+            return Err("The first frame was not reported exactly once".into());
+        }
+        self.window_manager
+            .user_event(event_loop, UserEvent::FirstFramePresented(window_id));
+        if !self.window_manager.first_frame_presented(window_id) || !self.visible(0) {
+            return Err("The window stayed hidden after its first frame".into());
+        }
+        Ok(())
+    }
+
+    fn check_timeout(&mut self, event_loop: &ActiveEventLoop) -> Result<(), String> {
+        let window_id = self
+            .window_manager
+            .create_window(event_loop, None)
+            .map_err(|error| error.to_string())?;
+        self.window_manager.about_to_wait(event_loop);
+        if self.visible(1) {
+            return Err("The second window was shown before its first frame".into());
+        }
+        thread::sleep(FIRST_FRAME_TIMEOUT);
+        self.window_manager.about_to_wait(event_loop);
+        if self.window_manager.first_frame_presented(window_id) || !self.visible(1) {
+            return Err("A window that never presents stayed hidden".into());
+        }
+        Ok(())
+    }
+}
+
+impl ApplicationHandler<UserEvent> for Host {
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        self.window_manager
+            .window_event(event_loop, window_id, event);
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        match self.check_presented(event_loop) {
+            Ok(()) => println!("Shown once its first frame was presented"),
+            Err(error) => eprintln!("{error}"),
+        }
+        match self.check_timeout(event_loop) {
+            Ok(()) => println!("Shown after {FIRST_FRAME_TIMEOUT:?} without a frame"),
+            Err(error) => eprintln!("{error}"),
+        }
+        self.window_manager.close_all();
+        event_loop.exit();
+    }
+}
+
+// Open windows hidden until their first frame, then one whose renderer never presents. Showing
+// them needs a display, and the real present path a GPU, which is not driven here.
+fn main() -> Result<(), Box<dyn Error>> {
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let created = Created::default();
+    let mut host = Host {
+        window_manager: WindowManager::new(
+            &event_loop,
+            WindowConfig {
+                hidden_until_first_frame: true,
+                ..Default::default()
+            },
+            Box::new(SilentFactory {
+                created: created.clone(),
+            }),
+        ),
+        created,
+    };
+    event_loop.run_app(&mut host).map_err(Into::into)
+}
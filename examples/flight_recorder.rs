@@ -0,0 +1,24 @@
+use pulsar::flight_recorder::{Decision, DumpReason, FlightRecorder};
+use std::{hint::black_box, time::Instant};
+
+const BENCH_RECORDS: u64 = 10_000_000;
+
+// Time the hot path of the recorder and print the dump of its last records, run it with
+// `--release` for meaningful timings
+fn main() {
+    let recorder = FlightRecorder::default();
+    let start = Instant::now();
+    for frame in 0..BENCH_RECORDS {
+        recorder.record(
+            black_box(frame),
+            black_box(Decision::Presented { suboptimal: false }),
+        );
+    }
+    let per_record = start.elapsed() / BENCH_RECORDS as u32;
+    println!("{per_record:?} per record");
+
+    let dump = recorder.dump(&DumpReason::Panicked("example".to_string()));
+    for line in dump.lines().take(6) {
+        println!("{line}");
+    }
+}
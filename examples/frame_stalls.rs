@@ -0,0 +1,74 @@
+#![cfg_attr(not(debug_assertions), allow(unused))]
+
+use pulsar::{
+    metrics::{FrameClass, Metrics},
+    watchdog::{Heartbeat, WaitSite},
+};
+use std::{thread, time::Duration};
+
+const STALL: Duration = Duration::from_millis(80);
+
+/// One fake frame going through the wait sites of a real one, `work` spent outside of them.
+fn frame(metrics: &mut Metrics, heartbeat: &Heartbeat, work: Duration) -> FrameClass {
+    metrics.start_frame();
+    for site in [
+        WaitSite::AcquireImage,
+        WaitSite::DrawFence,
+        WaitSite::Present,
+    ] {
+        drop(heartbeat.waiting(site));
+    }
+    thread::sleep(work);
+    metrics.waits = heartbeat.take_waits();
+    metrics.end_frame();
+    metrics.last_class
+}
+
+// Delay each wait site in turn and print what the slow frame is blamed on
+#[cfg(debug_assertions)]
+fn main() {
+    let heartbeat = Heartbeat::default();
+    let mut metrics = Metrics::default();
+    let idle = frame(&mut metrics, &heartbeat, Duration::ZERO);
+    println!("Idle frame: {idle:?}");
+
+    for site in [
+        Some(WaitSite::AcquireImage),
+        Some(WaitSite::Present),
+        Some(WaitSite::DrawFence),
+        None,
+    ] {
+        let work = match site {
+            Some(site) => {
+                heartbeat.inject_delay(site, STALL);
+                Duration::ZERO
+            }
+            None => STALL,
+        };
+        let class = frame(&mut metrics, &heartbeat, work);
+        if let Some(site) = site {
+            heartbeat.inject_delay(site, Duration::ZERO);
+        }
+        match site {
+            Some(site) => println!("{STALL:?} at {site:?}: {class:?}"),
+            None => println!("{STALL:?} of engine work: {class:?}"),
+        }
+    }
+    // The frame after a stall animates by the steady delta, not by the stall
+    frame(&mut metrics, &heartbeat, Duration::ZERO);
+    heartbeat.inject_delay(WaitSite::Present, STALL);
+    frame(&mut metrics, &heartbeat, Duration::ZERO);
+    heartbeat.inject_delay(WaitSite::Present, Duration::ZERO);
+    metrics.start_frame();
+    println!(
+        "After a stall the next frame animates by {:?} clamped, {:?} unclamped",
+        metrics.simulation_delta(true),
+        metrics.simulation_delta(false)
+    );
+    println!("{:?}", metrics.counters.waits);
+}
+
+#[cfg(not(debug_assertions))]
+fn main() {
+    println!("Delays can only be injected in debug builds");
+}
@@ -0,0 +1,28 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use pulsar::{
+    app::{Application, UserEvent},
+    config::ApplicationOptions,
+    gizmo::GizmoSnapping,
+};
+use std::error::Error;
+use winit::event_loop::EventLoop;
+
+// Click a demo quad to select it, then drag the handles.
+// No modifier translates, Shift rotates, Shift+Alt scales.
+fn main() -> Result<(), Box<dyn Error>> {
+    let options = ApplicationOptions {
+        title: Some("Pulsar gizmo example".to_string()),
+        gizmo_snapping: Some(GizmoSnapping {
+            translate: 0.25,
+            rotate_degrees: 15.0,
+            scale: 0.25,
+        }),
+        demo_scene: Some(true),
+        ..Default::default()
+    };
+
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut app = Application::with_options(&event_loop, options)?;
+    event_loop.run_app(&mut app).map_err(Into::into)
+}
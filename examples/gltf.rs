@@ -0,0 +1,81 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use pulsar::{
+    app::{Application, UserEvent},
+    model::Scene,
+};
+use std::{error::Error, path::Path};
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    window::WindowId,
+};
+
+const PATH: &str = "assets/models/quads.gltf";
+
+/// Adds the meshes of `PATH` to every window once they exist.
+struct Viewer {
+    app: Application,
+    added: bool,
+}
+
+impl ApplicationHandler<UserEvent> for Viewer {
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        self.app.user_event(event_loop, event);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        self.app.window_event(event_loop, window_id, event);
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.resumed(event_loop);
+        if self.added {
+            return;
+        }
+        let windows: Vec<WindowId> = self.app.window_manager.window_ids().collect();
+        for window_id in windows {
+            let scene = Scene::from_gltf(Path::new(PATH)).expect("Scene loaded at startup");
+            for mesh in scene.models.into_iter().flat_map(|model| model.meshes) {
+                self.app.add_mesh(window_id, mesh);
+            }
+        }
+        self.added = true;
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.about_to_wait(event_loop);
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.exiting(event_loop);
+    }
+}
+
+// Load a quad drawn twice under a translated parent, once colored and once textured as a strip
+fn main() -> Result<(), Box<dyn Error>> {
+    let scene = Scene::from_gltf(Path::new(PATH))?;
+    for model in &scene.models {
+        for mesh in &model.meshes {
+            println!(
+                "Texture {:?}: {} vertices, {} indices",
+                model.texture,
+                mesh.vertices.len(),
+                mesh.indices.len()
+            );
+        }
+    }
+
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut viewer = Viewer {
+        app: Application::new(&event_loop)?,
+        added: false,
+    };
+    event_loop.run_app(&mut viewer).map_err(Into::into)
+}
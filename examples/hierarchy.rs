@@ -0,0 +1,104 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use glam::{Mat4, Vec3};
+use pulsar::{
+    app::{Application, UserEvent},
+    model::Mesh,
+    MeshHandle,
+};
+use std::{
+    error::Error,
+    time::{Duration, Instant},
+};
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    window::WindowId,
+};
+
+const ANIMATION_INTERVAL: Duration = Duration::from_millis(16);
+
+/// A sun, a planet and its moon linked by their handles. Only the sun and the planet spin, the
+/// moon is carried along by its parents.
+struct System {
+    app: Application,
+    start: Instant,
+    /// Sun and planet of every window.
+    spinning: Vec<(WindowId, MeshHandle, MeshHandle)>,
+    added: bool,
+}
+
+impl ApplicationHandler<UserEvent> for System {
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        self.app.user_event(event_loop, event);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        self.app.window_event(event_loop, window_id, event);
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.resumed(event_loop);
+        if self.added {
+            return;
+        }
+        let windows: Vec<WindowId> = self.app.window_manager.window_ids().collect();
+        for window_id in windows {
+            let sun = Mesh::cube(0.6, [1.0, 0.8, 0.2, 1.0]);
+            let planet = Mesh {
+                transform: Mat4::from_translation(Vec3::new(1.5, 0.0, 0.0)),
+                ..Mesh::cube(0.3, [0.2, 0.5, 1.0, 1.0])
+            };
+            let moon = Mesh {
+                transform: Mat4::from_translation(Vec3::new(0.5, 0.0, 0.0)),
+                ..Mesh::cube(0.1, [0.8, 0.8, 0.8, 1.0])
+            };
+            let (Some(sun), Some(planet), Some(moon)) = (
+                self.app.add_mesh(window_id, sun),
+                self.app.add_mesh(window_id, planet),
+                self.app.add_mesh(window_id, moon),
+            ) else {
+                continue;
+            };
+            self.app.set_parent(window_id, planet, Some(sun));
+            self.app.set_parent(window_id, moon, Some(planet));
+            self.spinning.push((window_id, sun, planet));
+        }
+        self.added = true;
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.about_to_wait(event_loop);
+        let seconds = self.start.elapsed().as_secs_f32();
+        for &(window_id, sun, planet) in &self.spinning {
+            self.app
+                .set_local_transform(window_id, sun, Mat4::from_rotation_y(seconds * 0.5));
+            let orbit = Mat4::from_translation(Vec3::new(1.5, 0.0, 0.0))
+                * Mat4::from_rotation_y(seconds * 2.0);
+            self.app.set_local_transform(window_id, planet, orbit);
+        }
+        event_loop.set_control_flow(ControlFlow::WaitUntil(Instant::now() + ANIMATION_INTERVAL));
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.exiting(event_loop);
+    }
+}
+
+// Show a moon orbiting a planet orbiting a sun, each placed relative to its parent
+fn main() -> Result<(), Box<dyn Error>> {
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut system = System {
+        app: Application::new(&event_loop)?,
+        start: Instant::now(),
+        spinning: Vec::new(),
+        added: false,
+    };
+    event_loop.run_app(&mut system).map_err(Into::into)
+}
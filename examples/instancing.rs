@@ -0,0 +1,102 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use glam::{Mat4, Quat, Vec2, Vec3};
+use pulsar::{
+    app::{Application, UserEvent},
+    camera::Ortho2DController,
+    instancing::Instances,
+    model::Mesh,
+};
+use std::{
+    error::Error,
+    time::{Duration, Instant},
+};
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    window::WindowId,
+};
+
+const GRID: u32 = 100;
+const ANIMATION_INTERVAL: Duration = Duration::from_millis(16);
+
+/// Draws a grid of quads with a single instanced mesh, spinning in a wave across the grid.
+struct Editor {
+    app: Application,
+    instances: Instances,
+    start: Instant,
+    added: bool,
+}
+
+/// One transform per cell, each quad turned by the wave passing over it at `time`.
+fn grid(time: f32) -> Vec<Mat4> {
+    let mut transforms = Vec::with_capacity((GRID * GRID) as usize);
+    for y in 0..GRID {
+        for x in 0..GRID {
+            let wave = time * 2.0 - (x + y) as f32 * 0.1;
+            transforms.push(Mat4::from_scale_rotation_translation(
+                Vec3::splat(0.6 + 0.3 * wave.sin()),
+                Quat::from_rotation_z(wave),
+                Vec3::new(x as f32 + 0.5, y as f32 + 0.5, 0.0),
+            ));
+        }
+    }
+    transforms
+}
+
+impl ApplicationHandler<UserEvent> for Editor {
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        self.app.user_event(event_loop, event);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        self.app.window_event(event_loop, window_id, event);
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.resumed(event_loop);
+        if self.added {
+            return;
+        }
+        let windows: Vec<WindowId> = self.app.window_manager.window_ids().collect();
+        for window_id in windows {
+            let middle = GRID as f32 / 2.0;
+            self.app
+                .set_ortho_2d(window_id, Ortho2DController::new(Vec2::splat(middle), 6.0));
+            let quad = Mesh::quad(Vec2::ONE, [0.2, 0.6, 0.9, 1.0]);
+            self.app
+                .add_instanced_mesh_2d(window_id, quad, &self.instances);
+        }
+        self.added = true;
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.about_to_wait(event_loop);
+        // Every quad moves, the render thread copies the whole list once per change
+        self.instances.set(grid(self.start.elapsed().as_secs_f32()));
+        event_loop.set_control_flow(ControlFlow::WaitUntil(Instant::now() + ANIMATION_INTERVAL));
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.exiting(event_loop);
+    }
+}
+
+// Show 10000 quads drawn with one indexed draw, animated from the main thread
+fn main() -> Result<(), Box<dyn Error>> {
+    let instances = Instances::new(grid(0.0));
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut editor = Editor {
+        app: Application::new(&event_loop)?,
+        instances,
+        start: Instant::now(),
+        added: false,
+    };
+    event_loop.run_app(&mut editor).map_err(Into::into)
+}
@@ -0,0 +1,77 @@
+use pulsar::{
+    input_manager::EventStates,
+    loader::{load_mesh, LoadTarget},
+    model::{Mesh, MeshSpace},
+};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+const SLOW_LOADS: usize = 4;
+
+/// Held closed until the window is retired, the loads waiting on it are mid-way.
+struct Gate {
+    open: Mutex<bool>,
+    opened: Condvar,
+}
+
+impl Gate {
+    fn wait(&self) {
+        let open = self.open.lock().unwrap();
+        drop(self.opened.wait_while(open, |open| !*open).unwrap());
+    }
+
+    fn open(&self) {
+        *self.open.lock().unwrap() = true;
+        self.opened.notify_all();
+    }
+}
+
+// Close a window while slow loads are running for it and print what became of each load
+fn main() {
+    let event_states = Arc::new(EventStates::default());
+    let target = LoadTarget::new(&event_states);
+    let quick = load_mesh(target.clone(), MeshSpace::World, || {
+        Ok(Mesh::cube(1.0, [1.0; 4]))
+    });
+    println!("Load for the open window: {:?}", quick.wait());
+
+    let gate = Arc::new(Gate {
+        open: Mutex::new(false),
+        opened: Condvar::new(),
+    });
+    let started = Arc::new(AtomicUsize::new(0));
+    let handles: Vec<_> = (0..SLOW_LOADS)
+        .map(|_| {
+            let gate = gate.clone();
+            let started = started.clone();
+            load_mesh(target.clone(), MeshSpace::World, move || {
+                started.fetch_add(1, Ordering::SeqCst);
+                gate.wait();
+                Ok(Mesh::cube(1.0, [1.0; 4]))
+            })
+        })
+        .collect();
+    while started.load(Ordering::SeqCst) == 0 {
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    event_states.retire();
+    println!(
+        "Window closed with {} of {SLOW_LOADS} loads mid-way",
+        started.load(Ordering::SeqCst)
+    );
+    gate.open();
+    for (i, handle) in handles.into_iter().enumerate() {
+        println!("Slow load {i}: {:?}", handle.wait());
+    }
+    println!(
+        "{} meshes queued for the closed window",
+        event_states.take_mesh_additions().len()
+    );
+}
@@ -0,0 +1,31 @@
+use glam::Vec4;
+use pulsar::material_params::{MaterialParams, ParamValue};
+use std::{error::Error, time::Duration};
+
+// Ease material parameters through the command syntax and print them as they move
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut params = MaterialParams::default();
+    params.register("lava", "emissive", ParamValue::Float(1.0));
+    params.register("lava", "tint", ParamValue::Vec4(Vec4::ONE));
+
+    // Unknown names list the valid ones
+    if let Err(err) = params.execute("set_material_param lava emisive 3") {
+        println!("{err}");
+    }
+    println!(
+        "`set_material_param lava ` completes to {:?}",
+        params.complete("set_material_param lava ")
+    );
+
+    params.execute("set_material_param lava emissive 3 over 2")?;
+    params.execute("set_material_param lava tint 1,0,0,1 over 1")?;
+    for _ in 0..=8 {
+        println!(
+            "emissive {:?}, tint {:?}",
+            params.get("lava", "emissive")?,
+            params.get("lava", "tint")?
+        );
+        params.advance(Duration::from_millis(250));
+    }
+    Ok(())
+}
@@ -0,0 +1,87 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use glam::{Mat4, Vec3};
+use pulsar::{
+    app::{Application, UserEvent},
+    batching::MeshBatch,
+    metrics,
+    model::Mesh,
+};
+use std::error::Error;
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    window::WindowId,
+};
+
+const GRID: i32 = 10;
+
+/// A cube per cell, each placed by its own transform.
+fn cubes() -> Vec<Mesh> {
+    let mut cubes = Vec::new();
+    for z in -GRID / 2..GRID / 2 {
+        for x in -GRID / 2..GRID / 2 {
+            let mut cube = Mesh::cube(0.5, [0.3, 0.7, 0.4, 1.0]);
+            cube.transform = Mat4::from_translation(Vec3::new(x as f32, 0.0, z as f32));
+            cubes.push(cube);
+        }
+    }
+    cubes
+}
+
+/// Registers the grid once as a batch, then reports the buffers allocated for it.
+struct Host {
+    app: Application,
+    added: bool,
+}
+
+impl ApplicationHandler<UserEvent> for Host {
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        self.app.user_event(event_loop, event);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        self.app.window_event(event_loop, window_id, event);
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.resumed(event_loop);
+        if self.added {
+            return;
+        }
+        let windows: Vec<WindowId> = self.app.window_manager.window_ids().collect();
+        for window_id in windows {
+            self.app.add_mesh_batch(window_id, MeshBatch::new(cubes()));
+        }
+        self.added = true;
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.exiting(event_loop);
+        // Two per batch where the 100 cubes alone would take 200, plus the renderer's own buffers
+        println!("{} buffers allocated", metrics::buffer_allocations());
+    }
+}
+
+// Draw a grid of cubes as one batch and report the buffers allocated for it
+fn main() -> Result<(), Box<dyn Error>> {
+    let batch = MeshBatch::new(cubes());
+    println!(
+        "{} cubes in one batch of {} vertices",
+        batch.ranges().len(),
+        batch.mesh().vertices.len()
+    );
+
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut host = Host {
+        app: Application::new(&event_loop)?,
+        added: false,
+    };
+    event_loop.run_app(&mut host).map_err(Into::into)
+}
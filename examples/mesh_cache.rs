@@ -0,0 +1,26 @@
+use glam::{Mat4, Vec3};
+use pulsar::model::Mesh;
+use std::{error::Error, time::Instant};
+
+// Bake a large mesh to its cache file, read it back and print the size and timings
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut large = Mesh::plane(10.0, 10.0, 120, [0.2, 0.6, 0.3, 1.0]);
+    large.transform = Mat4::from_translation(Vec3::new(1.0, 2.0, 3.0));
+
+    let path = std::env::temp_dir().join("pulsar_mesh_cache.bin");
+    let start = Instant::now();
+    large.write_cache(&path)?;
+    let written = start.elapsed();
+    let start = Instant::now();
+    let read = Mesh::read_cache(&path)?;
+    let read_time = start.elapsed();
+    let bytes = std::fs::metadata(&path)?.len();
+    std::fs::remove_file(&path)?;
+
+    println!(
+        "{} vertices and {} indices in {bytes} bytes, written in {written:?}, read in {read_time:?}",
+        read.vertices.len(),
+        read.indices.len()
+    );
+    Ok(())
+}
@@ -0,0 +1,50 @@
+use glam::Mat4;
+use pulsar::model::{Mesh, MeshLimits, Vertex};
+
+const GRID: u32 = 256;
+
+// Split a grid too large for a small index limit and print the chunks
+fn main() {
+    let mesh = grid(GRID);
+    let limits = MeshLimits {
+        max_vertices: 1 << 12,
+        max_indices: 1 << 14,
+    };
+    let chunks = mesh.split(limits);
+    let vertices: usize = chunks.iter().map(|chunk| chunk.vertices.len()).sum();
+    println!(
+        "{} indices split into {} chunks, {} vertices for {} originally",
+        mesh.indices.len(),
+        chunks.len(),
+        vertices,
+        mesh.vertices.len()
+    );
+}
+
+fn grid(size: u32) -> Mesh {
+    let row = size + 1;
+    let vertices = (0..row * row)
+        .map(|i| Vertex {
+            pos: [(i % row) as f32, (i / row) as f32, 0.0, 1.0],
+            uv: [0.0; 2],
+            color: [1.0; 4],
+            normal: [0.0, 0.0, 1.0],
+            tangent: [1.0, 0.0, 0.0, 1.0],
+            joints: [0; 4],
+            weights: [0.0; 4],
+        })
+        .collect();
+    let mut indices = Vec::new();
+    for y in 0..size {
+        for x in 0..size {
+            let a = y * row + x;
+            indices.extend([a, a + 1, a + row + 1, a, a + row + 1, a + row]);
+        }
+    }
+    Mesh {
+        vertices,
+        indices,
+        transform: Mat4::IDENTITY,
+        user_id: 0,
+    }
+}
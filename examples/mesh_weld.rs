@@ -0,0 +1,23 @@
+use pulsar::model::{Mesh, Vertex, WeldMode};
+
+/// Corners of every triangle of `mesh`, the soup a loader without indices would produce.
+fn soup(mesh: &Mesh) -> Vec<Vertex> {
+    mesh.indices
+        .iter()
+        .map(|&index| mesh.vertices[index as usize])
+        .collect()
+}
+
+// Collapse a cube soup back to shared vertices and print how many each weld mode keeps
+fn main() {
+    let raw = soup(&Mesh::cube(1.0, [1.0; 4]));
+    println!("{} raw vertices", raw.len());
+    for mode in [WeldMode::default(), WeldMode::Positions { epsilon: 1e-5 }] {
+        let welded = Mesh::indexed_from_triangles_with(raw.clone(), mode);
+        println!(
+            "{mode:?}: {} vertices, {} indices",
+            welded.vertices.len(),
+            welded.indices.len()
+        );
+    }
+}
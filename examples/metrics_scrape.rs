@@ -0,0 +1,32 @@
+use pulsar::{metrics::Metrics, metrics_endpoint::MetricsEndpoint};
+use std::{
+    error::Error,
+    io::{Read, Write},
+    net::TcpStream,
+    time::{Duration, Instant},
+};
+
+// Headless run: fake a second of frames, then print what a Prometheus scrape of the endpoint sees
+fn main() -> Result<(), Box<dyn Error>> {
+    env_logger::init();
+    let endpoint = MetricsEndpoint::bind("127.0.0.1:0")?;
+
+    let mut metrics = Metrics::default();
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_millis(1100) {
+        metrics.start_frame();
+        std::thread::sleep(Duration::from_millis(16));
+        metrics.counters.draw_calls += 3;
+        metrics.end_frame();
+    }
+
+    let mut stream = TcpStream::connect(endpoint.local_addr())?;
+    stream.write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    let (_, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or("Response has no body")?;
+    print!("{body}");
+    Ok(())
+}
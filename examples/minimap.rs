@@ -0,0 +1,114 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use glam::{Mat4, Vec2, Vec3};
+use pulsar::{
+    app::{Application, UserEvent},
+    inset::{InsetAnchor, InsetCamera, InsetProjection, InsetRect, InsetSize, InsetView},
+    model::Mesh,
+    MeshHandle,
+};
+use std::{collections::HashMap, error::Error};
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    window::WindowId,
+};
+
+const GRID: i32 = 8;
+const MAIN_LAYER: u32 = 1;
+const MINIMAP_LAYER: u32 = 2;
+
+/// Square minimap in the bottom right corner, its sides 30% of the shorter side of the view.
+fn minimap() -> InsetView {
+    let rect = InsetRect::new(
+        InsetAnchor::BottomRight,
+        Vec2::splat(16.0),
+        InsetSize::Square(0.3),
+    );
+    // Straight down on the grid, +X to the right and +Z to the bottom of the map
+    let camera = InsetCamera::look_at(
+        Vec3::new(0.0, 30.0, 0.0),
+        Vec3::ZERO,
+        Vec3::NEG_Z,
+        InsetProjection::Orthographic {
+            half_height: GRID as f32 * 0.75,
+            near: 0.1,
+            far: 100.0,
+        },
+    );
+    let mut view = InsetView::new(rect, camera);
+    view.layer_mask = MINIMAP_LAYER;
+    view.background = Some([0.05, 0.05, 0.1, 1.0]);
+    view
+}
+
+/// Draws a grid of cubes with a minimap over it. A clicked cube leaves the minimap.
+struct Host {
+    app: Application,
+    added: bool,
+    hidden: HashMap<WindowId, MeshHandle>,
+}
+
+impl ApplicationHandler<UserEvent> for Host {
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        self.app.user_event(event_loop, event);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        self.app.window_event(event_loop, window_id, event);
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.resumed(event_loop);
+        if self.added {
+            return;
+        }
+        let windows: Vec<WindowId> = self.app.window_manager.window_ids().collect();
+        for window_id in windows {
+            for z in -GRID / 2..GRID / 2 {
+                for x in -GRID / 2..GRID / 2 {
+                    let mut cube = Mesh::cube(0.4, [0.3, 0.5 + 0.05 * x as f32, 0.8, 1.0]);
+                    cube.transform = Mat4::from_translation(Vec3::new(x as f32, 0.0, z as f32));
+                    self.app.add_mesh(window_id, cube);
+                }
+            }
+            self.app.set_insets(window_id, vec![minimap()]);
+        }
+        self.added = true;
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.about_to_wait(event_loop);
+        let windows: Vec<WindowId> = self.app.window_manager.window_ids().collect();
+        for window_id in windows {
+            let Some(mesh) = self.app.selected_mesh(window_id) else {
+                continue;
+            };
+            if self.hidden.insert(window_id, mesh) != Some(mesh) {
+                println!("{mesh:?} left the minimap");
+                self.app.set_mesh_layers(window_id, mesh, MAIN_LAYER);
+            }
+        }
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.exiting(event_loop);
+    }
+}
+
+// Draw a minimap over a grid of cubes, clicking a cube hides it from the minimap
+fn main() -> Result<(), Box<dyn Error>> {
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut host = Host {
+        app: Application::new(&event_loop)?,
+        added: false,
+        hidden: HashMap::new(),
+    };
+    event_loop.run_app(&mut host).map_err(Into::into)
+}
@@ -0,0 +1,29 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use pulsar::{
+    app::{Application, UserEvent},
+    config::{ApplicationOptions, RenderSettings},
+};
+use std::error::Error;
+use winit::event_loop::EventLoop;
+
+/// Samples asked for in the demo window, lowered to what the device supports.
+const MSAA_SAMPLES: u32 = 4;
+
+// Show the demo scene with 4x MSAA: the edges of the covers are smoothed, and the log tells when
+// the device offers less.
+fn main() -> Result<(), Box<dyn Error>> {
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut app = Application::with_options(
+        &event_loop,
+        ApplicationOptions {
+            demo_scene: Some(true),
+            render: Some(RenderSettings {
+                msaa_samples: MSAA_SAMPLES,
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    )?;
+    event_loop.run_app(&mut app).map_err(Into::into)
+}
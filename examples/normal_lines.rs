@@ -0,0 +1,95 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use pulsar::{
+    app::{Application, UserEvent},
+    material::Material,
+    model::Mesh,
+    MeshHandle,
+};
+use std::{collections::HashSet, error::Error};
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    window::WindowId,
+};
+
+fn cube() -> Mesh {
+    Mesh::cube(1.0, [0.3, 0.6, 0.9, 1.0])
+}
+
+/// Adds a cube to every window once they exist, each click draws the selected mesh as its normal
+/// lines or back as it is.
+struct Viewer {
+    app: Application,
+    added: bool,
+    /// Meshes drawn with [`Material::normal_lines`].
+    lined: HashSet<MeshHandle>,
+}
+
+impl ApplicationHandler<UserEvent> for Viewer {
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        self.app.user_event(event_loop, event);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        let clicked = matches!(
+            event,
+            WindowEvent::MouseInput {
+                state: winit::event::ElementState::Released,
+                ..
+            }
+        );
+        self.app.window_event(event_loop, window_id, event);
+        if !clicked {
+            return;
+        }
+        let Some(mesh) = self.app.selected_mesh(window_id) else {
+            return;
+        };
+        let material = if self.lined.remove(&mesh) {
+            None
+        } else {
+            self.lined.insert(mesh);
+            Some(Material::normal_lines())
+        };
+        self.app.set_mesh_material(window_id, mesh, material);
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.resumed(event_loop);
+        if self.added {
+            return;
+        }
+        let windows: Vec<WindowId> = self.app.window_manager.window_ids().collect();
+        for window_id in windows {
+            self.app.add_mesh(window_id, cube());
+        }
+        self.added = true;
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.about_to_wait(event_loop);
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.exiting(event_loop);
+    }
+}
+
+// Show a cube a click turns into its normal lines, drawn as it is on devices without geometry
+// shaders.
+fn main() -> Result<(), Box<dyn Error>> {
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut viewer = Viewer {
+        app: Application::new(&event_loop)?,
+        added: false,
+        lined: HashSet::new(),
+    };
+    event_loop.run_app(&mut viewer).map_err(Into::into)
+}
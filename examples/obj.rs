@@ -0,0 +1,88 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use glam::{Mat4, Vec3};
+use pulsar::{
+    app::{Application, UserEvent},
+    model::Model,
+};
+use std::{error::Error, path::Path};
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    window::WindowId,
+};
+
+const PATH: &str = "assets/models/shapes.obj";
+
+/// Adds the meshes of `PATH` side by side to every window once they exist.
+struct Viewer {
+    app: Application,
+    added: bool,
+}
+
+fn load() -> Result<Model, Box<dyn Error>> {
+    let mut model = Model::from_obj(Path::new(PATH))?;
+    let count = model.meshes.len();
+    for (index, mesh) in model.meshes.iter_mut().enumerate() {
+        let x = index as f32 - (count - 1) as f32 * 0.5;
+        mesh.transform = Mat4::from_translation(Vec3::new(x * 0.8, 0.0, 0.0));
+    }
+    Ok(model)
+}
+
+impl ApplicationHandler<UserEvent> for Viewer {
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        self.app.user_event(event_loop, event);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        self.app.window_event(event_loop, window_id, event);
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.resumed(event_loop);
+        if self.added {
+            return;
+        }
+        let windows: Vec<WindowId> = self.app.window_manager.window_ids().collect();
+        for window_id in windows {
+            for mesh in load().expect("Model loaded at startup").meshes {
+                self.app.add_mesh(window_id, mesh);
+            }
+        }
+        self.added = true;
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.about_to_wait(event_loop);
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.exiting(event_loop);
+    }
+}
+
+// Load a cube and a pyramid from an OBJ file and draw them next to the demo scene
+fn main() -> Result<(), Box<dyn Error>> {
+    let model = load()?;
+    for mesh in &model.meshes {
+        println!(
+            "{} vertices, {} indices",
+            mesh.vertices.len(),
+            mesh.indices.len()
+        );
+    }
+
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut viewer = Viewer {
+        app: Application::new(&event_loop)?,
+        added: false,
+    };
+    event_loop.run_app(&mut viewer).map_err(Into::into)
+}
@@ -0,0 +1,146 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use pulsar::{
+    app::{Application, UserEvent},
+    object_audit::ObjectAudit,
+    thumbnail::{Thumbnail, ThumbnailTarget},
+};
+use std::{
+    error::Error,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    window::WindowId,
+};
+
+/// Event loop iterations of reloads and thumbnail requests per cycle, then the time left to
+/// the render threads and the job pool to drain them.
+const CYCLE: u32 = 60;
+const SETTLE: Duration = Duration::from_millis(500);
+const CYCLES: u32 = 60;
+/// Cycles ending with a window closed and another opened.
+const CHURN_EVERY: u32 = 3;
+const THUMBNAIL_SIZE: u32 = 64;
+
+/// Reloads the window textures, requests and drops thumbnails and replaces a window every few
+/// cycles. The counts at the end of each settled cycle must match the first's.
+struct Soak {
+    app: Application,
+    textures: [PathBuf; 2],
+    thumbnails: Vec<Thumbnail>,
+    frame: u32,
+    settled_at: Instant,
+    cycle: u32,
+    baseline: Option<ObjectAudit>,
+    result: Result<(), String>,
+}
+
+impl Soak {
+    fn windows(&self) -> Vec<WindowId> {
+        self.app.window_manager.window_ids().collect()
+    }
+
+    fn step(&mut self, event_loop: &ActiveEventLoop) -> Result<bool, Box<dyn Error>> {
+        self.frame += 1;
+        if self.frame <= CYCLE {
+            let texture = &self.textures[self.frame as usize % 2];
+            for window_id in self.windows() {
+                self.app.load_texture(window_id, texture.clone());
+                self.thumbnails.extend(self.app.request_thumbnail(
+                    window_id,
+                    ThumbnailTarget::Texture,
+                    THUMBNAIL_SIZE,
+                ));
+            }
+            return Ok(true);
+        }
+        if self.frame == CYCLE + 1 {
+            self.settled_at = Instant::now() + SETTLE;
+            self.thumbnails.clear();
+            if self.cycle % CHURN_EVERY == CHURN_EVERY - 1 {
+                if let Some(window_id) = self.windows().first() {
+                    self.app.window_manager.close_window(*window_id);
+                }
+                self.app.window_manager.create_window(event_loop, None)?;
+            }
+        }
+        if Instant::now() < self.settled_at {
+            return Ok(true);
+        }
+
+        self.frame = 0;
+        self.cycle += 1;
+        let audit = self.app.object_audit(5);
+        match &self.baseline {
+            None => self.baseline = Some(audit),
+            Some(baseline) => {
+                let diff = audit.diff(baseline);
+                if !diff.is_flat() {
+                    return Err(format!("Cycle {}: {diff}", self.cycle).into());
+                }
+            }
+        }
+        self.app.audit_objects(5);
+        Ok(self.cycle < CYCLES)
+    }
+}
+
+impl ApplicationHandler<UserEvent> for Soak {
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        self.app.user_event(event_loop, event);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        self.app.window_event(event_loop, window_id, event);
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.resumed(event_loop);
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.about_to_wait(event_loop);
+        match self.step(event_loop) {
+            Ok(true) => {}
+            Ok(false) => {
+                println!("Object counts stayed flat over {CYCLES} cycles");
+                event_loop.exit();
+            }
+            Err(error) => {
+                self.result = Err(error.to_string());
+                event_loop.exit();
+            }
+        }
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.exiting(event_loop);
+    }
+}
+
+// Soak a renderer in texture reloads, thumbnails and window churn, its counts compared cycle
+// after cycle.
+fn main() -> Result<(), Box<dyn Error>> {
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut soak = Soak {
+        app: Application::new(&event_loop)?,
+        textures: ["assets/img/cross.png", "assets/img/cross2.png"].map(PathBuf::from),
+        thumbnails: Vec::new(),
+        frame: 0,
+        settled_at: Instant::now(),
+        cycle: 0,
+        baseline: None,
+        result: Ok(()),
+    };
+    event_loop.run_app(&mut soak)?;
+    soak.result.map_err(Into::into)
+}
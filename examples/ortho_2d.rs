@@ -0,0 +1,109 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use glam::{Mat4, Vec2, Vec3};
+use pulsar::{
+    app::{Application, UserEvent},
+    camera::Ortho2DController,
+    model::{Mesh, Vertex},
+};
+use std::error::Error;
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    window::WindowId,
+};
+
+const GRID: u32 = 10;
+
+/// Adds a grid of quads to the 2D world of every window once they exist.
+struct Editor {
+    app: Application,
+    added: bool,
+}
+
+fn controller() -> Ortho2DController {
+    let middle = GRID as f32 / 2.0;
+    Ortho2DController::new(Vec2::splat(middle), 60.0)
+        .with_zoom_limits(10.0, 400.0)
+        .with_bounds(Vec2::splat(-2.0), Vec2::splat(GRID as f32 + 2.0))
+}
+
+/// Unit quad at `(x, y)` with a little gap, colored like a checkerboard.
+fn quad(x: u32, y: u32) -> Mesh {
+    let color = if (x + y).is_multiple_of(2) {
+        [0.9, 0.9, 0.9, 1.0]
+    } else {
+        [0.2, 0.4, 0.8, 1.0]
+    };
+    let vertex = |u: f32, v: f32| Vertex {
+        pos: [u * 0.9, v * 0.9, 0.0, 1.0],
+        uv: [u, v],
+        color,
+        normal: [0.0, 0.0, 1.0],
+        tangent: [1.0, 0.0, 0.0, 1.0],
+        joints: [0; 4],
+        weights: [0.0; 4],
+    };
+    Mesh {
+        vertices: vec![
+            vertex(0.0, 0.0),
+            vertex(1.0, 0.0),
+            vertex(1.0, 1.0),
+            vertex(0.0, 1.0),
+        ],
+        indices: vec![0, 1, 2, 2, 3, 0],
+        transform: Mat4::from_translation(Vec3::new(x as f32, y as f32, 0.0)),
+        user_id: 0,
+    }
+}
+
+impl ApplicationHandler<UserEvent> for Editor {
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        self.app.user_event(event_loop, event);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        self.app.window_event(event_loop, window_id, event);
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.resumed(event_loop);
+        if self.added {
+            return;
+        }
+        let windows: Vec<WindowId> = self.app.window_manager.window_ids().collect();
+        for window_id in windows {
+            self.app.set_ortho_2d(window_id, controller());
+            for y in 0..GRID {
+                for x in 0..GRID {
+                    self.app.add_mesh_2d(window_id, quad(x, y));
+                }
+            }
+        }
+        self.added = true;
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.about_to_wait(event_loop);
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.exiting(event_loop);
+    }
+}
+
+// Show a grid panned with the middle button and zoomed with the wheel
+fn main() -> Result<(), Box<dyn Error>> {
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut editor = Editor {
+        app: Application::new(&event_loop)?,
+        added: false,
+    };
+    event_loop.run_app(&mut editor).map_err(Into::into)
+}
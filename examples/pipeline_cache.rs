@@ -0,0 +1,12 @@
+use pulsar::pipeline_cache::{cache_dir, cache_path, CACHE_DIR_ENV};
+
+// Print where pipeline caches are kept. Building pipelines through the cache needs a GPU: run any
+// windowed example twice and look for the `Pipeline cache hit` line.
+fn main() {
+    println!("Caches are kept in {}", cache_dir().display());
+    println!(
+        "An NVIDIA RTX 3070's would be {}",
+        cache_path(0x10de, 0x2484).display()
+    );
+    println!("Set {CACHE_DIR_ENV} to move them");
+}
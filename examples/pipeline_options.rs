@@ -0,0 +1,21 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use pulsar::{
+    app::{Application, UserEvent},
+    config::ApplicationOptions,
+};
+use std::error::Error;
+use winit::event_loop::EventLoop;
+
+// Show the demo scene: its covers are culled unless wound as the other meshes are.
+fn main() -> Result<(), Box<dyn Error>> {
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut app = Application::with_options(
+        &event_loop,
+        ApplicationOptions {
+            demo_scene: Some(true),
+            ..Default::default()
+        },
+    )?;
+    event_loop.run_app(&mut app).map_err(Into::into)
+}
@@ -0,0 +1,18 @@
+use glam::{Mat4, Vec2};
+use pulsar::pixel_snap::{origin_in_pixels, snap_to_pixels};
+
+const SIZE: Vec2 = Vec2::new(801.0, 601.0);
+
+// Print where meshes at fractional positions land before and after snapping
+fn main() {
+    let projection = Mat4::orthographic_rh(0.0, SIZE.x, 0.0, SIZE.y, -1.0, 1.0);
+    for step in 0..8 {
+        let position = Vec2::splat(7.0 + step as f32 * 31.13);
+        let pvm = projection * Mat4::from_translation(position.extend(0.0));
+        println!(
+            "{position}: {} unsnapped, {} snapped",
+            origin_in_pixels(pvm, Vec2::ZERO, SIZE),
+            origin_in_pixels(snap_to_pixels(pvm, Vec2::ZERO, SIZE), Vec2::ZERO, SIZE)
+        );
+    }
+}
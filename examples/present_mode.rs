@@ -0,0 +1,27 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use pulsar::{
+    app::{Application, UserEvent},
+    config::{ApplicationOptions, RenderSettings},
+    present_health::PresentModePreference as Preference,
+};
+use std::error::Error;
+use winit::event_loop::EventLoop;
+
+// Show the demo scene presenting IMMEDIATE where the surface offers it: Alt+V cycles the
+// preference and the log tells the mode each swapchain resolved to.
+fn main() -> Result<(), Box<dyn Error>> {
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut app = Application::with_options(
+        &event_loop,
+        ApplicationOptions {
+            demo_scene: Some(true),
+            render: Some(RenderSettings {
+                present_mode: Preference::Immediate,
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    )?;
+    event_loop.run_app(&mut app).map_err(Into::into)
+}
@@ -0,0 +1,52 @@
+use pulsar::present_wait::{PacingHistogram, PresentPacer, PresentWaitOutcome, PresentWaitSupport};
+use std::time::{Duration, Instant};
+
+const FRAME: Duration = Duration::from_millis(16);
+
+// Pace a few synthetic frames on present wait, print what each one measured and the histogram
+// the report would log, then time out until the pacer falls back to the fences. The latency
+// gained needs a device with the extensions, compare the pacing histograms logged with
+// `graphics.present_wait_pacing` on and off.
+fn main() {
+    let mut pacer = PresentPacer::new(PresentWaitSupport {
+        present_id: true,
+        present_wait: true,
+    });
+    pacer.set_enabled(true);
+    let mut now = Instant::now();
+    let mut intervals = Vec::new();
+    // Every third present reaches the screen a frame late
+    for index in 0..12 {
+        now += if index % 3 == 2 { FRAME * 2 } else { FRAME };
+        if pacer.wait_target().is_some() {
+            let (timing, _) = pacer.waited(PresentWaitOutcome::Presented, now);
+            if let Some(timing) = timing {
+                println!(
+                    "Frame {index}: input latency {:?}, present interval {:?}",
+                    timing.input_latency, timing.present_interval
+                );
+                intervals.extend(timing.present_interval);
+            }
+        }
+        pacer.input_sampled(now);
+        if let Some(present_id) = pacer.next_id() {
+            pacer.presented(present_id);
+        }
+    }
+    println!(
+        "Pacing histogram {}",
+        PacingHistogram::from_intervals(&intervals).summary()
+    );
+
+    let mut waits = 0;
+    while pacer.active() {
+        now += FRAME;
+        pacer.waited(PresentWaitOutcome::TimedOut, now);
+        waits += 1;
+        pacer.input_sampled(now);
+        if let Some(present_id) = pacer.next_id() {
+            pacer.presented(present_id);
+        }
+    }
+    println!("Fell back to fence pacing after {waits} timeouts in a row");
+}
@@ -0,0 +1,20 @@
+use glam::Vec2;
+use pulsar::model::Mesh;
+
+// Generate every primitive and print its vertex and triangle counts
+fn main() {
+    let color = [1.0; 4];
+    let meshes = [
+        ("quad", Mesh::quad(Vec2::new(2.0, 1.0), color)),
+        ("cube", Mesh::cube(1.0, color)),
+        ("sphere", Mesh::uv_sphere(1.0, 24, 12, color)),
+        ("plane", Mesh::plane(4.0, 2.0, 3, color)),
+    ];
+    for (name, mesh) in meshes {
+        println!(
+            "{name}: {} vertices, {} triangles",
+            mesh.vertices.len(),
+            mesh.indices.len() / 3
+        );
+    }
+}
@@ -0,0 +1,52 @@
+use pulsar::metrics::{QueueKind, QueueOverlap, QueueSpan};
+
+const MS: u64 = 1_000_000;
+
+fn span(queue: QueueKind, start_ms: u64, end_ms: u64) -> QueueSpan {
+    QueueSpan {
+        queue,
+        start: start_ms * MS,
+        end: end_ms * MS,
+    }
+}
+
+// Print how busy each queue is and the async efficiency of a few synthetic timestamp sets of a
+// frame. Reading them back from a device with transfer and compute queues needs a GPU.
+fn main() {
+    use QueueKind::{Compute, Graphics, Transfer};
+    let cases = [
+        (
+            "full overlap",
+            vec![span(Graphics, 0, 10), span(Transfer, 2, 6)],
+        ),
+        (
+            "zero overlap",
+            vec![span(Graphics, 0, 4), span(Transfer, 4, 8)],
+        ),
+        (
+            "partial overlap",
+            vec![span(Graphics, 0, 6), span(Transfer, 4, 8)],
+        ),
+        (
+            "two async queues",
+            vec![
+                span(Graphics, 0, 5),
+                span(Graphics, 10, 15),
+                span(Transfer, 3, 12),
+                span(Compute, 8, 12),
+            ],
+        ),
+        (
+            "graphics only",
+            vec![span(Graphics, 0, 10), span(Graphics, 5, 12)],
+        ),
+    ];
+    for (name, spans) in cases {
+        let overlap = QueueOverlap::from_spans(&spans);
+        let efficiency = overlap.async_efficiency().map(|share| share * 100.0);
+        println!(
+            "{name}: busy {:?}, async efficiency {efficiency:?}",
+            overlap.busy
+        );
+    }
+}
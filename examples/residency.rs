@@ -0,0 +1,36 @@
+use pulsar::residency::{ResidencyManager, ResidencyPriority};
+
+const MB: u64 = 1024 * 1024;
+
+// Drive the residency policy with a fake budget and print what it evicts
+fn main() {
+    let mut residency = ResidencyManager::new(4 * MB);
+    residency.track("ui", MB, ResidencyPriority::AlwaysResident);
+    residency.track("terrain", MB, ResidencyPriority::Normal);
+    for name in ["rock", "tree", "cloud"] {
+        residency.track(name, MB, ResidencyPriority::Streaming);
+    }
+
+    // Frame 1 uses everything but the rock, frame 2 only the ui and the cloud
+    residency.next_frame();
+    for name in ["ui", "terrain", "tree", "cloud"] {
+        residency.touch(&name);
+    }
+    residency.next_frame();
+    for name in ["ui", "cloud"] {
+        residency.touch(&name);
+    }
+    println!("Over a 4 MB budget: {:?}", residency.evict_over_budget());
+    residency.set_budget(MB);
+    println!("Over a 1 MB budget: {:?}", residency.evict_over_budget());
+
+    // Drawing an evicted resource reloads it
+    residency.next_frame();
+    println!("Rock reloaded: {}", residency.touch(&"rock"));
+
+    let stats = residency.stats();
+    println!(
+        "{} evictions, {} reloads, {}/{} bytes resident",
+        stats.evictions, stats.reloads, stats.resident_bytes, stats.budget_bytes
+    );
+}
@@ -0,0 +1,21 @@
+use pulsar::testing::{scene_hash, SceneGenerator};
+use std::{error::Error, time::Instant};
+
+/// Generating objects this many times must stay well under a second.
+const STRESS_COUNT: usize = 100_000;
+
+// Time how fast large seeded scenes are generated, the budget is only enforced in release
+fn main() -> Result<(), Box<dyn Error>> {
+    let start = Instant::now();
+    let objects = SceneGenerator::new(42).objects(STRESS_COUNT);
+    let elapsed = start.elapsed();
+    println!(
+        "{} objects generated in {elapsed:?}, hash {:#018x}",
+        objects.len(),
+        scene_hash(&objects)
+    );
+    if cfg!(not(debug_assertions)) && elapsed.as_secs_f32() > 0.25 {
+        return Err(format!("Generating {STRESS_COUNT} objects took {elapsed:?}").into());
+    }
+    Ok(())
+}
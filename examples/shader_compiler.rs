@@ -0,0 +1,26 @@
+use pulsar::shader_compiler::{discover, SHADER_COMPILER_ENV};
+use std::path::Path;
+
+// Print the GLSL compiler found on this machine and the command line a compute shader would be
+// compiled with. Set PULSAR_SHADERC to pick another one.
+fn main() {
+    env_logger::init();
+    match discover() {
+        Some(compiler) => {
+            let args: Vec<_> = compiler
+                .args("comp", Path::new("out.spv"))
+                .iter()
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect();
+            println!(
+                "{:?} at {}: {}",
+                compiler.kind,
+                compiler.path.display(),
+                args.join(" ")
+            );
+        }
+        None => println!(
+            "No compiler found, install glslc or glslangValidator or set {SHADER_COMPILER_ENV}"
+        ),
+    }
+}
@@ -0,0 +1,36 @@
+use pulsar::shader_include;
+use std::{error::Error, fs};
+
+// Inline a shader's includes, print the result with the file and line each line came from, then
+// what the preprocessor says of an include that cannot be found.
+fn main() -> Result<(), Box<dyn Error>> {
+    let root = std::env::temp_dir().join("pulsar_shader_include_example");
+    let include_dir = root.join("include");
+    fs::create_dir_all(&include_dir)?;
+    fs::write(
+        include_dir.join("common.glsl"),
+        "#pragma once\nstruct Light { vec3 dir; };\n",
+    )?;
+    fs::write(
+        include_dir.join("lighting.glsl"),
+        "#include \"common.glsl\"\nfloat lambert(Light l, vec3 n) { return max(dot(n, l.dir), 0.0); }\n",
+    )?;
+    let shader = root.join("lit.frag");
+    fs::write(
+        &shader,
+        "#version 450\n#include <common.glsl>\n#include <lighting.glsl>\nvoid main() {}\n",
+    )?;
+
+    let preprocessed = shader_include::preprocess(&shader, &include_dir)?;
+    for (line, origin) in preprocessed.source.lines().zip(&preprocessed.origins) {
+        println!("{}:{} | {line}", origin.path.display(), origin.line);
+    }
+
+    let missing = root.join("missing.frag");
+    fs::write(&missing, "#include <nowhere.glsl>\n")?;
+    if let Err(error) = shader_include::preprocess(&missing, &include_dir) {
+        println!("{error}");
+    }
+    fs::remove_dir_all(&root)?;
+    Ok(())
+}
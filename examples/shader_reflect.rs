@@ -0,0 +1,142 @@
+use ash::vk;
+use pulsar::shader_reflect;
+use std::error::Error;
+
+const STORAGE_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_UNIFORM: u32 = 2;
+const STORAGE_PUSH_CONSTANT: u32 = 9;
+const STORAGE_STORAGE_BUFFER: u32 = 12;
+
+/// SPIR-V assembled one instruction at a time, after a header of bound 64.
+struct Assembler {
+    words: Vec<u32>,
+}
+
+impl Assembler {
+    fn new() -> Self {
+        Self {
+            words: vec![0x0723_0203, 0x0001_0000, 0, 64, 0],
+        }
+    }
+
+    fn op(mut self, opcode: u32, operands: &[u32]) -> Self {
+        self.words
+            .push(((operands.len() as u32 + 1) << 16) | opcode);
+        self.words.extend_from_slice(operands);
+        self
+    }
+
+    fn name(self, id: u32, name: &str) -> Self {
+        let mut bytes = name.as_bytes().to_vec();
+        bytes.resize(name.len() / 4 * 4 + 4, 0);
+        let mut operands = vec![id];
+        operands.extend(
+            bytes
+                .chunks_exact(4)
+                .map(|word| u32::from_le_bytes(word.try_into().unwrap())),
+        );
+        self.op(5, &operands)
+    }
+
+    fn decorate(self, id: u32, decoration: &[u32]) -> Self {
+        self.op(71, &[&[id], decoration].concat())
+    }
+
+    fn member_decorate(self, id: u32, member: u32, decoration: &[u32]) -> Self {
+        self.op(72, &[&[id, member], decoration].concat())
+    }
+
+    fn variable(self, pointer: u32, id: u32, storage_class: u32) -> Self {
+        self.op(59, &[pointer, id, storage_class])
+    }
+
+    fn set_binding(self, id: u32, set: u32, binding: u32) -> Self {
+        self.decorate(id, &[34, set]).decorate(id, &[33, binding])
+    }
+}
+
+/// Floats, vectors, a `mat4` and the `uint` constant 4 every module below shares.
+fn scalars() -> Assembler {
+    Assembler::new()
+        .op(22, &[1, 32])
+        .op(23, &[2, 1, 4])
+        .op(24, &[3, 2, 4])
+        .op(21, &[9, 32, 0])
+        .op(43, &[9, 10, 4])
+}
+
+/// A push constant block of `members`, offsets in bytes, as `%18` pointed to by `%19`.
+fn push_block(assembler: Assembler, members: &[(u32, u32)]) -> Assembler {
+    let types: Vec<u32> = members.iter().map(|&(ty, _)| ty).collect();
+    let mut assembler = assembler.op(30, &[&[18], types.as_slice()].concat());
+    for (member, &(ty, offset)) in members.iter().enumerate() {
+        assembler = assembler.member_decorate(18, member as u32, &[35, offset]);
+        if ty == 3 {
+            assembler = assembler.member_decorate(18, member as u32, &[7, 16]);
+        }
+    }
+    assembler
+        .decorate(18, &[2])
+        .op(32, &[19, STORAGE_PUSH_CONSTANT, 18])
+        .variable(19, 20, STORAGE_PUSH_CONSTANT)
+}
+
+/// A uniform block, an array of four combined image samplers, a storage buffer of `vec4`s and
+/// a push constant block of a `mat4` and a `vec4`.
+fn vertex_module() -> Vec<u32> {
+    let module = scalars()
+        .name(4, "Globals")
+        .name(6, "ubo")
+        .name(13, "samplers")
+        .name(15, "Bones")
+        // set 0 binding 0, the uniform block
+        .op(30, &[4, 3])
+        .decorate(4, &[2])
+        .member_decorate(4, 0, &[35, 0])
+        .member_decorate(4, 0, &[7, 16])
+        .op(32, &[5, STORAGE_UNIFORM, 4])
+        .variable(5, 6, STORAGE_UNIFORM)
+        .set_binding(6, 0, 0)
+        // set 1 binding 2, sampler2D[4]
+        .op(25, &[7, 1, 1, 0, 0, 0, 1, 0])
+        .op(27, &[8, 7])
+        .op(28, &[11, 8, 10])
+        .op(32, &[12, STORAGE_UNIFORM_CONSTANT, 11])
+        .variable(12, 13, STORAGE_UNIFORM_CONSTANT)
+        .set_binding(13, 1, 2)
+        // set 2 binding 0, an anonymous storage block named after its type
+        .op(29, &[14, 2])
+        .decorate(14, &[6, 16])
+        .op(30, &[15, 14])
+        .decorate(15, &[2])
+        .member_decorate(15, 0, &[35, 0])
+        .op(32, &[16, STORAGE_STORAGE_BUFFER, 15])
+        .variable(16, 17, STORAGE_STORAGE_BUFFER)
+        .set_binding(17, 2, 0);
+    push_block(module, &[(3, 0), (2, 64)]).words
+}
+
+// Reflect a hand assembled vertex module, print its descriptors and push constants, then what
+// it reads that a renderer writing a single uniform buffer leaves out.
+fn main() -> Result<(), Box<dyn Error>> {
+    let interface = shader_reflect::reflect(&vertex_module(), vk::ShaderStageFlags::VERTEX)?;
+    for binding in &interface.bindings {
+        println!(
+            "set {} binding {}: {:?} x{} `{}`",
+            binding.set, binding.binding, binding.descriptor_type, binding.count, binding.name
+        );
+    }
+    for range in &interface.push_constants {
+        println!(
+            "{:?} push constants, bytes {}..{}",
+            range.stage_flags,
+            range.offset,
+            range.offset + range.size
+        );
+    }
+    let written = [(0, 0, vk::DescriptorType::UNIFORM_BUFFER)];
+    for problem in interface.unwritten(&written, &[]) {
+        println!("  {problem}");
+    }
+    Ok(())
+}
@@ -0,0 +1,79 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use pulsar::{
+    app::{Application, UserEvent},
+    skinning::{AnimationPlayer, SkinnedMesh},
+};
+use std::{error::Error, path::Path};
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    window::WindowId,
+};
+
+/// Built after the SimpleSkin sample of the glTF tutorial: a strip two units tall weighed on a
+/// root joint and a joint one unit up, which bends a quarter turn either way over 5.5 seconds.
+const PATH: &str = "assets/models/simple_skin.gltf";
+
+/// Plays the bend of `PATH` in every window once they exist.
+struct Viewer {
+    app: Application,
+    added: bool,
+}
+
+impl ApplicationHandler<UserEvent> for Viewer {
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        self.app.user_event(event_loop, event);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        self.app.window_event(event_loop, window_id, event);
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.resumed(event_loop);
+        if self.added {
+            return;
+        }
+        let windows: Vec<WindowId> = self.app.window_manager.window_ids().collect();
+        for window_id in windows {
+            let skinned = SkinnedMesh::from_gltf(Path::new(PATH)).expect("Skin loaded at startup");
+            for SkinnedMesh {
+                mesh,
+                skin,
+                animations,
+            } in skinned
+            {
+                // One player per window, each advances its own
+                let player = AnimationPlayer::new(skin, animations);
+                player.play("bend");
+                self.app.add_skinned_mesh(window_id, mesh, &player);
+            }
+        }
+        self.added = true;
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.about_to_wait(event_loop);
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.exiting(event_loop);
+    }
+}
+
+// Play the bend of a skinned strip, its palettes read from a uniform buffer
+fn main() -> Result<(), Box<dyn Error>> {
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut viewer = Viewer {
+        app: Application::new(&event_loop)?,
+        added: false,
+    };
+    event_loop.run_app(&mut viewer).map_err(Into::into)
+}
@@ -0,0 +1,83 @@
+use glam::{Mat4, Quat, Vec3};
+use pulsar::skinning::{
+    AnimationClip, AnimationPlayer, Channel, Interpolation, Joint, Keyframes, Skin,
+};
+use std::f32::consts::FRAC_PI_2;
+
+/// Seconds the player advances between two printed poses.
+const STEP: f32 = 0.5;
+
+/// A root at the origin and a child one unit above it, both at their bind pose.
+fn arm() -> Skin {
+    let joint = |name: &str, parent, translation: Vec3, inverse_bind| Joint {
+        name: name.into(),
+        parent,
+        origin: Mat4::IDENTITY,
+        translation,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+        inverse_bind,
+    };
+    // Listed child first, the parents are resolved whatever the order
+    Skin {
+        joints: vec![
+            joint(
+                "forearm",
+                Some(1),
+                Vec3::Y,
+                Mat4::from_translation(-Vec3::Y),
+            ),
+            joint("shoulder", None, Vec3::ZERO, Mat4::IDENTITY),
+        ],
+    }
+}
+
+/// The shoulder slides two units along +X over two seconds while the forearm turns a quarter
+/// around +Z, and the forearm's scale steps at one second.
+fn wave() -> AnimationClip {
+    AnimationClip::new(
+        "wave",
+        vec![
+            Channel {
+                joint: 1,
+                interpolation: Interpolation::Linear,
+                keyframes: Keyframes::Translation(vec![(0.0, Vec3::ZERO), (2.0, 2.0 * Vec3::X)]),
+            },
+            Channel {
+                joint: 0,
+                interpolation: Interpolation::Linear,
+                keyframes: Keyframes::Rotation(vec![
+                    (0.0, Quat::IDENTITY),
+                    (2.0, Quat::from_rotation_z(FRAC_PI_2)),
+                ]),
+            },
+            Channel {
+                joint: 0,
+                interpolation: Interpolation::Step,
+                keyframes: Keyframes::Scale(vec![(0.0, Vec3::ONE), (1.0, Vec3::splat(2.0))]),
+            },
+        ],
+    )
+}
+
+// Play a hand built clip on a two joint arm and print where each joint ends up
+fn main() {
+    let skin = arm();
+    let player = AnimationPlayer::new(skin.clone(), vec![wave()]);
+    player.play("wave");
+    player.set_looping(false);
+    while player.time() < 2.0 {
+        player.advance(STEP);
+        let matrices = player.bone_matrices();
+        for (joint, matrix) in skin.joints.iter().zip(matrices) {
+            let (scale, rotation, translation) = matrix.to_scale_rotation_translation();
+            let (axis, angle) = rotation.to_axis_angle();
+            println!(
+                "{:.2}s {}: moved by {translation}, turned {:.1} degrees around {axis}, scaled by {scale}",
+                player.time(),
+                joint.name,
+                angle.to_degrees()
+            );
+        }
+    }
+}
@@ -0,0 +1,102 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use pulsar::{
+    app::{Application, UserEvent},
+    config::ApplicationOptions,
+    metrics,
+};
+use std::{error::Error, path::PathBuf};
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    window::WindowId,
+};
+
+/// Frames drawn before the draw calls are checked.
+const FRAMES: u64 = 10;
+
+/// Opens a window without the demo scene nor the reference grid, which must then draw nothing.
+struct Host {
+    app: Application,
+    config_path: PathBuf,
+}
+
+impl Host {
+    /// Whether enough frames were drawn, and if so whether none of them drew a mesh.
+    fn check_empty(&self) -> Option<Result<(), String>> {
+        let snapshot = *metrics::snapshots().first()?;
+        if snapshot.counters.frames < FRAMES {
+            return None;
+        }
+        Some(match snapshot.counters.draw_calls {
+            0 => Ok(()),
+            draw_calls => Err(format!(
+                "{draw_calls} draw calls in {} frames of an empty scene",
+                snapshot.counters.frames
+            )),
+        })
+    }
+}
+
+impl ApplicationHandler<UserEvent> for Host {
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        self.app.user_event(event_loop, event);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        self.app.window_event(event_loop, window_id, event);
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.resumed(event_loop);
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.about_to_wait(event_loop);
+        let Some(result) = self.check_empty() else {
+            return;
+        };
+        match result {
+            Ok(()) => {
+                let phases = metrics::startup_phases();
+                if phases.iter().all(|(label, _)| *label != "meshes") {
+                    eprintln!("The window's startup phases were not recorded: {phases:?}");
+                }
+                for (label, elapsed) in phases {
+                    println!("{label}: {elapsed:?}");
+                }
+                println!("The renderer started with no meshes");
+            }
+            Err(error) => eprintln!("{error}"),
+        }
+        let _ = std::fs::remove_file(&self.config_path);
+        event_loop.exit();
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.exiting(event_loop);
+    }
+}
+
+// Start a renderer with nothing to draw and print its phases
+fn main() -> Result<(), Box<dyn Error>> {
+    let config_path = std::env::temp_dir().join("pulsar_startup.toml");
+    std::fs::write(&config_path, "[graphics]\nreference_grid = false\n")?;
+    let options = ApplicationOptions {
+        config_path: Some(config_path.clone()),
+        demo_scene: Some(false),
+        ..Default::default()
+    };
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut host = Host {
+        app: Application::with_options(&event_loop, options)?,
+        config_path,
+    };
+    event_loop.run_app(&mut host).map_err(Into::into)
+}
@@ -0,0 +1,65 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use pulsar::{
+    app::{Application, UserEvent},
+    error::PulsarError,
+    renderer::NullRendererFactory,
+};
+use std::error::Error;
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    window::WindowId,
+};
+
+/// Opens windows without rendering when the display cannot be presented to.
+struct Viewer {
+    app: Application,
+}
+
+impl ApplicationHandler<UserEvent> for Viewer {
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        self.app.user_event(event_loop, event);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        self.app.window_event(event_loop, window_id, event);
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.resumed(event_loop);
+        let Some(error) = self.app.window_manager.take_startup_error() else {
+            return;
+        };
+        match error.downcast_ref::<PulsarError>() {
+            Some(PulsarError::SurfaceUnsupported { reason }) => {
+                println!("Nothing to render to ({reason}), continuing headless");
+                let window_manager = &mut self.app.window_manager;
+                window_manager.set_renderer_factory(Box::new(NullRendererFactory));
+                if let Err(error) = window_manager.create_window(event_loop, None) {
+                    eprintln!("No window either: {error}");
+                }
+            }
+            _ => eprintln!("Startup failed: {error}"),
+        }
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.exiting(event_loop);
+    }
+}
+
+// Open a window, headless if the display cannot be presented to
+fn main() -> Result<(), Box<dyn Error>> {
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut viewer = Viewer {
+        app: Application::new(&event_loop)?,
+    };
+    event_loop.run_app(&mut viewer).map_err(Into::into)
+}
@@ -0,0 +1,29 @@
+use glam::Vec2;
+use pulsar::model::Mesh;
+
+// Generate normals and tangents for normal mapping and print the basis of a few vertices
+fn main() {
+    let mut cube = Mesh::cube(1.0, [1.0; 4]);
+    cube.generate_normals(true);
+    cube.generate_tangents();
+
+    let mut sphere = Mesh::uv_sphere(1.0, 32, 16, [1.0; 4]);
+    sphere.generate_normals(true);
+    sphere.generate_tangents();
+
+    // A unit quad's UVs run along +X and down -Y, its tangent is [1, 0, 0, -1]
+    let mut quad = Mesh::quad(Vec2::ONE, [1.0; 4]);
+    quad.generate_tangents();
+
+    for (name, mesh) in [("cube", &cube), ("sphere", &sphere), ("quad", &quad)] {
+        println!("{name}: {} vertices", mesh.vertices.len());
+        for vertex in mesh.vertices.iter().take(3) {
+            println!(
+                "  position {:?} normal {:?} tangent {:?}",
+                &vertex.pos[..3],
+                vertex.normal,
+                vertex.tangent
+            );
+        }
+    }
+}
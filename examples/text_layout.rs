@@ -0,0 +1,66 @@
+use glam::Vec2;
+use pulsar::text::{FontMetrics, FontStack, TextLayout};
+use std::sync::Arc;
+
+const PX: f32 = 10.0;
+
+/// Half an em per ASCII character.
+struct Latin;
+
+impl FontMetrics for Latin {
+    fn advance(&self, c: char) -> Option<f32> {
+        c.is_ascii().then_some(0.5)
+    }
+    fn ascender(&self) -> f32 {
+        0.8
+    }
+    fn descender(&self) -> f32 {
+        -0.2
+    }
+    fn line_gap(&self) -> f32 {
+        0.2
+    }
+}
+
+/// Full width ideographs only.
+struct Ideographs;
+
+impl FontMetrics for Ideographs {
+    fn advance(&self, c: char) -> Option<f32> {
+        ('\u{4E00}'..='\u{9FFF}').contains(&c).then_some(1.0)
+    }
+    fn ascender(&self) -> f32 {
+        0.9
+    }
+    fn descender(&self) -> f32 {
+        -0.1
+    }
+    fn line_gap(&self) -> f32 {
+        0.0
+    }
+}
+
+// Lay out text with synthetic fonts and print its lines, glyph fonts and carets
+fn main() {
+    let fonts = FontStack::new(Arc::new(Latin)).with_fallback(Arc::new(Ideographs));
+    let layout = TextLayout::new(&fonts, "hello 中文 world\nsecond line", PX, Some(40.0));
+    for line in &layout.metrics.lines {
+        println!(
+            "{:?}: {} px wide",
+            &layout.text[line.start..line.end],
+            line.width
+        );
+    }
+    let fonts_used: Vec<usize> = layout
+        .metrics
+        .glyphs
+        .iter()
+        .map(|glyph| glyph.font)
+        .collect();
+    println!("Fonts per glyph: {fonts_used:?}");
+    println!(
+        "Caret at the end: {}, hit at (12, 3): {}",
+        layout.caret_position(layout.text.len()),
+        layout.index_from_point(Vec2::new(12.0, 3.0))
+    );
+}
@@ -0,0 +1,140 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use pulsar::{
+    app::{Application, UserEvent},
+    config::ApplicationOptions,
+    error::PulsarError,
+    model::MeshUpdate,
+    texture::{SamplerDesc, TextureArrayDesc},
+    MeshHandle,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+};
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    window::WindowId,
+};
+
+const LAYER_SIZE: u32 = 16;
+/// Checkerboards of these colors, one per layer.
+const COLORS: [[u8; 4]; 4] = [
+    [230, 60, 60, 255],
+    [60, 200, 90, 255],
+    [70, 110, 230, 255],
+    [240, 200, 40, 255],
+];
+
+/// A `LAYER_SIZE` square checkerboard of `color` and white, in 4 by 4 texel cells.
+fn checkerboard(color: [u8; 4]) -> Vec<u8> {
+    (0..LAYER_SIZE * LAYER_SIZE)
+        .flat_map(|texel| {
+            let (x, y) = (texel % LAYER_SIZE, texel / LAYER_SIZE);
+            if (x / 4 + y / 4) % 2 == 0 {
+                color
+            } else {
+                [255; 4]
+            }
+        })
+        .collect()
+}
+
+/// Gives every window a texture array of the checkerboards, each click moves the selected mesh to
+/// the next layer.
+struct Layers {
+    app: Application,
+    created: HashSet<WindowId>,
+    /// Mesh selected last and the layer it samples.
+    selected: HashMap<WindowId, (MeshHandle, u32)>,
+}
+
+impl ApplicationHandler<UserEvent> for Layers {
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        self.app.user_event(event_loop, event);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        let clicked = matches!(
+            event,
+            WindowEvent::MouseInput {
+                state: winit::event::ElementState::Released,
+                ..
+            }
+        );
+        self.app.window_event(event_loop, window_id, event);
+        if !clicked {
+            return;
+        }
+        let Some(mesh) = self.app.selected_mesh(window_id) else {
+            return;
+        };
+        let layer = match self.selected.get(&window_id) {
+            Some(&(selected, layer)) if selected == mesh => (layer + 1) % COLORS.len() as u32,
+            _ => 0,
+        };
+        self.selected.insert(window_id, (mesh, layer));
+        self.app
+            .update_mesh(window_id, mesh, MeshUpdate::TextureLayer(Some(layer)));
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.resumed(event_loop);
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.about_to_wait(event_loop);
+        let windows: Vec<WindowId> = self.app.window_manager.window_ids().collect();
+        for window_id in windows {
+            if !self.created.insert(window_id) {
+                continue;
+            }
+            self.app.create_texture_array(
+                window_id,
+                TextureArrayDesc {
+                    width: LAYER_SIZE,
+                    height: LAYER_SIZE,
+                    layers: COLORS.len() as u32,
+                    sampler: SamplerDesc::nearest(),
+                },
+            );
+            for color in COLORS {
+                self.app.push_texture_layer(window_id, checkerboard(color));
+            }
+        }
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.exiting(event_loop);
+    }
+}
+
+// Click the demo scene's meshes to cycle them through the layers of one texture array
+fn main() -> Result<(), Box<dyn Error>> {
+    let error = PulsarError::TextureArrayLayers {
+        requested: 4096,
+        max: 2048,
+    };
+    println!("Over the limit: {error}");
+
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut layers = Layers {
+        app: Application::with_options(
+            &event_loop,
+            ApplicationOptions {
+                demo_scene: Some(true),
+                ..Default::default()
+            },
+        )?,
+        created: HashSet::new(),
+        selected: HashMap::new(),
+    };
+    event_loop.run_app(&mut layers).map_err(Into::into)
+}
@@ -0,0 +1,100 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use pulsar::{
+    app::{Application, UserEvent},
+    config::ApplicationOptions,
+    error::PulsarError,
+    texture::SamplerDesc,
+};
+use std::{collections::HashSet, error::Error, path::PathBuf};
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    window::WindowId,
+};
+
+/// Shown when no path is given, a grayscale PNG converted to RGBA8 on load.
+const DEFAULT_IMAGE: &str = "assets/img/gradient.png";
+
+/// Loads the image file into every window's texture, in place of the bundled picture.
+struct Viewer {
+    app: Application,
+    path: PathBuf,
+    sampler: SamplerDesc,
+    loaded: HashSet<WindowId>,
+}
+
+impl ApplicationHandler<UserEvent> for Viewer {
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        self.app.user_event(event_loop, event);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        self.app.window_event(event_loop, window_id, event);
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.resumed(event_loop);
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.about_to_wait(event_loop);
+        let windows: Vec<WindowId> = self.app.window_manager.window_ids().collect();
+        for window_id in windows {
+            if self.loaded.insert(window_id) {
+                self.app
+                    .load_texture_with_sampler(window_id, self.path.clone(), self.sampler);
+            }
+        }
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.exiting(event_loop);
+    }
+}
+
+// Show the image file given as the first argument on the demo scene's covers, `--nearest` keeps
+// its texels crisp as pixel art
+fn main() -> Result<(), Box<dyn Error>> {
+    let (flags, paths): (Vec<_>, Vec<_>) = std::env::args_os()
+        .skip(1)
+        .partition(|arg| arg == "--nearest");
+    let path = paths
+        .into_iter()
+        .next()
+        .map_or_else(|| PathBuf::from(DEFAULT_IMAGE), PathBuf::from);
+    let sampler = if flags.is_empty() {
+        SamplerDesc::default()
+    } else {
+        SamplerDesc::nearest()
+    };
+    // The same failures the render thread logs when the file is missing or not an image
+    if let Err(err) = image::open(&path) {
+        let error = PulsarError::TextureLoad {
+            path,
+            reason: err.to_string(),
+        };
+        return Err(error.into());
+    }
+
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut viewer = Viewer {
+        app: Application::with_options(
+            &event_loop,
+            ApplicationOptions {
+                demo_scene: Some(true),
+                ..Default::default()
+            },
+        )?,
+        path,
+        sampler,
+        loaded: HashSet::new(),
+    };
+    event_loop.run_app(&mut viewer).map_err(Into::into)
+}
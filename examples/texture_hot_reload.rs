@@ -0,0 +1,97 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use pulsar::{
+    app::{Application, UserEvent},
+    config::ApplicationOptions,
+};
+use std::{
+    collections::HashSet,
+    error::Error,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    window::WindowId,
+};
+
+/// Colors the texture cycles through in the window.
+const COLORS: [[u8; 4]; 3] = [[230, 60, 60, 255], [60, 200, 90, 255], [70, 110, 230, 255]];
+
+fn save_texture(path: &Path, color: [u8; 4]) -> Result<(), Box<dyn Error>> {
+    let texels: Vec<u8> = (0..16 * 16).flat_map(|_| color).collect();
+    image::save_buffer(path, &texels, 16, 16, image::ColorType::Rgba8)?;
+    Ok(())
+}
+
+/// Loads the texture into every window, then the thread below keeps rewriting it.
+struct Reloading {
+    app: Application,
+    path: PathBuf,
+    loaded: HashSet<WindowId>,
+}
+
+impl ApplicationHandler<UserEvent> for Reloading {
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        self.app.user_event(event_loop, event);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        self.app.window_event(event_loop, window_id, event);
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.resumed(event_loop);
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.about_to_wait(event_loop);
+        let windows: Vec<WindowId> = self.app.window_manager.window_ids().collect();
+        for window_id in windows {
+            if self.loaded.insert(window_id) {
+                self.app.load_texture(window_id, self.path.clone());
+            }
+        }
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.exiting(event_loop);
+    }
+}
+
+// The demo scene's texture changes color every two seconds as its file is rewritten
+fn main() -> Result<(), Box<dyn Error>> {
+    let path = std::env::temp_dir().join("pulsar_hot_reload.png");
+    save_texture(&path, COLORS[0])?;
+    let rewritten = path.clone();
+    thread::spawn(move || {
+        for color in COLORS.iter().cycle().skip(1) {
+            thread::sleep(Duration::from_secs(2));
+            if let Err(err) = save_texture(&rewritten, *color) {
+                eprintln!("Failed to rewrite {}: {err}", rewritten.display());
+            }
+        }
+    });
+
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut reloading = Reloading {
+        app: Application::with_options(
+            &event_loop,
+            ApplicationOptions {
+                demo_scene: Some(true),
+                ..Default::default()
+            },
+        )?,
+        path,
+        loaded: HashSet::new(),
+    };
+    event_loop.run_app(&mut reloading).map_err(Into::into)
+}
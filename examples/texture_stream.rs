@@ -0,0 +1,119 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use pulsar::{
+    app::{Application, UserEvent},
+    texture::{TextureRegion, TextureUpdate, TEXEL_SIZE},
+};
+use std::{
+    error::Error,
+    time::{Duration, Instant},
+};
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    window::WindowId,
+};
+
+const SIZE: u32 = 256;
+const FRAME_INTERVAL: Duration = Duration::from_nanos(1_000_000_000 / 60);
+/// Rows of the pattern buffer are padded to this many texels, like a decoder's output.
+const PADDED_WIDTH: u32 = SIZE + 64;
+
+/// Plays a procedural pattern into the texture of every window. Most frames update the whole
+/// pattern, every fourth one only a band of it through a padded source.
+struct Player {
+    app: Application,
+    frame: u32,
+    next_frame: Instant,
+}
+
+impl Player {
+    fn post_frame(&mut self) -> Result<(), Box<dyn Error>> {
+        let pixels = pattern(self.frame);
+        let update = if self.frame % 4 == 3 {
+            let band = SIZE / 4;
+            let y = (self.frame / 4 % 4) * band;
+            let row_pitch = PADDED_WIDTH * TEXEL_SIZE;
+            let start = (y * row_pitch) as usize;
+            TextureUpdate::with_row_pitch(
+                TextureRegion {
+                    x: 0,
+                    y,
+                    width: SIZE,
+                    height: band,
+                },
+                pixels[start..].to_vec(),
+                row_pitch,
+            )?
+        } else {
+            TextureUpdate::with_row_pitch(
+                TextureRegion::full(SIZE, SIZE),
+                pixels,
+                PADDED_WIDTH * TEXEL_SIZE,
+            )?
+        };
+        let windows: Vec<WindowId> = self.app.window_manager.window_ids().collect();
+        for window_id in windows {
+            self.app.update_texture(window_id, update.clone());
+        }
+        self.frame += 1;
+        Ok(())
+    }
+}
+
+/// Moving diagonal stripes, rows padded to `PADDED_WIDTH` texels.
+fn pattern(frame: u32) -> Vec<u8> {
+    let mut pixels = vec![0; (PADDED_WIDTH * SIZE * TEXEL_SIZE) as usize];
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let stripe = ((x + y + frame * 2) / 16 % 2) as u8;
+            let offset = ((y * PADDED_WIDTH + x) * TEXEL_SIZE) as usize;
+            pixels[offset..offset + 4].copy_from_slice(&[x as u8, y as u8, stripe * 255, 255]);
+        }
+    }
+    pixels
+}
+
+impl ApplicationHandler<UserEvent> for Player {
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        self.app.user_event(event_loop, event);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        self.app.window_event(event_loop, window_id, event);
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.resumed(event_loop);
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.about_to_wait(event_loop);
+        if Instant::now() >= self.next_frame {
+            self.post_frame().expect("Invalid texture update");
+            self.next_frame += FRAME_INTERVAL;
+        }
+        event_loop.set_control_flow(ControlFlow::WaitUntil(self.next_frame));
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.exiting(event_loop);
+    }
+}
+
+// Stream a 256x256 pattern at 60 fps into the window texture
+fn main() -> Result<(), Box<dyn Error>> {
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut player = Player {
+        app: Application::new(&event_loop)?,
+        frame: 0,
+        next_frame: Instant::now(),
+    };
+    event_loop.run_app(&mut player).map_err(Into::into)
+}
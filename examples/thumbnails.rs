@@ -0,0 +1,125 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use pulsar::{
+    app::{Application, UserEvent},
+    material::Material,
+    texture::{TextureRegion, TextureUpdate},
+    thumbnail::{Thumbnail, ThumbnailTarget},
+};
+use std::{
+    error::Error,
+    time::{Duration, Instant},
+};
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    window::WindowId,
+};
+
+const THUMBNAIL_SIZE: u32 = 96;
+const PATCH: u32 = 64;
+const UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Keeps a thumbnail of the texture and of the default material per window, and repaints a
+/// corner of the texture every second so its thumbnail is rendered again.
+struct Outliner {
+    app: Application,
+    thumbnails: Vec<(Thumbnail, Option<u32>)>,
+    next_update: Instant,
+    updates: u32,
+}
+
+impl Outliner {
+    fn paint(&mut self) {
+        let shade = (self.updates * 60 % 256) as u8;
+        let rgba = [shade, 255 - shade, 128, 255].repeat((PATCH * PATCH) as usize);
+        let update = TextureUpdate::new(TextureRegion::full(PATCH, PATCH), rgba)
+            .expect("The patch matches its region");
+        let windows: Vec<WindowId> = self.app.window_manager.window_ids().collect();
+        for window_id in windows {
+            self.app.update_texture(window_id, update.clone());
+        }
+        self.updates += 1;
+    }
+}
+
+impl ApplicationHandler<UserEvent> for Outliner {
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        self.app.user_event(event_loop, event);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        self.app.window_event(event_loop, window_id, event);
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.resumed(event_loop);
+        if !self.thumbnails.is_empty() {
+            return;
+        }
+        self.paint();
+        let windows: Vec<WindowId> = self.app.window_manager.window_ids().collect();
+        for window_id in windows {
+            for target in [
+                ThumbnailTarget::Texture,
+                ThumbnailTarget::Material(Material::default()),
+            ] {
+                if let Some(thumbnail) =
+                    self.app
+                        .request_thumbnail(window_id, target, THUMBNAIL_SIZE)
+                {
+                    self.thumbnails.push((thumbnail, None));
+                }
+            }
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.about_to_wait(event_loop);
+        if Instant::now() >= self.next_update {
+            self.paint();
+            self.next_update = Instant::now() + UPDATE_INTERVAL;
+        }
+        for (thumbnail, shown) in &mut self.thumbnails {
+            let Some(image) = thumbnail.image() else {
+                continue;
+            };
+            if *shown != Some(image.generation) {
+                *shown = Some(image.generation);
+                println!(
+                    "{:?} thumbnail {} rendered {}x{}, {} bytes",
+                    thumbnail.target(),
+                    image.generation,
+                    image.size,
+                    image.size,
+                    image.rgba.len()
+                );
+            }
+        }
+        event_loop.set_control_flow(ControlFlow::WaitUntil(
+            Instant::now() + Duration::from_millis(100),
+        ));
+    }
+
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.app.exiting(event_loop);
+    }
+}
+
+// Show thumbnails of the texture and of the default material updating as the texture is repainted
+fn main() -> Result<(), Box<dyn Error>> {
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut outliner = Outliner {
+        app: Application::new(&event_loop)?,
+        thumbnails: Vec::new(),
+        next_update: Instant::now() + UPDATE_INTERVAL,
+        updates: 0,
+    };
+    event_loop.run_app(&mut outliner).map_err(Into::into)
+}
@@ -0,0 +1,93 @@
+use glam::{Mat4, Quat, Vec3};
+use pulsar::{
+    skinning::{AnimationClip, AnimationPlayer, Channel, Interpolation, Joint, Keyframes, Skin},
+    time_control::{SimulationClock, TimeControl, FIXED_TICK, MAX_TIME_SCALE, MIN_TIME_SCALE},
+};
+use std::time::Duration;
+
+const TICKS: u32 = 150;
+
+/// A single joint sliding back and forth along +X, looping every 1.7 seconds.
+fn slider() -> AnimationPlayer {
+    let skin = Skin {
+        joints: vec![Joint {
+            name: "root".into(),
+            parent: None,
+            origin: Mat4::IDENTITY,
+            translation: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            scale: Vec3::ONE,
+            inverse_bind: Mat4::IDENTITY,
+        }],
+    };
+    let clip = AnimationClip::new(
+        "slide",
+        vec![Channel {
+            joint: 0,
+            interpolation: Interpolation::Linear,
+            keyframes: Keyframes::Translation(vec![
+                (0.0, Vec3::ZERO),
+                (0.85, 3.0 * Vec3::X),
+                (1.7, Vec3::ZERO),
+            ]),
+        }],
+    );
+    let player = AnimationPlayer::new(skin, vec![clip]);
+    player.play("slide");
+    player.set_looping(true);
+    player
+}
+
+/// Advance `player` as the render thread does, one fixed tick at a time.
+fn run(clock: &mut SimulationClock, player: &AnimationPlayer, real_delta: Duration) -> u32 {
+    let ticks = clock.advance(real_delta);
+    for _ in 0..ticks {
+        player.advance(FIXED_TICK.as_secs_f32());
+    }
+    ticks
+}
+
+/// Uneven frame times, as a busy frame rate gives.
+fn jittery_frame(frame: u32) -> Duration {
+    Duration::from_micros(9_000 + (frame * 7_919) as u64 % 15_000)
+}
+
+// Drive an animation through the simulation clock, running, paused, stepped and scaled, and print
+// where it gets to
+fn main() {
+    let player = slider();
+    let position = |player: &AnimationPlayer| player.bone_matrices()[0].w_axis.x;
+    let mut clock = SimulationClock::default();
+    for frame in 0..TICKS {
+        run(&mut clock, &player, jittery_frame(frame));
+    }
+    println!(
+        "Running: {} ticks, joint at x {:.3}",
+        clock.ticks(),
+        position(&player)
+    );
+
+    clock.set_control(TimeControl::default().with_paused(true));
+    for frame in 0..TICKS {
+        run(&mut clock, &player, jittery_frame(frame));
+    }
+    println!("Paused for {TICKS} frames: {} ticks", clock.ticks());
+    for _ in 0..10 {
+        clock.step_once();
+        run(&mut clock, &player, FIXED_TICK);
+    }
+    println!(
+        "Stepped 10 times: {} ticks, joint at x {:.3}",
+        clock.ticks(),
+        position(&player)
+    );
+
+    for scale in [MIN_TIME_SCALE, 1.0, MAX_TIME_SCALE] {
+        let mut clock = SimulationClock::default();
+        clock.set_control(TimeControl::default().with_scale(scale));
+        for frame in 0..TICKS {
+            run(&mut clock, &player, jittery_frame(frame));
+        }
+        println!("At {scale}x {TICKS} frames ran {} ticks", clock.ticks());
+    }
+}
@@ -0,0 +1,53 @@
+use pulsar::{
+    screenshot::{screenshot_passes, SCREENSHOT_ATTACHMENTS},
+    transient::{self, TransientImage},
+};
+use std::error::Error;
+
+/// Bytes of a 1920x1080 attachment with `texel` bytes per texel and `samples` samples.
+fn attachment_size(texel: u64, samples: u64) -> u64 {
+    1920 * 1080 * texel * samples
+}
+
+// Plan the attachments of a 1080p screenshot with 4x MSAA converted to another color space and
+// print where each one lands. Renderers count what they alias in `metrics::aliasing_stats`.
+fn main() -> Result<(), Box<dyn Error>> {
+    let lifetimes = screenshot_passes(true).lifetimes(SCREENSHOT_ATTACHMENTS)?;
+    // Depth, multisampled color, readback, export
+    let sizes = [
+        attachment_size(4, 4),
+        attachment_size(4, 4),
+        attachment_size(4, 1),
+        attachment_size(8, 1),
+    ];
+    let images: Vec<TransientImage> = lifetimes
+        .iter()
+        .zip(sizes)
+        .filter_map(|(lifetime, size)| {
+            Some(TransientImage {
+                size,
+                alignment: 65536,
+                memory_type_bits: 0b1,
+                lifetime: (*lifetime)?,
+            })
+        })
+        .collect();
+    let plan = transient::plan(&images);
+    for (index, (placement, image)) in plan.placements.iter().zip(&plan.images).enumerate() {
+        println!(
+            "Attachment {index}, {} bytes over passes {}..={}: block {} at {}",
+            image.size,
+            image.lifetime.first,
+            image.lifetime.last,
+            placement.block,
+            placement.offset
+        );
+    }
+    println!(
+        "{} bytes allocated for {}, {} saved",
+        plan.allocated_bytes(),
+        plan.requested_bytes(),
+        plan.bytes_saved()
+    );
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use glam::Mat4;
+use pulsar::{
+    model::MeshUpdate,
+    testing::{mesh_handle, SeededRng},
+    update_queue::{Backpressure, UpdateQueue},
+    MeshHandle,
+};
+use std::error::Error;
+
+/// Meshes the updates are spread over.
+const MESHES: usize = 5;
+const SEEDS: u64 = 10;
+
+fn random_update(rng: &mut SeededRng) -> (MeshHandle, MeshUpdate) {
+    let mesh = mesh_handle(rng.below(MESHES as u64) as usize, 0);
+    let value = rng.below(4) as u32;
+    let update = match rng.below(4) {
+        0 => MeshUpdate::Transform(Mat4::from_scale(glam::Vec3::splat(value as f32))),
+        1 => MeshUpdate::Parent((value == 0).then(|| mesh_handle(0, 0))),
+        2 => MeshUpdate::Layers(value),
+        _ => MeshUpdate::Blend(value < 2),
+    };
+    (mesh, update)
+}
+
+// Post random sequences of mesh updates and print how many the queue kept once merged. Counters
+// reach `MetricsSnapshot::counters` and the scrape endpoint when a window runs.
+fn main() -> Result<(), Box<dyn Error>> {
+    for seed in 0..SEEDS {
+        let mut rng = SeededRng::new(seed);
+        let mut queue = UpdateQueue::new(usize::MAX, Backpressure::Reject);
+        for _ in 0..1 + rng.below(200) {
+            let (mesh, update) = random_update(&mut rng);
+            queue.try_post(mesh, update)?;
+        }
+        let drained = queue.drain();
+        println!(
+            "Seed {seed}: {} updates queued, {} merged away",
+            drained.len(),
+            queue.counters.coalesced
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use pulsar::watchdog::{Heartbeat, RenderStage, WaitSite, Watchdog, WATCHDOG_POLL_INTERVAL};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+const THRESHOLD: Duration = Duration::from_millis(200);
+const RUN: Duration = Duration::from_secs(3);
+
+// Stall a fake render thread on its draw fence once a second and print what the watchdog reports
+fn main() {
+    env_logger::init();
+    let heartbeat = Arc::new(Heartbeat::default());
+    let stop = Arc::new(AtomicBool::new(false));
+    let render_thread = {
+        let heartbeat = heartbeat.clone();
+        let stop = stop.clone();
+        thread::spawn(move || {
+            let start = Instant::now();
+            let mut frame = 0;
+            while !stop.load(Ordering::Relaxed) {
+                heartbeat.beat(frame);
+                heartbeat.enter(RenderStage::Record);
+                let _waiting = heartbeat.waiting(WaitSite::DrawFence);
+                if frame % 60 == 59 {
+                    thread::sleep(THRESHOLD * 3);
+                }
+                thread::sleep(Duration::from_millis(16));
+                frame += 1;
+            }
+            println!("Rendered {frame} frames in {:?}", start.elapsed());
+            heartbeat.park();
+        })
+    };
+
+    let mut watchdog = Watchdog::new(THRESHOLD);
+    if !watchdog.enabled() {
+        println!("Watchdog disabled by the environment");
+    }
+    let start = Instant::now();
+    while start.elapsed() < RUN {
+        thread::sleep(WATCHDOG_POLL_INTERVAL / 5);
+        if let Some(report) = watchdog.check(&heartbeat) {
+            println!("Detected: {report}");
+        }
+    }
+    stop.store(true, Ordering::Relaxed);
+    render_thread.join().unwrap();
+}
@@ -0,0 +1,137 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use pulsar::{
+    app::UserEvent,
+    config::WindowConfig,
+    display::DisplayEnvironment,
+    input_manager::EventStates,
+    renderer::{NullRenderer, RendererFactory, WindowRenderer},
+    window_manager::WindowManager,
+};
+use std::{cell::Cell, error::Error, rc::Rc, sync::Arc};
+use winit::{
+    application::ApplicationHandler,
+    dpi::PhysicalPosition,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    window::{Window, WindowId},
+};
+
+/// Null renderers counting how often they are shut down.
+struct CountingFactory {
+    shutdowns: Rc<Cell<u32>>,
+}
+
+struct CountingRenderer {
+    renderer: NullRenderer,
+    shutdowns: Rc<Cell<u32>>,
+}
+
+impl WindowRenderer for CountingRenderer {
+    fn resize(&mut self, display: DisplayEnvironment) {
+        self.renderer.resize(display);
+    }
+
+    fn render(&mut self) {
+        self.renderer.render();
+    }
+
+    fn shutdown(&mut self) {
+        self.renderer.shutdown();
+        self.shutdowns.set(self.shutdowns.get() + 1);
+    }
+}
+
+impl RendererFactory for CountingFactory {
+    fn create_surface_renderer(
+        &mut self,
+        _window: &Arc<Window>,
+        _event_states: Arc<EventStates>,
+        display: DisplayEnvironment,
+    ) -> Result<Box<dyn WindowRenderer>, Box<dyn Error>> {
+        Ok(Box::new(CountingRenderer {
+            renderer: NullRenderer {
+                display: Some(display),
+                rendering: false,
+            },
+            shutdowns: self.shutdowns.clone(),
+        }))
+    }
+}
+
+/// Destroys a window the way some X11 window managers do, without a close request first.
+struct Host {
+    window_manager: WindowManager,
+    shutdowns: Rc<Cell<u32>>,
+}
+
+impl Host {
+    fn check_destroyed(&mut self, event_loop: &ActiveEventLoop) -> Result<(), String> {
+        let window_id = self
+            .window_manager
+            .create_window(event_loop, None)
+            .map_err(|error| error.to_string())?;
+        // Moves are handled before and after, the last one lands far off any monitor
+        for position in [(10, 10), (12, 14), (-100_000, -100_000)] {
+            let moved = WindowEvent::Moved(PhysicalPosition::new(position.0, position.1));
+            self.window_manager
+                .window_event(event_loop, window_id, moved);
+        }
+        self.window_manager
+            .window_event(event_loop, window_id, WindowEvent::Destroyed);
+        if self.window_manager.window_count() != 0 || self.shutdowns.get() != 1 {
+            return Err(format!(
+                "{} windows left, {} shutdowns after the destruction",
+                self.window_manager.window_count(),
+                self.shutdowns.get()
+            ));
+        }
+        // Late events of the dead window change nothing
+        for event in [WindowEvent::CloseRequested, WindowEvent::Destroyed] {
+            self.window_manager
+                .window_event(event_loop, window_id, event);
+        }
+        if !self.window_manager.close_window(window_id) && self.shutdowns.get() == 1 {
+            Ok(())
+        } else {
+            Err("The window was torn down twice".into())
+        }
+    }
+}
+
+impl ApplicationHandler<UserEvent> for Host {
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        self.window_manager
+            .window_event(event_loop, window_id, event);
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        match self.check_destroyed(event_loop) {
+            Ok(()) => println!("A destroyed window shut its renderer down once"),
+            Err(error) => eprintln!("{error}"),
+        }
+        event_loop.exit();
+    }
+}
+
+// Destroy a window without closing it and check its renderer is shut down once
+fn main() -> Result<(), Box<dyn Error>> {
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let shutdowns = Rc::new(Cell::new(0));
+    let mut host = Host {
+        window_manager: WindowManager::new(
+            &event_loop,
+            WindowConfig::default(),
+            Box::new(CountingFactory {
+                shutdowns: shutdowns.clone(),
+            }),
+        ),
+        shutdowns,
+    };
+    event_loop.run_app(&mut host).map_err(Into::into)
+}
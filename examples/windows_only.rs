@@ -0,0 +1,20 @@
+#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+
+use pulsar::{
+    app::UserEvent, config::WindowConfig, renderer::NullRendererFactory,
+    window_manager::WindowManager,
+};
+use std::error::Error;
+use winit::event_loop::EventLoop;
+
+// Window management and bindings without Vulkan
+fn main() -> Result<(), Box<dyn Error>> {
+    env_logger::init();
+    let event_loop = EventLoop::<UserEvent>::with_user_event().build()?;
+    let mut window_manager = WindowManager::new(
+        &event_loop,
+        WindowConfig::default(),
+        Box::new(NullRendererFactory),
+    );
+    event_loop.run_app(&mut window_manager).map_err(Into::into)
+}
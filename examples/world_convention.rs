@@ -0,0 +1,25 @@
+use glam::Vec3;
+use pulsar::world::WorldConvention;
+
+// Print the axes of both conventions and where a camera behind the origin sees them
+fn main() {
+    let conventions = [WorldConvention::YUp, WorldConvention::ZUp];
+    for convention in conventions {
+        let view = convention.look_at(-convention.forward() * 4.0, Vec3::ZERO);
+        println!(
+            "{convention:?}: up {} forward {} right {}, up on screen {}",
+            convention.up(),
+            convention.forward(),
+            convention.right(),
+            view.transform_vector3(convention.up())
+        );
+        for target in conventions {
+            println!(
+                "  to {target:?}: {}",
+                convention
+                    .conversion_to(target)
+                    .transform_vector3(convention.up())
+            );
+        }
+    }
+}
@@ -1,572 +1,744 @@
-use crate::shaders::Shader;
-#[cfg(debug_assertions)]
-use crate::vulkan::debug_callback::DebugUtils;
-use crate::vulkan::AAABase;
-use crate::window_state::WindowState;
-use ash::vk::PhysicalDevice;
-use ash::Entry;
-use log::info;
-use rwh_06::HasDisplayHandle;
-use std::collections::HashMap;
+use crate::assets::TextureLibrary;
+use crate::background::{BackgroundMode, BACKGROUND_USER_FLOATS};
+use crate::batching::MeshBatch;
+use crate::block_compression::CompressedTexture;
+use crate::camera::{CameraController, Ortho2DController};
+use crate::color_space::ExportColorSpace;
+use crate::compute::VertexDeformer;
+use crate::config::{ApplicationOptions, GraphicsConfig, PulsarConfig};
+use crate::custom_pass::{CustomPass, CustomPassSlot};
+use crate::debug_lines::DebugLines;
+use crate::error::PulsarError;
+use crate::failure::{Failure, RetryCallback};
+use crate::handles::{MeshHandle, TextureId};
+use crate::inset::InsetView;
+use crate::instancing::Instances;
+use crate::loader::{self, LoadHandle};
+use crate::material::{DepthBias, Material, PipelineOptions};
+use crate::model::{Mesh, MeshSpace, MeshUpdate};
+use crate::object_audit::{self, AuditDiff, ObjectAudit};
+use crate::present_health::{PresentDowngrade, PresentModePreference};
+use crate::residency::ResidencyPriority;
+use crate::skinning::AnimationPlayer;
+use crate::texture::{CubemapSource, SamplerDesc, TextureArrayDesc, TextureUpdate};
+use crate::thumbnail::{Thumbnail, ThumbnailTarget};
+use crate::time_control::TimeControl;
+use crate::vulkan::renderer::AAARendererFactory;
+use crate::watchdog::StallReport;
+use crate::window_manager::WindowManager;
+use glam::Mat4;
 use std::error::Error;
-use std::fmt;
-use std::fmt::Debug;
-use std::sync::Arc;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
 use winit::application::ApplicationHandler;
-use winit::dpi::{PhysicalPosition, PhysicalSize};
-use winit::event::{DeviceEvent, DeviceId, Ime, MouseButton, MouseScrollDelta, WindowEvent};
+use winit::dpi::PhysicalSize;
+use winit::event::{DeviceEvent, DeviceId, WindowEvent};
 use winit::event_loop::{ActiveEventLoop, EventLoop};
-use winit::keyboard::{Key, ModifiersState};
-use winit::window::{CustomCursor, CustomCursorSource, Icon, Window, WindowId};
+use winit::window::WindowId;
 
-const WIN_TITLE: &str = "Pulsar";
+pub const WIN_TITLE: &str = "Pulsar";
 pub const WIN_START_INNER_SIZE: PhysicalSize<u32> = PhysicalSize::new(1280, 720);
 
+/// Window management composed with the Vulkan renderer.
 pub struct Application {
-    pub custom_cursors: Vec<CustomCursor>,
-    icon: Icon,
-    windows: HashMap<WindowId, WindowState>,
-
-    #[cfg(debug_assertions)]
-    _debug_utils: DebugUtils,
-    pub renderer: Arc<AAABase>,
-
-    pub physical_device_list: Vec<PhysicalDevice>,
+    pub window_manager: WindowManager,
+
+    pub config: PulsarConfig,
+    /// Shared with every render thread, updated when the configuration file changes.
+    pub graphics_config: Arc<RwLock<GraphicsConfig>>,
+    #[cfg(feature = "metrics-endpoint")]
+    pub metrics_endpoint: Option<crate::metrics_endpoint::MetricsEndpoint>,
+    /// Decoded textures held once per content, dropped images and glTF textures land here.
+    pub textures: TextureLibrary,
+    /// References taken by the files dropped since [`Self::take_dropped_textures`].
+    dropped_textures: Vec<TextureId>,
+    /// Taken by the last [`Self::audit_objects`].
+    object_audit: Option<ObjectAudit>,
 }
 
 #[derive(Debug, Clone, Copy)]
 pub enum UserEvent {
-    Resize { width: u32, height: u32 },
+    Resize {
+        width: u32,
+        height: u32,
+    },
+    /// The window's render thread produced no frame for longer than `WindowConfig::stall_threshold_secs`.
+    RenderThreadStalled {
+        window_id: WindowId,
+        report: StallReport,
+    },
+    /// MAILBOX kept the window waiting on swapchain images with an idle GPU, presentation fell
+    /// back to FIFO for the rest of the session on this device.
+    PresentModeDowngraded {
+        window_id: WindowId,
+        report: PresentDowngrade,
+    },
+    /// The window's render thread presented its first frame. A window created hidden, see
+    /// `WindowConfig::hidden_until_first_frame`, is shown when the event is handled.
+    FirstFramePresented(WindowId),
 }
 
 impl Application {
     pub fn new<T>(event_loop: &EventLoop<T>) -> Result<Self, Box<dyn Error>> {
+        Self::with_options(event_loop, ApplicationOptions::default())
+    }
+
+    /// Values in `options` take precedence over the configuration file.
+    pub fn with_options<T>(
+        event_loop: &EventLoop<T>,
+        options: ApplicationOptions,
+    ) -> Result<Self, Box<dyn Error>> {
         env_logger::init();
 
-        #[cfg(debug_assertions)]
-        Shader::compile_shaders();
+        let config_path = options
+            .config_path
+            .clone()
+            .unwrap_or_else(PulsarConfig::default_path);
+        let config = PulsarConfig::load(&config_path)?.merged(&options);
+        let graphics_config = Arc::new(RwLock::new(config.graphics));
+        let surface_formats = options.surface_formats.clone();
+        crate::config::watch(
+            config_path,
+            options,
+            config.clone(),
+            graphics_config.clone(),
+        );
+
+        #[cfg(feature = "metrics-endpoint")]
+        let metrics_endpoint = if config.metrics.enabled {
+            Some(crate::metrics_endpoint::MetricsEndpoint::bind(
+                config.metrics.bind.as_str(),
+            )?)
+        } else {
+            None
+        };
+        #[cfg(not(feature = "metrics-endpoint"))]
+        if config.metrics.enabled {
+            log::warn!(
+                "Metrics are enabled but Pulsar was built without the `metrics-endpoint` feature"
+            );
+        }
 
-        // You'll have to choose an icon size at your own discretion. On Windows, you still have to account
-        //  for screen scaling. Here we use 32px, since it seems to work well enough in most cases.
-        // Be careful about going too high, or you'll be bitten by the low-quality downscaling built into the
-        // WM.
-        let icon = load_icon(include_bytes!("../assets/img/icon.png"));
+        let factory = AAARendererFactory::new(event_loop, graphics_config.clone())?
+            .with_surface_formats(surface_formats);
+        let window_manager =
+            WindowManager::new(event_loop, config.window.clone(), Box::new(factory));
 
-        // info!("Loading cursor assets");
-        let custom_cursors = vec![
-            event_loop
-                .create_custom_cursor(decode_cursor(include_bytes!("../assets/img/cross.png"))),
-            event_loop
-                .create_custom_cursor(decode_cursor(include_bytes!("../assets/img/cross2.png"))),
-            event_loop
-                .create_custom_cursor(decode_cursor(include_bytes!("../assets/img/gradient.png"))),
-        ];
+        Ok(Self {
+            window_manager,
+
+            config,
+            graphics_config,
+            #[cfg(feature = "metrics-endpoint")]
+            metrics_endpoint,
+            textures: TextureLibrary::default(),
+            dropped_textures: Vec::new(),
+            object_audit: None,
+        })
+    }
 
-        let entry = Entry::linked();
+    /// Textures of the files dropped on the windows, each a reference in [`Self::textures`] to
+    /// release once done with.
+    pub fn take_dropped_textures(&mut self) -> Vec<TextureId> {
+        std::mem::take(&mut self.dropped_textures)
+    }
 
-        let instance =
-            crate::vulkan::instance::create_instance(&entry, event_loop.display_handle().unwrap())?;
+    /// Route a file dropped on `window_id` through the asset libraries: images become textures,
+    /// the meshes of OBJ and glTF models are added to the window.
+    #[cfg(any(feature = "obj", feature = "images"))]
+    fn load_dropped(
+        &mut self,
+        window_id: WindowId,
+        path: &std::path::Path,
+    ) -> Result<(), Box<dyn Error>> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match extension.as_str() {
+            #[cfg(feature = "obj")]
+            "obj" => {
+                for mesh in crate::model::Model::from_obj(path)?.meshes {
+                    self.add_mesh(window_id, mesh);
+                }
+            }
+            #[cfg(feature = "gltf")]
+            "gltf" | "glb" => {
+                let scene = crate::model::Scene::from_gltf(path)?;
+                for texture in scene.textures {
+                    let texture = self.textures.insert(texture);
+                    self.dropped_textures.push(texture);
+                }
+                for mesh in scene.models.into_iter().flat_map(|model| model.meshes) {
+                    self.add_mesh(window_id, mesh);
+                }
+            }
+            #[cfg(feature = "images")]
+            _ if image::ImageFormat::from_path(path).is_ok() => {
+                let texture = self.textures.load(path)?;
+                log::info!(
+                    "{} loaded as {texture:?}, {} references",
+                    path.display(),
+                    self.textures.references(texture)
+                );
+                self.dropped_textures.push(texture);
+            }
+            _ => return Err(format!("{}: not a model nor an image", path.display()).into()),
+        }
+        Ok(())
+    }
 
-        let surface_loader = ash::khr::surface::Instance::new(&entry, &instance);
+    /// Live Vulkan objects of every window per type, and the `oldest` of them. Empty without the
+    /// `object-audit` feature.
+    pub fn object_audit(&self, oldest: usize) -> ObjectAudit {
+        object_audit::audit(oldest)
+    }
 
-        #[cfg(debug_assertions)]
-        let _debug_utils = DebugUtils::new(&entry, &instance)?;
+    /// Log how the object counts changed since the previous call and the oldest objects still
+    /// alive, the first call only takes the audit compared against. Counts growing call after
+    /// call at the same point of a session are leaks.
+    pub fn audit_objects(&mut self, oldest: usize) -> Option<AuditDiff> {
+        let audit = object_audit::audit(oldest);
+        let diff = self
+            .object_audit
+            .as_ref()
+            .map(|previous| audit.diff(previous));
+        match &diff {
+            Some(diff) => log::info!("{diff}"),
+            None => log::info!("Object audit of {} live objects taken", audit.total()),
+        }
+        for object in &audit.oldest {
+            log::info!("{object}");
+        }
+        self.object_audit = Some(audit);
+        diff
+    }
 
-        let physical_device_list = unsafe {
-            instance
-                .enumerate_physical_devices()
-                .expect("Physical device error")
-        };
+    /// Build the pipeline of `material` in the background on every window, before its first draw.
+    pub fn precompile(&self, material: &Material) {
+        self.window_manager.precompile(material);
+    }
 
-        let renderer = AAABase {
-            entry,
-            instance: Arc::new(instance),
-            surface_loader: Arc::new(surface_loader),
-        };
+    /// Render `window_id`'s scene at `extent` into `path`, independently of the window size.
+    /// [`ExportColorSpace::Linear16F`] keeps the values of HDR surfaces above SDR white, saved to
+    /// an OpenEXR `path`.
+    pub fn capture_screenshot_at(
+        &self,
+        window_id: WindowId,
+        extent: PhysicalSize<u32>,
+        path: impl Into<std::path::PathBuf>,
+        color_space: ExportColorSpace,
+    ) {
+        self.window_manager
+            .capture_screenshot_at(window_id, extent, path, color_space);
+    }
 
-        Ok(Self {
-            custom_cursors,
-            icon,
-            windows: Default::default(),
+    /// User id of the mesh selected in `window_id`.
+    pub fn selected_user_id(&self, window_id: WindowId) -> Option<u64> {
+        self.window_manager.selected_user_id(window_id)
+    }
 
-            #[cfg(debug_assertions)]
-            _debug_utils,
-            renderer: Arc::new(renderer),
+    /// Mesh selected in `window_id`.
+    pub fn selected_mesh(&self, window_id: WindowId) -> Option<MeshHandle> {
+        self.window_manager.selected_mesh(window_id)
+    }
 
-            physical_device_list,
-        })
+    /// Stream `update` into `window_id`'s texture, every update posted before the next frame is
+    /// copied before it draws. Updates covering the whole texture supersede the ones before them.
+    pub fn update_texture(&self, window_id: WindowId, update: TextureUpdate) {
+        self.window_manager.update_texture(window_id, update);
     }
 
-    fn create_window(
-        &mut self,
-        event_loop: &ActiveEventLoop,
-        _tab_id: Option<String>,
-    ) -> Result<WindowId, Box<dyn Error>> {
-        // TODO read-out activation token.
-
-        #[allow(unused_mut)]
-        let mut window_attributes = Window::default_attributes()
-            .with_title(WIN_TITLE)
-            .with_transparent(true)
-            .with_window_icon(Some(self.icon.clone()))
-            .with_inner_size(WIN_START_INNER_SIZE);
-
-        let window = event_loop.create_window(window_attributes)?;
-
-        let window_state = WindowState::new(self, window)?;
-        let window_id = window_state.window.id();
-        self.windows.insert(window_id, window_state);
-        Ok(window_id)
-    }
-
-    fn handle_action(&mut self, event_loop: &ActiveEventLoop, window_id: WindowId, action: Action) {
-        // let cursor_position = self.cursor_position;
-        let window = self.windows.get_mut(&window_id).unwrap();
-        // info!("Executing action: {action:?}");
-        match action {
-            Action::CloseWindow => {
-                self.windows.remove(&window_id).unwrap();
-            }
-            Action::CreateNewWindow => {
-                self.create_window(event_loop, None)
-                    .expect("failed to create new window");
-            }
-            Action::ToggleResizeIncrements => window.toggle_resize_increments(),
-            Action::ToggleCursorVisibility => window.toggle_cursor_visibility(),
-            Action::ToggleResizable => window.toggle_resizable(),
-            Action::ToggleDecorations => window.toggle_decorations(),
-            Action::ToggleFullscreen => window.toggle_fullscreen(),
-            Action::ToggleMaximize => window.toggle_maximize(),
-            Action::ToggleImeInput => window.toggle_ime(),
-            Action::Minimize => window.minimize(),
-            Action::NextCursor => window.next_cursor(),
-            Action::NextCustomCursor => window.next_custom_cursor(&self.custom_cursors),
-            Action::CycleCursorGrab => window.cycle_cursor_grab(),
-            Action::DragWindow => window.drag_window(),
-            Action::DragResizeWindow => window.drag_resize_window(),
-            Action::ShowWindowMenu => window.show_menu(),
-            Action::PrintHelp => self.print_help(),
-            Action::RequestResize => window.swap_dimensions(),
-        }
+    /// Replace `window_id`'s texture, the bundled picture by default, with the image file at
+    /// `path` decoded to RGBA8 whatever its format. Loaded on the render thread before the next
+    /// frame, a file failing to load is logged and the previous texture stays. Later updates
+    /// stream into the loaded texture, at its size. Needs the `images` feature.
+    pub fn load_texture(&self, window_id: WindowId, path: impl Into<PathBuf>) {
+        self.load_texture_with_sampler(window_id, path, SamplerDesc::default());
     }
 
-    fn dump_monitors(&self, event_loop: &ActiveEventLoop) {
-        // info!("Monitors information");
-        let primary_monitor = event_loop.primary_monitor();
-        for monitor in event_loop.available_monitors() {
-            let intro = if primary_monitor.as_ref() == Some(&monitor) {
-                "Primary monitor"
-            } else {
-                "Monitor"
-            };
-
-            if let Some(name) = monitor.name() {
-                info!("{intro}: {name}");
-            } else {
-                info!("{intro}: [no name]");
-            }
+    /// [`Self::load_texture`] sampled as `sampler`, [`SamplerDesc::nearest`] for pixel art.
+    pub fn load_texture_with_sampler(
+        &self,
+        window_id: WindowId,
+        path: impl Into<PathBuf>,
+        sampler: SamplerDesc,
+    ) {
+        self.window_manager
+            .load_texture(window_id, path.into(), sampler);
+    }
 
-            let PhysicalSize { width, height } = monitor.size();
-            info!(
-                "  Current mode: {width}x{height}{}",
-                if let Some(m_hz) = monitor.refresh_rate_millihertz() {
-                    format!(" @ {}.{} Hz", m_hz / 1000, m_hz % 1000)
-                } else {
-                    String::new()
-                }
-            );
+    /// Replace `window_id`'s texture with `texture`, blocks compressed offline by a KTX2 or DDS
+    /// loader, uploaded as they are with the levels it has. A device that cannot sample its format
+    /// gets the first level decoded to RGBA8 instead, logged as a
+    /// `PulsarError::UnsupportedTextureFormat`. Streamed `TextureUpdate`s are dropped until an
+    /// RGBA8 texture is loaded again.
+    pub fn load_compressed_texture(
+        &self,
+        window_id: WindowId,
+        texture: CompressedTexture,
+        sampler: SamplerDesc,
+    ) {
+        self.window_manager
+            .load_compressed_texture(window_id, texture, sampler);
+    }
 
-            let PhysicalPosition { x, y } = monitor.position();
-            info!("  Position: {x},{y}");
+    /// Let `window_id`'s texture be evicted when the window is over its memory budget and no mesh
+    /// in view samples it, with `ResidencyPriority::Streaming`, or keep it resident. Only a
+    /// texture loaded from a file or compressed blocks is tracked, it is sampled as white while
+    /// evicted and loaded again from them the first frame a mesh in view samples it. Streaming a
+    /// `TextureUpdate` into it keeps it resident from then on.
+    pub fn set_texture_residency(&self, window_id: WindowId, priority: ResidencyPriority) {
+        self.window_manager
+            .set_texture_residency(window_id, priority);
+    }
 
-            info!("  Scale factor: {}", monitor.scale_factor());
+    /// Give `window_id` a texture array of `desc.layers` white layers, replacing the previous one
+    /// and its layers. Meshes sample it through one descriptor, each at the layer set with
+    /// `MeshUpdate::TextureLayer`. More layers than the device's `maxImageArrayLayers` is logged as
+    /// a `PulsarError::TextureArrayLayers` and keeps the previous array.
+    pub fn create_texture_array(&self, window_id: WindowId, desc: TextureArrayDesc) {
+        self.window_manager.create_texture_array(window_id, desc);
+    }
 
-            info!("  Available modes (width x height x bit-depth):");
-            for mode in monitor.video_modes() {
-                let PhysicalSize { width, height } = mode.size();
-                let bits = mode.bit_depth();
-                let m_hz = mode.refresh_rate_millihertz();
-                info!(
-                    "    {width}x{height}x{bits} @ {}.{} Hz",
-                    m_hz / 1000,
-                    m_hz % 1000
-                );
-            }
-        }
+    /// Copy `data`, RGBA8 texels of the array's layer size from the top left, into the next layer
+    /// of `window_id`'s texture array: the first push fills layer 0, the next layer 1 and so on.
+    pub fn push_texture_layer(&self, window_id: WindowId, data: Vec<u8>) {
+        self.window_manager.push_texture_layer(window_id, data);
     }
 
-    /// Process the key binding.
-    fn process_key_binding(key: &str, mods: &ModifiersState) -> Option<Action> {
-        KEY_BINDINGS.iter().find_map(|binding| {
-            binding
-                .is_triggered_by(&key, mods)
-                .then_some(binding.action)
-        })
+    /// Load `source` as `window_id`'s environment cubemap, sampled as `sampler` by shaders
+    /// declaring `layout (set = 0, binding = 1) uniform samplerCube`, and light the scene with
+    /// its irradiance, see [`crate::environment`]. Faces that fail to decode, are not square or
+    /// differ in size are logged, as a `PulsarError::CubemapFace` for the latter, and keep the
+    /// previous cubemap. Needs the `images` feature.
+    pub fn load_cubemap(&self, window_id: WindowId, source: CubemapSource, sampler: SamplerDesc) {
+        self.window_manager.load_cubemap(window_id, source, sampler);
     }
 
-    /// Process mouse binding.
-    fn process_mouse_binding(button: MouseButton, mods: &ModifiersState) -> Option<Action> {
-        MOUSE_BINDINGS.iter().find_map(|binding| {
-            binding
-                .is_triggered_by(&button, mods)
-                .then_some(binding.action)
-        })
+    /// Drop `window_id`'s environment cubemap, the scene is lit by the flat environment again.
+    pub fn clear_cubemap(&self, window_id: WindowId) {
+        self.window_manager.clear_cubemap(window_id);
     }
 
-    fn print_help(&self) {
-        info!("Keyboard bindings:");
-        for binding in KEY_BINDINGS {
-            info!(
-                "{}{:<10} - {} ({})",
-                modifiers_to_string(binding.mods),
-                binding.trigger,
-                binding.action,
-                binding.action.help(),
-            );
-        }
-        info!("Mouse bindings:");
-        for binding in MOUSE_BINDINGS {
-            info!(
-                "{}{:<10} - {} ({})",
-                modifiers_to_string(binding.mods),
-                mouse_button_to_string(binding.trigger),
-                binding.action,
-                binding.action.help(),
-            );
-        }
+    /// Scale the ambient light `window_id`'s environment gives, 1 by default.
+    pub fn set_environment_intensity(&self, window_id: WindowId, intensity: f32) {
+        self.window_manager
+            .set_environment_intensity(window_id, intensity);
     }
-}
 
-impl ApplicationHandler<UserEvent> for Application {
-    fn user_event(&mut self, _event_loop: &ActiveEventLoop, event: UserEvent) {
-        info!("User event: {event:?}");
+    /// Draw `failure` in place of the scene until the user retries or the application clears it.
+    /// Esc closes the window, R runs the engine's recovery for the category then `retry` on the
+    /// render thread, and draws the scene again once it returns true.
+    pub fn show_failure(
+        &self,
+        window_id: WindowId,
+        failure: Failure,
+        retry: Option<RetryCallback>,
+    ) {
+        self.window_manager.show_failure(window_id, failure, retry);
     }
 
-    fn window_event(
+    /// Draw the scene again, without running the retry callback.
+    pub fn clear_failure(&self, window_id: WindowId) {
+        self.window_manager.clear_failure(window_id);
+    }
+
+    /// Whether `window_id` presented a frame yet, polled by splash screen flows before they
+    /// swap scenes. `UserEvent::FirstFramePresented` tells the same once.
+    pub fn first_frame_presented(&self, window_id: WindowId) -> bool {
+        self.window_manager.first_frame_presented(window_id)
+    }
+
+    /// Preview of `target` at `size` pixels square for editor UIs, `None` when the window is gone.
+    /// The handle fills in over the next frames, a few thumbnails are rendered per frame so the
+    /// view never hitches. Requests of the same thumbnail share one handle. Texture thumbnails are
+    /// rendered again when the texture is updated, material ones when their pipeline is ready.
+    /// Dropping every handle frees the thumbnail.
+    pub fn request_thumbnail(
+        &self,
+        window_id: WindowId,
+        target: ThumbnailTarget,
+        size: u32,
+    ) -> Option<Thumbnail> {
+        self.window_manager
+            .request_thumbnail(window_id, target, size)
+    }
+
+    /// Draw `mesh` in `window_id` from its next frame on, with the default material. The handle
+    /// takes updates and removals right away, they apply once the mesh is registered. `None` when
+    /// the window is gone.
+    pub fn add_mesh(&self, window_id: WindowId, mesh: Mesh) -> Option<MeshHandle> {
+        self.window_manager
+            .add_mesh(window_id, mesh, MeshSpace::World, None)
+    }
+
+    /// Run `load` on the job pool and draw the mesh it returns in `window_id` like
+    /// [`Self::add_mesh`]. Closing the window first cancels the load, see [`crate::loader`].
+    /// `None` when the window is gone.
+    pub fn load_mesh<F>(&self, window_id: WindowId, load: F) -> Option<LoadHandle>
+    where
+        F: FnOnce() -> Result<Mesh, Box<dyn Error + Send + Sync>> + Send + 'static,
+    {
+        let target = self.window_manager.load_target(window_id)?;
+        Some(loader::load_mesh(target, MeshSpace::World, load))
+    }
+
+    /// Draw the meshes of `batch` from one vertex buffer and one index buffer, with a single draw
+    /// when they share a transform. The batch is selected, moved and evicted as a whole.
+    pub fn add_mesh_batch(&self, window_id: WindowId, batch: MeshBatch) -> Option<MeshHandle> {
+        self.window_manager
+            .add_mesh_batch(window_id, batch, MeshSpace::World)
+    }
+
+    /// Draw `mesh` in `window_id`'s 2D world, panned with the middle mouse button and zoomed with
+    /// the wheel or a pinch. Picked like the other meshes, at any zoom.
+    pub fn add_mesh_2d(&self, window_id: WindowId, mesh: Mesh) -> Option<MeshHandle> {
+        self.window_manager
+            .add_mesh(window_id, mesh, MeshSpace::World2D, None)
+    }
+
+    /// Draw `mesh` once per transform of `instances` in a single draw call, each copy placed by
+    /// its instance transform then the mesh transform. Keep a clone of `instances` to move the
+    /// copies, changes are picked up before the next frame. Picking only sees the mesh itself.
+    pub fn add_instanced_mesh(
+        &self,
+        window_id: WindowId,
+        mesh: Mesh,
+        instances: &Instances,
+    ) -> Option<MeshHandle> {
+        self.window_manager
+            .add_mesh(window_id, mesh, MeshSpace::World, Some(instances.clone()))
+    }
+
+    /// [`Self::add_instanced_mesh`] in `window_id`'s 2D world, see [`Self::add_mesh_2d`].
+    pub fn add_instanced_mesh_2d(
+        &self,
+        window_id: WindowId,
+        mesh: Mesh,
+        instances: &Instances,
+    ) -> Option<MeshHandle> {
+        self.window_manager
+            .add_mesh(window_id, mesh, MeshSpace::World2D, Some(instances.clone()))
+    }
+
+    /// Draw `mesh` deformed by the joints of `player`'s skin, posed by the clip it plays. Keep a
+    /// clone of `player` to pick the clip, the window advances it by its frame time. Picking and
+    /// bounds see the mesh at its bind pose.
+    pub fn add_skinned_mesh(
+        &self,
+        window_id: WindowId,
+        mesh: Mesh,
+        player: &AnimationPlayer,
+    ) -> Option<MeshHandle> {
+        self.window_manager
+            .add_skinned_mesh(window_id, mesh, player.clone())
+    }
+
+    /// Draw `mesh` with its vertices rewritten before every frame by `deformer`'s compute shader,
+    /// from the vertices given here. Picking and bounds see the mesh as given.
+    pub fn add_deformed_mesh(
+        &self,
+        window_id: WindowId,
+        mesh: Mesh,
+        deformer: VertexDeformer,
+    ) -> Option<MeshHandle> {
+        self.window_manager
+            .add_deformed_mesh(window_id, mesh, deformer)
+    }
+
+    /// Replace how `window_id` looks at its 2D world: view, zoom limits and bounds.
+    pub fn set_ortho_2d(&self, window_id: WindowId, controller: Ortho2DController) {
+        self.window_manager.set_ortho_2d(window_id, controller);
+    }
+
+    /// Replace how the scroll wheel dollies `window_id`'s perspective camera toward the cursor.
+    pub fn set_camera_controller(&self, window_id: WindowId, controller: CameraController) {
+        self.window_manager
+            .set_camera_controller(window_id, controller);
+    }
+
+    /// Scale and pause state of `window_id`'s simulation, `None` when the window is gone.
+    pub fn time_control(&self, window_id: WindowId) -> Option<TimeControl> {
+        self.window_manager.time_control(window_id)
+    }
+
+    /// Slow down, speed up or pause `window_id`'s animations and background from the next frame
+    /// on. The camera keeps real time. See [`crate::time_control`].
+    pub fn set_time_control(&mut self, window_id: WindowId, control: TimeControl) {
+        self.window_manager.set_time_control(window_id, control);
+    }
+
+    /// Preference of `window_id`'s swapchain and the present mode it resolved to on this surface,
+    /// `None` until its render thread created one. Alt+V cycles the preference.
+    pub fn present_mode(
+        &self,
+        window_id: WindowId,
+    ) -> Option<(PresentModePreference, ash::vk::PresentModeKHR)> {
+        self.window_manager.present_mode(window_id)
+    }
+
+    /// Distance `window_id`'s depth of field is focused at as of its last frame, `None` while
+    /// `depth_of_field.enabled` is off. Follows the cursor with `FocusMode::Cursor`.
+    pub fn focus_distance(&self, window_id: WindowId) -> Option<f32> {
+        self.window_manager.focus_distance(window_id)
+    }
+
+    pub fn set_window_title(&self, window_id: WindowId, title: &str) {
+        self.window_manager.set_title(window_id, title);
+    }
+
+    /// Recreate `window_id`'s swapchain before its next frame, presenting with the first mode of
+    /// `preference` the surface supports. See [`PresentModePreference::priority`].
+    pub fn set_present_preference(
         &mut self,
-        event_loop: &ActiveEventLoop,
         window_id: WindowId,
-        event: WindowEvent,
+        preference: PresentModePreference,
     ) {
-        let window_state = match self.windows.get_mut(&window_id) {
-            Some(window) => window,
-            None => return,
-        };
+        self.window_manager
+            .set_present_preference(window_id, preference);
+    }
 
-        match event {
-            WindowEvent::Resized(size) => {
-                window_state.resize(size);
-            }
-            WindowEvent::Focused(focused) => {
-                if focused {
-                    info!("Window={window_id:?} focused");
-                } else {
-                    info!("Window={window_id:?} unfocused");
-                }
-            }
-            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
-                info!("Window={window_id:?} changed scale to {scale_factor}");
-            }
-            WindowEvent::ThemeChanged(theme) => {
-                info!("Theme changed to {theme:?}");
-                window_state.set_theme(theme);
-            }
-            WindowEvent::RedrawRequested => {}
-            WindowEvent::Occluded(occluded) => {
-                window_state.set_occluded(occluded);
-            }
-            WindowEvent::CloseRequested => {
-                info!("Closing Window={window_id:?}");
-                let mut window_state = self.windows.remove(&window_id).unwrap();
-                window_state.render_thread_close_join();
-            }
-            WindowEvent::ModifiersChanged(modifiers) => {
-                window_state.modifiers = modifiers.state();
-                info!("Modifiers changed to {:?}", window_state.modifiers);
-            }
-            WindowEvent::MouseWheel { delta, .. } => match delta {
-                MouseScrollDelta::LineDelta(x, y) => {
-                    info!("Mouse wheel Line Delta: ({x},{y})");
-                }
-                MouseScrollDelta::PixelDelta(px) => {
-                    info!("Mouse wheel Pixel Delta: ({},{})", px.x, px.y);
-                }
-            },
-            WindowEvent::KeyboardInput {
-                event,
-                is_synthetic: false,
-                ..
-            } => {
-                let mods = window_state.modifiers;
-
-                // Dispatch actions only on press.
-                if event.state.is_pressed() {
-                    let action = if let Key::Character(ch) = event.logical_key.as_ref() {
-                        Self::process_key_binding(&ch.to_uppercase(), &mods)
-                    } else {
-                        None
-                    };
-
-                    if let Some(action) = action {
-                        self.handle_action(event_loop, window_id, action);
-                    }
-                }
-            }
-            WindowEvent::MouseInput { button, state, .. } => {
-                let mods = window_state.modifiers;
-                if let Some(action) = state
-                    .is_pressed()
-                    .then(|| Self::process_mouse_binding(button, &mods))
-                    .flatten()
-                {
-                    self.handle_action(event_loop, window_id, action);
-                }
-            }
-            WindowEvent::CursorLeft { .. } => {
-                // info!("Cursor left Window={window_id:?}");
-                window_state.cursor_left();
-            }
-            WindowEvent::CursorMoved { position, .. } => {
-                // info!("Moved cursor to {position:?}");
-                window_state.cursor_moved(position);
-            }
-            WindowEvent::ActivationTokenDone { token: _token, .. } => {}
-            WindowEvent::Ime(event) => match event {
-                Ime::Enabled => {} // info!("IME enabled for Window={window_id:?}"),
-                Ime::Preedit(text, caret_pos) => {
-                    info!("Preedit: {}, with caret at {:?}", text, caret_pos);
-                }
-                Ime::Commit(text) => {
-                    info!("Committed: {}", text);
-                }
-                Ime::Disabled => info!("IME disabled for Window={window_id:?}"),
-            },
-            WindowEvent::PinchGesture { delta, .. } => {
-                window_state.zoom += delta;
-                let zoom = window_state.zoom;
-                if delta > 0.0 {
-                    info!("Zoomed in {delta:.5} (now: {zoom:.5})");
-                } else {
-                    info!("Zoomed out {delta:.5} (now: {zoom:.5})");
-                }
-            }
-            WindowEvent::RotationGesture { delta, .. } => {
-                window_state.rotated += delta;
-                let rotated = window_state.rotated;
-                if delta > 0.0 {
-                    info!("Rotated counterclockwise {delta:.5} (now: {rotated:.5})");
-                } else {
-                    info!("Rotated clockwise {delta:.5} (now: {rotated:.5})");
-                }
-            }
-            WindowEvent::PanGesture { delta, phase, .. } => {
-                window_state.panned.x += delta.x;
-                window_state.panned.y += delta.y;
-                info!(
-                    "Panned ({delta:?})) (now: {:?}), {phase:?}",
-                    window_state.panned
-                );
-            }
-            WindowEvent::DoubleTapGesture { .. } => {
-                info!("Smart zoom");
-            }
-            WindowEvent::TouchpadPressure { .. }
-            | WindowEvent::HoveredFileCancelled
-            | WindowEvent::KeyboardInput { .. }
-            | WindowEvent::CursorEntered { .. }
-            | WindowEvent::AxisMotion { .. }
-            | WindowEvent::DroppedFile(_)
-            | WindowEvent::HoveredFile(_)
-            | WindowEvent::Destroyed
-            | WindowEvent::Touch(_)
-            | WindowEvent::Moved(_) => (),
-        }
+    /// Advance `window_id`'s paused simulation by exactly one tick on the next frame. Ignored
+    /// while it runs.
+    pub fn step_once(&self, window_id: WindowId) {
+        self.window_manager.step_once(window_id);
     }
 
-    fn device_event(
-        &mut self,
-        _event_loop: &ActiveEventLoop,
-        _device_id: DeviceId,
-        _event: DeviceEvent,
+    /// Record `pass` into every frame of `window_id` at `slot`, replacing the slot's previous pass.
+    /// See [`crate::custom_pass`] for the state at each slot. A pass that panics is removed.
+    pub fn set_custom_pass(&self, window_id: WindowId, slot: CustomPassSlot, pass: CustomPass) {
+        self.window_manager
+            .set_custom_pass(window_id, slot, Some(pass));
+    }
+
+    /// The logical device `window_id` renders with, for interop the engine does not cover.
+    /// `None` until its renderer created the device and once it destroyed it.
+    ///
+    /// # Safety
+    ///
+    /// The device is destroyed when the window closes: nothing created from it may outlive that,
+    /// nor may it be used afterwards. The render thread records and submits concurrently,
+    /// external synchronization of its queues and of the objects it owns is the caller's, see the
+    /// Vulkan specification's threading rules.
+    pub unsafe fn raw_device(&self, window_id: WindowId) -> Option<ash::Device> {
+        self.window_manager.raw_device(window_id)
+    }
+
+    /// Stop recording the pass of `slot` in `window_id`, dropped on the render thread.
+    pub fn remove_custom_pass(&self, window_id: WindowId, slot: CustomPassSlot) {
+        self.window_manager.set_custom_pass(window_id, slot, None);
+    }
+
+    /// Draw `lines` over `window_id`'s scene from its next frame on, until other lines replace them.
+    /// Post an empty `DebugLines` to remove them.
+    pub fn set_debug_lines(&self, window_id: WindowId, lines: DebugLines) {
+        self.window_manager.set_debug_lines(window_id, lines);
+    }
+
+    /// Draw `mode` behind `window_id`'s scene from its next frame on, in place of the clear color.
+    /// A shader source is reloaded when it changes, see [`BackgroundMode::Shader`].
+    pub fn set_background(&self, window_id: WindowId, mode: BackgroundMode) {
+        self.window_manager.set_background(window_id, mode);
+    }
+
+    /// Values of the `user` block of `window_id`'s background shader, kept until replaced.
+    pub fn set_background_uniforms(
+        &self,
+        window_id: WindowId,
+        user: [f32; BACKGROUND_USER_FLOATS],
     ) {
-        // info!("Device {device_id:?} event: {event:?}");
+        self.window_manager.set_background_uniforms(window_id, user);
     }
 
-    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        info!("Resumed the event loop");
-        self.dump_monitors(event_loop);
+    /// Draw the scene again into each of `insets` over `window_id`'s main view, under the UI,
+    /// from its next frame on. Post an empty list to remove them.
+    pub fn set_insets(&self, window_id: WindowId, insets: Vec<InsetView>) {
+        self.window_manager.set_insets(window_id, insets);
+    }
 
-        let window_id = self
-            .create_window(event_loop, None)
-            .expect("failed to create initial window");
+    /// Put `mesh` on the layers set in `layers`, bit per layer. Meshes are on every layer until
+    /// then, the insets draw the ones sharing a layer with their mask.
+    pub fn set_mesh_layers(&self, window_id: WindowId, mesh: MeshHandle, layers: u32) {
+        self.update_mesh(window_id, mesh, MeshUpdate::Layers(layers));
+    }
 
-        let window_state = self.windows.get_mut(&window_id).unwrap();
-        window_state.create_renderer();
-        self.print_help();
+    /// Draw `mesh` in the `sort_bias` bucket of `window_id`'s perspective list, lower buckets
+    /// first. Meshes of a bucket keep their registration order.
+    pub fn set_mesh_sort_bias(&self, window_id: WindowId, mesh: MeshHandle, sort_bias: i32) {
+        self.update_mesh(window_id, mesh, MeshUpdate::SortBias(sort_bias));
     }
 
-    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
-        if self.windows.is_empty() {
-            // info!("No windows left, exiting...");
-            event_loop.exit();
-        }
+    /// Blend `mesh` over what is behind it by the alpha of its colors, or draw it opaque again.
+    /// Blended meshes draw after the opaque ones of `window_id`, farthest first.
+    pub fn set_mesh_blend(&self, window_id: WindowId, mesh: MeshHandle, blend: bool) {
+        self.update_mesh(window_id, mesh, MeshUpdate::Blend(blend));
     }
 
-    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
-        //
+    /// Identify `mesh` to the application: picking it reports `user_id`, as does the selection.
+    /// The meshes of a batch report the ids they were added with, see [`MeshUpdate::UserId`].
+    pub fn set_mesh_user_id(&self, window_id: WindowId, mesh: MeshHandle, user_id: u64) {
+        self.update_mesh(window_id, mesh, MeshUpdate::UserId(user_id));
     }
-}
 
-struct Binding<T: Eq> {
-    trigger: T,
-    mods: ModifiersState,
-    action: Action,
-}
+    /// Rasterize `mesh` with `options` rather than the defaults of its space: world meshes cull
+    /// their back faces, 2D world meshes draw both. Each distinct value gets its own pipeline,
+    /// built by `window_id`'s render thread when first drawn.
+    pub fn set_mesh_pipeline_options(
+        &self,
+        window_id: WindowId,
+        mesh: MeshHandle,
+        options: Option<PipelineOptions>,
+    ) {
+        self.update_mesh(window_id, mesh, MeshUpdate::PipelineOptions(options));
+    }
+
+    /// Draw `mesh` with `material`, or the default material again. A material the device cannot
+    /// build, a geometry or tessellation shader without its feature, is drawn without its
+    /// optional stages.
+    pub fn set_mesh_material(
+        &self,
+        window_id: WindowId,
+        mesh: MeshHandle,
+        material: Option<Material>,
+    ) {
+        self.update_mesh(window_id, mesh, MeshUpdate::Material(material));
+    }
 
-impl<T: Eq> Binding<T> {
-    const fn new(trigger: T, mods: ModifiersState, action: Action) -> Self {
-        Self {
-            trigger,
-            mods,
-            action,
+    /// Multiply the color of every fragment of `mesh` by `tint`, white to draw it as it is. Pushed
+    /// with every draw, no pipeline is built for it; fragment shaders of custom materials read it
+    /// after the vertex stage's push constants or ignore it.
+    pub fn set_mesh_tint(&self, window_id: WindowId, mesh: MeshHandle, tint: [f32; 4]) {
+        self.update_mesh(window_id, mesh, MeshUpdate::Tint(tint));
+    }
+
+    /// Bias the depth of `mesh`'s fragments, `None` to draw it unbiased again. Set per draw rather
+    /// than built into a pipeline, the way to keep a decal over a surface when both draw with the
+    /// default material. A mesh with a material takes the material's bias instead.
+    pub fn set_mesh_depth_bias(
+        &self,
+        window_id: WindowId,
+        mesh: MeshHandle,
+        depth_bias: Option<DepthBias>,
+    ) {
+        self.update_mesh(window_id, mesh, MeshUpdate::DepthBias(depth_bias));
+    }
+
+    /// Let `mesh` be evicted when `window_id` is over its memory budget and it is out of view,
+    /// with `ResidencyPriority::Streaming`, or keep it resident. Evicted meshes are reloaded the
+    /// first frame they are in view again, see [`crate::residency`].
+    pub fn set_mesh_residency(
+        &self,
+        window_id: WindowId,
+        mesh: MeshHandle,
+        priority: ResidencyPriority,
+    ) {
+        self.update_mesh(window_id, mesh, MeshUpdate::Residency(priority));
+    }
+
+    /// Replace the vertices or indices of `mesh` in `window_id` without registering it again.
+    /// Updates posted before the same frame are applied together, the last of each kind wins.
+    /// Post the indices of a mesh that loses vertices along with them, or the update is dropped.
+    /// A full update queue makes this wait, or drop the update with a warning under
+    /// `Backpressure::Reject`, see [`Self::try_update_mesh`].
+    pub fn update_mesh(&self, window_id: WindowId, mesh: MeshHandle, update: MeshUpdate) {
+        if let Err(err) = self.try_update_mesh(window_id, mesh, update) {
+            log::warn!("Update of {mesh:?} dropped: {err}");
         }
     }
 
-    fn is_triggered_by(&self, trigger: &T, mods: &ModifiersState) -> bool {
-        &self.trigger == trigger && &self.mods == mods
+    /// [`Self::update_mesh`] telling when a full queue refused the update, see
+    /// [`crate::update_queue`] for what is merged and in which order updates apply.
+    pub fn try_update_mesh(
+        &self,
+        window_id: WindowId,
+        mesh: MeshHandle,
+        update: MeshUpdate,
+    ) -> Result<(), PulsarError> {
+        self.window_manager.update_mesh(window_id, mesh, update)
+    }
+
+    /// Place `mesh` relative to its parent, or in the world when it has none. Its children follow.
+    pub fn set_local_transform(&self, window_id: WindowId, mesh: MeshHandle, transform: Mat4) {
+        self.update_mesh(window_id, mesh, MeshUpdate::Transform(transform));
     }
-}
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum Action {
-    CloseWindow,
-    ToggleCursorVisibility,
-    CreateNewWindow,
-    ToggleResizeIncrements,
-    ToggleImeInput,
-    ToggleDecorations,
-    ToggleResizable,
-    ToggleFullscreen,
-    ToggleMaximize,
-    Minimize,
-    NextCursor,
-    NextCustomCursor,
-    CycleCursorGrab,
-    PrintHelp,
-    DragWindow,
-    DragResizeWindow,
-    ShowWindowMenu,
-    RequestResize,
+    /// Make `child` move with `parent` in `window_id`, `None` detaches it. The child keeps its
+    /// local transform, a link that would make a mesh its own ancestor is refused with a warning.
+    pub fn set_parent(&self, window_id: WindowId, child: MeshHandle, parent: Option<MeshHandle>) {
+        self.update_mesh(window_id, child, MeshUpdate::Parent(parent));
+    }
+
+    /// Free `mesh` from `window_id`, its handle resolves to nothing afterwards.
+    pub fn remove_mesh(&self, window_id: WindowId, mesh: MeshHandle) {
+        self.window_manager.remove_mesh(window_id, mesh);
+    }
 }
 
-impl Action {
-    fn help(&self) -> &'static str {
-        match self {
-            Action::CloseWindow => "Close window",
-            Action::ToggleCursorVisibility => "Hide cursor",
-            Action::CreateNewWindow => "Create new window",
-            Action::ToggleImeInput => "Toggle IME input",
-            Action::ToggleDecorations => "Toggle decorations",
-            Action::ToggleResizable => "Toggle window resizable state",
-            Action::ToggleFullscreen => "Toggle fullscreen",
-            Action::ToggleMaximize => "Maximize",
-            Action::Minimize => "Minimize",
-            Action::ToggleResizeIncrements => "Use resize increments when resizing window",
-            Action::NextCursor => "Advance the cursor to the next value",
-            Action::NextCustomCursor => "Advance custom cursor to the next value",
-            Action::CycleCursorGrab => "Cycle through cursor grab mode",
-            Action::PrintHelp => "Print help",
-            Action::DragWindow => "Start window drag",
-            Action::DragResizeWindow => "Start window drag-resize",
-            Action::ShowWindowMenu => "Show window menu",
-            Action::RequestResize => "Request a resize",
+impl ApplicationHandler<UserEvent> for Application {
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        self.window_manager.user_event(event_loop, event);
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        #[cfg(any(feature = "obj", feature = "images"))]
+        if let WindowEvent::DroppedFile(path) = &event {
+            if let Err(error) = self.load_dropped(window_id, path) {
+                log::warn!("Dropped file not loaded: {error}");
+            }
         }
+        self.window_manager
+            .window_event(event_loop, window_id, event);
     }
-}
 
-impl fmt::Display for Action {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        Debug::fmt(&self, f)
+    fn device_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        device_id: DeviceId,
+        event: DeviceEvent,
+    ) {
+        self.window_manager
+            .device_event(event_loop, device_id, event);
     }
-}
 
-fn decode_cursor(bytes: &[u8]) -> CustomCursorSource {
-    let img = image::load_from_memory(bytes).unwrap().to_rgba8();
-    let samples = img.into_flat_samples();
-    let (_, w, h) = samples.extents();
-    let (w, h) = (w as u16, h as u16);
-    CustomCursor::from_rgba(samples.samples, w, h, w / 2, h / 2).unwrap()
-}
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.window_manager.resumed(event_loop);
+    }
 
-fn load_icon(bytes: &[u8]) -> Icon {
-    let (icon_rgba, icon_width, icon_height) = {
-        let image = image::load_from_memory(bytes).unwrap().into_rgba8();
-        let (width, height) = image.dimensions();
-        let rgba = image.into_raw();
-        (rgba, width, height)
-    };
-    Icon::from_rgba(icon_rgba, icon_width, icon_height).expect("Failed to open icon")
-}
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        for (window_id, report) in self.window_manager.stalled_render_threads() {
+            self.user_event(
+                event_loop,
+                UserEvent::RenderThreadStalled { window_id, report },
+            );
+        }
+        for window_id in self.window_manager.first_frames_presented() {
+            self.user_event(event_loop, UserEvent::FirstFramePresented(window_id));
+        }
+        for (window_id, report) in self.window_manager.present_mode_downgrades() {
+            self.user_event(
+                event_loop,
+                UserEvent::PresentModeDowngraded { window_id, report },
+            );
+        }
+        self.window_manager.about_to_wait(event_loop);
+    }
 
-fn modifiers_to_string(mods: ModifiersState) -> String {
-    [
-        (ModifiersState::SUPER, "Super+"),
-        (ModifiersState::ALT, "Alt+"),
-        (ModifiersState::CONTROL, "Ctrl+"),
-        (ModifiersState::SHIFT, "Shift+"),
-    ]
-    .iter()
-    .filter(|(modifier, _)| mods.contains(*modifier))
-    .map(|(_, desc)| *desc)
-    .collect::<String>()
+    fn exiting(&mut self, event_loop: &ActiveEventLoop) {
+        self.window_manager.exiting(event_loop);
+    }
 }
 
-fn mouse_button_to_string(button: MouseButton) -> &'static str {
-    match button {
-        MouseButton::Left => "LMB",
-        MouseButton::Right => "RMB",
-        MouseButton::Middle => "MMB",
-        MouseButton::Back => "Back",
-        MouseButton::Forward => "Forward",
-        MouseButton::Other(_) => "Other",
+impl Drop for Application {
+    fn drop(&mut self) {
+        // Windows first so their render threads stop spawning work
+        self.window_manager.close_all();
+        crate::jobs::shutdown();
     }
 }
-
-const KEY_BINDINGS: &[Binding<&'static str>] = &[
-    Binding::new("Q", ModifiersState::CONTROL, Action::CloseWindow),
-    Binding::new("H", ModifiersState::CONTROL, Action::PrintHelp),
-    Binding::new("F", ModifiersState::CONTROL, Action::ToggleFullscreen),
-    Binding::new("D", ModifiersState::CONTROL, Action::ToggleDecorations),
-    Binding::new("I", ModifiersState::CONTROL, Action::ToggleImeInput),
-    Binding::new("L", ModifiersState::CONTROL, Action::CycleCursorGrab),
-    Binding::new("P", ModifiersState::CONTROL, Action::ToggleResizeIncrements),
-    Binding::new("R", ModifiersState::CONTROL, Action::ToggleResizable),
-    Binding::new("R", ModifiersState::ALT, Action::RequestResize),
-    // M.
-    Binding::new("M", ModifiersState::CONTROL, Action::ToggleMaximize),
-    Binding::new("M", ModifiersState::ALT, Action::Minimize),
-    // N.
-    Binding::new("N", ModifiersState::CONTROL, Action::CreateNewWindow),
-    // C.
-    Binding::new("C", ModifiersState::CONTROL, Action::NextCursor),
-    Binding::new("C", ModifiersState::ALT, Action::NextCustomCursor),
-    Binding::new("Z", ModifiersState::CONTROL, Action::ToggleCursorVisibility),
-];
-
-const MOUSE_BINDINGS: &[Binding<MouseButton>] = &[
-    Binding::new(
-        MouseButton::Left,
-        ModifiersState::ALT,
-        Action::DragResizeWindow,
-    ),
-    Binding::new(
-        MouseButton::Left,
-        ModifiersState::CONTROL,
-        Action::DragWindow,
-    ),
-    Binding::new(
-        MouseButton::Right,
-        ModifiersState::CONTROL,
-        Action::ShowWindowMenu,
-    ),
-];
@@ -0,0 +1,303 @@
+//! Content addressed assets: loading the same bytes twice returns the asset already held and
+//! counts a reference, through paths that differ or through byte identical copies alike. Meshes
+//! are deduplicated by the renderer on registration, their GPU copy shared, see
+//! `MeshRegistry::find_geometry`.
+use crate::{
+    handles::TextureId,
+    metrics,
+    model::{as_bytes, Mesh},
+    texture::TextureUpdate,
+};
+use std::collections::HashMap;
+#[cfg(feature = "images")]
+use std::{error::Error, path::Path};
+use xxhash_rust::xxh3::{xxh3_64, Xxh3};
+
+/// XXH3 of `bytes`, the key of a loaded asset.
+pub fn content_hash(bytes: &[u8]) -> u64 {
+    xxh3_64(bytes)
+}
+
+/// Hash of the vertices and indices, the transform left out.
+pub fn geometry_hash(mesh: &Mesh) -> u64 {
+    let mut hasher = Xxh3::new();
+    hasher.update(&(mesh.vertices.len() as u64).to_le_bytes());
+    hasher.update(as_bytes(&mesh.vertices));
+    hasher.update(as_bytes(&mesh.indices));
+    hasher.digest()
+}
+
+/// Texels of a texture with its size, two textures only match with the same dimensions.
+fn texture_hash(texture: &TextureUpdate) -> u64 {
+    let mut hasher = Xxh3::new();
+    hasher.update(&texture.region.width.to_le_bytes());
+    hasher.update(&texture.region.height.to_le_bytes());
+    hasher.update(&texture.data[..texture.size_bytes()]);
+    hasher.digest()
+}
+
+#[derive(Debug)]
+struct TextureSlot {
+    generation: u32,
+    entry: Option<TextureEntry>,
+}
+
+#[derive(Debug)]
+struct TextureEntry {
+    texture: TextureUpdate,
+    /// Hashes of the encoded bytes and of the texels this texture was loaded from, empty for the
+    /// textures loaded unique, never shared.
+    hashes: Vec<u64>,
+    references: u32,
+}
+
+/// Decoded RGBA8 textures addressed by [`TextureId`], each held once per content. Every load
+/// returns a reference to [`Self::release`].
+#[derive(Debug, Default)]
+pub struct TextureLibrary {
+    slots: Vec<TextureSlot>,
+    free: Vec<usize>,
+    by_content: HashMap<u64, TextureId>,
+}
+
+impl TextureLibrary {
+    /// Decode the image at `path`, or return the texture already loaded from the same bytes.
+    #[cfg(feature = "images")]
+    pub fn load(&mut self, path: &Path) -> Result<TextureId, Box<dyn Error>> {
+        let bytes = std::fs::read(path)?;
+        self.load_bytes(&bytes)
+            .map_err(|error| format!("{}: {error}", path.display()).into())
+    }
+
+    /// Like [`Self::load`] for an encoded image in memory. The bytes are hashed before decoding,
+    /// then the texels, an image encoded differently is still shared.
+    #[cfg(feature = "images")]
+    pub fn load_bytes(&mut self, bytes: &[u8]) -> Result<TextureId, Box<dyn Error>> {
+        let hash = content_hash(bytes);
+        if let Some(&id) = self.by_content.get(&hash) {
+            return Ok(self.acquire(id));
+        }
+        let texture = decode(bytes)?;
+        let texels_hash = texture_hash(&texture);
+        if let Some(id) = self.find_texels(texels_hash, &texture) {
+            self.by_content.insert(hash, id);
+            self.slots[id.index()]
+                .entry
+                .as_mut()
+                .unwrap()
+                .hashes
+                .push(hash);
+            return Ok(self.acquire(id));
+        }
+        Ok(self.insert_entry(texture, vec![hash, texels_hash]))
+    }
+
+    /// A texture of its own even when its bytes match another, for the textures the application
+    /// keeps writing to, video streams.
+    #[cfg(feature = "images")]
+    pub fn load_unique(&mut self, bytes: &[u8]) -> Result<TextureId, Box<dyn Error>> {
+        Ok(self.insert_entry(decode(bytes)?, Vec::new()))
+    }
+
+    /// Add decoded texels covering a whole texture, shared with a texture of the same size and texels.
+    pub fn insert(&mut self, texture: TextureUpdate) -> TextureId {
+        let hash = texture_hash(&texture);
+        match self.find_texels(hash, &texture) {
+            Some(id) => self.acquire(id),
+            None => self.insert_entry(texture, vec![hash]),
+        }
+    }
+
+    /// Like [`Self::load_unique`] for decoded texels.
+    pub fn insert_unique(&mut self, texture: TextureUpdate) -> TextureId {
+        self.insert_entry(texture, Vec::new())
+    }
+
+    pub fn get(&self, id: TextureId) -> Option<&TextureUpdate> {
+        self.entry(id).map(|entry| &entry.texture)
+    }
+
+    /// Loads of `id` not released yet, 0 once freed.
+    pub fn references(&self, id: TextureId) -> u32 {
+        self.entry(id).map_or(0, |entry| entry.references)
+    }
+
+    /// Give back one reference to `id`, the texture is freed with its last. Returns whether it was.
+    pub fn release(&mut self, id: TextureId) -> bool {
+        let Some(slot) = self
+            .slots
+            .get_mut(id.index())
+            .filter(|slot| slot.generation == id.generation())
+        else {
+            return false;
+        };
+        let Some(entry) = slot.entry.as_mut() else {
+            return false;
+        };
+        entry.references -= 1;
+        if entry.references > 0 {
+            return false;
+        }
+        for hash in &entry.hashes {
+            if self.by_content.get(hash) == Some(&id) {
+                self.by_content.remove(hash);
+            }
+        }
+        slot.entry = None;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(id.index());
+        true
+    }
+
+    /// Textures held, each counted once however many references it has.
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn entry(&self, id: TextureId) -> Option<&TextureEntry> {
+        self.slots
+            .get(id.index())
+            .filter(|slot| slot.generation == id.generation())
+            .and_then(|slot| slot.entry.as_ref())
+    }
+
+    /// Held texture of the same size and texels as `texture`, `hash` being its [`texture_hash`].
+    fn find_texels(&self, hash: u64, texture: &TextureUpdate) -> Option<TextureId> {
+        let id = *self.by_content.get(&hash)?;
+        let held = self.get(id)?;
+        (held.region == texture.region && held.data == texture.data).then_some(id)
+    }
+
+    /// Count a new reference to the held texture `id`.
+    fn acquire(&mut self, id: TextureId) -> TextureId {
+        let entry = self.slots[id.index()].entry.as_mut().unwrap();
+        entry.references += 1;
+        metrics::count_dedup_hit(entry.texture.data.len() as u64);
+        id
+    }
+
+    fn insert_entry(&mut self, texture: TextureUpdate, hashes: Vec<u64>) -> TextureId {
+        let keys = hashes.clone();
+        let entry = TextureEntry {
+            texture,
+            hashes,
+            references: 1,
+        };
+        let id = match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index];
+                slot.entry = Some(entry);
+                TextureId::new(index, slot.generation)
+            }
+            None => {
+                self.slots.push(TextureSlot {
+                    generation: 0,
+                    entry: Some(entry),
+                });
+                TextureId::new(self.slots.len() - 1, 0)
+            }
+        };
+        for hash in keys {
+            self.by_content.insert(hash, id);
+        }
+        id
+    }
+}
+
+#[cfg(feature = "images")]
+fn decode(bytes: &[u8]) -> Result<TextureUpdate, Box<dyn Error>> {
+    use crate::texture::TextureRegion;
+
+    let image = image::load_from_memory(bytes)?.into_rgba8();
+    TextureUpdate::new(
+        TextureRegion::full(image.width(), image.height()),
+        image.into_raw(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture::TextureRegion;
+    use glam::{Mat4, Vec3};
+
+    fn cube_at(x: f32) -> Mesh {
+        let mut cube = Mesh::cube(0.5, [0.8, 0.4, 0.2, 1.0]);
+        cube.transform = Mat4::from_translation(Vec3::new(x, 0.0, 0.0));
+        cube
+    }
+
+    fn texels(value: u8) -> TextureUpdate {
+        TextureUpdate::new(TextureRegion::full(2, 2), vec![value; 16]).unwrap()
+    }
+
+    #[cfg(feature = "images")]
+    #[test]
+    fn one_picture_is_held_once_whatever_its_path() {
+        const PICTURE: &str = "assets/img/picture.png";
+        let mut textures = TextureLibrary::default();
+        let copy =
+            std::env::temp_dir().join(format!("pulsar_asset_dedup_{}.png", std::process::id()));
+        std::fs::copy(PICTURE, &copy).unwrap();
+        let before = metrics::dedup_stats();
+
+        let first = textures.load(Path::new(PICTURE)).unwrap();
+        let size = textures.get(first).unwrap().data.len() as u64;
+        let loads = [
+            textures.load(Path::new("./assets/img/../img/picture.png")),
+            textures.load(&copy),
+            textures.load_bytes(&std::fs::read(PICTURE).unwrap()),
+        ];
+        std::fs::remove_file(&copy).unwrap();
+        for id in loads {
+            assert_eq!(id.unwrap(), first);
+        }
+        assert_eq!(textures.len(), 1);
+        assert_eq!(textures.references(first), 4);
+        // Other tests may count hits meanwhile
+        let stats = metrics::dedup_stats();
+        assert!(stats.hits >= before.hits + 3, "{before:?} {stats:?}");
+        assert!(stats.bytes_saved >= before.bytes_saved + 3 * size);
+
+        // A video stream writes to its own texture
+        let unique = textures
+            .load_unique(&std::fs::read(PICTURE).unwrap())
+            .unwrap();
+        assert_ne!(unique, first);
+        assert_eq!(textures.len(), 2);
+    }
+
+    #[test]
+    fn identical_texels_share_a_texture_until_released() {
+        let mut textures = TextureLibrary::default();
+        let first = textures.insert(texels(255));
+        assert_eq!(textures.insert(texels(255)), first);
+        assert_eq!(textures.insert(texels(255)), first);
+        assert_ne!(textures.insert(texels(0)), first);
+        assert_ne!(textures.insert_unique(texels(255)), first);
+        assert_eq!(textures.len(), 3);
+
+        // Freeing references keeps the texture for the others
+        for _ in 0..2 {
+            assert!(!textures.release(first));
+            assert!(textures.get(first).is_some());
+        }
+        assert!(textures.release(first));
+        assert!(textures.get(first).is_none());
+        // A freed texture's id is not handed out again
+        assert_ne!(textures.insert(texels(255)), first);
+    }
+
+    #[test]
+    fn geometry_hash_leaves_the_transform_out() {
+        assert_eq!(geometry_hash(&cube_at(-1.0)), geometry_hash(&cube_at(1.0)));
+        assert_ne!(
+            geometry_hash(&cube_at(0.0)),
+            geometry_hash(&Mesh::cube(1.0, [1.0; 4]))
+        );
+    }
+}
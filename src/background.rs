@@ -0,0 +1,198 @@
+//! What the window draws behind the scene: the clear color, or a fullscreen fragment shader.
+//!
+//! A shader background is drawn with a single triangle covering the viewport before any mesh,
+//! without depth writes. Its fragment shader receives [`BackgroundConstants`] as push constants:
+//!
+//! ```glsl
+//! layout(push_constant) uniform Background {
+//!     vec2 resolution; // framebuffer size in pixels
+//!     vec2 cursor;     // cursor in pixels from the top left, -1 outside the window
+//!     float time;      // simulation seconds since the background was set, see `time_control`
+//!     vec4 user[2];    // set with `Application::set_background_uniforms`
+//! } background;
+//! layout(location = 0) in vec2 uv; // 0 at the top left, 1 at the bottom right
+//! ```
+
+use crate::{
+    error::PulsarError,
+    shader_include,
+    shaders::{compile_glsl, SHADERS_SOURCE_PATH},
+};
+use ash::{util::read_spv, vk};
+use std::{
+    io::Cursor,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Floats of the application's block, [`BackgroundConstants::user`].
+pub const BACKGROUND_USER_FLOATS: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum BackgroundMode {
+    /// `GraphicsConfig::clear_color`.
+    #[default]
+    Clear,
+    /// Fullscreen pass of the fragment shader at `fragment_source`, GLSL compiled as the engine's
+    /// shaders are or SPIR-V when it ends in `.spv`. Reloaded when the file or one it includes
+    /// changes, a source that fails to compile keeps the previous background.
+    Shader { fragment_source: PathBuf },
+}
+
+/// Push constants of the background's fragment shader, see the module documentation.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct BackgroundConstants {
+    pub resolution: [f32; 2],
+    pub cursor: [f32; 2],
+    pub time: f32,
+    pub padding: [f32; 3],
+    pub user: [f32; BACKGROUND_USER_FLOATS],
+}
+
+impl BackgroundConstants {
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            std::slice::from_raw_parts(
+                self as *const Self as *const u8,
+                std::mem::size_of::<Self>(),
+            )
+        }
+    }
+}
+
+/// SPIR-V of a background's fragment shader, with the modification time it was read at.
+#[derive(Debug, Clone)]
+pub struct BackgroundShader {
+    pub path: PathBuf,
+    pub code: Vec<u32>,
+    modified: Option<SystemTime>,
+}
+
+impl BackgroundShader {
+    /// Read or compile the fragment shader at `path`.
+    pub fn load(path: &Path) -> Result<Self, PulsarError> {
+        let modified = modified(path);
+        Ok(Self {
+            path: path.to_path_buf(),
+            code: compile(path)?,
+            modified,
+        })
+    }
+
+    /// Compile the source again when it changed since it was last read, returns whether the code
+    /// was replaced. On failure the previous code is kept, the change is not retried until the
+    /// file changes again.
+    pub fn reload_if_changed(&mut self) -> Result<bool, PulsarError> {
+        let modified = modified(&self.path);
+        if modified == self.modified {
+            return Ok(false);
+        }
+        self.modified = modified;
+        self.code = compile(&self.path)?;
+        Ok(true)
+    }
+}
+
+/// Of the file, or of the newest of a GLSL source and its includes.
+fn modified(path: &Path) -> Option<SystemTime> {
+    if is_spirv(path) {
+        std::fs::metadata(path)
+            .and_then(|meta| meta.modified())
+            .ok()
+    } else {
+        shader_include::newest_modified(path, Path::new(SHADERS_SOURCE_PATH))
+    }
+}
+
+fn is_spirv(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "spv")
+}
+
+fn compile(path: &Path) -> Result<Vec<u32>, PulsarError> {
+    let error = |reason: String| PulsarError::ShaderCompile {
+        path: path.to_path_buf(),
+        reason,
+    };
+    let bytes = if is_spirv(path) {
+        std::fs::read(path).map_err(|err| error(err.to_string()))?
+    } else {
+        compile_glsl(path, vk::ShaderStageFlags::FRAGMENT).map_err(error)?
+    };
+    let code = read_spv(&mut Cursor::new(bytes)).map_err(|err| error(err.to_string()))?;
+    // Magic number, version, generator, bound and schema come before any instruction
+    if code.len() <= 5 {
+        return Err(error("no instructions".into()));
+    }
+    Ok(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs::File, mem, time::Duration};
+
+    /// Header of a SPIR-V module declaring only the shader capability, or garbage words.
+    fn write_spirv(path: &Path, valid: bool, modified: SystemTime) {
+        let words: &[u32] = if valid {
+            &[0x0723_0203, 0x0001_0000, 0, 1, 0, (2 << 16) | 17, 1]
+        } else {
+            &[0xdead_beef, 0, 0]
+        };
+        let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+        std::fs::write(path, bytes).unwrap();
+        // Explicit so the change is seen on file systems with a coarse modification time
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+    }
+
+    #[test]
+    fn push_constants_match_the_documented_block() {
+        assert_eq!(BackgroundMode::default(), BackgroundMode::Clear);
+        // The GLSL block aligns `user` on 16 bytes after `time`
+        assert_eq!(mem::size_of::<BackgroundConstants>(), 64);
+        assert_eq!(mem::offset_of!(BackgroundConstants, user), 32);
+        assert_eq!(BackgroundConstants::default().as_bytes().len(), 64);
+    }
+
+    #[test]
+    fn broken_edits_keep_the_previous_shader() {
+        let path =
+            std::env::temp_dir().join(format!("pulsar_background_{}.spv", std::process::id()));
+        let epoch = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        write_spirv(&path, true, epoch);
+        let mut shader = BackgroundShader::load(&path).unwrap();
+        let working = shader.code.clone();
+        assert!(
+            !shader.reload_if_changed().unwrap(),
+            "unchanged source reloaded"
+        );
+
+        // Not retried until the file changes again
+        write_spirv(&path, false, epoch + Duration::from_secs(1));
+        let broken = shader.reload_if_changed();
+        assert!(
+            matches!(broken, Err(PulsarError::ShaderCompile { .. })),
+            "{broken:?}"
+        );
+        assert_eq!(shader.code, working);
+        assert!(
+            !shader.reload_if_changed().unwrap(),
+            "broken shader compiled twice"
+        );
+
+        write_spirv(&path, true, epoch + Duration::from_secs(2));
+        let fixed = shader.reload_if_changed();
+        std::fs::remove_file(&path).unwrap();
+        assert!(fixed.unwrap(), "fixed shader not reloaded");
+        assert_eq!(shader.code, working);
+        assert!(
+            BackgroundShader::load(&path).is_err(),
+            "missing shader loaded"
+        );
+    }
+}
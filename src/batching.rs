@@ -0,0 +1,260 @@
+//! Static meshes merged into one vertex buffer and one index buffer, drawn with a single bind.
+
+use crate::{
+    model::{Mesh, RegisteredMesh, Vertex},
+    vulkan::{device::AAADevice, upload::AAAUploadContext},
+};
+use ash::vk;
+use glam::{Mat4, Vec3};
+use log::info;
+use std::ops::Range;
+
+/// Where one of the merged meshes lies in the batch's buffers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BatchRange {
+    pub first_index: u32,
+    pub index_count: u32,
+    /// First vertex of the mesh. Indices are rebased onto it already, draws pass 0 as the offset.
+    pub vertex_offset: u32,
+    pub vertex_count: u32,
+    /// The mesh's own transform, applied before the batch's.
+    pub transform: Mat4,
+    /// The mesh's [`Mesh::user_id`], reported when the range is picked.
+    pub user_id: u64,
+}
+
+impl BatchRange {
+    pub fn indices(&self) -> Range<usize> {
+        self.first_index as usize..(self.first_index + self.index_count) as usize
+    }
+
+    pub fn vertices(&self) -> Range<usize> {
+        self.vertex_offset as usize..(self.vertex_offset + self.vertex_count) as usize
+    }
+}
+
+/// Transform every range shares, identity for no range. `None` when the ranges are drawn one by one.
+pub fn shared_transform(ranges: &[BatchRange]) -> Option<Mat4> {
+    match ranges {
+        [] => Some(Mat4::IDENTITY),
+        [first, rest @ ..] => rest
+            .iter()
+            .all(|range| range.transform == first.transform)
+            .then_some(first.transform),
+    }
+}
+
+/// Meshes registered together, in a single allocation per buffer. The batch is one mesh for
+/// picking, selection, the gizmo and residency; each merged mesh keeps its own transform.
+#[derive(Debug)]
+pub struct MeshBatch {
+    /// Concatenated vertices in their own mesh's space, identity transform.
+    mesh: Mesh,
+    ranges: Vec<BatchRange>,
+}
+
+impl MeshBatch {
+    pub fn new(meshes: Vec<Mesh>) -> Self {
+        let mut merged = Mesh {
+            vertices: Vec::with_capacity(meshes.iter().map(|mesh| mesh.vertices.len()).sum()),
+            indices: Vec::with_capacity(meshes.iter().map(|mesh| mesh.indices.len()).sum()),
+            transform: Mat4::IDENTITY,
+            user_id: 0,
+        };
+        let mut ranges = Vec::with_capacity(meshes.len());
+        for mesh in meshes {
+            let range = BatchRange {
+                first_index: merged.indices.len() as u32,
+                index_count: mesh.indices.len() as u32,
+                vertex_offset: merged.vertices.len() as u32,
+                vertex_count: mesh.vertices.len() as u32,
+                transform: mesh.transform,
+                user_id: mesh.user_id,
+            };
+            merged.indices.extend(
+                mesh.indices
+                    .iter()
+                    .map(|&index| index + range.vertex_offset),
+            );
+            merged.vertices.extend(mesh.vertices);
+            ranges.push(range);
+        }
+        Self {
+            mesh: merged,
+            ranges,
+        }
+    }
+
+    pub fn ranges(&self) -> &[BatchRange] {
+        &self.ranges
+    }
+
+    pub fn mesh(&self) -> &Mesh {
+        &self.mesh
+    }
+
+    /// See [`shared_transform`], a batch with one is drawn with a single `cmd_draw_indexed`.
+    pub fn shared_transform(&self) -> Option<Mat4> {
+        shared_transform(&self.ranges)
+    }
+
+    /// A single mesh with each range's transform applied to its vertices, unless they all share
+    /// one which becomes the mesh's transform.
+    pub fn into_mesh(self) -> Mesh {
+        let mut mesh = self.mesh;
+        match shared_transform(&self.ranges) {
+            Some(transform) => mesh.transform = transform,
+            None => {
+                for range in &self.ranges {
+                    bake(&mut mesh.vertices[range.vertices()], range.transform);
+                }
+            }
+        }
+        mesh
+    }
+
+    /// Uploaded like [`Mesh::register`]. A batch over the device limits would be split, its
+    /// ranges no longer matching the buffers, it is registered baked into a single mesh instead.
+    pub(crate) fn register(
+        self,
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        upload: Option<AAAUploadContext>,
+    ) -> RegisteredMesh {
+        if !device.mesh_limits.fits(&self.mesh) {
+            info!(
+                "Batch of {} meshes exceeds the device limits, registered as a single mesh",
+                self.ranges.len()
+            );
+            return self
+                .into_mesh()
+                .register(device, device_memory_properties, upload);
+        }
+        let mut registered_mesh = self.mesh.register(device, device_memory_properties, upload);
+        registered_mesh.batch = self.ranges;
+        registered_mesh
+    }
+}
+
+/// Move `vertices` by `transform`, normals and tangents by its inverse transpose.
+fn bake(vertices: &mut [Vertex], transform: Mat4) {
+    if transform == Mat4::IDENTITY {
+        return;
+    }
+    let normal_matrix = transform.inverse().transpose();
+    let handedness = transform.determinant().signum();
+    for vertex in vertices {
+        let pos = transform.transform_point3(Vec3::from_slice(&vertex.pos[..3]));
+        vertex.pos = pos.extend(1.0).to_array();
+        let normal = normal_matrix.transform_vector3(Vec3::from_array(vertex.normal));
+        vertex.normal = normal.normalize_or_zero().to_array();
+        let tangent = transform.transform_vector3(Vec3::from_slice(&vertex.tangent[..3]));
+        vertex.tangent = tangent
+            .normalize_or_zero()
+            .extend(vertex.tangent[3] * handedness)
+            .to_array();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::picking::{pick_mesh_ranges, Ray};
+    use std::sync::Arc;
+
+    const GRID: i32 = 10;
+
+    /// A cube per cell, each placed by its own transform.
+    fn cubes() -> Vec<Mesh> {
+        let mut cubes = Vec::new();
+        for z in -GRID / 2..GRID / 2 {
+            for x in -GRID / 2..GRID / 2 {
+                let mut cube = Mesh::cube(0.5, [0.3, 0.7, 0.4, 1.0]);
+                cube.transform = Mat4::from_translation(Vec3::new(x as f32, 0.0, z as f32));
+                cubes.push(cube);
+            }
+        }
+        cubes
+    }
+
+    #[test]
+    fn batch_holds_every_mesh_once() {
+        let cube = Mesh::cube(0.5, [1.0; 4]);
+        let batch = MeshBatch::new(cubes());
+        let count = (GRID * GRID) as usize;
+        assert_eq!(batch.ranges().len(), count);
+        assert_eq!(batch.mesh().vertices.len(), count * cube.vertices.len());
+        assert_eq!(batch.mesh().indices.len(), count * cube.indices.len());
+        // Indices are rebased onto their own cube's vertices
+        for range in batch.ranges() {
+            for &index in &batch.mesh().indices[range.indices()] {
+                assert!(range.vertices().contains(&(index as usize)), "{range:?}");
+            }
+        }
+        // Cubes placed apart need a draw each
+        assert_eq!(batch.shared_transform(), None);
+    }
+
+    #[test]
+    fn merge_bakes_the_placements() {
+        let batch = MeshBatch::new(cubes());
+        let merged = Mesh::merge(cubes());
+        assert_eq!(merged.transform, Mat4::IDENTITY);
+        let last = batch.ranges().last().unwrap();
+        let center = merged.vertices[last.vertices()]
+            .iter()
+            .map(|vertex| Vec3::from_slice(&vertex.pos[..3]))
+            .sum::<Vec3>()
+            / last.vertex_count as f32;
+        let expected = last.transform.transform_point3(Vec3::ZERO);
+        assert!(
+            (center - expected).length() < 1e-4,
+            "last cube centered on {center}, expected {expected}"
+        );
+    }
+
+    #[test]
+    fn shared_transform_is_kept() {
+        let shared = Mat4::from_rotation_y(1.0);
+        let together: Vec<Mesh> = (0..3)
+            .map(|_| {
+                let mut cube = Mesh::cube(1.0, [1.0; 4]);
+                cube.transform = shared;
+                cube
+            })
+            .collect();
+        let batch = MeshBatch::new(together);
+        assert_eq!(batch.shared_transform(), Some(shared));
+        assert_eq!(batch.into_mesh().transform, shared);
+    }
+
+    #[test]
+    fn picked_ranges_report_their_mesh_user_id() {
+        let meshes: Vec<Mesh> = cubes()
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut cube)| {
+                cube.user_id = 100 + i as u64;
+                cube
+            })
+            .collect();
+        let batch = MeshBatch::new(meshes);
+        let user_ids: Vec<u64> = batch.ranges().iter().map(|range| range.user_id).collect();
+        assert_eq!(
+            user_ids,
+            (100..100 + (GRID * GRID) as u64).collect::<Vec<_>>()
+        );
+
+        let mut registered_mesh = batch.mesh.share(Arc::new(Vec::new()));
+        registered_mesh.batch = batch.ranges;
+        let parts = registered_mesh.picked_parts();
+        let meshes = parts.into_iter().map(|(user_id, transform, indices)| {
+            (user_id, &registered_mesh.mesh, transform, indices)
+        });
+        // Straight down onto the cube at x 1, z 2
+        let ray = Ray::new(Vec3::new(1.0, 5.0, 2.0), Vec3::NEG_Y);
+        let hit = pick_mesh_ranges(&ray, meshes).expect("the ray should hit a cube");
+        let cell = (2 + GRID / 2) * GRID + 1 + GRID / 2;
+        assert_eq!(hit.user_id, 100 + cell as u64);
+    }
+}
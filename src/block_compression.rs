@@ -0,0 +1,773 @@
+//! Textures compressed offline into BC1, BC3 or BC7 blocks of 4x4 texels, from a KTX2 or DDS
+//! loader, uploaded as they are. Devices that cannot sample the format get the first level decoded
+//! to RGBA8 instead, see [`CompressedTexture::decode_rgba8`].
+use crate::{error::PulsarError, texture::TEXEL_SIZE};
+use ash::vk;
+
+/// Texels along each edge of a block.
+pub const BLOCK_EDGE: u32 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlockFormat {
+    /// Four colors between two RGB565 endpoints, or three and transparent black. 8 bytes a block.
+    Bc1,
+    /// BC1 colors and eight alpha values between two endpoints. 16 bytes a block.
+    Bc3,
+    /// RGBA in one of eight modes picked per block, up to three subsets of endpoints. 16 bytes a
+    /// block.
+    Bc7,
+}
+
+impl BlockFormat {
+    pub const ALL: [Self; 3] = [Self::Bc1, Self::Bc3, Self::Bc7];
+
+    pub fn block_bytes(self) -> usize {
+        match self {
+            Self::Bc1 => 8,
+            Self::Bc3 | Self::Bc7 => 16,
+        }
+    }
+
+    /// Vulkan format of the blocks, `_SRGB` when the texels are sampled linear like the RGBA8
+    /// textures.
+    pub fn vk_format(self, srgb: bool) -> vk::Format {
+        match (self, srgb) {
+            (Self::Bc1, false) => vk::Format::BC1_RGBA_UNORM_BLOCK,
+            (Self::Bc1, true) => vk::Format::BC1_RGBA_SRGB_BLOCK,
+            (Self::Bc3, false) => vk::Format::BC3_UNORM_BLOCK,
+            (Self::Bc3, true) => vk::Format::BC3_SRGB_BLOCK,
+            (Self::Bc7, false) => vk::Format::BC7_UNORM_BLOCK,
+            (Self::Bc7, true) => vk::Format::BC7_SRGB_BLOCK,
+        }
+    }
+}
+
+/// Blocks across and down a `width` by `height` level, the partial blocks of the right and bottom
+/// edges included.
+pub fn block_count(width: u32, height: u32) -> (u32, u32) {
+    (width.div_ceil(BLOCK_EDGE), height.div_ceil(BLOCK_EDGE))
+}
+
+/// Texels of mip `level` of a `width` by `height` texture, halved per level down to 1.
+pub fn level_extent(width: u32, height: u32, level: u32) -> (u32, u32) {
+    ((width >> level).max(1), (height >> level).max(1))
+}
+
+/// Bytes of the blocks of a `width` by `height` level.
+pub fn level_size(format: BlockFormat, width: u32, height: u32) -> usize {
+    let (columns, rows) = block_count(width, height);
+    columns as usize * rows as usize * format.block_bytes()
+}
+
+/// Mip levels of compressed blocks, the largest first, each level's blocks row by row from the top
+/// left.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressedTexture {
+    pub format: BlockFormat,
+    pub width: u32,
+    pub height: u32,
+    levels: Vec<Vec<u8>>,
+}
+
+impl CompressedTexture {
+    /// At least the first level and at most the full chain down to 1x1, each [`level_size`] bytes.
+    /// Other sizes are a [`PulsarError::CompressedLevel`], a level past the chain expects 0.
+    pub fn new(
+        format: BlockFormat,
+        width: u32,
+        height: u32,
+        levels: Vec<Vec<u8>>,
+    ) -> Result<Self, PulsarError> {
+        let chain = u32::BITS - width.max(height).leading_zeros();
+        if levels.is_empty() || width == 0 || height == 0 {
+            return Err(PulsarError::CompressedLevel {
+                level: 0,
+                len: levels.first().map_or(0, Vec::len),
+                expected: level_size(format, width, height),
+            });
+        }
+        for (level, data) in levels.iter().enumerate() {
+            let level = level as u32;
+            let expected = if level < chain {
+                let (width, height) = level_extent(width, height, level);
+                level_size(format, width, height)
+            } else {
+                0
+            };
+            if data.len() != expected {
+                return Err(PulsarError::CompressedLevel {
+                    level,
+                    len: data.len(),
+                    expected,
+                });
+            }
+        }
+        Ok(Self {
+            format,
+            width,
+            height,
+            levels,
+        })
+    }
+
+    pub fn levels(&self) -> &[Vec<u8>] {
+        &self.levels
+    }
+
+    /// The levels one after the other, as staged for [`Self::copy_regions`].
+    pub fn data(&self) -> Vec<u8> {
+        self.levels.concat()
+    }
+
+    /// One copy per level out of [`Self::data`]. Rows and images are whole blocks in the buffer,
+    /// while the copied extent stops at the level's edge as the copy requires for partial blocks.
+    pub fn copy_regions(&self) -> Vec<vk::BufferImageCopy> {
+        let mut offset = 0;
+        let mut regions = Vec::with_capacity(self.levels.len());
+        for (level, data) in self.levels.iter().enumerate() {
+            let (width, height) = level_extent(self.width, self.height, level as u32);
+            let (columns, rows) = block_count(width, height);
+            regions.push(
+                vk::BufferImageCopy::default()
+                    .buffer_offset(offset)
+                    .buffer_row_length(columns * BLOCK_EDGE)
+                    .buffer_image_height(rows * BLOCK_EDGE)
+                    .image_subresource(
+                        vk::ImageSubresourceLayers::default()
+                            .aspect_mask(vk::ImageAspectFlags::COLOR)
+                            .mip_level(level as u32)
+                            .layer_count(1),
+                    )
+                    .image_extent(vk::Extent3D {
+                        width,
+                        height,
+                        depth: 1,
+                    }),
+            );
+            offset += data.len() as u64;
+        }
+        regions
+    }
+
+    /// The first level as RGBA8 texels, rows tightly packed, for devices that cannot sample the
+    /// format. The texels of partial blocks past the edges are dropped.
+    pub fn decode_rgba8(&self) -> Vec<u8> {
+        let texel = TEXEL_SIZE as usize;
+        let (width, height) = (self.width as usize, self.height as usize);
+        let columns = block_count(self.width, self.height).0 as usize;
+        let mut texels = vec![0; width * height * texel];
+        for (index, block) in self.levels[0]
+            .chunks_exact(self.format.block_bytes())
+            .enumerate()
+        {
+            let decoded = decode_block(self.format, block);
+            let (block_x, block_y) = (index % columns * 4, index / columns * 4);
+            for (i, rgba) in decoded.iter().enumerate() {
+                let (x, y) = (block_x + i % 4, block_y + i / 4);
+                if x < width && y < height {
+                    let start = (y * width + x) * texel;
+                    texels[start..start + texel].copy_from_slice(rgba);
+                }
+            }
+        }
+        texels
+    }
+}
+
+/// RGBA8 texels of one block of `format`, row by row. `block` is [`BlockFormat::block_bytes`]
+/// long.
+pub fn decode_block(format: BlockFormat, block: &[u8]) -> [[u8; 4]; 16] {
+    match format {
+        BlockFormat::Bc1 => decode_colors(block, false),
+        BlockFormat::Bc3 => {
+            let mut texels = decode_colors(&block[8..], true);
+            for (texel, alpha) in texels.iter_mut().zip(decode_alpha(block)) {
+                texel[3] = alpha;
+            }
+            texels
+        }
+        BlockFormat::Bc7 => decode_bc7(block),
+    }
+}
+
+fn rgb565(color: u16) -> [u8; 4] {
+    let (r, g, b) = (color >> 11 & 31, color >> 5 & 63, color & 31);
+    [
+        (r << 3 | r >> 2) as u8,
+        (g << 2 | g >> 4) as u8,
+        (b << 3 | b >> 2) as u8,
+        255,
+    ]
+}
+
+/// The 8 bytes of a BC1 block. `opaque` always interpolates four colors, as BC3 does whatever
+/// the order of the endpoints.
+fn decode_colors(block: &[u8], opaque: bool) -> [[u8; 4]; 16] {
+    let color0 = u16::from_le_bytes([block[0], block[1]]);
+    let color1 = u16::from_le_bytes([block[2], block[3]]);
+    let (first, second) = (rgb565(color0), rgb565(color1));
+    let mix = |weight0: u16, weight1: u16| -> [u8; 4] {
+        let total = weight0 + weight1;
+        let channel =
+            |i: usize| ((first[i] as u16 * weight0 + second[i] as u16 * weight1) / total) as u8;
+        [channel(0), channel(1), channel(2), 255]
+    };
+    let palette = if color0 > color1 || opaque {
+        [first, second, mix(2, 1), mix(1, 2)]
+    } else {
+        [first, second, mix(1, 1), [0; 4]]
+    };
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    std::array::from_fn(|i| palette[(indices >> (2 * i) & 3) as usize])
+}
+
+/// The first 8 bytes of a BC3 block.
+fn decode_alpha(block: &[u8]) -> [u8; 16] {
+    let (alpha0, alpha1) = (block[0] as u32, block[1] as u32);
+    let mut palette = [0; 8];
+    palette[0] = alpha0;
+    palette[1] = alpha1;
+    if alpha0 > alpha1 {
+        for i in 1..7 {
+            palette[i + 1] = ((7 - i as u32) * alpha0 + i as u32 * alpha1) / 7;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = ((5 - i as u32) * alpha0 + i as u32 * alpha1) / 5;
+        }
+        palette[7] = 255;
+    }
+    let mut bits = [0; 8];
+    bits[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(bits);
+    std::array::from_fn(|i| palette[(indices >> (3 * i) & 7) as usize] as u8)
+}
+
+/// Field widths of a BC7 mode, in bits.
+struct Bc7Mode {
+    subsets: usize,
+    partition: u32,
+    rotation: u32,
+    selection: u32,
+    color: u32,
+    alpha: u32,
+    /// A P-bit per endpoint, or one shared by both endpoints of a subset.
+    endpoint_pbits: bool,
+    shared_pbits: bool,
+    indices: u32,
+    /// Separate alpha or color indices of modes 4 and 5, 0 without.
+    secondary_indices: u32,
+}
+
+#[allow(clippy::too_many_arguments)]
+const fn mode(
+    subsets: usize,
+    partition: u32,
+    rotation: u32,
+    selection: u32,
+    color: u32,
+    alpha: u32,
+    pbits: (bool, bool),
+    indices: (u32, u32),
+) -> Bc7Mode {
+    Bc7Mode {
+        subsets,
+        partition,
+        rotation,
+        selection,
+        color,
+        alpha,
+        endpoint_pbits: pbits.0,
+        shared_pbits: pbits.1,
+        indices: indices.0,
+        secondary_indices: indices.1,
+    }
+}
+
+const BC7_MODES: [Bc7Mode; 8] = [
+    mode(3, 4, 0, 0, 4, 0, (true, false), (3, 0)),
+    mode(2, 6, 0, 0, 6, 0, (false, true), (3, 0)),
+    mode(3, 6, 0, 0, 5, 0, (false, false), (2, 0)),
+    mode(2, 6, 0, 0, 7, 0, (true, false), (2, 0)),
+    mode(1, 0, 2, 1, 5, 6, (false, false), (2, 3)),
+    mode(1, 0, 2, 0, 7, 8, (false, false), (2, 2)),
+    mode(1, 0, 0, 0, 7, 7, (true, false), (4, 0)),
+    mode(2, 6, 0, 0, 5, 5, (true, false), (2, 0)),
+];
+
+/// Texels of the second subset of each two subset partition, bit `i` for texel `i`.
+const PARTITIONS_2: [u16; 64] = [
+    0xcccc, 0x8888, 0xeeee, 0xecc8, 0xc880, 0xfeec, 0xfec8, 0xec80, 0xc800, 0xffec, 0xfe80, 0xe800,
+    0xffe8, 0xff00, 0xfff0, 0xf000, 0xf710, 0x008e, 0x7100, 0x08ce, 0x008c, 0x7310, 0x3100, 0x8cce,
+    0x088c, 0x3110, 0x6666, 0x366c, 0x17e8, 0x0ff0, 0x718e, 0x399c, 0xaaaa, 0xf0f0, 0x5a5a, 0x33cc,
+    0x3c3c, 0x55aa, 0x9696, 0xa55a, 0x73ce, 0x13c8, 0x324c, 0x3bdc, 0x6996, 0xc33c, 0x9966, 0x0660,
+    0x0272, 0x04e4, 0x4e40, 0x2720, 0xc936, 0x936c, 0x39c6, 0x639c, 0x9336, 0x9cc6, 0x817e, 0xe718,
+    0xccf0, 0x0fcc, 0x7744, 0xee22,
+];
+
+/// Subset of each texel of the three subset partitions.
+const PARTITIONS_3: [[u8; 16]; 64] = [
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 1, 2, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 2, 0, 0, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2],
+    [0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0, 2, 2, 2, 0],
+    [0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2],
+    [0, 1, 1, 1, 0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0],
+    [0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2, 0, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 0, 1, 2, 2, 2, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 0, 0, 1, 1, 0, 0, 2, 2, 1, 0, 2, 2, 1, 0],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1, 0, 0, 0, 0],
+    [0, 0, 1, 2, 0, 0, 1, 2, 1, 1, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1, 0, 1, 1, 0],
+    [0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1],
+    [0, 0, 2, 2, 1, 1, 0, 2, 1, 1, 0, 2, 0, 0, 2, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 0, 0, 2, 2, 2, 2, 2],
+    [0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 0, 0, 2, 0, 0, 0, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 2, 0, 0, 2, 2, 0, 2, 2, 2],
+    [0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0],
+    [0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0],
+    [0, 1, 2, 0, 2, 0, 1, 2, 1, 2, 0, 1, 0, 1, 2, 0],
+    [0, 0, 1, 1, 2, 2, 0, 0, 1, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0, 1, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 0, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 1, 1],
+    [0, 2, 2, 0, 1, 2, 2, 1, 0, 2, 2, 0, 1, 2, 2, 1],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 0, 1, 0, 1],
+    [0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 2, 2, 2, 0, 1, 1, 1],
+    [0, 0, 0, 2, 1, 1, 1, 2, 0, 0, 0, 2, 1, 1, 1, 2],
+    [0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2],
+    [0, 0, 0, 2, 1, 1, 1, 2, 1, 1, 1, 2, 0, 0, 0, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2],
+    [0, 0, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2],
+    [0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1],
+    [0, 2, 2, 2, 1, 2, 2, 2, 0, 2, 2, 2, 1, 2, 2, 2],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 1, 2, 0, 1, 1, 2, 2, 0, 1, 2, 2, 2, 0],
+];
+
+/// Anchor texel of the second subset of the two subset partitions, its index is a bit shorter.
+/// The first subset's anchor is always texel 0.
+const ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8,
+    2, 2, 8, 8, 2, 2, 15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2,
+    2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+/// Anchor texels of the second and third subsets of the three subset partitions.
+const ANCHORS_3: [[u8; 2]; 64] = [
+    [3, 15],
+    [3, 8],
+    [15, 8],
+    [15, 3],
+    [8, 15],
+    [3, 15],
+    [15, 3],
+    [15, 8],
+    [8, 15],
+    [8, 15],
+    [6, 15],
+    [6, 15],
+    [6, 15],
+    [5, 15],
+    [3, 15],
+    [3, 8],
+    [3, 15],
+    [3, 8],
+    [8, 15],
+    [15, 3],
+    [3, 15],
+    [3, 8],
+    [6, 15],
+    [10, 8],
+    [5, 3],
+    [8, 15],
+    [8, 6],
+    [6, 10],
+    [8, 15],
+    [5, 15],
+    [15, 10],
+    [15, 8],
+    [8, 15],
+    [15, 3],
+    [3, 15],
+    [5, 10],
+    [6, 10],
+    [10, 8],
+    [8, 9],
+    [15, 10],
+    [15, 6],
+    [3, 15],
+    [15, 8],
+    [5, 15],
+    [15, 3],
+    [15, 6],
+    [15, 6],
+    [15, 8],
+    [3, 15],
+    [15, 3],
+    [5, 15],
+    [5, 15],
+    [5, 15],
+    [8, 15],
+    [5, 15],
+    [10, 15],
+    [5, 15],
+    [10, 15],
+    [8, 15],
+    [13, 15],
+    [15, 3],
+    [12, 15],
+    [3, 15],
+    [3, 8],
+];
+
+const WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+/// Reads a block's fields from its least significant bit on.
+struct Bits {
+    block: u128,
+    position: u32,
+}
+
+impl Bits {
+    fn read(&mut self, count: u32) -> u32 {
+        let value = (self.block >> self.position) as u32 & ((1 << count) - 1);
+        self.position += count;
+        value
+    }
+}
+
+fn interpolate(endpoint0: u32, endpoint1: u32, bits: u32, index: u32) -> u8 {
+    let weight = match bits {
+        2 => WEIGHTS_2[index as usize],
+        3 => WEIGHTS_3[index as usize],
+        _ => WEIGHTS_4[index as usize],
+    };
+    (((64 - weight) * endpoint0 + weight * endpoint1 + 32) >> 6) as u8
+}
+
+/// `value` of `bits` widened to 8 bits, its high bits repeated in the low ones.
+fn unquantize(value: u32, bits: u32) -> u32 {
+    let value = value << (8 - bits);
+    value | value >> bits
+}
+
+fn decode_bc7(block: &[u8]) -> [[u8; 4]; 16] {
+    let block = u128::from_le_bytes(block[..16].try_into().unwrap());
+    let mode_index = (block as u8).trailing_zeros();
+    // The reserved mode decodes to transparent black
+    let Some(mode) = BC7_MODES.get(mode_index as usize) else {
+        return [[0; 4]; 16];
+    };
+    let mut bits = Bits {
+        block,
+        position: mode_index + 1,
+    };
+    let partition = bits.read(mode.partition) as usize;
+    let rotation = bits.read(mode.rotation);
+    let selection = bits.read(mode.selection);
+
+    // Per subset, per endpoint, RGBA
+    let mut endpoints = [[[0; 4]; 2]; 3];
+    for channel in 0..4 {
+        let width = if channel < 3 { mode.color } else { mode.alpha };
+        for subset in endpoints.iter_mut().take(mode.subsets) {
+            for endpoint in subset.iter_mut() {
+                endpoint[channel] = bits.read(width);
+            }
+        }
+    }
+    let pbits = mode.endpoint_pbits || mode.shared_pbits;
+    for subset in endpoints.iter_mut().take(mode.subsets) {
+        let shared = if mode.shared_pbits { bits.read(1) } else { 0 };
+        for endpoint in subset.iter_mut() {
+            let pbit = if mode.endpoint_pbits {
+                bits.read(1)
+            } else {
+                shared
+            };
+            for (channel, value) in endpoint.iter_mut().enumerate() {
+                let width = if channel < 3 { mode.color } else { mode.alpha };
+                *value = match (width, pbits) {
+                    (0, _) => 255,
+                    (width, true) => unquantize(*value << 1 | pbit, width + 1),
+                    (width, false) => unquantize(*value, width),
+                };
+            }
+        }
+    }
+
+    let subset_of = |texel: usize| match mode.subsets {
+        1 => 0,
+        2 => (PARTITIONS_2[partition] >> texel & 1) as usize,
+        _ => PARTITIONS_3[partition][texel] as usize,
+    };
+    let anchor = |texel: usize| {
+        texel == 0
+            || match mode.subsets {
+                1 => false,
+                2 => texel == ANCHORS_2[partition] as usize,
+                _ => ANCHORS_3[partition].contains(&(texel as u8)),
+            }
+    };
+    let indices: [u32; 16] =
+        std::array::from_fn(|texel| bits.read(mode.indices - anchor(texel) as u32));
+    let secondary: [u32; 16] = std::array::from_fn(|texel| match mode.secondary_indices {
+        0 => 0,
+        width => bits.read(width - (texel == 0) as u32),
+    });
+
+    std::array::from_fn(|texel| {
+        let [endpoint0, endpoint1] = endpoints[subset_of(texel)];
+        let primary = (indices[texel], mode.indices);
+        let ((color, color_bits), (alpha, alpha_bits)) = match (mode.secondary_indices, selection) {
+            (0, _) => (primary, primary),
+            (width, 0) => (primary, (secondary[texel], width)),
+            (width, _) => ((secondary[texel], width), primary),
+        };
+        let mut rgba: [u8; 4] = std::array::from_fn(|channel| {
+            let (index, bits) = if channel < 3 {
+                (color, color_bits)
+            } else {
+                (alpha, alpha_bits)
+            };
+            interpolate(endpoint0[channel], endpoint1[channel], bits, index)
+        });
+        if rotation > 0 {
+            rgba.swap(rotation as usize - 1, 3);
+        }
+        rgba
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes the fields of a BC7 block from its least significant bit on.
+    #[derive(Default)]
+    struct Bc7Writer {
+        block: u128,
+        position: u32,
+    }
+
+    impl Bc7Writer {
+        fn write(&mut self, value: u32, bits: u32) -> &mut Self {
+            self.block |= (value as u128) << self.position;
+            self.position += bits;
+            self
+        }
+
+        fn finish(&self) -> [u8; 16] {
+            assert_eq!(self.position, 128, "BC7 block of {} bits", self.position);
+            self.block.to_le_bytes()
+        }
+    }
+
+    /// A BC1 block of `color0` and `color1`, each texel picking `index(texel)`.
+    fn bc1_block(color0: u16, color1: u16, index: impl Fn(u32) -> u32) -> [u8; 8] {
+        let indices = (0..16).fold(0, |indices, texel| indices | index(texel) << (2 * texel));
+        let mut block = [0; 8];
+        block[..2].copy_from_slice(&color0.to_le_bytes());
+        block[2..4].copy_from_slice(&color1.to_le_bytes());
+        block[4..].copy_from_slice(&indices.to_le_bytes());
+        block
+    }
+
+    const RED: u16 = 0xf800;
+    const BLUE: u16 = 0x001f;
+
+    /// Extent, blocks across and down, and bytes of a level.
+    type Level = ((u32, u32), (u32, u32), usize);
+
+    /// The levels of a 10x6 BC1 chain.
+    const CHAIN: [Level; 4] = [
+        ((10, 6), (3, 2), 48),
+        ((5, 3), (2, 1), 16),
+        ((2, 1), (1, 1), 8),
+        ((1, 1), (1, 1), 8),
+    ];
+
+    fn chain_levels() -> Vec<Vec<u8>> {
+        CHAIN.iter().map(|&(_, _, bytes)| vec![0; bytes]).collect()
+    }
+
+    #[test]
+    fn levels_round_up_to_whole_blocks() {
+        for (level, &(extent, _, bytes)) in CHAIN.iter().enumerate() {
+            let actual = level_extent(10, 6, level as u32);
+            assert_eq!(actual, extent, "level {level}");
+            assert_eq!(
+                level_size(BlockFormat::Bc1, actual.0, actual.1),
+                bytes,
+                "level {level}"
+            );
+        }
+        let texture = CompressedTexture::new(BlockFormat::Bc1, 10, 6, chain_levels()).unwrap();
+        let mut offset = 0;
+        for (region, &(extent, blocks, bytes)) in texture.copy_regions().iter().zip(&CHAIN) {
+            assert_eq!(region.buffer_offset, offset, "{region:?}");
+            assert_eq!(
+                (region.image_extent.width, region.image_extent.height),
+                extent,
+                "{region:?}"
+            );
+            assert_eq!(
+                (region.buffer_row_length, region.buffer_image_height),
+                (blocks.0 * BLOCK_EDGE, blocks.1 * BLOCK_EDGE),
+                "{region:?}"
+            );
+            offset += bytes as u64;
+        }
+        assert_eq!(texture.data().len() as u64, offset);
+    }
+
+    #[test]
+    fn mismatched_levels_are_refused() {
+        // A level short of its last partial block, then a level past the 1x1 one
+        let mut short = chain_levels();
+        short[1].truncate(8);
+        let mut long = chain_levels();
+        long.push(vec![0; 8]);
+        for (levels, expected) in [
+            (short, (1, 8, 16)),
+            (long, (4, 8, 0)),
+            (Vec::new(), (0, 0, 48)),
+        ] {
+            match CompressedTexture::new(BlockFormat::Bc1, 10, 6, levels) {
+                Err(PulsarError::CompressedLevel {
+                    level,
+                    len,
+                    expected: size,
+                }) => assert_eq!((level, len, size), expected),
+                other => panic!("{other:?}, expected {expected:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn bc1_and_bc3_blocks_decode_to_their_palettes() {
+        let block = bc1_block(RED, BLUE, |texel| texel % 4);
+        let palette = [
+            [255, 0, 0, 255],
+            [0, 0, 255, 255],
+            [170, 0, 85, 255],
+            [85, 0, 170, 255],
+        ];
+        let expected: [[u8; 4]; 16] = std::array::from_fn(|texel| palette[texel % 4]);
+        assert_eq!(decode_block(BlockFormat::Bc1, &block), expected);
+        // Endpoints in the other order leave three colors and transparent black
+        let block = bc1_block(BLUE, RED, |texel| texel % 4);
+        let palette = [
+            [0, 0, 255, 255],
+            [255, 0, 0, 255],
+            [127, 0, 127, 255],
+            [0; 4],
+        ];
+        let expected: [[u8; 4]; 16] = std::array::from_fn(|texel| palette[texel % 4]);
+        assert_eq!(decode_block(BlockFormat::Bc1, &block), expected);
+
+        let alphas = [255, 0, 218, 182, 145, 109, 72, 36];
+        let indices = (0..16u64).fold(0, |indices, texel| indices | (texel % 8) << (3 * texel));
+        let mut block = [0; 16];
+        block[..2].copy_from_slice(&[255, 0]);
+        block[2..8].copy_from_slice(&indices.to_le_bytes()[..6]);
+        block[8..].copy_from_slice(&bc1_block(0xffff, 0xffff, |_| 0));
+        let expected: [[u8; 4]; 16] =
+            std::array::from_fn(|texel| [255, 255, 255, alphas[texel % 8]]);
+        assert_eq!(decode_block(BlockFormat::Bc3, &block), expected);
+    }
+
+    #[test]
+    fn bc7_blocks_decode_to_their_endpoints() {
+        // Mode 6: black to white with a 4 bit index per texel, alpha from 254 to 255
+        let mut mode6 = Bc7Writer::default();
+        mode6.write(1 << 6, 7);
+        for _ in 0..3 {
+            mode6.write(0, 7).write(127, 7);
+        }
+        mode6.write(127, 7).write(127, 7).write(0, 1).write(1, 1);
+        mode6.write(0, 3);
+        for texel in 1..16 {
+            mode6.write(texel, 4);
+        }
+        let weights = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+        let expected: [[u8; 4]; 16] = std::array::from_fn(|texel| {
+            let weight = weights[texel];
+            let gray = ((weight * 255 + 32) >> 6) as u8;
+            let alpha = (((64 - weight) * 254 + weight * 255 + 32) >> 6) as u8;
+            [gray, gray, gray, alpha]
+        });
+        assert_eq!(decode_block(BlockFormat::Bc7, &mode6.finish()), expected);
+
+        // Mode 1, partition 13: a red top half and a blue bottom half. The top's shared P-bit is
+        // set on every channel, its zeros widen to 2
+        let mut mode1 = Bc7Writer::default();
+        mode1.write(1 << 1, 2).write(13, 6);
+        for (top, bottom) in [(63, 0), (0, 0), (0, 63)] {
+            mode1
+                .write(top, 6)
+                .write(top, 6)
+                .write(bottom, 6)
+                .write(bottom, 6);
+        }
+        mode1.write(1, 1).write(0, 1);
+        for texel in 0..16 {
+            // Texel 15 anchors the second subset
+            let bits = if texel == 0 || texel == 15 { 2 } else { 3 };
+            mode1.write(texel % 4, bits);
+        }
+        let expected: [[u8; 4]; 16] = std::array::from_fn(|texel| {
+            if texel < 8 {
+                [255, 2, 2, 255]
+            } else {
+                [0, 0, 253, 255]
+            }
+        });
+        assert_eq!(decode_block(BlockFormat::Bc7, &mode1.finish()), expected);
+    }
+
+    #[test]
+    fn fallback_drops_the_texels_of_partial_blocks() {
+        let blocks: Vec<u8> = [RED, BLUE, BLUE, RED]
+            .into_iter()
+            .flat_map(|color| bc1_block(color, color, |_| 0))
+            .collect();
+        let texture = CompressedTexture::new(BlockFormat::Bc1, 6, 6, vec![blocks]).unwrap();
+        let texels = texture.decode_rgba8();
+        assert_eq!(texels.len(), 6 * 6 * 4);
+        let texel = |x: usize, y: usize| &texels[(y * 6 + x) * 4..(y * 6 + x) * 4 + 4];
+        assert_eq!(texel(5, 5), [255, 0, 0, 255]);
+        assert_eq!(texel(4, 0), [0, 0, 255, 255]);
+    }
+}
@@ -1,4 +1,13 @@
-use glam::{Mat4, Vec3};
+use crate::{picking::Ray, world::WorldConvention};
+use glam::{EulerRot, Mat4, Quat, Vec2, Vec3};
+
+/// Zoom steps of one scroll wheel line, the zoom is multiplied by `exp` of the steps.
+pub const ORTHO_2D_ZOOM_PER_LINE: f32 = 0.1;
+/// Touchpad scrolling in pixels counts one line every this many pixels.
+pub const ORTHO_2D_PIXELS_PER_LINE: f32 = 40.0;
+/// Dolly steps of one scroll wheel line, the distance to the point under the cursor is
+/// multiplied by `exp` of minus the steps.
+pub const DOLLY_PER_LINE: f32 = 0.15;
 
 pub struct PerspectiveProjection {
     pub fov_y: f32,
@@ -24,7 +33,7 @@ impl PerspectiveProjection {
         }
     }
 
-    pub fn update(&mut self) {
+    fn update(&mut self) {
         self.projection = Mat4::perspective_rh(self.fov_y, self.aspect_ratio, self.near, self.far);
         self.projection_view = self.projection * self.view;
     }
@@ -66,7 +75,7 @@ impl OrthographicProjection {
         }
     }
 
-    pub fn update(&mut self) {
+    fn update(&mut self) {
         self.projection = Mat4::orthographic_rh(
             self.left,
             self.right,
@@ -79,35 +88,722 @@ impl OrthographicProjection {
     }
 }
 
+/// Position and orientation with the view derived from them. Every setter refreshes the view
+/// and both `projection_view`, they never go stale.
 pub struct Camera {
-    pub position: Vec3,
-    pub view: Mat4,
-    pub orthographic: OrthographicProjection,
-    pub perspective: PerspectiveProjection,
+    position: Vec3,
+    /// Rotates the camera's local axes into the world, the camera looks down its local -Z with +Y up.
+    orientation: Quat,
+    convention: WorldConvention,
+    view: Mat4,
+    orthographic: OrthographicProjection,
+    perspective: PerspectiveProjection,
+    /// 2D world, laid out by an [`Ortho2DController`]. The UI keeps `orthographic`.
+    world_2d: OrthographicProjection,
 }
 
 impl Camera {
+    /// Looks at the origin with the convention's up.
     pub fn new(
         position: Vec3,
-        orthographic_projections: OrthographicProjection,
-        perspective_projections: PerspectiveProjection,
+        convention: WorldConvention,
+        orthographic: OrthographicProjection,
+        perspective: PerspectiveProjection,
     ) -> Self {
-        let view = Mat4::look_at_rh(position, Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
-        Self {
+        let mut camera = Self {
             position,
-            view,
-            orthographic: orthographic_projections,
-            perspective: perspective_projections,
-        }
+            orientation: Quat::IDENTITY,
+            convention,
+            view: Mat4::IDENTITY,
+            orthographic,
+            perspective,
+            world_2d: OrthographicProjection::new(-1.0, 1.0, -1.0, 1.0, -1.0, 1.0, Mat4::IDENTITY),
+        };
+        camera.look_at(Vec3::ZERO);
+        camera
+    }
+
+    pub fn position(&self) -> Vec3 {
+        self.position
+    }
+
+    pub fn orientation(&self) -> Quat {
+        self.orientation
+    }
+
+    pub fn convention(&self) -> WorldConvention {
+        self.convention
+    }
+
+    pub fn view(&self) -> Mat4 {
+        self.view
+    }
+
+    pub fn perspective(&self) -> &PerspectiveProjection {
+        &self.perspective
     }
 
-    pub fn update(&mut self) {
+    pub fn orthographic(&self) -> &OrthographicProjection {
+        &self.orthographic
+    }
+
+    pub fn world_2d(&self) -> &OrthographicProjection {
+        &self.world_2d
+    }
+
+    /// Direction the camera looks at.
+    pub fn forward(&self) -> Vec3 {
+        self.orientation * Vec3::NEG_Z
+    }
+
+    pub fn up(&self) -> Vec3 {
+        self.orientation * Vec3::Y
+    }
+
+    pub fn right(&self) -> Vec3 {
+        self.orientation * Vec3::X
+    }
+
+    pub fn set_position(&mut self, position: Vec3) {
+        self.position = position;
+        self.refresh();
+    }
+
+    pub fn set_orientation(&mut self, orientation: Quat) {
+        self.orientation = orientation.normalize();
+        self.refresh();
+    }
+
+    /// Turn towards `target` without roll, the convention's up stays up.
+    pub fn look_at(&mut self, target: Vec3) {
+        let view = self.convention.look_at(self.position, target);
+        self.orientation = Quat::from_mat4(&view.inverse()).normalize();
+        self.refresh();
+    }
+
+    /// Move along the camera's own axes, `-Z` is forward.
+    pub fn translate_local(&mut self, offset: Vec3) {
+        self.position += self.orientation * offset;
+        self.refresh();
+    }
+
+    /// Yaw around the local up, then pitch around the local right, then roll around the view axis. Radians.
+    pub fn rotate_local(&mut self, yaw: f32, pitch: f32, roll: f32) {
+        self.orientation =
+            (self.orientation * Quat::from_euler(EulerRot::YXZ, yaw, pitch, roll)).normalize();
+        self.refresh();
+    }
+
+    pub fn set_aspect_ratio(&mut self, aspect_ratio: f32) {
+        self.perspective.aspect_ratio = aspect_ratio;
+        self.perspective.update();
+    }
+
+    /// The UI spans `(0, 0)` to `(width, height)`.
+    pub fn set_orthographic_extent(&mut self, width: f32, height: f32) {
+        self.orthographic.right = width;
+        self.orthographic.top = height;
         self.orthographic.update();
+    }
+
+    /// Show the part of the 2D world `controller` looks at in `viewport`.
+    pub fn apply_2d(&mut self, controller: &Ortho2DController, viewport: Vec2) {
+        controller.apply(&mut self.world_2d, viewport);
+    }
+
+    fn refresh(&mut self) {
+        self.view = Mat4::from_rotation_translation(self.orientation, self.position).inverse();
+        self.perspective.view = self.view;
         self.perspective.update();
-        self.view = Mat4::look_at_rh(
-            self.position,
-            Vec3::new(0.0, 0.0, 0.0),
-            Vec3::new(0.0, 1.0, 0.0),
+    }
+}
+
+/// What a window wider than the 2D world's aspect ratio shows on its sides.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Ortho2DFit {
+    /// More of the world, the scene viewport covers the window.
+    #[default]
+    Expand,
+    /// Bars of the clear color, the scene viewport is centered at most `aspect_ratio` wide. Windows
+    /// narrower than that still show less of the world horizontally.
+    Letterbox { aspect_ratio: f32 },
+}
+
+/// Pans and zooms a 2D world seen through an orthographic projection, for editors and maps.
+/// World Y grows down the screen like the UI, `zoom` is physical pixels per world unit and
+/// screen positions are physical pixels from the viewport's top left.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ortho2DController {
+    /// World point at the center of the viewport.
+    pub center: Vec2,
+    pub zoom: f32,
+    pub min_zoom: f32,
+    pub max_zoom: f32,
+    /// World rectangle, `(min, max)`, the view stays inside. `None` pans freely.
+    pub bounds: Option<(Vec2, Vec2)>,
+    /// World units shown from the top of the viewport to its bottom whatever its size, the zoom
+    /// follows the viewport's height. `None` keeps the zoom across resizes, showing more world.
+    pub vertical_units: Option<f32>,
+    pub fit: Ortho2DFit,
+}
+
+impl Default for Ortho2DController {
+    fn default() -> Self {
+        Self::new(Vec2::ZERO, 100.0)
+    }
+}
+
+impl Ortho2DController {
+    pub fn new(center: Vec2, zoom: f32) -> Self {
+        Self {
+            center,
+            zoom,
+            min_zoom: 1e-3,
+            max_zoom: 1e4,
+            bounds: None,
+            vertical_units: None,
+            fit: Ortho2DFit::Expand,
+        }
+    }
+
+    /// Show `units` world units vertically, content authored in world units, a tile each, keeps
+    /// its size relative to the window. Zooming changes the units shown, within the zoom limits.
+    pub fn with_vertical_units(mut self, units: f32) -> Self {
+        self.vertical_units = Some(units.max(f32::EPSILON));
+        self
+    }
+
+    pub fn with_fit(mut self, fit: Ortho2DFit) -> Self {
+        self.fit = fit;
+        self
+    }
+
+    /// Physical pixels per world unit, as of the last [`Self::resize`]. A sprite of `n` texels per
+    /// unit is drawn at an integer zoom when this is a multiple of `n`, see [`Self::snap`].
+    pub fn pixels_per_unit(&self) -> f32 {
+        self.zoom
+    }
+
+    /// Scene viewport in a `window` of physical pixels, offset and size: the whole window, or
+    /// the centered letterbox of [`Ortho2DFit::Letterbox`].
+    pub fn viewport_rect(&self, window: Vec2) -> (Vec2, Vec2) {
+        match self.fit {
+            Ortho2DFit::Letterbox { aspect_ratio } if window.x > window.y * aspect_ratio => {
+                let width = (window.y * aspect_ratio).round().max(1.0);
+                (
+                    Vec2::new(((window.x - width) * 0.5).floor(), 0.0),
+                    Vec2::new(width, window.y),
+                )
+            }
+            _ => (Vec2::ZERO, window),
+        }
+    }
+
+    /// Follow a `viewport` of another size: the zoom keeps [`Self::vertical_units`] if set, and
+    /// the view its bounds.
+    pub fn resize(&mut self, viewport: Vec2) {
+        if let Some(units) = self.vertical_units {
+            self.zoom = viewport.y.max(1.0) / units;
+        }
+        self.constrain(viewport);
+    }
+
+    /// World position moved to the nearest physical pixel corner in `viewport`, where a sprite
+    /// is placed to keep its texel edges on pixel edges at an integer zoom.
+    pub fn snap(&self, point: Vec2, viewport: Vec2) -> Vec2 {
+        self.screen_to_world(self.world_to_screen(point, viewport).round(), viewport)
+    }
+
+    pub fn with_zoom_limits(mut self, min_zoom: f32, max_zoom: f32) -> Self {
+        self.min_zoom = min_zoom;
+        self.max_zoom = max_zoom.max(min_zoom);
+        self.zoom = self.zoom.clamp(self.min_zoom, self.max_zoom);
+        self
+    }
+
+    /// Keep the view inside `min..max`, centered on an axis the view is larger than.
+    pub fn with_bounds(mut self, min: Vec2, max: Vec2) -> Self {
+        self.bounds = Some((min.min(max), min.max(max)));
+        self
+    }
+
+    pub fn screen_to_world(&self, position: Vec2, viewport: Vec2) -> Vec2 {
+        self.center + (position - viewport * 0.5) / self.zoom
+    }
+
+    pub fn world_to_screen(&self, point: Vec2, viewport: Vec2) -> Vec2 {
+        (point - self.center) * self.zoom + viewport * 0.5
+    }
+
+    /// World rectangle shown in `viewport`, `(min, max)`.
+    pub fn visible(&self, viewport: Vec2) -> (Vec2, Vec2) {
+        let half = viewport * 0.5 / self.zoom;
+        (self.center - half, self.center + half)
+    }
+
+    /// Follow a drag of `delta` pixels, the world point under the cursor moves with it.
+    pub fn pan(&mut self, delta: Vec2, viewport: Vec2) {
+        self.center -= delta / self.zoom;
+        self.constrain(viewport);
+    }
+
+    /// Multiply the zoom by `factor`, within the limits, keeping the world point under `cursor` in place.
+    pub fn zoom_at(&mut self, factor: f32, cursor: Vec2, viewport: Vec2) {
+        let anchor = self.screen_to_world(cursor, viewport);
+        self.zoom = (self.zoom * factor).clamp(self.min_zoom, self.max_zoom);
+        if self.vertical_units.is_some() {
+            self.vertical_units = Some(viewport.y.max(1.0) / self.zoom);
+        }
+        self.center = anchor - (cursor - viewport * 0.5) / self.zoom;
+        self.constrain(viewport);
+    }
+
+    /// Move the center back inside the bounds, the viewport may have changed size.
+    pub fn constrain(&mut self, viewport: Vec2) {
+        let Some((min, max)) = self.bounds else {
+            return;
+        };
+        let half = viewport * 0.5 / self.zoom;
+        let constrain = |center: f32, min: f32, max: f32, half: f32| {
+            if max - min <= half * 2.0 {
+                (min + max) * 0.5
+            } else {
+                center.clamp(min + half, max - half)
+            }
+        };
+        self.center = Vec2::new(
+            constrain(self.center.x, min.x, max.x, half.x),
+            constrain(self.center.y, min.y, max.y, half.y),
         );
     }
+
+    /// Set `projection`'s sides to the visible rectangle. Bottom is the smaller Y, Vulkan puts it
+    /// at the top of the screen.
+    pub fn apply(&self, projection: &mut OrthographicProjection, viewport: Vec2) {
+        let (min, max) = self.visible(viewport.max(Vec2::ONE));
+        projection.left = min.x;
+        projection.right = max.x;
+        projection.bottom = min.y;
+        projection.top = max.y;
+        projection.update();
+    }
+}
+
+/// Moves the perspective camera with the scroll wheel toward the point under the cursor, like
+/// 3D viewers do. The orientation is kept, the point stays under the cursor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraController {
+    /// Dolly steps of one scroll wheel line, see [`DOLLY_PER_LINE`].
+    pub dolly_per_line: f32,
+    /// Closest the camera gets to the point it dollies toward.
+    pub min_distance: f32,
+    /// Distance along the cursor's ray of the point dollied toward when neither a mesh nor the
+    /// ground is under the cursor.
+    pub fallback_distance: f32,
+}
+
+impl Default for CameraController {
+    fn default() -> Self {
+        Self {
+            dolly_per_line: DOLLY_PER_LINE,
+            min_distance: 0.1,
+            fallback_distance: 10.0,
+        }
+    }
+}
+
+impl CameraController {
+    /// Point a dolly along `ray`, cast from the cursor, moves toward: the mesh `hit`, else the
+    /// ground plane through the origin, else a point [`Self::fallback_distance`] away.
+    pub fn dolly_target(&self, camera: &Camera, ray: &Ray, hit: Option<Vec3>) -> Vec3 {
+        hit.or_else(|| {
+            ray.intersect_plane(Vec3::ZERO, camera.convention().up())
+                .map(|t| ray.at(t))
+        })
+        .unwrap_or_else(|| ray.at(self.fallback_distance))
+    }
+
+    /// Scrolling up, positive `scroll_lines`, moves the camera toward the point under the cursor
+    /// found with [`Self::dolly_target`], exponentially in the lines scrolled and never closer
+    /// than [`Self::min_distance`]. Scrolling down moves it away.
+    pub fn dolly_toward_cursor(
+        &self,
+        camera: &mut Camera,
+        scroll_lines: f32,
+        ray: &Ray,
+        hit: Option<Vec3>,
+    ) {
+        let target = self.dolly_target(camera, ray, hit);
+        self.dolly_toward(camera, target, scroll_lines);
+    }
+
+    /// [`Self::dolly_toward_cursor`] toward a known `target`. The camera moves along the line
+    /// through it, so `target` keeps its place on screen.
+    pub fn dolly_toward(&self, camera: &mut Camera, target: Vec3, scroll_lines: f32) {
+        let offset = camera.position() - target;
+        let distance = offset.length();
+        if scroll_lines == 0.0 || distance < f32::EPSILON {
+            return;
+        }
+        let scaled = distance * (-scroll_lines * self.dolly_per_line).exp();
+        // Already closer than the limit, scrolling up stays put
+        let distance = scaled.max(self.min_distance.min(distance));
+        camera.set_position(target + offset.normalize() * distance);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOLERANCE: f32 = 1e-4;
+
+    fn camera(convention: WorldConvention) -> Camera {
+        Camera::new(
+            Vec3::new(0.0, 0.0, 4.0),
+            convention,
+            OrthographicProjection::new(0.0, 1.0, 0.0, 1.0, -1.0, 1.0, Mat4::IDENTITY),
+            PerspectiveProjection::new(1.0, 1.5, 0.1, 100.0, Mat4::IDENTITY),
+        )
+    }
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!(a.abs_diff_eq(b, TOLERANCE), "{a} is not {b}");
+    }
+
+    #[test]
+    fn orientation_setters_match_the_view() {
+        for convention in [WorldConvention::YUp, WorldConvention::ZUp] {
+            let mut camera = camera(convention);
+
+            // Looking at a target matches the convention's view matrix
+            let target = Vec3::new(1.0, 2.0, -3.0);
+            camera.look_at(target);
+            assert!(
+                camera
+                    .view()
+                    .abs_diff_eq(convention.look_at(camera.position(), target), TOLERANCE),
+                "{convention:?}"
+            );
+            assert_close(camera.forward(), (target - camera.position()).normalize());
+
+            // Moving forward keeps looking at the same point
+            let before = camera.view().transform_point3(target).normalize();
+            camera.translate_local(Vec3::NEG_Z);
+            assert_close(camera.view().transform_point3(target).normalize(), before);
+
+            // The orientation and the view matrix describe the same rotation
+            let orientation = Quat::from_euler(EulerRot::YXZ, 0.7, -0.3, 1.1);
+            camera.set_orientation(orientation);
+            let expected =
+                Mat4::from_rotation_translation(orientation, camera.position()).inverse();
+            assert!(
+                camera.view().abs_diff_eq(expected, TOLERANCE),
+                "{convention:?}"
+            );
+            assert!(
+                camera
+                    .perspective()
+                    .projection_view
+                    .abs_diff_eq(camera.perspective().projection * camera.view(), TOLERANCE),
+                "{convention:?} projection_view is stale"
+            );
+        }
+    }
+
+    #[test]
+    fn roll_turns_around_the_view_axis() {
+        for convention in [WorldConvention::YUp, WorldConvention::ZUp] {
+            let mut camera = camera(convention);
+            camera.look_at(Vec3::new(1.0, 2.0, -3.0));
+            let upright = camera.view();
+            let roll = std::f32::consts::FRAC_PI_6;
+            camera.rotate_local(0.0, 0.0, roll);
+            let up_on_screen = camera.view().transform_vector3(convention.up());
+            let upright_up = upright.transform_vector3(convention.up());
+            let angle = up_on_screen.truncate().angle_to(upright_up.truncate());
+            assert!(
+                (angle - roll).abs() < TOLERANCE,
+                "{convention:?} rolled {angle}"
+            );
+            // Rolling back restores the horizon
+            camera.rotate_local(0.0, 0.0, -roll);
+            assert!(
+                camera.view().abs_diff_eq(upright, TOLERANCE),
+                "{convention:?}"
+            );
+        }
+    }
+
+    const VIEWPORT: Vec2 = Vec2::new(1200.0, 800.0);
+    /// Pixels a dollied point may drift on screen, the ray is rebuilt from the inverse projection.
+    const SCREEN_TOLERANCE: f32 = 0.05;
+
+    /// Physical pixels from the top left where `point` is drawn.
+    fn screen(camera: &Camera, point: Vec3) -> Vec2 {
+        let clip = camera.perspective().projection_view * point.extend(1.0);
+        (clip.truncate().truncate() / clip.w + Vec2::ONE) * 0.5 * VIEWPORT
+    }
+
+    #[test]
+    fn dolly_keeps_the_ground_under_the_cursor() {
+        let controller = CameraController::default();
+        let poses = [
+            (WorldConvention::YUp, Vec3::new(0.0, 3.0, 6.0), Vec3::ZERO),
+            (
+                WorldConvention::YUp,
+                Vec3::new(-4.0, 1.0, 2.0),
+                Vec3::new(1.0, 0.0, -1.0),
+            ),
+            (
+                WorldConvention::ZUp,
+                Vec3::new(5.0, -5.0, 4.0),
+                Vec3::new(0.0, 0.0, 1.0),
+            ),
+        ];
+        // The poses look down, rays through the upper half of the screen reach the ground
+        let cursors = [
+            VIEWPORT * 0.5,
+            Vec2::new(300.0, 200.0),
+            Vec2::new(1000.0, 350.0),
+        ];
+        for (convention, position, target) in poses {
+            for cursor in cursors {
+                for lines in [3.0, -2.0] {
+                    let mut camera = camera(convention);
+                    camera.set_position(position);
+                    camera.look_at(target);
+                    let orientation = camera.orientation();
+                    let ray =
+                        Ray::from_cursor(cursor, VIEWPORT, camera.perspective().projection_view);
+                    // Without a mesh hit the cursor's ray lands on the ground
+                    let point = controller.dolly_target(&camera, &ray, None);
+                    assert!(
+                        point.dot(convention.up()).abs() < TOLERANCE,
+                        "{convention:?} dolly target {point} is off the ground"
+                    );
+                    let before = camera.position().distance(point);
+                    controller.dolly_toward_cursor(&mut camera, lines, &ray, None);
+                    let after = camera.position().distance(point);
+                    let expected = before * (-lines * controller.dolly_per_line).exp();
+                    assert!(
+                        (after - expected).abs() < TOLERANCE * before,
+                        "{convention:?} dollied to {after} instead of {expected}"
+                    );
+                    assert!(camera.orientation().abs_diff_eq(orientation, TOLERANCE));
+                    let on_screen = screen(&camera, point);
+                    assert!(
+                        on_screen.distance(cursor) < SCREEN_TOLERANCE,
+                        "{convention:?} point at {cursor} moved to {on_screen}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn dolly_stops_at_the_minimum_distance_from_a_hit() {
+        let controller = CameraController::default();
+        let mut camera = camera(WorldConvention::YUp);
+        let cursor = Vec2::new(500.0, 350.0);
+        let ray = Ray::from_cursor(cursor, VIEWPORT, camera.perspective().projection_view);
+        // A mesh hit wins over the ground
+        let hit = ray.at(2.0);
+        controller.dolly_toward_cursor(&mut camera, 100.0, &ray, Some(hit));
+        let distance = camera.position().distance(hit);
+        assert!(
+            (distance - controller.min_distance).abs() < TOLERANCE,
+            "{distance}"
+        );
+        assert!(screen(&camera, hit).distance(cursor) < SCREEN_TOLERANCE);
+        let stuck = camera.position();
+        controller.dolly_toward_cursor(&mut camera, 1.0, &ray, Some(hit));
+        assert_close(camera.position(), stuck);
+    }
+
+    #[test]
+    fn dolly_toward_the_sky_uses_the_fallback_distance() {
+        let controller = CameraController::default();
+        let mut sky = camera(WorldConvention::YUp);
+        sky.look_at(Vec3::new(0.0, 10.0, 0.0));
+        let ray = Ray::from_cursor(VIEWPORT * 0.5, VIEWPORT, sky.perspective().projection_view);
+        let point = controller.dolly_target(&sky, &ray, None);
+        let distance = point.distance(ray.origin);
+        assert!(
+            (distance - controller.fallback_distance).abs() < TOLERANCE,
+            "{distance}"
+        );
+    }
+
+    const ORTHO_VIEWPORT: Vec2 = Vec2::new(1280.0, 720.0);
+    const CURSOR: Vec2 = Vec2::new(1000.0, 150.0);
+    const ORTHO_TOLERANCE: f32 = 1e-3;
+
+    /// A 10 by 10 world with a 2 unit margin around it.
+    fn ortho_2d() -> Ortho2DController {
+        Ortho2DController::new(Vec2::splat(5.0), 60.0)
+            .with_zoom_limits(10.0, 400.0)
+            .with_bounds(Vec2::splat(-2.0), Vec2::splat(12.0))
+    }
+
+    #[test]
+    fn zoom_and_pan_keep_the_point_under_the_cursor() {
+        let mut view = Ortho2DController::new(Vec2::new(3.0, -2.0), 50.0);
+        let anchor = view.screen_to_world(CURSOR, ORTHO_VIEWPORT);
+        for factor in [1.5, 3.0, 0.25, 0.9] {
+            view.zoom_at(factor, CURSOR, ORTHO_VIEWPORT);
+            let moved = view
+                .screen_to_world(CURSOR, ORTHO_VIEWPORT)
+                .distance(anchor);
+            assert!(moved < ORTHO_TOLERANCE, "zoom by {factor} moved by {moved}");
+        }
+        let back = view.world_to_screen(anchor, ORTHO_VIEWPORT);
+        assert!(back.distance(CURSOR) < ORTHO_TOLERANCE, "{back}");
+
+        // Dragging moves the world along with the cursor
+        let delta = Vec2::new(-40.0, 25.0);
+        view.pan(delta, ORTHO_VIEWPORT);
+        let dropped = view.world_to_screen(anchor, ORTHO_VIEWPORT);
+        assert!(
+            dropped.distance(CURSOR + delta) < ORTHO_TOLERANCE,
+            "{dropped}"
+        );
+    }
+
+    #[test]
+    fn zoom_limits_and_bounds_hold() {
+        let mut clamped = ortho_2d();
+        clamped.zoom_at(1e6, CURSOR, ORTHO_VIEWPORT);
+        assert_eq!(clamped.zoom, 400.0);
+        clamped.zoom_at(1e-6, CURSOR, ORTHO_VIEWPORT);
+        assert_eq!(clamped.zoom, 10.0);
+
+        let (min, max) = (Vec2::splat(-2.0), Vec2::splat(12.0));
+        let mut bounded = ortho_2d();
+        bounded.zoom_at(4.0, Vec2::ZERO, ORTHO_VIEWPORT);
+        bounded.pan(Vec2::splat(1e5), ORTHO_VIEWPORT);
+        let (visible_min, visible_max) = bounded.visible(ORTHO_VIEWPORT);
+        assert!(
+            visible_min.cmpge(min - ORTHO_TOLERANCE).all()
+                && visible_max.cmple(max + ORTHO_TOLERANCE).all(),
+            "panned out of bounds to {visible_min}..{visible_max}"
+        );
+        // A view larger than the bounds is centered on them
+        bounded.zoom_at(1e-6, CURSOR, ORTHO_VIEWPORT);
+        assert!(
+            bounded.center.distance((min + max) * 0.5) < ORTHO_TOLERANCE,
+            "{}",
+            bounded.center
+        );
+    }
+
+    #[test]
+    fn picking_goes_through_the_2d_projection() {
+        let mut camera = camera(WorldConvention::YUp);
+        for zoom in [12.0, 60.0, 333.0] {
+            let mut view = ortho_2d();
+            view.zoom_at(zoom / view.zoom, CURSOR, ORTHO_VIEWPORT);
+            camera.apply_2d(&view, ORTHO_VIEWPORT);
+            let ray = Ray::from_cursor(CURSOR, ORTHO_VIEWPORT, camera.world_2d().projection_view);
+            let expected = view.screen_to_world(CURSOR, ORTHO_VIEWPORT);
+            assert!(
+                ray.origin.truncate().distance(expected) < ORTHO_TOLERANCE,
+                "{ray:?} misses {expected} at zoom {zoom}"
+            );
+            assert!(
+                ray.direction.truncate().length() < ORTHO_TOLERANCE,
+                "{ray:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn vertical_units_hold_across_window_sizes_and_fits() {
+        const UNITS: f32 = 18.0;
+        let mut camera = camera(WorldConvention::YUp);
+        let windows = [
+            (1280.0, 720.0),
+            (800.0, 600.0),
+            (640.0, 960.0),
+            (3000.0, 500.0),
+        ];
+        for fit in [
+            Ortho2DFit::Expand,
+            Ortho2DFit::Letterbox {
+                aspect_ratio: 16.0 / 9.0,
+            },
+        ] {
+            let mut view = ortho_2d().with_vertical_units(UNITS).with_fit(fit);
+            for (width, height) in windows {
+                let window = Vec2::new(width, height);
+                let (offset, viewport) = view.viewport_rect(window);
+                view.resize(viewport);
+                camera.apply_2d(&view, viewport);
+                let projection = camera.world_2d();
+                let span = Vec2::new(
+                    projection.right - projection.left,
+                    projection.top - projection.bottom,
+                );
+                // Sprites keep their height relative to the window, only the width shown varies
+                let expected_width = match fit {
+                    Ortho2DFit::Letterbox { aspect_ratio } => {
+                        UNITS * aspect_ratio.min(width / height)
+                    }
+                    Ortho2DFit::Expand => UNITS * width / height,
+                };
+                assert!(
+                    (span.y - UNITS).abs() < ORTHO_TOLERANCE
+                        && (span.x - expected_width).abs() < 0.05,
+                    "{fit:?} in {window}: {span} units shown"
+                );
+                assert!(
+                    (view.pixels_per_unit() - height / UNITS).abs() < ORTHO_TOLERANCE,
+                    "{fit:?} in {window}: {} pixels per unit",
+                    view.pixels_per_unit()
+                );
+                assert!(
+                    (offset * 2.0 + viewport - window).abs().max_element() <= 1.0,
+                    "{fit:?} in {window}: viewport {offset} {viewport}"
+                );
+            }
+        }
+
+        // Zooming changes the units shown, resizes keep them
+        let (before, after) = (Vec2::new(1280.0, 720.0), Vec2::new(800.0, 600.0));
+        let mut view = ortho_2d().with_vertical_units(UNITS);
+        view.resize(before);
+        view.zoom_at(2.0, Vec2::ZERO, before);
+        view.resize(after);
+        let (min, max) = view.visible(after);
+        assert!(
+            (max.y - min.y - UNITS / 2.0).abs() < ORTHO_TOLERANCE,
+            "a resize undid the zoom, {} units shown",
+            max.y - min.y
+        );
+    }
+
+    #[test]
+    fn snapped_sprites_land_texels_on_pixels() {
+        const UNITS: f32 = 18.0;
+        const TEXELS_PER_UNIT: f32 = 16.0;
+        // At 1x texel centers land on pixel centers, at 2x on the corners between the pixels a
+        // texel covers
+        for (height, zoom) in [(288.0, 1.0), (576.0, 2.0)] {
+            let viewport = Vec2::new(height * 16.0 / 9.0, height);
+            let mut view =
+                Ortho2DController::new(Vec2::new(3.3, 7.7), 1.0).with_vertical_units(UNITS);
+            view.resize(viewport);
+            assert_eq!(view.pixels_per_unit(), TEXELS_PER_UNIT * zoom);
+            let sprite = view.snap(Vec2::new(1.234, 5.678), viewport);
+            let expected = if zoom == 1.0 { 0.5 } else { 0.0 };
+            for texel in [Vec2::ZERO, Vec2::new(3.0, 7.0), Vec2::splat(15.0)] {
+                let center =
+                    view.world_to_screen(sprite + (texel + 0.5) / TEXELS_PER_UNIT, viewport);
+                assert!(
+                    (center.fract() - Vec2::splat(expected)).abs().max_element() < ORTHO_TOLERANCE,
+                    "texel {texel} centered at {center} at {zoom}x"
+                );
+            }
+        }
+    }
 }
@@ -0,0 +1,585 @@
+//! Color of the exported images, whatever the surface presents.
+//!
+//! Screenshots and replay frames are read back from images in the swapchain's format. An 8 bit
+//! sRGB surface is copied as is, any other one goes through a convert pass on the GPU: its texels
+//! are decoded to linear BT.709 with the surface's transfer function, then written as sRGB bytes
+//! or half floats. The functions here are the same math on the CPU, the reference the pass is
+//! checked against.
+use ash::vk;
+use glam::{Mat3, Vec3, Vec4};
+use std::error::Error;
+
+/// Luminance of SDR white in an HDR10 image, decoded to 1.0. The BT.2408 graphics white.
+pub const HDR_REFERENCE_WHITE_NITS: f32 = 203.0;
+/// Luminance of a PQ signal of 1.0.
+pub const PQ_MAX_NITS: f32 = 10000.0;
+
+// SMPTE ST 2084 constants
+const PQ_M1: f32 = 2610.0 / 16384.0;
+const PQ_M2: f32 = 2523.0 / 4096.0 * 128.0;
+const PQ_C1: f32 = 3424.0 / 4096.0;
+const PQ_C2: f32 = 2413.0 / 4096.0 * 32.0;
+const PQ_C3: f32 = 2392.0 / 4096.0 * 32.0;
+
+/// Linear BT.2020 to linear BT.709 primaries, columns first like the shader's `mat3`.
+const BT2020_TO_BT709: Mat3 = Mat3::from_cols(
+    Vec3::new(1.660_491, -0.124_550, -0.018_151),
+    Vec3::new(-0.587_641, 1.132_9, -0.100_579),
+    Vec3::new(-0.072_850, -0.008_349, 1.118_73),
+);
+
+/// Texels of an exported image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExportColorSpace {
+    /// RGBA8 sRGB encoded, clamped to the SDR range. PNG screenshots and replay frames.
+    #[default]
+    Srgb8,
+    /// RGBA16F linear BT.709, keeps what an HDR surface shows above SDR white. OpenEXR screenshots.
+    Linear16F,
+}
+
+impl ExportColorSpace {
+    /// Format of the image the convert pass writes.
+    pub fn format(self) -> vk::Format {
+        match self {
+            Self::Srgb8 => vk::Format::R8G8B8A8_SRGB,
+            Self::Linear16F => vk::Format::R16G16B16A16_SFLOAT,
+        }
+    }
+
+    pub fn texel_size(self) -> usize {
+        match self {
+            Self::Srgb8 => 4,
+            Self::Linear16F => 8,
+        }
+    }
+
+    /// Append `linear` as one exported texel, alpha is never encoded.
+    pub fn encode(self, linear: Vec4, texels: &mut Vec<u8>) {
+        match self {
+            Self::Srgb8 => {
+                let [r, g, b, a] = linear.to_array();
+                let srgb = [linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b), a];
+                texels.extend(srgb.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8));
+            }
+            Self::Linear16F => {
+                for channel in linear.to_array() {
+                    texels.extend(f32_to_f16(channel).to_le_bytes());
+                }
+            }
+        }
+    }
+}
+
+/// Transfer function of the values the convert pass samples from a surface's image, the
+/// `transfer` push constant of `export.frag`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum SourceTransfer {
+    /// Already linear BT.709: `_SRGB` formats decoded by the view, or scRGB float surfaces.
+    Linear = 0,
+    /// sRGB encoded values in a `_UNORM` format.
+    Srgb = 1,
+    /// HDR10, SMPTE ST 2084 encoded BT.2020.
+    Pq = 2,
+}
+
+impl SourceTransfer {
+    pub fn of(surface_format: vk::SurfaceFormatKHR) -> Self {
+        match surface_format.color_space {
+            vk::ColorSpaceKHR::HDR10_ST2084_EXT => Self::Pq,
+            vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT => Self::Linear,
+            _ if is_srgb_format(surface_format.format) => Self::Linear,
+            _ if surface_format.format == vk::Format::R16G16B16A16_SFLOAT => Self::Linear,
+            _ => Self::Srgb,
+        }
+    }
+
+    /// Linear BT.709 of a sampled texel, relative to SDR white.
+    pub fn decode(self, sampled: Vec4) -> Vec4 {
+        let rgb = sampled.truncate();
+        let linear = match self {
+            Self::Linear => rgb,
+            Self::Srgb => Vec3::from(rgb.to_array().map(srgb_to_linear)),
+            Self::Pq => {
+                let nits = Vec3::from(rgb.to_array().map(pq_to_nits));
+                BT2020_TO_BT709 * (nits / HDR_REFERENCE_WHITE_NITS)
+            }
+        };
+        linear.extend(sampled.w)
+    }
+}
+
+/// Whether a blit into RGBA8 keeps the exact texels of `surface_format`, no convert pass needed.
+pub fn is_rgba8(surface_format: vk::SurfaceFormatKHR) -> bool {
+    surface_format.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR
+        && matches!(
+            surface_format.format,
+            vk::Format::R8G8B8A8_UNORM
+                | vk::Format::R8G8B8A8_SRGB
+                | vk::Format::B8G8R8A8_UNORM
+                | vk::Format::B8G8R8A8_SRGB
+        )
+}
+
+/// Whether exporting from `surface_format` into `color_space` takes the convert pass.
+pub fn needs_conversion(
+    surface_format: vk::SurfaceFormatKHR,
+    color_space: ExportColorSpace,
+) -> bool {
+    color_space != ExportColorSpace::Srgb8 || !is_rgba8(surface_format)
+}
+
+/// Surface formats the swapchain takes in this order, before the surface's first one. An 8 bit
+/// `_SRGB` format encodes the shaders' linear output, with `shader_gamma` the shaders encode it
+/// themselves and an 8 bit `_UNORM` format keeps it as is. The other encoding comes next, still
+/// better than a 10 bit or HDR format the rest of the renderer does not expect.
+pub fn preferred_surface_formats(shader_gamma: bool) -> [vk::SurfaceFormatKHR; 4] {
+    let srgb = [vk::Format::B8G8R8A8_SRGB, vk::Format::R8G8B8A8_SRGB];
+    let unorm = [vk::Format::B8G8R8A8_UNORM, vk::Format::R8G8B8A8_UNORM];
+    let (first, second) = if shader_gamma {
+        (unorm, srgb)
+    } else {
+        (srgb, unorm)
+    };
+    [first[0], first[1], second[0], second[1]].map(|format| vk::SurfaceFormatKHR {
+        format,
+        color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+    })
+}
+
+/// The first of `preferred` that `available` lists, otherwise the first available. A lone
+/// `UNDEFINED` format leaves the choice to the application, the first preferred is taken.
+pub fn choose_surface_format(
+    available: &[vk::SurfaceFormatKHR],
+    preferred: &[vk::SurfaceFormatKHR],
+) -> Option<vk::SurfaceFormatKHR> {
+    if let ([only], Some(&first)) = (available, preferred.first()) {
+        if only.format == vk::Format::UNDEFINED {
+            return Some(first);
+        }
+    }
+    preferred
+        .iter()
+        .find(|format| available.contains(format))
+        .or(available.first())
+        .copied()
+}
+
+/// Format of the color textures: `_SRGB` so they are sampled linear, `_UNORM` with `shader_gamma`
+/// so the shaders get the texels as stored.
+pub fn texture_format(shader_gamma: bool) -> vk::Format {
+    if shader_gamma {
+        vk::Format::R8G8B8A8_UNORM
+    } else {
+        vk::Format::R8G8B8A8_SRGB
+    }
+}
+
+/// An sRGB encoded RGBA color made linear, as vertex colors must be for an `_SRGB` swapchain to
+/// show them as authored. Alpha is kept.
+pub fn srgb_color_to_linear(color: [f32; 4]) -> [f32; 4] {
+    let [r, g, b, a] = color;
+    [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a]
+}
+
+fn is_srgb_format(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::R8G8B8A8_SRGB | vk::Format::B8G8R8A8_SRGB
+    )
+}
+
+/// Bytes per texel of the surface formats [`sample`] reads.
+pub fn texel_size(format: vk::Format) -> Option<usize> {
+    match format {
+        vk::Format::R8G8B8A8_UNORM
+        | vk::Format::R8G8B8A8_SRGB
+        | vk::Format::B8G8R8A8_UNORM
+        | vk::Format::B8G8R8A8_SRGB
+        | vk::Format::A2B10G10R10_UNORM_PACK32
+        | vk::Format::A2R10G10B10_UNORM_PACK32 => Some(4),
+        vk::Format::R16G16B16A16_SFLOAT => Some(8),
+        _ => None,
+    }
+}
+
+/// RGBA of one little endian texel of `format` as a shader samples it, `_SRGB` formats decoded.
+pub fn sample(format: vk::Format, texel: &[u8]) -> Option<Vec4> {
+    let unorm8 = |channel: u8| channel as f32 / 255.0;
+    let srgb8 = |channel: u8| srgb_to_linear(unorm8(channel));
+    let unorm10 = |packed: u32, shift: u32| ((packed >> shift) & 0x3ff) as f32 / 1023.0;
+    let sampled = match (format, texel) {
+        (vk::Format::R8G8B8A8_UNORM, &[r, g, b, a]) => {
+            Vec4::new(unorm8(r), unorm8(g), unorm8(b), unorm8(a))
+        }
+        (vk::Format::B8G8R8A8_UNORM, &[b, g, r, a]) => {
+            Vec4::new(unorm8(r), unorm8(g), unorm8(b), unorm8(a))
+        }
+        (vk::Format::R8G8B8A8_SRGB, &[r, g, b, a]) => {
+            Vec4::new(srgb8(r), srgb8(g), srgb8(b), unorm8(a))
+        }
+        (vk::Format::B8G8R8A8_SRGB, &[b, g, r, a]) => {
+            Vec4::new(srgb8(r), srgb8(g), srgb8(b), unorm8(a))
+        }
+        (vk::Format::A2B10G10R10_UNORM_PACK32, &[_, _, _, _]) => {
+            let packed = u32::from_le_bytes(texel.try_into().ok()?);
+            let alpha = (packed >> 30) as f32 / 3.0;
+            Vec4::new(
+                unorm10(packed, 0),
+                unorm10(packed, 10),
+                unorm10(packed, 20),
+                alpha,
+            )
+        }
+        (vk::Format::A2R10G10B10_UNORM_PACK32, &[_, _, _, _]) => {
+            let packed = u32::from_le_bytes(texel.try_into().ok()?);
+            let alpha = (packed >> 30) as f32 / 3.0;
+            Vec4::new(
+                unorm10(packed, 20),
+                unorm10(packed, 10),
+                unorm10(packed, 0),
+                alpha,
+            )
+        }
+        (vk::Format::R16G16B16A16_SFLOAT, &[_, _, _, _, _, _, _, _]) => {
+            let [r, g, b, a] = [0, 2, 4, 6]
+                .map(|offset| f16_to_f32(u16::from_le_bytes([texel[offset], texel[offset + 1]])));
+            Vec4::new(r, g, b, a)
+        }
+        _ => return None,
+    };
+    Some(sampled)
+}
+
+/// What the convert pass writes for `texels` of `surface_format`, row after row.
+pub fn convert(
+    surface_format: vk::SurfaceFormatKHR,
+    texels: &[u8],
+    color_space: ExportColorSpace,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let format = surface_format.format;
+    let size = texel_size(format).ok_or_else(|| format!("No export from {format:?}"))?;
+    if !texels.len().is_multiple_of(size) {
+        return Err(format!("{} bytes are not whole {format:?} texels", texels.len()).into());
+    }
+    let transfer = SourceTransfer::of(surface_format);
+    let mut converted = Vec::with_capacity(texels.len() / size * color_space.texel_size());
+    for texel in texels.chunks_exact(size) {
+        let sampled = sample(format, texel).ok_or_else(|| format!("No export from {format:?}"))?;
+        color_space.encode(transfer.decode(sampled), &mut converted);
+    }
+    Ok(converted)
+}
+
+pub fn srgb_to_linear(encoded: f32) -> f32 {
+    if encoded <= 0.04045 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(linear: f32) -> f32 {
+    if linear <= 0.003_130_8 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Luminance of a PQ signal, the ST 2084 EOTF.
+pub fn pq_to_nits(signal: f32) -> f32 {
+    let power = signal.max(0.0).powf(1.0 / PQ_M2);
+    ((power - PQ_C1).max(0.0) / (PQ_C2 - PQ_C3 * power)).powf(1.0 / PQ_M1) * PQ_MAX_NITS
+}
+
+/// PQ signal of a luminance, the inverse EOTF.
+pub fn nits_to_pq(nits: f32) -> f32 {
+    let power = (nits / PQ_MAX_NITS).clamp(0.0, 1.0).powf(PQ_M1);
+    ((PQ_C1 + PQ_C2 * power) / (1.0 + PQ_C3 * power)).powf(PQ_M2)
+}
+
+/// IEEE half precision bits to a float, subnormals and infinities included.
+pub fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 == 0 { 1.0 } else { -1.0 };
+    let exponent = i32::from((bits >> 10) & 0x1f);
+    let mantissa = f32::from(bits & 0x3ff);
+    match exponent {
+        0 => sign * mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => sign * f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+/// Nearest half precision bits of a float, too large is an infinity.
+pub fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent == 0xff {
+        let nan = if mantissa == 0 { 0 } else { 0x200 };
+        return sign | 0x7c00 | nan;
+    }
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        // Subnormal, the implicit bit shifted in
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        let round = (mantissa >> (shift - 1)) & 1;
+        return sign | ((mantissa >> shift) + round) as u16;
+    }
+    // A carry out of the mantissa rounds up into the exponent
+    let half = (exponent as u32) << 10 | mantissa >> 13;
+    sign | (half + ((mantissa >> 12) & 1)) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn surface_format(format: vk::Format) -> vk::SurfaceFormatKHR {
+        vk::SurfaceFormatKHR {
+            format,
+            color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+        }
+    }
+
+    #[test]
+    fn swapchain_format_follows_the_gamma_workflow() {
+        // The 8 bit format matching the workflow wherever it is listed
+        let listed = [
+            surface_format(vk::Format::B8G8R8A8_UNORM),
+            surface_format(vk::Format::B8G8R8A8_SRGB),
+        ];
+        for (shader_gamma, expected, texture) in [
+            (false, vk::Format::B8G8R8A8_SRGB, vk::Format::R8G8B8A8_SRGB),
+            (true, vk::Format::B8G8R8A8_UNORM, vk::Format::R8G8B8A8_UNORM),
+        ] {
+            let chosen = choose_surface_format(&listed, &preferred_surface_formats(shader_gamma));
+            assert_eq!(
+                chosen,
+                Some(surface_format(expected)),
+                "shader gamma {shader_gamma}"
+            );
+            assert_eq!(texture_format(shader_gamma), texture);
+        }
+    }
+
+    #[test]
+    fn srgb_colors_come_back_as_authored() {
+        // Cover colors decoded by the shader, encoded again by the swapchain
+        let authored = [0.0863, 0.0863, 0.1333, 1.0];
+        let shown = srgb_color_to_linear(authored).map(linear_to_srgb);
+        for (a, b) in authored.iter().zip(shown) {
+            assert!((a - b).abs() < 1e-5, "{authored:?} shown as {shown:?}");
+        }
+        assert_eq!(srgb_color_to_linear([0.5, 0.5, 0.5, 0.5])[3], 0.5);
+    }
+
+    #[test]
+    fn formats_are_taken_in_ranking_order() {
+        let hdr10 = vk::SurfaceFormatKHR {
+            format: vk::Format::A2B10G10R10_UNORM_PACK32,
+            color_space: vk::ColorSpaceKHR::HDR10_ST2084_EXT,
+        };
+        let ten_bit = surface_format(vk::Format::A2B10G10R10_UNORM_PACK32);
+        let bgra_srgb = surface_format(vk::Format::B8G8R8A8_SRGB);
+        let rgba_srgb = surface_format(vk::Format::R8G8B8A8_SRGB);
+        let bgra_unorm = surface_format(vk::Format::B8G8R8A8_UNORM);
+        let rgba_unorm = surface_format(vk::Format::R8G8B8A8_UNORM);
+        let undefined = surface_format(vk::Format::UNDEFINED);
+        // Available, shader gamma, expected
+        let cases = [
+            (vec![bgra_unorm, bgra_srgb], false, Some(bgra_srgb)),
+            (vec![bgra_unorm, bgra_srgb], true, Some(bgra_unorm)),
+            // A 10 bit format listed first is passed over for 8 bits
+            (vec![ten_bit, rgba_srgb, bgra_srgb], false, Some(bgra_srgb)),
+            (vec![ten_bit, rgba_unorm, rgba_srgb], false, Some(rgba_srgb)),
+            // The other encoding before a format the renderer does not expect
+            (vec![ten_bit, rgba_unorm], false, Some(rgba_unorm)),
+            (vec![hdr10, bgra_srgb], true, Some(bgra_srgb)),
+            // sRGB formats in another color space are not the ones preferred
+            (
+                vec![
+                    hdr10,
+                    vk::SurfaceFormatKHR {
+                        color_space: vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT,
+                        ..bgra_srgb
+                    },
+                ],
+                false,
+                Some(hdr10),
+            ),
+            // A display offering none keeps its first format
+            (vec![hdr10], false, Some(hdr10)),
+            (vec![undefined], false, Some(bgra_srgb)),
+            (vec![undefined], true, Some(bgra_unorm)),
+            (vec![], false, None),
+        ];
+        for (available, shader_gamma, expected) in cases {
+            let chosen =
+                choose_surface_format(&available, &preferred_surface_formats(shader_gamma));
+            assert_eq!(
+                chosen, expected,
+                "shader gamma {shader_gamma} of {available:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn callers_formats_replace_the_ranking() {
+        let ten_bit = surface_format(vk::Format::A2B10G10R10_UNORM_PACK32);
+        let bgra_srgb = surface_format(vk::Format::B8G8R8A8_SRGB);
+        let rgba_unorm = surface_format(vk::Format::R8G8B8A8_UNORM);
+        let custom = [ten_bit, rgba_unorm];
+        let available = [bgra_srgb, rgba_unorm, ten_bit];
+        assert_eq!(choose_surface_format(&available, &custom), Some(ten_bit));
+        // The first available is still the last resort
+        assert_eq!(
+            choose_surface_format(&[bgra_srgb], &custom),
+            Some(bgra_srgb)
+        );
+        assert_eq!(choose_surface_format(&available, &[]), Some(bgra_srgb));
+    }
+
+    /// Within quantization of the 8 and 10 bit sources.
+    fn close(actual: f32, expected: f32) -> bool {
+        (actual - expected).abs() <= 0.004 + 0.025 * expected.abs()
+    }
+
+    fn hdr10() -> vk::SurfaceFormatKHR {
+        vk::SurfaceFormatKHR {
+            format: vk::Format::A2B10G10R10_UNORM_PACK32,
+            color_space: vk::ColorSpaceKHR::HDR10_ST2084_EXT,
+        }
+    }
+
+    /// Stores one linear gray as a texel of a source format.
+    type Encode = fn(f32) -> Vec<u8>;
+
+    fn srgb8_gray(linear: f32) -> Vec<u8> {
+        let encoded = (linear_to_srgb(linear).clamp(0.0, 1.0) * 255.0).round() as u8;
+        vec![encoded, encoded, encoded, 255]
+    }
+
+    fn pq10_gray(linear: f32) -> Vec<u8> {
+        let signal = (nits_to_pq(linear * HDR_REFERENCE_WHITE_NITS) * 1023.0).round() as u32;
+        (3 << 30 | signal << 20 | signal << 10 | signal)
+            .to_le_bytes()
+            .to_vec()
+    }
+
+    fn half_gray(linear: f32) -> Vec<u8> {
+        [linear, linear, linear, 1.0]
+            .iter()
+            .flat_map(|&channel| f32_to_f16(channel).to_le_bytes())
+            .collect()
+    }
+
+    fn half_channels(texel: &[u8]) -> [f32; 4] {
+        [0, 2, 4, 6]
+            .map(|offset| f16_to_f32(u16::from_le_bytes([texel[offset], texel[offset + 1]])))
+    }
+
+    /// Linear gray of each exported texel.
+    fn exported_grays(texels: &[u8], color_space: ExportColorSpace) -> Vec<f32> {
+        match color_space {
+            ExportColorSpace::Srgb8 => texels
+                .chunks_exact(4)
+                .map(|texel| srgb_to_linear(texel[0] as f32 / 255.0))
+                .collect(),
+            ExportColorSpace::Linear16F => texels
+                .chunks_exact(8)
+                .map(|texel| half_channels(texel)[0])
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn half_precision_round_trips() {
+        for half in [0.0, 1.0, -2.5, 0.333, 65504.0, 1e-6] {
+            let back = f16_to_f32(f32_to_f16(half));
+            assert!(
+                (back - half).abs() <= half.abs() * 1e-3 + 1e-7,
+                "{half} came back as {back}"
+            );
+        }
+    }
+
+    #[test]
+    fn gradients_of_each_source_format_export_within_tolerance() {
+        const STEPS: usize = 64;
+        // Brightest gradient value of the HDR sources, relative to SDR white
+        const HDR_PEAK: f32 = 4.0;
+        let sources: [(vk::SurfaceFormatKHR, Encode, f32); 4] = [
+            (surface_format(vk::Format::B8G8R8A8_UNORM), srgb8_gray, 1.0),
+            (surface_format(vk::Format::R8G8B8A8_SRGB), srgb8_gray, 1.0),
+            (hdr10(), pq10_gray, HDR_PEAK),
+            (
+                vk::SurfaceFormatKHR {
+                    format: vk::Format::R16G16B16A16_SFLOAT,
+                    color_space: vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT,
+                },
+                half_gray,
+                HDR_PEAK,
+            ),
+        ];
+        for (format, encode, peak) in sources {
+            let grays: Vec<f32> = (0..STEPS)
+                .map(|step| peak * step as f32 / (STEPS - 1) as f32)
+                .collect();
+            let texels: Vec<u8> = grays.iter().flat_map(|&gray| encode(gray)).collect();
+            for color_space in [ExportColorSpace::Srgb8, ExportColorSpace::Linear16F] {
+                let converted = convert(format, &texels, color_space).unwrap();
+                let exported = exported_grays(&converted, color_space);
+                assert_eq!(exported.len(), STEPS);
+                for (&expected, &actual) in grays.iter().zip(&exported) {
+                    // 8 bit exports stop at SDR white
+                    let expected = match color_space {
+                        ExportColorSpace::Srgb8 => expected.min(1.0),
+                        ExportColorSpace::Linear16F => expected,
+                    };
+                    assert!(
+                        close(actual, expected),
+                        "{:?} to {color_space:?}: {expected} came out as {actual}",
+                        format.format
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bt2020_red_leaves_the_srgb_gamut() {
+        let signal = (nits_to_pq(HDR_REFERENCE_WHITE_NITS) * 1023.0).round() as u32;
+        let red = (3 << 30 | signal).to_le_bytes();
+        let linear = convert(hdr10(), &red, ExportColorSpace::Linear16F).unwrap();
+        let [r, g, b, a] = half_channels(&linear);
+        assert!(
+            close(r, 1.660_491) && close(g, -0.124_550) && close(b, -0.018_151) && a == 1.0,
+            "BT.2020 red converted to {r}, {g}, {b}, {a}"
+        );
+        assert_eq!(
+            convert(hdr10(), &red, ExportColorSpace::Srgb8).unwrap(),
+            [255, 0, 0, 255]
+        );
+    }
+
+    #[test]
+    fn only_surfaces_other_than_rgba8_are_converted() {
+        assert!(needs_conversion(hdr10(), ExportColorSpace::Srgb8));
+        assert!(!needs_conversion(
+            surface_format(vk::Format::B8G8R8A8_SRGB),
+            ExportColorSpace::Srgb8
+        ));
+    }
+}
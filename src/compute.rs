@@ -0,0 +1,382 @@
+//! Meshes whose vertices a compute shader rewrites before every frame.
+//!
+//! A [`VertexDeformer`] names a compute shader compiled from `assets/shaders`, `wave.comp` for
+//! [`VertexDeformer::wave`]. Before each frame the renderer dispatches it once per deformed mesh,
+//! a thread per vertex in groups of [`DEFORM_GROUP_SIZE`], then draws the mesh from what it wrote.
+//! The shader declares the deform set of `descriptor_set`:
+//!
+//! - binding 0, the vertices the mesh was added with, read only
+//! - binding 1, the vertex buffer the mesh is drawn from, written
+//!
+//! Both are arrays of 32 bit words, [`DeformConstants`] in the push constants says where a vertex
+//! starts and where its position and normal are. The deformer only sees the vertices it is
+//! handed, it writes every one of them each frame.
+use crate::{
+    model::Vertex,
+    vulkan::{
+        descriptor_set::{DEFORM_REST_BINDING, DEFORM_VERTEX_BINDING},
+        device::AAADevice,
+        upload::{create_filled_host_buffer, write_mapped, AAAOwnedBuffer},
+    },
+};
+use ash::vk;
+use glam::Vec3;
+use std::{f32::consts::TAU, mem};
+
+/// Threads per group, the `local_size_x` of the deform shaders.
+pub const DEFORM_GROUP_SIZE: u32 = 64;
+
+/// Groups along each axis covering `items` threads of `group_size` threads per group, along X.
+pub fn group_counts(items: u32, group_size: u32) -> [u32; 3] {
+    [items.div_ceil(group_size.max(1)), 1, 1]
+}
+
+/// Compute shader deforming a mesh and the values it is dispatched with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VertexDeformer {
+    /// Name of the compiled shader in `assets/bin`, without its `.spv` extension.
+    pub shader: String,
+    /// Read by the shader as [`DeformConstants::params`].
+    pub params: [f32; 4],
+}
+
+impl VertexDeformer {
+    pub fn new(shader: impl Into<String>, params: [f32; 4]) -> Self {
+        Self {
+            shader: shader.into(),
+            params,
+        }
+    }
+
+    /// Sine wave running along X over a mesh in the XZ plane, see [`wave`].
+    pub fn wave(amplitude: f32, wavelength: f32, speed: f32) -> Self {
+        Self::new("wave", [amplitude, wavelength, speed, 0.0])
+    }
+}
+
+/// `position` of a vertex lifted by the sine wave of `wave.comp` after `time` seconds, and its
+/// normal. `params` are [`VertexDeformer::wave`]'s amplitude, wavelength and speed.
+pub fn wave(position: Vec3, time: f32, params: [f32; 4]) -> (Vec3, Vec3) {
+    let [amplitude, wavelength, speed, _] = params;
+    let k = TAU / wavelength;
+    let phase = k * (position.x - speed * time);
+    let slope = amplitude * k * phase.cos();
+    (
+        position + Vec3::Y * amplitude * phase.sin(),
+        Vec3::new(-slope, 1.0, 0.0).normalize(),
+    )
+}
+
+/// Pushed to the deform shaders before each dispatch, offsets and stride counted in floats.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeformConstants {
+    /// Seconds the mesh has been deformed for, stopped while the simulation is paused.
+    pub time: f32,
+    pub vertex_count: u32,
+    pub stride: u32,
+    pub position: u32,
+    pub normal: u32,
+    _padding: [u32; 3],
+    /// `vec4` in std430, 16 bytes aligned.
+    pub params: [f32; 4],
+}
+
+impl DeformConstants {
+    pub fn new(time: f32, vertex_count: u32, params: [f32; 4]) -> Self {
+        let floats = |bytes: usize| (bytes / mem::size_of::<f32>()) as u32;
+        Self {
+            time,
+            vertex_count,
+            stride: floats(mem::size_of::<Vertex>()),
+            position: floats(mem::offset_of!(Vertex, pos)),
+            normal: floats(mem::offset_of!(Vertex, normal)),
+            _padding: [0; 3],
+            params,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            std::slice::from_raw_parts(self as *const Self as *const u8, mem::size_of::<Self>())
+        }
+    }
+}
+
+/// Copy of the vertices a deformed mesh was added with, bound to its deform set next to the
+/// vertex buffer it is drawn from.
+#[derive(Debug)]
+pub(crate) struct DeformBuffer {
+    pub deformer: VertexDeformer,
+    rest: AAAOwnedBuffer,
+    pub vertex_count: u32,
+    descriptor_pool: vk::DescriptorPool,
+    pub descriptor_set: vk::DescriptorSet,
+    /// Simulation seconds deformed for, see `SimulationClock`.
+    pub time: f32,
+}
+
+impl DeformBuffer {
+    pub fn new(
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        layout: vk::DescriptorSetLayout,
+        deformer: VertexDeformer,
+        vertices: &[Vertex],
+    ) -> Self {
+        let pool_sizes = [vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::STORAGE_BUFFER,
+            descriptor_count: 2,
+        }];
+        let pool_info = vk::DescriptorPoolCreateInfo::default()
+            .pool_sizes(&pool_sizes)
+            .max_sets(1);
+        let layouts = [layout];
+        let (descriptor_pool, descriptor_set) = unsafe {
+            let pool = device
+                .ash
+                .create_descriptor_pool(&pool_info, None)
+                .expect("Failed to create deform descriptor pool");
+            crate::object_audit::created(pool, "deform descriptor pool");
+            let alloc_info = vk::DescriptorSetAllocateInfo::default()
+                .descriptor_pool(pool)
+                .set_layouts(&layouts);
+            let sets = device
+                .ash
+                .allocate_descriptor_sets(&alloc_info)
+                .expect("Failed to allocate deform descriptor set");
+            (pool, sets[0])
+        };
+        let rest = create_filled_host_buffer(
+            device,
+            device_memory_properties,
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+            vertices,
+        );
+        let deform = Self {
+            deformer,
+            rest,
+            vertex_count: vertices.len() as u32,
+            descriptor_pool,
+            descriptor_set,
+            time: 0.0,
+        };
+        deform.bind(device, DEFORM_REST_BINDING, deform.rest.buffer);
+        deform
+    }
+
+    fn bind(&self, device: &AAADevice, binding: u32, buffer: vk::Buffer) {
+        let buffer_info = [vk::DescriptorBufferInfo {
+            buffer,
+            offset: 0,
+            range: vk::WHOLE_SIZE,
+        }];
+        let write = vk::WriteDescriptorSet::default()
+            .dst_set(self.descriptor_set)
+            .dst_binding(binding)
+            .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+            .buffer_info(&buffer_info);
+        unsafe { device.ash.update_descriptor_sets(&[write], &[]) };
+    }
+
+    /// Deform `vertices` from now on, the dispatches reading the previous ones must have completed.
+    pub fn set_rest(
+        &mut self,
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        vertices: &[Vertex],
+    ) {
+        self.vertex_count = vertices.len() as u32;
+        if mem::size_of_val(vertices) as u64 <= self.rest.size {
+            write_mapped(device, self.rest.memory, self.rest.size, vertices);
+            return;
+        }
+        self.destroy_rest(device);
+        self.rest = create_filled_host_buffer(
+            device,
+            device_memory_properties,
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+            vertices,
+        );
+        self.bind(device, DEFORM_REST_BINDING, self.rest.buffer);
+    }
+
+    /// Write into `vertex_buffer`. Bound again before every dispatch, eviction and updates
+    /// reallocate it and a new buffer may reuse the handle of the old one. The previous
+    /// dispatches must have completed.
+    pub fn target(&self, device: &AAADevice, vertex_buffer: vk::Buffer) {
+        self.bind(device, DEFORM_VERTEX_BINDING, vertex_buffer);
+    }
+
+    pub fn constants(&self) -> DeformConstants {
+        DeformConstants::new(self.time, self.vertex_count, self.deformer.params)
+    }
+
+    fn destroy_rest(&self, device: &AAADevice) {
+        unsafe {
+            crate::object_audit::destroyed(self.rest.memory);
+            device.ash.free_memory(self.rest.memory, None);
+            crate::object_audit::destroyed(self.rest.buffer);
+            device.ash.destroy_buffer(self.rest.buffer, None);
+        }
+    }
+
+    pub fn destroy(&self, device: &AAADevice) {
+        self.destroy_rest(device);
+        unsafe {
+            crate::object_audit::destroyed(self.descriptor_pool);
+            device
+                .ash
+                .destroy_descriptor_pool(self.descriptor_pool, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Mesh;
+
+    const AMPLITUDE: f32 = 0.25;
+    const WAVELENGTH: f32 = 2.0;
+    const SPEED: f32 = 0.5;
+    const TOLERANCE: f32 = 1e-5;
+
+    fn plane() -> Mesh {
+        Mesh::plane(6.0, 6.0, 63, [1.0; 4])
+    }
+
+    fn params() -> [f32; 4] {
+        VertexDeformer::wave(AMPLITUDE, WAVELENGTH, SPEED).params
+    }
+
+    /// Words of `vertices` as the deform shaders see them.
+    fn words(vertices: &[Vertex]) -> Vec<u32> {
+        let bytes = unsafe {
+            std::slice::from_raw_parts(vertices.as_ptr().cast::<u8>(), mem::size_of_val(vertices))
+        };
+        bytes
+            .chunks_exact(4)
+            .map(|word| u32::from_ne_bytes(word.try_into().unwrap()))
+            .collect()
+    }
+
+    fn word_vec3(words: &[u32], at: usize) -> Vec3 {
+        Vec3::from_array([0, 1, 2].map(|index| f32::from_bits(words[at + index])))
+    }
+
+    /// What `wave.comp` does to the words of every vertex, one thread at a time.
+    fn run_wave_shader(rest: &[u32], constants: &DeformConstants) -> Vec<u32> {
+        let mut deformed = vec![0; rest.len()];
+        let [x, y, _] = group_counts(constants.vertex_count, DEFORM_GROUP_SIZE);
+        for vertex in 0..x * y * DEFORM_GROUP_SIZE {
+            if vertex >= constants.vertex_count {
+                continue;
+            }
+            let base = (vertex * constants.stride) as usize;
+            let stride = constants.stride as usize;
+            deformed[base..base + stride].copy_from_slice(&rest[base..base + stride]);
+            let position = base + constants.position as usize;
+            let (moved, normal) = wave(word_vec3(rest, position), constants.time, constants.params);
+            deformed[position + 1] = moved.y.to_bits();
+            let at = base + constants.normal as usize;
+            for (index, value) in normal.to_array().into_iter().enumerate() {
+                deformed[at + index] = value.to_bits();
+            }
+        }
+        deformed
+    }
+
+    #[test]
+    fn groups_cover_every_vertex_once() {
+        for (items, groups) in [(0, 0), (1, 1), (64, 1), (65, 2), (4096, 64)] {
+            assert_eq!(
+                group_counts(items, DEFORM_GROUP_SIZE),
+                [groups, 1, 1],
+                "{items} vertices"
+            );
+        }
+    }
+
+    /// The push constants as std430 lays them out, and the words a vertex is read from.
+    #[test]
+    fn constants_match_the_shader_block() {
+        let constants = DeformConstants::new(1.5, 3, params());
+        assert_eq!(mem::size_of::<DeformConstants>(), 48);
+        assert_eq!(mem::offset_of!(DeformConstants, params), 32);
+        // The 128 bytes every device supports
+        assert!(constants.as_bytes().len() <= 128);
+        assert_eq!(constants.stride as usize * 4, mem::size_of::<Vertex>());
+        let vertices = plane().vertices;
+        let words = words(&vertices);
+        for (index, vertex) in vertices.iter().enumerate().take(5) {
+            let base = index * constants.stride as usize;
+            let position = word_vec3(&words, base + constants.position as usize);
+            let normal = word_vec3(&words, base + constants.normal as usize);
+            assert_eq!(position.to_array(), vertex.pos[..3], "vertex {index}");
+            assert_eq!(normal.to_array(), vertex.normal, "vertex {index}");
+        }
+    }
+
+    #[test]
+    fn wave_lifts_and_travels() {
+        let params = params();
+        let assert_close = |actual: Vec3, expected: Vec3| {
+            assert!(
+                actual.abs_diff_eq(expected, TOLERANCE),
+                "{actual} instead of {expected}"
+            )
+        };
+        // Crest a quarter wavelength along, trough three quarters, flat at the origin
+        let crest = wave(Vec3::new(WAVELENGTH / 4.0, 0.0, 1.0), 0.0, params);
+        let trough = wave(Vec3::new(WAVELENGTH * 0.75, 0.0, -1.0), 0.0, params);
+        assert_close(crest.0, Vec3::new(WAVELENGTH / 4.0, AMPLITUDE, 1.0));
+        assert_close(trough.0, Vec3::new(WAVELENGTH * 0.75, -AMPLITUDE, -1.0));
+        assert_close(wave(Vec3::ZERO, 0.0, params).0, Vec3::ZERO);
+        assert_close(crest.1, Vec3::Y);
+        assert_close(trough.1, Vec3::Y);
+        // The crest moves along +X at the wave's speed
+        let later = wave(Vec3::new(WAVELENGTH / 4.0 + SPEED, 0.0, 0.0), 1.0, params);
+        assert!((later.0.y - AMPLITUDE).abs() <= TOLERANCE, "{}", later.0);
+        // Normals stay perpendicular to the slope
+        for step in 0..16 {
+            let x = step as f32 * WAVELENGTH / 16.0;
+            let (moved, normal) = wave(Vec3::new(x, 0.0, 0.0), 0.3, params);
+            let (ahead, _) = wave(Vec3::new(x + 1e-3, 0.0, 0.0), 0.3, params);
+            let slope = (ahead - moved).normalize();
+            assert!(normal.dot(slope).abs() <= 1e-2, "{normal} against {slope}");
+            assert!((normal.length() - 1.0).abs() <= TOLERANCE, "{normal}");
+        }
+    }
+
+    /// The shader's work over the whole plane: every vertex deformed, only its height and normal.
+    #[test]
+    fn shader_deforms_only_heights_and_normals() {
+        let vertices = plane().vertices;
+        let rest = words(&vertices);
+        let constants = DeformConstants::new(2.25, vertices.len() as u32, params());
+        let deformed = run_wave_shader(&rest, &constants);
+        let stride = constants.stride as usize;
+        for (index, vertex) in vertices.iter().enumerate() {
+            let base = index * stride;
+            let position = base + constants.position as usize;
+            let normal = base + constants.normal as usize;
+            let (expected, expected_normal) = wave(
+                Vec3::from_slice(&vertex.pos),
+                constants.time,
+                constants.params,
+            );
+            assert_eq!(word_vec3(&deformed, position), expected, "vertex {index}");
+            assert_eq!(
+                word_vec3(&deformed, normal),
+                expected_normal,
+                "vertex {index}"
+            );
+            for word in base..base + stride {
+                if word != position + 1 && !(normal..normal + 3).contains(&word) {
+                    assert_eq!(deformed[word], rest[word], "vertex {index}");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,600 @@
+use crate::{
+    app::{WIN_START_INNER_SIZE, WIN_TITLE},
+    dof::DepthOfFieldConfig,
+    gizmo::GizmoSnapping,
+    present_health::PresentModePreference,
+    update_queue::{self, Backpressure},
+    world::WorldConvention,
+};
+use ash::vk;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+/// Environment variable overriding the configuration file location.
+pub const CONFIG_PATH_ENV: &str = "PULSAR_CONFIG";
+const CONFIG_FILE_NAME: &str = "pulsar.toml";
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    pub title: String,
+    pub width: u32,
+    pub height: u32,
+    pub transparent: bool,
+    /// Seconds without a rendered frame before the render thread is reported as stalled, 0 disables.
+    pub stall_threshold_secs: f32,
+    /// Create windows hidden and show them once their first frame is presented, instead of
+    /// showing whatever the compositor has until then.
+    pub hidden_until_first_frame: bool,
+    /// Mesh updates a window queues for its render thread, after merging, see
+    /// [`crate::update_queue`].
+    pub update_queue_capacity: usize,
+    /// Whether posting to a full update queue waits or fails.
+    pub update_backpressure: Backpressure,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            title: WIN_TITLE.to_string(),
+            width: WIN_START_INNER_SIZE.width,
+            height: WIN_START_INNER_SIZE.height,
+            transparent: true,
+            stall_threshold_secs: 5.0,
+            hidden_until_first_frame: false,
+            update_queue_capacity: update_queue::DEFAULT_CAPACITY,
+            update_backpressure: Backpressure::default(),
+        }
+    }
+}
+
+/// Host memory the replay buffer may use before evicting the oldest frames.
+pub const REPLAY_BUDGET_MB: usize = 64;
+/// Rate at which frames are captured into the replay buffer.
+pub const REPLAY_CAPTURE_FPS: u32 = 15;
+
+/// Settings of the render targets, read when a window is created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RenderSettings {
+    /// Samples per pixel of the color and depth targets, resolved into the presented image. 1
+    /// disables MSAA.
+    pub msaa_samples: u32,
+    /// Present mode of the window's first swapchain, cycled at runtime by the window's key
+    /// binding.
+    pub present_mode: PresentModePreference,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            msaa_samples: 1,
+            present_mode: PresentModePreference::default(),
+        }
+    }
+}
+
+impl RenderSettings {
+    /// The largest count of `supported` at most `msaa_samples`, a single sample otherwise.
+    /// `supported` is the intersection of the device's color and depth framebuffer counts.
+    pub fn sample_count(self, supported: vk::SampleCountFlags) -> vk::SampleCountFlags {
+        [
+            vk::SampleCountFlags::TYPE_64,
+            vk::SampleCountFlags::TYPE_32,
+            vk::SampleCountFlags::TYPE_16,
+            vk::SampleCountFlags::TYPE_8,
+            vk::SampleCountFlags::TYPE_4,
+            vk::SampleCountFlags::TYPE_2,
+        ]
+        .into_iter()
+        .find(|&count| count.as_raw() <= self.msaa_samples && supported.contains(count))
+        .unwrap_or(vk::SampleCountFlags::TYPE_1)
+    }
+}
+
+/// Settings read by the render thread.
+///
+/// Applied live, see [`PulsarConfig::apply_live`]: `clear_color`, `fps_cap`, `gizmo_snapping`,
+/// `depth_of_field`, `reference_grid`, `clamp_delta_after_stall`, `sort_by_material`,
+/// `texture_hot_reload` and `present_wait_pacing`. Read once and kept until a restart: the replay
+/// settings, `world_convention`, `demo_scene`, `shader_gamma` and `render`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphicsConfig {
+    pub clear_color: [f32; 4],
+    /// Frames per second limit, 0 means uncapped.
+    pub fps_cap: u32,
+    /// Replay settings are kept without the `replay` feature, they are ignored then.
+    pub replay_budget_mb: usize,
+    pub replay_capture_fps: u32,
+    pub gizmo_snapping: GizmoSnapping,
+    pub depth_of_field: DepthOfFieldConfig,
+    /// Read when a window is created.
+    pub world_convention: WorldConvention,
+    /// Ground grid and world axes.
+    pub reference_grid: bool,
+    /// Animate by the last steady delta after a frame held by the compositor, instead of
+    /// catching up the stall at once.
+    pub clamp_delta_after_stall: bool,
+    /// Cover quads registered at startup to show the renderer works, off since applications
+    /// register their own meshes. Read when a window is created.
+    pub demo_scene: bool,
+    /// Group the perspective draws of each sort bias bucket by material, binding each pipeline
+    /// once. Co-planar meshes need a bias or a depth biased material to keep their order, see
+    /// `draw_order`; off draws in registration order.
+    pub sort_by_material: bool,
+    /// Shaders gamma correct their output themselves: the swapchain and textures stay `_UNORM`
+    /// so nothing is encoded twice. Off, the swapchain is `_SRGB` when the display offers it and
+    /// textures are decoded to linear when sampled. Read when a window is created.
+    pub shader_gamma: bool,
+    /// Watch the texture file loaded last and swap it in again whenever it changes on disk. Read
+    /// when a texture file loads.
+    pub texture_hot_reload: bool,
+    /// Wait for the previous frame to reach the screen before sampling input, on devices with
+    /// `VK_KHR_present_wait`. Lowers the input latency, see `present_wait`.
+    pub present_wait_pacing: bool,
+    /// Read when a window is created.
+    pub render: RenderSettings,
+}
+
+impl Default for GraphicsConfig {
+    fn default() -> Self {
+        Self {
+            clear_color: [0.0, 0.0, 0.0, 0.0],
+            fps_cap: 0,
+            replay_budget_mb: REPLAY_BUDGET_MB,
+            replay_capture_fps: REPLAY_CAPTURE_FPS,
+            gizmo_snapping: GizmoSnapping::default(),
+            depth_of_field: DepthOfFieldConfig::default(),
+            world_convention: WorldConvention::default(),
+            reference_grid: true,
+            clamp_delta_after_stall: true,
+            demo_scene: false,
+            sort_by_material: true,
+            shader_gamma: false,
+            texture_hot_reload: true,
+            present_wait_pacing: false,
+            render: RenderSettings::default(),
+        }
+    }
+}
+
+impl GraphicsConfig {
+    pub fn with_demo_scene(mut self, demo_scene: bool) -> Self {
+        self.demo_scene = demo_scene;
+        self
+    }
+}
+
+/// Prometheus scrape endpoint, served when built with the `metrics-endpoint` feature.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    pub enabled: bool,
+    /// Address to listen on, keep it on localhost unless the network is trusted.
+    pub bind: String,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind: "127.0.0.1:9184".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PulsarConfig {
+    pub window: WindowConfig,
+    pub graphics: GraphicsConfig,
+    pub metrics: MetricsConfig,
+}
+
+/// Values set in code, they take precedence over the configuration file.
+#[derive(Debug, Clone, Default)]
+pub struct ApplicationOptions {
+    pub config_path: Option<PathBuf>,
+    pub title: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub transparent: Option<bool>,
+    pub clear_color: Option<[f32; 4]>,
+    pub fps_cap: Option<u32>,
+    pub gizmo_snapping: Option<GizmoSnapping>,
+    pub depth_of_field: Option<DepthOfFieldConfig>,
+    pub world_convention: Option<WorldConvention>,
+    pub demo_scene: Option<bool>,
+    pub hidden_until_first_frame: Option<bool>,
+    pub render: Option<RenderSettings>,
+    /// Surface formats the swapchain takes in this order before the surface's first, in place of
+    /// `color_space::preferred_surface_formats`.
+    pub surface_formats: Option<Vec<vk::SurfaceFormatKHR>>,
+}
+
+impl PulsarConfig {
+    /// `PULSAR_CONFIG` if set, otherwise `pulsar.toml` next to the executable.
+    pub fn default_path() -> PathBuf {
+        if let Ok(path) = std::env::var(CONFIG_PATH_ENV) {
+            return PathBuf::from(path);
+        }
+        std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(|dir| dir.join(CONFIG_FILE_NAME)))
+            .unwrap_or_else(|| PathBuf::from(CONFIG_FILE_NAME))
+    }
+
+    /// Load the file, falling back to defaults when it does not exist.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        if !path.exists() {
+            info!("No configuration at {}, using defaults", path.display());
+            return Ok(Self::default());
+        }
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(source: &str) -> Result<Self, Box<dyn Error>> {
+        let table: toml::Table = source.parse()?;
+        let known = toml::Table::try_from(Self::default())?;
+        warn_unknown_keys("", &table, &known);
+        Ok(table.try_into()?)
+    }
+
+    pub fn write_default(path: &Path) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, toml::to_string_pretty(&Self::default())?)?;
+        Ok(())
+    }
+
+    /// Apply the values specified in code on top of the file.
+    pub fn merged(mut self, options: &ApplicationOptions) -> Self {
+        if let Some(title) = &options.title {
+            self.window.title = title.clone();
+        }
+        if let Some(width) = options.width {
+            self.window.width = width;
+        }
+        if let Some(height) = options.height {
+            self.window.height = height;
+        }
+        if let Some(transparent) = options.transparent {
+            self.window.transparent = transparent;
+        }
+        if let Some(clear_color) = options.clear_color {
+            self.graphics.clear_color = clear_color;
+        }
+        if let Some(fps_cap) = options.fps_cap {
+            self.graphics.fps_cap = fps_cap;
+        }
+        if let Some(gizmo_snapping) = options.gizmo_snapping {
+            self.graphics.gizmo_snapping = gizmo_snapping;
+        }
+        if let Some(depth_of_field) = options.depth_of_field {
+            self.graphics.depth_of_field = depth_of_field;
+        }
+        if let Some(world_convention) = options.world_convention {
+            self.graphics.world_convention = world_convention;
+        }
+        if let Some(demo_scene) = options.demo_scene {
+            self.graphics.demo_scene = demo_scene;
+        }
+        if let Some(hidden) = options.hidden_until_first_frame {
+            self.window.hidden_until_first_frame = hidden;
+        }
+        if let Some(render) = options.render {
+            self.graphics.render = render;
+        }
+        self
+    }
+
+    /// Copy the settings that can change while rendering, returns the ones that need a restart.
+    pub fn apply_live(&self, reloaded: &Self, live: &mut GraphicsConfig) -> Vec<&'static str> {
+        live.clear_color = reloaded.graphics.clear_color;
+        live.fps_cap = reloaded.graphics.fps_cap;
+        live.gizmo_snapping = reloaded.graphics.gizmo_snapping;
+        live.depth_of_field = reloaded.graphics.depth_of_field;
+        live.reference_grid = reloaded.graphics.reference_grid;
+        live.clamp_delta_after_stall = reloaded.graphics.clamp_delta_after_stall;
+        live.sort_by_material = reloaded.graphics.sort_by_material;
+        live.texture_hot_reload = reloaded.graphics.texture_hot_reload;
+        live.present_wait_pacing = reloaded.graphics.present_wait_pacing;
+
+        let mut restart = Vec::new();
+        if self.window != reloaded.window {
+            restart.push("window");
+        }
+        if self.metrics != reloaded.metrics {
+            restart.push("metrics");
+        }
+        if self.graphics.replay_budget_mb != reloaded.graphics.replay_budget_mb {
+            restart.push("graphics.replay_budget_mb");
+        }
+        if self.graphics.replay_capture_fps != reloaded.graphics.replay_capture_fps {
+            restart.push("graphics.replay_capture_fps");
+        }
+        if self.graphics.world_convention != reloaded.graphics.world_convention {
+            restart.push("graphics.world_convention");
+        }
+        if self.graphics.demo_scene != reloaded.graphics.demo_scene {
+            restart.push("graphics.demo_scene");
+        }
+        if self.graphics.shader_gamma != reloaded.graphics.shader_gamma {
+            restart.push("graphics.shader_gamma");
+        }
+        if self.graphics.render != reloaded.graphics.render {
+            restart.push("graphics.render");
+        }
+        restart
+    }
+}
+
+fn warn_unknown_keys(prefix: &str, table: &toml::Table, known: &toml::Table) {
+    for (key, value) in table {
+        match known.get(key) {
+            Some(toml::Value::Table(known_section)) => {
+                if let toml::Value::Table(section) = value {
+                    warn_unknown_keys(&format!("{prefix}{key}."), section, known_section);
+                }
+            }
+            Some(_) => {}
+            None => {
+                let valid: Vec<&str> = known.keys().map(String::as_str).collect();
+                warn!(
+                    "Unknown configuration key `{prefix}{key}`, valid options are: {}",
+                    valid.join(", ")
+                );
+            }
+        }
+    }
+}
+
+/// Poll the file for changes and push the live subset to the render threads.
+/// Values set in code keep winning over the reloaded file.
+pub fn watch(
+    path: PathBuf,
+    options: ApplicationOptions,
+    mut current: PulsarConfig,
+    live: Arc<RwLock<GraphicsConfig>>,
+) {
+    thread::spawn(move || {
+        let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+        let mut last_modified: Option<SystemTime> = modified(&path);
+        loop {
+            thread::sleep(CONFIG_POLL_INTERVAL);
+            let now_modified = modified(&path);
+            if now_modified == last_modified {
+                continue;
+            }
+            last_modified = now_modified;
+
+            let reloaded = match PulsarConfig::load(&path) {
+                Ok(config) => config.merged(&options),
+                Err(err) => {
+                    error!("Failed to reload {}: {err}", path.display());
+                    continue;
+                }
+            };
+            let restart = {
+                let mut live = live.write().unwrap();
+                current.apply_live(&reloaded, &mut live)
+            };
+            info!("Reloaded configuration from {}", path.display());
+            if !restart.is_empty() {
+                warn!(
+                    "Configuration changes requiring a restart: {}",
+                    restart.join(", ")
+                );
+            }
+            current = reloaded;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_round_trips() {
+        let written = toml::to_string_pretty(&PulsarConfig::default()).unwrap();
+        assert_eq!(
+            PulsarConfig::parse(&written).unwrap(),
+            PulsarConfig::default()
+        );
+
+        let mut config = PulsarConfig::default();
+        config.window.title = "Round trip".to_string();
+        config.graphics.fps_cap = 30;
+        config.graphics.render.msaa_samples = 4;
+        config.metrics.enabled = true;
+        let written = toml::to_string_pretty(&config).unwrap();
+        assert_eq!(PulsarConfig::parse(&written).unwrap(), config);
+    }
+
+    #[test]
+    fn missing_and_unknown_keys() {
+        let config = PulsarConfig::parse("[graphics]\nfps_cap = 144\nunknown = 1\n").unwrap();
+        let mut expected = PulsarConfig::default();
+        expected.graphics.fps_cap = 144;
+        assert_eq!(config, expected);
+        assert!(PulsarConfig::parse("[graphics]\nfps_cap = \"fast\"\n").is_err());
+    }
+
+    #[test]
+    fn code_wins_over_the_file() {
+        let file = PulsarConfig::parse("[window]\ntitle = \"File\"\nwidth = 640\n").unwrap();
+        let merged = file.merged(&ApplicationOptions {
+            title: Some("Code".to_string()),
+            fps_cap: Some(60),
+            ..Default::default()
+        });
+        assert_eq!(merged.window.title, "Code");
+        assert_eq!(merged.window.width, 640);
+        assert_eq!(merged.graphics.fps_cap, 60);
+    }
+
+    #[test]
+    fn live_settings_and_restarts() {
+        let current = PulsarConfig::default();
+        let mut reloaded = current.clone();
+        reloaded.graphics.clear_color = [1.0, 0.0, 0.0, 1.0];
+        reloaded.graphics.fps_cap = 30;
+        reloaded.window.width += 1;
+        reloaded.graphics.replay_budget_mb += 1;
+        let mut live = current.graphics;
+        let restart = current.apply_live(&reloaded, &mut live);
+        assert_eq!(live.clear_color, [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(live.fps_cap, 30);
+        assert_eq!(live.replay_budget_mb, current.graphics.replay_budget_mb);
+        assert_eq!(restart, ["window", "graphics.replay_budget_mb"]);
+    }
+
+    #[test]
+    fn restart_only_settings_are_reported_and_kept() {
+        let current = PulsarConfig::default();
+        let mut reloaded = current.clone();
+        reloaded.graphics.world_convention = WorldConvention::ZUp;
+        reloaded.graphics.demo_scene = !current.graphics.demo_scene;
+        reloaded.graphics.shader_gamma = !current.graphics.shader_gamma;
+        reloaded.graphics.render.msaa_samples = 4;
+        let mut live = current.graphics;
+        assert_eq!(
+            current.apply_live(&reloaded, &mut live),
+            [
+                "graphics.world_convention",
+                "graphics.demo_scene",
+                "graphics.shader_gamma",
+                "graphics.render"
+            ]
+        );
+        assert_eq!(
+            live, current.graphics,
+            "A restart only setting applied live"
+        );
+    }
+
+    #[test]
+    fn present_mode_is_read_and_restarts_the_render_settings() {
+        let current = PulsarConfig::default();
+        assert_eq!(
+            current.graphics.render.present_mode,
+            PresentModePreference::Auto
+        );
+        let reloaded =
+            PulsarConfig::parse("[graphics.render]\npresent_mode = \"vsync_relaxed\"\n").unwrap();
+        assert_eq!(
+            reloaded.graphics.render.present_mode,
+            PresentModePreference::VsyncRelaxed
+        );
+        let mut live = current.graphics;
+        assert_eq!(
+            current.apply_live(&reloaded, &mut live),
+            ["graphics.render"]
+        );
+    }
+
+    #[test]
+    fn demo_scene_is_opt_in() {
+        assert!(!GraphicsConfig::default().demo_scene);
+        assert!(!PulsarConfig::parse("").unwrap().graphics.demo_scene);
+        assert!(GraphicsConfig::default().with_demo_scene(true).demo_scene);
+
+        // The options win over the file, and a reload asks for a restart rather than applying it
+        let file = PulsarConfig::parse("[graphics]\ndemo_scene = true").unwrap();
+        let options = ApplicationOptions {
+            demo_scene: Some(false),
+            ..Default::default()
+        };
+        let merged = file.clone().merged(&options);
+        assert!(!merged.graphics.demo_scene);
+        let mut live = merged.graphics;
+        assert_eq!(merged.apply_live(&file, &mut live), ["graphics.demo_scene"]);
+        assert!(!live.demo_scene);
+    }
+
+    #[test]
+    fn windows_are_hidden_until_their_first_frame_only_when_asked() {
+        assert!(!WindowConfig::default().hidden_until_first_frame);
+        let options = ApplicationOptions {
+            hidden_until_first_frame: Some(true),
+            ..Default::default()
+        };
+        assert!(
+            PulsarConfig::default()
+                .merged(&options)
+                .window
+                .hidden_until_first_frame
+        );
+    }
+
+    #[test]
+    fn sample_counts_are_clamped_to_the_device() {
+        let color = vk::SampleCountFlags::TYPE_1
+            | vk::SampleCountFlags::TYPE_2
+            | vk::SampleCountFlags::TYPE_4
+            | vk::SampleCountFlags::TYPE_8;
+        let depth = vk::SampleCountFlags::TYPE_1
+            | vk::SampleCountFlags::TYPE_4
+            | vk::SampleCountFlags::TYPE_8
+            | vk::SampleCountFlags::TYPE_16;
+        // Requested, supported, expected
+        let cases = [
+            (1, color & depth, 1),
+            (4, color & depth, 4),
+            (8, color & depth, 8),
+            // Above the device's best, and a count that is not a power of two
+            (16, color & depth, 8),
+            (6, color & depth, 4),
+            // Two is offered for color only
+            (2, color & depth, 1),
+            (0, color & depth, 1),
+            (4, vk::SampleCountFlags::TYPE_1, 1),
+        ];
+        for (msaa_samples, supported, expected) in cases {
+            let samples = RenderSettings {
+                msaa_samples,
+                ..Default::default()
+            }
+            .sample_count(supported);
+            assert_eq!(
+                samples.as_raw(),
+                expected,
+                "{msaa_samples} samples of {supported:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn sample_count_is_read_when_a_window_is_created() {
+        let defaults = PulsarConfig::default();
+        assert_eq!(defaults.graphics.render.msaa_samples, 1);
+        let reloaded = PulsarConfig::parse("[graphics.render]\nmsaa_samples = 4\n").unwrap();
+        assert_eq!(reloaded.graphics.render.msaa_samples, 4);
+        let mut live = defaults.graphics;
+        assert_eq!(
+            defaults.apply_live(&reloaded, &mut live),
+            ["graphics.render"]
+        );
+        assert_eq!(live.render, defaults.graphics.render);
+        let merged = defaults.merged(&ApplicationOptions {
+            render: Some(RenderSettings {
+                msaa_samples: 8,
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        assert_eq!(merged.graphics.render.msaa_samples, 8);
+    }
+}
@@ -0,0 +1,167 @@
+//! Bounding spheres and view frustums, what residency tells the meshes in view from the others by.
+use glam::{Mat4, Vec3, Vec4, Vec4Swizzles};
+
+/// Sphere around a set of points, centered on their bounds rather than the smallest one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    /// Around `points`, `None` without any.
+    pub fn of_points(points: &[Vec3]) -> Option<Self> {
+        let first = *points.first()?;
+        let (min, max) = points.iter().fold((first, first), |(min, max), &point| {
+            (min.min(point), max.max(point))
+        });
+        let center = (min + max) / 2.0;
+        let radius = points
+            .iter()
+            .map(|&point| point.distance_squared(center))
+            .fold(0.0, f32::max)
+            .sqrt();
+        Some(Self { center, radius })
+    }
+
+    /// Moved by `transform`, the radius scaled by its largest axis scale so the points it was
+    /// around stay inside.
+    pub fn transformed(self, transform: Mat4) -> Self {
+        let scale = transform
+            .x_axis
+            .xyz()
+            .length()
+            .max(transform.y_axis.xyz().length())
+            .max(transform.z_axis.xyz().length());
+        Self {
+            center: transform.transform_point3(self.center),
+            radius: self.radius * scale,
+        }
+    }
+}
+
+/// The planes bounding what a camera sees, normals pointing inside.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Planes of Vulkan's clip volume, depth from 0 to 1, seen through `projection_view`.
+    /// A plane at infinity, the far plane of an infinite projection, bounds nothing.
+    pub fn from_projection_view(projection_view: Mat4) -> Self {
+        let [x, y, z, w] = [0, 1, 2, 3].map(|row| projection_view.row(row));
+        let planes = [w + x, w - x, w + y, w - y, z, w - z].map(|plane| {
+            let length = plane.xyz().length();
+            if length > f32::EPSILON {
+                plane / length
+            } else {
+                Vec4::W
+            }
+        });
+        Self { planes }
+    }
+
+    /// Whether any of `sphere` may be seen. Spheres outside next to a corner are kept, the test
+    /// is per plane.
+    pub fn intersects_sphere(&self, sphere: BoundingSphere) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.xyz().dot(sphere.center) + plane.w >= -sphere.radius)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sphere(x: f32, y: f32, z: f32, radius: f32) -> BoundingSphere {
+        BoundingSphere {
+            center: Vec3::new(x, y, z),
+            radius,
+        }
+    }
+
+    /// Looking down -Z from the origin, 90 degrees high, 1 to 100 units away.
+    fn perspective() -> Frustum {
+        Frustum::from_projection_view(Mat4::perspective_rh(
+            std::f32::consts::FRAC_PI_2,
+            1.0,
+            1.0,
+            100.0,
+        ))
+    }
+
+    #[test]
+    fn sphere_around_points() {
+        let bounds =
+            BoundingSphere::of_points(&[Vec3::new(-1.0, 0.0, 0.0), Vec3::new(3.0, 0.0, 0.0)])
+                .unwrap();
+        assert_eq!(bounds, sphere(1.0, 0.0, 0.0, 2.0));
+        assert_eq!(BoundingSphere::of_points(&[]), None);
+    }
+
+    #[test]
+    fn transformed_sphere_keeps_its_points() {
+        let transform = Mat4::from_scale_rotation_translation(
+            Vec3::new(1.0, 3.0, 2.0),
+            glam::Quat::from_rotation_y(0.7),
+            Vec3::new(5.0, 0.0, -2.0),
+        );
+        let points = [Vec3::new(1.0, 1.0, 0.0), Vec3::new(-1.0, 0.0, 2.0)];
+        let moved = BoundingSphere::of_points(&points)
+            .unwrap()
+            .transformed(transform);
+        for point in points {
+            let distance = transform.transform_point3(point).distance(moved.center);
+            assert!(distance <= moved.radius + 1e-4, "{distance} {moved:?}");
+        }
+    }
+
+    #[test]
+    fn perspective_frustum() {
+        let frustum = perspective();
+        assert!(frustum.intersects_sphere(sphere(0.0, 0.0, -10.0, 1.0)));
+        // Behind the camera, in front of the near plane and past the far plane
+        assert!(!frustum.intersects_sphere(sphere(0.0, 0.0, 10.0, 1.0)));
+        assert!(!frustum.intersects_sphere(sphere(0.0, 0.0, -0.5, 0.25)));
+        assert!(!frustum.intersects_sphere(sphere(0.0, 0.0, -102.0, 1.0)));
+        // 10 units away the sides are 10 units off the axis
+        assert!(!frustum.intersects_sphere(sphere(12.0, 0.0, -10.0, 1.0)));
+        assert!(!frustum.intersects_sphere(sphere(0.0, -12.0, -10.0, 1.0)));
+        // Straddling a side or the near plane
+        assert!(frustum.intersects_sphere(sphere(10.5, 0.0, -10.0, 1.0)));
+        assert!(frustum.intersects_sphere(sphere(0.0, 0.0, 0.0, 1.5)));
+    }
+
+    #[test]
+    fn moved_camera() {
+        let view = Mat4::look_at_rh(Vec3::new(50.0, 0.0, 0.0), Vec3::ZERO, Vec3::Y);
+        let projection = Mat4::perspective_rh(std::f32::consts::FRAC_PI_2, 1.0, 1.0, 100.0);
+        let frustum = Frustum::from_projection_view(projection * view);
+        assert!(frustum.intersects_sphere(sphere(0.0, 0.0, 0.0, 1.0)));
+        assert!(frustum.intersects_sphere(sphere(0.0, 0.0, -10.0, 1.0)));
+        assert!(!frustum.intersects_sphere(sphere(60.0, 0.0, 0.0, 1.0)));
+    }
+
+    #[test]
+    fn orthographic_frustum() {
+        let frustum =
+            Frustum::from_projection_view(Mat4::orthographic_rh(0.0, 200.0, 100.0, 0.0, -1.0, 1.0));
+        assert!(frustum.intersects_sphere(sphere(100.0, 50.0, 0.0, 1.0)));
+        assert!(frustum.intersects_sphere(sphere(-0.5, 50.0, 0.0, 1.0)));
+        assert!(!frustum.intersects_sphere(sphere(-2.0, 50.0, 0.0, 1.0)));
+        assert!(!frustum.intersects_sphere(sphere(100.0, 102.0, 0.0, 1.0)));
+    }
+
+    #[test]
+    fn infinite_far_plane_bounds_nothing() {
+        let frustum = Frustum::from_projection_view(Mat4::perspective_infinite_rh(
+            std::f32::consts::FRAC_PI_2,
+            1.0,
+            1.0,
+        ));
+        assert!(frustum.intersects_sphere(sphere(0.0, 0.0, -1.0e6, 1.0)));
+        assert!(!frustum.intersects_sphere(sphere(0.0, 0.0, 10.0, 1.0)));
+    }
+}
@@ -0,0 +1,235 @@
+//! Application recorded Vulkan commands at fixed points of the frame, for custom pipelines and
+//! extensions without forking the renderer.
+//!
+//! | Slot | Where | Attachments |
+//! |------|-------|-------------|
+//! | `BeforeMain` | Outside any render pass, after the texture uploads | Color undefined, depth `DEPTH_STENCIL_ATTACHMENT_OPTIMAL`, both cleared when the main pass begins |
+//! | `AfterMain` | Main render pass, subpass 0, after the scene and debug lines, before the gizmo and the UI | Color `COLOR_ATTACHMENT_OPTIMAL`, depth `DEPTH_STENCIL_ATTACHMENT_OPTIMAL` |
+//! | `AfterUi` | Main render pass, subpass 0, after the UI, right before it ends | Same as `AfterMain` |
+//!
+//! Inside the render pass the viewport and scissor cover the whole target, the default pipeline,
+//! the global and default material descriptor sets and some vertex and index buffers are bound.
+//! Bind anything, the renderer binds its own state again after the callback. Pipelines drawing in
+//! these slots are built against [`FrameContext::render_pass`], one color and one depth
+//! attachment, with dynamic viewport and scissor. Do not end the render pass nor keep the command buffer after returning.
+//! Clear part of the target with [`FrameContext::clear_region`], inside the render pass only.
+
+use crate::flight_recorder::Decision;
+use ash::vk;
+use glam::Mat4;
+use log::error;
+use std::panic::{self, AssertUnwindSafe};
+
+/// Point of the frame a custom pass is recorded at, see the module documentation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum CustomPassSlot {
+    BeforeMain,
+    AfterMain,
+    AfterUi,
+}
+
+impl CustomPassSlot {
+    pub const ALL: [Self; 3] = [Self::BeforeMain, Self::AfterMain, Self::AfterUi];
+}
+
+/// What a custom pass knows about the frame it records into.
+#[derive(Debug, Clone, Copy)]
+pub struct FrameContext {
+    /// Render pass of the `AfterMain` and `AfterUi` slots.
+    pub render_pass: vk::RenderPass,
+    pub extent: vk::Extent2D,
+    /// Projection view of the perspective camera and of the UI camera.
+    pub perspective: Mat4,
+    pub orthographic: Mat4,
+    /// Memory types of the device, to allocate buffers and images.
+    pub device_memory_properties: vk::PhysicalDeviceMemoryProperties,
+    /// Frames the window presented before this one.
+    pub frame: u64,
+    /// Recorded for a screenshot at its own extent rather than for the window.
+    pub offscreen: bool,
+    /// Color attachments of the subpass and whether it has a depth attachment.
+    pub color_attachments: u32,
+    pub depth_attachment: bool,
+    /// Samples per pixel of the attachments, pipelines drawn in the pass rasterize with as many.
+    pub samples: vk::SampleCountFlags,
+}
+
+/// Attachments [`FrameContext::clear_region`] clears, and to what.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClearTarget {
+    Color([f32; 4]),
+    /// Depth from 0 at the near plane to 1 at the far plane, 1 clears to empty.
+    Depth(f32),
+    Both {
+        color: [f32; 4],
+        depth: f32,
+    },
+}
+
+impl FrameContext {
+    /// Part of `rect` inside the target, `None` when nothing is left.
+    pub fn clamp_region(&self, rect: vk::Rect2D) -> Option<vk::Rect2D> {
+        let start = |offset: i32| offset.max(0) as i64;
+        let end =
+            |offset: i32, size: u32, limit: u32| (offset as i64 + size as i64).min(limit as i64);
+        let (x, y) = (start(rect.offset.x), start(rect.offset.y));
+        let right = end(rect.offset.x, rect.extent.width, self.extent.width);
+        let bottom = end(rect.offset.y, rect.extent.height, self.extent.height);
+        (right > x && bottom > y).then(|| vk::Rect2D {
+            offset: vk::Offset2D {
+                x: x as i32,
+                y: y as i32,
+            },
+            extent: vk::Extent2D {
+                width: (right - x) as u32,
+                height: (bottom - y) as u32,
+            },
+        })
+    }
+
+    /// Clear `rect` of the first color attachment, the depth attachment or both, clamped to the
+    /// target. Records `cmd_clear_attachments`, so the render pass must be running: the
+    /// `AfterMain` and `AfterUi` slots, not `BeforeMain`. The scissor does not apply.
+    pub fn clear_region(
+        &self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        rect: vk::Rect2D,
+        target: ClearTarget,
+    ) {
+        let Some(rect) = self.clamp_region(rect) else {
+            return;
+        };
+        let color = |float32| vk::ClearAttachment {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            color_attachment: 0,
+            clear_value: vk::ClearValue {
+                color: vk::ClearColorValue { float32 },
+            },
+        };
+        let depth = |depth| vk::ClearAttachment {
+            aspect_mask: vk::ImageAspectFlags::DEPTH,
+            color_attachment: 0,
+            clear_value: vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue { depth, stencil: 0 },
+            },
+        };
+        let attachments = match target {
+            ClearTarget::Color(value) => vec![color(value)],
+            ClearTarget::Depth(value) => vec![depth(value)],
+            ClearTarget::Both {
+                color: color_value,
+                depth: depth_value,
+            } => vec![color(color_value), depth(depth_value)],
+        };
+        debug_assert!(
+            attachments.iter().all(|attachment| {
+                if attachment.aspect_mask == vk::ImageAspectFlags::COLOR {
+                    attachment.color_attachment < self.color_attachments
+                } else {
+                    self.depth_attachment
+                }
+            }),
+            "{target:?} clears an attachment the render pass does not have"
+        );
+        unsafe {
+            device.cmd_clear_attachments(
+                command_buffer,
+                &attachments,
+                &[vk::ClearRect {
+                    rect,
+                    base_array_layer: 0,
+                    layer_count: 1,
+                }],
+            );
+        }
+    }
+}
+
+/// Records commands into the frame's command buffer. Dropped with the window's renderer, after the
+/// device is idle and before it is destroyed, so state it owns can free its Vulkan objects.
+pub type CustomPass = Box<dyn FnMut(&ash::Device, vk::CommandBuffer, &FrameContext) + Send>;
+
+/// The custom pass of each slot.
+#[derive(Default)]
+pub(crate) struct CustomPasses {
+    passes: [Option<CustomPass>; 3],
+}
+
+impl CustomPasses {
+    pub fn set(&mut self, slot: CustomPassSlot, pass: Option<CustomPass>) {
+        self.passes[slot as usize] = pass;
+    }
+
+    pub fn is_set(&self, slot: CustomPassSlot) -> bool {
+        self.passes[slot as usize].is_some()
+    }
+
+    /// Run the pass of `slot` if any. A panicking pass is removed and reported, the frame goes on
+    /// with whatever it recorded.
+    pub fn record(
+        &mut self,
+        slot: CustomPassSlot,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        context: &FrameContext,
+    ) -> Option<Decision> {
+        let pass = self.passes[slot as usize].as_mut()?;
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            pass(device, command_buffer, context);
+        }));
+        let panic = result.err()?;
+        let message = panic
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        error!("Custom pass {slot:?} panicked and was removed: {message}");
+        self.passes[slot as usize] = None;
+        Some(Decision::CustomPassPanicked(slot))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32, width: u32, height: u32) -> vk::Rect2D {
+        vk::Rect2D {
+            offset: vk::Offset2D { x, y },
+            extent: vk::Extent2D { width, height },
+        }
+    }
+
+    #[test]
+    fn clear_regions_are_clamped_to_the_target() {
+        let context = FrameContext {
+            render_pass: vk::RenderPass::null(),
+            extent: vk::Extent2D {
+                width: 800,
+                height: 600,
+            },
+            perspective: Mat4::IDENTITY,
+            orthographic: Mat4::IDENTITY,
+            device_memory_properties: vk::PhysicalDeviceMemoryProperties::default(),
+            frame: 0,
+            offscreen: false,
+            color_attachments: 1,
+            depth_attachment: true,
+            samples: vk::SampleCountFlags::TYPE_1,
+        };
+        let cases = [
+            (rect(0, 0, 400, 300), Some(rect(0, 0, 400, 300))),
+            // Past the bottom right corner, then starting above and left of the target
+            (rect(700, 500, 300, 300), Some(rect(700, 500, 100, 100))),
+            (rect(-50, -20, 100, 100), Some(rect(0, 0, 50, 80))),
+            (rect(800, 0, 10, 10), None),
+            (rect(-100, 0, 100, 10), None),
+            (rect(0, 0, u32::MAX, u32::MAX), Some(rect(0, 0, 800, 600))),
+        ];
+        for (requested, expected) in cases {
+            assert_eq!(context.clamp_region(requested), expected, "{requested:?}");
+        }
+    }
+}
@@ -0,0 +1,221 @@
+//! Line segments drawn over the scene for debugging: bounding boxes, normals, axes and camera frusta.
+
+use crate::{
+    model::Vertex,
+    vulkan::{
+        device::AAADevice,
+        upload::{create_empty_buffer, write_mapped, AAAOwnedBuffer},
+    },
+};
+use ash::vk;
+use glam::{Mat4, Vec3, Vec4};
+use std::mem;
+
+/// Requested width of the debug lines in pixels, drawn 1 pixel wide on devices without wide lines.
+pub const DEBUG_LINE_WIDTH: f32 = 2.0;
+
+/// World space segments, two vertices each, drawn by the line pipeline with the perspective camera.
+#[derive(Debug, Clone, Default)]
+pub struct DebugLines {
+    vertices: Vec<Vertex>,
+}
+
+impl DebugLines {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_line(&mut self, start: Vec3, end: Vec3, color: [f32; 4]) {
+        for point in [start, end] {
+            self.vertices.push(Vertex {
+                pos: point.extend(1.0).to_array(),
+                uv: [0.0; 2],
+                color,
+                normal: [0.0; 3],
+                tangent: [1.0, 0.0, 0.0, 1.0],
+                joints: [0; 4],
+                weights: [0.0; 4],
+            });
+        }
+    }
+
+    /// The 12 edges of the axis aligned box between `min` and `max`.
+    pub fn add_aabb(&mut self, min: Vec3, max: Vec3, color: [f32; 4]) {
+        let corner = |index: usize| {
+            Vec3::new(
+                if index & 1 == 0 { min.x } else { max.x },
+                if index & 2 == 0 { min.y } else { max.y },
+                if index & 4 == 0 { min.z } else { max.z },
+            )
+        };
+        self.add_box_edges(corner, color);
+    }
+
+    /// X, Y and Z axes of `transform` in red, green and blue, `size` long.
+    pub fn add_axis_gizmo(&mut self, transform: Mat4, size: f32) {
+        let origin = transform.transform_point3(Vec3::ZERO);
+        for (axis, color) in [
+            (Vec3::X, [1.0, 0.0, 0.0, 1.0]),
+            (Vec3::Y, [0.0, 1.0, 0.0, 1.0]),
+            (Vec3::Z, [0.0, 0.0, 1.0, 1.0]),
+        ] {
+            self.add_line(origin, transform.transform_point3(axis * size), color);
+        }
+    }
+
+    /// Edges of the volume a camera with `projection_view` sees, Vulkan depth from 0 to 1.
+    pub fn add_frustum(&mut self, projection_view: Mat4, color: [f32; 4]) {
+        let inverse = projection_view.inverse();
+        let corner = |index: usize| {
+            let clip = Vec4::new(
+                if index & 1 == 0 { -1.0 } else { 1.0 },
+                if index & 2 == 0 { -1.0 } else { 1.0 },
+                if index & 4 == 0 { 0.0 } else { 1.0 },
+                1.0,
+            );
+            let world = inverse * clip;
+            world.truncate() / world.w
+        };
+        self.add_box_edges(corner, color);
+    }
+
+    /// Corners indexed by their bits, x in the first, y in the second and z in the third.
+    fn add_box_edges(&mut self, corner: impl Fn(usize) -> Vec3, color: [f32; 4]) {
+        for index in 0..8 {
+            for bit in [1, 2, 4] {
+                if index & bit == 0 {
+                    self.add_line(corner(index), corner(index | bit), color);
+                }
+            }
+        }
+    }
+
+    /// Segments added so far.
+    pub fn len(&self) -> usize {
+        self.vertices.len() / 2
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+
+    pub fn vertices(&self) -> &[Vertex] {
+        &self.vertices
+    }
+}
+
+/// Host visible vertex buffer holding the segments drawn every frame.
+#[derive(Debug)]
+pub(crate) struct DebugLineBuffer {
+    pub buffer: vk::Buffer,
+    pub memory: vk::DeviceMemory,
+    /// Allocated bytes, grown to the next power of two when the lines outgrow it.
+    pub capacity: u64,
+    pub vertex_count: u32,
+}
+
+impl DebugLineBuffer {
+    /// Empty until the first [`Self::write`].
+    pub fn new() -> Self {
+        Self {
+            buffer: vk::Buffer::null(),
+            memory: vk::DeviceMemory::null(),
+            capacity: 0,
+            vertex_count: 0,
+        }
+    }
+
+    /// Copy the segments of `lines` through a mapping, the commands reading the buffer must have completed.
+    pub fn write(
+        &mut self,
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        lines: &DebugLines,
+    ) {
+        let vertices = lines.vertices();
+        self.vertex_count = vertices.len() as u32;
+        if vertices.is_empty() {
+            return;
+        }
+        let size = mem::size_of_val(vertices) as u64;
+        if size > self.capacity {
+            self.destroy(device);
+            self.capacity = size.next_power_of_two();
+            AAAOwnedBuffer {
+                buffer: self.buffer,
+                memory: self.memory,
+                ..
+            } = create_empty_buffer(
+                device,
+                device_memory_properties,
+                self.capacity,
+                vk::BufferUsageFlags::VERTEX_BUFFER,
+                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            );
+        }
+        write_mapped(device, self.memory, self.capacity, vertices);
+    }
+
+    pub fn destroy(&self, device: &AAADevice) {
+        if self.capacity == 0 {
+            return;
+        }
+        unsafe {
+            crate::object_audit::destroyed(self.memory);
+            device.ash.free_memory(self.memory, None);
+            crate::object_audit::destroyed(self.buffer);
+            device.ash.destroy_buffer(self.buffer, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(vertex: &Vertex) -> Vec3 {
+        Vec3::from_slice(&vertex.pos[..3])
+    }
+
+    #[test]
+    fn helpers_add_their_segments() {
+        let mut lines = DebugLines::new();
+        lines.add_axis_gizmo(Mat4::IDENTITY, 1.0);
+        lines.add_aabb(Vec3::splat(-0.5), Vec3::splat(0.5), [1.0; 4]);
+        lines.add_frustum(Mat4::perspective_rh(0.8, 1.0, 0.5, 2.5), [1.0; 4]);
+        // 3 axes, 12 box edges and 12 frustum edges
+        assert_eq!(lines.len(), 27);
+        assert_eq!(lines.vertices().len(), 54);
+        lines.clear();
+        assert!(lines.is_empty());
+    }
+
+    #[test]
+    fn box_edges_join_neighbouring_corners() {
+        let mut aabb = DebugLines::new();
+        aabb.add_aabb(Vec3::ZERO, Vec3::ONE, [1.0; 4]);
+        for segment in aabb.vertices().chunks_exact(2) {
+            let length = (position(&segment[1]) - position(&segment[0])).length();
+            assert_eq!(length, 1.0);
+        }
+    }
+
+    #[test]
+    fn frustum_corners_land_on_the_near_and_far_planes() {
+        let (near, far) = (0.5, 2.5);
+        let view = Mat4::look_at_rh(Vec3::ZERO, Vec3::NEG_Z, Vec3::Y);
+        let mut frustum = DebugLines::new();
+        frustum.add_frustum(Mat4::perspective_rh(0.8, 1.0, near, far) * view, [1.0; 4]);
+        for vertex in frustum.vertices() {
+            let depth = -position(vertex).z;
+            assert!(
+                (depth - near).abs() < 1e-4 || (depth - far).abs() < 1e-3,
+                "corner at depth {depth}"
+            );
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use std::time::Duration;
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    monitor::MonitorHandle,
+    window::Window,
+};
+
+/// Everything about the monitor and window size that the renderer derives layout from.
+/// Applied as a whole by the render thread at a frame boundary, so no frame mixes old and new values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DisplayEnvironment {
+    pub width: u32,
+    pub height: u32,
+    pub scale_factor: f64,
+    pub refresh_rate_millihertz: Option<u32>,
+}
+
+impl DisplayEnvironment {
+    pub fn from_window(window: &Window) -> Self {
+        let size = window.inner_size();
+        Self {
+            width: size.width,
+            height: size.height,
+            scale_factor: window.scale_factor(),
+            refresh_rate_millihertz: window
+                .current_monitor()
+                .and_then(|monitor| monitor.refresh_rate_millihertz()),
+        }
+    }
+
+    /// Environment expected once the window follows a scale factor change, keeping its logical size.
+    /// Winit sends the matching `Resized` right after, applying both at once avoids a frame at the old extent.
+    pub fn rescaled(&self, scale_factor: f64) -> Self {
+        let ratio = scale_factor / self.scale_factor;
+        Self {
+            width: (self.width as f64 * ratio).round() as u32,
+            height: (self.height as f64 * ratio).round() as u32,
+            scale_factor,
+            ..*self
+        }
+    }
+
+    pub fn is_zero_sized(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// Size in logical pixels, what the UI is laid out in.
+    pub fn logical_size(&self) -> (f32, f32) {
+        (
+            (self.width as f64 / self.scale_factor) as f32,
+            (self.height as f64 / self.scale_factor) as f32,
+        )
+    }
+
+    /// Duration of a monitor refresh, when the monitor reports its rate.
+    pub fn refresh_interval(&self) -> Option<Duration> {
+        self.refresh_rate_millihertz
+            .filter(|&rate| rate > 0)
+            .map(|rate| Duration::from_secs(1000) / rate)
+    }
+}
+
+/// Desktop area of the monitor a window was last seen on, so moves within it skip the monitor query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MonitorBounds {
+    pub position: PhysicalPosition<i32>,
+    pub size: PhysicalSize<u32>,
+}
+
+impl MonitorBounds {
+    pub fn from_monitor(monitor: &MonitorHandle) -> Self {
+        Self {
+            position: monitor.position(),
+            size: monitor.size(),
+        }
+    }
+
+    pub fn contains(&self, point: PhysicalPosition<i32>) -> bool {
+        let (x, y) = (
+            point.x as i64 - self.position.x as i64,
+            point.y as i64 - self.position.y as i64,
+        );
+        (0..self.size.width as i64).contains(&x) && (0..self.size.height as i64).contains(&y)
+    }
+
+    /// Whether a window of `display`'s size moved to `position` has its center on another monitor.
+    /// The window belongs to the monitor holding its center, as the platforms decide it.
+    pub fn left_by(&self, position: PhysicalPosition<i32>, display: &DisplayEnvironment) -> bool {
+        let center = PhysicalPosition::new(
+            position.x.saturating_add((display.width / 2) as i32),
+            position.y.saturating_add((display.height / 2) as i32),
+        );
+        !self.contains(center)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input_manager::EventStates;
+
+    const ONE_X: DisplayEnvironment = DisplayEnvironment {
+        width: 800,
+        height: 600,
+        scale_factor: 1.0,
+        refresh_rate_millihertz: Some(60_000),
+    };
+
+    #[test]
+    fn rescaling_keeps_the_logical_size() {
+        let two_x = ONE_X.rescaled(2.0);
+        assert_eq!((two_x.width, two_x.height), (1600, 1200));
+        assert_eq!(two_x.logical_size(), ONE_X.logical_size());
+        assert_eq!(two_x.rescaled(1.0), ONE_X);
+    }
+
+    /// Dragging onto a 2x monitor of another rate posts a scale change, the resize that follows
+    /// and the new rate; the render thread takes a single environment with all of them.
+    #[test]
+    fn render_thread_takes_one_environment() {
+        let event_states = EventStates::default();
+        assert_eq!(event_states.take_display_environment(), None);
+
+        let mut display = ONE_X.rescaled(2.0);
+        event_states.post_display_environment(display);
+        display.width = 1602;
+        display.height = 1200;
+        event_states.post_display_environment(display);
+        display.refresh_rate_millihertz = Some(144_000);
+        event_states.post_display_environment(display);
+
+        let applied = event_states.take_display_environment().unwrap();
+        assert_eq!(
+            applied,
+            DisplayEnvironment {
+                width: 1602,
+                height: 1200,
+                scale_factor: 2.0,
+                refresh_rate_millihertz: Some(144_000),
+            }
+        );
+        assert_eq!(event_states.take_display_environment(), None);
+    }
+
+    #[test]
+    fn refresh_interval() {
+        assert_eq!(
+            ONE_X.refresh_interval(),
+            Some(Duration::from_secs(1000) / 60_000)
+        );
+        let unknown = DisplayEnvironment {
+            refresh_rate_millihertz: Some(0),
+            ..ONE_X
+        };
+        assert_eq!(unknown.refresh_interval(), None);
+        assert!(DisplayEnvironment { width: 0, ..ONE_X }.is_zero_sized());
+    }
+
+    #[test]
+    fn window_belongs_to_the_monitor_holding_its_center() {
+        let left = MonitorBounds {
+            position: PhysicalPosition::new(0, 0),
+            size: PhysicalSize::new(1920, 1080),
+        };
+        assert!(left.contains(PhysicalPosition::new(1919, 0)));
+        assert!(!left.contains(PhysicalPosition::new(1920, 0)));
+        assert!(!left.contains(PhysicalPosition::new(-1, 500)));
+        // The center of an 800 wide window at x 1500 is at 1900, still on the left monitor
+        assert!(!left.left_by(PhysicalPosition::new(1500, 100), &ONE_X));
+        assert!(left.left_by(PhysicalPosition::new(1530, 100), &ONE_X));
+
+        let right = MonitorBounds {
+            position: PhysicalPosition::new(1920, 0),
+            size: PhysicalSize::new(2560, 1440),
+        };
+        let cases = [
+            // Fully inside, then straddling with the center still inside, then mostly on the left
+            // monitor, below the right one and far past anything
+            (PhysicalPosition::new(2000, 100), false),
+            (PhysicalPosition::new(1600, 100), false),
+            (PhysicalPosition::new(1400, 100), true),
+            (PhysicalPosition::new(4100, 1000), true),
+            (PhysicalPosition::new(i32::MAX, i32::MAX), true),
+        ];
+        for (position, left) in cases {
+            assert_eq!(right.left_by(position, &ONE_X), left, "{position:?}");
+        }
+    }
+}
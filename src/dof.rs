@@ -0,0 +1,253 @@
+//! Depth of field: the thin lens circle of confusion, its parameters and the focus tracking.
+//!
+//! The render thread runs three fullscreen passes after the scene when enabled: the circle of
+//! confusion of every pixel from the depth buffer, a gather blur at half resolution of the pixels
+//! in front of the focus plane and of those behind it, then a composite of both over the sharp
+//! image. Every parameter is pushed as [`DofConstants`], changing them rebuilds nothing.
+use serde::{Deserialize, Serialize};
+use std::mem;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FocusMode {
+    /// Focus at `focus_distance`.
+    Manual,
+    /// Focus on the mesh under the cursor, keeping the last distance when nothing is hit.
+    Cursor,
+}
+
+/// Depth of field parameters, all of them can change live.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DepthOfFieldConfig {
+    pub enabled: bool,
+    pub focus_mode: FocusMode,
+    /// World units from the camera.
+    pub focus_distance: f32,
+    /// F-number, lower values blur more.
+    pub aperture: f32,
+    /// World units, 0.05 is a 50mm lens when a unit is a meter.
+    pub focal_length: f32,
+    /// World units, 0.024 is a full frame sensor.
+    pub sensor_height: f32,
+    /// Blur diameter limit in pixels.
+    pub max_coc_pixels: f32,
+    /// How fast the focus follows its target, per second.
+    pub adaptation_speed: f32,
+}
+
+impl Default for DepthOfFieldConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            focus_mode: FocusMode::Cursor,
+            focus_distance: 4.0,
+            aperture: 2.8,
+            focal_length: 0.05,
+            sensor_height: 0.024,
+            max_coc_pixels: 16.0,
+            adaptation_speed: 8.0,
+        }
+    }
+}
+
+/// Distance from the camera of a `[0, 1]` depth buffer value written with `Mat4::perspective_rh`.
+pub fn linearize_depth(depth: f32, near: f32, far: f32) -> f32 {
+    near * far / (far - depth * (far - near))
+}
+
+/// Thin lens circle of confusion diameter on the sensor, in world units.
+/// Negative in front of the focus plane, positive behind it.
+pub fn circle_of_confusion(distance: f32, focus: f32, aperture: f32, focal_length: f32) -> f32 {
+    if distance <= 0.0 || focus <= focal_length {
+        return 0.0;
+    }
+    focal_length * focal_length * (distance - focus)
+        / (aperture * distance * (focus - focal_length))
+}
+
+/// Circle of confusion diameter in pixels, clamped to the configured limit, sign preserved.
+pub fn coc_pixels(distance: f32, config: &DepthOfFieldConfig, viewport_height: f32) -> f32 {
+    let coc = circle_of_confusion(
+        distance,
+        config.focus_distance,
+        config.aperture,
+        config.focal_length,
+    );
+    (coc / config.sensor_height * viewport_height)
+        .clamp(-config.max_coc_pixels, config.max_coc_pixels)
+}
+
+/// Gather blur writing the pixels in front of the focus plane, see [`DofConstants::side`].
+pub const NEAR_FIELD: f32 = -1.0;
+/// Gather blur writing the pixels behind the focus plane.
+pub const FAR_FIELD: f32 = 1.0;
+
+/// Fragment push constants of the depth of field passes, the `DofConstants` block of the
+/// `dof_*.frag` shaders. The shaders repeat [`linearize_depth`] and [`coc_pixels`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DofConstants {
+    /// Clip planes of the perspective projection the depth buffer was written with.
+    pub near: f32,
+    pub far: f32,
+    /// The tracked focus distance, not the configured one when it follows the cursor.
+    pub focus_distance: f32,
+    pub aperture: f32,
+    pub focal_length: f32,
+    pub sensor_height: f32,
+    pub max_coc_pixels: f32,
+    /// Pixels, the circle of confusion is scaled from the sensor to them.
+    pub viewport_height: f32,
+    /// [`NEAR_FIELD`] or [`FAR_FIELD`] for the gather blur, unused by the other passes.
+    pub side: f32,
+}
+
+impl DofConstants {
+    pub fn new(
+        config: &DepthOfFieldConfig,
+        focus_distance: f32,
+        near: f32,
+        far: f32,
+        viewport_height: f32,
+    ) -> Self {
+        Self {
+            near,
+            far,
+            focus_distance,
+            aperture: config.aperture,
+            focal_length: config.focal_length,
+            sensor_height: config.sensor_height,
+            max_coc_pixels: config.max_coc_pixels,
+            viewport_height,
+            side: FAR_FIELD,
+        }
+    }
+
+    /// The configuration the circle of confusion is computed with, focused at
+    /// [`Self::focus_distance`].
+    pub fn config(&self) -> DepthOfFieldConfig {
+        DepthOfFieldConfig {
+            focus_distance: self.focus_distance,
+            aperture: self.aperture,
+            focal_length: self.focal_length,
+            sensor_height: self.sensor_height,
+            max_coc_pixels: self.max_coc_pixels,
+            ..Default::default()
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            std::slice::from_raw_parts(self as *const Self as *const u8, mem::size_of::<Self>())
+        }
+    }
+
+    /// Circle of confusion in pixels of the `[0, 1]` depth buffer value `depth`, what the CoC
+    /// pass writes.
+    pub fn coc(&self, depth: f32) -> f32 {
+        let distance = linearize_depth(depth, self.near, self.far);
+        coc_pixels(distance, &self.config(), self.viewport_height)
+    }
+}
+
+/// Smoothly moves the focus distance towards its target, independent of the frame rate.
+#[derive(Debug, Clone, Copy)]
+pub struct FocusTracker {
+    pub distance: f32,
+}
+
+impl FocusTracker {
+    pub fn new(distance: f32) -> Self {
+        Self { distance }
+    }
+
+    pub fn update(&mut self, target: f32, delta_seconds: f32, speed: f32) -> f32 {
+        let blend = 1.0 - (-speed * delta_seconds).exp();
+        self.distance += (target - self.distance) * blend;
+        self.distance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::{Mat4, Vec4};
+
+    const NEAR: f32 = 0.1;
+    const FAR: f32 = 100.0;
+
+    /// Depth buffer value of a point `distance` in front of the camera.
+    fn depth_at(distance: f32) -> f32 {
+        let clip = Mat4::perspective_rh(1.0, 1.0, NEAR, FAR) * Vec4::new(0.0, 0.0, -distance, 1.0);
+        clip.z / clip.w
+    }
+
+    #[test]
+    fn depth_is_linearized() {
+        for distance in [NEAR, 0.5, 4.0, 37.0, FAR] {
+            let linear = linearize_depth(depth_at(distance), NEAR, FAR);
+            assert!(
+                (linear - distance).abs() < distance * 1e-3,
+                "{distance}: {linear}"
+            );
+        }
+    }
+
+    #[test]
+    fn thin_lens_circle_of_confusion() {
+        // 50mm at f/2 focused at 2m: a point at 4m blurs over 0.3205mm on the sensor
+        let coc = circle_of_confusion(4.0, 2.0, 2.0, 0.05);
+        assert!((coc - 0.000_320_5).abs() < 1e-7, "{coc}");
+        // Twice the f-number halves it, in front of the focus plane it is negative
+        let stopped = circle_of_confusion(4.0, 2.0, 4.0, 0.05);
+        assert!((stopped * 2.0 - coc).abs() < 1e-9);
+        let front = circle_of_confusion(1.0, 2.0, 2.0, 0.05);
+        assert!((front + 0.000_641_0).abs() < 1e-7, "{front}");
+        assert_eq!(circle_of_confusion(2.0, 2.0, 2.0, 0.05), 0.0);
+        // Focused closer than the focal length, or a point behind the camera, stays sharp
+        assert_eq!(circle_of_confusion(4.0, 0.04, 2.0, 0.05), 0.0);
+        assert_eq!(circle_of_confusion(0.0, 2.0, 2.0, 0.05), 0.0);
+    }
+
+    #[test]
+    fn pixels_are_clamped() {
+        let config = DepthOfFieldConfig {
+            focus_distance: 2.0,
+            aperture: 2.0,
+            ..Default::default()
+        };
+        // 0.3205mm of a 24mm sensor on 1080 pixels
+        let pixels = coc_pixels(4.0, &config, 1080.0);
+        assert!((pixels - 14.42).abs() < 0.01, "{pixels}");
+        assert_eq!(coc_pixels(100.0, &config, 1080.0), config.max_coc_pixels);
+        assert_eq!(coc_pixels(0.5, &config, 1080.0), -config.max_coc_pixels);
+    }
+
+    #[test]
+    fn constants_match_the_shader_block() {
+        assert_eq!(std::mem::size_of::<DofConstants>(), 9 * 4);
+        let config = DepthOfFieldConfig {
+            aperture: 2.0,
+            ..Default::default()
+        };
+        let constants = DofConstants::new(&config, 2.0, NEAR, FAR, 1080.0);
+        assert!(constants.coc(depth_at(2.0)).abs() < 0.05);
+        let behind = constants.coc(depth_at(4.0));
+        assert!((behind - 14.42).abs() < 0.05, "{behind}");
+        assert!(constants.coc(depth_at(1.0)) < 0.0);
+        assert_eq!(constants.coc(1.0), config.max_coc_pixels);
+    }
+
+    #[test]
+    fn focus_adapts_whatever_the_frame_rate() {
+        let mut stepped = FocusTracker::new(1.0);
+        for _ in 0..10 {
+            stepped.update(5.0, 0.01, 8.0);
+        }
+        let mut once = FocusTracker::new(1.0);
+        once.update(5.0, 0.1, 8.0);
+        assert!((stepped.distance - once.distance).abs() < 1e-4);
+        assert!(once.distance > 1.0 && once.distance < 5.0);
+    }
+}
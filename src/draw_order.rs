@@ -0,0 +1,240 @@
+//! Order of the perspective meshes' draws.
+//!
+//! Meshes draw by ascending `sort_bias` bucket, then within a bucket in the order they were
+//! registered. With `GraphicsConfig::sort_by_material` the draws of a bucket are grouped by
+//! pipeline first to bind each once, the registration order only deciding within a pipeline.
+//!
+//! A bias is the supported way to layer co-planar geometry: a ground quad at 0 and the blob shadow
+//! over it at 1 stay in that order whatever their materials, the depth test letting the later of
+//! two equal depths win. A [`crate::material::DepthBias`] is the better fix for decals, a
+//! material's or one set on the mesh with `Application::set_mesh_depth_bias`, it keeps them over
+//! their surface whatever the order.
+//!
+//! Blended meshes draw after every opaque one, back to front by the view space depth of their
+//! center so each blends over what is behind it. Their bias and registration only order equal
+//! depths.
+
+/// Where one perspective mesh draws.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DrawKey {
+    pub sort_bias: i32,
+    /// Pipeline the mesh binds, grouped when sorting by material.
+    pub state: u64,
+    /// Registration sequence of the mesh, the earlier first.
+    pub registered: u64,
+    /// Distance in front of the camera of a blended mesh, `None` for an opaque one.
+    pub blend_depth: Option<f32>,
+}
+
+/// Indices of `keys` in draw order, a stable sort so equal keys keep their order.
+pub fn draw_order(keys: &[DrawKey], sort_by_material: bool) -> Vec<usize> {
+    let (mut order, mut blended): (Vec<usize>, Vec<usize>) =
+        (0..keys.len()).partition(|&index| keys[index].blend_depth.is_none());
+    if sort_by_material {
+        order.sort_by_key(|&index| {
+            let key = keys[index];
+            (key.sort_bias, key.state, key.registered)
+        });
+    } else {
+        order.sort_by_key(|&index| (keys[index].sort_bias, keys[index].registered));
+    }
+    // Farthest first, a stable sort keeps the order of equal keys
+    blended.sort_by(|&a, &b| {
+        let (a, b) = (keys[a], keys[b]);
+        let (a_depth, b_depth) = (a.blend_depth.unwrap(), b.blend_depth.unwrap());
+        b_depth
+            .total_cmp(&a_depth)
+            .then((a.sort_bias, a.registered).cmp(&(b.sort_bias, b.registered)))
+    });
+    order.extend(blended);
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{DepthBias, Material};
+
+    /// Pipelines of the two materials, the shadow's sorting before the ground's.
+    const GROUND_PIPELINE: u64 = 2;
+    const SHADOW_PIPELINE: u64 = 1;
+    /// Smallest resolvable depth difference of the engine's D16 attachment, one step of it.
+    const DEPTH_UNIT: f32 = 1.0 / u16::MAX as f32;
+    /// Depth both quads rasterize at, co-planar and on a step of the attachment.
+    const PLANE_DEPTH: f32 = 16384.0 * DEPTH_UNIT;
+
+    /// A quad covering the pixel under test.
+    struct Quad {
+        name: &'static str,
+        key: DrawKey,
+        depth_bias: Option<DepthBias>,
+    }
+
+    /// Offset the rasterizer adds to a fragment of depth slope `slope`, clamped as the device does
+    /// with the `depthBiasClamp` feature.
+    fn bias_offset(bias: DepthBias, slope: f32) -> f32 {
+        let offset = slope * bias.slope_factor + DEPTH_UNIT * bias.constant_factor;
+        if bias.clamp > 0.0 {
+            offset.min(bias.clamp)
+        } else if bias.clamp < 0.0 {
+            offset.max(bias.clamp)
+        } else {
+            offset
+        }
+    }
+
+    /// Name of the quad left in the pixel after drawing `quads`, all of depth slope `slope`, in
+    /// `order` against a cleared depth of 1, with the engine's `LESS_OR_EQUAL` test on a D16
+    /// attachment.
+    fn resolve(quads: &[Quad], order: &[usize], slope: f32) -> &'static str {
+        let mut depth = 1.0;
+        let mut color = "clear";
+        for &index in order {
+            let quad = &quads[index];
+            let offset = quad.depth_bias.map_or(0.0, |bias| bias_offset(bias, slope));
+            let fragment = ((PLANE_DEPTH + offset) / DEPTH_UNIT).round() * DEPTH_UNIT;
+            if fragment <= depth {
+                depth = fragment;
+                color = quad.name;
+            }
+        }
+        color
+    }
+
+    /// The ground registered first, the blob shadow over it second.
+    fn scene(shadow_sort_bias: i32, shadow_depth_bias: Option<DepthBias>) -> Vec<Quad> {
+        vec![
+            Quad {
+                name: "ground",
+                key: DrawKey {
+                    sort_bias: 0,
+                    state: GROUND_PIPELINE,
+                    registered: 0,
+                    blend_depth: None,
+                },
+                depth_bias: None,
+            },
+            Quad {
+                name: "shadow",
+                key: DrawKey {
+                    sort_bias: shadow_sort_bias,
+                    state: SHADOW_PIPELINE,
+                    registered: 1,
+                    blend_depth: None,
+                },
+                depth_bias: shadow_depth_bias,
+            },
+        ]
+    }
+
+    fn keys(quads: &[Quad]) -> Vec<DrawKey> {
+        quads.iter().map(|quad| quad.key).collect()
+    }
+
+    #[test]
+    fn co_planar_quads_draw_their_golden_pixel() {
+        let decal = Material::default().with_depth_bias(-1.0, -1.0).depth_bias;
+        // Sort bias of the shadow, its material's depth bias, material sorting, expected pixel
+        let cases = [
+            (0, None, false, "shadow"),
+            // Grouping by pipeline draws the shadow first, the ground hides it
+            (0, None, true, "ground"),
+            (1, None, false, "shadow"),
+            (1, None, true, "shadow"),
+            (0, decal, false, "shadow"),
+            (0, decal, true, "shadow"),
+        ];
+        for (sort_bias, depth_bias, sort_by_material, expected) in cases {
+            let quads = scene(sort_bias, depth_bias);
+            let order = draw_order(&keys(&quads), sort_by_material);
+            assert_eq!(
+                resolve(&quads, &order, 0.0),
+                expected,
+                "sort bias {sort_bias}, depth bias {depth_bias:?}, sorted by material \
+                 {sort_by_material}, order {order:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn equal_keys_keep_their_registration_order() {
+        let keys = [3, 0, 2, 1].map(|registered| DrawKey {
+            sort_bias: 0,
+            state: GROUND_PIPELINE,
+            registered,
+            blend_depth: None,
+        });
+        for sort_by_material in [false, true] {
+            assert_eq!(draw_order(&keys, sort_by_material), [1, 3, 2, 0]);
+        }
+    }
+
+    #[test]
+    fn blended_meshes_draw_after_the_opaque_ones_farthest_first() {
+        // Registered near glass first, then an opaque wall, far glass, and glass level with the
+        // near one but in a lower bucket
+        let key = |sort_bias, registered, blend_depth| DrawKey {
+            sort_bias,
+            state: GROUND_PIPELINE,
+            registered,
+            blend_depth,
+        };
+        let keys = [
+            key(0, 0, Some(2.0)),
+            key(0, 1, None),
+            key(0, 2, Some(5.0)),
+            key(-1, 3, Some(2.0)),
+        ];
+        for sort_by_material in [false, true] {
+            assert_eq!(draw_order(&keys, sort_by_material), [1, 2, 3, 0]);
+        }
+    }
+
+    /// The shadow registered before the ground, both drawn with the default material: only the
+    /// shadow's own bias, set per draw, keeps it on top.
+    #[test]
+    fn a_mesh_bias_beats_the_registration_order() {
+        let mut quads = scene(0, None);
+        quads[0].key.registered = 1;
+        quads[1].key = DrawKey {
+            registered: 0,
+            state: GROUND_PIPELINE,
+            ..quads[1].key
+        };
+        let order = draw_order(&keys(&quads), true);
+        assert_eq!(resolve(&quads, &order, 0.0), "ground");
+        quads[1].depth_bias = Some(DepthBias::new(-1.0, 0.0));
+        assert_eq!(resolve(&quads, &order, 0.0), "shadow");
+    }
+
+    /// What survives the 16 bits of the depth attachment: a fraction of a unit is rounded away,
+    /// the slope factor only acts on surfaces seen at an angle, and the clamp caps what both add
+    /// up to.
+    #[test]
+    fn depth_biases_survive_a_d16_attachment_as_documented() {
+        // Slope, shadow bias, expected pixel, the shadow drawing first
+        let cases = [
+            (0.0, DepthBias::new(-1.0, 0.0), "shadow"),
+            (0.0, DepthBias::new(-0.25, 0.0), "ground"),
+            (0.0, DepthBias::new(0.0, -1.0), "ground"),
+            (4.0 * DEPTH_UNIT, DepthBias::new(0.0, -1.0), "shadow"),
+            (
+                4.0 * DEPTH_UNIT,
+                DepthBias::new(0.0, -1.0).with_clamp(-0.25 * DEPTH_UNIT),
+                "ground",
+            ),
+        ];
+        for (slope, bias, expected) in cases {
+            let quads = scene(0, Some(bias));
+            assert_eq!(
+                resolve(&quads, &[1, 0], slope),
+                expected,
+                "{bias:?} at slope {slope}"
+            );
+        }
+        let clamped = DepthBias::new(-1.0, -1.0).with_clamp(-4.0 * DEPTH_UNIT);
+        assert_eq!(bias_offset(clamped, 0.01), clamped.clamp);
+        // A clamped bias needs a pipeline of its own
+        assert_ne!(clamped, DepthBias::new(-1.0, -1.0));
+    }
+}
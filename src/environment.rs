@@ -0,0 +1,379 @@
+//! Diffuse environment lighting precomputed from a skybox, on the CPU at load time.
+//!
+//! The lighting shader samples the baked [`Irradiance`] by the surface normal as its ambient term,
+//! scaled by `Application::set_environment_intensity`. Without a skybox it samples a flat
+//! environment of [`FLAT_AMBIENT`].
+use crate::color_space::{linear_to_srgb, srgb_to_linear};
+use glam::Vec3;
+#[cfg(feature = "images")]
+use std::{error::Error, path::Path};
+
+/// Faces in Vulkan layer order: +X, -X, +Y, -Y, +Z, -Z.
+pub const CUBE_FACES: usize = 6;
+/// Faces of the irradiance cubemap the lighting shader samples, low frequency enough for 16.
+pub const IRRADIANCE_SIZE: u32 = 16;
+/// Faces of a skybox are averaged down to this size before the convolution.
+pub const CONVOLUTION_SIZE: u32 = 64;
+/// Radiance of the environment when no skybox is loaded, the former constant ambient.
+pub const FLAT_AMBIENT: f32 = 0.2;
+
+/// Square faces of linear RGB radiance.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cubemap {
+    pub size: u32,
+    /// Row major, top row first.
+    pub faces: [Vec<Vec3>; CUBE_FACES],
+}
+
+impl Cubemap {
+    /// Evaluates `radiance` at the center of every texel.
+    pub fn from_fn(size: u32, radiance: impl Fn(Vec3) -> Vec3) -> Self {
+        let faces = std::array::from_fn(|face| {
+            (0..size * size)
+                .map(|texel| radiance(texel_direction(face, texel % size, texel / size, size)))
+                .collect()
+        });
+        Self { size, faces }
+    }
+
+    /// Six square sRGB images of the same size, in [`CUBE_FACES`] order.
+    #[cfg(feature = "images")]
+    pub fn load(paths: [&Path; CUBE_FACES]) -> Result<Self, Box<dyn Error>> {
+        let mut size = None;
+        let mut faces: [Vec<Vec3>; CUBE_FACES] = Default::default();
+        for (face, path) in faces.iter_mut().zip(paths) {
+            let image = image::open(path)?.into_rgb32f();
+            if image.width() != image.height() || size.is_some_and(|size| size != image.width()) {
+                return Err(format!(
+                    "Cubemap face {} is {}x{}, faces must be square and of the same size",
+                    path.display(),
+                    image.width(),
+                    image.height()
+                )
+                .into());
+            }
+            size = Some(image.width());
+            *face = image
+                .pixels()
+                .map(|pixel| Vec3::from(pixel.0).powf(2.2))
+                .collect();
+        }
+        Ok(Self {
+            size: size.unwrap_or(0),
+            faces,
+        })
+    }
+
+    /// Cubemap of sRGB encoded RGBA8 `faces` of `size`, made linear, alpha dropped.
+    pub fn from_rgba8(size: u32, faces: &[Vec<u8>; CUBE_FACES]) -> Self {
+        let faces = faces.each_ref().map(|texels| {
+            texels
+                .chunks_exact(4)
+                .map(|texel| {
+                    Vec3::from_array([0, 1, 2].map(|c| srgb_to_linear(texel[c] as f32 / 255.0)))
+                })
+                .collect()
+        });
+        Self { size, faces }
+    }
+
+    /// Texels averaged in square blocks so the faces are at most `size`, the remainder of a face
+    /// not a multiple of the block dropped.
+    pub fn downsample(&self, size: u32) -> Self {
+        let block = self.size.div_ceil(size.max(1)).max(1);
+        if block == 1 {
+            return self.clone();
+        }
+        let reduced = self.size / block;
+        let faces = self.faces.each_ref().map(|texels| {
+            (0..reduced * reduced)
+                .map(|texel| {
+                    let (x, y) = (texel % reduced * block, texel / reduced * block);
+                    let sum: Vec3 = (0..block * block)
+                        .map(|i| texels[((y + i / block) * self.size + x + i % block) as usize])
+                        .sum();
+                    sum / (block * block) as f32
+                })
+                .collect()
+        });
+        Self {
+            size: reduced,
+            faces,
+        }
+    }
+
+    /// RGBA8 faces to upload, encoded sRGB for an `_SRGB` texture so either format samples the
+    /// linear radiance, clamped to one.
+    pub fn to_rgba8(&self, srgb: bool) -> [Vec<u8>; CUBE_FACES] {
+        let encode = |linear: f32| {
+            let linear = linear.clamp(0.0, 1.0);
+            let stored = if srgb { linear_to_srgb(linear) } else { linear };
+            (stored * 255.0).round() as u8
+        };
+        self.faces.each_ref().map(|texels| {
+            texels
+                .iter()
+                .flat_map(|radiance| {
+                    let [r, g, b] = radiance.to_array().map(encode);
+                    [r, g, b, 255]
+                })
+                .collect()
+        })
+    }
+
+    /// Nearest texel in `direction`.
+    pub fn sample(&self, direction: Vec3) -> Vec3 {
+        let (face, s, t) = face_coordinates(direction);
+        let texel = |c: f32| (((c + 1.0) / 2.0 * self.size as f32) as u32).min(self.size - 1);
+        self.faces[face][(texel(t) * self.size + texel(s)) as usize]
+    }
+
+    /// Every texel with its direction and solid angle.
+    pub fn texels(&self) -> impl Iterator<Item = (Vec3, Vec3, f32)> + '_ {
+        let size = self.size;
+        self.faces
+            .iter()
+            .enumerate()
+            .flat_map(move |(face, texels)| {
+                texels.iter().enumerate().map(move |(texel, &radiance)| {
+                    let (x, y) = (texel as u32 % size, texel as u32 / size);
+                    let (s, t) = face_st(x, y, size);
+                    (
+                        texel_direction(face, x, y, size),
+                        radiance,
+                        texel_solid_angle(s, t, size),
+                    )
+                })
+            })
+    }
+
+    /// Small cubemap of the diffuse term, see [`Irradiance`].
+    pub fn irradiance(&self, size: u32) -> Self {
+        Irradiance::from_cubemap(self).bake(size)
+    }
+}
+
+/// Cosine convolution of the environment in 9 spherical harmonics coefficients, accurate to a
+/// few percent for diffuse lighting (Ramamoorthi and Hanrahan).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Irradiance {
+    pub coefficients: [Vec3; 9],
+}
+
+/// Cosine lobe convolution of each band, divided by pi so a white environment gives one.
+const BAND_WEIGHTS: [f32; 3] = [1.0, 2.0 / 3.0, 1.0 / 4.0];
+
+impl Irradiance {
+    pub fn from_cubemap(cubemap: &Cubemap) -> Self {
+        let mut coefficients = [Vec3::ZERO; 9];
+        for (direction, radiance, solid_angle) in cubemap.texels() {
+            for (coefficient, basis) in coefficients.iter_mut().zip(sh_basis(direction)) {
+                *coefficient += radiance * basis * solid_angle;
+            }
+        }
+        Self { coefficients }
+    }
+
+    /// Diffuse environment term for a surface facing `normal`, multiply by the albedo.
+    pub fn evaluate(&self, normal: Vec3) -> Vec3 {
+        let basis = sh_basis(normal.normalize_or_zero());
+        self.coefficients
+            .iter()
+            .zip(basis)
+            .enumerate()
+            .map(|(index, (&coefficient, basis))| coefficient * basis * BAND_WEIGHTS[band(index)])
+            .sum::<Vec3>()
+            .max(Vec3::ZERO)
+    }
+
+    pub fn bake(&self, size: u32) -> Cubemap {
+        Cubemap::from_fn(size, |normal| self.evaluate(normal))
+    }
+}
+
+fn band(index: usize) -> usize {
+    match index {
+        0 => 0,
+        1..=3 => 1,
+        _ => 2,
+    }
+}
+
+/// Real spherical harmonics up to band 2 at a unit `direction`.
+fn sh_basis(direction: Vec3) -> [f32; 9] {
+    let Vec3 { x, y, z } = direction;
+    [
+        0.282_095,
+        0.488_603 * y,
+        0.488_603 * z,
+        0.488_603 * x,
+        1.092_548 * x * y,
+        1.092_548 * y * z,
+        0.315_392 * (3.0 * z * z - 1.0),
+        1.092_548 * x * z,
+        0.546_274 * (x * x - y * y),
+    ]
+}
+
+/// Texel center in `[-1, 1]` face coordinates.
+fn face_st(x: u32, y: u32, size: u32) -> (f32, f32) {
+    let c = |i: u32| 2.0 * (i as f32 + 0.5) / size as f32 - 1.0;
+    (c(x), c(y))
+}
+
+/// Direction through a texel center, following the Vulkan cube face orientation.
+pub fn texel_direction(face: usize, x: u32, y: u32, size: u32) -> Vec3 {
+    let (s, t) = face_st(x, y, size);
+    match face {
+        0 => Vec3::new(1.0, -t, -s),
+        1 => Vec3::new(-1.0, -t, s),
+        2 => Vec3::new(s, 1.0, t),
+        3 => Vec3::new(s, -1.0, -t),
+        4 => Vec3::new(s, -t, 1.0),
+        _ => Vec3::new(-s, -t, -1.0),
+    }
+    .normalize()
+}
+
+/// Inverse of [`texel_direction`], the face and its `[-1, 1]` coordinates.
+fn face_coordinates(direction: Vec3) -> (usize, f32, f32) {
+    let a = direction.abs();
+    let Vec3 { x, y, z } = direction;
+    if a.x >= a.y && a.x >= a.z {
+        if x > 0.0 {
+            (0, -z / a.x, -y / a.x)
+        } else {
+            (1, z / a.x, -y / a.x)
+        }
+    } else if a.y >= a.z {
+        if y > 0.0 {
+            (2, x / a.y, z / a.y)
+        } else {
+            (3, x / a.y, -z / a.y)
+        }
+    } else if z > 0.0 {
+        (4, x / a.z, -y / a.z)
+    } else {
+        (5, -x / a.z, -y / a.z)
+    }
+}
+
+/// Solid angle of a texel, its `[-1, 1]` area projected on the unit sphere.
+fn texel_solid_angle(s: f32, t: f32, size: u32) -> f32 {
+    let texel_area = (2.0 / size as f32).powi(2);
+    texel_area / (1.0 + s * s + t * t).powf(1.5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SKY: Vec3 = Vec3::new(0.3, 0.5, 1.0);
+    const GROUND: Vec3 = Vec3::new(0.4, 0.3, 0.2);
+
+    fn sky_over_ground(direction: Vec3) -> Vec3 {
+        if direction.y > 0.0 {
+            SKY
+        } else {
+            GROUND
+        }
+    }
+
+    /// Cosine weighted integral over the hemisphere, divided by pi.
+    fn convolve(cubemap: &Cubemap, normal: Vec3) -> Vec3 {
+        cubemap
+            .texels()
+            .map(|(direction, radiance, solid_angle)| {
+                radiance * normal.dot(direction).max(0.0) * solid_angle
+            })
+            .sum::<Vec3>()
+            / std::f32::consts::PI
+    }
+
+    #[test]
+    fn texels_are_sampled_from_their_own_direction() {
+        let indexed = Cubemap::from_fn(8, |direction| direction);
+        for face in 0..CUBE_FACES {
+            for texel in 0..64 {
+                let direction = texel_direction(face, texel % 8, texel / 8, 8);
+                assert_eq!(indexed.sample(direction), direction, "face {face}");
+            }
+        }
+    }
+
+    #[test]
+    fn uniform_environment_lights_with_its_radiance() {
+        let uniform = Irradiance::from_cubemap(&Cubemap::from_fn(32, |_| SKY));
+        for normal in [Vec3::X, Vec3::NEG_Y, Vec3::new(1.0, 1.0, -1.0)] {
+            let irradiance = uniform.evaluate(normal);
+            assert!(irradiance.abs_diff_eq(SKY, 0.01), "{irradiance}");
+        }
+    }
+
+    #[test]
+    fn black_environment_does_not_light() {
+        let black = Cubemap::from_fn(16, |_| Vec3::ZERO).irradiance(4);
+        assert!(black
+            .faces
+            .iter()
+            .flatten()
+            .all(|&texel| texel == Vec3::ZERO));
+    }
+
+    #[test]
+    fn matches_the_convolution() {
+        let skybox = Cubemap::from_fn(32, sky_over_ground);
+        let irradiance = Irradiance::from_cubemap(&skybox);
+        let (up, down) = (
+            irradiance.evaluate(Vec3::Y),
+            irradiance.evaluate(Vec3::NEG_Y),
+        );
+        assert!(up.z > up.x && down.x > down.z, "up {up} down {down}");
+        for normal in [Vec3::Y, Vec3::NEG_Y, Vec3::X, Vec3::new(0.0, 1.0, 1.0)] {
+            let expected = convolve(&skybox, normal.normalize());
+            let actual = irradiance.evaluate(normal);
+            assert!(
+                actual.abs_diff_eq(expected, 0.05),
+                "{normal}: {actual} {expected}"
+            );
+        }
+        let baked = skybox.irradiance(4);
+        let direction = texel_direction(2, 1, 2, 4);
+        assert_eq!(baked.sample(direction), irradiance.evaluate(direction));
+    }
+
+    #[test]
+    fn downsample_averages_blocks() {
+        let skybox = Cubemap::from_fn(8, |direction| Vec3::splat(direction.x.max(0.0)));
+        assert_eq!(skybox.downsample(3).size, 2);
+        let reduced = skybox.downsample(2);
+        assert_eq!(reduced.size, 2);
+        // Faces of 8 in blocks of 4, the first block is the top left quarter of the face
+        let corner = (0..16)
+            .map(|i| skybox.faces[0][i / 4 * 8 + i % 4])
+            .sum::<Vec3>()
+            / 16.0;
+        assert!(reduced.faces[0][0].abs_diff_eq(corner, 1e-6));
+        assert_eq!(skybox.downsample(8), skybox);
+        let flat = Irradiance::from_cubemap(&skybox).evaluate(Vec3::X);
+        let reduced = Irradiance::from_cubemap(&skybox.downsample(4)).evaluate(Vec3::X);
+        assert!(flat.abs_diff_eq(reduced, 0.01), "{flat} {reduced}");
+    }
+
+    #[test]
+    fn rgba8_round_trip() {
+        let level = Cubemap::from_fn(2, |_| Vec3::splat(FLAT_AMBIENT));
+        for srgb in [false, true] {
+            let faces = level.to_rgba8(srgb);
+            assert_eq!(faces[0].len(), 2 * 2 * 4);
+            assert_eq!(faces[0][3], 255);
+            let stored = faces[0][0] as f32 / 255.0;
+            let sampled = if srgb { srgb_to_linear(stored) } else { stored };
+            assert!((sampled - FLAT_AMBIENT).abs() < 0.005, "{sampled}");
+        }
+        let decoded = Cubemap::from_rgba8(2, &level.to_rgba8(true));
+        assert!(decoded.faces[5][3].abs_diff_eq(level.faces[5][3], 0.005));
+        let bright = Cubemap::from_fn(1, |_| Vec3::splat(4.0)).to_rgba8(false);
+        assert_eq!(bright[0], [255; 4]);
+    }
+}
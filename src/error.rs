@@ -0,0 +1,162 @@
+//! Errors the application is expected to tell apart, the others stay `Box<dyn Error>`.
+//! Downcast the boxed error to match on them.
+
+use crate::block_compression::BlockFormat;
+use std::{error::Error, fmt, path::PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PulsarError {
+    /// The window surface cannot be presented to, the display offers no format or the queries kept
+    /// failing. Common over remote desktops and on virtual GPUs, fall back to a `NullRendererFactory`.
+    SurfaceUnsupported { reason: String },
+    /// A mesh, model, scene or pipeline cache is truncated, of another kind or holds impossible
+    /// values.
+    CacheCorrupt { reason: String },
+    /// A cache written with another layout, bake it again.
+    CacheVersion { found: u32, supported: u32 },
+    /// A pipeline cache on disk written by another device or driver version, rebuilt from empty.
+    PipelineCacheMismatch { vendor_id: u32, device_id: u32 },
+    /// A shader read at runtime failed to compile or is not SPIR-V.
+    ShaderCompile { path: PathBuf, reason: String },
+    /// An `#include` that could not be inlined, at `line` of `path`, see `shader_include`.
+    ShaderInclude {
+        path: PathBuf,
+        line: u32,
+        reason: String,
+    },
+    /// Shaders whose descriptors or push constants do not match what the renderer writes, or
+    /// that cannot be reflected, see `shader_reflect`.
+    ShaderInterface { shader: String, reason: String },
+    /// A material with a geometry stage on a device without the `geometryShader` feature.
+    GeometryShaderUnsupported { shader: String },
+    /// A material with tessellation stages the device cannot run, for lack of the
+    /// `tessellationShader` feature or of patches as large, or whose patches do not match them.
+    Tessellation { shader: String, reason: String },
+    /// An image file could not be read or decoded into a texture.
+    TextureLoad { path: PathBuf, reason: String },
+    /// A texture array of more layers than `maxImageArrayLayers`, or a layer pushed past its count.
+    TextureArrayLayers { requested: u32, max: u32 },
+    /// A cubemap face of another size than the first, or not square. `size` is the first face's
+    /// edge, faces count from 0 in layer order.
+    CubemapFace {
+        face: u32,
+        width: u32,
+        height: u32,
+        size: u32,
+    },
+    /// An image that is neither a cross nor a strip of square cubemap faces.
+    CubemapLayout { width: u32, height: u32 },
+    /// A mip level of a block compressed texture of another size than its blocks take, levels
+    /// count from 0.
+    CompressedLevel {
+        level: u32,
+        len: usize,
+        expected: usize,
+    },
+    /// The device cannot sample textures of a block compressed format, decode them to RGBA8
+    /// instead.
+    UnsupportedTextureFormat { format: BlockFormat },
+    /// A material parameter that was never registered, `valid` lists the material's parameters.
+    UnknownMaterialParam {
+        material: String,
+        name: String,
+        valid: Vec<String>,
+    },
+    /// A window's queue of mesh updates is full and set to reject, see
+    /// [`crate::update_queue::Backpressure`]. Post again once its render thread drained it.
+    Backpressure { capacity: usize },
+    /// A transient attachment is first used by a pass reading it, its memory may hold another
+    /// attachment's texels. See [`crate::transient`].
+    TransientUninitialized { attachment: usize, pass: String },
+}
+
+impl fmt::Display for PulsarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SurfaceUnsupported { reason } => write!(f, "Surface unsupported: {reason}"),
+            Self::CacheCorrupt { reason } => write!(f, "Corrupt cache: {reason}"),
+            Self::CacheVersion { found, supported } => {
+                write!(f, "Cache version {found}, only {supported} is supported")
+            }
+            Self::PipelineCacheMismatch {
+                vendor_id,
+                device_id,
+            } => write!(
+                f,
+                "Pipeline cache written by device {vendor_id:#06x}:{device_id:#06x} or another driver"
+            ),
+            Self::ShaderCompile { path, reason } => {
+                write!(f, "Shader {} failed to compile: {reason}", path.display())
+            }
+            Self::ShaderInclude { path, line, reason } => {
+                write!(f, "{}:{line}: {reason}", path.display())
+            }
+            Self::ShaderInterface { shader, reason } => write!(f, "Shader {shader}: {reason}"),
+            Self::GeometryShaderUnsupported { shader } => write!(
+                f,
+                "Geometry shader {shader} needs the geometryShader feature, which the device lacks"
+            ),
+            Self::Tessellation { shader, reason } => {
+                write!(f, "Tessellation shader {shader}: {reason}")
+            }
+            Self::TextureLoad { path, reason } => {
+                write!(f, "Texture {} failed to load: {reason}", path.display())
+            }
+            Self::TextureArrayLayers { requested, max } => {
+                write!(f, "Texture array of {requested} layers, at most {max}")
+            }
+            Self::CubemapFace {
+                face,
+                width,
+                height,
+                size,
+            } => write!(
+                f,
+                "Cubemap face {face} is {width}x{height}, the faces are {size}x{size}"
+            ),
+            Self::CubemapLayout { width, height } => write!(
+                f,
+                "A {width}x{height} image is no cross or strip of cubemap faces"
+            ),
+            Self::CompressedLevel {
+                level,
+                len,
+                expected,
+            } => write!(
+                f,
+                "{len} bytes for mip level {level} of a compressed texture, expected {expected}"
+            ),
+            Self::UnsupportedTextureFormat { format } => {
+                write!(f, "{format:?} textures cannot be sampled on this device")
+            }
+            Self::UnknownMaterialParam {
+                material,
+                name,
+                valid,
+            } if valid.is_empty() => {
+                write!(
+                    f,
+                    "Material {material} has no parameter {name}, nor any other"
+                )
+            }
+            Self::UnknownMaterialParam {
+                material,
+                name,
+                valid,
+            } => write!(
+                f,
+                "Material {material} has no parameter {name}, valid: {}",
+                valid.join(", ")
+            ),
+            Self::Backpressure { capacity } => {
+                write!(f, "Mesh update queue full, {capacity} updates waiting")
+            }
+            Self::TransientUninitialized { attachment, pass } => write!(
+                f,
+                "Transient attachment {attachment} first used by pass {pass} without a clear or overwrite"
+            ),
+        }
+    }
+}
+
+impl Error for PulsarError {}
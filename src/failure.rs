@@ -0,0 +1,574 @@
+//! Screen drawn in place of the scene once rendering cannot go on as is, so the window tells what
+//! went wrong instead of freezing or closing.
+//!
+//! The screen is a solid background, bars encoding the [`FailureCategory`], and with the `text`
+//! feature the failure's title and wrapped detail in a built-in bitmap font laid out by the text
+//! stack. Esc closes the window, R retries: the engine runs its own recovery for the category,
+//! then the application's [`RetryCallback`] if it gave one, and draws the scene again once both
+//! succeed.
+
+use crate::model::{Mesh, Vertex};
+use glam::{Mat4, Vec2};
+use winit::keyboard::{Key, NamedKey};
+
+/// Cleared behind the screen, a dark red that reads as an error whatever the scene looked like.
+pub const FAILURE_BACKGROUND: [f32; 4] = [0.09, 0.02, 0.03, 1.0];
+/// Space left around the screen's content, in screen pixels.
+const MARGIN: f32 = 48.0;
+/// Height and gap of the category bars.
+const BAR_HEIGHT: f32 = 12.0;
+const BAR_GAP: f32 = 8.0;
+
+/// What failed, told apart by color and bar count when no text can be drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FailureCategory {
+    /// Acquiring or presenting kept failing, retrying recreates the swapchain.
+    Device,
+    Shader,
+    Asset,
+    /// Raised by the application for its own fatal states.
+    Application,
+}
+
+impl FailureCategory {
+    pub fn color(self) -> [f32; 4] {
+        match self {
+            Self::Device => [0.95, 0.25, 0.2, 1.0],
+            Self::Shader => [0.95, 0.7, 0.15, 1.0],
+            Self::Asset => [0.3, 0.6, 0.95, 1.0],
+            Self::Application => [0.75, 0.4, 0.9, 1.0],
+        }
+    }
+
+    /// Bars drawn across the top of the screen, 1 for a device failure up to 4.
+    pub fn bars(self) -> u32 {
+        match self {
+            Self::Device => 1,
+            Self::Shader => 2,
+            Self::Asset => 3,
+            Self::Application => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    pub category: FailureCategory,
+    pub title: String,
+    /// Wrapped to the window, `\n` starts a new line.
+    pub detail: String,
+}
+
+impl Failure {
+    pub fn new(
+        category: FailureCategory,
+        title: impl Into<String>,
+        detail: impl Into<String>,
+    ) -> Self {
+        Self {
+            category,
+            title: title.into(),
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Run on the render thread when the user retries, returns whether the failure is over.
+pub type RetryCallback = Box<dyn FnMut() -> bool + Send>;
+
+/// What a key does while the screen is shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKey {
+    Exit,
+    Retry,
+}
+
+impl FailureKey {
+    pub fn from_key(key: &Key) -> Option<Self> {
+        match key.as_ref() {
+            Key::Named(NamedKey::Escape) => Some(Self::Exit),
+            Key::Character(ch) if ch.eq_ignore_ascii_case("r") => Some(Self::Retry),
+            _ => None,
+        }
+    }
+}
+
+/// Failure screen of one window, entered and left from either thread.
+#[derive(Default)]
+pub struct FailureScreen {
+    failure: Option<Failure>,
+    retry: Option<RetryCallback>,
+    retry_requested: bool,
+    /// Retries that did not recover since the screen was entered.
+    pub failed_retries: u32,
+}
+
+impl FailureScreen {
+    /// Show `failure` in place of the scene, replacing the one shown. Without `retry`, retrying
+    /// only runs the engine's recovery for the category.
+    pub fn enter(&mut self, failure: Failure, retry: Option<RetryCallback>) {
+        self.failure = Some(failure);
+        self.retry = retry;
+        self.retry_requested = false;
+        self.failed_retries = 0;
+    }
+
+    /// Draw the scene again, returns the failure that was shown.
+    pub fn leave(&mut self) -> Option<Failure> {
+        self.retry = None;
+        self.retry_requested = false;
+        self.failure.take()
+    }
+
+    pub fn failure(&self) -> Option<&Failure> {
+        self.failure.as_ref()
+    }
+
+    /// Ask the render thread to retry before its next frame, ignored when nothing failed.
+    pub fn request_retry(&mut self) {
+        self.retry_requested = self.failure.is_some();
+    }
+
+    pub fn take_retry_request(&mut self) -> bool {
+        std::mem::take(&mut self.retry_requested)
+    }
+
+    /// Run the application's callback once the engine's recovery went through, and leave the
+    /// screen when it succeeds. Returns whether it left.
+    pub fn retry(&mut self, engine_recovered: bool) -> bool {
+        let recovered = engine_recovered && self.retry.as_mut().is_none_or(|callback| callback());
+        if recovered {
+            self.leave();
+        } else {
+            self.failed_retries += 1;
+        }
+        recovered
+    }
+}
+
+/// A filled rectangle of the screen, in pixels from the top left.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScreenRect {
+    pub min: Vec2,
+    pub max: Vec2,
+    pub color: [f32; 4],
+}
+
+/// Rectangles drawing `failure` over the background on a `size` window: the category bars, then
+/// the title, detail and key hint when built with the `text` feature.
+pub fn failure_rects(failure: &Failure, size: Vec2) -> Vec<ScreenRect> {
+    let color = failure.category.color();
+    let width = (size.x - 2.0 * MARGIN).max(1.0);
+    let rects: Vec<ScreenRect> = (0..failure.category.bars())
+        .map(|bar| {
+            let top = MARGIN + bar as f32 * (BAR_HEIGHT + BAR_GAP);
+            ScreenRect {
+                min: Vec2::new(MARGIN, top),
+                max: Vec2::new(MARGIN + width, top + BAR_HEIGHT),
+                color,
+            }
+        })
+        .collect();
+    #[cfg(feature = "text")]
+    let rects = {
+        let top = rects.last().map_or(MARGIN, |bar| bar.max.y) + 2.0 * BAR_GAP;
+        let text = bitmap_font::failure_text(failure, Vec2::new(MARGIN, top), width);
+        [rects, text].concat()
+    };
+    rects
+}
+
+/// [`failure_rects`] as quads in normalized device coordinates, drawn unlit over the cleared
+/// background.
+pub fn failure_mesh(failure: &Failure, size: Vec2) -> Mesh {
+    let rects = failure_rects(failure, size);
+    let mut vertices = Vec::with_capacity(rects.len() * 4);
+    let mut indices = Vec::with_capacity(rects.len() * 6);
+    for rect in rects {
+        let min = rect.min / size * 2.0 - 1.0;
+        let max = rect.max / size * 2.0 - 1.0;
+        let start = vertices.len() as u32;
+        for (x, y) in [
+            (min.x, min.y),
+            (max.x, min.y),
+            (max.x, max.y),
+            (min.x, max.y),
+        ] {
+            vertices.push(Vertex {
+                pos: [x, y, 0.0, 1.0],
+                uv: [0.0; 2],
+                color: rect.color,
+                normal: [0.0, 0.0, 1.0],
+                tangent: [1.0, 0.0, 0.0, 1.0],
+                joints: [0; 4],
+                weights: [0.0; 4],
+            });
+        }
+        indices.extend([0, 1, 2, 0, 2, 3].map(|index| start + index));
+    }
+    Mesh {
+        vertices,
+        indices,
+        transform: Mat4::IDENTITY,
+        user_id: 0,
+    }
+}
+
+/// 5 by 7 pixel ASCII glyphs, lowercase drawn as uppercase, laid out by [`crate::text`].
+#[cfg(feature = "text")]
+mod bitmap_font {
+    use super::{Failure, ScreenRect};
+    use crate::text::{FontMetrics, FontStack, TextLayout};
+    use glam::Vec2;
+    use std::sync::Arc;
+
+    /// Font pixels per em, a glyph cell is 6 by 8 of them with the spacing.
+    const EM: f32 = 8.0;
+    /// Screen pixels per font pixel of the detail, the title is twice as large.
+    const FONT_SCALE: f32 = 3.0;
+    /// Widest block of text, in screen pixels.
+    const MAX_TEXT_WIDTH: f32 = 1200.0;
+    const HINT: &str = "Press Esc to exit, R to retry";
+
+    struct BitmapFont;
+
+    impl FontMetrics for BitmapFont {
+        fn advance(&self, c: char) -> Option<f32> {
+            (c == ' ' || glyph(c).is_some()).then_some(6.0 / EM)
+        }
+
+        fn ascender(&self) -> f32 {
+            7.0 / EM
+        }
+
+        fn descender(&self) -> f32 {
+            -1.0 / EM
+        }
+
+        fn line_gap(&self) -> f32 {
+            3.0 / EM
+        }
+    }
+
+    /// Title, detail and key hint of `failure` stacked from `origin`, wrapped to `width`.
+    pub fn failure_text(failure: &Failure, origin: Vec2, width: f32) -> Vec<ScreenRect> {
+        let mut rects = Vec::new();
+        let max_width = Some(width.min(MAX_TEXT_WIDTH));
+        let mut top = origin.y;
+        for (text, scale, color) in [
+            (&*failure.title, FONT_SCALE * 2.0, failure.category.color()),
+            (&*failure.detail, FONT_SCALE, [1.0; 4]),
+            (HINT, FONT_SCALE, [0.7, 0.7, 0.7, 1.0]),
+        ] {
+            let origin = Vec2::new(origin.x, top);
+            top = push_text(&mut rects, text, scale, origin, max_width, color) + EM * FONT_SCALE;
+        }
+        rects
+    }
+
+    /// Lay out `text` at `origin` in `scale` sized font pixels and push one rectangle per lit
+    /// pixel. Returns the top of the next block.
+    fn push_text(
+        rects: &mut Vec<ScreenRect>,
+        text: &str,
+        scale: f32,
+        origin: Vec2,
+        max_width: Option<f32>,
+        color: [f32; 4],
+    ) -> f32 {
+        let fonts = FontStack::new(Arc::new(BitmapFont));
+        let layout = TextLayout::new(&fonts, text, EM * scale, max_width);
+        for glyph_rect in &layout.metrics.glyphs {
+            // Characters the font lacks take its replacement, a question mark
+            let Some(rows) = glyph(glyph_rect.character).or(glyph('?')) else {
+                continue;
+            };
+            if glyph_rect.character == ' ' {
+                continue;
+            }
+            for (y, row) in rows.iter().enumerate() {
+                for x in (0..5).filter(|x| row & (0b10000 >> x) != 0) {
+                    let min = origin + glyph_rect.position + Vec2::new(x as f32, y as f32) * scale;
+                    rects.push(ScreenRect {
+                        min,
+                        max: min + Vec2::splat(scale),
+                        color,
+                    });
+                }
+            }
+        }
+        origin.y + layout.metrics.height
+    }
+
+    fn glyph(c: char) -> Option<[u8; 7]> {
+        Some(match c.to_ascii_uppercase() {
+            'A' => [
+                0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001,
+            ],
+            'B' => [
+                0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110,
+            ],
+            'C' => [
+                0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110,
+            ],
+            'D' => [
+                0b11100, 0b10010, 0b10001, 0b10001, 0b10001, 0b10010, 0b11100,
+            ],
+            'E' => [
+                0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111,
+            ],
+            'F' => [
+                0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000,
+            ],
+            'G' => [
+                0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111,
+            ],
+            'H' => [
+                0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001,
+            ],
+            'I' => [
+                0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110,
+            ],
+            'J' => [
+                0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100,
+            ],
+            'K' => [
+                0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001,
+            ],
+            'L' => [
+                0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111,
+            ],
+            'M' => [
+                0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001,
+            ],
+            'N' => [
+                0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001,
+            ],
+            'O' => [
+                0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110,
+            ],
+            'P' => [
+                0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000,
+            ],
+            'Q' => [
+                0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101,
+            ],
+            'R' => [
+                0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001,
+            ],
+            'S' => [
+                0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110,
+            ],
+            'T' => [
+                0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100,
+            ],
+            'U' => [
+                0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110,
+            ],
+            'V' => [
+                0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100,
+            ],
+            'W' => [
+                0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010,
+            ],
+            'X' => [
+                0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001,
+            ],
+            'Y' => [
+                0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100,
+            ],
+            'Z' => [
+                0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111,
+            ],
+            '0' => [
+                0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110,
+            ],
+            '1' => [
+                0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110,
+            ],
+            '2' => [
+                0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111,
+            ],
+            '3' => [
+                0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110,
+            ],
+            '4' => [
+                0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010,
+            ],
+            '5' => [
+                0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110,
+            ],
+            '6' => [
+                0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110,
+            ],
+            '7' => [
+                0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000,
+            ],
+            '8' => [
+                0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110,
+            ],
+            '9' => [
+                0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100,
+            ],
+            '.' => [
+                0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100,
+            ],
+            ',' => [
+                0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000,
+            ],
+            ':' => [
+                0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000,
+            ],
+            ';' => [
+                0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b00100, 0b01000,
+            ],
+            '!' => [
+                0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00000, 0b00100,
+            ],
+            '?' => [
+                0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100,
+            ],
+            '-' => [
+                0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000,
+            ],
+            '_' => [
+                0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b11111,
+            ],
+            '+' => [
+                0b00000, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0b00000,
+            ],
+            '=' => [
+                0b00000, 0b00000, 0b11111, 0b00000, 0b11111, 0b00000, 0b00000,
+            ],
+            '/' => [
+                0b00000, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b00000,
+            ],
+            '\\' => [
+                0b00000, 0b10000, 0b01000, 0b00100, 0b00010, 0b00001, 0b00000,
+            ],
+            '\'' => [
+                0b01100, 0b00100, 0b01000, 0b00000, 0b00000, 0b00000, 0b00000,
+            ],
+            '"' => [
+                0b01010, 0b01010, 0b01010, 0b00000, 0b00000, 0b00000, 0b00000,
+            ],
+            '(' => [
+                0b00010, 0b00100, 0b01000, 0b01000, 0b01000, 0b00100, 0b00010,
+            ],
+            ')' => [
+                0b01000, 0b00100, 0b00010, 0b00010, 0b00010, 0b00100, 0b01000,
+            ],
+            '[' => [
+                0b01110, 0b01000, 0b01000, 0b01000, 0b01000, 0b01000, 0b01110,
+            ],
+            ']' => [
+                0b01110, 0b00010, 0b00010, 0b00010, 0b00010, 0b00010, 0b01110,
+            ],
+            '<' => [
+                0b00010, 0b00100, 0b01000, 0b10000, 0b01000, 0b00100, 0b00010,
+            ],
+            '>' => [
+                0b01000, 0b00100, 0b00010, 0b00001, 0b00010, 0b00100, 0b01000,
+            ],
+            '#' => [
+                0b01010, 0b01010, 0b11111, 0b01010, 0b11111, 0b01010, 0b01010,
+            ],
+            '%' => [
+                0b11000, 0b11001, 0b00010, 0b00100, 0b01000, 0b10011, 0b00011,
+            ],
+            '*' => [
+                0b00000, 0b00100, 0b10101, 0b01110, 0b10101, 0b00100, 0b00000,
+            ],
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    };
+
+    const SIZE: Vec2 = Vec2::new(1280.0, 720.0);
+
+    #[test]
+    fn retries_run_the_callback_until_it_succeeds() {
+        const FAILED_ATTEMPTS: u32 = 2;
+        let calls = Arc::new(AtomicU32::new(0));
+        let counted = calls.clone();
+        let mut screen = FailureScreen::default();
+        screen.enter(
+            Failure::new(FailureCategory::Asset, "Title", "Detail"),
+            Some(Box::new(move || {
+                counted.fetch_add(1, Ordering::Relaxed) >= FAILED_ATTEMPTS
+            })),
+        );
+        // The engine's recovery failing leaves the callback alone
+        screen.request_retry();
+        assert!(screen.take_retry_request());
+        assert!(!screen.retry(false));
+        assert_eq!(calls.load(Ordering::Relaxed), 0);
+
+        for attempt in 1..=FAILED_ATTEMPTS + 1 {
+            screen.request_retry();
+            assert!(screen.take_retry_request(), "retry {attempt}");
+            assert_eq!(
+                screen.retry(true),
+                attempt > FAILED_ATTEMPTS,
+                "retry {attempt}"
+            );
+        }
+        assert_eq!(calls.load(Ordering::Relaxed), FAILED_ATTEMPTS + 1);
+        assert!(screen.failure().is_none());
+        // Nothing failed, nothing to retry
+        screen.request_retry();
+        assert!(!screen.take_retry_request());
+    }
+
+    #[test]
+    fn keys_map_to_exit_and_retry() {
+        let keys = [
+            (Key::Named(NamedKey::Escape), Some(FailureKey::Exit)),
+            (Key::Character("r".into()), Some(FailureKey::Retry)),
+            (Key::Character("R".into()), Some(FailureKey::Retry)),
+            (Key::Character("q".into()), None),
+        ];
+        for (key, expected) in keys {
+            assert_eq!(FailureKey::from_key(&key), expected, "{key:?}");
+        }
+    }
+
+    #[test]
+    fn each_category_draws_its_bars_within_the_screen() {
+        for category in [
+            FailureCategory::Device,
+            FailureCategory::Shader,
+            FailureCategory::Asset,
+            FailureCategory::Application,
+        ] {
+            let failure = Failure::new(category, "Title", "Detail");
+            let rects = failure_rects(&failure, SIZE);
+            let bars = rects
+                .iter()
+                .filter(|rect| rect.max.x - rect.min.x > SIZE.x / 2.0)
+                .count() as u32;
+            assert_eq!(bars, category.bars(), "{category:?}");
+            // Every lit pixel of the text is a quad of its own
+            if cfg!(feature = "text") {
+                assert!(rects.len() as u32 > bars, "{category:?} drew no text");
+            }
+            let mesh = failure_mesh(&failure, SIZE);
+            assert_eq!(mesh.vertices.len(), rects.len() * 4);
+            for vertex in &mesh.vertices {
+                assert!(
+                    vertex.pos[..2].iter().all(|ndc| (-1.0..=1.0).contains(ndc)),
+                    "{category:?} quads leave the screen at {:?}",
+                    vertex.pos
+                );
+            }
+        }
+    }
+}
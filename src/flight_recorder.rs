@@ -0,0 +1,481 @@
+//! Ring of the render thread's recent decisions, dumped to a file when something goes wrong.
+//!
+//! The render thread writes compact records without locking or formatting, the event loop and
+//! the metrics endpoint decode them after the fact. Readers skip the records overwritten while
+//! they were reading, the writer never waits for them.
+use crate::{custom_pass::CustomPassSlot, handles::MeshHandle, watchdog::StallReport};
+use ash::vk;
+use std::{
+    fmt::{self, Write as _},
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{fence, AtomicU64, Ordering},
+        Arc, Mutex, OnceLock, Weak,
+    },
+};
+
+/// Records kept per window, a bit over half a minute of acquires and presents at 60 fps.
+pub const FLIGHT_RECORDER_CAPACITY: usize = 4096;
+
+const DUMP_OUTPUT_DIR: &str = "diagnostics";
+/// Marks a slot being written, sequences start at 1.
+const WRITING: u64 = 0;
+const PAYLOAD_BITS: u32 = 56;
+const PAYLOAD_MASK: u64 = (1 << PAYLOAD_BITS) - 1;
+const EXTENT_MASK: u64 = 0xFF_FFFF;
+
+/// Why the swapchain was rebuilt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum RecreateReason {
+    Resized,
+    /// Acquire or present reported it out of date, or the window got an area back.
+    OutOfDate,
+    /// Acquire or present reported it suboptimal, the frame was still presented.
+    Suboptimal,
+    /// The application asked for another present mode preference.
+    PresentModeChanged,
+    /// MAILBOX was given up on for this device, by this window or another.
+    PresentModeDowngraded,
+}
+
+/// Something the render thread chose or was told, worth knowing after a glitch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Acquired {
+        image: u32,
+        suboptimal: bool,
+    },
+    AcquireFailed(vk::Result),
+    Presented {
+        suboptimal: bool,
+    },
+    PresentFailed(vk::Result),
+    /// Extents above 16777215 are truncated.
+    SwapchainRecreated {
+        reason: RecreateReason,
+        width: u32,
+        height: u32,
+    },
+    /// Draws using the default pipeline while their material's is being built.
+    PipelineFallback {
+        draws: u32,
+    },
+    /// Draws left out because their mesh was not resident.
+    DrawsSkipped {
+        draws: u32,
+    },
+    /// Generations above 16777215 are truncated.
+    Evicted(MeshHandle),
+    Reloaded(MeshHandle),
+    /// The frame rate cap changed, zero when uncapped.
+    PacingChanged {
+        fps_cap: u32,
+    },
+    /// The application's pass of the slot panicked while recording and was removed.
+    CustomPassPanicked(CustomPassSlot),
+    /// MAILBOX starved the window of images, presenting falls back to FIFO.
+    PresentModeDowngraded {
+        slow_frames: u32,
+    },
+    /// Waits for presents kept failing, frames are paced by the fences again.
+    PresentWaitFallback {
+        strikes: u32,
+    },
+    /// The swapchain presents with another mode, after the preference changed.
+    PresentModeChanged(vk::PresentModeKHR),
+    /// The default material's texture, sampled as white until reloaded.
+    TextureEvicted,
+    TextureReloaded,
+}
+
+impl Decision {
+    /// Tag in the high byte, the fields packed below it.
+    fn encode(self) -> u64 {
+        let (tag, payload) = match self {
+            Self::Acquired { image, suboptimal } => (1, image as u64 | (suboptimal as u64) << 32),
+            Self::AcquireFailed(result) => (2, result.as_raw() as u32 as u64),
+            Self::Presented { suboptimal } => (3, suboptimal as u64),
+            Self::PresentFailed(result) => (4, result.as_raw() as u32 as u64),
+            Self::SwapchainRecreated {
+                reason,
+                width,
+                height,
+            } => (
+                5,
+                reason as u64
+                    | (width as u64).min(EXTENT_MASK) << 8
+                    | (height as u64).min(EXTENT_MASK) << 32,
+            ),
+            Self::PipelineFallback { draws } => (6, draws as u64),
+            Self::DrawsSkipped { draws } => (7, draws as u64),
+            Self::Evicted(mesh) => (8, encode_handle(mesh)),
+            Self::Reloaded(mesh) => (9, encode_handle(mesh)),
+            Self::PacingChanged { fps_cap } => (10, fps_cap as u64),
+            Self::CustomPassPanicked(slot) => (11, slot as u64),
+            Self::PresentModeDowngraded { slow_frames } => (12, slow_frames as u64),
+            Self::PresentWaitFallback { strikes } => (13, strikes as u64),
+            Self::PresentModeChanged(mode) => (14, mode.as_raw() as u32 as u64),
+            Self::TextureEvicted => (15, 0),
+            Self::TextureReloaded => (16, 0),
+        };
+        (tag as u64) << PAYLOAD_BITS | payload & PAYLOAD_MASK
+    }
+
+    fn decode(code: u64) -> Option<Self> {
+        let payload = code & PAYLOAD_MASK;
+        let low = payload as u32;
+        Some(match code >> PAYLOAD_BITS {
+            1 => Self::Acquired {
+                image: low,
+                suboptimal: payload >> 32 & 1 == 1,
+            },
+            2 => Self::AcquireFailed(vk::Result::from_raw(low as i32)),
+            3 => Self::Presented {
+                suboptimal: payload & 1 == 1,
+            },
+            4 => Self::PresentFailed(vk::Result::from_raw(low as i32)),
+            5 => Self::SwapchainRecreated {
+                reason: match payload & 0xFF {
+                    0 => RecreateReason::Resized,
+                    1 => RecreateReason::OutOfDate,
+                    2 => RecreateReason::Suboptimal,
+                    3 => RecreateReason::PresentModeChanged,
+                    4 => RecreateReason::PresentModeDowngraded,
+                    _ => return None,
+                },
+                width: (payload >> 8 & EXTENT_MASK) as u32,
+                height: (payload >> 32 & EXTENT_MASK) as u32,
+            },
+            6 => Self::PipelineFallback { draws: low },
+            7 => Self::DrawsSkipped { draws: low },
+            8 => Self::Evicted(decode_handle(payload)),
+            9 => Self::Reloaded(decode_handle(payload)),
+            10 => Self::PacingChanged { fps_cap: low },
+            11 => Self::CustomPassPanicked(*CustomPassSlot::ALL.get(low as usize)?),
+            12 => Self::PresentModeDowngraded { slow_frames: low },
+            13 => Self::PresentWaitFallback { strikes: low },
+            14 => Self::PresentModeChanged(vk::PresentModeKHR::from_raw(low as i32)),
+            15 => Self::TextureEvicted,
+            16 => Self::TextureReloaded,
+            _ => return None,
+        })
+    }
+}
+
+fn encode_handle(mesh: MeshHandle) -> u64 {
+    mesh.index() as u32 as u64 | (mesh.generation() as u64 & EXTENT_MASK) << 32
+}
+
+fn decode_handle(payload: u64) -> MeshHandle {
+    MeshHandle::new(payload as u32 as usize, (payload >> 32) as u32)
+}
+
+/// A decision and the frame it was made in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecisionRecord {
+    pub frame: u64,
+    pub decision: Decision,
+}
+
+impl fmt::Display for DecisionRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "frame {:>8}  {:?}", self.frame, self.decision)
+    }
+}
+
+/// What triggered a dump, written at the top of it.
+#[derive(Debug, Clone)]
+pub enum DumpReason {
+    Requested,
+    Stalled(StallReport),
+    /// The render thread panicked, device lost included.
+    Panicked(String),
+}
+
+impl fmt::Display for DumpReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Requested => write!(f, "requested"),
+            Self::Stalled(report) => write!(f, "render thread stalled, {report}"),
+            Self::Panicked(message) => write!(f, "render thread panicked, {message}"),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Slot {
+    /// Sequence of the record in the slot, [`WRITING`] while it is replaced.
+    sequence: AtomicU64,
+    frame: AtomicU64,
+    code: AtomicU64,
+}
+
+/// Fixed capacity ring written by a single thread, readable from any.
+#[derive(Debug)]
+pub struct FlightRecorder {
+    slots: Box<[Slot]>,
+    /// Records written so far, the next one gets this plus one as its sequence.
+    written: AtomicU64,
+    device: OnceLock<String>,
+}
+
+static PUBLISHED: Mutex<Vec<Weak<FlightRecorder>>> = Mutex::new(Vec::new());
+
+/// Recorders of every live window, for the metrics endpoint.
+pub fn recorders() -> Vec<Arc<FlightRecorder>> {
+    let mut published = PUBLISHED.lock().unwrap();
+    published.retain(|recorder| recorder.strong_count() > 0);
+    published.iter().filter_map(Weak::upgrade).collect()
+}
+
+impl Default for FlightRecorder {
+    fn default() -> Self {
+        Self::new(FLIGHT_RECORDER_CAPACITY)
+    }
+}
+
+impl FlightRecorder {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            slots: (0..capacity.max(1)).map(|_| Slot::default()).collect(),
+            written: AtomicU64::new(0),
+            device: OnceLock::new(),
+        }
+    }
+
+    /// A default recorder listed by [`recorders`] while it lives.
+    pub fn published() -> Arc<Self> {
+        let recorder = Arc::new(Self::default());
+        PUBLISHED.lock().unwrap().push(Arc::downgrade(&recorder));
+        recorder
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Device description written in the dumps, the first one set stays.
+    pub fn set_device_info(&self, info: String) {
+        let _ = self.device.set(info);
+    }
+
+    /// Overwrites the oldest record once full. Only one thread may record.
+    #[inline]
+    pub fn record(&self, frame: u64, decision: Decision) {
+        let sequence = self.written.load(Ordering::Relaxed) + 1;
+        let slot = &self.slots[(sequence - 1) as usize % self.slots.len()];
+        slot.sequence.store(WRITING, Ordering::Relaxed);
+        fence(Ordering::Release);
+        slot.frame.store(frame, Ordering::Relaxed);
+        slot.code.store(decision.encode(), Ordering::Relaxed);
+        slot.sequence.store(sequence, Ordering::Release);
+        self.written.store(sequence, Ordering::Release);
+    }
+
+    /// Records still in the ring, oldest first.
+    pub fn records(&self) -> Vec<DecisionRecord> {
+        let written = self.written.load(Ordering::Acquire);
+        let first = written.saturating_sub(self.slots.len() as u64) + 1;
+        (first..=written)
+            .filter_map(|sequence| {
+                let slot = &self.slots[(sequence - 1) as usize % self.slots.len()];
+                if slot.sequence.load(Ordering::Acquire) != sequence {
+                    return None;
+                }
+                let frame = slot.frame.load(Ordering::Relaxed);
+                let code = slot.code.load(Ordering::Relaxed);
+                fence(Ordering::Acquire);
+                // Overwritten while read
+                if slot.sequence.load(Ordering::Relaxed) != sequence {
+                    return None;
+                }
+                Some(DecisionRecord {
+                    frame,
+                    decision: Decision::decode(code)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Human readable dump: the reason, the device, then one record per line, oldest first.
+    pub fn dump(&self, reason: &DumpReason) -> String {
+        let records = self.records();
+        let mut text = String::new();
+        let _ = writeln!(text, "reason: {reason}");
+        let _ = writeln!(
+            text,
+            "device: {}",
+            self.device.get().map_or("unknown", String::as_str)
+        );
+        let _ = writeln!(
+            text,
+            "records: {} of {} written",
+            records.len(),
+            self.written.load(Ordering::Relaxed)
+        );
+        for record in records {
+            let _ = writeln!(text, "{record}");
+        }
+        text
+    }
+
+    /// Write [`Self::dump`] to a timestamped file in the diagnostics directory.
+    pub fn dump_to_file(&self, reason: &DumpReason) -> std::io::Result<PathBuf> {
+        let stamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        fs::create_dir_all(DUMP_OUTPUT_DIR)?;
+        let path = PathBuf::from(DUMP_OUTPUT_DIR).join(format!("decisions_{stamp}.txt"));
+        fs::write(&path, self.dump(reason))?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_recreate_reason_survives_encoding() {
+        for reason in [
+            RecreateReason::Resized,
+            RecreateReason::OutOfDate,
+            RecreateReason::Suboptimal,
+            RecreateReason::PresentModeChanged,
+            RecreateReason::PresentModeDowngraded,
+        ] {
+            let decision = Decision::SwapchainRecreated {
+                reason,
+                width: 1280,
+                height: 720,
+            };
+            assert_eq!(Decision::decode(decision.encode()), Some(decision));
+        }
+    }
+
+    #[test]
+    fn present_mode_change_survives_encoding() {
+        let decision = Decision::PresentModeChanged(vk::PresentModeKHR::FIFO_RELAXED);
+        assert_eq!(Decision::decode(decision.encode()), Some(decision));
+    }
+
+    #[test]
+    fn residency_decisions_survive_encoding() {
+        for decision in [
+            Decision::Evicted(MeshHandle::new(7, 3)),
+            Decision::Reloaded(MeshHandle::new(7, 3)),
+            Decision::TextureEvicted,
+            Decision::TextureReloaded,
+        ] {
+            assert_eq!(Decision::decode(decision.encode()), Some(decision));
+        }
+    }
+
+    const CAPACITY: usize = 64;
+
+    #[test]
+    fn decisions_come_back_in_order() {
+        let recorder = FlightRecorder::new(CAPACITY);
+        let decisions = [
+            Decision::Acquired {
+                image: 2,
+                suboptimal: true,
+            },
+            Decision::AcquireFailed(vk::Result::ERROR_DEVICE_LOST),
+            Decision::Presented { suboptimal: false },
+            Decision::PresentFailed(vk::Result::ERROR_OUT_OF_DATE_KHR),
+            Decision::SwapchainRecreated {
+                reason: RecreateReason::OutOfDate,
+                width: 2560,
+                height: 1440,
+            },
+            Decision::PipelineFallback { draws: 3 },
+            Decision::DrawsSkipped { draws: 1 },
+            Decision::PacingChanged { fps_cap: 144 },
+        ];
+        for (index, decision) in decisions.into_iter().enumerate() {
+            recorder.record(index as u64, decision);
+        }
+        let recorded: Vec<Decision> = recorder
+            .records()
+            .iter()
+            .map(|record| record.decision)
+            .collect();
+        assert_eq!(recorded, decisions);
+    }
+
+    #[test]
+    fn wraparound_keeps_the_last_records_and_dumps_them() {
+        let recorder = FlightRecorder::new(CAPACITY);
+        // Wraps around more than once, only the last `CAPACITY` remain, oldest first
+        let total = CAPACITY as u64 * 2 + 5;
+        for frame in 0..total {
+            recorder.record(
+                frame,
+                Decision::PipelineFallback {
+                    draws: frame as u32,
+                },
+            );
+        }
+        let frames: Vec<u64> = recorder
+            .records()
+            .iter()
+            .map(|record| record.frame)
+            .collect();
+        let first = total - CAPACITY as u64;
+        assert_eq!(frames, (first..total).collect::<Vec<_>>());
+
+        let dump = recorder.dump(&DumpReason::Panicked("device lost".to_string()));
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(
+            lines[..3],
+            [
+                "reason: render thread panicked, device lost",
+                "device: unknown",
+                &format!("records: {CAPACITY} of {total} written"),
+            ]
+        );
+        assert_eq!(lines.len(), 3 + CAPACITY);
+        assert_eq!(
+            lines[3],
+            format!("frame {first:>8}  PipelineFallback {{ draws: {first} }}")
+        );
+    }
+
+    #[test]
+    fn readers_never_see_a_torn_record() {
+        let recorder = Arc::new(FlightRecorder::new(CAPACITY));
+        let writer = {
+            let recorder = recorder.clone();
+            std::thread::spawn(move || {
+                for frame in 0..200_000u64 {
+                    recorder.record(
+                        frame,
+                        Decision::DrawsSkipped {
+                            draws: frame as u32,
+                        },
+                    );
+                }
+            })
+        };
+        while !writer.is_finished() {
+            let records = recorder.records();
+            for pair in records.windows(2) {
+                assert!(pair[0].frame < pair[1].frame, "{pair:?}");
+            }
+            for record in records {
+                assert_eq!(
+                    record.decision,
+                    Decision::DrawsSkipped {
+                        draws: record.frame as u32
+                    },
+                    "torn record"
+                );
+            }
+        }
+        writer.join().unwrap();
+    }
+}
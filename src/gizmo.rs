@@ -0,0 +1,512 @@
+use crate::{
+    model::{Mesh, Vertex},
+    picking::Ray,
+};
+use glam::{Mat4, Quat, Vec3};
+use serde::{Deserialize, Serialize};
+use winit::keyboard::ModifiersState;
+
+/// Size of the handles on screen, in physical pixels.
+pub const GIZMO_SCREEN_SIZE: f32 = 120.0;
+/// Hit tolerance around the handles, relative to the gizmo size.
+const HANDLE_PICK_RADIUS: f32 = 0.08;
+const RING_SEGMENTS: u32 = 48;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GizmoMode {
+    Translate,
+    Rotate,
+    Scale,
+}
+
+impl GizmoMode {
+    /// No modifier translates, Shift rotates, Shift+Alt scales.
+    /// Other combinations are left to the mouse bindings.
+    pub fn from_modifiers(mods: ModifiersState) -> Option<Self> {
+        if mods.is_empty() {
+            Some(GizmoMode::Translate)
+        } else if mods == ModifiersState::SHIFT {
+            Some(GizmoMode::Rotate)
+        } else if mods == ModifiersState::SHIFT | ModifiersState::ALT {
+            Some(GizmoMode::Scale)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GizmoAxis {
+    X,
+    Y,
+    Z,
+}
+
+impl GizmoAxis {
+    pub const ALL: [GizmoAxis; 3] = [GizmoAxis::X, GizmoAxis::Y, GizmoAxis::Z];
+
+    pub fn direction(&self) -> Vec3 {
+        match self {
+            GizmoAxis::X => Vec3::X,
+            GizmoAxis::Y => Vec3::Y,
+            GizmoAxis::Z => Vec3::Z,
+        }
+    }
+
+    pub fn color(&self) -> [f32; 4] {
+        match self {
+            GizmoAxis::X => [1.0, 0.2, 0.2, 1.0],
+            GizmoAxis::Y => [0.2, 1.0, 0.2, 1.0],
+            GizmoAxis::Z => [0.2, 0.4, 1.0, 1.0],
+        }
+    }
+}
+
+/// Snapping increments, 0 disables snapping for that mode.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GizmoSnapping {
+    pub translate: f32,
+    pub rotate_degrees: f32,
+    pub scale: f32,
+}
+
+impl Default for GizmoSnapping {
+    fn default() -> Self {
+        Self {
+            translate: 0.0,
+            rotate_degrees: 0.0,
+            scale: 0.0,
+        }
+    }
+}
+
+pub fn snap(value: f32, increment: f32) -> f32 {
+    if increment > 0.0 {
+        (value / increment).round() * increment
+    } else {
+        value
+    }
+}
+
+/// World size that covers `pixels` on screen at the distance of `position`.
+pub fn screen_constant_scale(
+    eye: Vec3,
+    position: Vec3,
+    fov_y: f32,
+    viewport_height: f32,
+    pixels: f32,
+) -> f32 {
+    let distance = (position - eye).length();
+    distance * 2.0 * (fov_y * 0.5).tan() * pixels / viewport_height
+}
+
+/// Parameter along the axis of the point closest to the ray, `None` when they are parallel.
+pub fn closest_axis_parameter(ray: &Ray, origin: Vec3, axis: Vec3) -> Option<f32> {
+    let b = ray.direction.dot(axis);
+    let denom = 1.0 - b * b;
+    if denom.abs() < 1e-6 {
+        return None;
+    }
+    let w = origin - ray.origin;
+    let d = ray.direction.dot(w);
+    let e = axis.dot(w);
+    Some((b * d - e) / denom)
+}
+
+/// Movement along the axis between the points under the two rays.
+pub fn axis_drag_delta(origin: Vec3, axis: Vec3, start: &Ray, current: &Ray) -> Option<f32> {
+    Some(
+        closest_axis_parameter(current, origin, axis)?
+            - closest_axis_parameter(start, origin, axis)?,
+    )
+}
+
+/// Signed angle around the axis between the two rays projected on the plane of rotation.
+pub fn rotation_drag_angle(origin: Vec3, axis: Vec3, start: &Ray, current: &Ray) -> Option<f32> {
+    let from = start.at(start.intersect_plane(origin, axis)?) - origin;
+    let to = current.at(current.intersect_plane(origin, axis)?) - origin;
+    if from.length_squared() < f32::EPSILON || to.length_squared() < f32::EPSILON {
+        return None;
+    }
+    Some(axis.dot(from.cross(to)).atan2(from.dot(to)))
+}
+
+/// Ratio between the distances to the origin along the axis of the two rays.
+pub fn scale_drag_factor(origin: Vec3, axis: Vec3, start: &Ray, current: &Ray) -> Option<f32> {
+    let from = closest_axis_parameter(start, origin, axis)?;
+    let to = closest_axis_parameter(current, origin, axis)?;
+    if from.abs() < f32::EPSILON {
+        return None;
+    }
+    Some(to / from)
+}
+
+struct GizmoDrag {
+    axis: GizmoAxis,
+    start_ray: Ray,
+    start_transform: Mat4,
+}
+
+pub struct Gizmo {
+    pub mode: GizmoMode,
+    pub snapping: GizmoSnapping,
+    drag: Option<GizmoDrag>,
+}
+
+impl Default for Gizmo {
+    fn default() -> Self {
+        Self {
+            mode: GizmoMode::Translate,
+            snapping: GizmoSnapping::default(),
+            drag: None,
+        }
+    }
+}
+
+impl Gizmo {
+    pub fn is_dragging(&self) -> bool {
+        self.drag.is_some()
+    }
+
+    /// Handle under the ray for a gizmo of world size `scale` placed on `target`.
+    pub fn hit_test(&self, ray: &Ray, target: &Mat4, scale: f32) -> Option<GizmoAxis> {
+        let origin = target.w_axis.truncate();
+        let tolerance = HANDLE_PICK_RADIUS * scale;
+        GizmoAxis::ALL
+            .iter()
+            .filter_map(|axis| {
+                let direction = axis.direction();
+                match self.mode {
+                    GizmoMode::Translate | GizmoMode::Scale => {
+                        let (distance, t) =
+                            ray.distance_to_segment(origin, origin + direction * scale);
+                        (distance < tolerance).then_some((*axis, t))
+                    }
+                    GizmoMode::Rotate => {
+                        let t = ray.intersect_plane(origin, direction)?;
+                        let radius = (ray.at(t) - origin).length();
+                        ((radius - scale).abs() < tolerance).then_some((*axis, t))
+                    }
+                }
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(axis, _)| axis)
+    }
+
+    /// Start dragging if the ray hits a handle.
+    pub fn begin_drag(&mut self, ray: &Ray, target: &Mat4, scale: f32) -> bool {
+        self.drag = self.hit_test(ray, target, scale).map(|axis| GizmoDrag {
+            axis,
+            start_ray: *ray,
+            start_transform: *target,
+        });
+        self.drag.is_some()
+    }
+
+    /// Transform of the target for the current ray, `None` when not dragging or degenerate.
+    pub fn drag(&self, ray: &Ray) -> Option<Mat4> {
+        let drag = self.drag.as_ref()?;
+        let origin = drag.start_transform.w_axis.truncate();
+        let axis = drag.axis.direction();
+        let around_origin =
+            |m: Mat4| Mat4::from_translation(origin) * m * Mat4::from_translation(-origin);
+
+        let transform = match self.mode {
+            GizmoMode::Translate => {
+                let delta = axis_drag_delta(origin, axis, &drag.start_ray, ray)?;
+                Mat4::from_translation(axis * snap(delta, self.snapping.translate))
+                    * drag.start_transform
+            }
+            GizmoMode::Rotate => {
+                let angle = rotation_drag_angle(origin, axis, &drag.start_ray, ray)?;
+                let angle = snap(angle.to_degrees(), self.snapping.rotate_degrees).to_radians();
+                around_origin(Mat4::from_quat(Quat::from_axis_angle(axis, angle)))
+                    * drag.start_transform
+            }
+            GizmoMode::Scale => {
+                let factor = scale_drag_factor(origin, axis, &drag.start_ray, ray)?;
+                let factor = snap(factor, self.snapping.scale).max(0.01);
+                around_origin(Mat4::from_scale(Vec3::ONE + axis * (factor - 1.0)))
+                    * drag.start_transform
+            }
+        };
+        Some(transform)
+    }
+
+    pub fn end_drag(&mut self) {
+        self.drag = None;
+    }
+
+    /// Placement of the handle meshes, world aligned on the target position.
+    pub fn handle_transform(target: &Mat4, scale: f32) -> Mat4 {
+        Mat4::from_translation(target.w_axis.truncate()) * Mat4::from_scale(Vec3::splat(scale))
+    }
+
+    /// Unit sized handle geometry for one axis.
+    pub fn handle_mesh(mode: GizmoMode, axis: GizmoAxis) -> Mesh {
+        // Built along +X then rotated onto the axis
+        let basis = match axis {
+            GizmoAxis::X => Mat4::IDENTITY,
+            GizmoAxis::Y => Mat4::from_rotation_z(std::f32::consts::FRAC_PI_2),
+            GizmoAxis::Z => Mat4::from_rotation_y(-std::f32::consts::FRAC_PI_2),
+        };
+        let color = axis.color();
+        let mut builder = HandleBuilder::default();
+        match mode {
+            GizmoMode::Translate => {
+                builder.cuboid(Vec3::new(0.0, -0.01, -0.01), Vec3::new(0.8, 0.01, 0.01));
+                builder.pyramid(0.8, 1.0, 0.05);
+            }
+            GizmoMode::Scale => {
+                builder.cuboid(Vec3::new(0.0, -0.01, -0.01), Vec3::new(0.9, 0.01, 0.01));
+                builder.cuboid(Vec3::new(0.9, -0.05, -0.05), Vec3::new(1.0, 0.05, 0.05));
+            }
+            GizmoMode::Rotate => builder.ring(1.0, 0.015),
+        }
+        builder.build(basis, color)
+    }
+}
+
+#[derive(Default)]
+struct HandleBuilder {
+    positions: Vec<Vec3>,
+    indices: Vec<u32>,
+}
+
+impl HandleBuilder {
+    fn quad(&mut self, corners: [Vec3; 4]) {
+        let offset = self.positions.len() as u32;
+        self.positions.extend(corners);
+        self.indices.extend([
+            offset,
+            offset + 1,
+            offset + 2,
+            offset + 2,
+            offset + 3,
+            offset,
+        ]);
+    }
+
+    fn cuboid(&mut self, min: Vec3, max: Vec3) {
+        let c = |x: f32, y: f32, z: f32| Vec3::new(x, y, z);
+        let (a, b) = (min, max);
+        self.quad([
+            c(a.x, a.y, a.z),
+            c(b.x, a.y, a.z),
+            c(b.x, b.y, a.z),
+            c(a.x, b.y, a.z),
+        ]);
+        self.quad([
+            c(a.x, a.y, b.z),
+            c(a.x, b.y, b.z),
+            c(b.x, b.y, b.z),
+            c(b.x, a.y, b.z),
+        ]);
+        self.quad([
+            c(a.x, a.y, a.z),
+            c(a.x, b.y, a.z),
+            c(a.x, b.y, b.z),
+            c(a.x, a.y, b.z),
+        ]);
+        self.quad([
+            c(b.x, a.y, a.z),
+            c(b.x, a.y, b.z),
+            c(b.x, b.y, b.z),
+            c(b.x, b.y, a.z),
+        ]);
+        self.quad([
+            c(a.x, a.y, a.z),
+            c(a.x, a.y, b.z),
+            c(b.x, a.y, b.z),
+            c(b.x, a.y, a.z),
+        ]);
+        self.quad([
+            c(a.x, b.y, a.z),
+            c(b.x, b.y, a.z),
+            c(b.x, b.y, b.z),
+            c(a.x, b.y, b.z),
+        ]);
+    }
+
+    /// Square based pyramid pointing along +X.
+    fn pyramid(&mut self, base: f32, tip: f32, half_width: f32) {
+        let offset = self.positions.len() as u32;
+        let w = half_width;
+        self.positions.extend([
+            Vec3::new(base, -w, -w),
+            Vec3::new(base, w, -w),
+            Vec3::new(base, w, w),
+            Vec3::new(base, -w, w),
+            Vec3::new(tip, 0.0, 0.0),
+        ]);
+        self.indices.extend([
+            offset,
+            offset + 2,
+            offset + 1,
+            offset,
+            offset + 3,
+            offset + 2,
+        ]);
+        for side in 0..4 {
+            self.indices
+                .extend([offset + side, offset + (side + 1) % 4, offset + 4]);
+        }
+    }
+
+    /// Flat band in the YZ plane, around +X.
+    fn ring(&mut self, radius: f32, half_width: f32) {
+        let point = |i: u32, r: f32| {
+            let angle = i as f32 / RING_SEGMENTS as f32 * std::f32::consts::TAU;
+            Vec3::new(0.0, angle.cos() * r, angle.sin() * r)
+        };
+        for i in 0..RING_SEGMENTS {
+            self.quad([
+                point(i, radius - half_width),
+                point(i + 1, radius - half_width),
+                point(i + 1, radius + half_width),
+                point(i, radius + half_width),
+            ]);
+        }
+    }
+
+    fn build(self, basis: Mat4, color: [f32; 4]) -> Mesh {
+        let vertices = self
+            .positions
+            .iter()
+            .map(|position| {
+                let p = basis.transform_point3(*position);
+                Vertex {
+                    pos: [p.x, p.y, p.z, 1.0],
+                    uv: [0.0, 0.0],
+                    color,
+                    normal: [0.0; 3],
+                    tangent: [0.0; 4],
+                    joints: [0; 4],
+                    weights: [0.0; 4],
+                }
+            })
+            .collect();
+        let mut mesh = Mesh {
+            vertices,
+            indices: self.indices,
+            transform: Mat4::IDENTITY,
+            user_id: 0,
+        };
+        mesh.generate_normals(false);
+        mesh.generate_tangents();
+        mesh
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Looking down -Z from 10 units away at `target`.
+    fn ray_to(target: Vec3) -> Ray {
+        let eye = Vec3::new(0.0, 0.0, 10.0);
+        Ray::new(eye, target - eye)
+    }
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!(a.distance(b) < 1e-4, "{a} {b}");
+    }
+
+    #[test]
+    fn snapping() {
+        assert_eq!(snap(0.37, 0.25), 0.25);
+        assert_eq!(snap(0.38, 0.25), 0.5);
+        assert_eq!(snap(-0.9, 0.5), -1.0);
+        assert_eq!(snap(0.37, 0.0), 0.37);
+    }
+
+    #[test]
+    fn modifiers_select_the_mode() {
+        assert_eq!(
+            GizmoMode::from_modifiers(ModifiersState::empty()),
+            Some(GizmoMode::Translate)
+        );
+        assert_eq!(
+            GizmoMode::from_modifiers(ModifiersState::SHIFT),
+            Some(GizmoMode::Rotate)
+        );
+        assert_eq!(
+            GizmoMode::from_modifiers(ModifiersState::SHIFT | ModifiersState::ALT),
+            Some(GizmoMode::Scale)
+        );
+        assert_eq!(GizmoMode::from_modifiers(ModifiersState::CONTROL), None);
+    }
+
+    #[test]
+    fn axis_drag_follows_the_cursor() {
+        let start = ray_to(Vec3::new(0.5, 0.0, 0.0));
+        let current = ray_to(Vec3::new(2.0, 0.0, 0.0));
+        let delta = axis_drag_delta(Vec3::ZERO, Vec3::X, &start, &current).unwrap();
+        assert!((delta - 1.5).abs() < 1e-4, "{delta}");
+        // Along the view direction the axis is a point on screen
+        assert_eq!(
+            closest_axis_parameter(&ray_to(Vec3::ZERO), Vec3::ZERO, Vec3::Z),
+            None
+        );
+    }
+
+    #[test]
+    fn rotation_and_scale_drags() {
+        let angle =
+            rotation_drag_angle(Vec3::ZERO, Vec3::Z, &ray_to(Vec3::X), &ray_to(Vec3::Y)).unwrap();
+        assert!(
+            (angle - std::f32::consts::FRAC_PI_2).abs() < 1e-4,
+            "{angle}"
+        );
+        let factor = scale_drag_factor(
+            Vec3::ZERO,
+            Vec3::X,
+            &ray_to(Vec3::X),
+            &ray_to(Vec3::new(2.0, 0.0, 0.0)),
+        )
+        .unwrap();
+        assert!((factor - 2.0).abs() < 1e-4, "{factor}");
+    }
+
+    #[test]
+    fn drag_moves_the_target_by_snapped_steps() {
+        let target = Mat4::from_translation(Vec3::new(1.0, 0.0, 0.0));
+        let mut gizmo = Gizmo {
+            snapping: GizmoSnapping {
+                translate: 0.5,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(!gizmo.begin_drag(&ray_to(Vec3::new(1.5, 1.0, 0.0)), &target, 1.0));
+        assert!(gizmo.begin_drag(&ray_to(Vec3::new(1.5, 0.0, 0.0)), &target, 1.0));
+        let moved = gizmo.drag(&ray_to(Vec3::new(2.2, 0.0, 0.0))).unwrap();
+        assert_close(moved.w_axis.truncate(), Vec3::new(1.5, 0.0, 0.0));
+        gizmo.end_drag();
+        assert!(!gizmo.is_dragging());
+        assert_eq!(gizmo.drag(&ray_to(Vec3::ZERO)), None);
+    }
+
+    #[test]
+    fn rotate_and_scale_around_the_target() {
+        let target = Mat4::from_translation(Vec3::new(1.0, 0.0, 0.0));
+        let mut gizmo = Gizmo {
+            mode: GizmoMode::Rotate,
+            ..Default::default()
+        };
+        assert!(gizmo.begin_drag(&ray_to(Vec3::new(2.0, 0.0, 0.0)), &target, 1.0));
+        let rotated = gizmo.drag(&ray_to(Vec3::new(1.0, 1.0, 0.0))).unwrap();
+        assert_close(rotated.w_axis.truncate(), Vec3::new(1.0, 0.0, 0.0));
+        assert_close(rotated.transform_vector3(Vec3::X), Vec3::Y);
+
+        gizmo.mode = GizmoMode::Scale;
+        assert!(gizmo.begin_drag(&ray_to(Vec3::new(1.5, 0.0, 0.0)), &target, 1.0));
+        let scaled = gizmo.drag(&ray_to(Vec3::new(2.5, 0.0, 0.0))).unwrap();
+        assert_close(scaled.w_axis.truncate(), Vec3::new(1.0, 0.0, 0.0));
+        assert_close(
+            scaled.transform_vector3(Vec3::ONE),
+            Vec3::new(3.0, 1.0, 1.0),
+        );
+    }
+}
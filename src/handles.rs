@@ -0,0 +1,51 @@
+//! Opaque identifiers of renderer owned resources, the Vulkan objects behind them stay inside the crate.
+
+macro_rules! handle {
+    ($(#[$doc:meta])* $name:ident) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name {
+            index: u32,
+            generation: u32,
+        }
+
+        impl $name {
+            // Materials and render targets have no registry yet
+            #[allow(dead_code)]
+            pub(crate) fn new(index: usize, generation: u32) -> Self {
+                Self {
+                    index: index as u32,
+                    generation,
+                }
+            }
+
+            /// Slot of the resource in its window's registry, reused once the resource is removed.
+            pub fn index(self) -> usize {
+                self.index as usize
+            }
+
+            /// Tells apart the resources that held the same slot, a stale handle resolves to nothing.
+            pub fn generation(self) -> u32 {
+                self.generation
+            }
+        }
+    };
+}
+
+handle!(
+    /// A mesh drawn with the perspective camera.
+    MeshHandle
+);
+handle!(
+    /// A texture held by a [`crate::assets::TextureLibrary`].
+    TextureId
+);
+handle!(
+    /// A material, reserved for a material registry. Materials are plain values for now, see
+    /// [`crate::material::Material`].
+    MaterialId
+);
+handle!(
+    /// Offscreen color and depth target.
+    RenderTargetId
+);
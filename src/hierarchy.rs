@@ -0,0 +1,131 @@
+//! Parent links between meshes, resolved into world transforms once per frame before drawing.
+
+use glam::Mat4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Visit {
+    Pending,
+    Walking,
+    Resolved,
+}
+
+/// World transforms of every node and the nodes whose parent link was ignored.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResolvedTransforms {
+    pub world: Vec<Mat4>,
+    /// One node per cycle, resolved as a root so the rest of the cycle still has a transform.
+    pub cycles: Vec<usize>,
+}
+
+/// Multiply each local transform by the world transform of its parent, parents first.
+/// A parent out of range makes the node a root. Every node is visited once, deep chains included.
+pub fn resolve_world_transforms(locals: &[Mat4], parents: &[Option<usize>]) -> ResolvedTransforms {
+    let count = locals.len();
+    let parent = |node: usize| {
+        parents
+            .get(node)
+            .copied()
+            .flatten()
+            .filter(|&parent| parent < count)
+    };
+    let mut visits = vec![Visit::Pending; count];
+    let mut resolved = ResolvedTransforms {
+        world: locals.to_vec(),
+        cycles: Vec::new(),
+    };
+    let mut chain = Vec::new();
+    for start in 0..count {
+        let mut node = start;
+        loop {
+            match visits[node] {
+                Visit::Resolved => break,
+                Visit::Walking => {
+                    // Back on the chain being walked, cut the cycle here
+                    visits[node] = Visit::Resolved;
+                    resolved.cycles.push(node);
+                    break;
+                }
+                Visit::Pending => {
+                    visits[node] = Visit::Walking;
+                    chain.push(node);
+                    match parent(node) {
+                        Some(next) => node = next,
+                        None => break,
+                    }
+                }
+            }
+        }
+        // Closest to the root last, resolve it first
+        while let Some(node) = chain.pop() {
+            if visits[node] == Visit::Resolved {
+                continue;
+            }
+            if let Some(parent) = parent(node) {
+                resolved.world[node] = resolved.world[parent] * locals[node];
+            }
+            visits[node] = Visit::Resolved;
+        }
+    }
+    resolved
+}
+
+/// Whether making `parent` the parent of `child` would close a cycle, `child` being an ancestor
+/// of `parent` or `parent` itself.
+pub fn creates_cycle(parents: &[Option<usize>], child: usize, parent: usize) -> bool {
+    let mut node = Some(parent);
+    // A cycle already present among the ancestors ends the walk after every node was seen once
+    for _ in 0..=parents.len() {
+        match node {
+            Some(node) if node == child => return true,
+            Some(ancestor) => node = parents.get(ancestor).copied().flatten(),
+            None => return false,
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec3;
+
+    #[test]
+    fn children_listed_first_resolve_through_their_parents() {
+        // A moon around a planet around a sun
+        let sun = Mat4::from_rotation_y(0.5);
+        let planet = Mat4::from_translation(Vec3::new(10.0, 0.0, 0.0));
+        let moon = Mat4::from_translation(Vec3::new(2.0, 0.0, 0.0));
+        let resolved = resolve_world_transforms(&[moon, planet, sun], &[Some(1), Some(2), None]);
+        assert!(resolved.cycles.is_empty(), "{resolved:?}");
+        let expected = [sun * planet * moon, sun * planet, sun];
+        for (world, expected) in resolved.world.iter().zip(expected) {
+            assert!(
+                world.abs_diff_eq(expected, 1e-5),
+                "{world} is not {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn cycles_are_cut_and_detected() {
+        // 0 -> 1 -> 2 -> 0 and 3 hanging from the cycle, every node still gets a transform
+        let step = Mat4::from_translation(Vec3::X);
+        let resolved = resolve_world_transforms(&[step; 4], &[Some(1), Some(2), Some(0), Some(1)]);
+        assert_eq!(resolved.cycles.len(), 1, "{:?}", resolved.cycles);
+        assert_eq!(resolved.world.len(), 4);
+
+        let parents = [Some(1), Some(2), None];
+        assert!(creates_cycle(&parents, 2, 0));
+        assert!(creates_cycle(&parents, 1, 1));
+        assert!(!creates_cycle(&parents, 0, 2));
+    }
+
+    #[test]
+    fn deep_chains_are_walked_without_recursion() {
+        let depth: usize = 100_000;
+        let parents: Vec<Option<usize>> = (0..depth).map(|i| i.checked_sub(1)).collect();
+        let resolved = resolve_world_transforms(&vec![Mat4::IDENTITY; depth], &parents);
+        assert_eq!(resolved.world.len(), depth);
+        assert!(resolved.cycles.is_empty());
+    }
+}
@@ -0,0 +1,206 @@
+//! Files polled for changes on a thread of their own, decoded there so the render thread only
+//! uploads. Modification times are compared, no platform watcher is needed.
+use crate::error::PulsarError;
+use log::info;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, SystemTime},
+};
+
+/// How often the watched files are checked.
+pub const HOT_RELOAD_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Watched paths and the modification time they were last decoded at.
+type Watched = Arc<Mutex<HashMap<PathBuf, Option<SystemTime>>>>;
+
+/// Decodes the watched files again whenever they change, stops polling when dropped.
+pub struct FileWatcher<T> {
+    watched: Watched,
+    stop: Arc<AtomicBool>,
+    changes: Receiver<(PathBuf, Result<T, PulsarError>)>,
+}
+
+impl<T: Send + 'static> FileWatcher<T> {
+    /// Poll every `interval`, running `decode` on each changed file. Nothing is polled until a
+    /// path is watched.
+    pub fn new(
+        interval: Duration,
+        decode: impl Fn(&Path) -> Result<T, PulsarError> + Send + 'static,
+    ) -> Self {
+        let watched: Watched = Arc::default();
+        let stop = Arc::new(AtomicBool::new(false));
+        let (sender, changes) = mpsc::channel();
+        let thread_watched = watched.clone();
+        let thread_stop = stop.clone();
+        thread::Builder::new()
+            .name("pulsar-hot-reload".to_string())
+            .spawn(move || {
+                while !thread_stop.load(Ordering::Relaxed) {
+                    thread::sleep(interval);
+                    // Decoded outside the lock, watching a path never waits on a decode
+                    let changed: Vec<PathBuf> = thread_watched
+                        .lock()
+                        .unwrap()
+                        .iter_mut()
+                        .filter_map(|(path, last_modified)| {
+                            let modified = modified(path);
+                            (modified != *last_modified).then(|| {
+                                *last_modified = modified;
+                                path.clone()
+                            })
+                        })
+                        .collect();
+                    for path in changed {
+                        info!("{} changed, reloading", path.display());
+                        let decoded = decode(&path);
+                        if sender.send((path, decoded)).is_err() {
+                            return;
+                        }
+                    }
+                }
+            })
+            .expect("Failed to spawn the hot reload thread");
+        Self {
+            watched,
+            stop,
+            changes,
+        }
+    }
+
+    /// Decode `path` again once it changes from now on.
+    pub fn watch(&self, path: PathBuf) {
+        let modified = modified(&path);
+        self.watched.lock().unwrap().insert(path, modified);
+    }
+
+    pub fn unwatch(&self, path: &Path) {
+        self.watched.lock().unwrap().remove(path);
+    }
+
+    pub fn is_watched(&self, path: &Path) -> bool {
+        self.watched.lock().unwrap().contains_key(path)
+    }
+
+    /// Files decoded since the last call, the latest decode of each, without blocking. Changes
+    /// of a path no longer watched are dropped.
+    pub fn changes(&self) -> Vec<(PathBuf, Result<T, PulsarError>)> {
+        let mut latest: Vec<(PathBuf, Result<T, PulsarError>)> = Vec::new();
+        for (path, decoded) in self.changes.try_iter() {
+            latest.retain(|(changed, _)| *changed != path);
+            latest.push((path, decoded));
+        }
+        latest.retain(|(path, _)| self.is_watched(path));
+        latest
+    }
+}
+
+impl<T> Drop for FileWatcher<T> {
+    fn drop(&mut self) {
+        // The thread sees it after its current poll, not waited on
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs::File, io::Write, time::Instant};
+
+    const POLL: Duration = Duration::from_millis(10);
+    /// How long a change may take to come back decoded.
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Text files standing in for images: the decode fails unless the file holds a number.
+    fn decode(path: &Path) -> Result<u32, PulsarError> {
+        let error = |reason: String| PulsarError::TextureLoad {
+            path: path.to_path_buf(),
+            reason,
+        };
+        let text = std::fs::read_to_string(path).map_err(|err| error(err.to_string()))?;
+        text.trim()
+            .parse()
+            .map_err(|_| error(format!("{text:?} is not a number")))
+    }
+
+    /// Write `contents` and move the modification time forward, coarse file systems included.
+    fn write(path: &Path, contents: &str, version: u64) {
+        let mut file = File::create(path).unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000 + version))
+            .unwrap();
+    }
+
+    /// Changes of the watcher until one arrives or the timeout.
+    fn wait_changes(watcher: &FileWatcher<u32>) -> Vec<(PathBuf, Result<u32, PulsarError>)> {
+        let start = Instant::now();
+        loop {
+            let changes = watcher.changes();
+            if !changes.is_empty() || start.elapsed() > TIMEOUT {
+                return changes;
+            }
+            thread::sleep(POLL);
+        }
+    }
+
+    #[test]
+    fn edited_broken_and_unwatched_files() {
+        let directory = std::env::temp_dir();
+        let first = directory.join(format!("pulsar_hot_reload_{}_1.txt", std::process::id()));
+        let second = directory.join(format!("pulsar_hot_reload_{}_2.txt", std::process::id()));
+        write(&first, "1", 0);
+        write(&second, "10", 0);
+        let watcher = FileWatcher::new(POLL, decode);
+        watcher.watch(first.clone());
+        watcher.watch(second.clone());
+
+        // Unchanged files are not decoded
+        thread::sleep(POLL * 5);
+        assert!(watcher.changes().is_empty());
+
+        write(&first, "2", 1);
+        let changes = wait_changes(&watcher);
+        assert!(
+            matches!(changes.as_slice(), [(path, Ok(2))] if *path == first),
+            "{changes:?}"
+        );
+
+        // A half written file fails alone, the next save decodes
+        write(&second, "not a number", 1);
+        let changes = wait_changes(&watcher);
+        assert!(
+            matches!(
+                changes.as_slice(),
+                [(path, Err(PulsarError::TextureLoad { .. }))] if *path == second
+            ),
+            "{changes:?}"
+        );
+        write(&second, "11", 2);
+        let changes = wait_changes(&watcher);
+        assert!(
+            matches!(changes.as_slice(), [(path, Ok(11))] if *path == second),
+            "{changes:?}"
+        );
+
+        watcher.unwatch(&first);
+        assert!(!watcher.is_watched(&first));
+        write(&first, "3", 2);
+        thread::sleep(POLL * 10);
+        assert!(watcher.changes().is_empty());
+
+        std::fs::remove_file(&first).unwrap();
+        std::fs::remove_file(&second).unwrap();
+    }
+}
@@ -1,11 +1,143 @@
-use std::sync::atomic::{AtomicBool, Ordering};
+use crate::{
+    background::{BackgroundMode, BACKGROUND_USER_FLOATS},
+    batching::MeshBatch,
+    block_compression::CompressedTexture,
+    camera::{CameraController, Ortho2DController},
+    compute::VertexDeformer,
+    custom_pass::{CustomPass, CustomPassSlot},
+    debug_lines::DebugLines,
+    display::DisplayEnvironment,
+    error::PulsarError,
+    failure::FailureScreen,
+    flight_recorder::{DumpReason, FlightRecorder},
+    handles::MeshHandle,
+    inset::InsetView,
+    instancing::Instances,
+    material::Material,
+    model::{Mesh, MeshHandles, MeshSpace, MeshUpdate},
+    present_health::{PresentDowngrade, PresentModePreference},
+    residency::ResidencyPriority,
+    screenshot::ScreenshotRequest,
+    skinning::AnimationPlayer,
+    texture::{CubemapSource, SamplerDesc, TextureArrayDesc, TextureUpdate},
+    thumbnail::{Thumbnail, ThumbnailCache, ThumbnailTarget},
+    time_control::TimeControl,
+    update_queue::{Backpressure, UpdateCounters, UpdateQueue},
+    watchdog::Heartbeat,
+};
+use ash::vk;
+use glam::Vec2;
+use log::{error, info};
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+        Arc, Condvar, Mutex,
+    },
+};
+use winit::{event::MouseButton, keyboard::ModifiersState};
+
+/// A mesh posted for registration under its reserved handle, with its instances when instanced.
+pub type MeshAddition = (MeshHandle, Mesh, MeshSpace, Option<Instances>);
 
 pub struct EventStates {
-    // mouse_buttons: [AtomicBool; 3], // Assuming 3 buttons: left, right, middle
-    // mouse_pos_x: AtomicU32,
-    // mouse_pos_y: AtomicU32,
+    pub mouse_buttons: [AtomicBool; 3], // left, right, middle
+    /// f32 bits, physical pixels.
+    pub mouse_pos_x: AtomicU32,
+    pub mouse_pos_y: AtomicU32,
+    pub cursor_inside: AtomicBool,
+    /// `ModifiersState` bits.
+    pub modifiers: AtomicU32,
     // keyboard_keys: [AtomicBool; 256], // Assuming 256 possible key codes
     pub exiting: AtomicBool,
+    /// Bumped when the window closes, loads started for an older generation are cancelled.
+    pub generation: AtomicU64,
+    pub save_replay: AtomicBool,
+    /// The render thread presented its first frame.
+    pub first_frame_presented: AtomicBool,
+    /// Latest display environment not yet applied by the render thread.
+    pub display: Mutex<Option<DisplayEnvironment>>,
+    /// Materials whose pipeline should be built ahead of their first draw.
+    pub precompile: Mutex<Vec<Material>>,
+    /// Mesh selected by clicking it and its user id, `None` when nothing is selected.
+    pub selection: Mutex<Option<(MeshHandle, u64)>>,
+    /// Handles of the window's meshes, reserved when a mesh is posted.
+    pub(crate) mesh_handles: Arc<MeshHandles>,
+    /// Meshes to register before the next frame, with their instances when they are instanced.
+    pub mesh_additions: Mutex<Vec<MeshAddition>>,
+    /// Batches to register before the next frame.
+    pub batch_additions: Mutex<Vec<(MeshHandle, MeshBatch, MeshSpace)>>,
+    /// Skinned meshes to register before the next frame, with the player posing them.
+    pub skinned_additions: Mutex<Vec<(MeshHandle, Mesh, AnimationPlayer)>>,
+    /// Meshes to register before the next frame, with the compute shader deforming them.
+    pub deformed_additions: Mutex<Vec<(MeshHandle, Mesh, VertexDeformer)>>,
+    /// f32 bits, zoom steps of the 2D world camera not yet applied, see `Ortho2DController`.
+    pub zoom_2d: AtomicU32,
+    /// Replaces the render thread's 2D world controller.
+    pub ortho_2d: Mutex<Option<Ortho2DController>>,
+    /// f32 bits, scroll wheel lines the perspective camera did not dolly yet, see `CameraController`.
+    pub dolly: AtomicU32,
+    /// Replaces the render thread's perspective camera controller.
+    pub camera_controller: Mutex<Option<CameraController>>,
+    /// Replaces the scale and pause state of the simulation clock.
+    pub time_control: Mutex<Option<TimeControl>>,
+    /// Ticks to run while paused, not yet queued on the simulation clock.
+    pub time_steps: AtomicU32,
+    /// Custom passes to install or, when `None`, remove before the next frame.
+    pub custom_passes: Mutex<Vec<(CustomPassSlot, Option<CustomPass>)>>,
+    /// Replaces the debug lines drawn every frame.
+    pub debug_lines: Mutex<Option<DebugLines>>,
+    /// Replaces the background from the next frame on.
+    pub background: Mutex<Option<BackgroundMode>>,
+    /// Replaces the application's block of the background shader.
+    pub background_uniforms: Mutex<Option<[f32; BACKGROUND_USER_FLOATS]>>,
+    /// Replaces the inset views drawn every frame.
+    pub insets: Mutex<Option<Vec<InsetView>>>,
+    /// Mesh contents to copy before the next frame, in the order they were posted.
+    pub mesh_updates: Mutex<UpdateQueue>,
+    /// Signaled whenever the render thread drains `mesh_updates`, wakes the posts waiting for
+    /// room under [`Backpressure::Block`].
+    pub mesh_updates_drained: Condvar,
+    /// Meshes to free before the next frame.
+    pub mesh_removals: Mutex<Vec<MeshHandle>>,
+    /// Texture contents to copy before the next frame, in the order they were posted.
+    pub texture_updates: Mutex<Vec<TextureUpdate>>,
+    /// Image file replacing the texture before the next frame, and how to sample it.
+    pub texture_file: Mutex<Option<(PathBuf, SamplerDesc)>>,
+    /// Block compressed texture replacing the texture before the next frame, and how to sample it.
+    pub compressed_texture: Mutex<Option<(CompressedTexture, SamplerDesc)>>,
+    /// Texture array replacing the previous one before the next frame.
+    pub texture_array: Mutex<Option<TextureArrayDesc>>,
+    /// RGBA8 layers to copy into the texture array before the next frame, in the order pushed.
+    pub texture_layers: Mutex<Vec<Vec<u8>>>,
+    /// Cubemap replacing the environment before the next frame, and how to sample it, or `None`
+    /// to go back to the flat environment.
+    pub cubemap: Mutex<Option<Option<(CubemapSource, SamplerDesc)>>>,
+    /// Replaces the intensity of the environment's irradiance.
+    pub environment_intensity: Mutex<Option<f32>>,
+    /// Priority of the default material's texture, see `Application::set_texture_residency`.
+    pub texture_residency: Mutex<Option<ResidencyPriority>>,
+    /// Offscreen captures to render after the next frame.
+    pub screenshots: Mutex<Vec<ScreenshotRequest>>,
+    /// Thumbnails handed out to the application, rendered a few per frame.
+    pub thumbnails: Mutex<ThumbnailCache>,
+    /// Presentation fell back to FIFO, not yet reported to the application.
+    pub present_downgrade: Mutex<Option<PresentDowngrade>>,
+    /// Preference to recreate the swapchain with before the next frame.
+    pub present_preference: Mutex<Option<PresentModePreference>>,
+    /// Preference of the current swapchain and the mode it resolved to, `None` until the render
+    /// thread creates one.
+    pub present_mode: Mutex<Option<(PresentModePreference, vk::PresentModeKHR)>>,
+    /// Distance the depth of field is focused at, `None` while it is disabled.
+    pub focus_distance: Mutex<Option<f32>>,
+    /// Failure drawn in place of the scene, and the retry asked for from the event loop.
+    pub failure_screen: Mutex<FailureScreen>,
+    /// Render thread progress, watched from the event loop.
+    pub heartbeat: Heartbeat,
+    /// Recent render decisions, dumped when the render thread stalls or panics.
+    pub decisions: Arc<FlightRecorder>,
+    /// The window's device while its renderer holds it, see `Application::raw_device`.
+    pub(crate) raw_device: Mutex<Option<ash::Device>>,
 }
 
 impl EventStates {
@@ -18,20 +150,583 @@ impl EventStates {
     pub fn opening(&self) {
         self.exiting.store(false, Ordering::Relaxed);
     }
+
+    /// Returns whether this was the first frame presented.
+    #[inline]
+    pub fn mark_first_frame_presented(&self) -> bool {
+        !self.first_frame_presented.swap(true, Ordering::AcqRel)
+    }
+
+    #[inline]
+    pub fn first_frame_presented(&self) -> bool {
+        self.first_frame_presented.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// The window is closing: cancel the loads started for it and free the meshes, batches and
+    /// updates queued but not yet registered.
+    pub fn retire(&self) {
+        // Under the additions lock, a load delivering concurrently sees either generation whole
+        let mut mesh_additions = self.mesh_additions.lock().unwrap();
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        mesh_additions.clear();
+        drop(mesh_additions);
+        self.batch_additions.lock().unwrap().clear();
+        self.skinned_additions.lock().unwrap().clear();
+        self.deformed_additions.lock().unwrap().clear();
+        self.mesh_updates.lock().unwrap().close();
+        self.mesh_updates_drained.notify_all();
+        self.texture_updates.lock().unwrap().clear();
+        self.texture_layers.lock().unwrap().clear();
+    }
+
+    #[inline]
+    pub fn request_replay_save(&self) {
+        self.save_replay.store(true, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn take_replay_save_request(&self) -> bool {
+        self.save_replay.swap(false, Ordering::Relaxed)
+    }
+
+    /// Replaces any environment the render thread did not pick up yet.
+    #[inline]
+    pub fn post_display_environment(&self, display: DisplayEnvironment) {
+        *self.display.lock().unwrap() = Some(display);
+    }
+
+    #[inline]
+    pub fn take_display_environment(&self) -> Option<DisplayEnvironment> {
+        self.display.lock().unwrap().take()
+    }
+
+    #[inline]
+    pub fn request_precompile(&self, material: Material) {
+        self.precompile.lock().unwrap().push(material);
+    }
+
+    #[inline]
+    pub fn take_precompile_requests(&self) -> Vec<Material> {
+        std::mem::take(&mut *self.precompile.lock().unwrap())
+    }
+
+    #[inline]
+    pub fn request_screenshot(&self, request: ScreenshotRequest) {
+        self.screenshots.lock().unwrap().push(request);
+    }
+
+    #[inline]
+    pub fn take_screenshot_requests(&self) -> Vec<ScreenshotRequest> {
+        std::mem::take(&mut *self.screenshots.lock().unwrap())
+    }
+
+    /// Write the flight recorder to the diagnostics directory, failures are only logged.
+    pub fn dump_decisions(&self, reason: &DumpReason) {
+        match self.decisions.dump_to_file(reason) {
+            Ok(path) => info!("Render decisions ({reason}) saved to {}", path.display()),
+            Err(err) => error!("Failed to save the render decisions: {err}"),
+        }
+    }
+
+    /// Queue `mesh` under a new handle, valid for updates and removals right away.
+    pub fn request_mesh_addition(
+        &self,
+        mesh: Mesh,
+        space: MeshSpace,
+        instances: Option<Instances>,
+    ) -> MeshHandle {
+        let handle = self.mesh_handles.reserve();
+        self.mesh_additions
+            .lock()
+            .unwrap()
+            .push((handle, mesh, space, instances));
+        handle
+    }
+
+    /// Queue `mesh` unless the window was retired since `generation`, returns its handle when it
+    /// was queued.
+    pub fn request_mesh_addition_for(
+        &self,
+        generation: u64,
+        mesh: Mesh,
+        space: MeshSpace,
+    ) -> Option<MeshHandle> {
+        let mut mesh_additions = self.mesh_additions.lock().unwrap();
+        if self.generation() != generation {
+            return None;
+        }
+        let handle = self.mesh_handles.reserve();
+        mesh_additions.push((handle, mesh, space, None));
+        Some(handle)
+    }
+
+    #[inline]
+    pub fn take_mesh_additions(&self) -> Vec<MeshAddition> {
+        std::mem::take(&mut *self.mesh_additions.lock().unwrap())
+    }
+
+    pub fn request_skinned_addition(&self, mesh: Mesh, player: AnimationPlayer) -> MeshHandle {
+        let handle = self.mesh_handles.reserve();
+        self.skinned_additions
+            .lock()
+            .unwrap()
+            .push((handle, mesh, player));
+        handle
+    }
+
+    #[inline]
+    pub fn take_skinned_additions(&self) -> Vec<(MeshHandle, Mesh, AnimationPlayer)> {
+        std::mem::take(&mut *self.skinned_additions.lock().unwrap())
+    }
+
+    pub fn request_deformed_addition(&self, mesh: Mesh, deformer: VertexDeformer) -> MeshHandle {
+        let handle = self.mesh_handles.reserve();
+        self.deformed_additions
+            .lock()
+            .unwrap()
+            .push((handle, mesh, deformer));
+        handle
+    }
+
+    #[inline]
+    pub fn take_deformed_additions(&self) -> Vec<(MeshHandle, Mesh, VertexDeformer)> {
+        std::mem::take(&mut *self.deformed_additions.lock().unwrap())
+    }
+
+    pub fn request_batch_addition(&self, batch: MeshBatch, space: MeshSpace) -> MeshHandle {
+        let handle = self.mesh_handles.reserve();
+        self.batch_additions
+            .lock()
+            .unwrap()
+            .push((handle, batch, space));
+        handle
+    }
+
+    #[inline]
+    pub fn take_batch_additions(&self) -> Vec<(MeshHandle, MeshBatch, MeshSpace)> {
+        std::mem::take(&mut *self.batch_additions.lock().unwrap())
+    }
+
+    /// Queue `update`, waiting for the next drain or failing when the queue is full depending on
+    /// its [`Backpressure`].
+    pub fn post_mesh_update(
+        &self,
+        mesh: MeshHandle,
+        update: MeshUpdate,
+    ) -> Result<(), PulsarError> {
+        let mut queue = self.mesh_updates.lock().unwrap();
+        if queue.backpressure == Backpressure::Block {
+            while !queue.has_room_for(mesh, &update) {
+                queue = self.mesh_updates_drained.wait(queue).unwrap();
+            }
+        }
+        queue.try_post(mesh, update)
+    }
+
+    /// The queued updates in order, and the queue's counters including them.
+    pub fn take_mesh_updates(&self) -> (Vec<(MeshHandle, MeshUpdate)>, UpdateCounters) {
+        let mut queue = self.mesh_updates.lock().unwrap();
+        let updates = queue.drain();
+        let counters = queue.counters;
+        drop(queue);
+        self.mesh_updates_drained.notify_all();
+        (updates, counters)
+    }
+
+    #[inline]
+    pub fn request_mesh_removal(&self, mesh: MeshHandle) {
+        self.mesh_removals.lock().unwrap().push(mesh);
+    }
+
+    #[inline]
+    pub fn take_mesh_removals(&self) -> Vec<MeshHandle> {
+        std::mem::take(&mut *self.mesh_removals.lock().unwrap())
+    }
+
+    #[inline]
+    pub fn post_texture_update(&self, update: TextureUpdate) {
+        self.texture_updates.lock().unwrap().push(update);
+    }
+
+    #[inline]
+    pub fn take_texture_updates(&self) -> Vec<TextureUpdate> {
+        std::mem::take(&mut *self.texture_updates.lock().unwrap())
+    }
+
+    #[inline]
+    pub fn request_texture_file(&self, path: PathBuf, sampler: SamplerDesc) {
+        *self.texture_file.lock().unwrap() = Some((path, sampler));
+    }
+
+    /// Layers pushed before are dropped with the previous array.
+    #[inline]
+    pub fn request_texture_array(&self, desc: TextureArrayDesc) {
+        let mut texture_array = self.texture_array.lock().unwrap();
+        self.texture_layers.lock().unwrap().clear();
+        *texture_array = Some(desc);
+    }
+
+    /// The array to create, if any, and the layers pushed after it was requested, taken together
+    /// so layers never land in an array they were not pushed for.
+    #[inline]
+    pub fn take_texture_array(&self) -> (Option<TextureArrayDesc>, Vec<Vec<u8>>) {
+        let mut texture_array = self.texture_array.lock().unwrap();
+        let layers = std::mem::take(&mut *self.texture_layers.lock().unwrap());
+        (texture_array.take(), layers)
+    }
+
+    #[inline]
+    pub fn push_texture_layer(&self, data: Vec<u8>) {
+        self.texture_layers.lock().unwrap().push(data);
+    }
+
+    #[inline]
+    pub fn request_cubemap(&self, cubemap: Option<(CubemapSource, SamplerDesc)>) {
+        *self.cubemap.lock().unwrap() = Some(cubemap);
+    }
+
+    #[inline]
+    pub fn take_cubemap(&self) -> Option<Option<(CubemapSource, SamplerDesc)>> {
+        self.cubemap.lock().unwrap().take()
+    }
+
+    #[inline]
+    pub fn set_texture_residency(&self, priority: ResidencyPriority) {
+        *self.texture_residency.lock().unwrap() = Some(priority);
+    }
+
+    #[inline]
+    pub fn take_texture_residency(&self) -> Option<ResidencyPriority> {
+        self.texture_residency.lock().unwrap().take()
+    }
+
+    #[inline]
+    pub fn set_environment_intensity(&self, intensity: f32) {
+        *self.environment_intensity.lock().unwrap() = Some(intensity);
+    }
+
+    #[inline]
+    pub fn take_environment_intensity(&self) -> Option<f32> {
+        self.environment_intensity.lock().unwrap().take()
+    }
+
+    /// Whether a failure is drawn in place of the scene.
+    #[inline]
+    pub fn failing(&self) -> bool {
+        self.failure_screen.lock().unwrap().failure().is_some()
+    }
+
+    #[inline]
+    pub fn take_texture_file(&self) -> Option<(PathBuf, SamplerDesc)> {
+        self.texture_file.lock().unwrap().take()
+    }
+
+    #[inline]
+    pub fn request_compressed_texture(&self, texture: CompressedTexture, sampler: SamplerDesc) {
+        *self.compressed_texture.lock().unwrap() = Some((texture, sampler));
+    }
+
+    #[inline]
+    pub fn take_compressed_texture(&self) -> Option<(CompressedTexture, SamplerDesc)> {
+        self.compressed_texture.lock().unwrap().take()
+    }
+
+    #[inline]
+    pub fn request_thumbnail(&self, target: ThumbnailTarget, size: u32) -> Thumbnail {
+        self.thumbnails.lock().unwrap().request(target, size)
+    }
+
+    #[inline]
+    pub fn set_selection(&self, selection: Option<(MeshHandle, u64)>) {
+        *self.selection.lock().unwrap() = selection;
+    }
+
+    #[inline]
+    pub fn selection(&self) -> Option<(MeshHandle, u64)> {
+        *self.selection.lock().unwrap()
+    }
+
+    #[inline]
+    pub fn set_mouse_button(&self, button: MouseButton, pressed: bool) {
+        let index = match button {
+            MouseButton::Left => 0,
+            MouseButton::Right => 1,
+            MouseButton::Middle => 2,
+            _ => return,
+        };
+        self.mouse_buttons[index].store(pressed, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn mouse_button(&self, button: MouseButton) -> bool {
+        match button {
+            MouseButton::Left => self.mouse_buttons[0].load(Ordering::Relaxed),
+            MouseButton::Right => self.mouse_buttons[1].load(Ordering::Relaxed),
+            MouseButton::Middle => self.mouse_buttons[2].load(Ordering::Relaxed),
+            _ => false,
+        }
+    }
+
+    #[inline]
+    pub fn set_cursor_position(&self, position: Option<Vec2>) {
+        if let Some(position) = position {
+            self.mouse_pos_x
+                .store(position.x.to_bits(), Ordering::Relaxed);
+            self.mouse_pos_y
+                .store(position.y.to_bits(), Ordering::Relaxed);
+        }
+        self.cursor_inside
+            .store(position.is_some(), Ordering::Relaxed);
+    }
+
+    /// `None` while the cursor is outside the window.
+    #[inline]
+    pub fn cursor_position(&self) -> Option<Vec2> {
+        self.cursor_inside.load(Ordering::Relaxed).then(|| {
+            Vec2::new(
+                f32::from_bits(self.mouse_pos_x.load(Ordering::Relaxed)),
+                f32::from_bits(self.mouse_pos_y.load(Ordering::Relaxed)),
+            )
+        })
+    }
+
+    /// Zoom the 2D world camera by `exp(steps)` around the cursor, accumulated until the next frame.
+    #[inline]
+    pub fn add_zoom_2d(&self, steps: f32) {
+        let _ = self
+            .zoom_2d
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some((f32::from_bits(bits) + steps).to_bits())
+            });
+    }
+
+    #[inline]
+    pub fn take_zoom_2d(&self) -> f32 {
+        f32::from_bits(self.zoom_2d.swap(0.0f32.to_bits(), Ordering::Relaxed))
+    }
+
+    #[inline]
+    pub fn set_ortho_2d(&self, controller: Ortho2DController) {
+        *self.ortho_2d.lock().unwrap() = Some(controller);
+    }
+
+    #[inline]
+    pub fn take_ortho_2d(&self) -> Option<Ortho2DController> {
+        self.ortho_2d.lock().unwrap().take()
+    }
+
+    /// Dolly the perspective camera by `lines` toward the cursor, accumulated until the next frame.
+    #[inline]
+    pub fn add_dolly(&self, lines: f32) {
+        let _ = self
+            .dolly
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some((f32::from_bits(bits) + lines).to_bits())
+            });
+    }
+
+    #[inline]
+    pub fn take_dolly(&self) -> f32 {
+        f32::from_bits(self.dolly.swap(0.0f32.to_bits(), Ordering::Relaxed))
+    }
+
+    #[inline]
+    pub fn set_camera_controller(&self, controller: CameraController) {
+        *self.camera_controller.lock().unwrap() = Some(controller);
+    }
+
+    #[inline]
+    pub fn take_camera_controller(&self) -> Option<CameraController> {
+        self.camera_controller.lock().unwrap().take()
+    }
+
+    #[inline]
+    pub fn set_time_control(&self, control: TimeControl) {
+        *self.time_control.lock().unwrap() = Some(control);
+    }
+
+    #[inline]
+    pub fn take_time_control(&self) -> Option<TimeControl> {
+        self.time_control.lock().unwrap().take()
+    }
+
+    #[inline]
+    pub fn request_time_step(&self) {
+        self.time_steps.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn take_time_steps(&self) -> u32 {
+        self.time_steps.swap(0, Ordering::Relaxed)
+    }
+
+    #[inline]
+    pub fn set_custom_pass(&self, slot: CustomPassSlot, pass: Option<CustomPass>) {
+        self.custom_passes.lock().unwrap().push((slot, pass));
+    }
+
+    #[inline]
+    pub fn take_custom_passes(&self) -> Vec<(CustomPassSlot, Option<CustomPass>)> {
+        std::mem::take(&mut *self.custom_passes.lock().unwrap())
+    }
+
+    #[inline]
+    pub fn set_debug_lines(&self, lines: DebugLines) {
+        *self.debug_lines.lock().unwrap() = Some(lines);
+    }
+
+    #[inline]
+    pub fn take_debug_lines(&self) -> Option<DebugLines> {
+        self.debug_lines.lock().unwrap().take()
+    }
+
+    #[inline]
+    pub fn set_background(&self, mode: BackgroundMode) {
+        *self.background.lock().unwrap() = Some(mode);
+    }
+
+    #[inline]
+    pub fn take_background(&self) -> Option<BackgroundMode> {
+        self.background.lock().unwrap().take()
+    }
+
+    #[inline]
+    pub fn set_background_uniforms(&self, user: [f32; BACKGROUND_USER_FLOATS]) {
+        *self.background_uniforms.lock().unwrap() = Some(user);
+    }
+
+    #[inline]
+    pub fn take_background_uniforms(&self) -> Option<[f32; BACKGROUND_USER_FLOATS]> {
+        self.background_uniforms.lock().unwrap().take()
+    }
+
+    #[inline]
+    pub fn set_insets(&self, insets: Vec<InsetView>) {
+        *self.insets.lock().unwrap() = Some(insets);
+    }
+
+    #[inline]
+    pub fn take_insets(&self) -> Option<Vec<InsetView>> {
+        self.insets.lock().unwrap().take()
+    }
+
+    #[inline]
+    pub fn report_present_downgrade(&self, downgrade: PresentDowngrade) {
+        *self.present_downgrade.lock().unwrap() = Some(downgrade);
+    }
+
+    #[inline]
+    pub fn take_present_downgrade(&self) -> Option<PresentDowngrade> {
+        self.present_downgrade.lock().unwrap().take()
+    }
+
+    #[inline]
+    pub fn set_present_preference(&self, preference: PresentModePreference) {
+        *self.present_preference.lock().unwrap() = Some(preference);
+    }
+
+    #[inline]
+    pub fn take_present_preference(&self) -> Option<PresentModePreference> {
+        self.present_preference.lock().unwrap().take()
+    }
+
+    #[inline]
+    pub fn publish_present_mode(
+        &self,
+        preference: PresentModePreference,
+        mode: vk::PresentModeKHR,
+    ) {
+        *self.present_mode.lock().unwrap() = Some((preference, mode));
+    }
+
+    #[inline]
+    pub fn present_mode(&self) -> Option<(PresentModePreference, vk::PresentModeKHR)> {
+        *self.present_mode.lock().unwrap()
+    }
+
+    #[inline]
+    pub fn publish_focus_distance(&self, distance: Option<f32>) {
+        *self.focus_distance.lock().unwrap() = distance;
+    }
+
+    #[inline]
+    pub fn focus_distance(&self) -> Option<f32> {
+        *self.focus_distance.lock().unwrap()
+    }
+
+    #[inline]
+    pub fn set_modifiers(&self, modifiers: ModifiersState) {
+        self.modifiers.store(modifiers.bits(), Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn modifiers(&self) -> ModifiersState {
+        ModifiersState::from_bits_truncate(self.modifiers.load(Ordering::Relaxed))
+    }
 }
 
 impl Default for EventStates {
     fn default() -> Self {
         Self {
-            // mouse_buttons: [
-            //     AtomicBool::new(false),
-            //     AtomicBool::new(false),
-            //     AtomicBool::new(false),
-            // ],
-            // mouse_pos_x: AtomicU32::new(0),
-            // mouse_pos_y: AtomicU32::new(0),
+            mouse_buttons: [
+                AtomicBool::new(false),
+                AtomicBool::new(false),
+                AtomicBool::new(false),
+            ],
+            mouse_pos_x: AtomicU32::new(0),
+            mouse_pos_y: AtomicU32::new(0),
+            cursor_inside: AtomicBool::new(false),
+            modifiers: AtomicU32::new(0),
             // keyboard_keys: [0; 256].map(|_| AtomicBool::new(false)),
             exiting: AtomicBool::new(false),
+            generation: AtomicU64::new(0),
+            save_replay: AtomicBool::new(false),
+            first_frame_presented: AtomicBool::new(false),
+            display: Mutex::new(None),
+            precompile: Mutex::new(Vec::new()),
+            selection: Mutex::new(None),
+            mesh_handles: Arc::new(MeshHandles::default()),
+            mesh_additions: Mutex::new(Vec::new()),
+            batch_additions: Mutex::new(Vec::new()),
+            skinned_additions: Mutex::new(Vec::new()),
+            deformed_additions: Mutex::new(Vec::new()),
+            zoom_2d: AtomicU32::new(0.0f32.to_bits()),
+            ortho_2d: Mutex::new(None),
+            dolly: AtomicU32::new(0.0f32.to_bits()),
+            camera_controller: Mutex::new(None),
+            time_control: Mutex::new(None),
+            time_steps: AtomicU32::new(0),
+            custom_passes: Mutex::new(Vec::new()),
+            debug_lines: Mutex::new(None),
+            background: Mutex::new(None),
+            background_uniforms: Mutex::new(None),
+            insets: Mutex::new(None),
+            mesh_updates: Mutex::new(UpdateQueue::default()),
+            mesh_updates_drained: Condvar::new(),
+            mesh_removals: Mutex::new(Vec::new()),
+            texture_updates: Mutex::new(Vec::new()),
+            texture_file: Mutex::new(None),
+            compressed_texture: Mutex::new(None),
+            texture_array: Mutex::new(None),
+            texture_layers: Mutex::new(Vec::new()),
+            cubemap: Mutex::new(None),
+            environment_intensity: Mutex::new(None),
+            texture_residency: Mutex::new(None),
+            screenshots: Mutex::new(Vec::new()),
+            thumbnails: Mutex::new(ThumbnailCache::default()),
+            present_downgrade: Mutex::new(None),
+            present_preference: Mutex::new(None),
+            present_mode: Mutex::new(None),
+            focus_distance: Mutex::new(None),
+            failure_screen: Mutex::new(FailureScreen::default()),
+            heartbeat: Heartbeat::default(),
+            decisions: FlightRecorder::published(),
+            raw_device: Mutex::new(None),
         }
     }
 }
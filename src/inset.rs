@@ -0,0 +1,215 @@
+//! Picture in picture views: the scene drawn again from another camera into a corner of the
+//! window, a rear view mirror or a minimap, after the main view and under the UI.
+use glam::{Mat4, Vec2, Vec3};
+
+/// Layer mask of a mesh that was never given one, and of an inset showing everything.
+pub const ALL_LAYERS: u32 = u32::MAX;
+
+/// Corner of the scene viewport an inset is placed from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InsetAnchor {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+/// How large an inset is, resolved against the scene viewport every frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InsetSize {
+    /// Physical pixels.
+    Pixels(Vec2),
+    /// Share of the viewport's width and height.
+    Fraction(Vec2),
+    /// Square with sides a share of the viewport's shorter side, a minimap.
+    Square(f32),
+}
+
+/// Where an inset lies, anchored so it keeps its corner across resizes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InsetRect {
+    pub anchor: InsetAnchor,
+    /// Physical pixels between the anchored corner of the viewport and the inset.
+    pub margin: Vec2,
+    pub size: InsetSize,
+}
+
+impl InsetRect {
+    pub fn new(anchor: InsetAnchor, margin: Vec2, size: InsetSize) -> Self {
+        Self {
+            anchor,
+            margin,
+            size,
+        }
+    }
+
+    /// Top left corner and size in physical pixels within the viewport at `viewport_offset` of
+    /// `viewport_size`, letterbox bars excluded. Clamped inside it, `None` when nothing is left.
+    pub fn resolve(&self, viewport_offset: Vec2, viewport_size: Vec2) -> Option<(Vec2, Vec2)> {
+        let size = match self.size {
+            InsetSize::Pixels(size) => size,
+            InsetSize::Fraction(fraction) => viewport_size * fraction,
+            InsetSize::Square(fraction) => Vec2::splat(viewport_size.min_element() * fraction),
+        };
+        let size = size.round().clamp(Vec2::ZERO, viewport_size);
+        let far = viewport_size - size - self.margin;
+        let corner = match self.anchor {
+            InsetAnchor::TopLeft => self.margin,
+            InsetAnchor::TopRight => Vec2::new(far.x, self.margin.y),
+            InsetAnchor::BottomLeft => Vec2::new(self.margin.x, far.y),
+            InsetAnchor::BottomRight => far,
+        };
+        let corner = corner.round().clamp(Vec2::ZERO, viewport_size - size);
+        (size.x >= 1.0 && size.y >= 1.0).then_some((viewport_offset + corner, size))
+    }
+}
+
+/// Lens of an inset camera, its aspect ratio follows the inset's size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InsetProjection {
+    Perspective {
+        fov_y: f32,
+        near: f32,
+        far: f32,
+    },
+    /// `half_height` world units above and below the view center.
+    Orthographic {
+        half_height: f32,
+        near: f32,
+        far: f32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InsetCamera {
+    pub view: Mat4,
+    pub projection: InsetProjection,
+}
+
+impl InsetCamera {
+    /// At `eye` looking at `target`, `up` being the screen's up.
+    pub fn look_at(eye: Vec3, target: Vec3, up: Vec3, projection: InsetProjection) -> Self {
+        Self {
+            view: Mat4::look_at_rh(eye, target, up),
+            projection,
+        }
+    }
+
+    /// Projection times view for an inset `aspect_ratio` wide.
+    pub fn projection_view(&self, aspect_ratio: f32) -> Mat4 {
+        let projection = match self.projection {
+            InsetProjection::Perspective { fov_y, near, far } => {
+                Mat4::perspective_rh(fov_y, aspect_ratio, near, far)
+            }
+            InsetProjection::Orthographic {
+                half_height,
+                near,
+                far,
+            } => {
+                let half_width = half_height * aspect_ratio;
+                Mat4::orthographic_rh(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    near,
+                    far,
+                )
+            }
+        };
+        projection * self.view
+    }
+}
+
+/// The scene from `camera` in `rect`. Only the world meshes sharing a layer with `layer_mask`
+/// are drawn, without the gizmo, the reference grid and the debug lines.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InsetView {
+    pub rect: InsetRect,
+    pub camera: InsetCamera,
+    pub layer_mask: u32,
+    /// Clear the depth within the rect first, so the main view does not hide the inset's meshes.
+    pub clear_depth: bool,
+    /// Fill the rect with this color first, the main view shows through without one.
+    pub background: Option<[f32; 4]>,
+}
+
+impl InsetView {
+    /// Every layer, over a cleared depth and an opaque background.
+    pub fn new(rect: InsetRect, camera: InsetCamera) -> Self {
+        Self {
+            rect,
+            camera,
+            layer_mask: ALL_LAYERS,
+            clear_depth: true,
+            background: Some([0.0, 0.0, 0.0, 1.0]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec4Swizzles;
+
+    #[test]
+    fn anchored_rect_keeps_its_corner() {
+        let rect = InsetRect::new(
+            InsetAnchor::BottomRight,
+            Vec2::splat(16.0),
+            InsetSize::Square(0.3),
+        );
+        let cases = [
+            // Window, then after a resize, then letterboxed into 800 by 480
+            (
+                Vec2::ZERO,
+                Vec2::new(1280.0, 720.0),
+                Vec2::new(1048.0, 488.0),
+                216.0,
+            ),
+            (
+                Vec2::ZERO,
+                Vec2::new(800.0, 600.0),
+                Vec2::new(604.0, 404.0),
+                180.0,
+            ),
+            (
+                Vec2::new(0.0, 60.0),
+                Vec2::new(800.0, 480.0),
+                Vec2::new(640.0, 380.0),
+                144.0,
+            ),
+            // Margins larger than the window push the rect against its edge, not out of it
+            (Vec2::ZERO, Vec2::new(40.0, 20.0), Vec2::new(18.0, 0.0), 6.0),
+        ];
+        for (offset, size, corner, side) in cases {
+            assert_eq!(
+                rect.resolve(offset, size),
+                Some((corner, Vec2::splat(side))),
+                "in {size} at {offset}"
+            );
+        }
+        // Nothing is left to draw in a 1 pixel window
+        assert_eq!(rect.resolve(Vec2::ZERO, Vec2::ONE), None);
+    }
+
+    #[test]
+    fn top_down_camera_centers_its_target() {
+        let camera = InsetCamera::look_at(
+            Vec3::new(0.0, 30.0, 0.0),
+            Vec3::ZERO,
+            Vec3::NEG_Z,
+            InsetProjection::Orthographic {
+                half_height: 6.0,
+                near: 0.1,
+                far: 100.0,
+            },
+        );
+        let projection_view = camera.projection_view(1.0);
+        let center = projection_view * Vec3::ZERO.extend(1.0);
+        let right = projection_view * Vec3::X.extend(1.0);
+        assert!(center.xy().length() < 1e-5, "{center}");
+        assert!(right.x > 0.0 && right.y.abs() < 1e-5, "+X at {right}");
+    }
+}
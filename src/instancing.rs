@@ -0,0 +1,161 @@
+//! Per-instance transforms of meshes drawn many times with a single indexed draw.
+
+use crate::vulkan::{
+    device::AAADevice,
+    upload::{create_empty_buffer, write_mapped, AAAOwnedBuffer},
+};
+use ash::vk;
+use glam::Mat4;
+use std::{
+    mem,
+    sync::{Arc, Mutex},
+};
+
+/// Transforms of every copy of an instanced mesh, applied after the mesh's own transform.
+/// Shared with the render threads: clones write the same list, every window drawing it copies the
+/// changes before its next frame.
+#[derive(Debug, Clone, Default)]
+pub struct Instances {
+    shared: Arc<Mutex<InstanceList>>,
+}
+
+#[derive(Debug, Default)]
+struct InstanceList {
+    transforms: Vec<Mat4>,
+    /// Bumped on every change, instance buffers remember the last one they copied.
+    version: u64,
+}
+
+impl Instances {
+    pub fn new(transforms: Vec<Mat4>) -> Self {
+        Self {
+            shared: Arc::new(Mutex::new(InstanceList {
+                transforms,
+                version: 1,
+            })),
+        }
+    }
+
+    /// Replace every transform, the instance count follows the length of `transforms`.
+    pub fn set(&self, transforms: Vec<Mat4>) {
+        let mut list = self.shared.lock().unwrap();
+        list.transforms = transforms;
+        list.version += 1;
+    }
+
+    /// Edit the transforms in place, cheaper than [`Self::set`] when only a few move.
+    pub fn update(&self, edit: impl FnOnce(&mut Vec<Mat4>)) {
+        let mut list = self.shared.lock().unwrap();
+        edit(&mut list.transforms);
+        list.version += 1;
+    }
+
+    pub fn len(&self) -> usize {
+        self.shared.lock().unwrap().transforms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Current version and transforms when they changed after `version`.
+    pub(crate) fn changes_since(&self, version: u64) -> Option<(u64, Vec<Mat4>)> {
+        let list = self.shared.lock().unwrap();
+        (list.version != version).then(|| (list.version, list.transforms.clone()))
+    }
+}
+
+/// Host visible vertex buffer read at `vk::VertexInputRate::INSTANCE`, one `Mat4` per instance.
+#[derive(Debug)]
+pub(crate) struct InstanceBuffer {
+    pub source: Instances,
+    /// Version of `source` last written, zero before the first write.
+    pub version: u64,
+    pub buffer: vk::Buffer,
+    pub memory: vk::DeviceMemory,
+    /// Allocated bytes, grown to the next power of two when the instances outgrow it.
+    pub capacity: u64,
+    pub count: u32,
+}
+
+impl InstanceBuffer {
+    /// Bytes between two instances in the buffer.
+    pub const STRIDE: u32 = mem::size_of::<Mat4>() as u32;
+
+    /// Empty until the first [`Self::write`].
+    pub fn new(source: Instances) -> Self {
+        Self {
+            source,
+            version: 0,
+            buffer: vk::Buffer::null(),
+            memory: vk::DeviceMemory::null(),
+            capacity: 0,
+            count: 0,
+        }
+    }
+
+    /// Copy `transforms` of `version` through a mapping, the commands reading the buffer must have completed.
+    pub fn write(
+        &mut self,
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        (version, transforms): (u64, &[Mat4]),
+    ) {
+        self.version = version;
+        self.count = transforms.len() as u32;
+        if transforms.is_empty() {
+            return;
+        }
+        let size = mem::size_of_val(transforms) as u64;
+        if size > self.capacity {
+            self.destroy(device);
+            self.capacity = size.next_power_of_two();
+            AAAOwnedBuffer {
+                buffer: self.buffer,
+                memory: self.memory,
+                ..
+            } = create_empty_buffer(
+                device,
+                device_memory_properties,
+                self.capacity,
+                vk::BufferUsageFlags::VERTEX_BUFFER,
+                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            );
+        }
+        write_mapped(device, self.memory, self.capacity, transforms);
+    }
+
+    pub fn destroy(&self, device: &AAADevice) {
+        if self.capacity == 0 {
+            return;
+        }
+        unsafe {
+            crate::object_audit::destroyed(self.memory);
+            device.ash.free_memory(self.memory, None);
+            crate::object_audit::destroyed(self.buffer);
+            device.ash.destroy_buffer(self.buffer, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clones_share_the_transforms_and_their_changes() {
+        let instances = Instances::new(vec![Mat4::IDENTITY; 3]);
+        let (version, transforms) = instances.changes_since(0).unwrap();
+        assert_eq!(transforms.len(), 3);
+        assert_eq!(instances.changes_since(version), None);
+
+        // The instance count follows the transforms a clone sets
+        let clone = instances.clone();
+        clone.set(vec![Mat4::IDENTITY; 10_000]);
+        assert_eq!(instances.len(), 10_000);
+        let (edited, _) = instances.changes_since(version).unwrap();
+        clone.update(|transforms| transforms.truncate(1));
+        let (_, transforms) = instances.changes_since(edited).unwrap();
+        assert_eq!(transforms, vec![Mat4::IDENTITY]);
+    }
+}
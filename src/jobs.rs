@@ -0,0 +1,390 @@
+//! Worker pool shared by the engine and the application, so background work does not oversubscribe the CPU.
+use log::trace;
+use std::{
+    any::Any,
+    collections::VecDeque,
+    marker::PhantomData,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Condvar, Mutex, OnceLock,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+type Panic = Box<dyn Any + Send + 'static>;
+
+struct Queue {
+    jobs: VecDeque<Job>,
+    shutdown: bool,
+}
+
+struct Pool {
+    queue: Mutex<Queue>,
+    available: Condvar,
+    workers: Mutex<Vec<thread::JoinHandle<()>>>,
+    completed: AtomicU64,
+    busy_nanos: AtomicU64,
+}
+
+static POOL: OnceLock<Arc<Pool>> = OnceLock::new();
+
+/// Physical cores minus one, the remaining core is left to the event loop and render threads.
+pub fn worker_count() -> usize {
+    num_cpus::get_physical().saturating_sub(1).max(1)
+}
+
+fn pool() -> &'static Arc<Pool> {
+    POOL.get_or_init(|| Pool::new(worker_count()))
+}
+
+impl Pool {
+    fn new(worker_count: usize) -> Arc<Self> {
+        let pool = Arc::new(Pool {
+            queue: Mutex::new(Queue {
+                jobs: VecDeque::new(),
+                shutdown: false,
+            }),
+            available: Condvar::new(),
+            workers: Mutex::new(Vec::new()),
+            completed: AtomicU64::new(0),
+            busy_nanos: AtomicU64::new(0),
+        });
+        let workers = (0..worker_count)
+            .map(|index| {
+                let pool = pool.clone();
+                thread::Builder::new()
+                    .name(format!("pulsar-job-{index}"))
+                    .spawn(move || pool.work())
+                    .expect("Failed to spawn job worker")
+            })
+            .collect();
+        *pool.workers.lock().unwrap() = workers;
+        pool
+    }
+
+    fn push(&self, job: Job) {
+        let mut queue = self.queue.lock().unwrap();
+        if queue.shutdown {
+            // Workers are gone, keep the promise of running the job
+            drop(queue);
+            self.run(job);
+            return;
+        }
+        queue.jobs.push_back(job);
+        self.available.notify_one();
+    }
+
+    fn try_pop(&self) -> Option<Job> {
+        self.queue.lock().unwrap().jobs.pop_front()
+    }
+
+    fn work(&self) {
+        loop {
+            let job = {
+                let mut queue = self.queue.lock().unwrap();
+                loop {
+                    if let Some(job) = queue.jobs.pop_front() {
+                        break job;
+                    }
+                    if queue.shutdown {
+                        return;
+                    }
+                    queue = self.available.wait(queue).unwrap();
+                }
+            };
+            self.run(job);
+        }
+    }
+
+    fn run(&self, job: Job) {
+        let start = Instant::now();
+        job();
+        let elapsed = start.elapsed();
+        self.completed.fetch_add(1, Ordering::Relaxed);
+        self.busy_nanos
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+        trace!("Job ran in {elapsed:?} on {:?}", thread::current().name());
+    }
+
+    fn shutdown(&self) {
+        self.queue.lock().unwrap().shutdown = true;
+        self.available.notify_all();
+        for worker in self.workers.lock().unwrap().drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JobStats {
+    pub completed: u64,
+    /// Time spent running jobs, summed over every worker.
+    pub busy: Duration,
+    pub queued: usize,
+}
+
+pub fn stats() -> JobStats {
+    let Some(pool) = POOL.get() else {
+        return JobStats::default();
+    };
+    JobStats {
+        completed: pool.completed.load(Ordering::Relaxed),
+        busy: Duration::from_nanos(pool.busy_nanos.load(Ordering::Relaxed)),
+        queued: pool.queue.lock().unwrap().jobs.len(),
+    }
+}
+
+/// Finish the queued jobs then stop the workers. Jobs spawned afterwards run on the calling thread.
+pub fn shutdown() {
+    if let Some(pool) = POOL.get() {
+        pool.shutdown();
+    }
+}
+
+struct Slot<T> {
+    result: Mutex<Option<thread::Result<T>>>,
+    done: Condvar,
+}
+
+/// Handle to a spawned job's result.
+pub struct JoinToken<T> {
+    slot: Arc<Slot<T>>,
+}
+
+impl<T> JoinToken<T> {
+    pub fn is_finished(&self) -> bool {
+        self.slot.result.lock().unwrap().is_some()
+    }
+
+    /// Wait for the job, running queued jobs meanwhile. A panic in the job is resumed here.
+    pub fn join(self) -> T {
+        let pool = pool();
+        loop {
+            if let Some(result) = self.slot.result.lock().unwrap().take() {
+                return result.unwrap_or_else(|panic| panic::resume_unwind(panic));
+            }
+            match pool.try_pop() {
+                Some(job) => pool.run(job),
+                None => {
+                    let result = self.slot.result.lock().unwrap();
+                    let mut result = self
+                        .slot
+                        .done
+                        .wait_timeout_while(result, Duration::from_millis(1), |r| r.is_none())
+                        .unwrap()
+                        .0;
+                    if let Some(result) = result.take() {
+                        return result.unwrap_or_else(|panic| panic::resume_unwind(panic));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Run `f` on the pool.
+pub fn spawn<T, F>(f: F) -> JoinToken<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let slot = Arc::new(Slot {
+        result: Mutex::new(None),
+        done: Condvar::new(),
+    });
+    let job_slot = slot.clone();
+    pool().push(Box::new(move || {
+        let result = panic::catch_unwind(AssertUnwindSafe(f));
+        *job_slot.result.lock().unwrap() = Some(result);
+        job_slot.done.notify_all();
+    }));
+    JoinToken { slot }
+}
+
+struct ScopeState {
+    pending: Mutex<usize>,
+    done: Condvar,
+    panic: Mutex<Option<Panic>>,
+}
+
+/// Jobs spawned on a scope may borrow from outside of it, `scope` returns once they all finished.
+pub struct Scope<'scope> {
+    state: Arc<ScopeState>,
+    _borrows: PhantomData<&'scope mut &'scope ()>,
+}
+
+impl<'scope> Scope<'scope> {
+    pub fn spawn<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'scope,
+    {
+        *self.state.pending.lock().unwrap() += 1;
+        let state = self.state.clone();
+        let job: Box<dyn FnOnce() + Send + 'scope> = Box::new(move || {
+            if let Err(panic) = panic::catch_unwind(AssertUnwindSafe(f)) {
+                state.panic.lock().unwrap().get_or_insert(panic);
+            }
+            let mut pending = state.pending.lock().unwrap();
+            *pending -= 1;
+            if *pending == 0 {
+                state.done.notify_all();
+            }
+        });
+        // SAFETY: `scope` does not return before every job spawned here has run,
+        // so the borrows captured by the job outlive it.
+        let job: Job = unsafe { std::mem::transmute(job) };
+        pool().push(job);
+    }
+
+    fn wait(&self) {
+        let pool = pool();
+        loop {
+            if *self.state.pending.lock().unwrap() == 0 {
+                return;
+            }
+            // Help instead of blocking, nested scopes would otherwise starve the workers
+            match pool.try_pop() {
+                Some(job) => pool.run(job),
+                None => {
+                    let pending = self.state.pending.lock().unwrap();
+                    let _ = self
+                        .state
+                        .done
+                        .wait_timeout_while(pending, Duration::from_millis(1), |p| *p > 0)
+                        .unwrap();
+                }
+            }
+        }
+    }
+}
+
+/// Structured parallelism, the first panic of the scope or its jobs is resumed once everything finished.
+pub fn scope<'scope, T, F>(f: F) -> T
+where
+    F: FnOnce(&Scope<'scope>) -> T,
+{
+    let scope = Scope {
+        state: Arc::new(ScopeState {
+            pending: Mutex::new(0),
+            done: Condvar::new(),
+            panic: Mutex::new(None),
+        }),
+        _borrows: PhantomData,
+    };
+    let result = panic::catch_unwind(AssertUnwindSafe(|| f(&scope)));
+    scope.wait();
+    if let Some(panic) = scope.state.panic.lock().unwrap().take() {
+        panic::resume_unwind(panic);
+    }
+    result.unwrap_or_else(|panic| panic::resume_unwind(panic))
+}
+
+/// Parallel for over `items`, split in chunks of `chunk_size`.
+pub fn for_each_chunk<T, F>(items: &mut [T], chunk_size: usize, f: F)
+where
+    T: Send,
+    F: Fn(&mut [T]) + Sync,
+{
+    let f = &f;
+    scope(|scope| {
+        for chunk in items.chunks_mut(chunk_size.max(1)) {
+            scope.spawn(move || f(chunk));
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn spawn_returns_the_result() {
+        let tokens: Vec<JoinToken<usize>> = (0..16).map(|i| spawn(move || i * 2)).collect();
+        let results: Vec<usize> = tokens.into_iter().map(JoinToken::join).collect();
+        assert_eq!(results, (0..16).map(|i| i * 2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn nested_scopes_borrow_and_finish() {
+        let mut totals = [0usize; 8];
+        scope(|outer| {
+            for (index, total) in totals.iter_mut().enumerate() {
+                outer.spawn(move || {
+                    let sum = AtomicUsize::new(0);
+                    scope(|inner| {
+                        for value in 0..=index {
+                            let sum = &sum;
+                            inner.spawn(move || {
+                                sum.fetch_add(value, Ordering::Relaxed);
+                            });
+                        }
+                    });
+                    *total = sum.into_inner();
+                });
+            }
+        });
+        assert_eq!(totals, [0, 1, 3, 6, 10, 15, 21, 28]);
+    }
+
+    #[test]
+    fn for_each_chunk_covers_every_item() {
+        let mut items: Vec<u32> = (0..1000).collect();
+        for_each_chunk(&mut items, 64, |chunk| {
+            chunk.iter_mut().for_each(|item| *item *= 3)
+        });
+        assert!(items
+            .iter()
+            .enumerate()
+            .all(|(i, &item)| item == i as u32 * 3));
+    }
+
+    #[test]
+    fn panics_reach_the_joiner() {
+        let panic = panic::catch_unwind(|| spawn(|| panic!("spawned")).join()).unwrap_err();
+        assert_eq!(panic.downcast_ref::<&str>(), Some(&"spawned"));
+
+        let finished = AtomicUsize::new(0);
+        let panic = panic::catch_unwind(AssertUnwindSafe(|| {
+            scope(|scope| {
+                scope.spawn(|| panic!("scoped"));
+                for _ in 0..4 {
+                    scope.spawn(|| {
+                        finished.fetch_add(1, Ordering::Relaxed);
+                    });
+                }
+            })
+        }))
+        .unwrap_err();
+        assert_eq!(panic.downcast_ref::<&str>(), Some(&"scoped"));
+        // The scope still waited for its other jobs
+        assert_eq!(finished.load(Ordering::Relaxed), 4);
+    }
+
+    /// What `Application` does on drop, on a pool of its own so the shared one keeps its workers.
+    #[test]
+    fn shutdown_drains_the_queue() {
+        let pool = Pool::new(2);
+        let ran = Arc::new(AtomicUsize::new(0));
+        for _ in 0..32 {
+            let ran = ran.clone();
+            pool.push(Box::new(move || {
+                thread::sleep(Duration::from_millis(1));
+                ran.fetch_add(1, Ordering::Relaxed);
+            }));
+        }
+        pool.shutdown();
+        assert_eq!(ran.load(Ordering::Relaxed), 32);
+        assert!(pool.workers.lock().unwrap().is_empty());
+
+        // Jobs pushed afterwards run on the pushing thread
+        let ran_after = ran.clone();
+        pool.push(Box::new(move || {
+            ran_after.fetch_add(1, Ordering::Relaxed);
+        }));
+        assert_eq!(ran.load(Ordering::Relaxed), 33);
+    }
+}
@@ -1,8 +1,67 @@
 pub mod app;
-mod camera;
-mod input_manager;
-mod metrics;
-mod model;
+pub mod assets;
+pub mod background;
+pub mod batching;
+pub mod block_compression;
+pub mod camera;
+pub mod color_space;
+pub mod compute;
+pub mod config;
+pub mod culling;
+pub mod custom_pass;
+pub mod debug_lines;
+pub mod display;
+pub mod dof;
+pub mod draw_order;
+pub mod environment;
+pub mod error;
+pub mod failure;
+pub mod flight_recorder;
+pub mod gizmo;
+pub mod handles;
+pub mod hierarchy;
+pub mod hot_reload;
+pub mod input_manager;
+pub mod inset;
+pub mod instancing;
+pub mod jobs;
+pub mod loader;
+pub mod material;
+pub mod material_params;
+pub mod metrics;
+#[cfg(feature = "metrics-endpoint")]
+pub mod metrics_endpoint;
+pub mod model;
+pub mod object_audit;
+pub mod picking;
+pub mod pipeline_cache;
+pub mod pixel_snap;
+pub mod present_health;
+pub mod present_wait;
+pub mod renderer;
+#[cfg(feature = "replay")]
+pub mod replay;
+pub mod residency;
+pub mod screenshot;
+pub mod shader_compiler;
+pub mod shader_include;
+pub mod shader_reflect;
 mod shaders;
+pub mod skinning;
+pub mod surface_support;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+#[cfg(feature = "text")]
+pub mod text;
+pub mod texture;
+pub mod thumbnail;
+pub mod time_control;
+pub mod transient;
+pub mod update_queue;
 mod vulkan;
+pub mod watchdog;
+pub mod window_manager;
 mod window_state;
+pub mod world;
+
+pub use handles::{MaterialId, MeshHandle, RenderTargetId, TextureId};
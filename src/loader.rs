@@ -0,0 +1,196 @@
+//! Meshes loaded on the job pool and handed to a window once ready.
+//!
+//! A load targets the generation of the window's renderer it was started for. Closing the window
+//! retires that generation: loads still running are cancelled when they finish, their mesh is
+//! dropped on the worker instead of being queued for a renderer that is gone, and meshes queued
+//! but not yet registered are freed with the window.
+
+use crate::{
+    handles::MeshHandle,
+    input_manager::EventStates,
+    jobs::{self, JoinToken},
+    model::{Mesh, MeshSpace},
+};
+use std::{
+    error::Error,
+    sync::{Arc, Weak},
+};
+
+/// Outcome of a load, once its job finished.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadResult {
+    /// Queued for the window under this handle, registered before its next frame.
+    Loaded(MeshHandle),
+    /// The loader returned an error, nothing was queued.
+    Failed(String),
+    /// The window closed before the load finished, its mesh was dropped.
+    Cancelled,
+}
+
+/// Window renderer a load delivers to, see [`EventStates::generation`].
+#[derive(Debug, Clone)]
+pub struct LoadTarget {
+    event_states: Weak<EventStates>,
+    generation: u64,
+}
+
+impl LoadTarget {
+    /// The current generation of the renderer reading `event_states`.
+    pub fn new(event_states: &Arc<EventStates>) -> Self {
+        Self {
+            event_states: Arc::downgrade(event_states),
+            generation: event_states.generation(),
+        }
+    }
+
+    /// Whether a mesh delivered now would be dropped.
+    pub fn is_stale(&self) -> bool {
+        self.event_states
+            .upgrade()
+            .is_none_or(|event_states| event_states.generation() != self.generation)
+    }
+}
+
+/// A load running on the job pool.
+pub struct LoadHandle {
+    token: JoinToken<LoadResult>,
+}
+
+impl LoadHandle {
+    pub fn is_finished(&self) -> bool {
+        self.token.is_finished()
+    }
+
+    /// Wait for the load, running queued jobs meanwhile. A panic in the loader is resumed here.
+    pub fn wait(self) -> LoadResult {
+        self.token.join()
+    }
+}
+
+/// Run `load` on the job pool and queue its mesh in `space` for `target`. Loads whose target is
+/// already stale when their job starts skip `load` altogether.
+pub fn load_mesh<F>(target: LoadTarget, space: MeshSpace, load: F) -> LoadHandle
+where
+    F: FnOnce() -> Result<Mesh, Box<dyn Error + Send + Sync>> + Send + 'static,
+{
+    let token = jobs::spawn(move || {
+        if target.is_stale() {
+            return LoadResult::Cancelled;
+        }
+        let mesh = match load() {
+            Ok(mesh) => mesh,
+            Err(err) => return LoadResult::Failed(err.to_string()),
+        };
+        let Some(event_states) = target.event_states.upgrade() else {
+            return LoadResult::Cancelled;
+        };
+        match event_states.request_mesh_addition_for(target.generation, mesh, space) {
+            Some(handle) => LoadResult::Loaded(handle),
+            None => LoadResult::Cancelled,
+        }
+    });
+    LoadHandle { token }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Condvar, Mutex,
+        },
+        thread,
+        time::Duration,
+    };
+
+    /// Held closed until the window is retired, the loads waiting on it are mid-way.
+    #[derive(Default)]
+    struct Gate {
+        open: Mutex<bool>,
+        opened: Condvar,
+    }
+
+    impl Gate {
+        fn wait(&self) {
+            let open = self.open.lock().unwrap();
+            drop(self.opened.wait_while(open, |open| !*open).unwrap());
+        }
+
+        fn open(&self) {
+            *self.open.lock().unwrap() = true;
+            self.opened.notify_all();
+        }
+    }
+
+    fn cube() -> Result<Mesh, Box<dyn Error + Send + Sync>> {
+        Ok(Mesh::cube(1.0, [1.0; 4]))
+    }
+
+    #[test]
+    fn loads_for_an_open_window_are_queued_or_reported() {
+        let event_states = Arc::new(EventStates::default());
+        let target = LoadTarget::new(&event_states);
+        let quick = load_mesh(target.clone(), MeshSpace::World, cube);
+        let LoadResult::Loaded(handle) = quick.wait() else {
+            panic!("The cube was not queued");
+        };
+        let additions = event_states.take_mesh_additions();
+        assert_eq!(additions.len(), 1);
+        assert_eq!(additions[0].0, handle, "Queued under another handle");
+        let failed = load_mesh(target, MeshSpace::World, || Err("corrupt file".into()));
+        assert_eq!(failed.wait(), LoadResult::Failed("corrupt file".into()));
+    }
+
+    #[test]
+    fn retiring_a_window_cancels_its_running_loads() {
+        const SLOW_LOADS: usize = 4;
+        let event_states = Arc::new(EventStates::default());
+        let target = LoadTarget::new(&event_states);
+        let gate = Arc::new(Gate::default());
+        let started = Arc::new(AtomicUsize::new(0));
+        let handles: Vec<_> = (0..SLOW_LOADS)
+            .map(|_| {
+                let gate = gate.clone();
+                let started = started.clone();
+                load_mesh(target.clone(), MeshSpace::World, move || {
+                    started.fetch_add(1, Ordering::SeqCst);
+                    gate.wait();
+                    cube()
+                })
+            })
+            .collect();
+        while started.load(Ordering::SeqCst) == 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        // A mesh queued just before the window closes is freed with it
+        event_states.request_mesh_addition(Mesh::cube(1.0, [1.0; 4]), MeshSpace::World, None);
+        event_states.retire();
+        assert!(target.is_stale());
+        gate.open();
+        for handle in handles {
+            assert_eq!(handle.wait(), LoadResult::Cancelled);
+        }
+        assert!(event_states.take_mesh_additions().is_empty());
+
+        // The next generation takes loads again
+        let reopened = load_mesh(LoadTarget::new(&event_states), MeshSpace::World, cube);
+        assert!(matches!(reopened.wait(), LoadResult::Loaded(_)));
+    }
+
+    #[test]
+    fn loads_for_a_dropped_window_do_not_run() {
+        let gone = Arc::new(EventStates::default());
+        let target = LoadTarget::new(&gone);
+        drop(gone);
+        let ran = Arc::new(AtomicBool::new(false));
+        let loader_ran = ran.clone();
+        let orphan = load_mesh(target, MeshSpace::World, move || {
+            loader_ran.store(true, Ordering::SeqCst);
+            cube()
+        });
+        assert_eq!(orphan.wait(), LoadResult::Cancelled);
+        assert!(!ran.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,767 @@
+use crate::vulkan::pipeline::MeshPipeline;
+use ash::vk;
+use glam::Vec4;
+use std::hash::{Hash, Hasher};
+
+/// Shaders of [`Material::texture_array`].
+const TEXTURE_ARRAY_SHADERS: (&str, &str) = ("texture_array_vert", "texture_array");
+/// Shaders of [`Material::normal_lines`], vertex, geometry and fragment.
+const NORMAL_LINES_SHADERS: (&str, &str, &str) = ("normals_vert", "normals", "frag");
+/// Shaders of [`Material::displacement`], vertex, tessellation control, tessellation evaluation
+/// and fragment.
+const DISPLACEMENT_SHADERS: (&str, &str, &str, &str) =
+    ("displace_vert", "displace_tesc", "displace", "frag");
+/// Patches of [`Material::with_tessellation`], the mesh's triangles.
+const TRIANGLE_PATCH: u32 = 3;
+
+/// Shaders a mesh is drawn with and the state of their pipeline, each distinct material gets its
+/// own pipeline, built the first time a mesh draws with it and kept until the window closes.
+/// Names refer to the compiled shaders in `assets/bin`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Material {
+    pub vertex_shader: String,
+    pub fragment_shader: String,
+    /// Run between the vertex and fragment stages. Needs the `geometryShader` feature, devices
+    /// without it build the pipeline without this stage.
+    pub geometry_shader: Option<String>,
+    /// Run between the vertex and geometry or fragment stages on the patches of
+    /// [`PipelineOptions::patch_control_points`]. Needs the `tessellationShader` feature, devices
+    /// without it build the pipeline without these stages.
+    pub tessellation: Option<TessellationShaders>,
+    /// Offset of the depth of every fragment, `None` leaves it as rasterized.
+    pub depth_bias: Option<DepthBias>,
+    /// Culling, winding, fill, depth test, topology and blending, opaque triangles with their
+    /// back faces culled by default.
+    pub options: PipelineOptions,
+    /// Values of the specialization constants of every stage by `constant_id`, sorted, as their
+    /// 32 bits: a `float` constant is given its `to_bits`, a `bool` 0 or 1.
+    pub specialization: Vec<(u32, u32)>,
+}
+
+/// Tessellation control and evaluation shaders of a [`Material`], both or neither.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TessellationShaders {
+    pub control: String,
+    pub evaluation: String,
+}
+
+/// How the fragments of a pipeline combine with what is drawn under them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum BlendMode {
+    /// Replaces it.
+    #[default]
+    Opaque,
+    /// Over it by their alpha, the alpha accumulating as coverage.
+    Alpha,
+    /// Added to it weighted by their alpha, glows and particles.
+    Additive,
+}
+
+/// Rasterizer and depth state of a pipeline, each distinct value gets its own pipeline.
+///
+/// The projections keep +Y up in clip space while Vulkan's framebuffer points it down, so a
+/// triangle counter-clockwise in the world, as glTF, OBJ and the [`crate::model::Mesh`]
+/// constructors wind them, is clockwise once rasterized: the default front face is `CLOCKWISE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PipelineOptions {
+    pub cull_mode: vk::CullModeFlags,
+    /// Winding of the front faces in framebuffer space, see above.
+    pub front_face: vk::FrontFace,
+    /// `LINE` and `POINT` need the `fillModeNonSolid` feature, devices without it fill.
+    pub polygon_mode: vk::PolygonMode,
+    pub depth_compare: vk::CompareOp,
+    /// Fragments failing `depth_compare` against the depth buffer are dropped.
+    pub depth_test: bool,
+    /// Fragments kept write their depth, hiding what is drawn behind them later. Vulkan writes
+    /// nothing without `depth_test`.
+    pub depth_write: bool,
+    /// Primitives the indices form, the mesh's triangles by default. `LINE_LIST` and
+    /// `POINT_LIST` read the same indices by twos and ones, the points need a vertex shader
+    /// writing `gl_PointSize`.
+    pub topology: vk::PrimitiveTopology,
+    /// Anything but `Opaque` tests the depth without writing it, and the mesh draws with the
+    /// blended ones, back to front after the opaque meshes.
+    pub blend: BlendMode,
+    /// Vertices of every tessellation patch, 0 to draw without tessellation. Any other count
+    /// draws `PATCH_LIST` whatever `topology`, for a material with [`Material::tessellation`]
+    /// shaders only. Ignored by the default material.
+    pub patch_control_points: u32,
+}
+
+impl Default for PipelineOptions {
+    fn default() -> Self {
+        Self {
+            cull_mode: vk::CullModeFlags::BACK,
+            front_face: vk::FrontFace::CLOCKWISE,
+            polygon_mode: vk::PolygonMode::FILL,
+            depth_compare: vk::CompareOp::LESS_OR_EQUAL,
+            depth_test: true,
+            depth_write: true,
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            blend: BlendMode::Opaque,
+            patch_control_points: 0,
+        }
+    }
+}
+
+impl PipelineOptions {
+    /// Both faces drawn, the default of the 2D world whose sprites are often mirrored by a
+    /// negative scale.
+    pub fn unculled() -> Self {
+        Self {
+            cull_mode: vk::CullModeFlags::NONE,
+            ..Default::default()
+        }
+    }
+
+    /// Unculled, neither tested against nor written to the depth buffer: drawn in order over
+    /// whatever is there, as the UI is.
+    pub fn ui() -> Self {
+        Self {
+            depth_test: false,
+            depth_write: false,
+            ..Self::unculled()
+        }
+    }
+
+    /// Whether the pipeline blends, see [`PipelineOptions::blend`].
+    pub fn is_blended(&self) -> bool {
+        self.blend != BlendMode::Opaque
+    }
+
+    /// Depth state of a pipeline built with these options, the stencil left out. Blended ones
+    /// never write it.
+    pub fn depth_stencil_state(&self) -> vk::PipelineDepthStencilStateCreateInfo<'static> {
+        let noop_stencil_state = vk::StencilOpState {
+            fail_op: vk::StencilOp::KEEP,
+            pass_op: vk::StencilOp::KEEP,
+            depth_fail_op: vk::StencilOp::KEEP,
+            compare_op: vk::CompareOp::ALWAYS,
+            ..Default::default()
+        };
+        vk::PipelineDepthStencilStateCreateInfo {
+            depth_test_enable: self.depth_test as vk::Bool32,
+            depth_write_enable: (self.depth_test && self.depth_write && !self.is_blended())
+                as vk::Bool32,
+            depth_compare_op: self.depth_compare,
+            front: noop_stencil_state,
+            back: noop_stencil_state,
+            max_depth_bounds: 1.0,
+            ..Default::default()
+        }
+    }
+
+    /// Whether the rasterizer drops `triangle`, given by its clip space corners, decided as
+    /// Vulkan does from the sign of its area in framebuffer space. Checks a winding on the CPU.
+    pub fn culls(&self, triangle: [Vec4; 3]) -> bool {
+        let [a, b, c] = triangle.map(|corner| corner.truncate().truncate() / corner.w);
+        // The viewport scales both axes by positive factors, the sign is the same in NDC
+        let area = -0.5 * ((b - a).perp_dot(c - a));
+        let front = match self.front_face {
+            vk::FrontFace::COUNTER_CLOCKWISE => area > 0.0,
+            _ => area < 0.0,
+        };
+        let dropped = if front {
+            vk::CullModeFlags::FRONT
+        } else {
+            vk::CullModeFlags::BACK
+        };
+        self.cull_mode.intersects(dropped)
+    }
+}
+
+/// Rasterization depth bias, in the units of `VkPipelineRasterizationStateCreateInfo`. Negative
+/// factors pull the fragments toward the camera: a decal drawn with one stays over the surface it
+/// lies on, whatever the draw order. Compared and hashed bit for bit.
+///
+/// The depth attachment is `D16_UNORM`, its smallest resolvable difference is about 2^-16 of the
+/// whole depth range where a 32 bit float one resolves 2^-23 of the depth. A constant factor of -1
+/// is enough for co-planar geometry but steps of a sixteenth of a unit do nothing, and distant
+/// surfaces, crowded in the last units of the range, want the slope factor rather than a larger
+/// constant one.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DepthBias {
+    /// Multiple of the smallest resolvable depth difference.
+    pub constant_factor: f32,
+    /// Multiple of the fragment's depth slope, for surfaces seen at grazing angles.
+    pub slope_factor: f32,
+    /// Largest offset the factors add up to, in depth, toward the camera when negative. 0 leaves
+    /// the offset unclamped, as does a device without the `depthBiasClamp` feature.
+    pub clamp: f32,
+}
+
+impl PartialEq for DepthBias {
+    fn eq(&self, other: &Self) -> bool {
+        self.bits() == other.bits()
+    }
+}
+
+impl Eq for DepthBias {}
+
+impl Hash for DepthBias {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.bits().hash(state);
+    }
+}
+
+impl DepthBias {
+    pub fn new(constant_factor: f32, slope_factor: f32) -> Self {
+        Self {
+            constant_factor,
+            slope_factor,
+            clamp: 0.0,
+        }
+    }
+
+    /// The same factors, their offset clamped to `clamp`.
+    pub fn with_clamp(self, clamp: f32) -> Self {
+        Self { clamp, ..self }
+    }
+
+    fn bits(&self) -> (u32, u32, u32) {
+        (
+            self.constant_factor.to_bits(),
+            self.slope_factor.to_bits(),
+            self.clamp.to_bits(),
+        )
+    }
+}
+
+impl Material {
+    pub fn new(vertex_shader: &str, fragment_shader: &str) -> Self {
+        Self {
+            vertex_shader: vertex_shader.to_string(),
+            fragment_shader: fragment_shader.to_string(),
+            geometry_shader: None,
+            tessellation: None,
+            depth_bias: None,
+            options: PipelineOptions::default(),
+            specialization: Vec::new(),
+        }
+    }
+
+    /// The same shaders with their depth biased, the fix for decals z-fighting with the surface
+    /// under them. Another pipeline than the unbiased material's.
+    pub fn with_depth_bias(mut self, constant_factor: f32, slope_factor: f32) -> Self {
+        self.depth_bias = Some(DepthBias::new(constant_factor, slope_factor));
+        self
+    }
+
+    /// The same shaders rasterized with `options`, another pipeline than the default's.
+    pub fn with_options(mut self, options: PipelineOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// The same shaders blended by `blend`, see [`PipelineOptions::blend`].
+    pub fn with_blend(mut self, blend: BlendMode) -> Self {
+        self.options.blend = blend;
+        self
+    }
+
+    /// The same shaders with the specialization constant `constant_id` set to `value`, the bits
+    /// of a `float`. Each distinct set of values is another pipeline, constants the shaders do
+    /// not declare are ignored by Vulkan.
+    pub fn with_specialization(mut self, constant_id: u32, value: u32) -> Self {
+        match self
+            .specialization
+            .binary_search_by_key(&constant_id, |&(id, _)| id)
+        {
+            Ok(index) => self.specialization[index].1 = value,
+            Err(index) => self.specialization.insert(index, (constant_id, value)),
+        }
+        self
+    }
+
+    /// The same shaders with the geometry shader `geometry_shader` between the vertex and
+    /// fragment stages, another pipeline than the material without it.
+    pub fn with_geometry_shader(mut self, geometry_shader: &str) -> Self {
+        self.geometry_shader = Some(geometry_shader.to_string());
+        self
+    }
+
+    /// The same shaders with the tessellation shaders `control` and `evaluation` after the vertex
+    /// stage, tessellating the mesh's triangles unless [`PipelineOptions::patch_control_points`]
+    /// is set already. Another pipeline than the material without them.
+    pub fn with_tessellation(mut self, control: &str, evaluation: &str) -> Self {
+        self.tessellation = Some(TessellationShaders {
+            control: control.to_string(),
+            evaluation: evaluation.to_string(),
+        });
+        if self.options.patch_control_points == 0 {
+            self.options.patch_control_points = TRIANGLE_PATCH;
+        }
+        self
+    }
+
+    /// The material without its geometry and tessellation stages, drawing the mesh's own
+    /// primitives: what a device lacking the features of those stages draws instead.
+    pub fn without_optional_stages(&self) -> Self {
+        Self {
+            geometry_shader: None,
+            tessellation: None,
+            options: PipelineOptions {
+                patch_control_points: 0,
+                ..self.options
+            },
+            ..self.clone()
+        }
+    }
+
+    /// Displaces the mesh along its normals by the red channel of the window's texture, the
+    /// heightmap, each triangle subdivided by the tessellator first. `TESSELLATION_LEVEL`
+    /// (constant 0, 16 by default) sets the subdivisions of every edge, `DISPLACEMENT` (constant
+    /// 1, 0.25 by default) the height of a white texel in the mesh's own units, both `float`s for
+    /// [`Material::with_specialization`]. Lit as the undisplaced mesh, both faces drawn as
+    /// terrain seen from under it would be. Devices without tessellation draw the mesh flat, as
+    /// the default material does.
+    pub fn displacement() -> Self {
+        let (vertex, control, evaluation, fragment) = DISPLACEMENT_SHADERS;
+        Self::new(vertex, fragment)
+            .with_options(PipelineOptions::unculled())
+            .with_tessellation(control, evaluation)
+    }
+
+    /// Debug view of the vertex normals: every vertex of the triangles drawn as a line along
+    /// its normal, a tenth of a unit long in the mesh's own space. Shared vertices are drawn once
+    /// per triangle. Devices without geometry shaders draw the mesh as the default material does.
+    pub fn normal_lines() -> Self {
+        let (vertex, geometry, fragment) = NORMAL_LINES_SHADERS;
+        Self::new(vertex, fragment).with_geometry_shader(geometry)
+    }
+
+    /// Samples the window's texture array at the mesh's layer, see `MeshUpdate::TextureLayer`.
+    pub fn texture_array() -> Self {
+        Self::new(TEXTURE_ARRAY_SHADERS.0, TEXTURE_ARRAY_SHADERS.1)
+    }
+
+    /// Whether the material samples the texture array, biased or not.
+    pub fn is_texture_array(&self) -> bool {
+        (self.vertex_shader.as_str(), self.fragment_shader.as_str()) == TEXTURE_ARRAY_SHADERS
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self::new("vert", "frag")
+    }
+}
+
+/// What a mesh draws with, the key its pipeline is cached by in the `PipelineRegistry`: shader
+/// pair, topology, blend mode, culling and specialization values. Every distinct key gets its
+/// own pipeline, all sharing the one pipeline layout.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum MaterialKey {
+    /// The built-in shaders of `MeshPipeline` rasterized with [`MeshPipeline::options`].
+    Default(MeshPipeline, PipelineOptions),
+    /// An application material, see `Application::set_mesh_material`.
+    Material(Material),
+}
+
+impl Default for MaterialKey {
+    fn default() -> Self {
+        Self::Default(MeshPipeline::Triangles, PipelineOptions::default())
+    }
+}
+
+impl MaterialKey {
+    /// Rasterizer state the pipeline is built with.
+    pub fn options(&self) -> PipelineOptions {
+        match self {
+            Self::Default(variant, options) => variant.options(*options),
+            Self::Material(material) => material.options,
+        }
+    }
+
+    /// Whether the pipeline takes its depth bias as dynamic state, set per draw.
+    pub fn has_dynamic_depth_bias(&self) -> bool {
+        matches!(self, Self::Default(..))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        camera::{Camera, OrthographicProjection, PerspectiveProjection},
+        model::{Mesh, Vertex},
+        world::WorldConvention,
+    };
+    use glam::{Mat4, Vec2, Vec3};
+    use std::f32::consts::{PI, TAU};
+
+    /// `NORMAL_LENGTH` of `normals.vert`.
+    const NORMAL_LENGTH: f32 = 0.1;
+    /// `DISPLACEMENT` and `TESSELLATION_LEVEL` of `displace.tese` and `displace.tesc`.
+    const DISPLACEMENT: f32 = 0.25;
+    const TESSELLATION_LEVEL: u32 = 16;
+
+    /// The demo scene's camera: four units back on +Z, looking at the origin.
+    fn demo_camera() -> Mat4 {
+        let camera = Camera::new(
+            Vec3::new(0.0, 0.0, 4.0),
+            WorldConvention::YUp,
+            OrthographicProjection::new(0.0, 1.0, 0.0, 1.0, -1.0, 1.0, Mat4::IDENTITY),
+            PerspectiveProjection::new(PI / 4.0, 16.0 / 9.0, 0.01, 1000.0, Mat4::IDENTITY),
+        );
+        camera.perspective().projection_view
+    }
+
+    /// What `normals.vert` then `normals.geom` emit for `mesh`: a line per corner of every
+    /// triangle, from the vertex to the tip of its normal, in clip space.
+    fn run_normal_shaders(mesh: &Mesh, pvm: Mat4) -> Vec<[Vec4; 2]> {
+        let project = |position: Vec3| pvm * position.extend(1.0);
+        mesh.indices
+            .chunks_exact(3)
+            .flat_map(|triangle| triangle.iter().map(|&index| &mesh.vertices[index as usize]))
+            .map(|vertex| {
+                let position = Vec3::from_slice(&vertex.pos);
+                let direction = Vec3::from_array(vertex.normal).normalize_or_zero();
+                [
+                    project(position),
+                    project(position + direction * NORMAL_LENGTH),
+                ]
+            })
+            .collect()
+    }
+
+    /// Red channel of a heightmap of ripples, standing for the window's texture.
+    fn heightmap(uv: Vec2) -> f32 {
+        0.5 + 0.5 * (uv.x * TAU * 2.0).sin() * (uv.y * TAU).cos()
+    }
+
+    /// `attribute` of the triangle's `corners` at barycentric `weights`, as the evaluation stage
+    /// interpolates its inputs.
+    fn interpolate<T>(corners: [&Vertex; 3], weights: Vec3, attribute: impl Fn(&Vertex) -> T) -> T
+    where
+        T: std::ops::Mul<f32, Output = T> + std::iter::Sum,
+    {
+        corners
+            .into_iter()
+            .zip(weights.to_array())
+            .map(|(vertex, weight)| attribute(vertex) * weight)
+            .sum()
+    }
+
+    /// Corners of every triangle of `mesh`.
+    fn triangles(mesh: &Mesh) -> impl Iterator<Item = [&Vertex; 3]> {
+        mesh.indices
+            .chunks_exact(3)
+            .map(|triangle| [0, 1, 2].map(|corner| &mesh.vertices[triangle[corner] as usize]))
+    }
+
+    fn direction(vertex: &Vertex) -> Vec3 {
+        Vec3::from_array(vertex.normal).normalize_or_zero()
+    }
+
+    /// Where `displace.vert` then `displace.tese` put the vertex at `weights` of every triangle,
+    /// in clip space: the corners and their unit displacement projected first, interpolated after.
+    fn run_displacement_shaders(mesh: &Mesh, pvm: Mat4, weights: Vec3) -> Vec<Vec4> {
+        triangles(mesh)
+            .map(|corners| {
+                let position = interpolate(corners, weights, |vertex| {
+                    pvm * Vec4::from_array(vertex.pos)
+                });
+                let displacement = interpolate(corners, weights, |vertex| {
+                    pvm * direction(vertex).extend(0.0)
+                });
+                let uv = interpolate(corners, weights, |vertex| Vec2::from_array(vertex.uv));
+                position + displacement * heightmap(uv) * DISPLACEMENT
+            })
+            .collect()
+    }
+
+    /// The same vertices displaced in the mesh's own space then projected, as the displacement
+    /// is meant.
+    fn displace_on_cpu(mesh: &Mesh, pvm: Mat4, weights: Vec3) -> Vec<Vec4> {
+        triangles(mesh)
+            .map(|corners| {
+                let position = interpolate(corners, weights, |vertex| {
+                    Vec4::from_array(vertex.pos).truncate()
+                });
+                let normal = interpolate(corners, weights, direction);
+                let uv = interpolate(corners, weights, |vertex| Vec2::from_array(vertex.uv));
+                pvm * (position + normal * heightmap(uv) * DISPLACEMENT).extend(1.0)
+            })
+            .collect()
+    }
+
+    /// Triangles of `mesh` seen through `projection_view` that `options` keeps.
+    fn kept(mesh: &Mesh, projection_view: Mat4, options: &PipelineOptions) -> usize {
+        let pvm = projection_view * mesh.transform;
+        mesh.indices
+            .chunks(3)
+            .filter(|triangle| {
+                let corners = [0, 1, 2].map(|corner| {
+                    pvm * Vec4::from_array(mesh.vertices[triangle[corner] as usize].pos)
+                });
+                !options.culls(corners)
+            })
+            .count()
+    }
+
+    #[test]
+    fn texture_array_material_is_told_apart() {
+        assert!(Material::texture_array().is_texture_array());
+        assert!(!Material::default().is_texture_array());
+    }
+
+    #[test]
+    fn biased_material_has_its_own_pipeline() {
+        assert_ne!(
+            Material::default(),
+            Material::default().with_depth_bias(-1.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn meshes_wound_counter_clockwise_keep_their_front_faces() {
+        let projection_view = demo_camera();
+        let culled = PipelineOptions::default();
+        let unculled = PipelineOptions::unculled();
+        let quad = Mesh::quad(Vec2::ONE, [1.0; 4]);
+        let turned = Mesh {
+            transform: Mat4::from_rotation_y(PI),
+            ..Mesh::quad(Vec2::ONE, [1.0; 4])
+        };
+        let cube = Mesh::cube(1.0, [1.0; 4]);
+
+        // Mesh, options, triangles kept
+        let cases = [
+            ("quad facing the camera", &quad, culled, 2),
+            ("quad facing the camera, unculled", &quad, unculled, 2),
+            // Only the +Z face of the cube faces the camera, the others are seen edge on or from
+            // behind
+            ("cube", &cube, culled, 2),
+            ("cube, unculled", &cube, unculled, 12),
+            (
+                "cube culling its front faces",
+                &cube,
+                PipelineOptions {
+                    cull_mode: vk::CullModeFlags::FRONT,
+                    ..culled
+                },
+                10,
+            ),
+            (
+                "cube wound the other way",
+                &cube,
+                PipelineOptions {
+                    front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+                    ..culled
+                },
+                10,
+            ),
+            ("quad turned away", &turned, culled, 0),
+            ("quad turned away, unculled", &turned, unculled, 2),
+        ];
+        for (name, mesh, options, expected) in cases {
+            assert_eq!(kept(mesh, projection_view, &options), expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn back_faces_are_culled_by_default() {
+        let options = PipelineOptions::default();
+        assert_eq!(options.cull_mode, vk::CullModeFlags::BACK);
+        assert_eq!(options.polygon_mode, vk::PolygonMode::FILL);
+        assert_eq!(options.depth_compare, vk::CompareOp::LESS_OR_EQUAL);
+        assert_eq!(Material::default().options, options);
+        // Each set of options gets its own pipeline
+        let wireframe = Material::default().with_options(PipelineOptions {
+            polygon_mode: vk::PolygonMode::LINE,
+            ..options
+        });
+        assert_ne!(wireframe, Material::default());
+    }
+
+    /// The UI's depth state against the opaque meshes', only the depth toggles differ.
+    #[test]
+    fn ui_neither_tests_nor_writes_depth() {
+        let opaque = PipelineOptions::default().depth_stencil_state();
+        let ui = PipelineOptions::ui().depth_stencil_state();
+        assert_eq!(
+            (opaque.depth_test_enable, opaque.depth_write_enable),
+            (vk::TRUE, vk::TRUE)
+        );
+        assert_eq!(
+            (ui.depth_test_enable, ui.depth_write_enable),
+            (vk::FALSE, vk::FALSE)
+        );
+        assert_eq!(ui.depth_compare_op, opaque.depth_compare_op);
+        assert_eq!(ui.stencil_test_enable, opaque.stencil_test_enable);
+        assert_eq!(ui.max_depth_bounds, opaque.max_depth_bounds);
+        // Vulkan writes no depth untested, the state says so too
+        let write_only = PipelineOptions {
+            depth_test: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            write_only.depth_stencil_state().depth_write_enable,
+            vk::FALSE
+        );
+        let read_only = PipelineOptions {
+            depth_write: false,
+            ..Default::default()
+        }
+        .depth_stencil_state();
+        assert_eq!(
+            (read_only.depth_test_enable, read_only.depth_write_enable),
+            (vk::TRUE, vk::FALSE)
+        );
+    }
+
+    /// Topology, blending and specialization constants key the pipeline as the other options do,
+    /// the order constants are set in does not.
+    #[test]
+    fn each_key_makes_a_distinct_material() {
+        let options = PipelineOptions::default();
+        assert_eq!(options.topology, vk::PrimitiveTopology::TRIANGLE_LIST);
+        assert!(!options.is_blended());
+        let base = Material::default();
+        let variants = [
+            base.clone(),
+            base.clone().with_options(PipelineOptions {
+                topology: vk::PrimitiveTopology::LINE_LIST,
+                ..options
+            }),
+            base.clone().with_blend(BlendMode::Alpha),
+            base.clone().with_blend(BlendMode::Additive),
+            base.clone().with_specialization(0, 1),
+            base.clone().with_specialization(0, 2),
+            base.clone().with_specialization(1, 1),
+        ];
+        let distinct: std::collections::HashSet<_> = variants.iter().collect();
+        assert_eq!(distinct.len(), variants.len());
+        let ordered = base
+            .clone()
+            .with_specialization(3, 1.5f32.to_bits())
+            .with_specialization(1, 7);
+        let reordered = base
+            .clone()
+            .with_specialization(1, 0)
+            .with_specialization(3, 1.5f32.to_bits())
+            .with_specialization(1, 7);
+        assert_eq!(ordered, reordered);
+        assert_eq!(ordered.specialization, [(1, 7), (3, 1.5f32.to_bits())]);
+        // Blended pipelines test the depth without writing it, whatever their options say
+        for blend in [BlendMode::Alpha, BlendMode::Additive] {
+            let state = base.clone().with_blend(blend).options.depth_stencil_state();
+            assert_eq!(
+                (state.depth_test_enable, state.depth_write_enable),
+                (vk::TRUE, vk::FALSE),
+                "{blend:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn geometry_stage_is_part_of_the_material() {
+        let normals = Material::normal_lines();
+        assert_eq!(normals.geometry_shader.as_deref(), Some("normals"));
+        assert_eq!(Material::default().geometry_shader, None);
+        let two_stages = Material {
+            geometry_shader: None,
+            ..normals.clone()
+        };
+        assert_ne!(two_stages, normals);
+        // Adding the stage replaces no other
+        let with_stage = Material::default().with_geometry_shader("normals");
+        assert_eq!(with_stage.geometry_shader, normals.geometry_shader);
+        assert_eq!(with_stage.fragment_shader, normals.fragment_shader);
+    }
+
+    /// The lines of a cube through the identity: as long as the normals, from every corner of
+    /// every triangle along its face's normal.
+    #[test]
+    fn normal_lines_run_from_every_corner() {
+        let mesh = Mesh::cube(1.0, [1.0; 4]);
+        let lines = run_normal_shaders(&mesh, Mat4::IDENTITY);
+        assert_eq!(lines.len(), mesh.indices.len());
+        let corners = mesh
+            .indices
+            .iter()
+            .map(|&index| &mesh.vertices[index as usize]);
+        for ([start, tip], vertex) in lines.iter().zip(corners) {
+            assert_eq!(start.truncate(), Vec3::from_slice(&vertex.pos));
+            let line = (*tip - *start).truncate();
+            assert!(
+                line.abs_diff_eq(Vec3::from_array(vertex.normal) * NORMAL_LENGTH, 1e-5),
+                "from {start} to {tip}"
+            );
+        }
+        // A scaled mesh scales its lines, they are in the mesh's own units
+        let scaled = run_normal_shaders(&mesh, Mat4::from_scale(Vec3::splat(2.0)));
+        let length = (scaled[0][1] - scaled[0][0]).truncate().length();
+        assert!((length - 2.0 * NORMAL_LENGTH).abs() <= 1e-5, "{length}");
+    }
+
+    #[test]
+    fn tessellation_stages_are_part_of_the_material() {
+        let displacement = Material::displacement();
+        let tessellation = displacement
+            .tessellation
+            .as_ref()
+            .expect("The displacement has tessellation stages");
+        assert_eq!(
+            (
+                tessellation.control.as_str(),
+                tessellation.evaluation.as_str()
+            ),
+            ("displace_tesc", "displace")
+        );
+        assert_eq!(displacement.options.patch_control_points, 3);
+        // The fallback draws the mesh's triangles with the vertex and fragment stages
+        let flat = displacement.without_optional_stages();
+        assert_eq!(flat.tessellation, None);
+        assert_eq!(flat.options.patch_control_points, 0);
+        assert_eq!(flat.vertex_shader, displacement.vertex_shader);
+        assert_ne!(flat, displacement);
+        assert_eq!(Material::default().options.patch_control_points, 0);
+        // Patches set first are kept, quads of a custom control shader
+        let quads = Material::default()
+            .with_options(PipelineOptions {
+                patch_control_points: 4,
+                ..Default::default()
+            })
+            .with_tessellation("quad_tesc", "quad_tese");
+        assert_eq!(quads.options.patch_control_points, 4);
+        let level = Material::displacement().with_specialization(0, 64.0f32.to_bits());
+        assert_ne!(
+            level, displacement,
+            "Another tessellation level shares the pipeline"
+        );
+    }
+
+    /// Displacing in clip space is displacing in the mesh's own space: the shaders never need the
+    /// transforms past the vertex stage.
+    #[test]
+    fn clip_space_displacement_matches_the_mesh_space_one() {
+        let mesh = Mesh::plane(2.0, 2.0, 7, [0.4, 0.7, 0.3, 1.0]);
+        let pvm = Mat4::perspective_rh(1.0, 16.0 / 9.0, 0.1, 100.0)
+            * Mat4::look_at_rh(Vec3::new(1.5, 2.0, 3.0), Vec3::ZERO, Vec3::Y)
+            * Mat4::from_rotation_y(0.3);
+        let level = TESSELLATION_LEVEL as f32;
+        for i in 0..=TESSELLATION_LEVEL {
+            for j in 0..=TESSELLATION_LEVEL - i {
+                let weights =
+                    Vec3::new(i as f32, j as f32, (TESSELLATION_LEVEL - i - j) as f32) / level;
+                let shaded = run_displacement_shaders(&mesh, pvm, weights);
+                let expected = displace_on_cpu(&mesh, pvm, weights);
+                for (index, (shaded, expected)) in shaded.iter().zip(&expected).enumerate() {
+                    assert!(
+                        shaded.abs_diff_eq(*expected, 1e-4),
+                        "Triangle {index} at {weights}: {shaded}, expected {expected}"
+                    );
+                }
+            }
+        }
+        // Seen through the identity the plane's vertices rise by the height along +Y
+        let corner = run_displacement_shaders(&mesh, Mat4::IDENTITY, Vec3::X)[0];
+        let uv = Vec2::from_array(mesh.vertices[mesh.indices[0] as usize].uv);
+        assert!(
+            (corner.y - heightmap(uv) * DISPLACEMENT).abs() <= 1e-4,
+            "Corner displaced to {corner}"
+        );
+    }
+}
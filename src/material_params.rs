@@ -0,0 +1,321 @@
+//! Named parameters of materials, eased toward the values set by artists instead of snapping.
+//!
+//! Parameters are registered per material with their initial value, a float or a vec4. Commands
+//! read `set_material_param <material> <name> <value> [over <seconds>]`, vec4 values written as
+//! four comma separated floats without spaces. A parameter holds at most one animation: setting it
+//! again while it eases starts over from where it is, toward the new target.
+
+use crate::error::PulsarError;
+use glam::Vec4;
+use std::{collections::BTreeMap, error::Error, time::Duration};
+
+pub const SET_MATERIAL_PARAM: &str = "set_material_param";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParamValue {
+    Float(f32),
+    Vec4(Vec4),
+}
+
+impl ParamValue {
+    /// `0.5` or `1,0.5,0,1`.
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error>> {
+        let floats = text
+            .split(',')
+            .map(|float| float.trim().parse::<f32>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| format!("Parameter value {text}: {err}"))?;
+        match floats[..] {
+            [float] => Ok(Self::Float(float)),
+            [x, y, z, w] => Ok(Self::Vec4(Vec4::new(x, y, z, w))),
+            _ => Err(format!("Parameter value {text} is neither a float nor four of them").into()),
+        }
+    }
+
+    fn lerp(self, target: Self, t: f32) -> Self {
+        match (self, target) {
+            (Self::Float(from), Self::Float(to)) => Self::Float(from + (to - from) * t),
+            (Self::Vec4(from), Self::Vec4(to)) => Self::Vec4(from.lerp(to, t)),
+            _ => target,
+        }
+    }
+
+    fn same_kind(self, other: Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::Float(_), Self::Float(_)) | (Self::Vec4(_), Self::Vec4(_))
+        )
+    }
+}
+
+/// Easing from `from` to `to` over `duration`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Track {
+    from: ParamValue,
+    to: ParamValue,
+    elapsed: Duration,
+    duration: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Param {
+    value: ParamValue,
+    track: Option<Track>,
+}
+
+/// Parameters of every material, advanced by the render loop's time.
+#[derive(Debug, Clone, Default)]
+pub struct MaterialParams {
+    materials: BTreeMap<String, BTreeMap<String, Param>>,
+}
+
+impl MaterialParams {
+    /// Expose `name` on `material` at `value`, replacing a parameter of the same name.
+    pub fn register(&mut self, material: &str, name: &str, value: ParamValue) {
+        self.materials
+            .entry(material.to_string())
+            .or_default()
+            .insert(name.to_string(), Param { value, track: None });
+    }
+
+    /// Current value, part way through its animation.
+    pub fn get(&self, material: &str, name: &str) -> Result<ParamValue, PulsarError> {
+        Ok(self.param(material, name)?.value)
+    }
+
+    /// Whether the parameter is still easing toward its target.
+    pub fn is_animating(&self, material: &str, name: &str) -> bool {
+        self.param(material, name)
+            .is_ok_and(|param| param.track.is_some())
+    }
+
+    /// Ease `name` toward `value` over `over`, at once when zero. Replaces the animation running
+    /// on the parameter, from its current value.
+    pub fn set(
+        &mut self,
+        material: &str,
+        name: &str,
+        value: ParamValue,
+        over: Duration,
+    ) -> Result<(), Box<dyn Error>> {
+        let param = self.param_mut(material, name)?;
+        if !param.value.same_kind(value) {
+            return Err(
+                format!("{material}.{name} is a {:?}, not a {value:?}", param.value).into(),
+            );
+        }
+        if over.is_zero() {
+            param.value = value;
+            param.track = None;
+        } else {
+            param.track = Some(Track {
+                from: param.value,
+                to: value,
+                elapsed: Duration::ZERO,
+                duration: over,
+            });
+        }
+        Ok(())
+    }
+
+    /// Move every animation `delta` forward, smoothstep eased.
+    pub fn advance(&mut self, delta: Duration) {
+        let params = self.materials.values_mut().flat_map(BTreeMap::values_mut);
+        for param in params {
+            let Some(track) = &mut param.track else {
+                continue;
+            };
+            track.elapsed += delta;
+            if track.elapsed >= track.duration {
+                param.value = track.to;
+                param.track = None;
+            } else {
+                let t = track.elapsed.as_secs_f32() / track.duration.as_secs_f32();
+                param.value = track.from.lerp(track.to, t * t * (3.0 - 2.0 * t));
+            }
+        }
+    }
+
+    pub fn materials(&self) -> impl Iterator<Item = &str> {
+        self.materials.keys().map(String::as_str)
+    }
+
+    /// Parameters of `material` in alphabetical order, none for an unknown material.
+    pub fn names(&self, material: &str) -> Vec<&str> {
+        self.materials
+            .get(material)
+            .map(|params| params.keys().map(String::as_str).collect())
+            .unwrap_or_default()
+    }
+
+    /// Run a `set_material_param` command line.
+    pub fn execute(&mut self, line: &str) -> Result<(), Box<dyn Error>> {
+        let usage =
+            || format!("Usage: {SET_MATERIAL_PARAM} <material> <name> <value> [over <seconds>]");
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (material, name, value, over) = match words[..] {
+            [SET_MATERIAL_PARAM, material, name, value] => (material, name, value, None),
+            [SET_MATERIAL_PARAM, material, name, value, "over", seconds] => {
+                (material, name, value, Some(seconds))
+            }
+            _ => return Err(usage().into()),
+        };
+        let over = match over {
+            Some(seconds) => seconds
+                .parse::<f32>()
+                .ok()
+                .and_then(|seconds| Duration::try_from_secs_f32(seconds).ok())
+                .ok_or_else(|| format!("Duration {seconds} is not a positive number of seconds"))?,
+            None => Duration::ZERO,
+        };
+        self.set(material, name, ParamValue::parse(value)?, over)
+    }
+
+    /// Candidates for the last word of a partial command line, for tab completion.
+    pub fn complete(&self, line: &str) -> Vec<String> {
+        let mut words: Vec<&str> = line.split_whitespace().collect();
+        if line.is_empty() || line.ends_with(char::is_whitespace) {
+            words.push("");
+        }
+        let candidates: Vec<&str> = match words[..] {
+            [_] => vec![SET_MATERIAL_PARAM],
+            [SET_MATERIAL_PARAM, _] => self.materials().collect(),
+            [SET_MATERIAL_PARAM, material, _] => self.names(material),
+            [SET_MATERIAL_PARAM, _, _, _, _] => vec!["over"],
+            _ => Vec::new(),
+        };
+        let prefix = words.last().copied().unwrap_or_default();
+        candidates
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(prefix))
+            .map(str::to_string)
+            .collect()
+    }
+
+    fn param(&self, material: &str, name: &str) -> Result<&Param, PulsarError> {
+        self.materials
+            .get(material)
+            .and_then(|params| params.get(name))
+            .ok_or_else(|| self.unknown(material, name))
+    }
+
+    fn param_mut(&mut self, material: &str, name: &str) -> Result<&mut Param, PulsarError> {
+        self.param(material, name)?;
+        Ok(self
+            .materials
+            .get_mut(material)
+            .and_then(|params| params.get_mut(name))
+            .expect("Parameter looked up above"))
+    }
+
+    fn unknown(&self, material: &str, name: &str) -> PulsarError {
+        PulsarError::UnknownMaterialParam {
+            material: material.to_string(),
+            name: name.to_string(),
+            valid: self
+                .names(material)
+                .into_iter()
+                .map(str::to_string)
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params() -> MaterialParams {
+        let mut params = MaterialParams::default();
+        params.register("lava", "emissive", ParamValue::Float(1.0));
+        params.register("lava", "scroll_speed", ParamValue::Float(0.0));
+        params.register("lava", "tint", ParamValue::Vec4(Vec4::ONE));
+        params.register("water", "scroll_speed", ParamValue::Float(0.5));
+        params
+    }
+
+    fn assert_float(params: &MaterialParams, material: &str, name: &str, expected: f32) {
+        let value = params.get(material, name).unwrap();
+        assert!(
+            matches!(value, ParamValue::Float(value) if (value - expected).abs() < 1e-5),
+            "{material}.{name} is {value:?}, not {expected}"
+        );
+    }
+
+    #[test]
+    fn unknown_names_and_malformed_lines_are_refused() {
+        let mut params = params();
+        let err = params
+            .execute("set_material_param lava emisive 3")
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<PulsarError>(),
+            Some(&PulsarError::UnknownMaterialParam {
+                material: "lava".into(),
+                name: "emisive".into(),
+                valid: vec!["emissive".into(), "scroll_speed".into(), "tint".into()],
+            })
+        );
+        for line in [
+            "set_material_param lava tint 3",
+            "set_material_param lava emissive 1,2",
+            "set_material_param lava emissive 3 over -1",
+            "set_material_param lava emissive",
+        ] {
+            assert!(params.execute(line).is_err(), "`{line}` was accepted");
+        }
+    }
+
+    #[test]
+    fn values_snap_or_ease_with_a_smoothstep() {
+        let mut params = params();
+        params
+            .execute("set_material_param water scroll_speed 2")
+            .unwrap();
+        assert_float(&params, "water", "scroll_speed", 2.0);
+
+        // Halfway is halfway, smoothstep is symmetric
+        params
+            .execute("set_material_param lava emissive 3 over 2")
+            .unwrap();
+        params.advance(Duration::from_millis(500));
+        assert_float(&params, "lava", "emissive", 1.0 + 2.0 * 0.15625);
+        params.advance(Duration::from_millis(500));
+        assert_float(&params, "lava", "emissive", 2.0);
+        params.advance(Duration::from_millis(1500));
+        assert_float(&params, "lava", "emissive", 3.0);
+        assert!(!params.is_animating("lava", "emissive"));
+    }
+
+    #[test]
+    fn a_new_target_supersedes_the_running_animation() {
+        let mut params = params();
+        params
+            .execute("set_material_param lava tint 0,0,0,1 over 1")
+            .unwrap();
+        params.advance(Duration::from_millis(500));
+        params
+            .execute("set_material_param lava tint 1,0,0,1 over 1")
+            .unwrap();
+        params.advance(Duration::from_secs(1));
+        assert_eq!(
+            params.get("lava", "tint").unwrap(),
+            ParamValue::Vec4(Vec4::new(1.0, 0.0, 0.0, 1.0))
+        );
+    }
+
+    #[test]
+    fn each_word_completes() {
+        let params = params();
+        let completions = [
+            ("set_mat", vec!["set_material_param"]),
+            ("set_material_param ", vec!["lava", "water"]),
+            ("set_material_param w", vec!["water"]),
+            ("set_material_param lava s", vec!["scroll_speed"]),
+            ("set_material_param lava tint 1,1,1,1 o", vec!["over"]),
+        ];
+        for (line, expected) in completions {
+            assert_eq!(params.complete(line), expected, "`{line}`");
+        }
+    }
+}
@@ -1,34 +1,428 @@
-use std::time::{Duration, Instant};
+use crate::{
+    present_wait::{PacingHistogram, PresentTiming},
+    update_queue::UpdateCounters,
+    watchdog::FrameWaits,
+};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, RwLock, Weak,
+    },
+    time::{Duration, Instant},
+};
 
 const CYCLE_REPORT_INTERVAL: Duration = Duration::from_millis(1000);
+/// Frames taking longer are slow, blamed on the compositor or on the engine.
+pub const SLOW_FRAME_THRESHOLD: Duration = Duration::from_millis(50);
 
+/// Where a frame's time went, decided once it ends.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FrameClass {
+    #[default]
+    OnTime,
+    /// Over the threshold with most of the time spent outside acquire and present.
+    EngineSlow,
+    /// Over the threshold with most of the time spent in acquire and present, a compositor
+    /// stall or a UAC prompt rather than the engine.
+    ExternalStall,
+}
+
+impl FrameClass {
+    pub fn classify(frame_time: Duration, waits: FrameWaits, threshold: Duration) -> Self {
+        if frame_time <= threshold {
+            Self::OnTime
+        } else if waits.compositor() * 2 > frame_time {
+            Self::ExternalStall
+        } else {
+            Self::EngineSlow
+        }
+    }
+}
+
+/// Queue a submission or a GPU timestamp pair ran on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QueueKind {
+    Graphics,
+    Transfer,
+    Compute,
+}
+
+impl QueueKind {
+    pub const ALL: [Self; 3] = [Self::Graphics, Self::Transfer, Self::Compute];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Graphics => "graphics",
+            Self::Transfer => "transfer",
+            Self::Compute => "compute",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// GPU work between a timestamp pair, in nanoseconds of the time domain shared by every queue
+/// of the device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueSpan {
+    pub queue: QueueKind,
+    pub start: u64,
+    pub end: u64,
+}
+
+impl QueueSpan {
+    pub fn duration(&self) -> Duration {
+        Duration::from_nanos(self.end.saturating_sub(self.start))
+    }
+}
+
+/// Time each queue was busy, and how much of the transfer and compute work ran while graphics
+/// was busy too.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueueOverlap {
+    /// By [`QueueKind`], spans of one queue overlapping each other counted once.
+    pub busy: [Duration; 3],
+    /// Transfer or compute busy, whichever.
+    pub async_busy: Duration,
+    /// Graphics busy while transfer or compute was.
+    pub overlap: Duration,
+}
+
+impl QueueOverlap {
+    pub fn from_spans(spans: &[QueueSpan]) -> Self {
+        let graphics = busy_intervals(spans, |queue| queue == QueueKind::Graphics);
+        let asynchronous = busy_intervals(spans, |queue| queue != QueueKind::Graphics);
+        let busy = QueueKind::ALL.map(|kind| total(&busy_intervals(spans, |queue| queue == kind)));
+        // Both lists are sorted and disjoint, walk them together
+        let (mut overlap, mut g, mut a) = (0, 0, 0);
+        while g < graphics.len() && a < asynchronous.len() {
+            let (start, end) = (
+                graphics[g].0.max(asynchronous[a].0),
+                graphics[g].1.min(asynchronous[a].1),
+            );
+            overlap += end.saturating_sub(start);
+            if graphics[g].1 < asynchronous[a].1 {
+                g += 1;
+            } else {
+                a += 1;
+            }
+        }
+        Self {
+            busy,
+            async_busy: total(&asynchronous),
+            overlap: Duration::from_nanos(overlap),
+        }
+    }
+
+    /// Share of the transfer and compute time hidden under graphics work, `None` without any.
+    pub fn async_efficiency(&self) -> Option<f64> {
+        (!self.async_busy.is_zero())
+            .then(|| self.overlap.as_secs_f64() / self.async_busy.as_secs_f64())
+    }
+
+    fn add(&mut self, other: &Self) {
+        for (busy, other) in self.busy.iter_mut().zip(other.busy) {
+            *busy += other;
+        }
+        self.async_busy += other.async_busy;
+        self.overlap += other.overlap;
+    }
+}
+
+/// Union of the spans of the queues picked, sorted and merged.
+fn busy_intervals(spans: &[QueueSpan], picked: impl Fn(QueueKind) -> bool) -> Vec<(u64, u64)> {
+    let mut intervals: Vec<(u64, u64)> = spans
+        .iter()
+        .filter(|span| picked(span.queue) && span.end > span.start)
+        .map(|span| (span.start, span.end))
+        .collect();
+    intervals.sort_unstable();
+    let mut merged: Vec<(u64, u64)> = Vec::with_capacity(intervals.len());
+    for (start, end) in intervals {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+fn total(intervals: &[(u64, u64)]) -> Duration {
+    Duration::from_nanos(intervals.iter().map(|(start, end)| end - start).sum())
+}
+
+/// Counters since the renderer started, never reset.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameCounters {
+    pub frames: u64,
+    pub draw_calls: u64,
+    /// Descriptor set binds recorded, one per frame plus one per change of material set.
+    pub descriptor_binds: u64,
+    pub swapchain_recreations: u64,
+    /// Frames abandoned because the swapchain went out of date.
+    pub dropped_frames: u64,
+    pub device_lost_recoveries: u64,
+    /// GPU copies destroyed to stay within the memory budget.
+    pub residency_evictions: u64,
+    pub residency_reloads: u64,
+    /// Slow frames held by acquire or present, see [`FrameClass`].
+    pub external_stalls: u64,
+    pub engine_slow_frames: u64,
+    /// Time blocked in each wait site, summed over every frame.
+    pub waits: FrameWaitTotals,
+    /// Command buffers submitted, by [`QueueKind`].
+    pub queue_submissions: [u64; 3],
+    /// GPU time of each queue, summed over every frame with timestamps.
+    pub queue_busy: [Duration; 3],
+    /// Mesh updates applied, merged and refused, see [`crate::update_queue`].
+    pub mesh_updates: UpdateCounters,
+}
+
+/// [`FrameWaits`] summed over frames.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameWaitTotals {
+    pub acquire: Duration,
+    pub fences: Duration,
+    pub present: Duration,
+    pub pacing: Duration,
+}
+
+/// Frame times over the current report interval.
 #[derive(Debug)]
-pub struct Metrics {
+pub struct WindowedStats {
     pub start: Instant,
-    pub cycle_start: Instant,
+    pub frame_times: Vec<Duration>,
+    /// Of the frames with timestamps on more than one queue.
+    pub queue_overlap: QueueOverlap,
+    /// Between presents waited for, see [`crate::present_wait`].
+    pub present_intervals: Vec<Duration>,
+    /// From input sampling to present, of the same frames.
+    pub input_latencies: Vec<Duration>,
+}
+
+impl WindowedStats {
+    fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            frame_times: Vec::new(),
+            queue_overlap: QueueOverlap::default(),
+            present_intervals: Vec::new(),
+            input_latencies: Vec::new(),
+        }
+    }
+
+    pub fn slowest(&self) -> Duration {
+        self.frame_times.iter().max().copied().unwrap_or_default()
+    }
+
+    pub fn fastest(&self) -> Duration {
+        self.frame_times.iter().min().copied().unwrap_or_default()
+    }
+
+    pub fn mean(&self) -> Duration {
+        let total: Duration = self.frame_times.iter().sum();
+        total / self.frame_times.len().max(1) as u32
+    }
+
+    /// Nearest rank percentile, `p` in `[0, 1]`.
+    pub fn percentile(&self, p: f64) -> Duration {
+        if self.frame_times.is_empty() {
+            return Duration::ZERO;
+        }
+        let mut sorted = self.frame_times.clone();
+        sorted.sort_unstable();
+        let rank = ((p * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len());
+        sorted[rank - 1]
+    }
+
+    /// Mean of the present intervals, `None` without present wait pacing.
+    pub fn present_interval(&self) -> Option<Duration> {
+        mean(&self.present_intervals)
+    }
+
+    pub fn input_latency(&self) -> Option<Duration> {
+        mean(&self.input_latencies)
+    }
+
+    pub fn fps(&self) -> f64 {
+        self.frame_times.len() as f64 / self.start.elapsed().as_secs_f64()
+    }
+}
+
+fn mean(durations: &[Duration]) -> Option<Duration> {
+    (!durations.is_empty()).then(|| durations.iter().sum::<Duration>() / durations.len() as u32)
+}
+
+/// Last published state of a renderer, readable from any thread.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MetricsSnapshot {
+    /// Identifies the renderer, in creation order.
+    pub id: u64,
+    pub counters: FrameCounters,
+    pub fps: f64,
+    pub frame_time_p50: Duration,
+    pub frame_time_p90: Duration,
+    pub frame_time_p99: Duration,
+    pub pipelines_outstanding: usize,
+    pub pipelines_completed: usize,
+    pub replay_bytes: usize,
+    pub resident_bytes: u64,
+    pub memory_budget_bytes: u64,
+    /// Share of the transfer and compute GPU time overlapping graphics over the last report
+    /// interval, `None` while everything runs on the graphics queue.
+    pub async_efficiency: Option<f64>,
+    /// Mean interval between presents over the last report interval, `None` unless frames are
+    /// paced on `VK_KHR_present_wait`.
+    pub present_interval: Option<Duration>,
+    /// Mean time from input sampling to present of the same frames.
+    pub input_latency: Option<Duration>,
+}
+
+static PUBLISHED: Mutex<Vec<Weak<RwLock<MetricsSnapshot>>>> = Mutex::new(Vec::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+static BUFFER_ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+/// Device memory allocations made for buffers by every renderer so far, freed ones included.
+pub fn buffer_allocations() -> u64 {
+    BUFFER_ALLOCATIONS.load(Ordering::Relaxed)
+}
+
+pub(crate) fn count_buffer_allocation() {
+    BUFFER_ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+}
+
+static DEDUP_HITS: AtomicU64 = AtomicU64::new(0);
+static DEDUP_BYTES_SAVED: AtomicU64 = AtomicU64::new(0);
+
+/// Loads answered with an asset already held, see `assets`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DedupStats {
+    pub hits: u64,
+    /// Texels and GPU mesh data not copied again thanks to the hits.
+    pub bytes_saved: u64,
+}
+
+/// Textures and meshes deduplicated by every library and renderer so far.
+pub fn dedup_stats() -> DedupStats {
+    DedupStats {
+        hits: DEDUP_HITS.load(Ordering::Relaxed),
+        bytes_saved: DEDUP_BYTES_SAVED.load(Ordering::Relaxed),
+    }
+}
+
+pub(crate) fn count_dedup_hit(bytes: u64) {
+    DEDUP_HITS.fetch_add(1, Ordering::Relaxed);
+    DEDUP_BYTES_SAVED.fetch_add(bytes, Ordering::Relaxed);
+}
+
+static ALIASED_ATTACHMENTS: AtomicU64 = AtomicU64::new(0);
+static ALIASING_BYTES_SAVED: AtomicU64 = AtomicU64::new(0);
+
+/// Transient attachments placed in memory shared with others, see `transient`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AliasingStats {
+    pub attachments: u64,
+    /// Device memory not allocated thanks to the sharing, summed over every allocation.
+    pub bytes_saved: u64,
+}
+
+/// Attachments aliased by every renderer so far.
+pub fn aliasing_stats() -> AliasingStats {
+    AliasingStats {
+        attachments: ALIASED_ATTACHMENTS.load(Ordering::Relaxed),
+        bytes_saved: ALIASING_BYTES_SAVED.load(Ordering::Relaxed),
+    }
+}
+
+pub(crate) fn count_aliasing(attachments: u64, bytes_saved: u64) {
+    ALIASED_ATTACHMENTS.fetch_add(attachments, Ordering::Relaxed);
+    ALIASING_BYTES_SAVED.fetch_add(bytes_saved, Ordering::Relaxed);
+}
+
+static STARTUP_PHASES: Mutex<Vec<(&'static str, Duration)>> = Mutex::new(Vec::new());
+
+/// Time spent in each phase of renderer creation so far, timed by [`stopwatch!`] in the order the
+/// phases ran. The factory's phases are recorded once, every window adds its own.
+pub fn startup_phases() -> Vec<(&'static str, Duration)> {
+    STARTUP_PHASES.lock().unwrap().clone()
+}
+
+/// Log `phases` as one breakdown and keep them for [`startup_phases`].
+pub(crate) fn record_startup_phases(name: &str, phases: Vec<(&'static str, Duration)>) {
+    let total: Duration = phases.iter().map(|(_, elapsed)| *elapsed).sum();
+    let breakdown: Vec<String> = phases
+        .iter()
+        .map(|(label, elapsed)| format!("{label} {:.1}ms", elapsed.as_secs_f64() * 1000.0))
+        .collect();
+    log::info!(
+        "{name} created in {:.1}ms: {}",
+        total.as_secs_f64() * 1000.0,
+        breakdown.join(", ")
+    );
+    STARTUP_PHASES.lock().unwrap().extend(phases);
+}
+
+/// Snapshots of every live renderer, published once per report interval.
+pub fn snapshots() -> Vec<MetricsSnapshot> {
+    let mut published = PUBLISHED.lock().unwrap();
+    published.retain(|snapshot| snapshot.strong_count() > 0);
+    published
+        .iter()
+        .filter_map(Weak::upgrade)
+        .map(|snapshot| *snapshot.read().unwrap())
+        .collect()
+}
+
+#[derive(Debug)]
+pub struct Metrics {
     pub frame_start: Instant,
     pub frame_end: Instant,
-    pub slowest_render: Duration,
-    pub fastest_render: Duration,
-    pub total_render: Duration,
-    pub total_frames: u32,
     pub delta_end_to_start: Duration,
     pub delta_start_to_start: Duration,
+    pub counters: FrameCounters,
+    pub window: WindowedStats,
+    pub pipelines_outstanding: usize,
+    pub pipelines_completed: usize,
+    pub replay_bytes: usize,
+    /// GPU memory tracked by the residency manager.
+    pub resident_bytes: u64,
+    pub memory_budget_bytes: u64,
+    /// Blocked time of the current frame, set before [`Self::end_frame`].
+    pub waits: FrameWaits,
+    pub slow_frame_threshold: Duration,
+    /// Class of the last ended frame.
+    pub last_class: FrameClass,
+    /// Start to start delta of the last frame that did not follow an external stall.
+    steady_delta: Duration,
+    published: Arc<RwLock<MetricsSnapshot>>,
 }
 
 impl Default for Metrics {
     fn default() -> Self {
+        let published = Arc::new(RwLock::new(MetricsSnapshot {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            ..Default::default()
+        }));
+        PUBLISHED.lock().unwrap().push(Arc::downgrade(&published));
         Self {
-            start: Instant::now(),
-            cycle_start: Instant::now(),
             frame_start: Instant::now(),
             frame_end: Instant::now(),
-            slowest_render: Duration::from_secs(0),
-            fastest_render: Duration::from_secs(30),
-            total_render: Duration::from_secs(0),
-            total_frames: 0,
             delta_end_to_start: Duration::from_secs(0),
             delta_start_to_start: Duration::from_secs(0),
+            counters: FrameCounters::default(),
+            window: WindowedStats::new(),
+            pipelines_outstanding: 0,
+            pipelines_completed: 0,
+            replay_bytes: 0,
+            resident_bytes: 0,
+            memory_budget_bytes: 0,
+            waits: FrameWaits::default(),
+            slow_frame_threshold: SLOW_FRAME_THRESHOLD,
+            last_class: FrameClass::OnTime,
+            steady_delta: Duration::ZERO,
+            published,
         }
     }
 }
@@ -38,39 +432,142 @@ impl Metrics {
         self.delta_end_to_start = self.frame_end.elapsed();
         self.delta_start_to_start = self.frame_start.elapsed();
         self.frame_start = Instant::now();
+        if self.last_class != FrameClass::ExternalStall {
+            self.steady_delta = self.delta_start_to_start;
+        }
     }
 
-    pub fn end_frame(&mut self) {
-        self.total_frames += 1;
-        let elapsed_time = self.frame_start.elapsed();
-        self.total_render += elapsed_time;
+    /// Delta to advance animation by. Right after an external stall and with `clamp` it is the
+    /// last steady delta, the time the compositor held the frame is skipped instead of caught up.
+    pub fn simulation_delta(&self, clamp: bool) -> Duration {
+        if clamp && self.last_class == FrameClass::ExternalStall {
+            self.steady_delta
+        } else {
+            self.delta_start_to_start
+        }
+    }
+
+    pub fn count_submissions(&mut self, queue: QueueKind, submissions: u64) {
+        self.counters.queue_submissions[queue.index()] += submissions;
+    }
 
-        if elapsed_time > self.slowest_render {
-            self.slowest_render = elapsed_time;
+    /// Timestamp pairs read back this frame, tagged with their queue.
+    pub fn record_queue_spans(&mut self, spans: &[QueueSpan]) {
+        // A single queue has nothing to overlap with
+        if spans.iter().all(|span| span.queue == QueueKind::Graphics) {
+            for span in spans {
+                self.counters.queue_busy[QueueKind::Graphics.index()] += span.duration();
+            }
+            return;
         }
-        if elapsed_time < self.fastest_render {
-            self.fastest_render = elapsed_time;
+        let overlap = QueueOverlap::from_spans(spans);
+        for (busy, frame_busy) in self.counters.queue_busy.iter_mut().zip(overlap.busy) {
+            *busy += frame_busy;
         }
+        self.window.queue_overlap.add(&overlap);
+    }
 
-        if self.cycle_start.elapsed() > CYCLE_REPORT_INTERVAL {
+    /// A present waited for reached the screen.
+    pub fn record_present_timing(&mut self, timing: PresentTiming) {
+        if let Some(interval) = timing.present_interval {
+            self.window.present_intervals.push(interval);
+        }
+        self.window.input_latencies.push(timing.input_latency);
+    }
+
+    pub fn end_frame(&mut self) {
+        let frame_time = self.frame_start.elapsed();
+        self.counters.frames += 1;
+        self.window.frame_times.push(frame_time);
+
+        let waits = std::mem::take(&mut self.waits);
+        self.counters.waits.acquire += waits.acquire;
+        self.counters.waits.fences += waits.fences;
+        self.counters.waits.present += waits.present;
+        self.counters.waits.pacing += waits.pacing;
+        self.last_class = FrameClass::classify(frame_time, waits, self.slow_frame_threshold);
+        match self.last_class {
+            FrameClass::OnTime => {}
+            FrameClass::EngineSlow => self.counters.engine_slow_frames += 1,
+            FrameClass::ExternalStall => {
+                self.counters.external_stalls += 1;
+                log::warn!(
+                    "Frame took {frame_time:?}, {:?} in acquire and {:?} in present",
+                    waits.acquire,
+                    waits.present
+                );
+            }
+        }
+
+        if self.window.start.elapsed() > CYCLE_REPORT_INTERVAL {
             log::info!(
-                "ΔEndStart {:?} Max(RenderTime) {:?} Min(RenderTime) {:?} x̄ {:?} t {} / {:?}s",
+                "ΔEndStart {:?} Max(RenderTime) {:?} Min(RenderTime) {:?} x̄ {:?} t {} / {:?}s pipelines {}/{}",
                 self.delta_end_to_start,
-                self.slowest_render,
-                self.fastest_render,
-                self.total_render / self.total_frames,
-                self.total_frames,
-                CYCLE_REPORT_INTERVAL.as_secs_f64()
+                self.window.slowest(),
+                self.window.fastest(),
+                self.window.mean(),
+                self.window.frame_times.len(),
+                CYCLE_REPORT_INTERVAL.as_secs_f64(),
+                self.pipelines_completed,
+                self.pipelines_completed + self.pipelines_outstanding
             );
-            *self = Self::default();
+            if let Some(efficiency) = self.window.queue_overlap.async_efficiency() {
+                let busy = self.window.queue_overlap.busy;
+                log::info!(
+                    "GPU busy graphics {:?} transfer {:?} compute {:?}, async efficiency {:.0}%",
+                    busy[QueueKind::Graphics.index()],
+                    busy[QueueKind::Transfer.index()],
+                    busy[QueueKind::Compute.index()],
+                    efficiency * 100.0
+                );
+            }
+            if let (Some(interval), Some(latency)) =
+                (self.window.present_interval(), self.window.input_latency())
+            {
+                log::info!(
+                    "Present interval x̄ {interval:?} input latency x̄ {latency:?}, pacing {}",
+                    PacingHistogram::from_intervals(&self.window.present_intervals).summary()
+                );
+            }
+            self.publish();
+            // Only the windowed statistics restart, counters keep accumulating
+            self.window = WindowedStats::new();
         }
 
         self.frame_end = Instant::now();
     }
+
+    fn publish(&self) {
+        let mut published = self.published.write().unwrap();
+        *published = MetricsSnapshot {
+            id: published.id,
+            counters: self.counters,
+            fps: self.window.fps(),
+            frame_time_p50: self.window.percentile(0.5),
+            frame_time_p90: self.window.percentile(0.9),
+            frame_time_p99: self.window.percentile(0.99),
+            pipelines_outstanding: self.pipelines_outstanding,
+            pipelines_completed: self.pipelines_completed,
+            replay_bytes: self.replay_bytes,
+            resident_bytes: self.resident_bytes,
+            memory_budget_bytes: self.memory_budget_bytes,
+            async_efficiency: self.window.queue_overlap.async_efficiency(),
+            present_interval: self.window.present_interval(),
+            input_latency: self.window.input_latency(),
+        };
+    }
 }
 
+/// Time `$func`, logged at debug level, or pushed as `($label, elapsed)` onto the `$phases` vector.
 #[macro_export]
 macro_rules! stopwatch {
+    ($phases:expr, $label:expr, $func:expr) => {{
+        let start_time = std::time::Instant::now();
+        let result = $func;
+        $phases.push(($label, start_time.elapsed()));
+
+        result
+    }};
     ($func:expr) => {{
         use std::time::{Duration, Instant};
 
@@ -82,3 +579,183 @@ macro_rules! stopwatch {
         result
     }};
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::watchdog::{Heartbeat, WaitSite};
+    use std::thread;
+
+    const STALL: Duration = Duration::from_millis(80);
+
+    #[test]
+    fn classify_by_where_the_time_went() {
+        let threshold = SLOW_FRAME_THRESHOLD;
+        let waits = |acquire_ms, present_ms, fences_ms| FrameWaits {
+            acquire: Duration::from_millis(acquire_ms),
+            present: Duration::from_millis(present_ms),
+            fences: Duration::from_millis(fences_ms),
+            ..Default::default()
+        };
+        let frame = Duration::from_millis(100);
+        assert_eq!(
+            FrameClass::classify(threshold, waits(50, 0, 0), threshold),
+            FrameClass::OnTime
+        );
+        assert_eq!(
+            FrameClass::classify(frame, waits(40, 20, 0), threshold),
+            FrameClass::ExternalStall
+        );
+        // Half is not most, nor is the GPU finishing the engine's own work
+        assert_eq!(
+            FrameClass::classify(frame, waits(25, 25, 0), threshold),
+            FrameClass::EngineSlow
+        );
+        assert_eq!(
+            FrameClass::classify(frame, waits(0, 0, 90), threshold),
+            FrameClass::EngineSlow
+        );
+    }
+
+    /// One fake frame going through the wait sites of a real one, `work` spent outside of them.
+    fn frame(metrics: &mut Metrics, heartbeat: &Heartbeat, work: Duration) -> FrameClass {
+        metrics.start_frame();
+        for site in [
+            WaitSite::AcquireImage,
+            WaitSite::DrawFence,
+            WaitSite::Present,
+        ] {
+            drop(heartbeat.waiting(site));
+        }
+        thread::sleep(work);
+        metrics.waits = heartbeat.take_waits();
+        metrics.end_frame();
+        metrics.last_class
+    }
+
+    /// Delays injected at each wait site in turn are blamed on the right side.
+    #[cfg(debug_assertions)]
+    #[test]
+    fn injected_stalls_are_attributed() {
+        let heartbeat = Heartbeat::default();
+        let mut metrics = Metrics::default();
+        assert_eq!(
+            frame(&mut metrics, &heartbeat, Duration::ZERO),
+            FrameClass::OnTime
+        );
+        let cases = [
+            (Some(WaitSite::AcquireImage), FrameClass::ExternalStall),
+            (Some(WaitSite::Present), FrameClass::ExternalStall),
+            (Some(WaitSite::DrawFence), FrameClass::EngineSlow),
+            (None, FrameClass::EngineSlow),
+        ];
+        for (site, expected) in cases {
+            let work = match site {
+                Some(site) => {
+                    heartbeat.inject_delay(site, STALL);
+                    Duration::ZERO
+                }
+                None => STALL,
+            };
+            let class = frame(&mut metrics, &heartbeat, work);
+            if let Some(site) = site {
+                heartbeat.inject_delay(site, Duration::ZERO);
+            }
+            assert_eq!(class, expected, "{site:?}");
+        }
+        let counters = metrics.counters;
+        assert_eq!(
+            (counters.external_stalls, counters.engine_slow_frames),
+            (2, 2)
+        );
+        assert!(counters.waits.acquire >= STALL);
+        assert!(counters.waits.present >= STALL);
+        assert!(counters.waits.fences >= STALL);
+    }
+
+    /// The frame after a stall animates by the steady delta, not by the stall.
+    #[cfg(debug_assertions)]
+    #[test]
+    fn delta_after_a_stall_is_clamped() {
+        let heartbeat = Heartbeat::default();
+        let mut metrics = Metrics::default();
+        frame(&mut metrics, &heartbeat, Duration::ZERO);
+        frame(&mut metrics, &heartbeat, Duration::ZERO);
+        heartbeat.inject_delay(WaitSite::Present, STALL);
+        frame(&mut metrics, &heartbeat, Duration::ZERO);
+        heartbeat.inject_delay(WaitSite::Present, Duration::ZERO);
+        metrics.start_frame();
+        assert!(metrics.simulation_delta(false) >= STALL);
+        assert!(metrics.simulation_delta(true) < STALL);
+    }
+
+    const MS: u64 = 1_000_000;
+
+    fn span(queue: QueueKind, start_ms: u64, end_ms: u64) -> QueueSpan {
+        QueueSpan {
+            queue,
+            start: start_ms * MS,
+            end: end_ms * MS,
+        }
+    }
+
+    #[test]
+    fn async_efficiency_follows_the_overlap() {
+        use QueueKind::{Compute, Graphics, Transfer};
+        // Spans, expected async efficiency in percent
+        let cases = [
+            (
+                "full overlap",
+                vec![span(Graphics, 0, 10), span(Transfer, 2, 6)],
+                Some(100.0),
+            ),
+            (
+                "zero overlap",
+                vec![span(Graphics, 0, 4), span(Transfer, 4, 8)],
+                Some(0.0),
+            ),
+            (
+                "partial overlap",
+                vec![span(Graphics, 0, 6), span(Transfer, 4, 8)],
+                Some(50.0),
+            ),
+            (
+                // Transfer and compute overlapping each other count once
+                "two async queues",
+                vec![
+                    span(Graphics, 0, 5),
+                    span(Graphics, 10, 15),
+                    span(Transfer, 3, 12),
+                    span(Compute, 8, 12),
+                ],
+                Some(400.0 / 9.0),
+            ),
+            (
+                "graphics only",
+                vec![span(Graphics, 0, 10), span(Graphics, 5, 12)],
+                None,
+            ),
+        ];
+        for (name, spans, expected) in cases {
+            let efficiency = QueueOverlap::from_spans(&spans)
+                .async_efficiency()
+                .map(|share| share * 100.0);
+            match (efficiency, expected) {
+                (Some(actual), Some(expected)) => {
+                    assert!((actual - expected).abs() < 1e-6, "{name}: {actual}")
+                }
+                (actual, expected) => assert_eq!(actual, expected, "{name}"),
+            }
+        }
+        let merged = QueueOverlap::from_spans(&[span(Graphics, 0, 10), span(Graphics, 5, 12)]);
+        assert_eq!(merged.busy[0], Duration::from_millis(12));
+    }
+
+    #[test]
+    fn a_single_queue_skips_the_overlap_estimate() {
+        let mut metrics = Metrics::default();
+        metrics.record_queue_spans(&[span(QueueKind::Graphics, 0, 3)]);
+        assert_eq!(metrics.window.queue_overlap, QueueOverlap::default());
+        assert_eq!(metrics.counters.queue_busy[0], Duration::from_millis(3));
+    }
+}
@@ -0,0 +1,528 @@
+//! Prometheus text exposition of the published frame statistics, the render threads are never touched.
+use crate::{
+    flight_recorder::{self, DumpReason},
+    metrics::{self, MetricsSnapshot, QueueKind},
+};
+use log::{info, warn};
+use std::{
+    fmt::Write as _,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+/// For the whole request, however slowly it trickles in, and for each write of the response.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(2);
+/// Request line and headers, more is answered 431.
+const MAX_REQUEST_BYTES: u64 = 8 * 1024;
+/// Connections answered at once, each on its own thread. Others are closed unanswered.
+const MAX_CONNECTIONS: usize = 4;
+
+type Field<T> = fn(&MetricsSnapshot) -> T;
+
+const QUANTILES: [(&str, Field<Duration>); 3] = [
+    ("0.5", |snapshot| snapshot.frame_time_p50),
+    ("0.9", |snapshot| snapshot.frame_time_p90),
+    ("0.99", |snapshot| snapshot.frame_time_p99),
+];
+
+const WAIT_SITES: [(&str, Field<Duration>); 4] = [
+    ("acquire", |snapshot| snapshot.counters.waits.acquire),
+    ("fences", |snapshot| snapshot.counters.waits.fences),
+    ("present", |snapshot| snapshot.counters.waits.present),
+    ("pacing", |snapshot| snapshot.counters.waits.pacing),
+];
+
+/// HTTP listener answering `GET /metrics` and `GET /decisions`, stops when dropped.
+pub struct MetricsEndpoint {
+    local_addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl MetricsEndpoint {
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let thread = thread::Builder::new()
+            .name("pulsar-metrics".to_string())
+            .spawn(move || {
+                let active = Arc::new(AtomicUsize::new(0));
+                for stream in listener.incoming() {
+                    if thread_stop.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    match stream {
+                        Ok(stream) => serve(stream, &active),
+                        Err(err) => warn!("Metrics scrape failed: {err}"),
+                    }
+                }
+            })?;
+        info!("Serving metrics on http://{local_addr}/metrics");
+        Ok(Self {
+            local_addr,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl Drop for MetricsEndpoint {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // Wake the listener blocked in accept
+        let mut wake_addr = self.local_addr;
+        if wake_addr.ip().is_unspecified() {
+            wake_addr.set_ip(match wake_addr {
+                SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+                SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+            });
+        }
+        let _ = TcpStream::connect_timeout(&wake_addr, CLIENT_TIMEOUT);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Answer `stream` on a thread of its own, the accept loop never waits on a client. Past
+/// [`MAX_CONNECTIONS`] at once the connection is closed.
+fn serve(stream: TcpStream, active: &Arc<AtomicUsize>) {
+    if active.fetch_add(1, Ordering::AcqRel) >= MAX_CONNECTIONS {
+        active.fetch_sub(1, Ordering::AcqRel);
+        warn!("Metrics scrape refused, {MAX_CONNECTIONS} already being answered");
+        return;
+    }
+    let active = active.clone();
+    let spawned = thread::Builder::new()
+        .name("pulsar-metrics-client".to_string())
+        .spawn(move || {
+            if let Err(err) = respond(stream) {
+                warn!("Metrics scrape failed: {err}");
+            }
+            active.fetch_sub(1, Ordering::AcqRel);
+        });
+    if let Err(err) = spawned {
+        warn!("Metrics scrape failed: {err}");
+    }
+}
+
+/// Reads the stream until `deadline`, past it every read times out.
+struct DeadlineReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(remaining))?;
+        self.stream.read(buf)
+    }
+}
+
+fn respond(stream: TcpStream) -> io::Result<()> {
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let deadline = DeadlineReader {
+        stream: &stream,
+        deadline: Instant::now() + CLIENT_TIMEOUT,
+    };
+    let mut reader = BufReader::new(deadline.take(MAX_REQUEST_BYTES));
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Headers are not needed, read them so the client does not get a reset
+    let mut header = String::new();
+    let mut complete = false;
+    while reader.read_line(&mut header)? > 0 {
+        if header.trim_end().is_empty() {
+            complete = true;
+            break;
+        }
+        header.clear();
+    }
+    let too_large = !complete && reader.get_ref().limit() == 0;
+
+    let mut parts = request_line.split_whitespace();
+    let (status, body) = match (parts.next(), parts.next()) {
+        _ if too_large => ("431 Request Header Fields Too Large", String::new()),
+        (Some("GET"), Some("/metrics")) => ("200 OK", encode(&metrics::snapshots())),
+        (Some("GET"), Some("/decisions")) => ("200 OK", decisions()),
+        (Some("GET"), Some(_)) => (
+            "404 Not Found",
+            "Not found, try /metrics or /decisions\n".to_string(),
+        ),
+        _ => ("405 Method Not Allowed", String::new()),
+    };
+    let mut stream = &stream;
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {CONTENT_TYPE}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+/// Flight recorder dumps of every window, separated by a blank line.
+fn decisions() -> String {
+    flight_recorder::recorders()
+        .iter()
+        .map(|recorder| recorder.dump(&DumpReason::Requested))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn family(
+    out: &mut String,
+    name: &str,
+    kind: &str,
+    help: &str,
+    snapshots: &[MetricsSnapshot],
+    value: impl Fn(&MetricsSnapshot) -> f64,
+) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+    for snapshot in snapshots {
+        let _ = writeln!(
+            out,
+            "{name}{{window=\"{}\"}} {}",
+            snapshot.id,
+            value(snapshot)
+        );
+    }
+}
+
+/// Prometheus text format, one series per renderer labeled by its id.
+pub fn encode(snapshots: &[MetricsSnapshot]) -> String {
+    let mut out = String::new();
+    #[rustfmt::skip]
+    let counters: [(&str, &str, Field<u64>); 13] = [
+        ("pulsar_frames_total", "Frames rendered.", |s| s.counters.frames),
+        ("pulsar_draw_calls_total", "Draw calls recorded.", |s| s.counters.draw_calls),
+        ("pulsar_descriptor_binds_total", "Descriptor set binds recorded.", |s| s.counters.descriptor_binds),
+        ("pulsar_swapchain_recreations_total", "Swapchain recreations.", |s| s.counters.swapchain_recreations),
+        ("pulsar_dropped_frames_total", "Frames abandoned on an out of date swapchain.", |s| s.counters.dropped_frames),
+        ("pulsar_device_lost_recoveries_total", "Recoveries from a lost device.", |s| s.counters.device_lost_recoveries),
+        ("pulsar_residency_evictions_total", "GPU copies evicted to stay within the memory budget.", |s| s.counters.residency_evictions),
+        ("pulsar_residency_reloads_total", "Evicted GPU copies reloaded on use.", |s| s.counters.residency_reloads),
+        ("pulsar_external_stalls_total", "Slow frames held by acquire or present.", |s| s.counters.external_stalls),
+        ("pulsar_engine_slow_frames_total", "Slow frames spent outside acquire and present.", |s| s.counters.engine_slow_frames),
+        ("pulsar_mesh_updates_processed_total", "Mesh updates applied by the render thread.", |s| s.counters.mesh_updates.processed),
+        ("pulsar_mesh_updates_coalesced_total", "Mesh updates merged into one still queued.", |s| s.counters.mesh_updates.coalesced),
+        ("pulsar_mesh_updates_dropped_total", "Mesh updates refused by a full queue.", |s| s.counters.mesh_updates.dropped),
+    ];
+    for (name, help, value) in counters {
+        family(&mut out, name, "counter", help, snapshots, |s| {
+            value(s) as f64
+        });
+    }
+    family(
+        &mut out,
+        "pulsar_fps",
+        "gauge",
+        "Frames per second over the last report interval.",
+        snapshots,
+        |s| s.fps,
+    );
+    family(
+        &mut out,
+        "pulsar_replay_bytes",
+        "gauge",
+        "Memory held by the replay buffer.",
+        snapshots,
+        |s| s.replay_bytes as f64,
+    );
+    family(
+        &mut out,
+        "pulsar_pipelines_outstanding",
+        "gauge",
+        "Pipelines still building in the background.",
+        snapshots,
+        |s| s.pipelines_outstanding as f64,
+    );
+    family(
+        &mut out,
+        "pulsar_resident_bytes",
+        "gauge",
+        "GPU memory tracked by the residency manager.",
+        snapshots,
+        |s| s.resident_bytes as f64,
+    );
+    family(
+        &mut out,
+        "pulsar_memory_budget_bytes",
+        "gauge",
+        "Device local memory budget the residency manager evicts against.",
+        snapshots,
+        |s| s.memory_budget_bytes as f64,
+    );
+
+    let name = "pulsar_frame_time_seconds";
+    let _ = writeln!(
+        out,
+        "# HELP {name} CPU frame time quantiles over the last report interval."
+    );
+    let _ = writeln!(out, "# TYPE {name} gauge");
+    for snapshot in snapshots {
+        for (quantile, value) in QUANTILES {
+            let _ = writeln!(
+                out,
+                "{name}{{window=\"{}\",quantile=\"{quantile}\"}} {}",
+                snapshot.id,
+                value(snapshot).as_secs_f64()
+            );
+        }
+    }
+
+    let name = "pulsar_wait_seconds_total";
+    let _ = writeln!(
+        out,
+        "# HELP {name} Time the render thread spent blocked, by what it waited on."
+    );
+    let _ = writeln!(out, "# TYPE {name} counter");
+    for snapshot in snapshots {
+        for (site, value) in WAIT_SITES {
+            let _ = writeln!(
+                out,
+                "{name}{{window=\"{}\",site=\"{site}\"}} {}",
+                snapshot.id,
+                value(snapshot).as_secs_f64()
+            );
+        }
+    }
+
+    // Everything on the graphics queue reads as before
+    let name = "pulsar_queue_submissions_total";
+    let _ = writeln!(out, "# HELP {name} Command buffers submitted, by queue.");
+    let _ = writeln!(out, "# TYPE {name} counter");
+    for snapshot in snapshots {
+        for (index, queue) in QueueKind::ALL.into_iter().enumerate() {
+            let submissions = snapshot.counters.queue_submissions[index];
+            if submissions > 0 || queue == QueueKind::Graphics {
+                let _ = writeln!(
+                    out,
+                    "{name}{{window=\"{}\",queue=\"{}\"}} {submissions}",
+                    snapshot.id,
+                    queue.label()
+                );
+            }
+        }
+    }
+    let asynchronous: Vec<MetricsSnapshot> = snapshots
+        .iter()
+        .filter(|snapshot| snapshot.async_efficiency.is_some())
+        .copied()
+        .collect();
+    if !asynchronous.is_empty() {
+        let name = "pulsar_queue_busy_seconds_total";
+        let _ = writeln!(
+            out,
+            "# HELP {name} GPU time of each queue, from timestamps."
+        );
+        let _ = writeln!(out, "# TYPE {name} counter");
+        for snapshot in &asynchronous {
+            for (index, queue) in QueueKind::ALL.into_iter().enumerate() {
+                let _ = writeln!(
+                    out,
+                    "{name}{{window=\"{}\",queue=\"{}\"}} {}",
+                    snapshot.id,
+                    queue.label(),
+                    snapshot.counters.queue_busy[index].as_secs_f64()
+                );
+            }
+        }
+        family(
+            &mut out,
+            "pulsar_async_efficiency",
+            "gauge",
+            "Share of the transfer and compute GPU time overlapping graphics over the last report interval.",
+            &asynchronous,
+            |s| s.async_efficiency.unwrap_or_default(),
+        );
+    }
+    let paced: Vec<MetricsSnapshot> = snapshots
+        .iter()
+        .filter(|snapshot| snapshot.present_interval.is_some())
+        .copied()
+        .collect();
+    if !paced.is_empty() {
+        family(
+            &mut out,
+            "pulsar_present_interval_seconds",
+            "gauge",
+            "Mean interval between presents over the last report interval, with present wait pacing.",
+            &paced,
+            |s| s.present_interval.unwrap_or_default().as_secs_f64(),
+        );
+        family(
+            &mut out,
+            "pulsar_input_latency_seconds",
+            "gauge",
+            "Mean time from input sampling to present over the last report interval.",
+            &paced,
+            |s| s.input_latency.unwrap_or_default().as_secs_f64(),
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::{Metrics, QueueKind, QueueSpan};
+    use std::io::Read;
+
+    fn get(endpoint: &MetricsEndpoint, path: &str) -> (String, String) {
+        let mut stream = TcpStream::connect(endpoint.local_addr()).unwrap();
+        write!(stream, "GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (head.to_string(), body.to_string())
+    }
+
+    #[test]
+    fn exposition_format() {
+        let snapshot = MetricsSnapshot {
+            id: 7,
+            fps: 60.0,
+            ..Default::default()
+        };
+        let body = encode(&[snapshot]);
+        for line in body.lines() {
+            if let Some(comment) = line.strip_prefix("# ") {
+                assert!(
+                    comment.starts_with("HELP ") || comment.starts_with("TYPE "),
+                    "{line}"
+                );
+                continue;
+            }
+            let (series, value) = line.rsplit_once(' ').unwrap();
+            value.parse::<f64>().unwrap();
+            assert!(series.starts_with("pulsar_"), "{line}");
+            assert!(series.ends_with('}'), "{line}");
+        }
+        assert!(body.contains("\npulsar_fps{window=\"7\"} 60\n"), "{body}");
+    }
+
+    #[test]
+    fn scrape() {
+        let mut metrics = Metrics::default();
+        metrics.start_frame();
+        metrics.counters.draw_calls += 3;
+        metrics.end_frame();
+        let endpoint = MetricsEndpoint::bind("127.0.0.1:0").unwrap();
+
+        let (head, body) = get(&endpoint, "/metrics");
+        assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+        for expected in [
+            "pulsar_frames_total",
+            "pulsar_draw_calls_total",
+            "pulsar_fps",
+            "pulsar_external_stalls_total",
+            "pulsar_wait_seconds_total",
+        ] {
+            assert!(body.contains(&format!("\n{expected}{{")), "{expected}");
+        }
+        let (head, _) = get(&endpoint, "/missing");
+        assert!(head.starts_with("HTTP/1.1 404"), "{head}");
+    }
+
+    #[test]
+    fn a_silent_client_does_not_hold_the_others() {
+        let endpoint = MetricsEndpoint::bind("127.0.0.1:0").unwrap();
+        let _silent = TcpStream::connect(endpoint.local_addr()).unwrap();
+        let started = Instant::now();
+        let (head, _) = get(&endpoint, "/metrics");
+        assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+        assert!(
+            started.elapsed() < CLIENT_TIMEOUT,
+            "Answered in {:?}, after the silent client timed out",
+            started.elapsed()
+        );
+    }
+
+    #[test]
+    fn oversized_requests_are_refused() {
+        let endpoint = MetricsEndpoint::bind("127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(endpoint.local_addr()).unwrap();
+        // Exactly the limit and no blank line, so nothing is left unread to reset the connection
+        let head = "GET /metrics HTTP/1.1\r\nX-Filler: ";
+        let filler = "x".repeat(MAX_REQUEST_BYTES as usize - head.len());
+        write!(stream, "{head}{filler}").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 431"), "{response}");
+    }
+
+    #[test]
+    fn a_trickling_request_is_cut_at_the_deadline() {
+        let endpoint = MetricsEndpoint::bind("127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(endpoint.local_addr()).unwrap();
+        write!(stream, "GET /metrics HTTP/1.1\r\n").unwrap();
+        let started = Instant::now();
+        // One header line at a time, never the blank line ending them, until the endpoint
+        // hangs up
+        while write!(stream, "X-Slow: 1\r\n").is_ok() {
+            assert!(
+                started.elapsed() < CLIENT_TIMEOUT * 3,
+                "Still reading a request sent for {:?}",
+                started.elapsed()
+            );
+            thread::sleep(Duration::from_millis(100));
+        }
+        assert!(
+            started.elapsed() >= CLIENT_TIMEOUT,
+            "{:?}",
+            started.elapsed()
+        );
+    }
+
+    #[test]
+    fn async_efficiency_is_exported_only_with_async_work() {
+        let span = |queue, start_ms: u64, end_ms: u64| QueueSpan {
+            queue,
+            start: start_ms * 1_000_000,
+            end: end_ms * 1_000_000,
+        };
+        let mut metrics = Metrics::default();
+        metrics.record_queue_spans(&[span(QueueKind::Graphics, 0, 3)]);
+        metrics.count_submissions(QueueKind::Graphics, 2);
+        let single = MetricsSnapshot {
+            counters: metrics.counters,
+            ..Default::default()
+        };
+        let text = encode(&[single]);
+        assert!(!text.contains("pulsar_async_efficiency"), "{text}");
+        assert!(text.contains("queue=\"graphics\"} 2"), "{text}");
+
+        metrics.record_queue_spans(&[
+            span(QueueKind::Graphics, 0, 6),
+            span(QueueKind::Transfer, 4, 8),
+        ]);
+        let multi = MetricsSnapshot {
+            id: 1,
+            async_efficiency: metrics.window.queue_overlap.async_efficiency(),
+            ..single
+        };
+        let text = encode(&[single, multi]);
+        assert!(
+            text.contains("pulsar_async_efficiency{window=\"1\"} 0.5"),
+            "{text}"
+        );
+    }
+}
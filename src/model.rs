@@ -1,166 +1,1945 @@
-use crate::vulkan::{device::AAADevice, views::find_memorytype_index};
-use ash::{util::Align, vk};
-use glam::Mat4;
-use std::mem;
-
-#[derive(Clone, Debug, Copy)]
-pub struct Vertex {
-    pub pos: [f32; 4],
-    pub uv: [f32; 2],
-    pub color: [f32; 4],
-}
-
-#[derive(Debug)]
-pub struct Mesh {
-    pub vertices: Vec<Vertex>,
-    pub indices: Vec<u32>,
-
-    pub transform: glam::Mat4,
-}
-
-// TODO make own math lib I guess
-pub fn mat4_to_bytes(matrix: &Mat4) -> &[u8] {
-    unsafe {
-        std::slice::from_raw_parts(
-            matrix as *const Mat4 as *const u8,
-            std::mem::size_of::<Mat4>(),
-        )
-    }
-}
-
-#[derive(Debug)]
-pub struct RegisteredMesh {
-    pub mesh: Mesh,
-    pub vertex_buffer: vk::Buffer,
-    pub vertex_buffer_memory: vk::DeviceMemory,
-    pub index_buffer: vk::Buffer,
-    pub index_buffer_memory: vk::DeviceMemory,
-}
-
-impl Mesh {
-    pub fn register(
-        self,
-        device: &AAADevice,
-        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
-    ) -> RegisteredMesh {
-        unsafe {
-            let index_buffer_info = vk::BufferCreateInfo::default()
-                .size((self.indices.len() * mem::size_of::<u32>()) as u64)
-                .usage(vk::BufferUsageFlags::INDEX_BUFFER)
-                .sharing_mode(vk::SharingMode::EXCLUSIVE);
-
-            let index_buffer = device.ash.create_buffer(&index_buffer_info, None).unwrap();
-            let index_buffer_memory_req = device.ash.get_buffer_memory_requirements(index_buffer);
-            let index_buffer_memory_index = find_memorytype_index(
-                &index_buffer_memory_req,
-                device_memory_properties,
-                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-            )
-            .expect("Unable to find suitable memorytype for the index buffer.");
-
-            let index_allocate_info = vk::MemoryAllocateInfo {
-                allocation_size: index_buffer_memory_req.size,
-                memory_type_index: index_buffer_memory_index,
-                ..Default::default()
-            };
-            let index_buffer_memory = device
-                .ash
-                .allocate_memory(&index_allocate_info, None)
-                .unwrap();
-            let index_ptr = device
-                .ash
-                .map_memory(
-                    index_buffer_memory,
-                    0,
-                    index_buffer_memory_req.size,
-                    vk::MemoryMapFlags::empty(),
-                )
-                .unwrap();
-            let mut index_slice = Align::new(
-                index_ptr,
-                mem::align_of::<u32>() as u64,
-                index_buffer_memory_req.size,
-            );
-            index_slice.copy_from_slice(&self.indices);
-            device.ash.unmap_memory(index_buffer_memory);
-            device
-                .ash
-                .bind_buffer_memory(index_buffer, index_buffer_memory, 0)
-                .unwrap();
-
-            let vertex_input_buffer_info = vk::BufferCreateInfo {
-                size: (self.vertices.len() * mem::size_of::<Vertex>()) as u64,
-                usage: vk::BufferUsageFlags::VERTEX_BUFFER,
-                sharing_mode: vk::SharingMode::EXCLUSIVE,
-                ..Default::default()
-            };
-
-            let vertex_input_buffer = device
-                .ash
-                .create_buffer(&vertex_input_buffer_info, None)
-                .unwrap();
-
-            let vertex_input_buffer_memory_req = device
-                .ash
-                .get_buffer_memory_requirements(vertex_input_buffer);
-
-            let vertex_input_buffer_memory_index = find_memorytype_index(
-                &vertex_input_buffer_memory_req,
-                device_memory_properties,
-                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-            )
-            .expect("Unable to find suitable memorytype for the vertex buffer.");
-
-            let vertex_buffer_allocate_info = vk::MemoryAllocateInfo {
-                allocation_size: vertex_input_buffer_memory_req.size,
-                memory_type_index: vertex_input_buffer_memory_index,
-                ..Default::default()
-            };
-
-            let vertex_input_buffer_memory = device
-                .ash
-                .allocate_memory(&vertex_buffer_allocate_info, None)
-                .unwrap();
-
-            let vert_ptr = device
-                .ash
-                .map_memory(
-                    vertex_input_buffer_memory,
-                    0,
-                    vertex_input_buffer_memory_req.size,
-                    vk::MemoryMapFlags::empty(),
-                )
-                .unwrap();
-
-            let mut vert_align = Align::new(
-                vert_ptr,
-                mem::align_of::<Vertex>() as u64,
-                vertex_input_buffer_memory_req.size,
-            );
-            vert_align.copy_from_slice(&self.vertices);
-            device.ash.unmap_memory(vertex_input_buffer_memory);
-            device
-                .ash
-                .bind_buffer_memory(vertex_input_buffer, vertex_input_buffer_memory, 0)
-                .unwrap();
-
-            RegisteredMesh {
-                mesh: self,
-                vertex_buffer: vertex_input_buffer,
-                vertex_buffer_memory: vertex_input_buffer_memory,
-                index_buffer,
-                index_buffer_memory,
-            }
-        }
-    }
-}
-
-#[derive(Debug)]
-pub struct Model {
-    pub meshes: Vec<Mesh>,
-}
-
-#[derive(Debug)]
-pub struct Scene {
-    pub models: Vec<Model>,
-}
+use crate::{
+    batching::{self, BatchRange, MeshBatch},
+    compute::DeformBuffer,
+    culling::{BoundingSphere, Frustum},
+    handles::MeshHandle,
+    hierarchy,
+    inset::ALL_LAYERS,
+    instancing::{InstanceBuffer, Instances},
+    material::{DepthBias, Material, MaterialKey, PipelineOptions},
+    residency::ResidencyPriority,
+    skinning::{PaletteBuffer, SkinBuffer},
+    texture::TextureUpdate,
+    vulkan::{
+        device::AAADevice,
+        pipeline::MeshPipeline,
+        upload::{
+            create_empty_buffer, write_mapped, AAAOwnedBuffer, AAAStagingBuffer, AAAUploadContext,
+        },
+    },
+    world::WorldConvention,
+};
+use ash::vk;
+use glam::{Mat4, Vec2, Vec3};
+use log::info;
+use std::{
+    cell::OnceCell,
+    collections::HashMap,
+    mem,
+    ops::Range,
+    sync::{Arc, Mutex},
+};
+
+pub mod cache;
+#[cfg(feature = "gltf")]
+mod gltf;
+#[cfg(feature = "obj")]
+mod obj;
+
+#[derive(Clone, Debug, Copy)]
+#[repr(C)]
+pub struct Vertex {
+    pub pos: [f32; 4],
+    pub uv: [f32; 2],
+    pub color: [f32; 4],
+    pub normal: [f32; 3],
+    /// `w` is the handedness, the bitangent is `cross(normal, tangent.xyz) * w`.
+    pub tangent: [f32; 4],
+    /// Bones of [`crate::skinning::Skin::joints`] moving the vertex, read by skinned meshes only.
+    pub joints: [u16; 4],
+    /// Share of each of `joints`, summing to 1 on skinned meshes.
+    pub weights: [f32; 4],
+}
+
+// SAFETY: 4 byte aligned arrays of plain numbers, the `u16` ones in pairs, so no padding
+unsafe impl bytemuck::Zeroable for Vertex {}
+unsafe impl bytemuck::Pod for Vertex {}
+
+impl Vertex {
+    fn position(&self) -> Vec3 {
+        Vec3::new(self.pos[0], self.pos[1], self.pos[2])
+    }
+}
+
+#[derive(Debug)]
+pub struct Mesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+
+    pub transform: glam::Mat4,
+    /// Application side identifier, returned by picking and kept by the cache. 0 by default.
+    pub user_id: u64,
+}
+
+/// Largest mesh drawable with a single indexed draw.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MeshLimits {
+    /// One past `maxDrawIndexedIndexValue`, or fewer when the vertex buffer would exceed the largest allocation.
+    pub max_vertices: u32,
+    pub max_indices: u32,
+}
+
+impl MeshLimits {
+    pub fn fits(&self, mesh: &Mesh) -> bool {
+        mesh.vertices.len() <= self.max_vertices as usize
+            && mesh.indices.len() <= self.max_indices as usize
+    }
+}
+
+/// Index buffer contents, 16 bit whenever every vertex is addressable with it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexData {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl IndexData {
+    /// Narrows to 16 bit when there are at most 65536 vertices, larger meshes keep 32 bit indices.
+    pub fn compact(indices: &[u32], vertex_count: usize) -> Self {
+        if vertex_count <= u16::MAX as usize + 1 {
+            Self::U16(indices.iter().map(|&index| index as u16).collect())
+        } else {
+            Self::U32(indices.to_vec())
+        }
+    }
+
+    /// Bytes per index of [`Self::compact`] for `vertex_count` vertices.
+    pub fn stride(vertex_count: usize) -> usize {
+        if vertex_count <= u16::MAX as usize + 1 {
+            mem::size_of::<u16>()
+        } else {
+            mem::size_of::<u32>()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::U16(indices) => indices.len(),
+            Self::U32(indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn size_bytes(&self) -> u64 {
+        match self {
+            Self::U16(indices) => mem::size_of_val(indices.as_slice()) as u64,
+            Self::U32(indices) => mem::size_of_val(indices.as_slice()) as u64,
+        }
+    }
+
+    pub fn index_type(&self) -> vk::IndexType {
+        match self {
+            Self::U16(_) => vk::IndexType::UINT16,
+            Self::U32(_) => vk::IndexType::UINT32,
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            Self::U16(indices) => as_bytes(indices),
+            Self::U32(indices) => as_bytes(indices),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct MeshBuffers {
+    pub vertex_buffer: vk::Buffer,
+    pub vertex_buffer_memory: vk::DeviceMemory,
+    pub index_buffer: vk::Buffer,
+    pub index_buffer_memory: vk::DeviceMemory,
+    pub index_count: u32,
+    pub index_type: vk::IndexType,
+    /// Allocated bytes, updates up to them are written in place.
+    pub vertex_capacity: u64,
+    pub index_capacity: u64,
+    /// Written through a mapping rather than a staging copy.
+    pub host_visible: bool,
+}
+
+/// New contents for a registered mesh, copied by the render thread before its next frame.
+#[derive(Debug, Clone)]
+pub enum MeshUpdate {
+    Vertices(Vec<Vertex>),
+    Indices(Vec<u32>),
+    /// Transform relative to the parent, the world when the mesh has none.
+    Transform(Mat4),
+    /// Follow another mesh of the same window, `None` detaches it. Links closing a cycle are refused.
+    Parent(Option<MeshHandle>),
+    /// Layers the mesh is on, see [`crate::inset::InsetView::layer_mask`].
+    Layers(u32),
+    /// Sample this layer of the window's texture array with [`Material::texture_array`], `None`
+    /// draws the mesh with the default material again.
+    TextureLayer(Option<u32>),
+    /// Draw bucket of a perspective mesh, lower first, 0 by default. Layers co-planar meshes
+    /// whatever their materials, see [`crate::draw_order`].
+    SortBias(i32),
+    /// Blend the mesh over what is behind it by its alpha, drawn back to front after the opaque
+    /// meshes. Off by default.
+    Blend(bool),
+    /// Application side identifier of the mesh, see [`Mesh::user_id`]. Meshes of a batch keep
+    /// the ones they were merged with.
+    UserId(u64),
+    /// Culling, winding, fill and depth test of the mesh's default material, `None` for the
+    /// defaults of its space. Materials carry their own, see [`Material::options`].
+    PipelineOptions(Option<PipelineOptions>),
+    /// Draw the mesh with this material, `None` with the default material again. Replaces the
+    /// one a [`MeshUpdate::TextureLayer`] set, and the other way around.
+    Material(Option<Material>),
+    /// Color every fragment of the mesh is multiplied by, white by default.
+    Tint([f32; 4]),
+    /// Depth bias of the mesh's default material, set per draw. `None` by default, meshes with a
+    /// material take its [`Material::depth_bias`].
+    DepthBias(Option<DepthBias>),
+    /// Whether the mesh may be evicted when the window is over its memory budget, `Normal` by
+    /// default, see [`crate::residency`].
+    Residency(ResidencyPriority),
+}
+
+/// A mesh and its GPU copy, owned by the renderer. Applications refer to it through a [`crate::MeshHandle`].
+#[derive(Debug)]
+pub(crate) struct RegisteredMesh {
+    /// Its transform is relative to `parent`.
+    pub mesh: Mesh,
+    /// Mesh this one moves with. A removed parent leaves its children in place as roots.
+    pub parent: Option<MeshHandle>,
+    /// `mesh.transform` under the parents, resolved once per frame before drawing and picking.
+    pub world_transform: Mat4,
+    /// More than one when the mesh exceeds the device limits, empty while evicted. Shared by the
+    /// meshes of identical geometry, freed with the last of them.
+    pub chunks: Arc<Vec<MeshBuffers>>,
+    /// Given by the application, `None` draws with the default material. Resolved into
+    /// [`Self::material`] every frame.
+    pub assigned_material: Option<Material>,
+    /// Pipeline the mesh draws with, see [`Self::resolve_material`].
+    pub material: MaterialKey,
+    /// Only `Streaming` meshes are evicted when over the memory budget.
+    pub residency: ResidencyPriority,
+    /// Around the vertices of each of [`Self::parts`] in its own space, computed when first
+    /// culled and again after the geometry changes.
+    pub bounds: OnceCell<Vec<Option<BoundingSphere>>>,
+    /// Drawn with the orthographic camera, moves the origin onto a physical pixel. Turn it off
+    /// for UI animating smoothly, it would step from pixel to pixel.
+    pub pixel_snap: bool,
+    pub space: MeshSpace,
+    /// Per-instance transforms, `None` draws the mesh once.
+    pub instances: Option<InstanceBuffer>,
+    /// Joint matrices, `None` draws the mesh rigid with the fast path.
+    pub skin: Option<SkinBuffer>,
+    /// Compute shader rewriting the vertex buffer before every frame, see [`crate::compute`].
+    pub deform: Option<DeformBuffer>,
+    /// Merged meshes of a [`MeshBatch`], empty for a single mesh.
+    pub batch: Vec<BatchRange>,
+    /// Bit per layer, the insets only draw the meshes sharing one with their mask.
+    pub layers: u32,
+    /// Layer of the texture array sampled by [`Material::texture_array`].
+    pub texture_layer: u32,
+    /// Draw bucket among the perspective meshes, see [`crate::draw_order`].
+    pub sort_bias: i32,
+    /// Blended over what is behind it by its alpha, after the opaque meshes and without writing
+    /// the depth. Instances and skins have no blended variant yet and stay opaque, materials
+    /// blend by their [`PipelineOptions::blend`].
+    pub blend: bool,
+    /// Multiplies the color of its fragments, pushed to the fragment stage with every draw.
+    pub tint: [f32; 4],
+    /// Set with `cmd_set_depth_bias` before drawing with a default material variant, whose
+    /// pipelines take the bias as dynamic state. Decals layer over a surface without a material.
+    pub depth_bias: Option<DepthBias>,
+    /// Rasterizer state of the default material. `None` culls the back faces of the world
+    /// meshes and draws both faces of the 2D world's, see [`Self::pipeline_options`].
+    pub pipeline_options: Option<PipelineOptions>,
+    /// Sequence number given by the registry, orders the draws of a bucket.
+    pub registered: u64,
+}
+
+/// Which corners of a triangle soup [`Mesh::indexed_from_triangles_with`] share a vertex.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WeldMode {
+    /// Positions within `epsilon` and every other attribute equal, UV and normal seams are kept.
+    Attributes { epsilon: f32 },
+    /// Positions within `epsilon` alone, the first corner seen gives the vertex its attributes.
+    Positions { epsilon: f32 },
+}
+
+impl Default for WeldMode {
+    fn default() -> Self {
+        Self::Attributes { epsilon: 1e-6 }
+    }
+}
+
+impl WeldMode {
+    /// Key of `vertex`, equal for the corners merged. Positions are snapped to a grid of
+    /// `epsilon`, two positions closer than it on either side of a grid line stay apart.
+    fn key(self, vertex: &Vertex) -> ([i64; 3], Option<[u32; 19]>) {
+        let (Self::Attributes { epsilon } | Self::Positions { epsilon }) = self;
+        let snap = |value: f32| {
+            if epsilon > 0.0 {
+                (value / epsilon).round() as i64
+            } else {
+                // Plus zero folds -0 into 0
+                (value + 0.0).to_bits() as i64
+            }
+        };
+        let position = vertex.position().to_array().map(snap);
+        let attributes = matches!(self, Self::Attributes { .. }).then(|| {
+            let mut bits = [0; 19];
+            let values = vertex
+                .uv
+                .iter()
+                .chain(&vertex.color)
+                .chain(&vertex.normal)
+                .chain(&vertex.tangent)
+                .chain(&vertex.weights);
+            for (bits, value) in bits.iter_mut().zip(values) {
+                *bits = (value + 0.0).to_bits();
+            }
+            // Joints packed two per slot after the 17 floats
+            bits[17] = u32::from(vertex.joints[0]) | u32::from(vertex.joints[1]) << 16;
+            bits[18] = u32::from(vertex.joints[2]) | u32::from(vertex.joints[3]) << 16;
+            bits
+        });
+        (position, attributes)
+    }
+}
+
+/// Camera a mesh of the scene is drawn and picked with. Screen space UI is not part of the scene,
+/// it keeps its own list drawn with the UI camera.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MeshSpace {
+    /// Perspective camera, editable with the gizmo.
+    #[default]
+    World,
+    /// Orthographic 2D world camera, panned and zoomed by the `Ortho2DController`.
+    World2D,
+}
+
+impl Mesh {
+    /// Rectangle in the XY plane centered on the origin, facing +Z. UVs start at the top left.
+    pub fn quad(size: Vec2, color: [f32; 4]) -> Self {
+        let h = size / 2.0;
+        let vertex = |s: f32, t: f32| Vertex {
+            pos: [s * h.x, t * h.y, 0.0, 1.0],
+            uv: [(s + 1.0) / 2.0, (1.0 - t) / 2.0],
+            color,
+            normal: [0.0, 0.0, 1.0],
+            // `v` runs down the quad, against the bitangent `cross(normal, tangent)`
+            tangent: [1.0, 0.0, 0.0, -1.0],
+            joints: [0; 4],
+            weights: [0.0; 4],
+        };
+        Self {
+            vertices: vec![
+                vertex(-1.0, -1.0),
+                vertex(1.0, -1.0),
+                vertex(1.0, 1.0),
+                vertex(-1.0, 1.0),
+            ],
+            indices: vec![0, 1, 2, 0, 2, 3],
+            transform: Mat4::IDENTITY,
+            user_id: 0,
+        }
+    }
+
+    /// Grid in the XZ plane centered on the origin, facing +Y, `subdivisions` extra cuts along
+    /// each side. UVs span the whole plane, `u` along X and `v` along Z.
+    pub fn plane(width: f32, depth: f32, subdivisions: u32, color: [f32; 4]) -> Self {
+        let cells = subdivisions + 1;
+        let row = cells + 1;
+        let mut vertices = Vec::with_capacity((row * row) as usize);
+        for z in 0..row {
+            for x in 0..row {
+                let (u, v) = (x as f32 / cells as f32, z as f32 / cells as f32);
+                vertices.push(Vertex {
+                    pos: [(u - 0.5) * width, 0.0, (v - 0.5) * depth, 1.0],
+                    uv: [u, v],
+                    color,
+                    normal: [0.0, 1.0, 0.0],
+                    // `v` runs along +Z, the bitangent `cross(normal, tangent)` is -Z
+                    tangent: [1.0, 0.0, 0.0, -1.0],
+                    joints: [0; 4],
+                    weights: [0.0; 4],
+                });
+            }
+        }
+        let mut indices = Vec::with_capacity((cells * cells * 6) as usize);
+        for z in 0..cells {
+            for x in 0..cells {
+                let a = z * row + x;
+                let b = a + row;
+                indices.extend([a, b, b + 1, a, b + 1, a + 1]);
+            }
+        }
+        Self {
+            vertices,
+            indices,
+            transform: Mat4::IDENTITY,
+            user_id: 0,
+        }
+    }
+
+    /// Axis aligned cube centered on the origin, one quad per face so the edges stay sharp.
+    pub fn cube(size: f32, color: [f32; 4]) -> Self {
+        let h = size / 2.0;
+        let mut vertices = Vec::with_capacity(24);
+        let mut indices = Vec::with_capacity(36);
+        for normal in [
+            Vec3::X,
+            Vec3::NEG_X,
+            Vec3::Y,
+            Vec3::NEG_Y,
+            Vec3::Z,
+            Vec3::NEG_Z,
+        ] {
+            let u = normal.any_orthonormal_vector();
+            let v = normal.cross(u);
+            let offset = vertices.len() as u32;
+            for (s, t) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+                let p = (normal + u * s + v * t) * h;
+                vertices.push(Vertex {
+                    pos: [p.x, p.y, p.z, 1.0],
+                    uv: [(s + 1.0) / 2.0, (t + 1.0) / 2.0],
+                    color,
+                    normal: [0.0; 3],
+                    tangent: [0.0; 4],
+                    joints: [0; 4],
+                    weights: [0.0; 4],
+                });
+            }
+            indices.extend([
+                offset,
+                offset + 1,
+                offset + 2,
+                offset,
+                offset + 2,
+                offset + 3,
+            ]);
+        }
+        let mut cube = Self {
+            vertices,
+            indices,
+            transform: Mat4::IDENTITY,
+            user_id: 0,
+        };
+        cube.generate_normals(true);
+        cube.generate_tangents();
+        cube
+    }
+
+    /// Latitude longitude sphere, `u` goes around the equator and `v` from pole to pole.
+    /// Normals point away from the center.
+    pub fn uv_sphere(radius: f32, segments: u32, rings: u32, color: [f32; 4]) -> Self {
+        let segments = segments.max(3);
+        let rings = rings.max(2);
+        let vertex = |u: f32, v: f32| {
+            let polar = v * std::f32::consts::PI;
+            let azimuth = u * std::f32::consts::TAU;
+            let p = Vec3::new(
+                polar.sin() * azimuth.cos(),
+                polar.cos(),
+                -polar.sin() * azimuth.sin(),
+            ) * radius;
+            Vertex {
+                pos: [p.x, p.y, p.z, 1.0],
+                uv: [u, v],
+                color,
+                normal: [0.0; 3],
+                tangent: [0.0; 4],
+                joints: [0; 4],
+                weights: [0.0; 4],
+            }
+        };
+
+        // One pole vertex per segment, centered on it, so each belongs to exactly one triangle
+        let pole =
+            |v: f32| (0..segments).map(move |s| vertex((s as f32 + 0.5) / segments as f32, v));
+        let mut vertices: Vec<Vertex> = pole(0.0).collect();
+        for ring in 1..rings {
+            let v = ring as f32 / rings as f32;
+            vertices.extend((0..=segments).map(|s| vertex(s as f32 / segments as f32, v)));
+        }
+        vertices.extend(pole(1.0));
+
+        let row = segments + 1;
+        let ring_start = |ring: u32| segments + (ring - 1) * row;
+        let bottom = ring_start(rings);
+        let mut indices = Vec::new();
+        for s in 0..segments {
+            let b = ring_start(1) + s;
+            indices.extend([s, b, b + 1]);
+        }
+        for ring in 1..rings - 1 {
+            for s in 0..segments {
+                let a = ring_start(ring) + s;
+                let b = ring_start(ring + 1) + s;
+                indices.extend([a, b, b + 1, a, b + 1, a + 1]);
+            }
+        }
+        for s in 0..segments {
+            let a = ring_start(rings - 1) + s;
+            indices.extend([a, bottom + s, a + 1]);
+        }
+        for vertex in &mut vertices {
+            vertex.normal = vertex
+                .position()
+                .try_normalize()
+                .unwrap_or(Vec3::Y)
+                .to_array();
+        }
+        let mut sphere = Self {
+            vertices,
+            indices,
+            transform: Mat4::IDENTITY,
+            user_id: 0,
+        };
+        sphere.generate_tangents();
+        sphere
+    }
+
+    /// Smooth normals are the area weighted average of the faces sharing a vertex,
+    /// flat normals duplicate the vertices so every triangle gets its own.
+    pub fn generate_normals(&mut self, smooth: bool) {
+        if !smooth {
+            let vertices = self
+                .indices
+                .iter()
+                .map(|&index| self.vertices[index as usize])
+                .collect();
+            self.vertices = vertices;
+            self.indices = (0..self.vertices.len() as u32).collect();
+        }
+
+        let mut normals = vec![Vec3::ZERO; self.vertices.len()];
+        for triangle in self.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize);
+            let [pa, pb, pc] = [a, b, c].map(|i| self.vertices[i].position());
+            // Not normalized, its length is twice the triangle area
+            let face = (pb - pa).cross(pc - pa);
+            for index in [a, b, c] {
+                normals[index] += face;
+            }
+        }
+        for (vertex, normal) in self.vertices.iter_mut().zip(normals) {
+            vertex.normal = normal.try_normalize().unwrap_or(Vec3::Z).to_array();
+        }
+    }
+
+    /// Per vertex tangents from the UV gradients of the adjacent triangles (Lengyel's method),
+    /// orthogonalized against the normal. Needs normals, see [`Self::generate_normals`].
+    /// Degenerate UVs and zero area triangles fall back to an arbitrary orthonormal basis.
+    pub fn generate_tangents(&mut self) {
+        let mut tangents = vec![Vec3::ZERO; self.vertices.len()];
+        let mut bitangents = vec![Vec3::ZERO; self.vertices.len()];
+        for triangle in self.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize);
+            let [pa, pb, pc] = [a, b, c].map(|i| self.vertices[i].position());
+            let [ta, tb, tc] = [a, b, c].map(|i| Vec2::from(self.vertices[i].uv));
+            let (edge1, edge2) = (pb - pa, pc - pa);
+            let (duv1, duv2) = (tb - ta, tc - ta);
+            let det = duv1.perp_dot(duv2);
+            if det.abs() <= f32::EPSILON {
+                continue;
+            }
+            let tangent = (edge1 * duv2.y - edge2 * duv1.y) / det;
+            let bitangent = (edge2 * duv1.x - edge1 * duv2.x) / det;
+            if !tangent.is_finite() || !bitangent.is_finite() {
+                continue;
+            }
+            for index in [a, b, c] {
+                tangents[index] += tangent;
+                bitangents[index] += bitangent;
+            }
+        }
+
+        for (i, vertex) in self.vertices.iter_mut().enumerate() {
+            let normal = Vec3::from(vertex.normal).try_normalize().unwrap_or(Vec3::Z);
+            let tangent = (tangents[i] - normal * normal.dot(tangents[i]))
+                .try_normalize()
+                .unwrap_or_else(|| normal.any_orthonormal_vector());
+            let handedness = if normal.cross(tangent).dot(bitangents[i]) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            vertex.tangent = tangent.extend(handedness).to_array();
+        }
+    }
+
+    /// Copied into device local memory through a staging buffer with `upload`, written directly
+    /// to host visible memory without it or when the device has no dedicated device local heap.
+    pub(crate) fn register(
+        self,
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        upload: Option<AAAUploadContext>,
+    ) -> RegisteredMesh {
+        let chunks = self.upload(device, device_memory_properties, upload);
+        self.share(Arc::new(chunks))
+    }
+
+    /// Registered drawing from the GPU copy of a mesh of the same geometry, see
+    /// [`MeshRegistry::find_geometry`].
+    pub(crate) fn share(self, chunks: Arc<Vec<MeshBuffers>>) -> RegisteredMesh {
+        RegisteredMesh {
+            world_transform: self.transform,
+            mesh: self,
+            parent: None,
+            chunks,
+            assigned_material: None,
+            material: MaterialKey::default(),
+            residency: ResidencyPriority::default(),
+            bounds: OnceCell::new(),
+            pixel_snap: true,
+            space: MeshSpace::default(),
+            instances: None,
+            skin: None,
+            deform: None,
+            batch: Vec::new(),
+            layers: ALL_LAYERS,
+            texture_layer: 0,
+            sort_bias: 0,
+            blend: false,
+            tint: [1.0; 4],
+            depth_bias: None,
+            pipeline_options: None,
+            registered: 0,
+        }
+    }
+
+    /// Concatenate `meshes` into one, indices rebased onto the merged vertices. Meshes placed
+    /// differently have their transform applied to their vertices, see [`MeshBatch::into_mesh`].
+    pub fn merge(meshes: Vec<Mesh>) -> Mesh {
+        MeshBatch::new(meshes).into_mesh()
+    }
+
+    /// Bytes of the GPU copy.
+    pub fn size_bytes(&self) -> u64 {
+        (self.vertices.len() * mem::size_of::<Vertex>()
+            + self.indices.len() * IndexData::stride(self.vertices.len())) as u64
+    }
+
+    /// Split into meshes within `limits`, keeping triangles whole. A vertex is only duplicated
+    /// into the chunks whose triangles use it, indices are remapped per chunk.
+    pub fn split(&self, limits: MeshLimits) -> Vec<Mesh> {
+        let max_indices = (limits.max_indices as usize / 3 * 3).max(3);
+        let max_vertices = (limits.max_vertices as usize).max(3);
+        let empty = || Mesh {
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            transform: self.transform,
+            user_id: self.user_id,
+        };
+        let mut chunks = Vec::new();
+        let mut chunk = empty();
+        // Position of each source vertex in the current chunk
+        let mut remap = vec![u32::MAX; self.vertices.len()];
+        let mut mapped = Vec::new();
+        for triangle in self.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize);
+            let added = [a, b, c]
+                .iter()
+                .enumerate()
+                .filter(|&(i, &v)| remap[v] == u32::MAX && !triangle[..i].contains(&(v as u32)))
+                .count();
+            if chunk.indices.len() + 3 > max_indices || chunk.vertices.len() + added > max_vertices
+            {
+                for vertex in mapped.drain(..) {
+                    remap[vertex] = u32::MAX;
+                }
+                chunks.push(mem::replace(&mut chunk, empty()));
+            }
+            for vertex in [a, b, c] {
+                if remap[vertex] == u32::MAX {
+                    remap[vertex] = chunk.vertices.len() as u32;
+                    chunk.vertices.push(self.vertices[vertex]);
+                    mapped.push(vertex);
+                }
+                chunk.indices.push(remap[vertex]);
+            }
+        }
+        if !chunk.indices.is_empty() {
+            chunks.push(chunk);
+        }
+        chunks
+    }
+
+    /// Indexed mesh of a triangle soup, three vertices per triangle, duplicates sharing one
+    /// vertex. See [`WeldMode::default`] for what counts as a duplicate.
+    pub fn indexed_from_triangles(vertices: Vec<Vertex>) -> Mesh {
+        Self::indexed_from_triangles_with(vertices, WeldMode::default())
+    }
+
+    /// [`Self::indexed_from_triangles`] merging the corners `mode` finds equal. Triangles left
+    /// with fewer than three distinct vertices are dropped, as is a trailing partial triangle.
+    pub fn indexed_from_triangles_with(vertices: Vec<Vertex>, mode: WeldMode) -> Mesh {
+        let mut welded = HashMap::new();
+        let mut mesh = Mesh {
+            vertices: Vec::new(),
+            indices: Vec::with_capacity(vertices.len() / 3 * 3),
+            transform: Mat4::IDENTITY,
+            user_id: 0,
+        };
+        for triangle in vertices.chunks_exact(3) {
+            let corners = [0, 1, 2].map(|i| {
+                *welded.entry(mode.key(&triangle[i])).or_insert_with(|| {
+                    mesh.vertices.push(triangle[i]);
+                    mesh.vertices.len() as u32 - 1
+                })
+            });
+            let [a, b, c] = corners;
+            if a != b && b != c && a != c {
+                mesh.indices.extend(corners);
+            }
+        }
+        // Corners of the dropped triangles only
+        mesh.compact();
+        mesh
+    }
+
+    /// Drop the vertices no index refers to, the others keep their order.
+    pub fn compact(&mut self) {
+        let mut remap = vec![u32::MAX; self.vertices.len()];
+        for &index in &self.indices {
+            remap[index as usize] = 0;
+        }
+        let mut kept = 0;
+        for (vertex, new_index) in remap.iter_mut().enumerate() {
+            if *new_index == 0 {
+                *new_index = kept;
+                self.vertices[kept as usize] = self.vertices[vertex];
+                kept += 1;
+            }
+        }
+        self.vertices.truncate(kept as usize);
+        for index in &mut self.indices {
+            *index = remap[*index as usize];
+        }
+    }
+
+    /// One buffer pair per chunk, split when the mesh exceeds the device limits.
+    fn upload(
+        &self,
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        upload: Option<AAAUploadContext>,
+    ) -> Vec<MeshBuffers> {
+        let split;
+        let parts: Vec<&Mesh> = if device.mesh_limits.fits(self) {
+            vec![self]
+        } else {
+            split = self.split(device.mesh_limits);
+            info!(
+                "Mesh of {} vertices and {} indices exceeds the device limits, drawn in {} chunks",
+                self.vertices.len(),
+                self.indices.len(),
+                split.len()
+            );
+            split.iter().collect()
+        };
+        match upload.filter(|_| AAAUploadContext::worthwhile(device_memory_properties)) {
+            Some(upload) => MeshBuffers::staged(device, device_memory_properties, upload, &parts),
+            None => parts
+                .iter()
+                .map(|part| {
+                    MeshBuffers::host_visible(
+                        device,
+                        device_memory_properties,
+                        &part.vertices,
+                        &part.indices,
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+impl MeshBuffers {
+    /// Buffers sized for `vertices` and `index_data` in `memory_flags` memory, left unwritten.
+    fn allocate(
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        vertices: &[Vertex],
+        index_data: &IndexData,
+        memory_flags: vk::MemoryPropertyFlags,
+    ) -> Self {
+        let vertex_capacity = mem::size_of_val(vertices) as u64;
+        let index_capacity = index_data.size_bytes();
+        let AAAOwnedBuffer {
+            buffer: vertex_buffer,
+            memory: vertex_buffer_memory,
+            ..
+        } = create_empty_buffer(
+            device,
+            device_memory_properties,
+            vertex_capacity,
+            // Storage too, for the compute shaders deforming meshes to write
+            vk::BufferUsageFlags::VERTEX_BUFFER
+                | vk::BufferUsageFlags::STORAGE_BUFFER
+                | vk::BufferUsageFlags::TRANSFER_DST,
+            memory_flags,
+        );
+        let AAAOwnedBuffer {
+            buffer: index_buffer,
+            memory: index_buffer_memory,
+            ..
+        } = create_empty_buffer(
+            device,
+            device_memory_properties,
+            index_capacity,
+            vk::BufferUsageFlags::INDEX_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
+            memory_flags,
+        );
+        Self {
+            vertex_buffer,
+            vertex_buffer_memory,
+            index_buffer,
+            index_buffer_memory,
+            index_count: index_data.len() as u32,
+            index_type: index_data.index_type(),
+            vertex_capacity,
+            index_capacity,
+            host_visible: memory_flags.contains(vk::MemoryPropertyFlags::HOST_VISIBLE),
+        }
+    }
+
+    /// Overwrite the start of `buffer`, through a staging copy when it is device local.
+    fn write(
+        &self,
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        upload: AAAUploadContext,
+        (buffer, memory, capacity): (vk::Buffer, vk::DeviceMemory, u64),
+        bytes: &[u8],
+    ) {
+        if self.host_visible {
+            write_mapped(device, memory, capacity, bytes);
+            return;
+        }
+        let mut staging =
+            AAAStagingBuffer::new(device, device_memory_properties, bytes.len() as u64);
+        staging.write(0, bytes);
+        upload.copy(
+            device,
+            staging,
+            &[(
+                buffer,
+                vk::BufferCopy {
+                    src_offset: 0,
+                    dst_offset: 0,
+                    size: bytes.len() as u64,
+                },
+            )],
+            vk::PipelineStageFlags::VERTEX_INPUT,
+            vk::AccessFlags::VERTEX_ATTRIBUTE_READ | vk::AccessFlags::INDEX_READ,
+        );
+    }
+
+    /// Written through a mapping, the GPU reads it across the bus on discrete cards.
+    fn host_visible(
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        vertices: &[Vertex],
+        indices: &[u32],
+    ) -> Self {
+        let index_data = IndexData::compact(indices, vertices.len());
+        let buffers = Self::allocate(
+            device,
+            device_memory_properties,
+            vertices,
+            &index_data,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        );
+        write_mapped(
+            device,
+            buffers.vertex_buffer_memory,
+            buffers.vertex_capacity,
+            vertices,
+        );
+        write_mapped(
+            device,
+            buffers.index_buffer_memory,
+            buffers.index_capacity,
+            index_data.as_bytes(),
+        );
+        buffers
+    }
+
+    /// Device local, every part copied from a single staging buffer in one submission.
+    fn staged(
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        upload: AAAUploadContext,
+        parts: &[&Mesh],
+    ) -> Vec<Self> {
+        let index_data: Vec<IndexData> = parts
+            .iter()
+            .map(|part| IndexData::compact(&part.indices, part.vertices.len()))
+            .collect();
+        let size = parts
+            .iter()
+            .zip(&index_data)
+            .map(|(part, indices)| {
+                mem::size_of_val(part.vertices.as_slice()) as u64 + indices.size_bytes()
+            })
+            .sum();
+        let mut staging = AAAStagingBuffer::new(device, device_memory_properties, size);
+        let mut copies = Vec::new();
+        let mut offset = 0;
+        let mut stage = |buffer: vk::Buffer, bytes: &[u8]| {
+            staging.write(offset, bytes);
+            copies.push((
+                buffer,
+                vk::BufferCopy {
+                    src_offset: offset,
+                    dst_offset: 0,
+                    size: bytes.len() as u64,
+                },
+            ));
+            offset += bytes.len() as u64;
+        };
+        let buffers = parts
+            .iter()
+            .zip(&index_data)
+            .map(|(part, indices)| {
+                let buffers = Self::allocate(
+                    device,
+                    device_memory_properties,
+                    &part.vertices,
+                    indices,
+                    vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                );
+                stage(buffers.vertex_buffer, as_bytes(&part.vertices));
+                stage(buffers.index_buffer, indices.as_bytes());
+                buffers
+            })
+            .collect();
+        upload.copy(
+            device,
+            staging,
+            &copies,
+            vk::PipelineStageFlags::VERTEX_INPUT,
+            vk::AccessFlags::VERTEX_ATTRIBUTE_READ | vk::AccessFlags::INDEX_READ,
+        );
+        buffers
+    }
+
+    fn destroy(&self, device: &AAADevice) {
+        unsafe {
+            crate::object_audit::destroyed(self.index_buffer_memory);
+            device.ash.free_memory(self.index_buffer_memory, None);
+            crate::object_audit::destroyed(self.index_buffer);
+            device.ash.destroy_buffer(self.index_buffer, None);
+            crate::object_audit::destroyed(self.vertex_buffer_memory);
+            device.ash.free_memory(self.vertex_buffer_memory, None);
+            crate::object_audit::destroyed(self.vertex_buffer);
+            device.ash.destroy_buffer(self.vertex_buffer, None);
+        }
+    }
+}
+
+/// Vertices and indices are plain numbers without padding.
+pub(crate) fn as_bytes<T: Copy>(values: &[T]) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(values.as_ptr().cast(), mem::size_of_val(values)) }
+}
+
+impl RegisteredMesh {
+    pub fn with_space(mut self, space: MeshSpace) -> Self {
+        self.space = space;
+        self
+    }
+
+    pub fn with_blend(mut self, blend: bool) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    /// Key [`Self::material`] on the assigned material, or on the default material variant the
+    /// mesh's state asks for. Materials have no instanced nor skinned variant yet, and the
+    /// texture array one draws as triangles until the application creates the array.
+    pub fn resolve_material(&mut self, texture_array: bool) {
+        let variant = match &self.assigned_material {
+            _ if self.instances.is_some() => MeshPipeline::Instanced,
+            _ if self.skin.as_ref().map(|skin| skin.palette) == Some(PaletteBuffer::Uniform) => {
+                MeshPipeline::SkinnedUniform
+            }
+            _ if self.skin.is_some() => MeshPipeline::Skinned,
+            Some(material) if material.is_texture_array() && !texture_array => {
+                MeshPipeline::Triangles
+            }
+            Some(material) => {
+                // Cloned only when it changed, the key is resolved every frame
+                if !matches!(&self.material, MaterialKey::Material(key) if key == material) {
+                    self.material = MaterialKey::Material(material.clone());
+                }
+                return;
+            }
+            None if self.blend => MeshPipeline::Blended,
+            None => MeshPipeline::Triangles,
+        };
+        self.material = MaterialKey::Default(variant, self.pipeline_options());
+    }
+
+    /// Options the default material pipeline of the mesh is built with.
+    pub fn pipeline_options(&self) -> PipelineOptions {
+        self.pipeline_options.unwrap_or_else(|| match self.space {
+            MeshSpace::World => PipelineOptions::default(),
+            MeshSpace::World2D => PipelineOptions::unculled(),
+        })
+    }
+
+    /// Distance in front of the camera looking through `view` of the center of the vertices'
+    /// bounds, what blended meshes are sorted by.
+    pub fn view_depth(&self, view: Mat4) -> f32 {
+        let Some(first) = self.mesh.vertices.first().map(Vertex::position) else {
+            return 0.0;
+        };
+        let (min, max) = self
+            .mesh
+            .vertices
+            .iter()
+            .map(Vertex::position)
+            .fold((first, first), |(min, max), position| {
+                (min.min(position), max.max(position))
+            });
+        // Right handed, the camera looks down -Z
+        -(view * self.world_transform)
+            .transform_point3((min + max) / 2.0)
+            .z
+    }
+
+    /// Drawn once per transform of `instances`, with the instanced pipeline.
+    pub fn with_instances(mut self, instances: Instances) -> Self {
+        self.instances = Some(InstanceBuffer::new(instances));
+        self
+    }
+
+    /// Drawn with the skinned pipeline, posed by `skin` before every frame.
+    pub fn with_skin(mut self, skin: SkinBuffer) -> Self {
+        self.skin = Some(skin);
+        self
+    }
+
+    /// Drawn from the vertices `deform`'s shader writes before every frame.
+    pub fn with_deform(mut self, deform: DeformBuffer) -> Self {
+        self.deform = Some(deform);
+        self
+    }
+
+    /// One past the largest joint index the vertices weigh on, zero for a rigid mesh.
+    pub fn joints_used(&self) -> usize {
+        self.mesh
+            .vertices
+            .iter()
+            .flat_map(|vertex| vertex.joints.iter().zip(vertex.weights))
+            .filter(|&(_, weight)| weight != 0.0)
+            .map(|(&joint, _)| joint as usize + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn destroy(&mut self, device: &AAADevice) {
+        self.release_chunks(device);
+        if let Some(instances) = &self.instances {
+            instances.destroy(device);
+        }
+        if let Some(skin) = &self.skin {
+            skin.destroy(device);
+        }
+        if let Some(deform) = &self.deform {
+            deform.destroy(device);
+        }
+    }
+
+    /// Free the GPU copy, the mesh is kept to [`Self::reload`] it. The commands using it must have completed.
+    /// The instance buffer is small and stays.
+    pub fn evict(&mut self, device: &AAADevice) {
+        self.release_chunks(device);
+    }
+
+    /// Drop this mesh's share of the GPU copy, destroyed when no other mesh draws from it.
+    fn release_chunks(&mut self, device: &AAADevice) {
+        if let Some(chunks) = Arc::into_inner(mem::take(&mut self.chunks)) {
+            for chunk in &chunks {
+                chunk.destroy(device);
+            }
+        }
+    }
+
+    /// Record the indexed draw of every chunk, `count` times each. Instanced meshes bind their
+    /// instance buffer next to the vertices, the bound pipeline must be the instanced one.
+    pub fn draw_instanced(
+        &self,
+        device: &AAADevice,
+        command_buffer: vk::CommandBuffer,
+        count: u32,
+    ) {
+        for chunk in self.chunks.iter() {
+            unsafe {
+                match &self.instances {
+                    Some(instances) => device.ash.cmd_bind_vertex_buffers(
+                        command_buffer,
+                        0,
+                        &[chunk.vertex_buffer, instances.buffer],
+                        &[0, 0],
+                    ),
+                    None => device.ash.cmd_bind_vertex_buffers(
+                        command_buffer,
+                        0,
+                        &[chunk.vertex_buffer],
+                        &[0],
+                    ),
+                }
+                device.ash.cmd_bind_index_buffer(
+                    command_buffer,
+                    chunk.index_buffer,
+                    0,
+                    chunk.index_type,
+                );
+                device
+                    .ash
+                    .cmd_draw_indexed(command_buffer, chunk.index_count, count, 0, 0, 0);
+            }
+        }
+    }
+
+    /// Bind the buffers once, then record the indexed draw of every range of the batch after
+    /// `before_draw` pushed its constants. Batches are always a single chunk.
+    pub fn draw_batch(
+        &self,
+        device: &AAADevice,
+        command_buffer: vk::CommandBuffer,
+        mut before_draw: impl FnMut(&BatchRange),
+    ) {
+        let Some(chunk) = self.chunks.first() else {
+            return;
+        };
+        unsafe {
+            device
+                .ash
+                .cmd_bind_vertex_buffers(command_buffer, 0, &[chunk.vertex_buffer], &[0]);
+            device.ash.cmd_bind_index_buffer(
+                command_buffer,
+                chunk.index_buffer,
+                0,
+                chunk.index_type,
+            );
+        }
+        for range in &self.batch {
+            before_draw(range);
+            unsafe {
+                device.ash.cmd_draw_indexed(
+                    command_buffer,
+                    range.index_count,
+                    1,
+                    range.first_index,
+                    0,
+                    0,
+                );
+            }
+        }
+    }
+
+    /// World transform and indices of each separately placed part: the ranges of a batch whose
+    /// meshes have their own transforms, else the whole mesh.
+    pub fn parts(&self) -> Vec<(Mat4, Range<usize>)> {
+        match batching::shared_transform(&self.batch) {
+            Some(shared) => vec![(self.world_transform * shared, 0..self.mesh.indices.len())],
+            None => self
+                .batch
+                .iter()
+                .map(|range| (self.world_transform * range.transform, range.indices()))
+                .collect(),
+        }
+    }
+
+    /// [`Self::parts`] with the user id each reports when picked: every range of a batch keeps the
+    /// id of the mesh it was merged from, even when they share a transform.
+    pub fn picked_parts(&self) -> Vec<(u64, Mat4, Range<usize>)> {
+        if self.batch.is_empty() {
+            return vec![(
+                self.mesh.user_id,
+                self.world_transform,
+                0..self.mesh.indices.len(),
+            )];
+        }
+        self.batch
+            .iter()
+            .map(|range| {
+                (
+                    range.user_id,
+                    self.world_transform * range.transform,
+                    range.indices(),
+                )
+            })
+            .collect()
+    }
+
+    /// Whether a part of the mesh may be seen through `frustum`. Instanced, skinned and deformed
+    /// meshes are placed on the GPU, they are always in view.
+    pub fn in_frustum(&self, frustum: &Frustum) -> bool {
+        if self.instances.is_some() || self.skin.is_some() || self.deform.is_some() {
+            return true;
+        }
+        let parts = self.parts();
+        let bounds = self.bounds.get_or_init(|| {
+            parts
+                .iter()
+                .map(|(_, range)| {
+                    let positions: Vec<Vec3> = self.mesh.indices[range.clone()]
+                        .iter()
+                        .map(|&index| self.mesh.vertices[index as usize].position())
+                        .collect();
+                    BoundingSphere::of_points(&positions)
+                })
+                .collect()
+        });
+        parts.iter().zip(bounds).any(|((transform, _), bounds)| {
+            bounds.is_some_and(|bounds| frustum.intersects_sphere(bounds.transformed(*transform)))
+        })
+    }
+
+    /// Instances drawn by [`Self::draw_instanced`], one for meshes that are not instanced.
+    pub fn instance_count(&self) -> u32 {
+        self.instances
+            .as_ref()
+            .map_or(1, |instances| instances.count)
+    }
+
+    pub fn is_resident(&self) -> bool {
+        !self.chunks.is_empty()
+    }
+
+    /// Replace the vertices. The buffers are written in place when the data fits them and
+    /// reallocated when it grows, the commands reading them must have completed. A batch becomes
+    /// a single mesh, its merged meshes no longer placed on their own.
+    pub fn update_vertices(
+        &mut self,
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        upload: AAAUploadContext,
+        vertices: &[Vertex],
+    ) {
+        self.mesh.vertices = vertices.to_vec();
+        self.batch.clear();
+        self.bounds.take();
+        self.refresh(device, device_memory_properties, upload, true, false);
+    }
+
+    /// Replace the indices, like [`Self::update_vertices`].
+    pub fn update_indices(
+        &mut self,
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        upload: AAAUploadContext,
+        indices: &[u32],
+    ) {
+        self.mesh.indices = indices.to_vec();
+        self.batch.clear();
+        self.bounds.take();
+        self.refresh(device, device_memory_properties, upload, false, true);
+    }
+
+    fn refresh(
+        &mut self,
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        upload: AAAUploadContext,
+        vertices: bool,
+        indices: bool,
+    ) {
+        // An evicted mesh picks the new data up when it is reloaded
+        if !self.is_resident() {
+            return;
+        }
+        let index_data = IndexData::compact(&self.mesh.indices, self.mesh.vertices.len());
+        let vertex_bytes = as_bytes(&self.mesh.vertices);
+        // A shared copy is left to the other meshes, this one gets its own
+        let fits = match Arc::get_mut(&mut self.chunks).map(|chunks| chunks.as_slice()) {
+            Some([chunk]) => {
+                device.mesh_limits.fits(&self.mesh)
+                    && vertex_bytes.len() as u64 <= chunk.vertex_capacity
+                    && index_data.size_bytes() <= chunk.index_capacity
+                    && index_data.index_type() == chunk.index_type
+            }
+            _ => false,
+        };
+        if !fits {
+            self.release_chunks(device);
+            self.chunks = Arc::new(self.mesh.upload(
+                device,
+                device_memory_properties,
+                Some(upload),
+            ));
+            return;
+        }
+        let chunk = &mut Arc::get_mut(&mut self.chunks).unwrap()[0];
+        if vertices {
+            chunk.write(
+                device,
+                device_memory_properties,
+                upload,
+                (
+                    chunk.vertex_buffer,
+                    chunk.vertex_buffer_memory,
+                    chunk.vertex_capacity,
+                ),
+                vertex_bytes,
+            );
+        }
+        if indices {
+            chunk.write(
+                device,
+                device_memory_properties,
+                upload,
+                (
+                    chunk.index_buffer,
+                    chunk.index_buffer_memory,
+                    chunk.index_capacity,
+                ),
+                index_data.as_bytes(),
+            );
+        }
+        chunk.index_count = index_data.len() as u32;
+    }
+
+    pub fn reload(
+        &mut self,
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        upload: Option<AAAUploadContext>,
+    ) {
+        self.chunks = Arc::new(self.mesh.upload(device, device_memory_properties, upload));
+    }
+}
+
+/// Handles of a window's meshes, shared by the event loop and the render thread. A handle is
+/// handed out when the mesh is posted, before the render thread registers it, and its slot is
+/// only reused once the render thread removed the mesh.
+#[derive(Debug, Default)]
+pub(crate) struct MeshHandles {
+    slots: Mutex<HandleSlots>,
+}
+
+#[derive(Debug, Default)]
+struct HandleSlots {
+    generations: Vec<u32>,
+    free: Vec<usize>,
+}
+
+impl MeshHandles {
+    pub fn reserve(&self) -> MeshHandle {
+        let mut slots = self.slots.lock().unwrap();
+        match slots.free.pop() {
+            Some(index) => MeshHandle::new(index, slots.generations[index]),
+            None => {
+                slots.generations.push(0);
+                MeshHandle::new(slots.generations.len() - 1, 0)
+            }
+        }
+    }
+
+    /// Free the slot of `handle` for a later mesh, under a new generation.
+    fn release(&self, handle: MeshHandle) {
+        let mut slots = self.slots.lock().unwrap();
+        let generation = &mut slots.generations[handle.index()];
+        *generation = generation.wrapping_add(1);
+        slots.free.push(handle.index());
+    }
+}
+
+/// Registered meshes addressed by handles that stay valid while other meshes are removed.
+/// Freed slots are reused with a new generation so stale handles resolve to nothing.
+#[derive(Debug, Default)]
+pub(crate) struct MeshRegistry {
+    slots: Vec<MeshSlot>,
+    /// Hands out the slots, shared with the window's event states.
+    handles: Arc<MeshHandles>,
+    /// Last mesh registered with each geometry hash, checked again before its copy is shared.
+    by_geometry: HashMap<u64, MeshHandle>,
+    /// Meshes registered so far, slots are reused so their order is not the registration's.
+    registered: u64,
+}
+
+#[derive(Debug, Default)]
+struct MeshSlot {
+    generation: u32,
+    mesh: Option<RegisteredMesh>,
+}
+
+impl MeshRegistry {
+    pub fn new(handles: Arc<MeshHandles>) -> Self {
+        Self {
+            handles,
+            ..Default::default()
+        }
+    }
+
+    pub fn insert(&mut self, registered_mesh: RegisteredMesh) -> MeshHandle {
+        let handle = self.handles.reserve();
+        self.insert_at(handle, registered_mesh);
+        handle
+    }
+
+    /// Register `registered_mesh` under `handle`, reserved from the registry's [`MeshHandles`]
+    /// when the mesh was posted.
+    pub fn insert_at(&mut self, handle: MeshHandle, mut registered_mesh: RegisteredMesh) {
+        registered_mesh.registered = self.registered;
+        self.registered += 1;
+        if self.slots.len() <= handle.index() {
+            self.slots
+                .resize_with(handle.index() + 1, MeshSlot::default);
+        }
+        self.slots[handle.index()] = MeshSlot {
+            generation: handle.generation(),
+            mesh: Some(registered_mesh),
+        };
+    }
+
+    /// GPU copy of a resident mesh with the vertices and indices of `mesh`, `hash` being its
+    /// [`crate::assets::geometry_hash`]. Batches are never shared.
+    pub fn find_geometry(&self, hash: u64, mesh: &Mesh) -> Option<Arc<Vec<MeshBuffers>>> {
+        let held = self.get(*self.by_geometry.get(&hash)?)?;
+        let same = held.is_resident()
+            && held.batch.is_empty()
+            && as_bytes(&held.mesh.vertices) == as_bytes(&mesh.vertices)
+            && held.mesh.indices == mesh.indices;
+        same.then(|| held.chunks.clone())
+    }
+
+    /// Offer the GPU copy of `handle` to the meshes registered later with the same geometry.
+    pub fn remember_geometry(&mut self, hash: u64, handle: MeshHandle) {
+        self.by_geometry.insert(hash, handle);
+    }
+
+    /// The GPU copy is left to the caller to destroy.
+    pub fn remove(&mut self, handle: MeshHandle) -> Option<RegisteredMesh> {
+        self.get(handle)?;
+        self.handles.release(handle);
+        let slot = &mut self.slots[handle.index()];
+        slot.generation = slot.generation.wrapping_add(1);
+        slot.mesh.take()
+    }
+
+    pub fn get(&self, handle: MeshHandle) -> Option<&RegisteredMesh> {
+        self.slots
+            .get(handle.index())
+            .filter(|slot| slot.generation == handle.generation())
+            .and_then(|slot| slot.mesh.as_ref())
+    }
+
+    pub fn get_mut(&mut self, handle: MeshHandle) -> Option<&mut RegisteredMesh> {
+        self.slots
+            .get_mut(handle.index())
+            .filter(|slot| slot.generation == handle.generation())
+            .and_then(|slot| slot.mesh.as_mut())
+    }
+
+    /// Live meshes in slot order.
+    pub fn iter(&self) -> impl Iterator<Item = (MeshHandle, &RegisteredMesh)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            let handle = MeshHandle::new(index, slot.generation);
+            slot.mesh.as_ref().map(|mesh| (handle, mesh))
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (MeshHandle, &mut RegisteredMesh)> {
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                let handle = MeshHandle::new(index, slot.generation);
+                slot.mesh.as_mut().map(|mesh| (handle, mesh))
+            })
+    }
+
+    pub fn meshes(&self) -> impl Iterator<Item = &RegisteredMesh> {
+        self.iter().map(|(_, registered_mesh)| registered_mesh)
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.mesh.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.iter().all(|slot| slot.mesh.is_none())
+    }
+
+    /// Parent slot of every slot, `None` for empty slots, roots and removed parents.
+    fn parent_slots(&self) -> Vec<Option<usize>> {
+        self.slots
+            .iter()
+            .map(|slot| {
+                let parent = slot.mesh.as_ref()?.parent?;
+                self.get(parent).map(|_| parent.index())
+            })
+            .collect()
+    }
+
+    /// Returns whether the mesh is still registered.
+    pub fn set_local_transform(&mut self, handle: MeshHandle, transform: Mat4) -> bool {
+        let Some(registered_mesh) = self.get_mut(handle) else {
+            return false;
+        };
+        registered_mesh.mesh.transform = transform;
+        true
+    }
+
+    /// Returns whether the link was made, refused when either mesh is gone or it would close a cycle.
+    pub fn set_parent(&mut self, child: MeshHandle, parent: Option<MeshHandle>) -> bool {
+        if let Some(parent) = parent {
+            if self.get(parent).is_none()
+                || hierarchy::creates_cycle(&self.parent_slots(), child.index(), parent.index())
+            {
+                return false;
+            }
+        }
+        let Some(registered_mesh) = self.get_mut(child) else {
+            return false;
+        };
+        registered_mesh.parent = parent;
+        true
+    }
+
+    /// Update the world transform of every mesh from its parents. Returns the meshes found in a
+    /// cycle, drawn as roots.
+    pub fn resolve_world_transforms(&mut self) -> Vec<MeshHandle> {
+        let locals: Vec<Mat4> = self
+            .slots
+            .iter()
+            .map(|slot| {
+                slot.mesh
+                    .as_ref()
+                    .map_or(Mat4::IDENTITY, |registered_mesh| {
+                        registered_mesh.mesh.transform
+                    })
+            })
+            .collect();
+        let resolved = hierarchy::resolve_world_transforms(&locals, &self.parent_slots());
+        for (slot, world) in self.slots.iter_mut().zip(resolved.world) {
+            if let Some(registered_mesh) = &mut slot.mesh {
+                registered_mesh.world_transform = world;
+            }
+        }
+        resolved
+            .cycles
+            .into_iter()
+            .map(|index| MeshHandle::new(index, self.slots[index].generation))
+            .collect()
+    }
+
+    /// World transform of the parent of `handle`, identity for roots.
+    pub fn parent_world_transform(&self, handle: MeshHandle) -> Mat4 {
+        self.get(handle)
+            .and_then(|registered_mesh| registered_mesh.parent)
+            .and_then(|parent| self.get(parent))
+            .map_or(Mat4::IDENTITY, |parent| parent.world_transform)
+    }
+}
+
+#[derive(Debug)]
+pub struct Model {
+    pub meshes: Vec<Mesh>,
+    /// Base color texture of the meshes, an index in [`Scene::textures`].
+    pub texture: Option<usize>,
+}
+
+#[derive(Debug)]
+pub struct Scene {
+    pub models: Vec<Model>,
+    /// Up axis the scene was authored with.
+    pub convention: WorldConvention,
+    /// Decoded images the models refer to as whole RGBA8 texture updates, not bound yet.
+    pub textures: Vec<TextureUpdate>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOLERANCE: f32 = 1e-4;
+
+    /// Side of a generated face its triangles should be counter clockwise from.
+    enum Facing {
+        Axis(Vec3),
+        /// Away from the origin, for closed shapes centered on it.
+        Outward,
+    }
+
+    fn position(vertex: &Vertex) -> Vec3 {
+        Vec3::new(vertex.pos[0], vertex.pos[1], vertex.pos[2])
+    }
+
+    #[test]
+    fn primitives_are_indexed_and_wound_counter_clockwise() {
+        let color = [1.0; 4];
+        let (segments, rings, subdivisions) = (24, 12, 3);
+        let cells = (subdivisions + 1) as usize;
+        let meshes = [
+            (
+                Mesh::quad(Vec2::new(2.0, 1.0), color),
+                4,
+                6,
+                Facing::Axis(Vec3::Z),
+            ),
+            (Mesh::cube(1.0, color), 24, 36, Facing::Outward),
+            (
+                Mesh::uv_sphere(1.0, segments, rings, color),
+                (segments * 2 + (segments + 1) * (rings - 1)) as usize,
+                (segments * (rings - 1) * 6) as usize,
+                Facing::Outward,
+            ),
+            (
+                Mesh::plane(4.0, 2.0, subdivisions, color),
+                (cells + 1) * (cells + 1),
+                cells * cells * 6,
+                Facing::Axis(Vec3::Y),
+            ),
+        ];
+        for (mesh, vertex_count, index_count, facing) in meshes {
+            assert_eq!(mesh.vertices.len(), vertex_count);
+            assert_eq!(mesh.indices.len(), index_count);
+            assert!(mesh
+                .indices
+                .iter()
+                .all(|&index| (index as usize) < mesh.vertices.len()));
+            for vertex in &mesh.vertices {
+                assert!(vertex.uv.iter().all(|c| (0.0..=1.0).contains(c)));
+                assert!((Vec3::from(vertex.normal).length() - 1.0).abs() < TOLERANCE);
+            }
+            for triangle in mesh.indices.chunks_exact(3) {
+                let [a, b, c] = [0, 1, 2].map(|i| position(&mesh.vertices[triangle[i] as usize]));
+                let face = (b - a).cross(c - a);
+                let expected = match facing {
+                    Facing::Axis(axis) => axis,
+                    Facing::Outward => (a + b + c) / 3.0,
+                };
+                // Counter clockwise seen from the side it faces, like the pipeline's front face
+                assert!(face.dot(expected) > 0.0, "{triangle:?}");
+                let normal = Vec3::from(mesh.vertices[triangle[0] as usize].normal);
+                assert!(normal.dot(face) > 0.0, "{triangle:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn quad_top_left_samples_the_top_left_texel() {
+        let quad = Mesh::quad(Vec2::new(2.0, 1.0), [1.0; 4]);
+        assert_eq!(quad.vertices[3].pos[..2], [-1.0, 0.5]);
+        assert_eq!(quad.vertices[3].uv, [0.0, 0.0]);
+    }
+
+    /// Unit normals and tangents at right angles, every tangent of the same handedness, normals
+    /// pointing away from the origin.
+    fn assert_orthonormal_outward(mesh: &Mesh) {
+        let handedness = mesh.vertices[0].tangent[3];
+        for (i, vertex) in mesh.vertices.iter().enumerate() {
+            let normal = Vec3::from(vertex.normal);
+            let tangent = Vec3::new(vertex.tangent[0], vertex.tangent[1], vertex.tangent[2]);
+            assert!((normal.length() - 1.0).abs() < TOLERANCE, "vertex {i}");
+            assert!((tangent.length() - 1.0).abs() < TOLERANCE, "vertex {i}");
+            assert!(normal.dot(tangent).abs() < TOLERANCE, "vertex {i}");
+            assert_eq!(vertex.tangent[3], handedness, "vertex {i}");
+            assert!(normal.dot(position(vertex)) > 0.0, "vertex {i}");
+        }
+    }
+
+    #[test]
+    fn generated_bases_are_orthonormal() {
+        let mut cube = Mesh::cube(1.0, [1.0; 4]);
+        cube.generate_normals(true);
+        cube.generate_tangents();
+        assert_orthonormal_outward(&cube);
+
+        let mut sphere = Mesh::uv_sphere(1.0, 32, 16, [1.0; 4]);
+        sphere.generate_normals(true);
+        sphere.generate_tangents();
+        assert_orthonormal_outward(&sphere);
+    }
+
+    /// A unit quad's UVs run along +X and down -Y, its tangent is known exactly.
+    #[test]
+    fn quad_tangent_and_handedness() {
+        let mut quad = Mesh::quad(Vec2::ONE, [1.0; 4]);
+        quad.generate_tangents();
+        for vertex in &quad.vertices {
+            assert_eq!(vertex.tangent, [1.0, 0.0, 0.0, -1.0]);
+        }
+    }
+
+    #[test]
+    fn generators_author_the_generated_tangents() {
+        let generators: [fn() -> Mesh; 2] = [
+            || Mesh::quad(Vec2::ONE, [1.0; 4]),
+            || Mesh::plane(2.0, 2.0, 3, [1.0; 4]),
+        ];
+        for generator in generators {
+            let authored = generator();
+            let mut generated = generator();
+            generated.generate_tangents();
+            for (authored, generated) in authored.vertices.iter().zip(&generated.vertices) {
+                assert_eq!(authored.tangent, generated.tangent);
+            }
+        }
+    }
+
+    #[test]
+    fn degenerate_triangles_stay_finite() {
+        let mut degenerate = Mesh::cube(1.0, [1.0; 4]);
+        for vertex in &mut degenerate.vertices {
+            vertex.uv = [0.5, 0.5];
+        }
+        degenerate.vertices[0].pos = degenerate.vertices[1].pos;
+        degenerate.generate_normals(false);
+        degenerate.generate_tangents();
+        for vertex in &degenerate.vertices {
+            assert!(vertex
+                .normal
+                .iter()
+                .chain(&vertex.tangent)
+                .all(|v| v.is_finite()));
+        }
+    }
+
+    /// Corners of every triangle of `mesh`, the soup a loader without indices would produce.
+    fn soup(mesh: &Mesh) -> Vec<Vertex> {
+        mesh.indices
+            .iter()
+            .map(|&index| mesh.vertices[index as usize])
+            .collect()
+    }
+
+    /// Positions of every triangle's corners, to compare meshes indexed differently.
+    fn triangles(mesh: &Mesh) -> Vec<[f32; 4]> {
+        soup(mesh).iter().map(|vertex| vertex.pos).collect()
+    }
+
+    #[test]
+    fn cube_soup_welds_back_to_shared_vertices() {
+        let cube = Mesh::cube(1.0, [1.0; 4]);
+        let raw = soup(&cube);
+        assert_eq!(raw.len(), 36);
+        // The faces' normals and UVs keep their corners apart, the positions alone merge them
+        for (mode, expected) in [
+            (WeldMode::default(), 24),
+            (WeldMode::Positions { epsilon: 1e-5 }, 8),
+        ] {
+            let welded = Mesh::indexed_from_triangles_with(raw.clone(), mode);
+            assert_eq!(welded.vertices.len(), expected, "{mode:?}");
+            assert_eq!(triangles(&welded), triangles(&cube), "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn positions_within_the_epsilon_merge() {
+        let mut nudged = soup(&Mesh::cube(1.0, [1.0; 4]));
+        for (i, vertex) in nudged.iter_mut().enumerate() {
+            vertex.pos[i % 3] += 1e-7;
+        }
+        let welded =
+            Mesh::indexed_from_triangles_with(nudged, WeldMode::Positions { epsilon: 1e-4 });
+        assert_eq!(welded.vertices.len(), 8);
+    }
+
+    #[test]
+    fn flattened_triangles_are_dropped() {
+        let raw = soup(&Mesh::cube(1.0, [1.0; 4]));
+        let mut sliver = raw[..3].to_vec();
+        sliver.extend_from_slice(&raw[..3]);
+        sliver[4].pos = sliver[3].pos;
+        sliver[4].pos[0] += 1e-6;
+        let welded =
+            Mesh::indexed_from_triangles_with(sliver, WeldMode::Positions { epsilon: 1e-3 });
+        assert_eq!(welded.indices.len(), 3);
+        assert_eq!(welded.vertices.len(), 3);
+    }
+
+    #[test]
+    fn compact_drops_unreferenced_vertices() {
+        let mut padded = Mesh::indexed_from_triangles(soup(&Mesh::cube(1.0, [1.0; 4])));
+        let unused = padded.vertices[0];
+        padded.vertices.insert(0, unused);
+        padded.vertices.push(unused);
+        for index in &mut padded.indices {
+            *index += 1;
+        }
+        let before = triangles(&padded);
+        padded.compact();
+        assert_eq!(padded.vertices.len(), 24);
+        assert_eq!(triangles(&padded), before);
+    }
+
+    #[test]
+    fn indices_narrow_up_to_65536_vertices() {
+        let indices = [0, 1, 65535, 65535, 1, 0];
+        let narrow = IndexData::compact(&indices, 65536);
+        assert_eq!(
+            narrow,
+            IndexData::U16(vec![0, 1, 65535, 65535, 1, 0]),
+            "Every vertex is addressable with 16 bits"
+        );
+        assert_eq!(narrow.index_type(), vk::IndexType::UINT16);
+        assert_eq!(narrow.size_bytes(), 6 * 2);
+        assert_eq!(narrow.as_bytes().len(), 6 * 2);
+        assert_eq!(IndexData::stride(65536), 2);
+
+        let indices = [0, 1, 65536, 65536, 1, 0];
+        let wide = IndexData::compact(&indices, 65537);
+        assert_eq!(
+            wide,
+            IndexData::U32(indices.to_vec()),
+            "Vertex 65536 needs 32 bits"
+        );
+        assert_eq!(wide.index_type(), vk::IndexType::UINT32);
+        assert_eq!(wide.size_bytes(), 6 * 4);
+        assert_eq!(wide.as_bytes().len(), 6 * 4);
+        assert_eq!(IndexData::stride(65537), 4);
+        assert_eq!(wide.len(), narrow.len());
+    }
+
+    /// Square grid of `size` by `size` cells in rows, two triangles per cell.
+    fn grid(size: u32) -> Mesh {
+        let row = size + 1;
+        let vertices = (0..row * row)
+            .map(|i| Vertex {
+                pos: [(i % row) as f32, (i / row) as f32, 0.0, 1.0],
+                uv: [0.0; 2],
+                color: [1.0; 4],
+                normal: [0.0, 0.0, 1.0],
+                tangent: [1.0, 0.0, 0.0, 1.0],
+                joints: [0; 4],
+                weights: [0.0; 4],
+            })
+            .collect();
+        let mut indices = Vec::new();
+        for y in 0..size {
+            for x in 0..size {
+                let a = y * row + x;
+                indices.extend([a, a + 1, a + row + 1, a, a + row + 1, a + row]);
+            }
+        }
+        Mesh {
+            vertices,
+            indices,
+            transform: Mat4::IDENTITY,
+            user_id: 0,
+        }
+    }
+
+    #[test]
+    fn split_chunks_fit_and_keep_the_triangles_in_order() {
+        const GRID: u32 = 256;
+        let mesh = grid(GRID);
+        let limits = MeshLimits {
+            max_vertices: 1 << 12,
+            max_indices: 1 << 14,
+        };
+        assert!(!limits.fits(&mesh));
+
+        let chunks = mesh.split(limits);
+        let minimum = mesh
+            .indices
+            .len()
+            .div_ceil(limits.max_indices as usize / 3 * 3);
+        assert!(
+            (minimum..=minimum * 2).contains(&chunks.len()),
+            "{} chunks, expected about {minimum}",
+            chunks.len()
+        );
+        let mut source = mesh.indices.chunks_exact(3);
+        for (i, chunk) in chunks.iter().enumerate() {
+            assert!(limits.fits(chunk), "chunk {i} exceeds the limits");
+            for triangle in chunk.indices.chunks_exact(3) {
+                let original = source.next().expect("triangles were duplicated");
+                for (&index, &original) in triangle.iter().zip(original) {
+                    assert_eq!(
+                        chunk.vertices[index as usize].pos, mesh.vertices[original as usize].pos,
+                        "chunk {i}"
+                    );
+                }
+            }
+        }
+        assert!(source.next().is_none(), "triangles were lost");
+
+        // Only the rows shared by consecutive chunks are duplicated
+        let vertices: usize = chunks.iter().map(|chunk| chunk.vertices.len()).sum();
+        let shared_row = (GRID + 1) as usize * 2;
+        assert!(vertices <= mesh.vertices.len() + (chunks.len() - 1) * shared_row);
+    }
+
+    fn registered_cube() -> RegisteredMesh {
+        Mesh::cube(1.0, [1.0; 4]).share(Arc::new(Vec::new()))
+    }
+
+    #[test]
+    fn materials_key_the_variant_their_mesh_draws_with() {
+        let mut cube = registered_cube();
+        let options = cube.pipeline_options();
+        cube.resolve_material(false);
+        assert_eq!(
+            cube.material,
+            MaterialKey::Default(MeshPipeline::Triangles, options)
+        );
+        cube.blend = true;
+        cube.resolve_material(false);
+        assert_eq!(
+            cube.material,
+            MaterialKey::Default(MeshPipeline::Blended, options)
+        );
+        let material = Material::new("shader.vert.spv", "shader.frag.spv");
+        cube.assigned_material = Some(material.clone());
+        cube.resolve_material(false);
+        assert_eq!(cube.material, MaterialKey::Material(material));
+        // Nothing to sample until the array exists
+        cube.assigned_material = Some(Material::texture_array());
+        cube.resolve_material(false);
+        assert_eq!(
+            cube.material,
+            MaterialKey::Default(MeshPipeline::Triangles, options),
+            "Keyed on the texture array without one"
+        );
+        cube.resolve_material(true);
+        assert_eq!(
+            cube.material,
+            MaterialKey::Material(Material::texture_array())
+        );
+    }
+
+    #[test]
+    fn reserved_handles_resolve_once_registered() {
+        let handles = Arc::new(MeshHandles::default());
+        let mut registry = MeshRegistry::new(handles.clone());
+        let demo = registry.insert(registered_cube());
+        // Posted by the application before the render thread registers it
+        let posted = handles.reserve();
+        assert_ne!(posted, demo);
+        assert!(
+            registry.get(posted).is_none(),
+            "Resolved before registration"
+        );
+        let mut cube = Mesh::cube(1.0, [1.0; 4]);
+        cube.user_id = 7;
+        registry.insert_at(posted, cube.share(Arc::new(Vec::new())));
+        assert_eq!(registry.get(posted).map(|mesh| mesh.mesh.user_id), Some(7));
+        assert_eq!(registry.len(), 2);
+    }
+
+    #[test]
+    fn removed_slots_are_reused_under_a_new_generation() {
+        let handles = Arc::new(MeshHandles::default());
+        let mut registry = MeshRegistry::new(handles.clone());
+        let first = registry.insert(registered_cube());
+        // A slot is only handed out again once the render thread removed its mesh
+        let pending = handles.reserve();
+        assert_ne!(pending.index(), first.index());
+        assert!(registry.remove(first).is_some());
+        assert!(registry.remove(first).is_none(), "Removed twice");
+        let reused = handles.reserve();
+        assert_eq!(reused.index(), first.index());
+        assert_ne!(reused.generation(), first.generation());
+        registry.insert_at(reused, registered_cube());
+        assert!(registry.get(first).is_none(), "The stale handle resolved");
+        assert!(registry.get(reused).is_some());
+    }
+}
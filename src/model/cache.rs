@@ -0,0 +1,506 @@
+//! Pre-baked meshes, models and scenes in a little-endian binary layout read back without parsing.
+//!
+//! A file is a 4 byte magic naming what it holds, a `u32` version, then its records:
+//!
+//! - mesh: vertex count and index count as `u32`, the transform as 16 `f32` in column order, the
+//!   user id as a `u64`, every vertex as 17 `f32` in field order up to the tangent, its joints as 4 `u16` and its weights as
+//!   4 `f32`, then the indices as `u32`
+//! - model: its texture as a `u32`, `u32::MAX` for none, the mesh count as a `u32` and the meshes
+//! - scene: its convention as a `u8`, the texture count as a `u32` and every texture's region,
+//!   row pitch and byte count as `u32` followed by its bytes, then the model count and the models
+use super::{Mesh, Model, Scene, Vertex};
+use crate::{
+    error::PulsarError,
+    texture::{TextureRegion, TextureUpdate},
+    world::WorldConvention,
+};
+use glam::Mat4;
+use std::{error::Error, mem, path::Path};
+
+/// Bumped with every change of the layout, older files are refused rather than misread.
+pub const CACHE_VERSION: u32 = 3;
+
+const MESH_MAGIC: [u8; 4] = *b"PMSH";
+const MODEL_MAGIC: [u8; 4] = *b"PMDL";
+const SCENE_MAGIC: [u8; 4] = *b"PSCN";
+const VERTEX_BYTES: usize = 21 * mem::size_of::<f32>() + 4 * mem::size_of::<u16>();
+const NO_TEXTURE: u32 = u32::MAX;
+
+impl Mesh {
+    /// Write the cache of this mesh to `path`, see [`Self::read_cache`].
+    pub fn write_cache(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, self.to_cache_bytes())
+    }
+
+    /// Read a mesh written by [`Self::write_cache`]. A damaged or outdated file is a
+    /// [`PulsarError::CacheCorrupt`] or a [`PulsarError::CacheVersion`], I/O errors stay `io::Error`.
+    pub fn read_cache(path: &Path) -> Result<Mesh, Box<dyn Error>> {
+        Ok(Self::from_cache_bytes(&std::fs::read(path)?)?)
+    }
+
+    pub fn to_cache_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::new(MESH_MAGIC);
+        writer.mesh(self);
+        writer.bytes
+    }
+
+    pub fn from_cache_bytes(bytes: &[u8]) -> Result<Mesh, PulsarError> {
+        let mut reader = Reader::new(bytes, MESH_MAGIC)?;
+        let mesh = reader.mesh()?;
+        reader.finish()?;
+        Ok(mesh)
+    }
+}
+
+impl Model {
+    /// Write every mesh of the model with its transform and its texture index to `path`.
+    pub fn write_cache(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, self.to_cache_bytes())
+    }
+
+    /// Read a model written by [`Self::write_cache`], errors as in [`Mesh::read_cache`].
+    pub fn read_cache(path: &Path) -> Result<Model, Box<dyn Error>> {
+        Ok(Self::from_cache_bytes(&std::fs::read(path)?)?)
+    }
+
+    pub fn to_cache_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::new(MODEL_MAGIC);
+        writer.model(self);
+        writer.bytes
+    }
+
+    pub fn from_cache_bytes(bytes: &[u8]) -> Result<Model, PulsarError> {
+        let mut reader = Reader::new(bytes, MODEL_MAGIC)?;
+        let model = reader.model()?;
+        reader.finish()?;
+        Ok(model)
+    }
+}
+
+impl Scene {
+    /// Write the models, their textures and the convention to `path` as one file.
+    pub fn write_cache(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, self.to_cache_bytes())
+    }
+
+    /// Read a scene written by [`Self::write_cache`], errors as in [`Mesh::read_cache`].
+    pub fn read_cache(path: &Path) -> Result<Scene, Box<dyn Error>> {
+        Ok(Self::from_cache_bytes(&std::fs::read(path)?)?)
+    }
+
+    pub fn to_cache_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::new(SCENE_MAGIC);
+        writer.bytes.push(match self.convention {
+            WorldConvention::YUp => 0,
+            WorldConvention::ZUp => 1,
+        });
+        writer.u32(self.textures.len() as u32);
+        for texture in &self.textures {
+            let region = texture.region;
+            for value in [region.x, region.y, region.width, region.height] {
+                writer.u32(value);
+            }
+            writer.u32(texture.row_pitch);
+            writer.u32(texture.data.len() as u32);
+            writer.bytes.extend_from_slice(&texture.data);
+        }
+        writer.u32(self.models.len() as u32);
+        for model in &self.models {
+            writer.model(model);
+        }
+        writer.bytes
+    }
+
+    pub fn from_cache_bytes(bytes: &[u8]) -> Result<Scene, PulsarError> {
+        let mut reader = Reader::new(bytes, SCENE_MAGIC)?;
+        let convention = match reader.take(1)?[0] {
+            0 => WorldConvention::YUp,
+            1 => WorldConvention::ZUp,
+            other => return Err(corrupt(format!("unknown world convention {other}"))),
+        };
+        let texture_count = reader.count(6 * mem::size_of::<u32>())?;
+        let mut textures = Vec::with_capacity(texture_count);
+        for _ in 0..texture_count {
+            let [x, y, width, height, row_pitch, len] = [(); 6].map(|_| reader.u32());
+            let region = TextureRegion {
+                x: x?,
+                y: y?,
+                width: width?,
+                height: height?,
+            };
+            let data = reader.take(len? as usize)?.to_vec();
+            let texture = TextureUpdate::with_row_pitch(region, data, row_pitch?)
+                .map_err(|error| corrupt(format!("texture {}: {error}", textures.len())))?;
+            textures.push(texture);
+        }
+        let model_count = reader.count(2 * mem::size_of::<u32>())?;
+        let mut models = Vec::with_capacity(model_count);
+        for _ in 0..model_count {
+            let model = reader.model()?;
+            if model
+                .texture
+                .is_some_and(|texture| texture >= textures.len())
+            {
+                return Err(corrupt(format!(
+                    "model {} refers to a missing texture",
+                    models.len()
+                )));
+            }
+            models.push(model);
+        }
+        reader.finish()?;
+        Ok(Scene {
+            models,
+            convention,
+            textures,
+        })
+    }
+}
+
+fn corrupt(reason: String) -> PulsarError {
+    PulsarError::CacheCorrupt { reason }
+}
+
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn new(magic: [u8; 4]) -> Self {
+        let mut bytes = magic.to_vec();
+        bytes.extend_from_slice(&CACHE_VERSION.to_le_bytes());
+        Self { bytes }
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f32s(&mut self, values: &[f32]) {
+        for value in values {
+            self.bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    fn mesh(&mut self, mesh: &Mesh) {
+        self.u32(mesh.vertices.len() as u32);
+        self.u32(mesh.indices.len() as u32);
+        self.f32s(&mesh.transform.to_cols_array());
+        self.bytes.extend_from_slice(&mesh.user_id.to_le_bytes());
+        for vertex in &mesh.vertices {
+            self.f32s(&vertex.pos);
+            self.f32s(&vertex.uv);
+            self.f32s(&vertex.color);
+            self.f32s(&vertex.normal);
+            self.f32s(&vertex.tangent);
+            for joint in vertex.joints {
+                self.bytes.extend_from_slice(&joint.to_le_bytes());
+            }
+            self.f32s(&vertex.weights);
+        }
+        for &index in &mesh.indices {
+            self.u32(index);
+        }
+    }
+
+    fn model(&mut self, model: &Model) {
+        self.u32(model.texture.map_or(NO_TEXTURE, |texture| texture as u32));
+        self.u32(model.meshes.len() as u32);
+        for mesh in &model.meshes {
+            self.mesh(mesh);
+        }
+    }
+}
+
+/// Bounds checked cursor, every count is checked against the bytes left before allocating.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8], magic: [u8; 4]) -> Result<Self, PulsarError> {
+        let mut reader = Self { bytes };
+        let found = reader
+            .take(magic.len())
+            .map_err(|_| corrupt("not a cache".into()))?;
+        if found != magic {
+            return Err(corrupt(format!(
+                "expected a {} cache",
+                String::from_utf8_lossy(&magic)
+            )));
+        }
+        let version = reader.u32()?;
+        if version != CACHE_VERSION {
+            return Err(PulsarError::CacheVersion {
+                found: version,
+                supported: CACHE_VERSION,
+            });
+        }
+        Ok(reader)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], PulsarError> {
+        if len > self.bytes.len() {
+            return Err(corrupt(format!(
+                "truncated, {len} bytes expected and {} left",
+                self.bytes.len()
+            )));
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> Result<u32, PulsarError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, PulsarError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn f32s<const N: usize>(&mut self) -> Result<[f32; N], PulsarError> {
+        let bytes = self.take(N * 4)?;
+        Ok(std::array::from_fn(|i| {
+            f32::from_le_bytes(bytes[i * 4..i * 4 + 4].try_into().unwrap())
+        }))
+    }
+
+    fn u16s<const N: usize>(&mut self) -> Result<[u16; N], PulsarError> {
+        let bytes = self.take(N * 2)?;
+        Ok(std::array::from_fn(|i| {
+            u16::from_le_bytes(bytes[i * 2..i * 2 + 2].try_into().unwrap())
+        }))
+    }
+
+    /// A count of items at least `item_size` bytes each, refused when the file is too short.
+    fn count(&mut self, item_size: usize) -> Result<usize, PulsarError> {
+        let count = self.u32()? as usize;
+        if count.saturating_mul(item_size) > self.bytes.len() {
+            return Err(corrupt(format!("{count} items do not fit in the file")));
+        }
+        Ok(count)
+    }
+
+    fn mesh(&mut self) -> Result<Mesh, PulsarError> {
+        let vertex_count = self.u32()? as usize;
+        let index_count = self.u32()? as usize;
+        let size = vertex_count
+            .saturating_mul(VERTEX_BYTES)
+            .saturating_add(index_count.saturating_mul(4));
+        if size.saturating_add(16 * 4 + 8) > self.bytes.len() {
+            return Err(corrupt(format!(
+                "{vertex_count} vertices and {index_count} indices do not fit in the file"
+            )));
+        }
+        let transform = Mat4::from_cols_array(&self.f32s::<16>()?);
+        let user_id = self.u64()?;
+        let mut vertices = Vec::with_capacity(vertex_count);
+        for _ in 0..vertex_count {
+            vertices.push(Vertex {
+                pos: self.f32s()?,
+                uv: self.f32s()?,
+                color: self.f32s()?,
+                normal: self.f32s()?,
+                tangent: self.f32s()?,
+                joints: self.u16s()?,
+                weights: self.f32s()?,
+            });
+        }
+        let mut indices = Vec::with_capacity(index_count);
+        for _ in 0..index_count {
+            let index = self.u32()?;
+            if index as usize >= vertex_count {
+                return Err(corrupt(format!(
+                    "index {index} addresses one of {vertex_count} vertices"
+                )));
+            }
+            indices.push(index);
+        }
+        Ok(Mesh {
+            vertices,
+            indices,
+            transform,
+            user_id,
+        })
+    }
+
+    fn model(&mut self) -> Result<Model, PulsarError> {
+        let texture = match self.u32()? {
+            NO_TEXTURE => None,
+            texture => Some(texture as usize),
+        };
+        let mesh_count = self.count(2 * 4 + 16 * 4 + 8)?;
+        let mut meshes = Vec::with_capacity(mesh_count);
+        for _ in 0..mesh_count {
+            meshes.push(self.mesh()?);
+        }
+        Ok(Model { meshes, texture })
+    }
+
+    fn finish(self) -> Result<(), PulsarError> {
+        match self.bytes.len() {
+            0 => Ok(()),
+            left => Err(corrupt(format!("{left} trailing bytes"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec3;
+
+    fn empty() -> Mesh {
+        Mesh {
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            transform: Mat4::IDENTITY,
+            user_id: 0,
+        }
+    }
+
+    /// A plane needing 32 bit indices, moved off the origin.
+    fn large() -> Mesh {
+        let mut large = Mesh::plane(10.0, 10.0, 120, [0.2, 0.6, 0.3, 1.0]);
+        large.transform = Mat4::from_translation(Vec3::new(1.0, 2.0, 3.0));
+        large.user_id = u64::MAX - 1;
+        large
+    }
+
+    fn assert_round_trips(mesh: &Mesh) {
+        let bytes = mesh.to_cache_bytes();
+        let read = Mesh::from_cache_bytes(&bytes).unwrap();
+        assert_eq!(read.vertices.len(), mesh.vertices.len());
+        assert_eq!(read.indices, mesh.indices);
+        assert_eq!(read.transform, mesh.transform);
+        assert_eq!(read.user_id, mesh.user_id);
+        assert_eq!(read.to_cache_bytes(), bytes);
+    }
+
+    #[test]
+    fn meshes_round_trip() {
+        let large = large();
+        assert!(large.indices.len() > 65536);
+        assert_round_trips(&empty());
+        assert_round_trips(&large);
+    }
+
+    #[test]
+    fn skin_attributes_survive_the_cache() {
+        let mut mesh = Mesh::cube(1.0, [1.0; 4]);
+        for (i, vertex) in mesh.vertices.iter_mut().enumerate() {
+            vertex.joints = [i as u16 % 2, 1, 0, 0];
+            vertex.weights = [0.25, 0.75, 0.0, 0.0];
+        }
+        let read = Mesh::from_cache_bytes(&mesh.to_cache_bytes()).unwrap();
+        for (read, vertex) in read.vertices.iter().zip(&mesh.vertices) {
+            assert_eq!(read.joints, vertex.joints);
+            assert_eq!(read.weights, vertex.weights);
+        }
+    }
+
+    #[test]
+    fn mesh_round_trips_through_a_file() {
+        let large = large();
+        let path =
+            std::env::temp_dir().join(format!("pulsar_mesh_cache_{}.bin", std::process::id()));
+        large.write_cache(&path).unwrap();
+        let read = Mesh::read_cache(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(read.to_cache_bytes(), large.to_cache_bytes());
+        let missing = Mesh::read_cache(&path).expect_err("a missing file should fail");
+        assert!(missing.is::<std::io::Error>(), "{missing}");
+    }
+
+    #[test]
+    fn models_and_scenes_round_trip() {
+        let scene = Scene {
+            models: vec![Model {
+                meshes: vec![Mesh::cube(1.0, [1.0; 4]), empty()],
+                texture: Some(0),
+            }],
+            convention: WorldConvention::ZUp,
+            textures: vec![TextureUpdate::new(TextureRegion::full(2, 2), vec![255; 16]).unwrap()],
+        };
+        let bytes = scene.to_cache_bytes();
+        let read = Scene::from_cache_bytes(&bytes).unwrap();
+        assert_eq!(read.convention, scene.convention);
+        assert_eq!(read.textures, scene.textures);
+        assert_eq!(read.models[0].texture, Some(0));
+        assert_eq!(read.to_cache_bytes(), bytes);
+
+        let model_bytes = scene.models[0].to_cache_bytes();
+        let model = Model::from_cache_bytes(&model_bytes).unwrap();
+        assert_eq!(model.to_cache_bytes(), model_bytes);
+    }
+
+    #[test]
+    fn user_ids_survive_a_scene_saved_and_loaded() {
+        let mut picked = Mesh::cube(1.0, [1.0; 4]);
+        picked.user_id = 42;
+        let scene = Scene {
+            models: vec![Model {
+                meshes: vec![picked, empty()],
+                texture: None,
+            }],
+            convention: WorldConvention::YUp,
+            textures: Vec::new(),
+        };
+        let path =
+            std::env::temp_dir().join(format!("pulsar_scene_cache_{}.bin", std::process::id()));
+        scene.write_cache(&path).unwrap();
+        let read = Scene::read_cache(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let user_ids: Vec<u64> = read.models[0]
+            .meshes
+            .iter()
+            .map(|mesh| mesh.user_id)
+            .collect();
+        assert_eq!(user_ids, [42, 0]);
+    }
+
+    #[test]
+    fn damaged_caches_are_refused() {
+        let mesh_bytes = large().to_cache_bytes();
+        let model_bytes = Model {
+            meshes: vec![empty()],
+            texture: None,
+        }
+        .to_cache_bytes();
+        let mut huge_count = mesh_bytes.clone();
+        huge_count[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        let mut bad_index = Mesh::cube(1.0, [1.0; 4]).to_cache_bytes();
+        let last = bad_index.len() - 4;
+        bad_index[last..].copy_from_slice(&24u32.to_le_bytes());
+        let mut trailing = mesh_bytes.clone();
+        trailing.push(0);
+        let cases: [(&str, &[u8]); 6] = [
+            ("nothing", &[]),
+            ("a truncated mesh", &mesh_bytes[..mesh_bytes.len() - 1]),
+            ("a model read as a mesh", &model_bytes),
+            ("an impossible vertex count", &huge_count),
+            ("an index past the vertices", &bad_index),
+            ("trailing bytes", &trailing),
+        ];
+        for (name, bytes) in cases {
+            let read = Mesh::from_cache_bytes(bytes).map(|mesh| mesh.vertices.len());
+            assert!(
+                matches!(read, Err(PulsarError::CacheCorrupt { .. })),
+                "{name} read as {read:?}"
+            );
+        }
+
+        let mut version = mesh_bytes;
+        version[4] += 1;
+        let read = Mesh::from_cache_bytes(&version).map(|mesh| mesh.vertices.len());
+        assert!(
+            matches!(
+                read,
+                Err(PulsarError::CacheVersion { found, supported })
+                    if found == CACHE_VERSION + 1 && supported == CACHE_VERSION
+            ),
+            "{read:?}"
+        );
+    }
+}
@@ -0,0 +1,606 @@
+//! glTF 2.0 scene import.
+use super::{Mesh, Model, Scene, Vertex};
+use crate::{
+    skinning::{AnimationClip, Channel, Interpolation, Joint, Keyframes, Skin, SkinnedMesh},
+    texture::{TextureRegion, TextureUpdate},
+    world::WorldConvention,
+};
+use glam::{Mat4, Quat, Vec3};
+use log::{info, warn};
+use std::{error::Error, path::Path};
+
+impl Scene {
+    /// Load a glTF 2.0 scene, a `.gltf` with its buffers and images or a self contained `.glb`.
+    /// Nodes are walked from the default scene, each mesh gets its node's world transform and
+    /// becomes a model per base color texture. POSITION, TEXCOORD_0, COLOR_0, NORMAL and TANGENT
+    /// are read, missing colors are white and missing normals are generated flat as the spec
+    /// asks. Skinned meshes are drawn in their bind pose with a warning, see
+    /// [`SkinnedMesh::from_gltf`] to animate them. Morph targets are ignored with a warning.
+    pub fn from_gltf(path: &Path) -> Result<Scene, Box<dyn Error>> {
+        let (document, buffers, images) =
+            gltf::import(path).map_err(|error| format!("{}: {error}", path.display()))?;
+        let mut models = Vec::new();
+        for root in gltf_roots(&document) {
+            gltf_node(&root, Mat4::IDENTITY, &buffers, &mut models);
+        }
+        if models.is_empty() {
+            return Err(format!("{}: no triangles", path.display()).into());
+        }
+        let textures = images
+            .into_iter()
+            .enumerate()
+            .map(|(index, image)| gltf_texture(index, image))
+            .collect();
+        info!(
+            "Loaded {} with {} models and {} textures",
+            path.display(),
+            models.len(),
+            document.images().len()
+        );
+        Ok(Scene {
+            models,
+            convention: WorldConvention::YUp,
+            textures,
+        })
+    }
+}
+
+fn gltf_node(
+    node: &gltf::Node,
+    parent: Mat4,
+    buffers: &[gltf::buffer::Data],
+    models: &mut Vec<Model>,
+) {
+    let transform = parent * Mat4::from_cols_array_2d(&node.transform().matrix());
+    let name = node
+        .name()
+        .map_or(format!("node {}", node.index()), String::from);
+    if node.skin().is_some() {
+        warn!("{name}: skinned, drawn in its bind pose, load it with SkinnedMesh::from_gltf");
+    }
+    if let Some(mesh) = node.mesh() {
+        let mut node_models: Vec<Model> = Vec::new();
+        for primitive in mesh.primitives() {
+            let label = format!("{name} primitive {}", primitive.index());
+            let Some(mut imported) = gltf_primitive(&primitive, buffers, &label) else {
+                continue;
+            };
+            imported.transform = transform;
+            let texture = primitive
+                .material()
+                .pbr_metallic_roughness()
+                .base_color_texture()
+                .map(|info| info.texture().source().index());
+            match node_models
+                .iter_mut()
+                .find(|model| model.texture == texture)
+            {
+                Some(model) => model.meshes.push(imported),
+                None => node_models.push(Model {
+                    meshes: vec![imported],
+                    texture,
+                }),
+            }
+        }
+        models.extend(node_models);
+    }
+    for child in node.children() {
+        gltf_node(&child, transform, buffers, models);
+    }
+}
+
+impl SkinnedMesh {
+    /// Load every skinned mesh of a glTF 2.0 file with its skin and the animations moving its
+    /// joints. The primitives of a mesh are merged, read like [`Scene::from_gltf`] plus JOINTS_0
+    /// and WEIGHTS_0. Cubic spline channels are played linearly through their keyframes.
+    pub fn from_gltf(path: &Path) -> Result<Vec<SkinnedMesh>, Box<dyn Error>> {
+        let (document, buffers, _) =
+            gltf::import(path).map_err(|error| format!("{}: {error}", path.display()))?;
+        // World transform and parent of every node reached from the roots
+        let mut placements: Vec<Option<(Mat4, Option<usize>)>> = vec![None; document.nodes().len()];
+        let mut stack: Vec<(gltf::Node, Mat4, Option<usize>)> = gltf_roots(&document)
+            .into_iter()
+            .map(|root| (root, Mat4::IDENTITY, None))
+            .collect();
+        while let Some((node, parent_world, parent)) = stack.pop() {
+            let world = parent_world * Mat4::from_cols_array_2d(&node.transform().matrix());
+            placements[node.index()] = Some((world, parent));
+            stack.extend(
+                node.children()
+                    .map(|child| (child, world, Some(node.index()))),
+            );
+        }
+
+        let get_buffer_data =
+            |buffer: gltf::Buffer| buffers.get(buffer.index()).map(|data| &data.0[..]);
+        let mut skinned_meshes = Vec::new();
+        for node in document.nodes() {
+            let (Some(mesh), Some(skin)) = (node.mesh(), node.skin()) else {
+                continue;
+            };
+            let name = node
+                .name()
+                .map_or(format!("node {}", node.index()), String::from);
+            let primitives: Vec<Mesh> = mesh
+                .primitives()
+                .filter_map(|primitive| {
+                    let label = format!("{name} primitive {}", primitive.index());
+                    gltf_primitive(&primitive, &buffers, &label)
+                })
+                .collect();
+            if primitives.is_empty() {
+                continue;
+            }
+
+            let joint_nodes: Vec<gltf::Node> = skin.joints().collect();
+            let inverse_binds: Vec<Mat4> = skin
+                .reader(get_buffer_data)
+                .read_inverse_bind_matrices()
+                .map(|matrices| {
+                    matrices
+                        .map(|matrix| Mat4::from_cols_array_2d(&matrix))
+                        .collect()
+                })
+                .unwrap_or_default();
+            let joints = joint_nodes
+                .iter()
+                .enumerate()
+                .map(|(index, joint)| {
+                    let (translation, rotation, scale) = joint.transform().decomposed();
+                    let parent_node = placements[joint.index()].and_then(|(_, parent)| parent);
+                    let parent = parent_node.and_then(|parent| {
+                        joint_nodes.iter().position(|other| other.index() == parent)
+                    });
+                    // Roots keep the ancestors placing the skeleton
+                    let origin = match (parent, parent_node) {
+                        (None, Some(parent_node)) => {
+                            placements[parent_node].map_or(Mat4::IDENTITY, |(world, _)| world)
+                        }
+                        _ => Mat4::IDENTITY,
+                    };
+                    Joint {
+                        name: joint
+                            .name()
+                            .map_or(format!("node {}", joint.index()), String::from),
+                        parent,
+                        origin,
+                        translation: Vec3::from(translation),
+                        rotation: Quat::from_array(rotation),
+                        scale: Vec3::from(scale),
+                        inverse_bind: inverse_binds.get(index).copied().unwrap_or(Mat4::IDENTITY),
+                    }
+                })
+                .collect();
+
+            let animations = document
+                .animations()
+                .filter_map(|animation| {
+                    let channels: Vec<Channel> = animation
+                        .channels()
+                        .filter_map(|channel| {
+                            let target = channel.target().node().index();
+                            let joint = joint_nodes
+                                .iter()
+                                .position(|joint| joint.index() == target)?;
+                            gltf_channel(&channel, joint, get_buffer_data, &name)
+                        })
+                        .collect();
+                    (!channels.is_empty()).then(|| {
+                        let clip_name = animation
+                            .name()
+                            .map_or(format!("animation {}", animation.index()), String::from);
+                        AnimationClip::new(clip_name, channels)
+                    })
+                })
+                .collect();
+
+            // Skinned vertices are placed by the joints, the node's own transform is ignored
+            skinned_meshes.push(SkinnedMesh {
+                mesh: Mesh::merge(primitives),
+                skin: Skin { joints },
+                animations,
+            });
+        }
+        if skinned_meshes.is_empty() {
+            return Err(format!("{}: no skinned meshes", path.display()).into());
+        }
+        info!(
+            "Loaded {} with {} skinned meshes and {} animations",
+            path.display(),
+            skinned_meshes.len(),
+            document.animations().len()
+        );
+        Ok(skinned_meshes)
+    }
+}
+
+/// Nodes of the default scene, or of the first, or every node nobody parents without scenes.
+fn gltf_roots(document: &gltf::Document) -> Vec<gltf::Node<'_>> {
+    match document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+    {
+        Some(scene) => scene.nodes().collect(),
+        None => {
+            let children: Vec<usize> = document
+                .nodes()
+                .flat_map(|node| node.children())
+                .map(|child| child.index())
+                .collect();
+            document
+                .nodes()
+                .filter(|node| !children.contains(&node.index()))
+                .collect()
+        }
+    }
+}
+
+/// Keyframes of `channel` animating `joint`, `None` for morph target weights.
+fn gltf_channel<'a, 's, F>(
+    channel: &gltf::animation::Channel<'a>,
+    joint: usize,
+    get_buffer_data: F,
+    label: &str,
+) -> Option<Channel>
+where
+    F: Clone + Fn(gltf::Buffer<'a>) -> Option<&'s [u8]>,
+{
+    use gltf::animation::{util::ReadOutputs, Interpolation as GltfInterpolation};
+
+    let reader = channel.reader(get_buffer_data);
+    let times: Vec<f32> = reader.read_inputs()?.collect();
+    let cubic = channel.sampler().interpolation() == GltfInterpolation::CubicSpline;
+    let interpolation = match channel.sampler().interpolation() {
+        GltfInterpolation::Step => Interpolation::Step,
+        GltfInterpolation::Linear => Interpolation::Linear,
+        GltfInterpolation::CubicSpline => {
+            warn!("{label}: cubic spline channel played linearly");
+            Interpolation::Linear
+        }
+    };
+    let keyframes = match reader.read_outputs()? {
+        ReadOutputs::Translations(values) => {
+            Keyframes::Translation(gltf_keys(&times, values.map(Vec3::from), cubic))
+        }
+        ReadOutputs::Rotations(values) => Keyframes::Rotation(gltf_keys(
+            &times,
+            values.into_f32().map(Quat::from_array),
+            cubic,
+        )),
+        ReadOutputs::Scales(values) => {
+            Keyframes::Scale(gltf_keys(&times, values.map(Vec3::from), cubic))
+        }
+        ReadOutputs::MorphTargetWeights(_) => {
+            warn!("{label}: morph target animations are not supported");
+            return None;
+        }
+    };
+    Some(Channel {
+        joint,
+        interpolation,
+        keyframes,
+    })
+}
+
+/// `values` paired with their keyframe time. Cubic splines store an in tangent, the value and an
+/// out tangent per keyframe, only the value is kept.
+fn gltf_keys<T>(times: &[f32], values: impl Iterator<Item = T>, cubic: bool) -> Vec<(f32, T)> {
+    let values: Box<dyn Iterator<Item = T>> = match cubic {
+        true => Box::new(values.skip(1).step_by(3)),
+        false => Box::new(values),
+    };
+    times.iter().copied().zip(values).collect()
+}
+
+/// The primitive as a triangle list, `None` when it cannot be drawn as one.
+fn gltf_primitive(
+    primitive: &gltf::Primitive,
+    buffers: &[gltf::buffer::Data],
+    label: &str,
+) -> Option<Mesh> {
+    if primitive.morph_targets().len() > 0 {
+        warn!("{label}: morph targets are not supported, drawn without them");
+    }
+    let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+    let Some(positions) = reader.read_positions() else {
+        warn!("{label}: skipped, no positions");
+        return None;
+    };
+    let mut vertices: Vec<Vertex> = positions
+        .map(|position| Vertex {
+            pos: [position[0], position[1], position[2], 1.0],
+            uv: [0.0, 0.0],
+            color: [1.0; 4],
+            normal: [0.0, 0.0, 1.0],
+            tangent: [1.0, 0.0, 0.0, 1.0],
+            joints: [0; 4],
+            weights: [0.0; 4],
+        })
+        .collect();
+    // glTF UVs already start at the top left
+    if let Some(uvs) = reader.read_tex_coords(0) {
+        for (vertex, uv) in vertices.iter_mut().zip(uvs.into_f32()) {
+            vertex.uv = uv;
+        }
+    }
+    if let Some(colors) = reader.read_colors(0) {
+        for (vertex, color) in vertices.iter_mut().zip(colors.into_rgba_f32()) {
+            vertex.color = color;
+        }
+    }
+    let normals = reader.read_normals();
+    let has_normals = normals.is_some();
+    for (vertex, normal) in vertices.iter_mut().zip(normals.into_iter().flatten()) {
+        vertex.normal = normal;
+    }
+    if let Some(joints) = reader.read_joints(0) {
+        for (vertex, joints) in vertices.iter_mut().zip(joints.into_u16()) {
+            vertex.joints = joints;
+        }
+    }
+    if let Some(weights) = reader.read_weights(0) {
+        for (vertex, weights) in vertices.iter_mut().zip(weights.into_f32()) {
+            vertex.weights = weights;
+        }
+    }
+    let tangents = reader.read_tangents();
+    let has_tangents = has_normals && tangents.is_some();
+    for (vertex, tangent) in vertices.iter_mut().zip(tangents.into_iter().flatten()) {
+        vertex.tangent = tangent;
+    }
+
+    let count = vertices.len() as u32;
+    let indices: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..count).collect(),
+    };
+    if let Some(index) = indices.iter().find(|&&index| index >= count) {
+        warn!("{label}: skipped, index {index} out of {count} vertices");
+        return None;
+    }
+    let corners = indices.len().saturating_sub(2);
+    let indices = match primitive.mode() {
+        gltf::mesh::Mode::Triangles => indices,
+        // Every other triangle is flipped to keep the winding
+        gltf::mesh::Mode::TriangleStrip => (0..corners)
+            .flat_map(|i| [indices[i], indices[i + 1 + i % 2], indices[i + 2 - i % 2]])
+            .collect(),
+        gltf::mesh::Mode::TriangleFan => (0..corners)
+            .flat_map(|i| [indices[i + 1], indices[i + 2], indices[0]])
+            .collect(),
+        mode => {
+            warn!("{label}: skipped, {mode:?} are not supported");
+            return None;
+        }
+    };
+    if indices.len() < 3 {
+        warn!("{label}: skipped, no triangles");
+        return None;
+    }
+    let mut mesh = Mesh {
+        vertices,
+        indices,
+        transform: Mat4::IDENTITY,
+        user_id: 0,
+    };
+    if !has_normals {
+        mesh.generate_normals(false);
+    }
+    if !has_tangents {
+        mesh.generate_tangents();
+    }
+    Some(mesh)
+}
+
+/// An image of any glTF pixel format as RGBA8, a white pixel when its data does not fit it.
+fn gltf_texture(index: usize, data: gltf::image::Data) -> TextureUpdate {
+    use gltf::image::Format;
+    use image::{DynamicImage, ImageBuffer};
+
+    let gltf::image::Data {
+        pixels,
+        format,
+        width,
+        height,
+    } = data;
+    // Wider channels come in native byte order
+    let wide = |pixels: &[u8]| -> Vec<u16> {
+        pixels
+            .chunks_exact(2)
+            .map(|bytes| u16::from_ne_bytes([bytes[0], bytes[1]]))
+            .collect()
+    };
+    let float = |pixels: &[u8]| -> Vec<f32> {
+        pixels
+            .chunks_exact(4)
+            .map(|bytes| f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect()
+    };
+    let image = match format {
+        Format::R8 => ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageLuma8),
+        Format::R8G8 => ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageLumaA8),
+        Format::R8G8B8 => ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageRgb8),
+        Format::R8G8B8A8 => {
+            ImageBuffer::from_raw(width, height, pixels).map(DynamicImage::ImageRgba8)
+        }
+        Format::R16 => {
+            ImageBuffer::from_raw(width, height, wide(&pixels)).map(DynamicImage::ImageLuma16)
+        }
+        Format::R16G16 => {
+            ImageBuffer::from_raw(width, height, wide(&pixels)).map(DynamicImage::ImageLumaA16)
+        }
+        Format::R16G16B16 => {
+            ImageBuffer::from_raw(width, height, wide(&pixels)).map(DynamicImage::ImageRgb16)
+        }
+        Format::R16G16B16A16 => {
+            ImageBuffer::from_raw(width, height, wide(&pixels)).map(DynamicImage::ImageRgba16)
+        }
+        Format::R32G32B32FLOAT => {
+            ImageBuffer::from_raw(width, height, float(&pixels)).map(DynamicImage::ImageRgb32F)
+        }
+        Format::R32G32B32A32FLOAT => {
+            ImageBuffer::from_raw(width, height, float(&pixels)).map(DynamicImage::ImageRgba32F)
+        }
+    };
+    image
+        .and_then(|image| {
+            TextureUpdate::new(TextureRegion::full(width, height), image.to_rgba8().into_raw())
+                .ok()
+        })
+        .unwrap_or_else(|| {
+            warn!(
+                "Image {index}: {width}x{height} {format:?} does not match its data, replaced by white"
+            );
+            TextureUpdate::new(TextureRegion::full(1, 1), vec![255; 4]).unwrap()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A quad drawn twice under a translated parent, once colored and once textured as a strip.
+    fn quads() -> Scene {
+        Scene::from_gltf(Path::new("assets/models/quads.gltf")).unwrap()
+    }
+
+    #[test]
+    fn node_transforms_are_baked_into_models_per_texture() {
+        let scene = quads();
+        // The points primitive is skipped, the other two differ by texture
+        let textures: Vec<Option<usize>> = scene.models.iter().map(|model| model.texture).collect();
+        assert_eq!(textures, [None, Some(0)]);
+        let expected =
+            Mat4::from_translation(Vec3::new(1.0, 2.0, 0.0)) * Mat4::from_scale(Vec3::splat(2.0));
+        for mesh in scene.models.iter().flat_map(|model| &model.meshes) {
+            assert!(
+                mesh.transform.abs_diff_eq(expected, 1e-6),
+                "{}",
+                mesh.transform
+            );
+            for vertex in &mesh.vertices {
+                assert_eq!(vertex.normal, [0.0, 0.0, 1.0]);
+            }
+        }
+    }
+
+    #[test]
+    fn indexed_and_strip_primitives() {
+        let scene = quads();
+        let colored = &scene.models[0].meshes[0];
+        assert_eq!(colored.indices, [0, 1, 3, 3, 2, 0]);
+        // Normalized colors
+        assert_eq!(colored.vertices[1].color, [0.0, 1.0, 0.0, 1.0]);
+
+        // Without normals every triangle gets its own vertices, every other strip one is flipped
+        let strip = &scene.models[1].meshes[0];
+        let corners: Vec<[f32; 2]> = strip
+            .vertices
+            .iter()
+            .map(|vertex| [vertex.pos[0], vertex.pos[1]])
+            .collect();
+        let (p0, p1, p2, p3) = ([0.0, 0.0], [1.0, 0.0], [0.0, 1.0], [1.0, 1.0]);
+        assert_eq!(corners, [p0, p1, p2, p1, p3, p2]);
+        assert_eq!(strip.vertices[2].color, [1.0; 4]);
+    }
+
+    #[test]
+    fn images_are_decoded_to_rgba8() {
+        let scene = quads();
+        let texture = scene.textures.first().expect("texture not decoded");
+        assert_eq!((texture.region.width, texture.region.height), (2, 2));
+        // The last texel, half transparent white
+        assert_eq!(texture.data[12..], [255, 255, 255, 128]);
+    }
+
+    /// A strip two units tall weighed on a root joint and a joint one unit up, built after the
+    /// SimpleSkin sample of the glTF tutorial.
+    fn simple_skin() -> SkinnedMesh {
+        let mut skinned =
+            SkinnedMesh::from_gltf(Path::new("assets/models/simple_skin.gltf")).unwrap();
+        assert_eq!(skinned.len(), 1);
+        skinned.remove(0)
+    }
+
+    /// The bend joint turned `angle` around +Z about its rest position, back from its bind pose.
+    fn bent(angle: f32) -> Mat4 {
+        Mat4::from_translation(Vec3::Y)
+            * Mat4::from_quat(Quat::from_rotation_z(angle))
+            * Mat4::from_translation(-Vec3::Y)
+    }
+
+    fn assert_matrix(label: &str, actual: Mat4, expected: Mat4) {
+        assert!(
+            actual.abs_diff_eq(expected, 1e-5),
+            "{label}: {actual} instead of {expected}"
+        );
+    }
+
+    #[test]
+    fn skins_import_their_joints_weights_and_clips() {
+        let skinned = simple_skin();
+        let names: Vec<&str> = skinned
+            .skin
+            .joints
+            .iter()
+            .map(|joint| joint.name.as_str())
+            .collect();
+        assert_eq!(names, ["root", "bend"]);
+        assert_eq!(skinned.skin.joints[1].parent, Some(0));
+        assert_matrix(
+            "inverse bind of the bend",
+            skinned.skin.joints[1].inverse_bind,
+            Mat4::from_translation(-Vec3::Y),
+        );
+        let clip = &skinned.animations[0];
+        assert_eq!((clip.name.as_str(), clip.duration), ("bend", 5.5));
+        // All on the root at the bottom, all on the bend at the top, in quarters between
+        for vertex in &skinned.mesh.vertices {
+            let root = 1.0 - vertex.pos[1] / 2.0;
+            assert_eq!(vertex.joints, [0, 1, 0, 0], "{:?}", vertex.pos);
+            assert_eq!(
+                vertex.weights,
+                [root, 1.0 - root, 0.0, 0.0],
+                "{:?}",
+                vertex.pos
+            );
+        }
+    }
+
+    #[test]
+    fn skin_palettes_match_the_reference_poses() {
+        use std::f32::consts::FRAC_PI_8;
+
+        let skinned = simple_skin();
+        let clip = &skinned.animations[0];
+        let pose = |time| skinned.skin.pose(Some((clip, time)));
+
+        // A quarter turn at one second, written out: x to y, y to -x, about (0, 1)
+        let quarter = Mat4::from_cols_array(&[
+            0.0, 1.0, 0.0, 0.0, //
+            -1.0, 0.0, 0.0, 0.0, //
+            0.0, 0.0, 1.0, 0.0, //
+            1.0, 1.0, 0.0, 1.0,
+        ]);
+        let at_one = pose(1.0);
+        assert_matrix("root at 1s", at_one[0], Mat4::IDENTITY);
+        assert_matrix("bend at 1s", at_one[1], quarter);
+        // Between keyframes the rotation is spherically interpolated
+        assert_matrix("bend at 0.25s", pose(0.25)[1], bent(FRAC_PI_8));
+        assert_matrix("bend at 3.75s", pose(3.75)[1], bent(-3.0 * FRAC_PI_8));
+        assert_matrix("bend at rest", skinned.skin.pose(None)[1], Mat4::IDENTITY);
+
+        // Vertices blended as the shader does: the top corner turns with the bend, the middle
+        // one halfway between both joints
+        let blend = |position: Vec3, weights: [f32; 2]| {
+            (at_one[0] * weights[0] + at_one[1] * weights[1]).transform_point3(position)
+        };
+        let top = blend(Vec3::new(1.0, 2.0, 0.0), [0.0, 1.0]);
+        let middle = blend(Vec3::new(1.0, 1.0, 0.0), [0.5, 0.5]);
+        assert!(top.abs_diff_eq(Vec3::new(-1.0, 2.0, 0.0), 1e-5), "{top}");
+        assert!(
+            middle.abs_diff_eq(Vec3::new(0.5, 1.5, 0.0), 1e-5),
+            "{middle}"
+        );
+    }
+}
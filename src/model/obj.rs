@@ -0,0 +1,214 @@
+//! Wavefront OBJ loading.
+use super::{Mesh, Model, Vertex};
+use glam::{Mat4, Vec2, Vec3};
+use std::{collections::HashMap, error::Error, mem, path::Path};
+
+impl Model {
+    /// Load a Wavefront OBJ, one mesh per object or group.
+    pub fn from_obj(path: &Path) -> Result<Model, Box<dyn Error>> {
+        let source = std::fs::read_to_string(path)?;
+        Self::parse_obj(&source).map_err(|error| format!("{}: {error}", path.display()).into())
+    }
+
+    /// Positions, UVs, normals and faces of an OBJ source, polygons are triangulated as fans.
+    /// Corners sharing the same position, UV and normal share a vertex. Missing UVs are `[0, 0]`,
+    /// missing normals are generated smooth, colors are white. V is flipped, OBJ UVs start at the
+    /// bottom left and Vulkan samples from the top left. Materials, lines and points are ignored.
+    pub fn parse_obj(source: &str) -> Result<Model, Box<dyn Error>> {
+        let mut positions = Vec::new();
+        let mut uvs = Vec::new();
+        let mut normals = Vec::new();
+        let mut meshes = Vec::new();
+        let mut current = ObjMesh::default();
+
+        for (line_index, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut words = line.split_whitespace();
+            let Some(keyword) = words.next() else {
+                continue;
+            };
+            let fail = |error: String| format!("line {}: {error}", line_index + 1);
+            match keyword {
+                "v" => positions.push(Vec3::from(parse_floats::<3>(words).map_err(fail)?)),
+                "vt" => uvs.push(Vec2::from(parse_floats::<2>(words).map_err(fail)?)),
+                "vn" => normals.push(Vec3::from(parse_floats::<3>(words).map_err(fail)?)),
+                "o" | "g" => {
+                    if let Some(mesh) = mem::take(&mut current).finish() {
+                        meshes.push(mesh);
+                    }
+                }
+                "f" => {
+                    let corners = words
+                        .map(|corner| {
+                            parse_corner(corner, positions.len(), uvs.len(), normals.len())
+                        })
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(fail)?;
+                    if corners.len() < 3 {
+                        return Err(fail(format!("face with {} corners", corners.len())).into());
+                    }
+                    let indices = corners
+                        .into_iter()
+                        .map(|corner| current.vertex(corner, &positions, &uvs, &normals))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    for i in 1..indices.len() - 1 {
+                        current
+                            .mesh
+                            .indices
+                            .extend([indices[0], indices[i], indices[i + 1]]);
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let Some(mesh) = current.finish() {
+            meshes.push(mesh);
+        }
+        if meshes.is_empty() {
+            return Err("No faces".into());
+        }
+        Ok(Model {
+            meshes,
+            texture: None,
+        })
+    }
+}
+
+/// Absolute position, UV and normal indices of a face corner.
+type ObjCorner = (usize, Option<usize>, Option<usize>);
+
+/// Mesh of the object or group being parsed.
+struct ObjMesh {
+    mesh: Mesh,
+    vertices: HashMap<ObjCorner, u32>,
+    has_normals: bool,
+}
+
+impl Default for ObjMesh {
+    fn default() -> Self {
+        Self {
+            mesh: Mesh {
+                vertices: Vec::new(),
+                indices: Vec::new(),
+                transform: Mat4::IDENTITY,
+                user_id: 0,
+            },
+            vertices: HashMap::new(),
+            has_normals: true,
+        }
+    }
+}
+
+impl ObjMesh {
+    /// Index of the vertex at `corner`, added the first time it is used.
+    fn vertex(
+        &mut self,
+        corner: ObjCorner,
+        positions: &[Vec3],
+        uvs: &[Vec2],
+        normals: &[Vec3],
+    ) -> Result<u32, Box<dyn Error>> {
+        if let Some(&index) = self.vertices.get(&corner) {
+            return Ok(index);
+        }
+        let (position, uv, normal) = corner;
+        self.has_normals &= normal.is_some();
+        let uv = uv.map_or(Vec2::ZERO, |uv| Vec2::new(uvs[uv].x, 1.0 - uvs[uv].y));
+        let index = u32::try_from(self.mesh.vertices.len())?;
+        self.mesh.vertices.push(Vertex {
+            pos: positions[position].extend(1.0).to_array(),
+            uv: uv.to_array(),
+            color: [1.0; 4],
+            normal: normal.map_or(Vec3::Z, |normal| normals[normal]).to_array(),
+            tangent: [1.0, 0.0, 0.0, 1.0],
+            joints: [0; 4],
+            weights: [0.0; 4],
+        });
+        self.vertices.insert(corner, index);
+        Ok(index)
+    }
+
+    /// The mesh with its normals and tangents, `None` without faces.
+    fn finish(self) -> Option<Mesh> {
+        let mut mesh = self.mesh;
+        if mesh.indices.is_empty() {
+            return None;
+        }
+        if !self.has_normals {
+            mesh.generate_normals(true);
+        }
+        mesh.generate_tangents();
+        Some(mesh)
+    }
+}
+
+fn parse_floats<'a, const N: usize>(
+    mut words: impl Iterator<Item = &'a str>,
+) -> Result<[f32; N], String> {
+    let mut values = [0.0; N];
+    for value in &mut values {
+        let word = words.next().ok_or(format!("expected {N} numbers"))?;
+        *value = word
+            .parse()
+            .map_err(|_| format!("invalid number {word:?}"))?;
+    }
+    Ok(values)
+}
+
+/// `v`, `v/vt`, `v//vn` or `v/vt/vn`, 1 based or negative from the end of what was read so far.
+fn parse_corner(
+    corner: &str,
+    positions: usize,
+    uvs: usize,
+    normals: usize,
+) -> Result<ObjCorner, String> {
+    let mut parts = corner.split('/');
+    let mut next = |count: usize| -> Result<Option<usize>, String> {
+        let Some(part) = parts.next().filter(|part| !part.is_empty()) else {
+            return Ok(None);
+        };
+        let index: i64 = part
+            .parse()
+            .map_err(|_| format!("invalid index {part:?}"))?;
+        let resolved = match index {
+            1.. => index - 1,
+            ..=-1 => count as i64 + index,
+            0 => -1,
+        };
+        if resolved < 0 || resolved >= count as i64 {
+            return Err(format!("index {index} out of {count} in {corner:?}"));
+        }
+        Ok(Some(resolved as usize))
+    };
+    let position = next(positions)?.ok_or(format!("corner {corner:?} without a position"))?;
+    Ok((position, next(uvs)?, next(normals)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shapes_share_their_corners() {
+        let model = Model::from_obj(Path::new("assets/models/shapes.obj")).unwrap();
+        let counts: Vec<(usize, usize)> = model
+            .meshes
+            .iter()
+            .map(|mesh| (mesh.vertices.len(), mesh.indices.len()))
+            .collect();
+        // Quads split in two, corners shared within a face and between faces when they match
+        assert_eq!(counts, [(24, 36), (5, 18)]);
+        // Missing UVs are zero and colors white
+        for vertex in &model.meshes[1].vertices {
+            assert_eq!(vertex.uv, [0.0, 0.0]);
+            assert_eq!(vertex.color, [1.0; 4]);
+        }
+    }
+
+    #[test]
+    fn malformed_sources_are_refused() {
+        for source in ["f 1 2 3", "v 0 0 0\nv 1 0 0\nf 1 2", "v 0 0\n", "o empty\n"] {
+            assert!(Model::parse_obj(source).is_err(), "{source:?}");
+        }
+    }
+}
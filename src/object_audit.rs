@@ -0,0 +1,338 @@
+//! Registry of the live Vulkan objects, for leak hunting in long sessions.
+//!
+//! With the `object-audit` feature every object the renderer creates is registered with its
+//! type, when and where it was created and why, until it is destroyed; without it [`created`]
+//! and [`destroyed`] do nothing. An [`audit`] counts the live objects per type and lists the
+//! oldest, and the [`AuditDiff`] of two audits taken minutes apart shows what keeps growing.
+//!
+//! The registry is split in shards picked by handle, so render threads creating objects at the
+//! same time rarely wait on one another, and an object costs one map insertion when created and
+//! one removal when destroyed. Backtraces are only captured once [`capture_backtraces`] is on.
+use ash::vk::{self, Handle};
+use std::{
+    backtrace::Backtrace,
+    collections::BTreeMap,
+    fmt,
+    panic::Location,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+
+const SHARDS: usize = 16;
+
+type Shard = Mutex<BTreeMap<(vk::ObjectType, u64), AuditedObject>>;
+
+static REGISTRY: [Shard; SHARDS] = [const { Mutex::new(BTreeMap::new()) }; SHARDS];
+static NEXT_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+static BACKTRACES: AtomicBool = AtomicBool::new(false);
+
+/// A live object and where it comes from.
+#[derive(Debug, Clone)]
+pub struct AuditedObject {
+    pub object_type: vk::ObjectType,
+    pub handle: u64,
+    /// What the caller created it for.
+    pub reason: &'static str,
+    /// Where [`created`] was called.
+    pub site: &'static Location<'static>,
+    pub created: Instant,
+    /// Order of creation among every audited object.
+    pub sequence: u64,
+    pub backtrace: Option<Arc<Backtrace>>,
+}
+
+impl fmt::Display for AuditedObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} {:#x}, {} at {}, {:.1}s old",
+            self.object_type,
+            self.handle,
+            self.reason,
+            self.site,
+            self.created.elapsed().as_secs_f64()
+        )
+    }
+}
+
+/// Whether objects are registered, the `object-audit` feature.
+pub const fn enabled() -> bool {
+    cfg!(feature = "object-audit")
+}
+
+/// Capture a backtrace with each object created from now on. Slow, for the hunt itself.
+pub fn capture_backtraces(enabled: bool) {
+    BACKTRACES.store(enabled, Ordering::Relaxed);
+}
+
+fn shard(object_type: vk::ObjectType, handle: u64) -> &'static Shard {
+    // Handles are often aligned addresses, the low bits alone would pick few shards
+    let mixed = (handle ^ object_type.as_raw() as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    &REGISTRY[(mixed >> 60) as usize % SHARDS]
+}
+
+/// Register `handle`, just created for `reason`. Null handles are skipped.
+#[track_caller]
+pub fn created<H: Handle>(handle: H, reason: &'static str) {
+    if !enabled() {
+        return;
+    }
+    let raw = handle.as_raw();
+    if raw == 0 {
+        return;
+    }
+    let object = AuditedObject {
+        object_type: H::TYPE,
+        handle: raw,
+        reason,
+        site: Location::caller(),
+        created: Instant::now(),
+        sequence: NEXT_SEQUENCE.fetch_add(1, Ordering::Relaxed),
+        backtrace: BACKTRACES
+            .load(Ordering::Relaxed)
+            .then(|| Arc::new(Backtrace::force_capture())),
+    };
+    shard(H::TYPE, raw)
+        .lock()
+        .unwrap()
+        .insert((H::TYPE, raw), object);
+}
+
+/// Forget `handle`, about to be destroyed. Handles never registered are ignored.
+pub fn destroyed<H: Handle>(handle: H) {
+    if !enabled() {
+        return;
+    }
+    let raw = handle.as_raw();
+    shard(H::TYPE, raw).lock().unwrap().remove(&(H::TYPE, raw));
+}
+
+/// Live objects per type and the `oldest` first created, see [`ObjectAudit`].
+pub fn audit(oldest: usize) -> ObjectAudit {
+    let mut counts = BTreeMap::new();
+    let mut objects = Vec::new();
+    for shard in &REGISTRY {
+        let shard = shard.lock().unwrap();
+        for object in shard.values() {
+            *counts.entry(object.object_type).or_insert(0) += 1;
+        }
+        objects.extend(shard.values().cloned());
+        // Only the oldest are kept, the shards are locked one at a time
+        objects.sort_unstable_by_key(|object: &AuditedObject| object.sequence);
+        objects.truncate(oldest);
+    }
+    ObjectAudit {
+        counts,
+        oldest: objects,
+        taken: Instant::now(),
+    }
+}
+
+/// Live objects when the audit was taken.
+#[derive(Debug, Clone)]
+pub struct ObjectAudit {
+    pub counts: BTreeMap<vk::ObjectType, usize>,
+    /// Oldest first.
+    pub oldest: Vec<AuditedObject>,
+    pub taken: Instant,
+}
+
+impl ObjectAudit {
+    pub fn count(&self, object_type: vk::ObjectType) -> usize {
+        self.counts.get(&object_type).copied().unwrap_or(0)
+    }
+
+    pub fn total(&self) -> usize {
+        self.counts.values().sum()
+    }
+
+    /// Counts that changed since `earlier`.
+    pub fn diff(&self, earlier: &ObjectAudit) -> AuditDiff {
+        let mut types: Vec<vk::ObjectType> = self
+            .counts
+            .keys()
+            .chain(earlier.counts.keys())
+            .copied()
+            .collect();
+        types.sort_unstable();
+        types.dedup();
+        AuditDiff {
+            changes: types
+                .into_iter()
+                .map(|object_type| CountChange {
+                    object_type,
+                    before: earlier.count(object_type),
+                    after: self.count(object_type),
+                })
+                .filter(|change| change.before != change.after)
+                .collect(),
+            elapsed: self.taken.saturating_duration_since(earlier.taken),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CountChange {
+    pub object_type: vk::ObjectType,
+    pub before: usize,
+    pub after: usize,
+}
+
+impl CountChange {
+    pub fn delta(&self) -> isize {
+        self.after as isize - self.before as isize
+    }
+}
+
+/// What changed between two audits, one line per type when printed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditDiff {
+    /// Types whose count changed, in type order.
+    pub changes: Vec<CountChange>,
+    pub elapsed: std::time::Duration,
+}
+
+impl AuditDiff {
+    pub fn is_flat(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Objects created and not destroyed since the earlier audit, less the ones destroyed.
+    pub fn growth(&self) -> isize {
+        self.changes.iter().map(CountChange::delta).sum()
+    }
+}
+
+impl fmt::Display for AuditDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = self.elapsed.as_secs_f64();
+        if self.is_flat() {
+            return write!(f, "No object count changed in {seconds:.1}s");
+        }
+        write!(f, "Object counts over {seconds:.1}s:")?;
+        for change in &self.changes {
+            write!(
+                f,
+                "\n{:?} {} -> {} ({:+})",
+                change.object_type,
+                change.before,
+                change.after,
+                change.delta()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "object-audit"))]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    /// Fake handles, far above what a driver hands out.
+    const FAKE_HANDLES: u64 = 0xFA4E_0000_0000;
+    const THREADS: u64 = 8;
+    const PER_THREAD: u64 = 10_000;
+
+    fn image(index: u64) -> vk::Image {
+        vk::Image::from_raw(FAKE_HANDLES + index)
+    }
+
+    fn buffer(index: u64) -> vk::Buffer {
+        vk::Buffer::from_raw(FAKE_HANDLES + index)
+    }
+
+    /// Counts, oldest list and diff of a known set, then threads, in one test since the registry
+    /// is process wide.
+    #[test]
+    fn known_objects_are_counted_listed_and_diffed() {
+        let before = audit(0);
+        for index in 0..3 {
+            created(image(index), "checked image");
+        }
+        for index in 0..2 {
+            created(buffer(index), "checked buffer");
+        }
+        // Null handles are not objects, unknown ones were never registered
+        created(vk::Image::null(), "null image");
+        destroyed(image(99));
+        destroyed(image(1));
+
+        let current = audit(2);
+        let grown = |object_type| current.count(object_type) - before.count(object_type);
+        assert_eq!(grown(vk::ObjectType::IMAGE), 2);
+        assert_eq!(grown(vk::ObjectType::BUFFER), 2);
+        let oldest: Vec<(u64, &str)> = current
+            .oldest
+            .iter()
+            .map(|object| (object.handle, object.reason))
+            .collect();
+        assert_eq!(
+            oldest,
+            [
+                (FAKE_HANDLES, "checked image"),
+                (FAKE_HANDLES + 2, "checked image")
+            ]
+        );
+        assert!(current.oldest[0].site.file().ends_with("object_audit.rs"));
+
+        let diff = current.diff(&before);
+        let lines: Vec<String> = diff.to_string().lines().skip(1).map(String::from).collect();
+        assert_eq!(
+            lines,
+            [
+                format!(
+                    "BUFFER {} -> {} (+2)",
+                    before.count(vk::ObjectType::BUFFER),
+                    current.count(vk::ObjectType::BUFFER)
+                ),
+                format!(
+                    "IMAGE {} -> {} (+2)",
+                    before.count(vk::ObjectType::IMAGE),
+                    current.count(vk::ObjectType::IMAGE)
+                ),
+            ]
+        );
+        assert_eq!(diff.growth(), 4);
+
+        for index in [0, 2] {
+            destroyed(image(index));
+        }
+        for index in 0..2 {
+            destroyed(buffer(index));
+        }
+        let diff = audit(0).diff(&before);
+        assert!(diff.is_flat(), "{diff}");
+        assert!(diff.to_string().starts_with("No object count changed"));
+
+        // Threads creating and destroying objects at once leave the counts where they were
+        thread::scope(|scope| {
+            for thread in 0..THREADS {
+                scope.spawn(move || {
+                    let first = (thread + 1) * 2 * PER_THREAD;
+                    for index in first..first + PER_THREAD {
+                        created(buffer(index), "threaded buffer");
+                    }
+                    for index in first..first + PER_THREAD {
+                        destroyed(buffer(index));
+                    }
+                });
+            }
+        });
+        let diff = audit(0).diff(&before);
+        assert!(diff.is_flat(), "{diff}");
+
+        capture_backtraces(true);
+        created(image(7), "traced image");
+        capture_backtraces(false);
+        let traced = audit(usize::MAX)
+            .oldest
+            .into_iter()
+            .find(|object| object.handle == image(7).as_raw());
+        destroyed(image(7));
+        assert!(traced.and_then(|object| object.backtrace).is_some());
+    }
+}
@@ -0,0 +1,163 @@
+use crate::model::Mesh;
+use glam::{Mat4, Vec2, Vec3, Vec4Swizzles};
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    /// Normalized.
+    pub direction: Vec3,
+}
+
+impl Ray {
+    pub fn new(origin: Vec3, direction: Vec3) -> Self {
+        Self {
+            origin,
+            direction: direction.normalize(),
+        }
+    }
+
+    /// Ray going through `cursor` (physical pixels, origin top left) for the given `projection_view`.
+    pub fn from_cursor(cursor: Vec2, viewport: Vec2, projection_view: Mat4) -> Self {
+        // Vulkan clip space has Y pointing down, like window coordinates
+        let ndc = cursor / viewport * 2.0 - Vec2::ONE;
+        let inverse = projection_view.inverse();
+        let near = inverse * ndc.extend(0.0).extend(1.0);
+        let far = inverse * ndc.extend(1.0).extend(1.0);
+        let near = near.xyz() / near.w;
+        let far = far.xyz() / far.w;
+        Self::new(near, far - near)
+    }
+
+    pub fn at(&self, t: f32) -> Vec3 {
+        self.origin + self.direction * t
+    }
+
+    /// Distance along the ray to the plane, `None` when parallel or behind.
+    pub fn intersect_plane(&self, point: Vec3, normal: Vec3) -> Option<f32> {
+        let denom = normal.dot(self.direction);
+        if denom.abs() < f32::EPSILON {
+            return None;
+        }
+        let t = (point - self.origin).dot(normal) / denom;
+        (t >= 0.0).then_some(t)
+    }
+
+    /// Möller–Trumbore, returns the distance along the ray.
+    pub fn intersect_triangle(&self, a: Vec3, b: Vec3, c: Vec3) -> Option<f32> {
+        let edge1 = b - a;
+        let edge2 = c - a;
+        let p = self.direction.cross(edge2);
+        let det = edge1.dot(p);
+        if det.abs() < f32::EPSILON {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+        let s = self.origin - a;
+        let u = s.dot(p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = s.cross(edge1);
+        let v = self.direction.dot(q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = edge2.dot(q) * inv_det;
+        (t >= 0.0).then_some(t)
+    }
+
+    /// Closest distance between the ray and the segment `start..end`, with the ray parameter of that point.
+    pub fn distance_to_segment(&self, start: Vec3, end: Vec3) -> (f32, f32) {
+        let segment = end - start;
+        let length_squared = segment.length_squared();
+        if length_squared < f32::EPSILON {
+            let s = (start - self.origin).dot(self.direction).max(0.0);
+            return ((self.at(s) - start).length(), s);
+        }
+
+        // Closest points of the two infinite lines, then clamp to the segment and the ray
+        let b = self.direction.dot(segment);
+        let d = self.direction.dot(self.origin - start);
+        let e = segment.dot(self.origin - start);
+        let denom = length_squared - b * b;
+        let t = if denom.abs() < f32::EPSILON {
+            0.0
+        } else {
+            ((e - b * d) / denom).clamp(0.0, 1.0)
+        };
+        let s = (start + segment * t - self.origin)
+            .dot(self.direction)
+            .max(0.0);
+        let t = ((self.at(s) - start).dot(segment) / length_squared).clamp(0.0, 1.0);
+        ((self.at(s) - (start + segment * t)).length(), s)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshHit {
+    pub index: usize,
+    pub user_id: u64,
+    pub distance: f32,
+    pub point: Vec3,
+}
+
+/// Closest mesh under the ray, testing every triangle in world space.
+/// Meshes come with their user id, carried over to the hit.
+pub fn pick_mesh<'a>(
+    ray: &Ray,
+    meshes: impl IntoIterator<Item = (u64, &'a Mesh)>,
+) -> Option<MeshHit> {
+    pick_mesh_transformed(
+        ray,
+        meshes
+            .into_iter()
+            .map(|(user_id, mesh)| (user_id, mesh, mesh.transform)),
+    )
+}
+
+/// [`pick_mesh`] with the world transform of each mesh given, for meshes placed under a parent.
+pub fn pick_mesh_transformed<'a>(
+    ray: &Ray,
+    meshes: impl IntoIterator<Item = (u64, &'a Mesh, Mat4)>,
+) -> Option<MeshHit> {
+    pick_mesh_ranges(
+        ray,
+        meshes
+            .into_iter()
+            .map(|(user_id, mesh, transform)| (user_id, mesh, transform, 0..mesh.indices.len())),
+    )
+}
+
+/// User id, mesh, world transform and the range of its indices to test.
+pub type MeshPart<'a> = (u64, &'a Mesh, Mat4, Range<usize>);
+
+/// [`pick_mesh_transformed`] over a range of each mesh's indices, for the parts of a batch
+/// placed on their own. A mesh may come several times with different ranges.
+pub fn pick_mesh_ranges<'a>(
+    ray: &Ray,
+    meshes: impl IntoIterator<Item = MeshPart<'a>>,
+) -> Option<MeshHit> {
+    let mut closest: Option<MeshHit> = None;
+    for (index, (user_id, mesh, transform, indices)) in meshes.into_iter().enumerate() {
+        let world = |i: u32| {
+            let pos = mesh.vertices[i as usize].pos;
+            transform.transform_point3(Vec3::new(pos[0], pos[1], pos[2]))
+        };
+        for triangle in mesh.indices[indices].chunks_exact(3) {
+            let hit =
+                ray.intersect_triangle(world(triangle[0]), world(triangle[1]), world(triangle[2]));
+            if let Some(distance) = hit {
+                if closest.is_none_or(|closest| distance < closest.distance) {
+                    closest = Some(MeshHit {
+                        index,
+                        user_id,
+                        distance,
+                        point: ray.at(distance),
+                    });
+                }
+            }
+        }
+    }
+    closest
+}
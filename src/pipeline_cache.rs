@@ -0,0 +1,167 @@
+//! Where the Vulkan pipeline cache is kept between runs and whether a file on disk is worth
+//! handing to the driver. Pipelines built by a previous launch on the same device and driver then
+//! skip most of their compilation.
+use crate::error::PulsarError;
+use ash::vk;
+use std::path::PathBuf;
+
+/// Environment variable overriding the directory the pipeline caches are kept in.
+pub const CACHE_DIR_ENV: &str = "PULSAR_CACHE_DIR";
+const CACHE_DIR_NAME: &str = "pulsar";
+/// `VkPipelineCacheHeaderVersionOne`, the header every driver starts its cache data with.
+pub const HEADER_BYTES: usize = 16 + vk::UUID_SIZE;
+
+/// `PULSAR_CACHE_DIR` if set, otherwise `pulsar` in the temporary directory.
+pub fn cache_dir() -> PathBuf {
+    match std::env::var_os(CACHE_DIR_ENV) {
+        Some(dir) => PathBuf::from(dir),
+        None => std::env::temp_dir().join(CACHE_DIR_NAME),
+    }
+}
+
+/// One file per GPU, so machines with several do not keep replacing each other's cache.
+pub fn cache_path(vendor_id: u32, device_id: u32) -> PathBuf {
+    cache_dir().join(format!("pipelines_{vendor_id:04x}_{device_id:04x}.bin"))
+}
+
+/// Identifies the device and driver a pipeline cache was written by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PipelineCacheHeader {
+    pub vendor_id: u32,
+    pub device_id: u32,
+    /// `pipelineCacheUUID`, changes with the driver version.
+    pub uuid: [u8; vk::UUID_SIZE],
+}
+
+impl PipelineCacheHeader {
+    /// The header of cache data, its fields least significant byte first whatever the host.
+    pub fn parse(data: &[u8]) -> Result<Self, PulsarError> {
+        let corrupt = |reason: String| PulsarError::CacheCorrupt { reason };
+        if data.len() < HEADER_BYTES {
+            return Err(corrupt(format!(
+                "pipeline cache of {} bytes, its header alone takes {HEADER_BYTES}",
+                data.len()
+            )));
+        }
+        let field =
+            |index: usize| u32::from_le_bytes(data[index * 4..index * 4 + 4].try_into().unwrap());
+        let (length, version) = (field(0), field(1));
+        if (length as usize) < HEADER_BYTES || length as usize > data.len() {
+            return Err(corrupt(format!("pipeline cache header of {length} bytes")));
+        }
+        if version != vk::PipelineCacheHeaderVersion::ONE.as_raw() as u32 {
+            return Err(corrupt(format!("pipeline cache header version {version}")));
+        }
+        Ok(Self {
+            vendor_id: field(2),
+            device_id: field(3),
+            uuid: data[16..HEADER_BYTES].try_into().unwrap(),
+        })
+    }
+
+    pub fn to_bytes(&self) -> [u8; HEADER_BYTES] {
+        let mut bytes = [0; HEADER_BYTES];
+        let fields = [
+            HEADER_BYTES as u32,
+            vk::PipelineCacheHeaderVersion::ONE.as_raw() as u32,
+            self.vendor_id,
+            self.device_id,
+        ];
+        for (chunk, field) in bytes.chunks_exact_mut(4).zip(fields) {
+            chunk.copy_from_slice(&field.to_le_bytes());
+        }
+        bytes[16..].copy_from_slice(&self.uuid);
+        bytes
+    }
+
+    /// Keep `data` read from disk only if this device and driver wrote it. Drivers are meant to
+    /// ignore foreign data, not all of them do.
+    pub fn validate(&self, data: &[u8]) -> Result<(), PulsarError> {
+        let written = Self::parse(data)?;
+        if written != *self {
+            return Err(PulsarError::PipelineCacheMismatch {
+                vendor_id: written.vendor_id,
+                device_id: written.device_id,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cache data as a driver writes it: the header then its own blob.
+    fn cache_data(header: &PipelineCacheHeader) -> Vec<u8> {
+        let mut data = header.to_bytes().to_vec();
+        data.extend_from_slice(b"compiled pipelines");
+        data
+    }
+
+    const DEVICE: PipelineCacheHeader = PipelineCacheHeader {
+        vendor_id: 0x1002,
+        device_id: 0x73bf,
+        uuid: [7; 16],
+    };
+
+    #[test]
+    fn one_cache_per_gpu_moved_by_the_environment() {
+        let dir = std::env::temp_dir().join("pulsar_pipeline_cache_test");
+        std::env::set_var(CACHE_DIR_ENV, &dir);
+        let path = cache_path(0x10de, 0x2484);
+        std::env::remove_var(CACHE_DIR_ENV);
+        assert_eq!(path, dir.join("pipelines_10de_2484.bin"));
+        assert_eq!(cache_dir(), std::env::temp_dir().join("pulsar"));
+    }
+
+    #[test]
+    fn caches_of_another_driver_or_gpu_are_refused() {
+        let data = cache_data(&DEVICE);
+        assert_eq!(PipelineCacheHeader::parse(&data).unwrap(), DEVICE);
+        DEVICE.validate(&data).unwrap();
+
+        // Another driver version, then another GPU
+        let updated = PipelineCacheHeader {
+            uuid: [8; 16],
+            ..DEVICE
+        };
+        let other_gpu = PipelineCacheHeader {
+            device_id: 0x1234,
+            ..DEVICE
+        };
+        for (written, expected) in [(updated, 0x73bf), (other_gpu, 0x1234)] {
+            match DEVICE.validate(&cache_data(&written)) {
+                Err(PulsarError::PipelineCacheMismatch {
+                    vendor_id: 0x1002,
+                    device_id,
+                }) => assert_eq!(device_id, expected),
+                other => panic!("{written:?} validated as {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn damaged_caches_are_corrupt() {
+        let data = cache_data(&DEVICE);
+        // Truncated, a header claiming more than the file holds, an unknown header version
+        let mut oversized = data.clone();
+        oversized[..4].copy_from_slice(&1000u32.to_le_bytes());
+        let mut version_two = data.clone();
+        version_two[4..8].copy_from_slice(&2u32.to_le_bytes());
+        for (name, data) in [
+            ("truncated", data[..HEADER_BYTES - 1].to_vec()),
+            ("oversized", oversized),
+            ("version 2", version_two),
+            ("empty", Vec::new()),
+        ] {
+            assert!(
+                matches!(
+                    DEVICE.validate(&data),
+                    Err(PulsarError::CacheCorrupt { .. })
+                ),
+                "a {name} cache was accepted"
+            );
+        }
+    }
+}
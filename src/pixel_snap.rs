@@ -0,0 +1,111 @@
+//! Keeps UI geometry on physical pixels so one pixel lines stay sharp.
+//!
+//! UI meshes put their edges on whole logical pixels. Before the draw, the origin of each mesh is
+//! moved to the nearest physical pixel corner, after the projection, the UI scale and the viewport
+//! offset are applied, so fractional translations and letterboxed viewports land on the same grid.
+//! Edges stay on physical pixels when the scale factor is a whole number, a one logical pixel line
+//! at a scale of 1.5 is one and a half physical pixels wide whatever the snapping.
+//!
+//! Atlases sample texel centers: texel `i` of a `size` texels wide atlas spans `i / size` to
+//! `(i + 1) / size` and its center is at `(i + 0.5) / size`, see [`texel_center_uv`]. A sprite drawn
+//! at its texel size with snapped edges then samples each texel at its center and the linear
+//! sampler returns it unblended.
+use glam::{Mat4, Vec2, Vec4Swizzles};
+
+/// Physical pixel the origin of a mesh drawn with `pvm` lands on, from the viewport's top left.
+pub fn origin_in_pixels(pvm: Mat4, viewport_offset: Vec2, viewport_size: Vec2) -> Vec2 {
+    let clip = pvm.w_axis;
+    let ndc = clip.xy() / clip.w;
+    viewport_offset + (ndc * 0.5 + 0.5) * viewport_size
+}
+
+/// `pvm` moved by less than half a pixel so the mesh origin is on a physical pixel corner.
+pub fn snap_to_pixels(pvm: Mat4, viewport_offset: Vec2, viewport_size: Vec2) -> Mat4 {
+    if viewport_size.cmple(Vec2::ZERO).any() {
+        return pvm;
+    }
+    let origin = origin_in_pixels(pvm, viewport_offset, viewport_size);
+    let offset_ndc = (origin.round() - origin) / viewport_size * 2.0;
+    // Added to clip space times w, so every vertex moves by the same offset after the divide
+    Mat4::from_translation(offset_ndc.extend(0.0)) * pvm
+}
+
+/// Center of texel `texel` in an atlas of `size` texels.
+pub fn texel_center_uv(texel: Vec2, size: Vec2) -> Vec2 {
+    (texel + 0.5) / size
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec3;
+
+    /// Odd sizes, a letterboxed viewport and a HiDPI scale.
+    const VIEWPORTS: [(Vec2, Vec2, f32); 4] = [
+        (Vec2::ZERO, Vec2::new(801.0, 601.0), 1.0),
+        (Vec2::ZERO, Vec2::new(1023.0, 767.0), 1.0),
+        (Vec2::new(13.0, 7.0), Vec2::new(1281.0, 721.0), 1.0),
+        (Vec2::new(0.0, 40.0), Vec2::new(1999.0, 1001.0), 2.0),
+    ];
+
+    fn to_pixels(pvm: Mat4, point: Vec2, offset: Vec2, size: Vec2) -> Vec2 {
+        let clip = pvm * point.extend(0.0).extend(1.0);
+        offset + (clip.xy() / clip.w * 0.5 + 0.5) * size
+    }
+
+    /// Pixels whose center is inside `min..max`, how the rasterizer decides coverage.
+    fn covered_pixels(min: f32, max: f32) -> u32 {
+        ((max - 0.5).ceil() - (min - 0.5).ceil()).max(0.0) as u32
+    }
+
+    #[test]
+    fn one_pixel_lines_cover_whole_pixels() {
+        for (offset, size, scale) in VIEWPORTS {
+            // The orthographic camera works in logical pixels
+            let logical = size / scale;
+            let projection = Mat4::orthographic_rh(0.0, logical.x, 0.0, logical.y, -1.0, 1.0);
+            for step in 0..16 {
+                let position = Vec2::splat(7.0 + step as f32 * 31.13);
+                let transform = Mat4::from_translation(position.extend(0.0));
+                let pvm = snap_to_pixels(projection * transform, offset, size);
+                let origin = origin_in_pixels(pvm, offset, size);
+                assert!(
+                    (origin - origin.round()).abs().max_element() < 1e-3,
+                    "origin at {origin} in a {size} viewport"
+                );
+                // A vertical and a horizontal line, one logical pixel thick
+                for (min, max) in [
+                    (Vec2::ZERO, Vec2::new(1.0, 50.0)),
+                    (Vec2::ZERO, Vec2::new(50.0, 1.0)),
+                ] {
+                    let min = to_pixels(pvm, min, offset, size);
+                    let max = to_pixels(pvm, max, offset, size);
+                    let thickness = (max - min).abs().min_element();
+                    let covered = covered_pixels(min.x.min(max.x), min.x.max(max.x))
+                        .min(covered_pixels(min.y.min(max.y), min.y.max(max.y)));
+                    assert!(
+                        (thickness - scale).abs() < 1e-3 && covered == scale as u32,
+                        "line at {position} covers {covered} pixels, {thickness} thick, in a {size} viewport"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unsnapped_line_straddles_two_pixels() {
+        let size = Vec2::new(801.0, 601.0);
+        let projection = Mat4::orthographic_rh(0.0, size.x, 0.0, size.y, -1.0, 1.0);
+        let blurry = projection * Mat4::from_translation(Vec3::X * 7.5);
+        let min = to_pixels(blurry, Vec2::ZERO, Vec2::ZERO, size);
+        assert!((min.x - min.x.round()).abs() >= 0.25, "{min}");
+    }
+
+    #[test]
+    fn texel_centers() {
+        assert_eq!(
+            texel_center_uv(Vec2::new(0.0, 3.0), Vec2::splat(4.0)),
+            Vec2::new(0.125, 0.875)
+        );
+    }
+}
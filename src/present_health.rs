@@ -0,0 +1,333 @@
+//! Watches how MAILBOX presentation behaves on this driver, some advertise it and then starve the
+//! application of images. A window that keeps waiting on acquires while its GPU is idle falls
+//! back to FIFO for the rest of the session.
+//!
+//! [`PresentModePreference`] is what the application asks for, resolved against the modes the
+//! surface supports each time the swapchain is created. The watch only runs while MAILBOX is the
+//! mode chosen.
+use ash::vk;
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, fmt, sync::Mutex, time::Duration};
+
+/// Frames judged together, about 10 seconds at 60 Hz.
+pub const PRESENT_HEALTH_WINDOW: usize = 600;
+/// An acquire waiting longer than this many refresh intervals is slow.
+pub const SLOW_ACQUIRE_REFRESHES: u32 = 2;
+/// Share of slow frames in a full window that triggers the fallback.
+pub const SLOW_FRAME_FRACTION: f32 = 0.05;
+/// A frame whose GPU work took this share of a refresh interval or more is GPU bound, its slow
+/// acquire is the engine's doing rather than the presentation's.
+pub const GPU_BOUND_FRACTION: f32 = 0.75;
+/// Refresh interval assumed when the monitor reports no rate.
+pub const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_nanos(16_666_667);
+
+/// PCI vendor and device ids of the devices MAILBOX was given up on, for the rest of the session.
+static DOWNGRADED_DEVICES: Mutex<Vec<(u32, u32)>> = Mutex::new(Vec::new());
+
+/// Present with FIFO on this device from every window's next swapchain on.
+pub fn remember_downgrade(vendor_id: u32, device_id: u32) {
+    let mut devices = DOWNGRADED_DEVICES.lock().unwrap();
+    if !devices.contains(&(vendor_id, device_id)) {
+        devices.push((vendor_id, device_id));
+    }
+}
+
+pub fn is_downgraded(vendor_id: u32, device_id: u32) -> bool {
+    DOWNGRADED_DEVICES
+        .lock()
+        .unwrap()
+        .contains(&(vendor_id, device_id))
+}
+
+/// Present mode asked for, each falls back through [`PresentModePreference::priority`] to what
+/// the surface supports. FIFO is always supported and ends every list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PresentModePreference {
+    /// FIFO, every frame waits for a vertical blank.
+    Vsync,
+    /// FIFO_RELAXED, a frame late for its blank is shown at once and may tear.
+    VsyncRelaxed,
+    /// MAILBOX, the newest frame replaces the queued one, no tearing and no waiting.
+    Mailbox,
+    /// IMMEDIATE, frames are shown as soon as they are ready and tear.
+    Immediate,
+    /// MAILBOX when the device handles it well, FIFO otherwise.
+    #[default]
+    Auto,
+}
+
+impl PresentModePreference {
+    /// In the order [`Self::next`] goes through them.
+    pub const ALL: [Self; 5] = [
+        Self::Auto,
+        Self::Vsync,
+        Self::VsyncRelaxed,
+        Self::Mailbox,
+        Self::Immediate,
+    ];
+
+    /// Modes tried in this order:
+    ///
+    /// - `Vsync`: FIFO
+    /// - `VsyncRelaxed`: FIFO_RELAXED, FIFO
+    /// - `Mailbox`: MAILBOX, FIFO
+    /// - `Immediate`: IMMEDIATE, MAILBOX, FIFO
+    /// - `Auto`: MAILBOX, FIFO
+    pub fn priority(self) -> &'static [vk::PresentModeKHR] {
+        use vk::PresentModeKHR as Mode;
+        match self {
+            Self::Vsync => &[Mode::FIFO],
+            Self::VsyncRelaxed => &[Mode::FIFO_RELAXED, Mode::FIFO],
+            Self::Mailbox | Self::Auto => &[Mode::MAILBOX, Mode::FIFO],
+            Self::Immediate => &[Mode::IMMEDIATE, Mode::MAILBOX, Mode::FIFO],
+        }
+    }
+
+    /// The first of [`Self::priority`] in `supported`. MAILBOX is skipped on a device it was
+    /// `downgraded` on, whatever was asked for.
+    pub fn resolve(self, supported: &[vk::PresentModeKHR], downgraded: bool) -> vk::PresentModeKHR {
+        self.priority()
+            .iter()
+            .copied()
+            .filter(|&mode| !(downgraded && mode == vk::PresentModeKHR::MAILBOX))
+            .find(|mode| supported.contains(mode))
+            .unwrap_or(vk::PresentModeKHR::FIFO)
+    }
+
+    /// The one after this in [`Self::ALL`], wrapping around.
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&preference| preference == self);
+        Self::ALL[index.map_or(0, |index| (index + 1) % Self::ALL.len())]
+    }
+}
+
+/// What the render thread measured of one frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PresentSample {
+    pub acquire_wait: Duration,
+    /// Execution time of the frame's commands, `None` without GPU timestamps.
+    pub gpu_time: Option<Duration>,
+    /// Presenting reported the swapchain suboptimal or out of date.
+    pub present_degraded: bool,
+}
+
+/// Why presentation fell back to FIFO, sent with `UserEvent::PresentModeDowngraded`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PresentDowngrade {
+    pub frames: u32,
+    /// Frames with a slow acquire while the GPU was not the bottleneck.
+    pub slow_frames: u32,
+    pub degraded_presents: u32,
+    pub worst_acquire: Duration,
+    pub refresh_interval: Duration,
+}
+
+impl fmt::Display for PresentDowngrade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} frames waited over {} refreshes of {:?} to acquire, worst {:?}, {} degraded presents",
+            self.slow_frames,
+            self.frames,
+            SLOW_ACQUIRE_REFRESHES,
+            self.refresh_interval,
+            self.worst_acquire,
+            self.degraded_presents
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Judged {
+    slow: bool,
+    degraded: bool,
+    acquire_wait: Duration,
+}
+
+/// Rolling window of judged frames. Frames without a GPU time are never counted slow, the GPU
+/// could not be ruled out as the cause.
+#[derive(Debug, Clone)]
+pub struct PresentHealthMonitor {
+    refresh_interval: Duration,
+    frames: VecDeque<Judged>,
+    slow_frames: usize,
+    degraded_presents: usize,
+}
+
+impl PresentHealthMonitor {
+    pub fn new(refresh_interval: Option<Duration>) -> Self {
+        Self {
+            refresh_interval: refresh_interval.unwrap_or(DEFAULT_REFRESH_INTERVAL),
+            frames: VecDeque::with_capacity(PRESENT_HEALTH_WINDOW),
+            slow_frames: 0,
+            degraded_presents: 0,
+        }
+    }
+
+    /// Forget the frames so far, after a resize or a monitor change that legitimately disturbs acquires.
+    pub fn reset(&mut self, refresh_interval: Option<Duration>) {
+        *self = Self::new(refresh_interval);
+    }
+
+    /// Add a frame, returns the evidence once a full window is anomalous.
+    pub fn record(&mut self, sample: PresentSample) -> Option<PresentDowngrade> {
+        let gpu_bound = sample.gpu_time.is_none_or(|gpu_time| {
+            gpu_time.as_secs_f32() >= self.refresh_interval.as_secs_f32() * GPU_BOUND_FRACTION
+        });
+        let judged = Judged {
+            slow: !gpu_bound
+                && sample.acquire_wait > self.refresh_interval * SLOW_ACQUIRE_REFRESHES,
+            degraded: sample.present_degraded,
+            acquire_wait: sample.acquire_wait,
+        };
+        if self.frames.len() == PRESENT_HEALTH_WINDOW {
+            let oldest = self.frames.pop_front().unwrap();
+            self.slow_frames -= oldest.slow as usize;
+            self.degraded_presents -= oldest.degraded as usize;
+        }
+        self.slow_frames += judged.slow as usize;
+        self.degraded_presents += judged.degraded as usize;
+        self.frames.push_back(judged);
+
+        let full = self.frames.len() == PRESENT_HEALTH_WINDOW;
+        let anomalous =
+            self.slow_frames as f32 > PRESENT_HEALTH_WINDOW as f32 * SLOW_FRAME_FRACTION;
+        (full && anomalous).then(|| PresentDowngrade {
+            frames: self.frames.len() as u32,
+            slow_frames: self.slow_frames as u32,
+            degraded_presents: self.degraded_presents as u32,
+            worst_acquire: self
+                .frames
+                .iter()
+                .map(|frame| frame.acquire_wait)
+                .max()
+                .unwrap_or_default(),
+            refresh_interval: self.refresh_interval,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REFRESH: Duration = Duration::from_nanos(16_666_667);
+    const FRAMES: usize = PRESENT_HEALTH_WINDOW * 3;
+
+    /// Feed `frames` samples made by `sample` from the frame index, return the first verdict and its frame.
+    fn run(
+        frames: usize,
+        sample: impl Fn(usize) -> PresentSample,
+    ) -> Option<(usize, PresentDowngrade)> {
+        let mut monitor = PresentHealthMonitor::new(Some(REFRESH));
+        (0..frames).find_map(|frame| monitor.record(sample(frame)).map(|report| (frame, report)))
+    }
+
+    fn frame(acquire_ms: u64, gpu_ms: Option<u64>) -> PresentSample {
+        PresentSample {
+            acquire_wait: Duration::from_millis(acquire_ms),
+            gpu_time: gpu_ms.map(Duration::from_millis),
+            present_degraded: false,
+        }
+    }
+
+    #[test]
+    fn healthy_presentation_is_kept() {
+        assert_eq!(run(FRAMES, |_| frame(1, Some(5))), None);
+    }
+
+    #[test]
+    fn starved_presentation_is_judged_once_the_window_fills() {
+        // Every tenth acquire waits 3 refreshes while the GPU idles
+        let (index, report) = run(FRAMES, |index| {
+            frame(if index % 10 == 0 { 50 } else { 1 }, Some(4))
+        })
+        .expect("starved presentation was not downgraded");
+        assert_eq!(index + 1, PRESENT_HEALTH_WINDOW);
+        assert_eq!(report.frames as usize, PRESENT_HEALTH_WINDOW);
+        assert_eq!(report.slow_frames as usize, PRESENT_HEALTH_WINDOW / 10);
+        assert_eq!(report.worst_acquire, Duration::from_millis(50));
+        assert_eq!(report.refresh_interval, REFRESH);
+    }
+
+    #[test]
+    fn gpu_bound_frames_are_not_blamed_on_presentation() {
+        // Every acquire waits 3 refreshes, because the GPU takes almost a refresh per frame
+        assert_eq!(run(FRAMES, |_| frame(50, Some(15))), None);
+    }
+
+    #[test]
+    fn frames_without_gpu_times_are_not_judged() {
+        assert_eq!(run(FRAMES, |_| frame(50, None)), None);
+    }
+
+    #[test]
+    fn short_hitch_stays_under_the_threshold() {
+        // A loading hitch, 4% of a window
+        let hitch = PRESENT_HEALTH_WINDOW / 25;
+        let verdict = run(FRAMES, |index| {
+            frame(if index < hitch { 50 } else { 1 }, Some(4))
+        });
+        assert_eq!(verdict, None);
+    }
+
+    #[test]
+    fn preferences_fall_back_to_what_the_surface_supports() {
+        use vk::PresentModeKHR as Mode;
+        use PresentModePreference as Preference;
+
+        let every = [
+            Mode::IMMEDIATE,
+            Mode::MAILBOX,
+            Mode::FIFO,
+            Mode::FIFO_RELAXED,
+        ];
+        let fifo_only = [Mode::FIFO];
+        let no_mailbox = [Mode::IMMEDIATE, Mode::FIFO];
+        let no_immediate = [Mode::MAILBOX, Mode::FIFO];
+        // Preference, supported, downgraded, expected
+        let cases = [
+            (Preference::Auto, &every[..], false, Mode::MAILBOX),
+            (Preference::Auto, &fifo_only, false, Mode::FIFO),
+            // A device MAILBOX starved keeps FIFO, whatever was asked for
+            (Preference::Auto, &every, true, Mode::FIFO),
+            (Preference::Mailbox, &every, true, Mode::FIFO),
+            (Preference::Vsync, &every, false, Mode::FIFO),
+            (Preference::VsyncRelaxed, &every, false, Mode::FIFO_RELAXED),
+            (Preference::VsyncRelaxed, &no_mailbox, false, Mode::FIFO),
+            (Preference::Immediate, &every, false, Mode::IMMEDIATE),
+            (Preference::Immediate, &no_immediate, false, Mode::MAILBOX),
+            (Preference::Immediate, &no_immediate, true, Mode::FIFO),
+            (Preference::Mailbox, &no_mailbox, false, Mode::FIFO),
+            // FIFO is required of every surface, it is the answer even when not listed
+            (Preference::Immediate, &[], false, Mode::FIFO),
+        ];
+        for (preference, supported, downgraded, expected) in cases {
+            assert_eq!(
+                preference.resolve(supported, downgraded),
+                expected,
+                "{preference:?} on {supported:?}, downgraded {downgraded}"
+            );
+        }
+        for preference in Preference::ALL {
+            assert_eq!(
+                preference.priority().last(),
+                Some(&Mode::FIFO),
+                "{preference:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn cycling_goes_through_every_preference_and_back() {
+        let mut preference = PresentModePreference::default();
+        let mut seen = Vec::new();
+        for _ in 0..PresentModePreference::ALL.len() {
+            seen.push(preference);
+            preference = preference.next();
+        }
+        assert_eq!(seen, PresentModePreference::ALL);
+        assert_eq!(preference, PresentModePreference::default());
+    }
+}
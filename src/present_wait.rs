@@ -0,0 +1,458 @@
+//! Frame pacing on `VK_KHR_present_wait`: the render thread waits for the previous frame to reach
+//! the screen before sampling input for the next one, rather than running ahead until a fence or
+//! an acquire blocks it. Input is then read as late as the display allows.
+//!
+//! Opt in with `graphics.present_wait_pacing`. Without both extensions, or once the waits
+//! misbehave, frames are paced by the fences as before.
+use ash::{khr, prelude::VkResult, vk};
+use log::{info, warn};
+use std::{
+    ffi::CStr,
+    time::{Duration, Instant},
+};
+
+/// Longest wait for a present before the frame goes on without it.
+pub const PRESENT_WAIT_TIMEOUT: Duration = Duration::from_millis(100);
+/// Waits in a row that time out or fail before pacing falls back to the fences for good.
+pub const PRESENT_WAIT_STRIKES: u32 = 3;
+/// Width of the buckets of the pacing histogram.
+pub const PACING_BUCKET: Duration = Duration::from_millis(1);
+/// Buckets of the pacing histogram, the last one counts every longer interval.
+pub const PACING_BUCKETS: usize = 50;
+
+/// What the device offers of `VK_KHR_present_id` and `VK_KHR_present_wait`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PresentWaitSupport {
+    /// Presents can be tagged with an id.
+    pub present_id: bool,
+    /// Presents can be waited for by id, never without `present_id`.
+    pub present_wait: bool,
+}
+
+impl PresentWaitSupport {
+    /// Each extension counts once offered with its feature, present wait needs present id.
+    pub fn detect(
+        extensions: &[&CStr],
+        present_id_feature: bool,
+        present_wait_feature: bool,
+    ) -> Self {
+        let offered = |name: &CStr| extensions.contains(&name);
+        let present_id = present_id_feature && offered(khr::present_id::NAME);
+        Self {
+            present_id,
+            present_wait: present_id && present_wait_feature && offered(khr::present_wait::NAME),
+        }
+    }
+
+    /// Extensions to enable on the device.
+    pub fn extension_names(self) -> Vec<&'static CStr> {
+        let mut names = Vec::new();
+        if self.present_id {
+            names.push(khr::present_id::NAME);
+        }
+        if self.present_wait {
+            names.push(khr::present_wait::NAME);
+        }
+        names
+    }
+
+    /// Frames can be paced on their presents.
+    pub fn available(self) -> bool {
+        self.present_wait
+    }
+}
+
+/// Chains `present_id` onto `info`, `None` leaves the present untagged.
+pub fn tag_present<'a>(
+    info: vk::PresentInfoKHR<'a>,
+    present_id: Option<&'a mut vk::PresentIdKHR<'a>>,
+) -> vk::PresentInfoKHR<'a> {
+    match present_id {
+        Some(present_id) => info.push_next(present_id),
+        None => info,
+    }
+}
+
+/// How a `vkWaitForPresentKHR` call ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresentWaitOutcome {
+    Presented,
+    TimedOut,
+    Failed(vk::Result),
+}
+
+impl From<VkResult<()>> for PresentWaitOutcome {
+    fn from(result: VkResult<()>) -> Self {
+        match result {
+            // The present was shown, the swapchain will be recreated anyway
+            Ok(()) | Err(vk::Result::SUBOPTIMAL_KHR) => Self::Presented,
+            Err(vk::Result::TIMEOUT) => Self::TimedOut,
+            Err(err) => Self::Failed(err),
+        }
+    }
+}
+
+/// Measured once a waited present reached the screen.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PresentTiming {
+    /// Since the previous waited present, `None` for the first one.
+    pub present_interval: Option<Duration>,
+    /// From the input sampled for the frame to its present, the input to photon latency less
+    /// the scanout.
+    pub input_latency: Duration,
+}
+
+/// Tags presents with increasing ids and tells the render thread which one to wait for.
+#[derive(Debug, Default)]
+pub struct PresentPacer {
+    support: PresentWaitSupport,
+    enabled: bool,
+    /// Waits misbehaved, the fences pace frames for the rest of the session.
+    fallen_back: bool,
+    /// Timeouts and failures in a row.
+    strikes: u32,
+    last_id: u64,
+    /// Input sample time of the frame being built.
+    sampled_at: Option<Instant>,
+    /// Id of the last present queued and the input sample time of its frame, waited for before
+    /// the next frame.
+    queued: Option<(u64, Instant)>,
+    last_presented_at: Option<Instant>,
+}
+
+impl PresentPacer {
+    pub fn new(support: PresentWaitSupport) -> Self {
+        Self {
+            support,
+            ..Default::default()
+        }
+    }
+
+    /// Follows `graphics.present_wait_pacing`, a fallback stays.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if enabled != self.enabled {
+            self.enabled = enabled;
+            self.queued = None;
+            self.last_presented_at = None;
+            if enabled && !self.support.available() {
+                info!("Present wait pacing asked for, the device has no VK_KHR_present_wait");
+            }
+        }
+    }
+
+    /// Frames wait on their presents.
+    pub fn active(&self) -> bool {
+        self.enabled && self.support.available() && !self.fallen_back
+    }
+
+    pub fn fallen_back(&self) -> bool {
+        self.fallen_back
+    }
+
+    /// Present to wait for before sampling input, the previous frame's.
+    pub fn wait_target(&self) -> Option<u64> {
+        self.queued
+            .filter(|_| self.active())
+            .map(|(present_id, _)| present_id)
+    }
+
+    /// Record the end of the wait for [`Self::wait_target`], at `now`. Returns the timing of a
+    /// present that was reached, and whether this wait made pacing fall back.
+    pub fn waited(
+        &mut self,
+        outcome: PresentWaitOutcome,
+        now: Instant,
+    ) -> (Option<PresentTiming>, bool) {
+        let Some((_, sampled_at)) = self.queued.take() else {
+            return (None, false);
+        };
+        match outcome {
+            PresentWaitOutcome::Presented => {
+                self.strikes = 0;
+                let timing = PresentTiming {
+                    present_interval: self
+                        .last_presented_at
+                        .map(|last| now.saturating_duration_since(last)),
+                    input_latency: now.saturating_duration_since(sampled_at),
+                };
+                self.last_presented_at = Some(now);
+                (Some(timing), false)
+            }
+            // The swapchain is recreated, its presents are gone rather than late
+            PresentWaitOutcome::Failed(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                self.last_presented_at = None;
+                (None, false)
+            }
+            PresentWaitOutcome::TimedOut | PresentWaitOutcome::Failed(_) => {
+                self.strikes += 1;
+                self.last_presented_at = None;
+                if self.strikes < PRESENT_WAIT_STRIKES {
+                    return (None, false);
+                }
+                self.fallen_back = true;
+                warn!(
+                    "Waits for presents ended {outcome:?} {} times in a row, frames are paced by fences from now on",
+                    self.strikes
+                );
+                (None, true)
+            }
+        }
+    }
+
+    /// Input for the frame is sampled at `now`.
+    pub fn input_sampled(&mut self, now: Instant) {
+        self.sampled_at = Some(now);
+    }
+
+    /// Id to tag the frame's present with, `None` leaves it untagged.
+    pub fn next_id(&mut self) -> Option<u64> {
+        if !self.active() || !self.support.present_id {
+            return None;
+        }
+        self.last_id += 1;
+        Some(self.last_id)
+    }
+
+    /// The present tagged `present_id` was queued, waited for before the next frame.
+    pub fn presented(&mut self, present_id: u64) {
+        if let Some(sampled_at) = self.sampled_at.take() {
+            self.queued = Some((present_id, sampled_at));
+        }
+    }
+
+    /// The swapchain was recreated, presents queued to the old one are not waited for. Ids keep
+    /// increasing, they only need to within a swapchain.
+    pub fn reset(&mut self) {
+        self.queued = None;
+        self.sampled_at = None;
+        self.last_presented_at = None;
+    }
+}
+
+/// Present to present intervals counted in [`PACING_BUCKET`] wide buckets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacingHistogram {
+    pub buckets: [u32; PACING_BUCKETS],
+}
+
+impl Default for PacingHistogram {
+    fn default() -> Self {
+        Self {
+            buckets: [0; PACING_BUCKETS],
+        }
+    }
+}
+
+impl PacingHistogram {
+    pub fn from_intervals(intervals: &[Duration]) -> Self {
+        let mut histogram = Self::default();
+        for interval in intervals {
+            let bucket = (interval.as_nanos() / PACING_BUCKET.as_nanos()) as usize;
+            histogram.buckets[bucket.min(PACING_BUCKETS - 1)] += 1;
+        }
+        histogram
+    }
+
+    /// Non empty buckets as `16ms:58 17ms:2`, the last one as `49ms+`.
+    pub fn summary(&self) -> String {
+        let bucket_ms = PACING_BUCKET.as_millis();
+        self.buckets
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(bucket, count)| {
+                let plus = if bucket == PACING_BUCKETS - 1 {
+                    "+"
+                } else {
+                    ""
+                };
+                format!("{}ms{plus}:{count}", bucket as u128 * bucket_ms)
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: Duration = Duration::from_millis(16);
+
+    const SUPPORTED: PresentWaitSupport = PresentWaitSupport {
+        present_id: true,
+        present_wait: true,
+    };
+
+    /// One frame: wait for the previous present, sample input, present with the next id.
+    fn frame(
+        pacer: &mut PresentPacer,
+        now: Instant,
+        outcome: PresentWaitOutcome,
+    ) -> (Option<u64>, bool, Option<u64>) {
+        let target = pacer.wait_target();
+        let fell_back = target.is_some() && pacer.waited(outcome, now).1;
+        pacer.input_sampled(now);
+        let present_id = pacer.next_id();
+        if let Some(present_id) = present_id {
+            pacer.presented(present_id);
+        }
+        (target, fell_back, present_id)
+    }
+
+    #[test]
+    fn support_needs_both_extensions_and_features() {
+        let both: &[&CStr] = &[khr::present_id::NAME, khr::present_wait::NAME];
+        let id_only: &[&CStr] = &[khr::present_id::NAME];
+        let wait_only: &[&CStr] = &[khr::present_wait::NAME];
+        // Extensions, present id feature, present wait feature, expected
+        let matrix = [
+            (both, true, true, (true, true)),
+            (both, true, false, (true, false)),
+            (both, false, true, (false, false)),
+            (id_only, true, true, (true, false)),
+            (wait_only, true, true, (false, false)),
+            (&[][..], true, true, (false, false)),
+        ];
+        for (extensions, id_feature, wait_feature, (present_id, present_wait)) in matrix {
+            let support = PresentWaitSupport::detect(extensions, id_feature, wait_feature);
+            let case = format!("{extensions:?} with features {id_feature}/{wait_feature}");
+            assert_eq!(
+                support,
+                PresentWaitSupport {
+                    present_id,
+                    present_wait,
+                },
+                "{case}"
+            );
+            assert_eq!(support.available(), present_wait, "{case}");
+            let enabled = support.extension_names();
+            assert_eq!(
+                enabled.contains(&khr::present_id::NAME),
+                present_id,
+                "{case}"
+            );
+            assert_eq!(
+                enabled.contains(&khr::present_wait::NAME),
+                present_wait,
+                "{case}"
+            );
+        }
+    }
+
+    #[test]
+    fn present_ids_are_chained_into_the_present() {
+        let present_ids = [7];
+        let mut present_id = vk::PresentIdKHR::default().present_ids(&present_ids);
+        let info = tag_present(vk::PresentInfoKHR::default(), Some(&mut present_id));
+        let chained = info.p_next as *const vk::PresentIdKHR;
+        assert!(!chained.is_null());
+        let chained = unsafe { &*chained };
+        let ids = unsafe {
+            std::slice::from_raw_parts(chained.p_present_ids, chained.swapchain_count as usize)
+        };
+        assert_eq!(chained.s_type, vk::StructureType::PRESENT_ID_KHR);
+        assert_eq!(ids, [7]);
+        assert!(tag_present(vk::PresentInfoKHR::default(), None)
+            .p_next
+            .is_null());
+    }
+
+    #[test]
+    fn frames_wait_for_the_previous_present() {
+        // Off by default, and never on without support
+        assert!(!crate::config::GraphicsConfig::default().present_wait_pacing);
+        let mut unsupported = PresentPacer::new(PresentWaitSupport {
+            present_id: true,
+            present_wait: false,
+        });
+        unsupported.set_enabled(true);
+        assert!(!unsupported.active());
+        assert_eq!(unsupported.next_id(), None);
+
+        let mut pacer = PresentPacer::new(SUPPORTED);
+        pacer.set_enabled(true);
+        let start = Instant::now();
+        let mut ids = Vec::new();
+        for index in 0..4 {
+            let (target, _, present_id) = frame(
+                &mut pacer,
+                start + FRAME * index,
+                PresentWaitOutcome::Presented,
+            );
+            assert_eq!(target, ids.last().copied(), "frame {index}");
+            ids.extend(present_id);
+        }
+        assert_eq!(ids, [1, 2, 3, 4]);
+
+        // The frame sampled at 4 frames presents 20ms later
+        let (timing, _) = pacer.waited(
+            PresentWaitOutcome::Presented,
+            start + FRAME * 4 + Duration::from_millis(20),
+        );
+        let timing = timing.unwrap();
+        assert_eq!(
+            timing.input_latency,
+            FRAME * 4 + Duration::from_millis(20) - FRAME * 3
+        );
+        assert_eq!(
+            timing.present_interval,
+            Some(FRAME + Duration::from_millis(20))
+        );
+
+        // Recreating the swapchain forgets the queued present, ids keep increasing
+        pacer.input_sampled(start);
+        let present_id = pacer.next_id().unwrap();
+        pacer.presented(present_id);
+        pacer.reset();
+        assert_eq!(pacer.wait_target(), None);
+        assert_eq!(pacer.next_id(), Some(6));
+    }
+
+    #[test]
+    fn misbehaving_waits_fall_back_to_fence_pacing() {
+        let mut pacer = PresentPacer::new(SUPPORTED);
+        pacer.set_enabled(true);
+        // Out of date presents are not strikes, timeouts in a row are
+        let mut fell_back_at = None;
+        let mut now = Instant::now();
+        let outcomes = [
+            PresentWaitOutcome::TimedOut,
+            PresentWaitOutcome::Presented,
+            PresentWaitOutcome::Failed(vk::Result::ERROR_OUT_OF_DATE_KHR),
+            PresentWaitOutcome::TimedOut,
+            PresentWaitOutcome::Failed(vk::Result::ERROR_DEVICE_LOST),
+            PresentWaitOutcome::TimedOut,
+            PresentWaitOutcome::Presented,
+        ];
+        for (index, outcome) in outcomes.into_iter().enumerate() {
+            now += FRAME;
+            let (_, fell_back, _) = frame(&mut pacer, now, outcome);
+            if fell_back {
+                fell_back_at = Some(index);
+            }
+        }
+        // The first frame has no present queued yet
+        assert_eq!(fell_back_at, Some(PRESENT_WAIT_STRIKES as usize + 2));
+        assert!(pacer.fallen_back());
+        pacer.set_enabled(false);
+        pacer.set_enabled(true);
+        assert!(!pacer.active());
+        assert_eq!(pacer.wait_target(), None);
+        assert_eq!(pacer.next_id(), None);
+    }
+
+    #[test]
+    fn histogram_buckets_intervals_by_the_millisecond() {
+        let intervals = [
+            Duration::from_micros(16_600),
+            Duration::from_micros(16_700),
+            Duration::from_micros(33_300),
+            Duration::from_secs(1),
+        ];
+        assert_eq!(
+            PacingHistogram::from_intervals(&intervals).summary(),
+            "16ms:2 33ms:1 49ms+:1"
+        );
+    }
+}
@@ -0,0 +1,237 @@
+use crate::{
+    config::GraphicsConfig, display::DisplayEnvironment, flight_recorder::RecreateReason,
+    input_manager::EventStates,
+};
+use ash::vk;
+use std::{error::Error, sync::Arc};
+use winit::window::Window;
+
+/// What became of one pass of a render thread's loop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameOutcome {
+    Rendered,
+    /// The swapchain is recreated before the next frame. A suboptimal one still presented this
+    /// frame.
+    NeedsRecreate(RecreateReason),
+    /// Nothing presented, the device failed and the failure screen takes over.
+    Dropped,
+    /// The window is closing.
+    Exit,
+}
+
+impl FrameOutcome {
+    /// Of an acquire or a present that failed with `err`.
+    pub fn failed(err: vk::Result) -> Self {
+        if err == vk::Result::ERROR_OUT_OF_DATE_KHR {
+            Self::NeedsRecreate(RecreateReason::OutOfDate)
+        } else {
+            Self::Dropped
+        }
+    }
+
+    /// Of a frame whose image was acquired, `acquire_suboptimal` as acquiring reported it, then
+    /// presented with `present`.
+    pub fn presented(acquire_suboptimal: bool, present: Result<bool, vk::Result>) -> Self {
+        match present {
+            Ok(false) if !acquire_suboptimal => Self::Rendered,
+            Ok(_) => Self::NeedsRecreate(RecreateReason::Suboptimal),
+            Err(err) => Self::failed(err),
+        }
+    }
+}
+
+/// Textures and meshes a window renderer holds. The default texture, its set and staging ring,
+/// the gizmo handles and the reference grid are made on first use, a new renderer holds the
+/// demo scene's alone.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct StartupContents {
+    pub textures: usize,
+    pub meshes: usize,
+}
+
+impl StartupContents {
+    /// Of a renderer just created with `config`: the demo scene samples the bundled picture
+    /// with its UI cover and two squares.
+    pub fn of(config: &GraphicsConfig) -> Self {
+        if config.demo_scene {
+            Self {
+                textures: 1,
+                meshes: 3,
+            }
+        } else {
+            Self::default()
+        }
+    }
+}
+
+/// Rendering side of a window, driven from the event loop thread.
+pub trait WindowRenderer {
+    /// The window size, scale factor or monitor changed.
+    fn resize(&mut self, display: DisplayEnvironment);
+    /// Start presenting frames.
+    fn render(&mut self);
+    /// Stop rendering, returns once no frame is in flight.
+    fn shutdown(&mut self);
+}
+
+/// Creates the renderer of every window the `WindowManager` opens.
+pub trait RendererFactory {
+    fn create_surface_renderer(
+        &mut self,
+        window: &Arc<Window>,
+        event_states: Arc<EventStates>,
+        display: DisplayEnvironment,
+    ) -> Result<Box<dyn WindowRenderer>, Box<dyn Error>>;
+}
+
+/// Renders nothing, for tools that only need windows and for driving the event handling without a GPU.
+#[derive(Debug, Default)]
+pub struct NullRenderer {
+    pub display: Option<DisplayEnvironment>,
+    pub rendering: bool,
+}
+
+impl NullRenderer {
+    /// Nothing is ever created, whatever the configuration.
+    pub fn contents(&self) -> StartupContents {
+        StartupContents::default()
+    }
+}
+
+impl WindowRenderer for NullRenderer {
+    fn resize(&mut self, display: DisplayEnvironment) {
+        self.display = Some(display);
+    }
+
+    fn render(&mut self) {
+        self.rendering = true;
+    }
+
+    fn shutdown(&mut self) {
+        self.rendering = false;
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct NullRendererFactory;
+
+impl RendererFactory for NullRendererFactory {
+    fn create_surface_renderer(
+        &mut self,
+        _window: &Arc<Window>,
+        event_states: Arc<EventStates>,
+        display: DisplayEnvironment,
+    ) -> Result<Box<dyn WindowRenderer>, Box<dyn Error>> {
+        // Nothing to present, the window is ready as it is
+        event_states.mark_first_frame_presented();
+        Ok(Box::new(NullRenderer {
+            display: Some(display),
+            rendering: false,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUBOPTIMAL: FrameOutcome = FrameOutcome::NeedsRecreate(RecreateReason::Suboptimal);
+    const OUT_OF_DATE: FrameOutcome = FrameOutcome::NeedsRecreate(RecreateReason::OutOfDate);
+
+    /// Part of the feature matrix, run with the defaults and with `--no-default-features`: the
+    /// headless renderer and the configuration are there whatever is compiled in.
+    #[test]
+    fn headless_renderer_in_every_feature_set() {
+        let config = crate::config::PulsarConfig::default();
+        let written = toml::to_string(&config).unwrap();
+        assert_eq!(
+            crate::config::PulsarConfig::parse(&written).unwrap(),
+            config
+        );
+        let display = DisplayEnvironment {
+            width: config.window.width,
+            height: config.window.height,
+            scale_factor: 1.0,
+            refresh_rate_millihertz: None,
+        };
+        let mut renderer = NullRenderer::default();
+        assert_eq!(renderer.contents(), StartupContents::of(&config.graphics));
+        let headless: &mut dyn WindowRenderer = &mut renderer;
+        headless.resize(display);
+        headless.render();
+        assert_eq!(renderer.display, Some(display));
+        assert!(renderer.rendering, "Rendering once asked to");
+        (&mut renderer as &mut dyn WindowRenderer).shutdown();
+        assert!(!renderer.rendering, "Shut down, no frame in flight");
+    }
+
+    #[test]
+    fn a_new_renderer_holds_the_demo_scene_alone() {
+        let empty = StartupContents::of(&GraphicsConfig::default());
+        assert_eq!(
+            (empty.textures, empty.meshes),
+            (0, 0),
+            "No texture, set nor helper mesh before a frame samples them"
+        );
+        let demo = StartupContents::of(&GraphicsConfig::default().with_demo_scene(true));
+        assert_eq!((demo.textures, demo.meshes), (1, 3));
+    }
+
+    #[test]
+    fn null_renderer_follows_the_window() {
+        let display = DisplayEnvironment {
+            width: 640,
+            height: 480,
+            scale_factor: 1.5,
+            refresh_rate_millihertz: None,
+        };
+        let mut renderer = NullRenderer::default();
+        renderer.resize(display);
+        renderer.render();
+        assert_eq!(renderer.display, Some(display));
+        assert!(renderer.rendering);
+        renderer.shutdown();
+        assert!(!renderer.rendering);
+    }
+
+    #[test]
+    fn out_of_date_recreates_other_failures_drop() {
+        assert_eq!(
+            FrameOutcome::failed(vk::Result::ERROR_OUT_OF_DATE_KHR),
+            OUT_OF_DATE
+        );
+        assert_eq!(
+            FrameOutcome::failed(vk::Result::ERROR_DEVICE_LOST),
+            FrameOutcome::Dropped
+        );
+        assert_eq!(
+            FrameOutcome::failed(vk::Result::ERROR_SURFACE_LOST_KHR),
+            FrameOutcome::Dropped
+        );
+    }
+
+    #[test]
+    fn suboptimal_acquire_or_present_recreates_after_presenting() {
+        assert_eq!(
+            FrameOutcome::presented(false, Ok(false)),
+            FrameOutcome::Rendered
+        );
+        assert_eq!(FrameOutcome::presented(true, Ok(false)), SUBOPTIMAL);
+        assert_eq!(FrameOutcome::presented(false, Ok(true)), SUBOPTIMAL);
+        assert_eq!(FrameOutcome::presented(true, Ok(true)), SUBOPTIMAL);
+    }
+
+    #[test]
+    fn failed_present_wins_over_suboptimal_acquire() {
+        for acquire_suboptimal in [false, true] {
+            assert_eq!(
+                FrameOutcome::presented(acquire_suboptimal, Err(vk::Result::ERROR_OUT_OF_DATE_KHR)),
+                OUT_OF_DATE
+            );
+        }
+        assert_eq!(
+            FrameOutcome::presented(false, Err(vk::Result::ERROR_DEVICE_LOST)),
+            FrameOutcome::Dropped
+        );
+    }
+}
@@ -0,0 +1,255 @@
+pub use crate::config::{REPLAY_BUDGET_MB, REPLAY_CAPTURE_FPS};
+use crate::jobs::{self, JoinToken};
+use image::{codecs::gif::GifEncoder, Delay, Frame, RgbaImage};
+use log::{error, info};
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// Each side of the captured frame is divided by this factor on the GPU before readback.
+pub const REPLAY_DOWNSCALE: u32 = 4;
+
+const REPLAY_OUTPUT_DIR: &str = "replays";
+
+pub struct ReplayFrame {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+    pub captured_at: Instant,
+}
+
+impl ReplayFrame {
+    fn size(&self) -> usize {
+        self.rgba.len()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayFormat {
+    Gif,
+    PngSequence,
+}
+
+/// Ring buffer of downscaled frames, bounded by a memory budget instead of a frame count.
+pub struct ReplayBuffer {
+    frames: VecDeque<ReplayFrame>,
+    budget_bytes: usize,
+    used_bytes: usize,
+    interval: Duration,
+    last_capture: Option<Instant>,
+}
+
+impl Default for ReplayBuffer {
+    fn default() -> Self {
+        Self::new(REPLAY_BUDGET_MB, REPLAY_CAPTURE_FPS)
+    }
+}
+
+impl ReplayBuffer {
+    pub fn new(budget_mb: usize, capture_fps: u32) -> Self {
+        Self {
+            frames: VecDeque::new(),
+            budget_bytes: budget_mb * 1024 * 1024,
+            used_bytes: 0,
+            interval: Duration::from_secs(1) / capture_fps.max(1),
+            last_capture: None,
+        }
+    }
+
+    /// Whether enough time elapsed since the last capture to take another frame.
+    pub fn wants_capture(&self, now: Instant) -> bool {
+        match self.last_capture {
+            Some(last) => now.duration_since(last) >= self.interval,
+            None => true,
+        }
+    }
+
+    pub fn mark_captured(&mut self, now: Instant) {
+        self.last_capture = Some(now);
+    }
+
+    /// Push a frame, evicting the oldest ones until the buffer fits its budget again.
+    pub fn push(&mut self, frame: ReplayFrame) {
+        if frame.size() > self.budget_bytes {
+            return;
+        }
+        self.used_bytes += frame.size();
+        self.frames.push_back(frame);
+        while self.used_bytes > self.budget_bytes {
+            match self.frames.pop_front() {
+                Some(evicted) => self.used_bytes -= evicted.size(),
+                None => break,
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn used_bytes(&self) -> usize {
+        self.used_bytes
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.used_bytes = 0;
+    }
+
+    /// Drain the buffered frames and encode them on the job pool.
+    pub fn save(&mut self, format: ReplayFormat) -> Option<JoinToken<()>> {
+        if self.frames.is_empty() {
+            info!("Replay buffer is empty, nothing to save");
+            return None;
+        }
+        let frames: Vec<ReplayFrame> = self.frames.drain(..).collect();
+        self.used_bytes = 0;
+
+        let stamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let path = PathBuf::from(REPLAY_OUTPUT_DIR).join(format!("replay_{stamp}"));
+
+        Some(jobs::spawn(move || {
+            let result = match format {
+                ReplayFormat::Gif => encode_gif(&frames, &path.with_extension("gif")),
+                ReplayFormat::PngSequence => encode_png_sequence(&frames, &path),
+            };
+            match result {
+                Ok(()) => info!("Saved {} replay frames to {}", frames.len(), path.display()),
+                Err(err) => error!("Failed to save replay: {err}"),
+            }
+        }))
+    }
+}
+
+/// Per-frame delays derived from the capture timestamps, the last frame reusing the previous delay.
+pub fn frame_delays(frames: &[ReplayFrame]) -> Vec<Duration> {
+    let mut delays: Vec<Duration> = frames
+        .windows(2)
+        .map(|pair| pair[1].captured_at.duration_since(pair[0].captured_at))
+        .collect();
+    let last = delays
+        .last()
+        .copied()
+        .unwrap_or(Duration::from_secs(1) / REPLAY_CAPTURE_FPS);
+    if !frames.is_empty() {
+        delays.push(last);
+    }
+    delays
+}
+
+fn encode_gif(frames: &[ReplayFrame], path: &Path) -> Result<(), Box<dyn Error>> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut encoder = GifEncoder::new(BufWriter::new(File::create(path)?));
+    encoder.set_repeat(image::codecs::gif::Repeat::Infinite)?;
+    for (frame, delay) in frames.iter().zip(frame_delays(frames)) {
+        let buffer = RgbaImage::from_raw(frame.width, frame.height, frame.rgba.clone())
+            .ok_or("Replay frame does not match its dimensions")?;
+        encoder.encode_frame(Frame::from_parts(
+            buffer,
+            0,
+            0,
+            Delay::from_saturating_duration(delay),
+        ))?;
+    }
+    Ok(())
+}
+
+fn encode_png_sequence(frames: &[ReplayFrame], dir: &Path) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(dir)?;
+    for (index, frame) in frames.iter().enumerate() {
+        image::save_buffer(
+            dir.join(format!("{index:05}.png")),
+            &frame.rgba,
+            frame.width,
+            frame.height,
+            image::ExtendedColorType::Rgba8,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A quarter of a megabyte, four fit a budget of one.
+    fn frame(captured_at: Instant) -> ReplayFrame {
+        ReplayFrame {
+            width: 256,
+            height: 256,
+            rgba: vec![0; 256 * 256 * 4],
+            captured_at,
+        }
+    }
+
+    #[test]
+    fn push_evicts_the_oldest_frames_over_budget() {
+        let start = Instant::now();
+        let mut buffer = ReplayBuffer::new(1, 10);
+        for index in 0..6 {
+            buffer.push(frame(start + Duration::from_millis(index * 100)));
+        }
+        assert_eq!(buffer.len(), 4);
+        assert_eq!(buffer.used_bytes(), 1024 * 1024);
+        let oldest = buffer.frames.front().unwrap().captured_at;
+        assert_eq!(oldest, start + Duration::from_millis(200));
+    }
+
+    #[test]
+    fn frame_over_budget_is_dropped() {
+        let mut buffer = ReplayBuffer::new(1, 10);
+        buffer.push(frame(Instant::now()));
+        buffer.push(ReplayFrame {
+            width: 1024,
+            height: 1024,
+            rgba: vec![0; 1024 * 1024 * 4],
+            captured_at: Instant::now(),
+        });
+        assert_eq!(buffer.len(), 1);
+        assert_eq!(buffer.used_bytes(), 256 * 256 * 4);
+        buffer.clear();
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.used_bytes(), 0);
+    }
+
+    #[test]
+    fn captures_at_the_requested_rate() {
+        let start = Instant::now();
+        let mut buffer = ReplayBuffer::new(1, 10);
+        assert!(buffer.wants_capture(start));
+        buffer.mark_captured(start);
+        assert!(!buffer.wants_capture(start + Duration::from_millis(99)));
+        assert!(buffer.wants_capture(start + Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn delays_follow_the_capture_times() {
+        let start = Instant::now();
+        let frames: Vec<ReplayFrame> = [0, 100, 250]
+            .map(|millis| frame(start + Duration::from_millis(millis)))
+            .into();
+        assert_eq!(
+            frame_delays(&frames),
+            [100, 150, 150].map(Duration::from_millis)
+        );
+        assert_eq!(
+            frame_delays(&frames[..1]),
+            [Duration::from_secs(1) / REPLAY_CAPTURE_FPS]
+        );
+        assert!(frame_delays(&[]).is_empty());
+    }
+}
@@ -0,0 +1,269 @@
+//! Keeps the GPU copies of resources within a memory budget by evicting the least recently used streaming ones.
+use crate::handles::MeshHandle;
+use std::{collections::HashMap, hash::Hash};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ResidencyPriority {
+    /// Evicted first, least recently used first, reloaded when drawn again.
+    Streaming,
+    /// Counted against the budget, never evicted.
+    #[default]
+    Normal,
+    /// UI and placeholders, never evicted.
+    AlwaysResident,
+}
+
+/// What a window's renderer keeps within its budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ResidencyKey {
+    Mesh(MeshHandle),
+    /// The default material's texture, while it can be loaded again from what it was loaded from.
+    DefaultTexture,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ResidencyStats {
+    pub evictions: u64,
+    pub reloads: u64,
+    pub resident_bytes: u64,
+    pub budget_bytes: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    size: u64,
+    priority: ResidencyPriority,
+    last_used: u64,
+    resident: bool,
+}
+
+/// Bookkeeping only, the caller destroys and reloads the GPU copies it is told about.
+#[derive(Debug)]
+pub struct ResidencyManager<K> {
+    entries: HashMap<K, Entry>,
+    frame: u64,
+    stats: ResidencyStats,
+}
+
+impl<K: Eq + Hash + Clone> ResidencyManager<K> {
+    pub fn new(budget_bytes: u64) -> Self {
+        Self {
+            entries: HashMap::new(),
+            frame: 0,
+            stats: ResidencyStats {
+                budget_bytes,
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn set_budget(&mut self, budget_bytes: u64) {
+        self.stats.budget_bytes = budget_bytes;
+    }
+
+    /// Start tracking a resource whose GPU copy was just created.
+    pub fn track(&mut self, key: K, size: u64, priority: ResidencyPriority) {
+        let previous = self.entries.insert(
+            key,
+            Entry {
+                size,
+                priority,
+                last_used: self.frame,
+                resident: true,
+            },
+        );
+        if let Some(previous) = previous.filter(|entry| entry.resident) {
+            self.stats.resident_bytes -= previous.size;
+        }
+        self.stats.resident_bytes += size;
+    }
+
+    /// The GPU copy was reallocated at `size`.
+    pub fn resize(&mut self, key: &K, size: u64) {
+        if let Some(entry) = self.entries.get_mut(key) {
+            if entry.resident {
+                self.stats.resident_bytes = self.stats.resident_bytes - entry.size + size;
+            }
+            entry.size = size;
+        }
+    }
+
+    /// Evicted or kept by `priority` from now on, resident or not.
+    pub fn set_priority(&mut self, key: &K, priority: ResidencyPriority) {
+        if let Some(entry) = self.entries.get_mut(key) {
+            entry.priority = priority;
+        }
+    }
+
+    pub fn forget(&mut self, key: &K) {
+        if let Some(entry) = self.entries.remove(key).filter(|entry| entry.resident) {
+            self.stats.resident_bytes -= entry.size;
+        }
+    }
+
+    /// Record a use this frame. Returns true when the resource was evicted and must be reloaded before the draw.
+    pub fn touch(&mut self, key: &K) -> bool {
+        let Some(entry) = self.entries.get_mut(key) else {
+            return false;
+        };
+        entry.last_used = self.frame;
+        if entry.resident {
+            return false;
+        }
+        entry.resident = true;
+        self.stats.resident_bytes += entry.size;
+        self.stats.reloads += 1;
+        true
+    }
+
+    pub fn is_resident(&self, key: &K) -> bool {
+        self.entries.get(key).is_some_and(|entry| entry.resident)
+    }
+
+    pub fn next_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    /// Resources to evict so the resident bytes fit the budget, least recently used first.
+    /// Anything used during the current frame is kept, so this may stay over budget.
+    pub fn evict_over_budget(&mut self) -> Vec<K> {
+        if self.stats.resident_bytes <= self.stats.budget_bytes {
+            return Vec::new();
+        }
+        let mut candidates: Vec<(&K, &Entry)> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| {
+                entry.resident
+                    && entry.priority == ResidencyPriority::Streaming
+                    && entry.last_used < self.frame
+            })
+            .collect();
+        candidates.sort_by_key(|(_, entry)| entry.last_used);
+
+        let mut excess = self.stats.resident_bytes - self.stats.budget_bytes;
+        let mut evicted = Vec::new();
+        for (key, entry) in candidates {
+            if excess == 0 {
+                break;
+            }
+            excess = excess.saturating_sub(entry.size);
+            evicted.push(key.clone());
+        }
+        for key in &evicted {
+            let entry = self.entries.get_mut(key).unwrap();
+            entry.resident = false;
+            self.stats.resident_bytes -= entry.size;
+            self.stats.evictions += 1;
+        }
+        evicted
+    }
+
+    pub fn stats(&self) -> ResidencyStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_what_was_not_used_this_frame() {
+        let mut residency = ResidencyManager::new(100);
+        residency.track(0, 60, ResidencyPriority::Streaming);
+        residency.track(1, 60, ResidencyPriority::Streaming);
+        residency.next_frame();
+        // Both in view, over budget but nothing to evict
+        assert!(!residency.touch(&0));
+        assert!(!residency.touch(&1));
+        assert!(residency.evict_over_budget().is_empty());
+        residency.next_frame();
+        assert!(!residency.touch(&1));
+        assert_eq!(residency.evict_over_budget(), [0]);
+        assert_eq!(residency.stats().resident_bytes, 60);
+        // Back in view, reloaded
+        residency.next_frame();
+        assert!(residency.touch(&0));
+        assert!(residency.is_resident(&0));
+        assert_eq!(residency.stats().reloads, 1);
+    }
+
+    #[test]
+    fn least_recently_used_first() {
+        let mut residency = ResidencyManager::new(100);
+        for key in 0..3 {
+            residency.track(key, 50, ResidencyPriority::Streaming);
+        }
+        residency.next_frame();
+        residency.touch(&2);
+        residency.touch(&0);
+        residency.next_frame();
+        residency.touch(&2);
+        residency.next_frame();
+        assert_eq!(residency.evict_over_budget(), [1]);
+    }
+
+    #[test]
+    fn priority_keeps_resources() {
+        let mut residency = ResidencyManager::new(0);
+        residency.track(0, 10, ResidencyPriority::Normal);
+        residency.track(1, 10, ResidencyPriority::AlwaysResident);
+        residency.track(2, 10, ResidencyPriority::Streaming);
+        residency.next_frame();
+        assert_eq!(residency.evict_over_budget(), [2]);
+        residency.set_priority(&0, ResidencyPriority::Streaming);
+        assert_eq!(residency.evict_over_budget(), [0]);
+        // An evicted resource kept from now on stays evicted until used
+        residency.set_priority(&2, ResidencyPriority::Normal);
+        assert!(!residency.is_resident(&2));
+        assert!(residency.touch(&2));
+    }
+
+    #[test]
+    fn forgotten_and_resized_bytes() {
+        let mut residency = ResidencyManager::new(100);
+        residency.track(0, 40, ResidencyPriority::Streaming);
+        residency.resize(&0, 70);
+        assert_eq!(residency.stats().resident_bytes, 70);
+        residency.forget(&0);
+        assert_eq!(residency.stats().resident_bytes, 0);
+        assert!(!residency.touch(&0));
+    }
+
+    #[test]
+    fn shrinking_budget_spares_what_must_stay() {
+        const MB: u64 = 1024 * 1024;
+        let mut residency = ResidencyManager::new(4 * MB);
+        residency.track("ui", MB, ResidencyPriority::AlwaysResident);
+        residency.track("terrain", MB, ResidencyPriority::Normal);
+        for name in ["rock", "tree", "cloud"] {
+            residency.track(name, MB, ResidencyPriority::Streaming);
+        }
+        // Frame 1 uses everything but the rock, frame 2 only the ui and the cloud
+        residency.next_frame();
+        for name in ["ui", "terrain", "tree", "cloud"] {
+            residency.touch(&name);
+        }
+        residency.next_frame();
+        for name in ["ui", "cloud"] {
+            residency.touch(&name);
+        }
+
+        assert_eq!(residency.evict_over_budget(), ["rock"]);
+        assert!(residency.evict_over_budget().is_empty());
+        // Down to 1 MB, only streaming resources not used this frame may go
+        residency.set_budget(MB);
+        assert_eq!(residency.evict_over_budget(), ["tree"]);
+        for name in ["ui", "terrain", "cloud"] {
+            assert!(residency.is_resident(&name), "{name}");
+        }
+
+        residency.next_frame();
+        assert!(residency.touch(&"rock"));
+        assert!(!residency.touch(&"rock"));
+        let stats = residency.stats();
+        assert_eq!((stats.evictions, stats.reloads), (2, 1));
+        assert_eq!(stats.resident_bytes, 4 * MB);
+    }
+}
@@ -0,0 +1,243 @@
+use crate::{
+    color_space::{f16_to_f32, ExportColorSpace},
+    jobs::{self, JoinToken},
+    transient::{Access, Pass, PassList},
+};
+use log::{error, info};
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+const SCREENSHOT_OUTPUT_DIR: &str = "screenshots";
+/// Extensions of timestamped 8 bit and half float screenshots, PAM and PFM are written without an
+/// image encoder.
+#[cfg(feature = "images")]
+const SCREENSHOT_EXTENSIONS: [&str; 2] = ["png", "exr"];
+#[cfg(not(feature = "images"))]
+const SCREENSHOT_EXTENSIONS: [&str; 2] = ["pam", "pfm"];
+/// Captures larger than this on either side are refused, most drivers cap images at 16384.
+pub const SCREENSHOT_MAX_EXTENT: u32 = 16384;
+
+/// Attachments of a screenshot, by index in [`screenshot_passes`].
+pub const SCREENSHOT_DEPTH: usize = 0;
+pub const SCREENSHOT_MSAA_COLOR: usize = 1;
+/// The image the render is blitted into.
+pub const SCREENSHOT_READBACK: usize = 2;
+/// The image of the export's color space, when the blit is converted.
+pub const SCREENSHOT_EXPORT: usize = 3;
+pub const SCREENSHOT_ATTACHMENTS: usize = 4;
+
+/// A screenshot renders the scene, blits it into the readback image, converts that to the
+/// export's color space if needed, then copies the result into a host buffer. The depth and
+/// multisampled color are done with once the scene is, the images after it reuse their memory.
+pub fn screenshot_passes(convert: bool) -> PassList {
+    let mut passes = vec![
+        Pass::new(
+            "scene",
+            &[
+                (SCREENSHOT_DEPTH, Access::Clear),
+                (SCREENSHOT_MSAA_COLOR, Access::Clear),
+            ],
+        ),
+        Pass::new("blit", &[(SCREENSHOT_READBACK, Access::Overwrite)]),
+    ];
+    let copied = if convert {
+        passes.push(Pass::new(
+            "convert",
+            &[
+                (SCREENSHOT_READBACK, Access::Read),
+                (SCREENSHOT_EXPORT, Access::Overwrite),
+            ],
+        ));
+        SCREENSHOT_EXPORT
+    } else {
+        SCREENSHOT_READBACK
+    };
+    passes.push(Pass::new("copy", &[(copied, Access::Read)]));
+    PassList::new(passes)
+}
+
+/// One offscreen render of the current scene, independent of the window size.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScreenshotRequest {
+    pub width: u32,
+    pub height: u32,
+    pub path: PathBuf,
+    pub color_space: ExportColorSpace,
+}
+
+impl ScreenshotRequest {
+    pub fn new(
+        width: u32,
+        height: u32,
+        path: impl Into<PathBuf>,
+        color_space: ExportColorSpace,
+    ) -> Self {
+        Self {
+            width,
+            height,
+            path: path.into(),
+            color_space,
+        }
+    }
+
+    /// Timestamped PNG or OpenEXR in the screenshots directory, PAM or PFM without the `images`
+    /// feature.
+    pub fn timestamped(width: u32, height: u32, color_space: ExportColorSpace) -> Self {
+        let extension = match color_space {
+            ExportColorSpace::Srgb8 => SCREENSHOT_EXTENSIONS[0],
+            ExportColorSpace::Linear16F => SCREENSHOT_EXTENSIONS[1],
+        };
+        let stamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        Self::new(
+            width,
+            height,
+            PathBuf::from(SCREENSHOT_OUTPUT_DIR).join(format!("screenshot_{stamp}.{extension}")),
+            color_space,
+        )
+    }
+
+    pub fn is_valid(&self) -> bool {
+        (1..=SCREENSHOT_MAX_EXTENT).contains(&self.width)
+            && (1..=SCREENSHOT_MAX_EXTENT).contains(&self.height)
+    }
+
+    /// Encode `texels`, in the request's color space, on the job pool. The format follows the
+    /// extension of `path`, one holding floats for [`ExportColorSpace::Linear16F`]. Without the
+    /// `images` feature it is always an uncompressed PAM or PFM, whatever the extension.
+    pub fn save(self, texels: Vec<u8>) -> JoinToken<()> {
+        jobs::spawn(move || {
+            let written = match self.color_space {
+                ExportColorSpace::Srgb8 => {
+                    write_image(&self.path, self.width, self.height, &texels)
+                }
+                ExportColorSpace::Linear16F => {
+                    write_float_image(&self.path, self.width, self.height, &texels)
+                }
+            };
+            match written {
+                Ok(()) => info!(
+                    "Saved {}x{} screenshot to {}",
+                    self.width,
+                    self.height,
+                    self.path.display()
+                ),
+                Err(err) => error!("Failed to save screenshot: {err}"),
+            }
+        })
+    }
+}
+
+fn write_image(path: &Path, width: u32, height: u32, rgba: &[u8]) -> Result<(), Box<dyn Error>> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    #[cfg(feature = "images")]
+    image::save_buffer(path, rgba, width, height, image::ExtendedColorType::Rgba8)?;
+    #[cfg(not(feature = "images"))]
+    {
+        let header = format!(
+            "P7\nWIDTH {width}\nHEIGHT {height}\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n"
+        );
+        fs::write(path, [header.as_bytes(), rgba].concat())?;
+    }
+    Ok(())
+}
+
+/// `texels` of RGBA16F, linear values above 1.0 kept. PFM has no alpha channel.
+fn write_float_image(
+    path: &Path,
+    width: u32,
+    height: u32,
+    texels: &[u8],
+) -> Result<(), Box<dyn Error>> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let floats: Vec<f32> = texels
+        .chunks_exact(2)
+        .map(|half| f16_to_f32(u16::from_le_bytes([half[0], half[1]])))
+        .collect();
+    #[cfg(feature = "images")]
+    image::Rgba32FImage::from_raw(width, height, floats)
+        .ok_or("Screenshot does not match its dimensions")?
+        .save(path)?;
+    #[cfg(not(feature = "images"))]
+    {
+        let mut pfm = format!("PF\n{width} {height}\n-1.0\n").into_bytes();
+        // Little endian, bottom row first
+        for row in floats.chunks_exact(width as usize * 4).rev() {
+            for rgba in row.chunks_exact(4) {
+                for channel in &rgba[..3] {
+                    pfm.extend(channel.to_le_bytes());
+                }
+            }
+        }
+        fs::write(path, pfm)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transient::Lifetime;
+
+    /// Part of the feature matrix: PNG and OpenEXR with an image encoder, PAM and PFM without.
+    #[test]
+    fn screenshots_are_written_in_every_feature_set() {
+        let dir = std::env::temp_dir().join(format!("pulsar_screenshot_{}", std::process::id()));
+        let [extension, float_extension] = SCREENSHOT_EXTENSIONS;
+        let path = dir.join(format!("rgba.{extension}"));
+        write_image(&path, 2, 1, &[255, 0, 0, 255, 0, 0, 255, 128]).unwrap();
+        let float_path = dir.join(format!("float.{float_extension}"));
+        // 1.0 and 2.0 as half floats
+        let texels: Vec<u8> = [0x3c00u16, 0x4000, 0, 0x3c00]
+            .iter()
+            .flat_map(|half| half.to_le_bytes())
+            .collect();
+        write_float_image(&float_path, 1, 1, &texels).unwrap();
+
+        let written = fs::read(&path).unwrap();
+        let float_written = fs::read(&float_path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        #[cfg(feature = "images")]
+        {
+            assert!(written.starts_with(b"\x89PNG"));
+            assert!(float_written.starts_with(&[0x76, 0x2f, 0x31, 0x01]));
+        }
+        #[cfg(not(feature = "images"))]
+        {
+            assert!(written.starts_with(b"P7\nWIDTH 2\nHEIGHT 1\n"));
+            assert!(written.ends_with(&[255, 0, 0, 255, 0, 0, 255, 128]));
+            let mut expected = b"PF\n1 1\n-1.0\n".to_vec();
+            for channel in [1.0f32, 2.0, 0.0] {
+                expected.extend(channel.to_le_bytes());
+            }
+            assert_eq!(float_written, expected);
+        }
+    }
+
+    #[test]
+    fn screenshot_attachments_live_until_their_last_pass() {
+        let some = |first, last| Some(Lifetime { first, last });
+        let expected = [
+            (false, [some(0, 0), some(0, 0), some(1, 2), None]),
+            (true, [some(0, 0), some(0, 0), some(1, 2), some(2, 3)]),
+        ];
+        for (convert, expected) in expected {
+            assert_eq!(
+                screenshot_passes(convert)
+                    .lifetimes(SCREENSHOT_ATTACHMENTS)
+                    .unwrap(),
+                expected,
+                "converted {convert}"
+            );
+        }
+    }
+}
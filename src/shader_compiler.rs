@@ -0,0 +1,266 @@
+//! GLSL compilers run as a process of their own, when the `shaderc` feature is off.
+//!
+//! [`discover`] takes the compiler [`SHADER_COMPILER_ENV`] names, a path or a program looked up
+//! on the `PATH`, otherwise the first of `glslc` and `glslangValidator` found on the `PATH`. Both
+//! read the source on their standard input, [`GlslCompiler::args`] adapts the rest of the command
+//! line to the one found.
+use log::{info, warn};
+use std::{
+    env,
+    ffi::{OsStr, OsString},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// Compiler to run in place of the one found on the `PATH`.
+pub const SHADER_COMPILER_ENV: &str = "PULSAR_SHADERC";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompilerKind {
+    /// Writes the SPIR-V on its standard output.
+    Glslc,
+    /// Writes the SPIR-V to a file only, its errors on the standard output.
+    GlslangValidator,
+}
+
+impl CompilerKind {
+    /// Looked up on the `PATH` in this order.
+    pub const SEARCHED: [Self; 2] = [Self::Glslc, Self::GlslangValidator];
+
+    /// Name of the executable, without the platform's suffix.
+    pub fn program(self) -> &'static str {
+        match self {
+            Self::Glslc => "glslc",
+            Self::GlslangValidator => "glslangValidator",
+        }
+    }
+
+    /// Of the executable at `path` by its name, glslc unless it names glslangValidator.
+    pub fn of(path: &Path) -> Self {
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        if name.starts_with("glslangvalidator") {
+            Self::GlslangValidator
+        } else {
+            Self::Glslc
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlslCompiler {
+    pub kind: CompilerKind,
+    pub path: PathBuf,
+}
+
+impl GlslCompiler {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            kind: CompilerKind::of(&path),
+            path,
+        }
+    }
+
+    /// Whether the SPIR-V comes on the standard output, written to the `output` of
+    /// [`GlslCompiler::args`] otherwise.
+    pub fn writes_stdout(&self) -> bool {
+        self.kind == CompilerKind::Glslc
+    }
+
+    /// Command line compiling the source on the standard input as a `stage` shader, `vert`,
+    /// `tesc`, `tese`, `geom`, `frag` or `comp`. `output` is ignored when [`GlslCompiler::writes_stdout`].
+    pub fn args(&self, stage: &str, output: &Path) -> Vec<OsString> {
+        match self.kind {
+            CompilerKind::Glslc => vec![
+                format!("-fshader-stage={stage}").into(),
+                "-".into(),
+                "-o".into(),
+                "-".into(),
+            ],
+            CompilerKind::GlslangValidator => ["-V", "--stdin", "-S", stage, "-o"]
+                .into_iter()
+                .map(OsString::from)
+                .chain([output.as_os_str().to_owned()])
+                .collect(),
+        }
+    }
+}
+
+/// `program`, with the platform's executable suffix, in the first directory of `path_var`, a
+/// `PATH`, holding it.
+pub fn find_program(program: &str, path_var: &OsStr) -> Option<PathBuf> {
+    let file_name = format!("{program}{}", env::consts::EXE_SUFFIX);
+    env::split_paths(path_var)
+        .map(|directory| directory.join(&file_name))
+        .find(|candidate| candidate.is_file())
+}
+
+/// The compiler `override_path` names, a path or a program on `path_var`, otherwise the first of
+/// [`CompilerKind::SEARCHED`] on `path_var`. An override naming nothing is an error, not a reason
+/// to run another compiler.
+pub fn resolve(
+    override_path: Option<&OsStr>,
+    path_var: Option<&OsStr>,
+) -> Result<Option<GlslCompiler>, String> {
+    let path_var = path_var.unwrap_or_default();
+    if let Some(named) = override_path.filter(|named| !named.is_empty()) {
+        let as_path = Path::new(named);
+        let found = if as_path.components().count() > 1 || as_path.is_file() {
+            as_path.is_file().then(|| as_path.to_path_buf())
+        } else {
+            find_program(&named.to_string_lossy(), path_var)
+        };
+        return found
+            .map(|path| Some(GlslCompiler::new(path)))
+            .ok_or_else(|| {
+                format!(
+                    "{SHADER_COMPILER_ENV} names {}, which was not found",
+                    as_path.display()
+                )
+            });
+    }
+    Ok(CompilerKind::SEARCHED
+        .iter()
+        .find_map(|kind| find_program(kind.program(), path_var))
+        .map(GlslCompiler::new))
+}
+
+/// [`resolve`]d from the environment the first time it is asked for, the outcome logged once.
+pub fn discover() -> Option<&'static GlslCompiler> {
+    static COMPILER: OnceLock<Option<GlslCompiler>> = OnceLock::new();
+    COMPILER
+        .get_or_init(|| {
+            let override_path = env::var_os(SHADER_COMPILER_ENV);
+            match resolve(override_path.as_deref(), env::var_os("PATH").as_deref()) {
+                Ok(Some(compiler)) => {
+                    info!("Compiling shaders with {}", compiler.path.display());
+                    Some(compiler)
+                }
+                Ok(None) => {
+                    warn!(
+                        "No GLSL compiler found, install glslc or glslangValidator or set {SHADER_COMPILER_ENV}"
+                    );
+                    None
+                }
+                Err(err) => {
+                    warn!("{err}");
+                    None
+                }
+            }
+        })
+        .as_ref()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Directories standing for a `PATH` under a directory of their own, holding empty files
+    /// named after the compilers.
+    fn fake_path(name: &str) -> PathBuf {
+        let root = env::temp_dir().join(format!(
+            "pulsar_shader_compiler_{name}_{}",
+            std::process::id()
+        ));
+        for directory in ["empty", "glslang", "both"] {
+            fs::create_dir_all(root.join(directory)).unwrap();
+        }
+        for (directory, program) in [
+            ("glslang", "glslangValidator"),
+            ("both", "glslangValidator"),
+            ("both", "glslc"),
+        ] {
+            fs::write(executable(&root, directory, program), []).unwrap();
+        }
+        root
+    }
+
+    fn executable(root: &Path, directory: &str, program: &str) -> PathBuf {
+        root.join(directory)
+            .join(format!("{program}{}", env::consts::EXE_SUFFIX))
+    }
+
+    /// A `PATH` of `directories`, in that order.
+    fn path_var(root: &Path, directories: &[&str]) -> OsString {
+        env::join_paths(directories.iter().map(|directory| root.join(directory))).unwrap()
+    }
+
+    #[test]
+    fn glslc_is_preferred_on_the_path() {
+        let root = fake_path("search");
+        let cases = [
+            (vec!["empty"], None),
+            (
+                vec!["empty", "glslang"],
+                Some(("glslang", "glslangValidator")),
+            ),
+            // glslc first whatever the order of the directories
+            (vec!["glslang", "both"], Some(("both", "glslc"))),
+        ];
+        for (directories, expected) in cases {
+            let found = resolve(None, Some(&path_var(&root, &directories))).unwrap();
+            let expected = expected.map(|(directory, program)| {
+                GlslCompiler::new(executable(&root, directory, program))
+            });
+            assert_eq!(found, expected, "{directories:?}");
+        }
+        assert_eq!(resolve(None, None).unwrap(), None);
+        assert_eq!(
+            find_program("glslangValidator", &path_var(&root, &["empty", "both"])),
+            Some(executable(&root, "both", "glslangValidator"))
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn overrides_name_a_path_or_a_program() {
+        let root = fake_path("override");
+        let path = path_var(&root, &["both"]);
+        let validator = executable(&root, "glslang", "glslangValidator");
+        // A path is taken as it is, a program name looked up on the PATH
+        let named = resolve(Some(validator.as_os_str()), Some(&path)).unwrap();
+        assert_eq!(named, Some(GlslCompiler::new(validator)));
+        let program = resolve(Some("glslangValidator".as_ref()), Some(&path)).unwrap();
+        assert_eq!(
+            program.map(|compiler| compiler.path),
+            Some(executable(&root, "both", "glslangValidator"))
+        );
+        // An empty override is no override
+        let searched = resolve(Some("".as_ref()), Some(&path)).unwrap();
+        assert_eq!(
+            searched.map(|compiler| compiler.kind),
+            Some(CompilerKind::Glslc)
+        );
+        // Naming nothing does not fall back to another compiler
+        let missing = root.join("empty").join("glslc");
+        let err = resolve(Some(missing.as_os_str()), Some(&path)).unwrap_err();
+        assert!(err.contains(SHADER_COMPILER_ENV), "{err}");
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn each_compiler_gets_its_command_line() {
+        let output = Path::new("out.spv");
+        let glslc = GlslCompiler::new(PathBuf::from("/usr/bin/glslc"));
+        let validator = GlslCompiler::new(PathBuf::from("C:/VulkanSDK/Bin/glslangValidator.exe"));
+        assert_eq!(glslc.kind, CompilerKind::Glslc);
+        assert_eq!(validator.kind, CompilerKind::GlslangValidator);
+        assert!(glslc.writes_stdout());
+        assert!(!validator.writes_stdout());
+        let args = |compiler: &GlslCompiler| -> Vec<String> {
+            compiler
+                .args("comp", output)
+                .iter()
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect()
+        };
+        assert_eq!(args(&glslc), ["-fshader-stage=comp", "-", "-o", "-"]);
+        assert_eq!(
+            args(&validator),
+            ["-V", "--stdin", "-S", "comp", "-o", "out.spv"]
+        );
+    }
+}
@@ -0,0 +1,390 @@
+//! `#include` in GLSL sources, inlined before they reach the compiler.
+//!
+//! `#include "common.glsl"` is looked up next to the including file, then in the include
+//! directory, `assets/shaders` for the engine's shaders; `#include <common.glsl>` only in the
+//! include directory. A file holding `#pragma once` is inlined once per shader. Includes nested
+//! deeper than [`MAX_INCLUDE_DEPTH`] or going back to a file still being inlined are refused.
+//!
+//! The inlined source carries no `#line` directive: every line of it keeps its origin in
+//! [`Preprocessed::origins`], which compilers' diagnostics are mapped back through.
+use crate::error::PulsarError;
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Files inlined into one another at most this deep, the shader itself included.
+pub const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// File and line, counted from 1, a line of a [`Preprocessed`] source comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineOrigin {
+    pub path: PathBuf,
+    pub line: u32,
+}
+
+/// A shader with its includes inlined.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Preprocessed {
+    pub source: String,
+    /// Origin of each line of `source`.
+    pub origins: Vec<LineOrigin>,
+    /// The shader and every file it includes, each once.
+    pub files: Vec<PathBuf>,
+}
+
+impl Preprocessed {
+    /// Origin of `line` of [`Self::source`], counted from 1 as compilers do.
+    pub fn origin(&self, line: u32) -> Option<&LineOrigin> {
+        self.origins.get((line as usize).checked_sub(1)?)
+    }
+
+    /// Latest modification of the shader and its includes, `None` when one cannot be read.
+    pub fn newest_modified(&self) -> Option<SystemTime> {
+        self.files
+            .iter()
+            .map(|path| std::fs::metadata(path).and_then(|metadata| metadata.modified()))
+            .collect::<Result<Vec<_>, _>>()
+            .ok()?
+            .into_iter()
+            .max()
+    }
+}
+
+/// Inline the includes of the shader at `path`, see the module documentation.
+pub fn preprocess(path: &Path, include_dir: &Path) -> Result<Preprocessed, PulsarError> {
+    let mut preprocessed = Preprocessed::default();
+    let mut stack = Vec::new();
+    inline(path, include_dir, &mut stack, &mut preprocessed)?;
+    Ok(preprocessed)
+}
+
+/// Latest modification of the shader at `path` and its includes, `None` when they cannot all
+/// be read or inlined.
+pub fn newest_modified(path: &Path, include_dir: &Path) -> Option<SystemTime> {
+    preprocess(path, include_dir).ok()?.newest_modified()
+}
+
+/// The file an include of `requested` from `requesting` names, `None` when none exists.
+fn resolve(
+    requested: &str,
+    quoted: bool,
+    requesting: &Path,
+    include_dir: &Path,
+) -> Option<PathBuf> {
+    let beside = quoted
+        .then(|| {
+            requesting
+                .parent()
+                .map(|directory| directory.join(requested))
+        })
+        .flatten();
+    beside
+        .into_iter()
+        .chain([include_dir.join(requested)])
+        .find(|candidate| candidate.is_file())
+}
+
+/// The file named by an `#include` line and whether it is quoted, `None` for other lines.
+fn include_directive(line: &str) -> Option<Result<(&str, bool), String>> {
+    let rest = line.trim_start().strip_prefix('#')?.trim_start();
+    let rest = rest.strip_prefix("include")?.trim();
+    let name = match rest.chars().next() {
+        Some('"') => rest[1..].strip_suffix('"').map(|name| (name, true)),
+        Some('<') => rest[1..].strip_suffix('>').map(|name| (name, false)),
+        _ => None,
+    };
+    Some(name.ok_or_else(|| format!("malformed include `{}`", line.trim())))
+}
+
+fn is_pragma_once(line: &str) -> bool {
+    let mut words = line.trim_start().trim_start_matches('#').split_whitespace();
+    line.trim_start().starts_with('#')
+        && words.next() == Some("pragma")
+        && words.next() == Some("once")
+}
+
+fn inline(
+    path: &Path,
+    include_dir: &Path,
+    stack: &mut Vec<PathBuf>,
+    preprocessed: &mut Preprocessed,
+) -> Result<(), PulsarError> {
+    // Canonical so a file reached through two relative paths is still one file
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let text = std::fs::read_to_string(path).map_err(|err| PulsarError::ShaderInclude {
+        path: path.to_path_buf(),
+        line: 0,
+        reason: err.to_string(),
+    })?;
+    let once = text.lines().any(is_pragma_once);
+    let seen = preprocessed
+        .files
+        .iter()
+        .any(|file| file.canonicalize().is_ok_and(|file| file == canonical));
+    if once && seen {
+        return Ok(());
+    }
+    if !seen {
+        preprocessed.files.push(path.to_path_buf());
+    }
+    stack.push(canonical);
+
+    for (index, line) in text.lines().enumerate() {
+        let number = index as u32 + 1;
+        let error = |reason: String| PulsarError::ShaderInclude {
+            path: path.to_path_buf(),
+            line: number,
+            reason,
+        };
+        if is_pragma_once(line) {
+            // Kept as an empty line so the lines after it keep their numbers
+            preprocessed.source.push('\n');
+        } else if let Some(directive) = include_directive(line) {
+            let (requested, quoted) = directive.map_err(error)?;
+            let include = resolve(requested, quoted, path, include_dir)
+                .ok_or_else(|| error(format!("cannot find `{requested}`")))?;
+            let include_canonical = include.canonicalize().unwrap_or_else(|_| include.clone());
+            if let Some(start) = stack.iter().position(|file| *file == include_canonical) {
+                let cycle: Vec<String> = stack[start..]
+                    .iter()
+                    .chain([&include_canonical])
+                    .map(|file| {
+                        file.file_name()
+                            .unwrap_or_default()
+                            .to_string_lossy()
+                            .into()
+                    })
+                    .collect();
+                return Err(error(format!("include cycle {}", cycle.join(" -> "))));
+            }
+            if stack.len() >= MAX_INCLUDE_DEPTH {
+                return Err(error(format!(
+                    "includes nested deeper than {MAX_INCLUDE_DEPTH} files"
+                )));
+            }
+            inline(&include, include_dir, stack, preprocessed)?;
+            continue;
+        } else {
+            preprocessed.source.push_str(line);
+            preprocessed.source.push('\n');
+        }
+        preprocessed.origins.push(LineOrigin {
+            path: path.to_path_buf(),
+            line: number,
+        });
+    }
+    stack.pop();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        fs::{self, File},
+        time::Duration,
+    };
+
+    /// Files of one test, `shader/` holding the shaders and `include/` the include directory.
+    struct Sources {
+        root: PathBuf,
+    }
+
+    impl Sources {
+        fn new(name: &str) -> Self {
+            let root = std::env::temp_dir().join(format!(
+                "pulsar_shader_include_{name}_{}",
+                std::process::id()
+            ));
+            fs::create_dir_all(root.join("shader")).unwrap();
+            fs::create_dir_all(root.join("include")).unwrap();
+            Self { root }
+        }
+
+        fn write(&self, path: &str, lines: &[&str]) -> PathBuf {
+            let path = self.root.join(path);
+            fs::write(&path, lines.join("\n") + "\n").unwrap();
+            path
+        }
+
+        fn include_dir(&self) -> PathBuf {
+            self.root.join("include")
+        }
+
+        fn preprocess(&self, path: &Path) -> Result<Preprocessed, PulsarError> {
+            preprocess(path, &self.include_dir())
+        }
+    }
+
+    fn assert_refused(
+        result: Result<Preprocessed, PulsarError>,
+        path: &Path,
+        line: u32,
+        reason: &str,
+    ) {
+        match result {
+            Err(PulsarError::ShaderInclude {
+                path: at,
+                line: at_line,
+                reason: message,
+            }) => {
+                assert_eq!((at.as_path(), at_line), (path, line), "{message}");
+                assert!(message.contains(reason), "{message}");
+            }
+            other => panic!(
+                "expected `{reason}` at {}:{line}, got {other:?}",
+                path.display()
+            ),
+        }
+    }
+
+    /// Includes inlined in place, a `#pragma once` file only once, each line traced to its file.
+    #[test]
+    fn includes_are_inlined_once_and_traced_to_their_file() {
+        let sources = Sources::new("inlining");
+        let common = sources.write(
+            "include/common.glsl",
+            &["#pragma once", "struct Light { vec3 dir; };"],
+        );
+        let lighting = sources.write(
+            "include/lighting.glsl",
+            &[
+                "#include \"common.glsl\"",
+                "float lambert(Light l, vec3 n) { return max(dot(n, l.dir), 0.0); }",
+            ],
+        );
+        // Quoted, a file next to the shader comes before the include directory's
+        let local = sources.write("shader/local.glsl", &["const float AMBIENT = 0.1;"]);
+        sources.write(
+            "include/local.glsl",
+            &["#error the shader's copy comes first"],
+        );
+        let shader = sources.write(
+            "shader/lit.frag",
+            &[
+                "#version 450",
+                "#include \"common.glsl\"",
+                "  #  include <lighting.glsl>",
+                "#include \"local.glsl\"",
+                "void main() {}",
+            ],
+        );
+
+        let preprocessed = sources.preprocess(&shader).unwrap();
+        let expected_source = [
+            "#version 450",
+            "",
+            "struct Light { vec3 dir; };",
+            "float lambert(Light l, vec3 n) { return max(dot(n, l.dir), 0.0); }",
+            "const float AMBIENT = 0.1;",
+            "void main() {}",
+        ]
+        .join("\n")
+            + "\n";
+        assert_eq!(preprocessed.source, expected_source);
+        let origin = |path: &PathBuf, line| LineOrigin {
+            path: path.clone(),
+            line,
+        };
+        let expected_origins = [
+            origin(&shader, 1),
+            origin(&common, 1),
+            origin(&common, 2),
+            origin(&lighting, 2),
+            origin(&local, 1),
+            origin(&shader, 5),
+        ];
+        assert_eq!(preprocessed.origins, expected_origins);
+        // Compilers count lines from 1
+        assert_eq!(preprocessed.origin(4), Some(&expected_origins[3]));
+        assert_eq!(preprocessed.origin(0), None);
+        assert_eq!(preprocessed.files, [shader, common, lighting, local]);
+        fs::remove_dir_all(&sources.root).unwrap();
+    }
+
+    /// Touching an include makes the shader newer than a binary built before.
+    #[test]
+    fn edited_includes_make_the_shader_stale() {
+        let sources = Sources::new("staleness");
+        let common = sources.write("include/common.glsl", &["const float AMBIENT = 0.1;"]);
+        let shader = sources.write("shader/lit.frag", &["#include <common.glsl>"]);
+        let built = SystemTime::now() - Duration::from_secs(60);
+        for path in [&shader, &common] {
+            File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(built - Duration::from_secs(60))
+                .unwrap();
+        }
+        let newest = || newest_modified(&shader, &sources.include_dir()).unwrap();
+        assert!(newest() <= built);
+        File::options()
+            .write(true)
+            .open(&common)
+            .unwrap()
+            .set_modified(SystemTime::now())
+            .unwrap();
+        assert!(newest() > built);
+        fs::remove_dir_all(&sources.root).unwrap();
+    }
+
+    #[test]
+    fn cycles_and_deep_chains_are_refused() {
+        let sources = Sources::new("nesting");
+        sources.write("shader/a.glsl", &["// a", "#include \"b.glsl\""]);
+        let second = sources.write("shader/b.glsl", &["#include \"a.glsl\""]);
+        let shader = sources.write(
+            "shader/cycle.frag",
+            &["#version 450", "#include \"a.glsl\""],
+        );
+        assert_refused(
+            sources.preprocess(&shader),
+            &second,
+            1,
+            "a.glsl -> b.glsl -> a.glsl",
+        );
+
+        // A chain of distinct files one too deep
+        for depth in 0..MAX_INCLUDE_DEPTH {
+            sources.write(
+                &format!("include/{depth}.glsl"),
+                &[&format!("#include <{}.glsl>", depth + 1)],
+            );
+        }
+        sources.write(&format!("include/{MAX_INCLUDE_DEPTH}.glsl"), &["// bottom"]);
+        let deep = sources.write("shader/deep.frag", &["#include <1.glsl>"]);
+        let last = sources
+            .include_dir()
+            .join(format!("{}.glsl", MAX_INCLUDE_DEPTH - 1));
+        assert_refused(sources.preprocess(&deep), &last, 1, "nested deeper");
+        let shallow = sources.write("shader/shallow.frag", &["#include <2.glsl>"]);
+        sources.preprocess(&shallow).unwrap();
+        fs::remove_dir_all(&sources.root).unwrap();
+    }
+
+    #[test]
+    fn missing_and_malformed_includes_are_refused() {
+        let sources = Sources::new("errors");
+        sources.write("shader/a.glsl", &["// a"]);
+        let missing = sources.write("shader/missing.frag", &["", "", "#include <nowhere.glsl>"]);
+        assert_refused(
+            sources.preprocess(&missing),
+            &missing,
+            3,
+            "cannot find `nowhere.glsl`",
+        );
+        // Angled includes skip the shader's directory
+        let angled = sources.write("shader/angled.frag", &["#include <a.glsl>"]);
+        assert_refused(sources.preprocess(&angled), &angled, 1, "cannot find");
+        let malformed = sources.write("shader/malformed.frag", &["#include common.glsl"]);
+        assert_refused(
+            sources.preprocess(&malformed),
+            &malformed,
+            1,
+            "malformed include",
+        );
+        fs::remove_dir_all(&sources.root).unwrap();
+    }
+}
@@ -0,0 +1,822 @@
+//! Descriptors and push constants a SPIR-V module declares, read from its decorations and types.
+//!
+//! [`reflect`] finds every variable of a descriptor or push constant storage class, its set and
+//! binding, the descriptor type its type makes and the bytes its push constant block spans, from
+//! its first member to the end of its last, so stages can read their own part of the block. The
+//! interfaces of the stages drawing with one pipeline layout are [`ShaderInterface::merge`]d, the
+//! layout is built from the union and checked against what the renderer writes with
+//! [`ShaderInterface::unwritten`]. Only what `descriptor_set` can bind is understood: buffers,
+//! images, samplers and their fixed size arrays.
+use ash::vk;
+use std::collections::HashMap;
+
+const MAGIC: u32 = 0x0723_0203;
+const HEADER_WORDS: usize = 5;
+
+const OP_NAME: u32 = 5;
+const OP_TYPE_BOOL: u32 = 20;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLER: u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+
+const DECORATION_BLOCK: u32 = 2;
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+
+const STORAGE_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_UNIFORM: u32 = 2;
+const STORAGE_PUSH_CONSTANT: u32 = 9;
+const STORAGE_STORAGE_BUFFER: u32 = 12;
+
+const DIM_BUFFER: u32 = 5;
+const DIM_SUBPASS_DATA: u32 = 6;
+
+/// A descriptor declared by one or more stages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReflectedBinding {
+    pub set: u32,
+    pub binding: u32,
+    pub descriptor_type: vk::DescriptorType,
+    /// Elements of a descriptor array, 1 otherwise.
+    pub count: u32,
+    pub stages: vk::ShaderStageFlags,
+    /// Name of the variable or its block, empty when the module was stripped.
+    pub name: String,
+}
+
+/// What the stages of a pipeline read through descriptors and push constants.
+#[derive(Debug, Clone, Default)]
+pub struct ShaderInterface {
+    /// By set then binding.
+    pub bindings: Vec<ReflectedBinding>,
+    /// A range per stage reading push constants, from the first member its block declares to the
+    /// end of the last. Stages merged in a range cover the bytes any of them reads.
+    pub push_constants: Vec<vk::PushConstantRange>,
+}
+
+impl ShaderInterface {
+    pub fn binding(&self, set: u32, binding: u32) -> Option<&ReflectedBinding> {
+        self.bindings
+            .iter()
+            .find(|reflected| reflected.set == set && reflected.binding == binding)
+    }
+
+    /// Add the descriptors and push constants of another stage, or of the same one compiled
+    /// differently. A binding declared with two types or counts is refused, so is a stage
+    /// reading from two push constant ranges.
+    pub fn merge(&mut self, other: &ShaderInterface) -> Result<(), String> {
+        for reflected in &other.bindings {
+            match self
+                .bindings
+                .iter_mut()
+                .find(|known| known.set == reflected.set && known.binding == reflected.binding)
+            {
+                Some(known)
+                    if known.descriptor_type != reflected.descriptor_type
+                        || known.count != reflected.count =>
+                {
+                    return Err(format!(
+                        "set {} binding {} is {} for {:?} and {} for {:?}",
+                        reflected.set,
+                        reflected.binding,
+                        describe(known.descriptor_type, known.count),
+                        known.stages,
+                        describe(reflected.descriptor_type, reflected.count),
+                        reflected.stages
+                    ));
+                }
+                Some(known) => known.stages |= reflected.stages,
+                None => self.bindings.push(reflected.clone()),
+            }
+        }
+        self.bindings
+            .sort_unstable_by_key(|reflected| (reflected.set, reflected.binding));
+        for &range in &other.push_constants {
+            match self
+                .push_constants
+                .iter_mut()
+                .find(|known| known.stage_flags.intersects(range.stage_flags))
+            {
+                Some(known) if known.stage_flags != range.stage_flags => {
+                    return Err(format!(
+                        "push constants of {:?} are in the range of {:?} and of {:?}",
+                        known.stage_flags & range.stage_flags,
+                        known.stage_flags,
+                        range.stage_flags
+                    ));
+                }
+                Some(known) => {
+                    let end = (known.offset + known.size).max(range.offset + range.size);
+                    known.offset = known.offset.min(range.offset);
+                    known.size = end - known.offset;
+                }
+                None => self.push_constants.push(range),
+            }
+        }
+        self.push_constants
+            .sort_unstable_by_key(|range| range.offset);
+        Ok(())
+    }
+
+    /// Layout bindings of `set`, in binding order.
+    pub fn set_layout_bindings(&self, set: u32) -> Vec<vk::DescriptorSetLayoutBinding<'static>> {
+        self.bindings
+            .iter()
+            .filter(|reflected| reflected.set == set)
+            .map(|reflected| vk::DescriptorSetLayoutBinding {
+                binding: reflected.binding,
+                descriptor_type: reflected.descriptor_type,
+                descriptor_count: reflected.count,
+                stage_flags: reflected.stages,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    /// What the stages read that the renderer does not write: descriptors missing from
+    /// `written` or of another type there, push constants outside the `pushed` range of their
+    /// stage or read from a stage none covers. One line each, empty when everything read is
+    /// written.
+    pub fn unwritten(
+        &self,
+        written: &[(u32, u32, vk::DescriptorType)],
+        pushed: &[vk::PushConstantRange],
+    ) -> Vec<String> {
+        let mut problems = Vec::new();
+        for reflected in &self.bindings {
+            let at = format!(
+                "{} at set {} binding {}{}",
+                describe(reflected.descriptor_type, reflected.count),
+                reflected.set,
+                reflected.binding,
+                named(&reflected.name)
+            );
+            match written
+                .iter()
+                .find(|&&(set, binding, _)| set == reflected.set && binding == reflected.binding)
+            {
+                None => problems.push(format!("reads {at}, which the renderer never writes")),
+                Some(&(_, _, descriptor_type)) if descriptor_type != reflected.descriptor_type => {
+                    problems.push(format!(
+                        "reads {at}, the renderer writes a {descriptor_type:?} there"
+                    ))
+                }
+                Some(_) if reflected.count != 1 => problems.push(format!(
+                    "reads {at}, the renderer writes a single descriptor there"
+                )),
+                Some(_) => {}
+            }
+        }
+        let pushed_stages = pushed
+            .iter()
+            .fold(vk::ShaderStageFlags::empty(), |stages, range| {
+                stages | range.stage_flags
+            });
+        for read in &self.push_constants {
+            let bytes = |range: &vk::PushConstantRange| {
+                format!("bytes {}..{}", range.offset, range.offset + range.size)
+            };
+            if pushed.is_empty() {
+                problems.push(format!(
+                    "reads push constant {}, the renderer pushes none",
+                    bytes(read)
+                ));
+                continue;
+            }
+            if !pushed_stages.contains(read.stage_flags) {
+                problems.push(format!(
+                    "reads push constants from {:?}, the renderer pushes them to {:?}",
+                    read.stage_flags, pushed_stages
+                ));
+                continue;
+            }
+            for range in pushed
+                .iter()
+                .filter(|range| range.stage_flags.intersects(read.stage_flags))
+            {
+                if read.offset < range.offset || read.offset + read.size > range.offset + range.size
+                {
+                    problems.push(format!(
+                        "reads push constant {} from {:?}, the renderer pushes {}",
+                        bytes(read),
+                        range.stage_flags & read.stage_flags,
+                        bytes(range)
+                    ));
+                }
+            }
+        }
+        problems
+    }
+}
+
+fn describe(descriptor_type: vk::DescriptorType, count: u32) -> String {
+    match count {
+        1 => format!("a {descriptor_type:?}"),
+        count => format!("an array of {count} {descriptor_type:?}"),
+    }
+}
+
+fn named(name: &str) -> String {
+    if name.is_empty() {
+        String::new()
+    } else {
+        format!(" (`{name}`)")
+    }
+}
+
+/// Types and decorations of a module, by result id.
+#[derive(Default)]
+struct Module {
+    names: HashMap<u32, String>,
+    /// Opcode and operands after the result id.
+    types: HashMap<u32, (u32, Vec<u32>)>,
+    constants: HashMap<u32, u32>,
+    /// Decoration and its first operand, 0 when it has none.
+    decorations: HashMap<u32, Vec<(u32, u32)>>,
+    /// By struct id and member index.
+    member_decorations: HashMap<(u32, u32), Vec<(u32, u32)>>,
+    /// Pointer type and storage class.
+    variables: Vec<(u32, u32, u32)>,
+}
+
+impl Module {
+    fn decoration(&self, id: u32, decoration: u32) -> Option<u32> {
+        self.decorations
+            .get(&id)?
+            .iter()
+            .find(|&&(known, _)| known == decoration)
+            .map(|&(_, value)| value)
+    }
+
+    fn member_decoration(&self, id: u32, member: u32, decoration: u32) -> Option<u32> {
+        self.member_decorations
+            .get(&(id, member))?
+            .iter()
+            .find(|&&(known, _)| known == decoration)
+            .map(|&(_, value)| value)
+    }
+
+    fn ty(&self, id: u32) -> Result<(u32, &[u32]), String> {
+        self.types
+            .get(&id)
+            .map(|(opcode, operands)| (*opcode, operands.as_slice()))
+            .ok_or_else(|| format!("type %{id} is not declared"))
+    }
+
+    fn name(&self, id: u32) -> String {
+        self.names.get(&id).cloned().unwrap_or_default()
+    }
+
+    /// Descriptor type of a variable of type `id` and its count.
+    fn descriptor(&self, id: u32, storage_class: u32) -> Result<(vk::DescriptorType, u32), String> {
+        let (opcode, operands) = self.ty(id)?;
+        match (opcode, operands) {
+            (OP_TYPE_ARRAY, &[element, length, ..]) => {
+                let (descriptor_type, count) = self.descriptor(element, storage_class)?;
+                let length = *self
+                    .constants
+                    .get(&length)
+                    .ok_or("descriptor array sized by a specialization constant")?;
+                Ok((descriptor_type, count * length))
+            }
+            (OP_TYPE_RUNTIME_ARRAY, _) => Err("unsized descriptor arrays are not supported".into()),
+            (OP_TYPE_SAMPLED_IMAGE, _) => Ok((vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 1)),
+            (OP_TYPE_SAMPLER, _) => Ok((vk::DescriptorType::SAMPLER, 1)),
+            (OP_TYPE_IMAGE, &[_, dim, _, _, _, sampled, ..]) => {
+                let descriptor_type = match (dim, sampled) {
+                    (DIM_SUBPASS_DATA, _) => vk::DescriptorType::INPUT_ATTACHMENT,
+                    (DIM_BUFFER, 2) => vk::DescriptorType::STORAGE_TEXEL_BUFFER,
+                    (DIM_BUFFER, _) => vk::DescriptorType::UNIFORM_TEXEL_BUFFER,
+                    (_, 2) => vk::DescriptorType::STORAGE_IMAGE,
+                    _ => vk::DescriptorType::SAMPLED_IMAGE,
+                };
+                Ok((descriptor_type, 1))
+            }
+            (OP_TYPE_STRUCT, _) if storage_class == STORAGE_STORAGE_BUFFER => {
+                Ok((vk::DescriptorType::STORAGE_BUFFER, 1))
+            }
+            (OP_TYPE_STRUCT, _) if self.decoration(id, DECORATION_BUFFER_BLOCK).is_some() => {
+                Ok((vk::DescriptorType::STORAGE_BUFFER, 1))
+            }
+            (OP_TYPE_STRUCT, _) if self.decoration(id, DECORATION_BLOCK).is_some() => {
+                Ok((vk::DescriptorType::UNIFORM_BUFFER, 1))
+            }
+            _ => Err(format!("%{id} is not a type a descriptor can bind")),
+        }
+    }
+
+    /// Offset of the first member of the struct `id`, 0 for any other type.
+    fn start(&self, id: u32) -> Result<u32, String> {
+        match self.ty(id)? {
+            (OP_TYPE_STRUCT, members) => Ok((0..members.len() as u32)
+                .filter_map(|member| self.member_decoration(id, member, DECORATION_OFFSET))
+                .min()
+                .unwrap_or(0)),
+            _ => Ok(0),
+        }
+    }
+
+    /// Bytes a value of type `id` spans, `matrix_stride` from the member holding it.
+    fn size(&self, id: u32, matrix_stride: Option<u32>) -> Result<u32, String> {
+        let (opcode, operands) = self.ty(id)?;
+        match (opcode, operands) {
+            (OP_TYPE_BOOL, _) => Ok(4),
+            (OP_TYPE_INT | OP_TYPE_FLOAT, &[width, ..]) => Ok(width / 8),
+            (OP_TYPE_VECTOR, &[component, count]) => Ok(self.size(component, None)? * count),
+            (OP_TYPE_MATRIX, &[column, columns]) => match matrix_stride {
+                Some(stride) => Ok(stride * columns),
+                None => Ok(self.size(column, None)? * columns),
+            },
+            (OP_TYPE_ARRAY, &[element, length]) => {
+                let length = *self
+                    .constants
+                    .get(&length)
+                    .ok_or("array sized by a specialization constant")?;
+                let stride = match self.decoration(id, DECORATION_ARRAY_STRIDE) {
+                    Some(stride) => stride,
+                    None => self.size(element, matrix_stride)?,
+                };
+                Ok(stride * length)
+            }
+            (OP_TYPE_STRUCT, members) => {
+                let mut end = 0;
+                for (member, &member_type) in members.iter().enumerate() {
+                    let member = member as u32;
+                    let offset = self
+                        .member_decoration(id, member, DECORATION_OFFSET)
+                        .ok_or_else(|| format!("member {member} of %{id} has no offset"))?;
+                    let stride = self.member_decoration(id, member, DECORATION_MATRIX_STRIDE);
+                    end = end.max(offset + self.size(member_type, stride)?);
+                }
+                Ok(end)
+            }
+            _ => Err(format!("%{id} has no size known to the reflection")),
+        }
+    }
+}
+
+/// Descriptors and push constants `code` declares, all read by `stage`.
+pub fn reflect(code: &[u32], stage: vk::ShaderStageFlags) -> Result<ShaderInterface, String> {
+    if code.len() < HEADER_WORDS || code[0] != MAGIC {
+        return Err("not a SPIR-V module".into());
+    }
+    let mut module = Module::default();
+    let mut words = &code[HEADER_WORDS..];
+    while let Some(&first) = words.first() {
+        let count = (first >> 16) as usize;
+        if count == 0 || count > words.len() {
+            return Err("malformed SPIR-V, an instruction runs past the end".into());
+        }
+        let (instruction, rest) = words.split_at(count);
+        words = rest;
+        let opcode = first & 0xffff;
+        let operands = &instruction[1..];
+        match (opcode, operands) {
+            (OP_NAME, &[id, ref name @ ..]) => {
+                let bytes: Vec<u8> = name.iter().flat_map(|word| word.to_le_bytes()).collect();
+                let end = bytes
+                    .iter()
+                    .position(|&byte| byte == 0)
+                    .unwrap_or(bytes.len());
+                module
+                    .names
+                    .insert(id, String::from_utf8_lossy(&bytes[..end]).into_owned());
+            }
+            (OP_TYPE_BOOL..=OP_TYPE_POINTER, &[id, ref rest @ ..]) => {
+                module.types.insert(id, (opcode, rest.to_vec()));
+            }
+            (OP_CONSTANT, &[_, id, value, ..]) => {
+                module.constants.insert(id, value);
+            }
+            (OP_VARIABLE, &[pointer, id, storage_class, ..]) => {
+                module.variables.push((pointer, id, storage_class));
+            }
+            (OP_DECORATE, &[id, decoration, ref value @ ..]) => {
+                module
+                    .decorations
+                    .entry(id)
+                    .or_default()
+                    .push((decoration, value.first().copied().unwrap_or(0)));
+            }
+            (OP_MEMBER_DECORATE, &[id, member, decoration, ref value @ ..]) => {
+                module
+                    .member_decorations
+                    .entry((id, member))
+                    .or_default()
+                    .push((decoration, value.first().copied().unwrap_or(0)));
+            }
+            _ => {}
+        }
+    }
+
+    let mut interface = ShaderInterface::default();
+    for &(pointer, id, storage_class) in &module.variables {
+        let pointee = match module.ty(pointer)? {
+            (OP_TYPE_POINTER, &[_, pointee]) => pointee,
+            _ => return Err(format!("variable %{id} is not a pointer")),
+        };
+        match storage_class {
+            STORAGE_PUSH_CONSTANT => {
+                let offset = module.start(pointee)?;
+                let size = module.size(pointee, None)? - offset;
+                interface.merge(&ShaderInterface {
+                    bindings: Vec::new(),
+                    push_constants: vec![vk::PushConstantRange {
+                        stage_flags: stage,
+                        offset,
+                        size,
+                    }],
+                })?;
+            }
+            STORAGE_UNIFORM_CONSTANT | STORAGE_UNIFORM | STORAGE_STORAGE_BUFFER => {
+                // Without a binding it is no descriptor, without a set it lands in set 0 like GLSL
+                let Some(binding) = module.decoration(id, DECORATION_BINDING) else {
+                    continue;
+                };
+                let set = module
+                    .decoration(id, DECORATION_DESCRIPTOR_SET)
+                    .unwrap_or(0);
+                let (descriptor_type, count) = module.descriptor(pointee, storage_class)?;
+                // Blocks are named after their type, the variable of an anonymous block is not
+                let name = match module.name(id) {
+                    name if name.is_empty() => {
+                        let element = match module.ty(pointee)? {
+                            (OP_TYPE_ARRAY, &[element, ..]) => element,
+                            _ => pointee,
+                        };
+                        module.name(element)
+                    }
+                    name => name,
+                };
+                interface.merge(&ShaderInterface {
+                    bindings: vec![ReflectedBinding {
+                        set,
+                        binding,
+                        descriptor_type,
+                        count,
+                        stages: stage,
+                        name,
+                    }],
+                    push_constants: Vec::new(),
+                })?;
+            }
+            _ => {}
+        }
+    }
+    Ok(interface)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// SPIR-V assembled one instruction at a time, after a header of bound 64.
+    struct Assembler {
+        words: Vec<u32>,
+    }
+
+    impl Assembler {
+        fn new() -> Self {
+            Self {
+                words: vec![0x0723_0203, 0x0001_0000, 0, 64, 0],
+            }
+        }
+
+        fn op(mut self, opcode: u32, operands: &[u32]) -> Self {
+            self.words
+                .push(((operands.len() as u32 + 1) << 16) | opcode);
+            self.words.extend_from_slice(operands);
+            self
+        }
+
+        fn name(self, id: u32, name: &str) -> Self {
+            let mut bytes = name.as_bytes().to_vec();
+            bytes.resize(name.len() / 4 * 4 + 4, 0);
+            let mut operands = vec![id];
+            operands.extend(
+                bytes
+                    .chunks_exact(4)
+                    .map(|word| u32::from_le_bytes(word.try_into().unwrap())),
+            );
+            self.op(5, &operands)
+        }
+
+        fn decorate(self, id: u32, decoration: &[u32]) -> Self {
+            self.op(71, &[&[id], decoration].concat())
+        }
+
+        fn member_decorate(self, id: u32, member: u32, decoration: &[u32]) -> Self {
+            self.op(72, &[&[id, member], decoration].concat())
+        }
+
+        fn variable(self, pointer: u32, id: u32, storage_class: u32) -> Self {
+            self.op(59, &[pointer, id, storage_class])
+        }
+
+        fn set_binding(self, id: u32, set: u32, binding: u32) -> Self {
+            self.decorate(id, &[34, set]).decorate(id, &[33, binding])
+        }
+    }
+
+    /// Floats, vectors, a `mat4` and the `uint` constant 4 every module below shares.
+    fn scalars() -> Assembler {
+        Assembler::new()
+            .op(22, &[1, 32])
+            .op(23, &[2, 1, 4])
+            .op(24, &[3, 2, 4])
+            .op(21, &[9, 32, 0])
+            .op(43, &[9, 10, 4])
+    }
+
+    /// A push constant block of `members`, offsets in bytes, as `%18` pointed to by `%19`.
+    fn push_block(assembler: Assembler, members: &[(u32, u32)]) -> Assembler {
+        let types: Vec<u32> = members.iter().map(|&(ty, _)| ty).collect();
+        let mut assembler = assembler.op(30, &[&[18], types.as_slice()].concat());
+        for (member, &(ty, offset)) in members.iter().enumerate() {
+            assembler = assembler.member_decorate(18, member as u32, &[35, offset]);
+            if ty == 3 {
+                assembler = assembler.member_decorate(18, member as u32, &[7, 16]);
+            }
+        }
+        assembler
+            .decorate(18, &[2])
+            .op(32, &[19, STORAGE_PUSH_CONSTANT, 18])
+            .variable(19, 20, STORAGE_PUSH_CONSTANT)
+    }
+
+    /// A uniform block, an array of four combined image samplers, a storage buffer of `vec4`s and
+    /// a push constant block of a `mat4` and a `vec4`.
+    fn vertex_module() -> Vec<u32> {
+        let module = scalars()
+            .name(4, "Globals")
+            .name(6, "ubo")
+            .name(13, "samplers")
+            .name(15, "Bones")
+            // set 0 binding 0, the uniform block
+            .op(30, &[4, 3])
+            .decorate(4, &[2])
+            .member_decorate(4, 0, &[35, 0])
+            .member_decorate(4, 0, &[7, 16])
+            .op(32, &[5, STORAGE_UNIFORM, 4])
+            .variable(5, 6, STORAGE_UNIFORM)
+            .set_binding(6, 0, 0)
+            // set 1 binding 2, sampler2D[4]
+            .op(25, &[7, 1, 1, 0, 0, 0, 1, 0])
+            .op(27, &[8, 7])
+            .op(28, &[11, 8, 10])
+            .op(32, &[12, STORAGE_UNIFORM_CONSTANT, 11])
+            .variable(12, 13, STORAGE_UNIFORM_CONSTANT)
+            .set_binding(13, 1, 2)
+            // set 2 binding 0, an anonymous storage block named after its type
+            .op(29, &[14, 2])
+            .decorate(14, &[6, 16])
+            .op(30, &[15, 14])
+            .decorate(15, &[2])
+            .member_decorate(15, 0, &[35, 0])
+            .op(32, &[16, STORAGE_STORAGE_BUFFER, 15])
+            .variable(16, 17, STORAGE_STORAGE_BUFFER)
+            .set_binding(17, 2, 0);
+        push_block(module, &[(3, 0), (2, 64)]).words
+    }
+
+    /// The samplers of [`vertex_module`] and a push constant block of a single `vec4`, after the
+    /// vertex stage's.
+    fn fragment_module() -> Vec<u32> {
+        let module = scalars()
+            .op(25, &[7, 1, 1, 0, 0, 0, 1, 0])
+            .op(27, &[8, 7])
+            .op(28, &[11, 8, 10])
+            .op(32, &[12, STORAGE_UNIFORM_CONSTANT, 11])
+            .variable(12, 13, STORAGE_UNIFORM_CONSTANT)
+            .set_binding(13, 1, 2);
+        push_block(module, &[(2, 80)]).words
+    }
+
+    #[test]
+    fn descriptors_and_push_constants_are_reflected() {
+        let interface = reflect(&vertex_module(), vk::ShaderStageFlags::VERTEX).unwrap();
+        let found: Vec<_> = interface
+            .bindings
+            .iter()
+            .map(|binding| {
+                (
+                    binding.set,
+                    binding.binding,
+                    binding.descriptor_type,
+                    binding.count,
+                    binding.name.as_str(),
+                )
+            })
+            .collect();
+        let expected = [
+            (0, 0, vk::DescriptorType::UNIFORM_BUFFER, 1, "ubo"),
+            (
+                1,
+                2,
+                vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                4,
+                "samplers",
+            ),
+            (2, 0, vk::DescriptorType::STORAGE_BUFFER, 1, "Bones"),
+        ];
+        assert_eq!(found, expected);
+        let &[push] = interface.push_constants.as_slice() else {
+            panic!("push constants reflected as {:?}", interface.push_constants);
+        };
+        assert_eq!(
+            (push.stage_flags, push.offset, push.size),
+            (vk::ShaderStageFlags::VERTEX, 0, 80)
+        );
+    }
+
+    #[test]
+    fn merged_stages_share_their_descriptors() {
+        let vertex = reflect(&vertex_module(), vk::ShaderStageFlags::VERTEX).unwrap();
+        let fragment = reflect(&fragment_module(), vk::ShaderStageFlags::FRAGMENT).unwrap();
+        let mut merged = vertex.clone();
+        merged.merge(&fragment).unwrap();
+        let both = vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT;
+        assert_eq!(merged.bindings.len(), 3);
+        assert_eq!(merged.binding(1, 2).unwrap().stages, both);
+        let layout = merged.set_layout_bindings(1);
+        assert_eq!(layout.len(), 1);
+        assert_eq!(layout[0].descriptor_count, 4);
+        assert_eq!(layout[0].stage_flags, both);
+        // No stage declares set 3
+        assert!(merged.set_layout_bindings(3).is_empty());
+
+        // The same binding as a uniform block in one stage and samplers in the other
+        let mut conflict = fragment;
+        conflict.bindings[0].set = 0;
+        conflict.bindings[0].binding = 0;
+        let err = vertex.clone().merge(&conflict).unwrap_err();
+        assert!(err.contains("set 0 binding 0"), "{err}");
+    }
+
+    #[test]
+    fn push_constant_ranges_are_kept_per_stage() {
+        let vertex = reflect(&vertex_module(), vk::ShaderStageFlags::VERTEX).unwrap();
+        let fragment = reflect(&fragment_module(), vk::ShaderStageFlags::FRAGMENT).unwrap();
+        let mut merged = vertex.clone();
+        merged.merge(&fragment).unwrap();
+        // A range per stage, the fragment stage's from the first byte its block declares
+        let ranges: Vec<_> = merged
+            .push_constants
+            .iter()
+            .map(|range| (range.stage_flags, range.offset, range.size))
+            .collect();
+        assert_eq!(
+            ranges,
+            [
+                (vk::ShaderStageFlags::VERTEX, 0, 80),
+                (vk::ShaderStageFlags::FRAGMENT, 80, 16),
+            ]
+        );
+
+        // Two vertex modules share a range covering both, a stage in two ranges is refused
+        let mut vertices = vertex;
+        vertices
+            .merge(&ShaderInterface {
+                bindings: Vec::new(),
+                push_constants: vec![vk::PushConstantRange {
+                    stage_flags: vk::ShaderStageFlags::VERTEX,
+                    offset: 64,
+                    size: 64,
+                }],
+            })
+            .unwrap();
+        assert_eq!(vertices.push_constants[0].size, 128);
+        let shared = ShaderInterface {
+            bindings: Vec::new(),
+            push_constants: vec![vk::PushConstantRange {
+                stage_flags: vk::ShaderStageFlags::VERTEX | vk::ShaderStageFlags::FRAGMENT,
+                offset: 0,
+                size: 16,
+            }],
+        };
+        assert!(merged.merge(&shared).is_err());
+    }
+
+    #[test]
+    fn mismatches_with_what_the_renderer_writes_are_reported() {
+        let vertex = reflect(&vertex_module(), vk::ShaderStageFlags::VERTEX).unwrap();
+        let written = [
+            (0, 0, vk::DescriptorType::UNIFORM_BUFFER),
+            (1, 2, vk::DescriptorType::COMBINED_IMAGE_SAMPLER),
+            (2, 0, vk::DescriptorType::STORAGE_BUFFER),
+        ];
+        let pushed = [vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::VERTEX,
+            offset: 0,
+            size: 128,
+        }];
+        // Written as declared but for the sampler array
+        let problems = vertex.unwritten(&written, &pushed);
+        assert_eq!(problems.len(), 1, "{problems:?}");
+        assert!(problems[0].contains("single descriptor"), "{problems:?}");
+
+        // Binding 1 of set 0 missing, a uniform buffer where the storage buffer is read, 64 bytes
+        // pushed to the vertex stage and 16 to the fragment stage, before its block
+        let written = [
+            (0, 1, vk::DescriptorType::UNIFORM_BUFFER),
+            (2, 0, vk::DescriptorType::UNIFORM_BUFFER),
+        ];
+        let vertex_range = vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::VERTEX,
+            offset: 0,
+            size: 64,
+        };
+        let pushed = [
+            vertex_range,
+            vk::PushConstantRange {
+                stage_flags: vk::ShaderStageFlags::FRAGMENT,
+                offset: 64,
+                size: 16,
+            },
+        ];
+        let fragment = reflect(&fragment_module(), vk::ShaderStageFlags::FRAGMENT).unwrap();
+        let samplers = [(1, 2, vk::DescriptorType::COMBINED_IMAGE_SAMPLER)];
+        let mut problems = vertex.unwritten(&written, &pushed);
+        problems.extend(fragment.unwritten(&samplers, &pushed));
+        problems.extend(fragment.unwritten(&samplers, &[vertex_range]));
+        let expected = [
+            "set 0 binding 0 (`ubo`), which the renderer never writes",
+            "set 1 binding 2 (`samplers`), which the renderer never writes",
+            "set 2 binding 0 (`Bones`), the renderer writes a UNIFORM_BUFFER there",
+            "reads push constant bytes 0..80 from VERTEX, the renderer pushes bytes 0..64",
+            "set 1 binding 2, the renderer writes a single descriptor there",
+            "reads push constant bytes 80..96 from FRAGMENT, the renderer pushes bytes 64..80",
+            "set 1 binding 2, the renderer writes a single descriptor there",
+            "reads push constants from FRAGMENT, the renderer pushes them to VERTEX",
+        ];
+        assert_eq!(problems.len(), expected.len(), "{problems:#?}");
+        for (problem, expected) in problems.iter().zip(expected) {
+            assert!(problem.ends_with(expected), "{problem}");
+        }
+        assert_eq!(
+            vertex.unwritten(&written, &[]).last().map(String::as_str),
+            Some("reads push constant bytes 0..80, the renderer pushes none")
+        );
+    }
+
+    #[test]
+    fn malformed_modules_are_refused() {
+        let stage = vk::ShaderStageFlags::VERTEX;
+        let mut truncated = vertex_module();
+        truncated.truncate(truncated.len() - 1);
+        let mut unsized_samplers = scalars()
+            .op(26, &[7])
+            .op(29, &[11, 7])
+            .op(32, &[12, STORAGE_UNIFORM_CONSTANT, 11])
+            .variable(12, 13, STORAGE_UNIFORM_CONSTANT)
+            .set_binding(13, 0, 0)
+            .words;
+        let cases = [
+            ("the header", vec![0; 5], "not a SPIR-V module"),
+            ("a truncated module", truncated, "runs past the end"),
+            ("an unsized array", unsized_samplers.clone(), "unsized"),
+        ];
+        for (case, code, expected) in cases {
+            let err = reflect(&code, stage).expect_err(case);
+            assert!(err.contains(expected), "{case}: {err}");
+        }
+        // Samplers without a binding are no descriptors, both decorations dropped, four words each
+        unsized_samplers.truncate(unsized_samplers.len() - 8);
+        assert!(reflect(&unsized_samplers, stage)
+            .unwrap()
+            .bindings
+            .is_empty());
+    }
+
+    /// A binding without set is in set 0, as in GLSL.
+    #[test]
+    fn bindings_without_a_set_are_in_set_0() {
+        let no_set = scalars()
+            .op(26, &[7])
+            .op(32, &[12, STORAGE_UNIFORM_CONSTANT, 7])
+            .variable(12, 13, STORAGE_UNIFORM_CONSTANT)
+            .decorate(13, &[33, 3])
+            .words;
+        let sampler = reflect(&no_set, vk::ShaderStageFlags::VERTEX).unwrap();
+        assert_eq!(
+            sampler.binding(0, 3).map(|binding| binding.descriptor_type),
+            Some(vk::DescriptorType::SAMPLER)
+        );
+    }
+}
@@ -1,29 +1,122 @@
-use crate::vulkan::device::AAADevice;
+#[cfg(not(feature = "shaderc"))]
+use crate::shader_compiler::{self, SHADER_COMPILER_ENV};
+use crate::{
+    error::PulsarError,
+    shader_include::{self, Preprocessed},
+    shader_reflect::{self, ShaderInterface},
+    vulkan::{
+        descriptor_set::{BINDINGS, DEFORM_BINDINGS, DOF_BINDINGS, DOF_SHADERS},
+        device::AAADevice,
+    },
+};
 use ash::{util::*, vk};
-use std::{ffi, io::Cursor, path::Path};
+use log::{error, info, warn};
+use std::{
+    borrow::Cow,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+#[cfg(not(feature = "shaderc"))]
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+    sync::atomic::{AtomicU32, Ordering},
+};
 
 const COMPILE_SHADERS_PATH: &str = "assets/bin/";
+pub const SHADERS_SOURCE_PATH: &str = "assets/shaders/";
+
+/// Binaries of [`COMPILE_SHADERS_PATH`] when the library was built, by name, see `build.rs`.
+const EMBEDDED_SHADERS: &[(&str, &[u8])] =
+    include!(concat!(env!("OUT_DIR"), "/embedded_shaders.rs"));
+
+/// SPIR-V of the compiled shader `name` as it was when the library was built.
+pub fn embedded(name: &str) -> Option<&'static [u8]> {
+    EMBEDDED_SHADERS
+        .iter()
+        .find(|&&(embedded, _)| embedded == name)
+        .map(|&(_, code)| code)
+}
+
+/// Path of the compiled shader `name`.
+fn compiled_path(name: &str) -> String {
+    format!("{COMPILE_SHADERS_PATH}{name}.spv")
+}
+
+/// SPIR-V of the compiled shader `name`, from [`COMPILE_SHADERS_PATH`] when the file exists,
+/// embedded otherwise.
+fn compiled(name: &str) -> Result<Cow<'static, [u8]>, String> {
+    let path = compiled_path(name);
+    if Path::new(&path).exists() {
+        return std::fs::read(&path)
+            .map(Cow::Owned)
+            .map_err(|err| format!("{path}: {err}"));
+    }
+    embedded(name)
+        .map(Cow::Borrowed)
+        .ok_or_else(|| format!("not compiled at {path}, and no copy embedded at build time"))
+}
 
 pub struct Shader<'a> {
     pub module: vk::ShaderModule,
     pub pipeline_shader_stage_create_info: vk::PipelineShaderStageCreateInfo<'a>,
+    /// Descriptors and push constants the module declares, empty when it could not be reflected.
+    pub interface: ShaderInterface,
 }
 
 impl<'a> Shader<'a> {
+    /// The compiled shader `filename` from `assets/bin`, so a rebuilt or hot reloaded binary is
+    /// picked up, or its copy embedded at build time when the file does not exist.
+    #[track_caller]
     pub fn from_filename(
         filename: &str,
         stage: vk::ShaderStageFlags,
         device: &AAADevice,
     ) -> Shader<'a> {
-        let path = format!("assets/bin/{}.spv", filename);
+        let path = compiled_path(filename);
         if !Path::new(&path).exists() {
-            panic!("Shader not compiled: {}", path);
+            info!("Shader {filename} not found at {path}, using the copy embedded at build time");
+            return Self::from_embedded(filename, stage, device);
+        }
+        let file_content = std::fs::read(&path).expect("Failed to read shader file");
+        info!("Shader {filename} read from {path}");
+        Self::from_code(filename, &file_content, stage, device)
+    }
+
+    /// [`Shader::from_filename`] from the copy embedded at build time, whatever is on disk.
+    #[track_caller]
+    pub fn from_embedded(
+        name: &str,
+        stage: vk::ShaderStageFlags,
+        device: &AAADevice,
+    ) -> Shader<'a> {
+        match embedded(name) {
+            Some(code) => Self::from_code(name, code, stage, device),
+            None => panic!(
+                "Shader not compiled: {}, and no copy embedded at build time",
+                compiled_path(name)
+            ),
         }
-        let file_content = std::fs::read(path).expect("Failed to read shader file");
-        let mut shader_bin_cursor = Cursor::new(file_content);
+    }
 
+    fn from_code(
+        filename: &str,
+        code: &[u8],
+        stage: vk::ShaderStageFlags,
+        device: &AAADevice,
+    ) -> Shader<'a> {
         let shader_aligned =
-            read_spv(&mut shader_bin_cursor).expect("Failed to read vertex shader spv file");
+            read_spv(&mut Cursor::new(code)).expect("Failed to read vertex shader spv file");
+        let interface = match shader_reflect::reflect(&shader_aligned, stage) {
+            Ok(interface) => {
+                check_bindings(filename, &interface, stage);
+                interface
+            }
+            Err(reason) => {
+                warn!("{filename}: not reflected, bindings not checked, {reason}");
+                ShaderInterface::default()
+            }
+        };
         let shader_info = vk::ShaderModuleCreateInfo::default().code(&shader_aligned);
 
         unsafe {
@@ -31,8 +124,9 @@ impl<'a> Shader<'a> {
                 .ash
                 .create_shader_module(&shader_info, None)
                 .expect("Vertex shader module error");
+            crate::object_audit::created(shader_module, "shader module");
 
-            let shader_entry_name = ffi::CStr::from_bytes_with_nul_unchecked(b"main\0");
+            let shader_entry_name = c"main";
 
             let mut pipeline_shader_stage_create_info = vk::PipelineShaderStageCreateInfo {
                 module: shader_module,
@@ -49,47 +143,286 @@ impl<'a> Shader<'a> {
             Self {
                 module: shader_module,
                 pipeline_shader_stage_create_info,
+                interface,
             }
         }
     }
 
+    /// Descriptors and push constants of the compiled shader `filename`, read as
+    /// [`Shader::from_filename`] does, without creating its module.
+    pub fn interface_of(
+        filename: &str,
+        stage: vk::ShaderStageFlags,
+    ) -> Result<ShaderInterface, PulsarError> {
+        let error = |reason: String| PulsarError::ShaderInterface {
+            shader: filename.to_string(),
+            reason,
+        };
+        let code = read_spv(&mut Cursor::new(compiled(filename).map_err(error)?))
+            .map_err(|err| error(err.to_string()))?;
+        shader_reflect::reflect(&code, stage).map_err(error)
+    }
+
+    /// Compile the sources of [`SHADERS`] whose binary is missing or older than them or a file
+    /// they include. Panics listing every error by file and line. Without a compiler, nothing
+    /// happens as long as every shader has a binary, on disk or embedded, the stale ones are
+    /// logged.
     pub fn compile_shaders() {
-        if Path::new(COMPILE_SHADERS_PATH).exists() {
-            let files =
-                std::fs::read_dir(COMPILE_SHADERS_PATH).expect("Failed to read shader files");
-            for file in files {
-                let file = file.expect("Failed to read shader files");
-                let path = file.path();
-                if let Some(ext) = path.extension() {
-                    if ext == "spv" {
-                        std::fs::remove_file(path).expect("Failed to remove old shader files");
-                    }
-                }
+        std::fs::create_dir_all(COMPILE_SHADERS_PATH).expect("Failed to create shader directory");
+        let stale: Vec<(PathBuf, PathBuf)> = SHADERS
+            .iter()
+            .map(|(source, name)| {
+                (
+                    Path::new(SHADERS_SOURCE_PATH).join(source),
+                    Path::new(COMPILE_SHADERS_PATH).join(format!("{name}.spv")),
+                )
+            })
+            .filter(|(source, binary)| {
+                let built = std::fs::metadata(binary).and_then(|metadata| metadata.modified());
+                // Unreadable includes are compiled again for the compiler to report them
+                let newest =
+                    shader_include::newest_modified(source, Path::new(SHADERS_SOURCE_PATH));
+                built.map_or(true, |built| newest.is_none_or(|newest| newest > built))
+            })
+            .collect();
+        if stale.is_empty() {
+            return;
+        }
+        #[cfg(not(feature = "shaderc"))]
+        if shader_compiler::discover().is_none() {
+            // Shaders with a binary on disk or embedded still load, only those without are fatal
+            let (kept, missing): (Vec<_>, Vec<_>) = stale.iter().partition(|(_, binary)| {
+                binary.exists()
+                    || binary
+                        .file_stem()
+                        .and_then(|name| name.to_str())
+                        .is_some_and(|name| embedded(name).is_some())
+            });
+            let list = |shaders: Vec<&(PathBuf, PathBuf)>| {
+                shaders
+                    .iter()
+                    .map(|(source, _)| source.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            if !missing.is_empty() {
+                panic!(
+                    "No GLSL compiler to compile {}, install glslc or glslangValidator or set {SHADER_COMPILER_ENV}",
+                    list(missing)
+                );
             }
-        } else {
-            std::fs::create_dir(COMPILE_SHADERS_PATH).expect("Failed to create shader directory");
+            warn!(
+                "No GLSL compiler, {} changed since their binaries were built and were not compiled again",
+                list(kept)
+            );
+            return;
         }
 
-        let output_vert = std::process::Command::new("glslc.exe")
-            .arg("assets/shaders/shader.vert")
-            .arg("-o")
-            .arg(format!("{}/vert.spv", COMPILE_SHADERS_PATH))
-            .output()
-            .expect("Failed to execute glslc.exe for vertex shader");
-
-        let output_frag = std::process::Command::new("glslc.exe")
-            .arg("assets/shaders/shader.frag")
-            .arg("-o")
-            .arg(format!("{}/frag.spv", COMPILE_SHADERS_PATH))
-            .output()
-            .expect("Failed to execute glslc.exe for fragment shader");
-
-        if !(output_vert.status.success() && output_frag.status.success()) {
-            panic!(
-                "Failed to compile shaders:\n{}\n{}",
-                String::from_utf8_lossy(&output_vert.stderr),
-                String::from_utf8_lossy(&output_frag.stderr)
-            );
+        // Every stale shader compiles at once, on a thread each
+        let errors: Vec<String> = std::thread::scope(|scope| {
+            let compilers: Vec<_> = stale
+                .iter()
+                .map(|(source, binary)| {
+                    scope.spawn(move || {
+                        let stage =
+                            match source.extension().and_then(|extension| extension.to_str()) {
+                                Some("frag") => vk::ShaderStageFlags::FRAGMENT,
+                                Some("geom") => vk::ShaderStageFlags::GEOMETRY,
+                                Some("tesc") => vk::ShaderStageFlags::TESSELLATION_CONTROL,
+                                Some("tese") => vk::ShaderStageFlags::TESSELLATION_EVALUATION,
+                                Some("comp") => vk::ShaderStageFlags::COMPUTE,
+                                _ => vk::ShaderStageFlags::VERTEX,
+                            };
+                        let spirv = compile_glsl(source, stage)?;
+                        std::fs::write(binary, spirv)
+                            .map_err(|err| format!("{}: {err}", binary.display()))
+                    })
+                })
+                .collect();
+            compilers
+                .into_iter()
+                .filter_map(|compiler| compiler.join().expect("Shader compiler panicked").err())
+                .collect()
+        });
+        if !errors.is_empty() {
+            panic!("Failed to compile shaders:\n{}", errors.join("\n"));
         }
     }
 }
+
+/// Log the descriptors `interface` reads outside of [`BINDINGS`], of [`DOF_BINDINGS`] for the
+/// [`DOF_SHADERS`], or of [`DEFORM_BINDINGS`] for a compute shader. The set index matters as much
+/// as the binding, a known binding in the wrong set is reported too. Push constants are checked against the layouts pushing them.
+fn check_bindings(filename: &str, interface: &ShaderInterface, stage: vk::ShaderStageFlags) {
+    let written: Vec<_> = if stage == vk::ShaderStageFlags::COMPUTE {
+        DEFORM_BINDINGS
+            .iter()
+            .map(|&(set, binding, descriptor_type, _)| (set, binding, descriptor_type))
+            .collect()
+    } else if DOF_SHADERS.contains(&filename) {
+        DOF_BINDINGS.to_vec()
+    } else {
+        BINDINGS.to_vec()
+    };
+    for problem in interface.unwritten(&written, &interface.push_constants) {
+        error!("{filename}: {problem}");
+    }
+}
+
+/// GLSL sources in [`SHADERS_SOURCE_PATH`] and the name of their binary.
+const SHADERS: [(&str, &str); 18] = [
+    ("shader.vert", "vert"),
+    ("shader.frag", "frag"),
+    ("instanced.vert", "instanced"),
+    ("skinned.vert", "skinned"),
+    ("skinned_uniform.vert", "skinned_uniform"),
+    ("background.vert", "background"),
+    ("texture_array.vert", "texture_array_vert"),
+    ("texture_array.frag", "texture_array"),
+    ("export.frag", "export"),
+    ("wave.comp", "wave"),
+    ("normals.vert", "normals_vert"),
+    ("normals.geom", "normals"),
+    ("displace.vert", "displace_vert"),
+    ("displace.tesc", "displace_tesc"),
+    ("displace.tese", "displace"),
+    ("dof_coc.frag", "dof_coc"),
+    ("dof_gather.frag", "dof_gather"),
+    ("dof_composite.frag", "dof_composite"),
+];
+
+/// SPIR-V of the GLSL source at `path`, its includes inlined by [`shader_include`] from
+/// [`SHADERS_SOURCE_PATH`]. Errors are one `file:line: message` line each, in the files written.
+#[cfg(feature = "shaderc")]
+pub fn compile_glsl(path: &Path, stage: vk::ShaderStageFlags) -> Result<Vec<u8>, String> {
+    let preprocessed = shader_include::preprocess(path, Path::new(SHADERS_SOURCE_PATH))
+        .map_err(|err| err.to_string())?;
+    let kind = match stage {
+        vk::ShaderStageFlags::FRAGMENT => shaderc::ShaderKind::Fragment,
+        vk::ShaderStageFlags::GEOMETRY => shaderc::ShaderKind::Geometry,
+        vk::ShaderStageFlags::TESSELLATION_CONTROL => shaderc::ShaderKind::TessControl,
+        vk::ShaderStageFlags::TESSELLATION_EVALUATION => shaderc::ShaderKind::TessEvaluation,
+        vk::ShaderStageFlags::COMPUTE => shaderc::ShaderKind::Compute,
+        _ => shaderc::ShaderKind::Vertex,
+    };
+    let mut compiler = shaderc::Compiler::new().ok_or("Unable to create the shader compiler")?;
+    compiler
+        .compile_into_spirv(
+            &preprocessed.source,
+            kind,
+            &path.to_string_lossy(),
+            "main",
+            None,
+        )
+        .map(|artifact| artifact.as_binary_u8().to_vec())
+        .map_err(|err| match err {
+            shaderc::Error::CompilationError(_, messages) => diagnostics(&messages, &preprocessed),
+            err => format!("{}: {err}", path.display()),
+        })
+}
+
+/// [`compile_glsl`] through the compiler [`shader_compiler::discover`] finds, the inlined
+/// source written to its standard input.
+#[cfg(not(feature = "shaderc"))]
+pub fn compile_glsl(path: &Path, stage: vk::ShaderStageFlags) -> Result<Vec<u8>, String> {
+    // Names the SPIR-V of compilers that only write it to a file, unique within the process
+    static OUTPUTS: AtomicU32 = AtomicU32::new(0);
+
+    let compiler = shader_compiler::discover().ok_or_else(|| {
+        format!(
+            "{}: no GLSL compiler, install glslc or glslangValidator or set {SHADER_COMPILER_ENV}",
+            path.display()
+        )
+    })?;
+    let preprocessed = shader_include::preprocess(path, Path::new(SHADERS_SOURCE_PATH))
+        .map_err(|err| err.to_string())?;
+    let stage = match stage {
+        vk::ShaderStageFlags::FRAGMENT => "frag",
+        vk::ShaderStageFlags::GEOMETRY => "geom",
+        vk::ShaderStageFlags::TESSELLATION_CONTROL => "tesc",
+        vk::ShaderStageFlags::TESSELLATION_EVALUATION => "tese",
+        vk::ShaderStageFlags::COMPUTE => "comp",
+        _ => "vert",
+    };
+    let output_path = std::env::temp_dir().join(format!(
+        "pulsar-{}-{}.spv",
+        std::process::id(),
+        OUTPUTS.fetch_add(1, Ordering::Relaxed)
+    ));
+    let not_run = |err: std::io::Error| {
+        format!(
+            "{}: {} did not run, {err}",
+            path.display(),
+            compiler.path.display()
+        )
+    };
+    let mut process = Command::new(&compiler.path)
+        .args(compiler.args(stage, &output_path))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(not_run)?;
+    // The compilers read the whole source before writing anything
+    process
+        .stdin
+        .take()
+        .expect("The standard input is piped")
+        .write_all(preprocessed.source.as_bytes())
+        .map_err(not_run)?;
+    let output = process.wait_with_output().map_err(not_run)?;
+    if !output.status.success() {
+        let _ = std::fs::remove_file(&output_path);
+        // glslangValidator reports on the standard output
+        let report =
+            String::from_utf8_lossy(&output.stderr) + String::from_utf8_lossy(&output.stdout);
+        return Err(diagnostics(&report, &preprocessed));
+    }
+    if compiler.writes_stdout() {
+        return Ok(output.stdout);
+    }
+    let spirv = std::fs::read(&output_path).map_err(|err| {
+        format!(
+            "{}: {} wrote no SPIR-V, {err}",
+            path.display(),
+            compiler.path.display()
+        )
+    });
+    let _ = std::fs::remove_file(&output_path);
+    spirv
+}
+
+/// The `file:line: error: message` lines of a compiler's output, or glslangValidator's
+/// `ERROR: file:line: message`, as `file:line: message`, the lines of the inlined source mapped
+/// back to the files they were written in. The summary lines are left out, the output is kept as
+/// is when none are found.
+fn diagnostics(output: &str, preprocessed: &Preprocessed) -> String {
+    let lines: Vec<String> = output
+        .lines()
+        .filter_map(|line| {
+            let line = line
+                .strip_prefix("ERROR: ")
+                .or_else(|| line.strip_prefix("WARNING: "))
+                .unwrap_or(line);
+            let mut parts = line.splitn(3, ':');
+            let (_, number, message) = (parts.next()?, parts.next()?, parts.next()?);
+            let origin = preprocessed.origin(number.trim().parse().ok()?)?;
+            let message = message.trim();
+            let message = message
+                .strip_prefix("error:")
+                .or_else(|| message.strip_prefix("warning:"))
+                .unwrap_or(message);
+            Some(format!(
+                "{}:{}: {}",
+                origin.path.display(),
+                origin.line,
+                message.trim()
+            ))
+        })
+        .collect();
+    if lines.is_empty() {
+        output.trim().to_string()
+    } else {
+        lines.join("\n")
+    }
+}
@@ -0,0 +1,631 @@
+//! Skeletal animation: joints moving the vertices they weigh on, posed from keyframed clips.
+
+use crate::{
+    model::Mesh,
+    vulkan::{
+        descriptor_set::{PALETTE_STORAGE_BINDING, PALETTE_UNIFORM_BINDING},
+        device::AAADevice,
+        upload::{create_empty_buffer, write_mapped, AAAOwnedBuffer},
+    },
+};
+use ash::vk;
+use glam::{Mat4, Quat, Vec3};
+use std::{
+    mem,
+    sync::{Arc, Mutex},
+};
+
+/// Bone of a [`Skin`], posed by its rest transform unless a channel animates it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Joint {
+    pub name: String,
+    /// Index in [`Skin::joints`], `None` for a root.
+    pub parent: Option<usize>,
+    /// Transform of a root's ancestors outside the skin, identity for the other joints.
+    pub origin: Mat4,
+    /// Rest transform relative to the parent.
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+    /// Brings a vertex from the mesh into the joint's space at the bind pose.
+    pub inverse_bind: Mat4,
+}
+
+impl Joint {
+    fn local(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
+
+/// Joints of a skinned mesh, indexed by [`crate::model::Vertex::joints`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Skin {
+    pub joints: Vec<Joint>,
+}
+
+impl Skin {
+    /// Matrix of every joint from the mesh's bind pose to `clip` at `time`, the rest pose without
+    /// a clip. The identity for each joint at the bind pose.
+    pub fn pose(&self, clip: Option<(&AnimationClip, f32)>) -> Vec<Mat4> {
+        let mut locals: Vec<Mat4> = self.joints.iter().map(Joint::local).collect();
+        if let Some((clip, time)) = clip {
+            for (index, joint) in self.joints.iter().enumerate() {
+                let mut translation = joint.translation;
+                let mut rotation = joint.rotation;
+                let mut scale = joint.scale;
+                let mut animated = false;
+                for channel in clip
+                    .channels
+                    .iter()
+                    .filter(|channel| channel.joint == index)
+                {
+                    animated = true;
+                    match &channel.keyframes {
+                        Keyframes::Translation(keys) => {
+                            translation = sample(keys, time, channel.interpolation, Vec3::lerp)
+                                .unwrap_or(translation)
+                        }
+                        Keyframes::Rotation(keys) => {
+                            rotation = sample(keys, time, channel.interpolation, Quat::slerp)
+                                .unwrap_or(rotation)
+                        }
+                        Keyframes::Scale(keys) => {
+                            scale = sample(keys, time, channel.interpolation, Vec3::lerp)
+                                .unwrap_or(scale)
+                        }
+                    }
+                }
+                if animated {
+                    locals[index] =
+                        Mat4::from_scale_rotation_translation(scale, rotation, translation);
+                }
+            }
+        }
+
+        // Parents are resolved before their children whatever the order of the joints
+        let mut globals: Vec<Option<Mat4>> = vec![None; self.joints.len()];
+        for index in 0..self.joints.len() {
+            let mut chain = vec![index];
+            while let Some(parent) = self.joints[*chain.last().unwrap()].parent {
+                if globals[parent].is_some() || chain.contains(&parent) {
+                    break;
+                }
+                chain.push(parent);
+            }
+            for &joint in chain.iter().rev() {
+                if globals[joint].is_some() {
+                    continue;
+                }
+                let parent = match self.joints[joint].parent {
+                    Some(parent) => globals[parent].unwrap_or(Mat4::IDENTITY),
+                    None => self.joints[joint].origin,
+                };
+                globals[joint] = Some(parent * locals[joint]);
+            }
+        }
+        globals
+            .into_iter()
+            .zip(&self.joints)
+            .map(|(global, joint)| global.unwrap_or(Mat4::IDENTITY) * joint.inverse_bind)
+            .collect()
+    }
+}
+
+/// How a channel moves between two keyframes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Interpolation {
+    /// Linear for translations and scales, spherical for rotations.
+    #[default]
+    Linear,
+    /// Holds each keyframe until the next.
+    Step,
+}
+
+/// Keyframes of one component of a joint, as time in seconds and value, sorted by time.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Keyframes {
+    Translation(Vec<(f32, Vec3)>),
+    Rotation(Vec<(f32, Quat)>),
+    Scale(Vec<(f32, Vec3)>),
+}
+
+impl Keyframes {
+    fn last_time(&self) -> f32 {
+        let last = match self {
+            Self::Translation(keys) | Self::Scale(keys) => keys.last().map(|key| key.0),
+            Self::Rotation(keys) => keys.last().map(|key| key.0),
+        };
+        last.unwrap_or(0.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Channel {
+    /// Index in [`Skin::joints`].
+    pub joint: usize,
+    pub interpolation: Interpolation,
+    pub keyframes: Keyframes,
+}
+
+/// Named animation of a skin, its channels animating the joints independently.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationClip {
+    pub name: String,
+    /// Time of the last keyframe of every channel, in seconds.
+    pub duration: f32,
+    pub channels: Vec<Channel>,
+}
+
+impl AnimationClip {
+    pub fn new(name: impl Into<String>, channels: Vec<Channel>) -> Self {
+        let duration = channels
+            .iter()
+            .map(|channel| channel.keyframes.last_time())
+            .fold(0.0, f32::max);
+        Self {
+            name: name.into(),
+            duration,
+            channels,
+        }
+    }
+}
+
+/// Value of `keys` at `time`, clamped to the first and last keyframes. `None` without keyframes.
+fn sample<T: Copy>(
+    keys: &[(f32, T)],
+    time: f32,
+    interpolation: Interpolation,
+    mix: impl Fn(T, T, f32) -> T,
+) -> Option<T> {
+    let next = keys.partition_point(|&(key_time, _)| key_time <= time);
+    if next == 0 {
+        return keys.first().map(|key| key.1);
+    }
+    let (start, from) = keys[next - 1];
+    let Some(&(end, to)) = keys.get(next) else {
+        return Some(from);
+    };
+    Some(match interpolation {
+        Interpolation::Step => from,
+        Interpolation::Linear => mix(from, to, (time - start) / (end - start)),
+    })
+}
+
+/// A mesh weighed on the joints of its skin, with the clips animating it.
+#[derive(Debug)]
+pub struct SkinnedMesh {
+    pub mesh: Mesh,
+    pub skin: Skin,
+    pub animations: Vec<AnimationClip>,
+}
+
+/// Plays the clips of a skin, sampled by the render thread every frame. Shared with the render
+/// thread: clones control the same playback. The window drawing it advances it by its frame time,
+/// add a player to a single window.
+#[derive(Debug, Clone)]
+pub struct AnimationPlayer {
+    shared: Arc<Mutex<Playback>>,
+}
+
+#[derive(Debug)]
+struct Playback {
+    skin: Skin,
+    clips: Vec<AnimationClip>,
+    /// Index in `clips`, the rest pose when `None`.
+    playing: Option<usize>,
+    time: f32,
+    speed: f32,
+    looping: bool,
+}
+
+impl AnimationPlayer {
+    /// Stopped at the rest pose, looping at normal speed once a clip plays.
+    pub fn new(skin: Skin, clips: Vec<AnimationClip>) -> Self {
+        Self {
+            shared: Arc::new(Mutex::new(Playback {
+                skin,
+                clips,
+                playing: None,
+                time: 0.0,
+                speed: 1.0,
+                looping: true,
+            })),
+        }
+    }
+
+    /// Play the clip named `name` from its start, false when the player has none of that name.
+    pub fn play(&self, name: &str) -> bool {
+        let mut playback = self.shared.lock().unwrap();
+        let Some(index) = playback.clips.iter().position(|clip| clip.name == name) else {
+            return false;
+        };
+        playback.playing = Some(index);
+        playback.time = 0.0;
+        true
+    }
+
+    /// Back to the rest pose.
+    pub fn stop(&self) {
+        let mut playback = self.shared.lock().unwrap();
+        playback.playing = None;
+        playback.time = 0.0;
+    }
+
+    /// Playback rate, negative plays backwards.
+    pub fn set_speed(&self, speed: f32) {
+        self.shared.lock().unwrap().speed = speed;
+    }
+
+    /// Whether the clip wraps around at its ends, it holds its last pose otherwise.
+    pub fn set_looping(&self, looping: bool) {
+        self.shared.lock().unwrap().looping = looping;
+    }
+
+    /// Name of the clip playing, `None` at the rest pose.
+    pub fn playing(&self) -> Option<String> {
+        let playback = self.shared.lock().unwrap();
+        playback
+            .playing
+            .map(|index| playback.clips[index].name.clone())
+    }
+
+    /// Seconds into the clip playing.
+    pub fn time(&self) -> f32 {
+        self.shared.lock().unwrap().time
+    }
+
+    pub fn joint_count(&self) -> usize {
+        self.shared.lock().unwrap().skin.joints.len()
+    }
+
+    /// Move the clip playing `delta_seconds` forward at the player's speed.
+    pub fn advance(&self, delta_seconds: f32) {
+        let mut playback = self.shared.lock().unwrap();
+        let Some(index) = playback.playing else {
+            return;
+        };
+        let duration = playback.clips[index].duration;
+        let time = playback.time + delta_seconds * playback.speed;
+        playback.time = if duration <= 0.0 {
+            0.0
+        } else if playback.looping {
+            time.rem_euclid(duration)
+        } else {
+            time.clamp(0.0, duration)
+        };
+    }
+
+    /// Joint matrices of the current pose, see [`Skin::pose`].
+    pub fn bone_matrices(&self) -> Vec<Mat4> {
+        let playback = self.shared.lock().unwrap();
+        let clip = playback
+            .playing
+            .map(|index| (&playback.clips[index], playback.time));
+        playback.skin.pose(clip)
+    }
+}
+
+/// Joint matrices of a uniform palette, 16KiB: the smallest `maxUniformBufferRange` a device may
+/// report. `skinned_uniform.vert` declares its array at this size.
+pub const UNIFORM_PALETTE_JOINTS: usize = 256;
+
+/// Buffer the vertex shader reads the joint matrices of a skinned mesh from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaletteBuffer {
+    /// Up to [`UNIFORM_PALETTE_JOINTS`] joints, read faster than storage on most GPUs.
+    Uniform,
+    /// Any number of joints.
+    Storage,
+}
+
+impl PaletteBuffer {
+    /// Uniform when `joints` matrices fit the uniform palette and the device's
+    /// `maxUniformBufferRange` holds it, storage otherwise.
+    pub fn for_joints(joints: usize, max_uniform_buffer_range: u32) -> Self {
+        let palette_size = UNIFORM_PALETTE_JOINTS * mem::size_of::<Mat4>();
+        if joints <= UNIFORM_PALETTE_JOINTS && palette_size <= max_uniform_buffer_range as usize {
+            Self::Uniform
+        } else {
+            Self::Storage
+        }
+    }
+
+    fn descriptor(self) -> (u32, vk::DescriptorType, vk::BufferUsageFlags) {
+        match self {
+            Self::Uniform => (
+                PALETTE_UNIFORM_BINDING,
+                vk::DescriptorType::UNIFORM_BUFFER,
+                vk::BufferUsageFlags::UNIFORM_BUFFER,
+            ),
+            Self::Storage => (
+                PALETTE_STORAGE_BINDING,
+                vk::DescriptorType::STORAGE_BUFFER,
+                vk::BufferUsageFlags::STORAGE_BUFFER,
+            ),
+        }
+    }
+}
+
+/// Host visible buffer of a skinned mesh's joint matrices, bound to the object set.
+#[derive(Debug)]
+pub(crate) struct SkinBuffer {
+    pub source: AnimationPlayer,
+    /// Selects the skinned pipeline reading it.
+    pub palette: PaletteBuffer,
+    pub buffer: vk::Buffer,
+    pub memory: vk::DeviceMemory,
+    /// Matrices the buffer holds, at least one past the largest joint index of the vertices, the
+    /// whole uniform palette for [`PaletteBuffer::Uniform`].
+    pub count: usize,
+    pub descriptor_pool: vk::DescriptorPool,
+    pub descriptor_set: vk::DescriptorSet,
+}
+
+impl SkinBuffer {
+    /// Room for `count` matrices, all identity until the first [`Self::write`].
+    pub fn new(
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        layout: vk::DescriptorSetLayout,
+        source: AnimationPlayer,
+        count: usize,
+    ) -> Self {
+        let count = count.max(source.joint_count()).max(1);
+        let palette = PaletteBuffer::for_joints(count, device.max_uniform_buffer_range);
+        // The uniform block is declared at its full size, the buffer must cover it
+        let count = match palette {
+            PaletteBuffer::Uniform => UNIFORM_PALETTE_JOINTS,
+            PaletteBuffer::Storage => count,
+        };
+        let (binding, descriptor_type, usage) = palette.descriptor();
+        let size = (count * mem::size_of::<Mat4>()) as u64;
+        let AAAOwnedBuffer { buffer, memory, .. } = create_empty_buffer(
+            device,
+            device_memory_properties,
+            size,
+            usage,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        );
+        write_mapped(device, memory, size, &vec![Mat4::IDENTITY; count]);
+
+        let pool_sizes = [vk::DescriptorPoolSize {
+            ty: descriptor_type,
+            descriptor_count: 1,
+        }];
+        let pool_info = vk::DescriptorPoolCreateInfo::default()
+            .pool_sizes(&pool_sizes)
+            .max_sets(1);
+        let layouts = [layout];
+        let (descriptor_pool, descriptor_set) = unsafe {
+            let pool = device
+                .ash
+                .create_descriptor_pool(&pool_info, None)
+                .expect("Failed to create skin descriptor pool");
+            crate::object_audit::created(pool, "skin descriptor pool");
+            let alloc_info = vk::DescriptorSetAllocateInfo::default()
+                .descriptor_pool(pool)
+                .set_layouts(&layouts);
+            let sets = device
+                .ash
+                .allocate_descriptor_sets(&alloc_info)
+                .expect("Failed to allocate skin descriptor set");
+            (pool, sets[0])
+        };
+        let buffer_info = [vk::DescriptorBufferInfo {
+            buffer,
+            offset: 0,
+            range: size,
+        }];
+        let write = vk::WriteDescriptorSet::default()
+            .dst_set(descriptor_set)
+            .dst_binding(binding)
+            .descriptor_type(descriptor_type)
+            .buffer_info(&buffer_info);
+        unsafe { device.ash.update_descriptor_sets(&[write], &[]) };
+
+        Self {
+            source,
+            palette,
+            buffer,
+            memory,
+            count,
+            descriptor_pool,
+            descriptor_set,
+        }
+    }
+
+    /// Copy the player's current pose, the commands reading the buffer must have completed.
+    pub fn write(&self, device: &AAADevice) {
+        let mut matrices = self.source.bone_matrices();
+        matrices.resize(self.count, Mat4::IDENTITY);
+        write_mapped(
+            device,
+            self.memory,
+            (self.count * mem::size_of::<Mat4>()) as u64,
+            &matrices,
+        );
+    }
+
+    pub fn destroy(&self, device: &AAADevice) {
+        unsafe {
+            crate::object_audit::destroyed(self.descriptor_pool);
+            device
+                .ash
+                .destroy_descriptor_pool(self.descriptor_pool, None);
+            crate::object_audit::destroyed(self.memory);
+            device.ash.free_memory(self.memory, None);
+            crate::object_audit::destroyed(self.buffer);
+            device.ash.destroy_buffer(self.buffer, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    const TOLERANCE: f32 = 1e-5;
+
+    /// A root at the origin and a child one unit above it, both at their bind pose.
+    fn arm() -> Skin {
+        let joint = |name: &str, parent, translation: Vec3, inverse_bind| Joint {
+            name: name.into(),
+            parent,
+            origin: Mat4::IDENTITY,
+            translation,
+            rotation: Quat::IDENTITY,
+            scale: Vec3::ONE,
+            inverse_bind,
+        };
+        // Listed child first, the parents are resolved whatever the order
+        Skin {
+            joints: vec![
+                joint(
+                    "forearm",
+                    Some(1),
+                    Vec3::Y,
+                    Mat4::from_translation(-Vec3::Y),
+                ),
+                joint("shoulder", None, Vec3::ZERO, Mat4::IDENTITY),
+            ],
+        }
+    }
+
+    /// The shoulder slides two units along +X over two seconds while the forearm turns a quarter
+    /// around +Z, and the forearm's scale steps at one second.
+    fn wave() -> AnimationClip {
+        AnimationClip::new(
+            "wave",
+            vec![
+                Channel {
+                    joint: 1,
+                    interpolation: Interpolation::Linear,
+                    keyframes: Keyframes::Translation(vec![
+                        (0.0, Vec3::ZERO),
+                        (2.0, 2.0 * Vec3::X),
+                    ]),
+                },
+                Channel {
+                    joint: 0,
+                    interpolation: Interpolation::Linear,
+                    keyframes: Keyframes::Rotation(vec![
+                        (0.0, Quat::IDENTITY),
+                        (2.0, Quat::from_rotation_z(FRAC_PI_2)),
+                    ]),
+                },
+                Channel {
+                    joint: 0,
+                    interpolation: Interpolation::Step,
+                    keyframes: Keyframes::Scale(vec![(0.0, Vec3::ONE), (1.0, Vec3::splat(2.0))]),
+                },
+            ],
+        )
+    }
+
+    fn assert_matrix(label: &str, actual: Mat4, expected: Mat4) {
+        assert!(
+            actual.abs_diff_eq(expected, TOLERANCE),
+            "{label}: {actual} instead of {expected}"
+        );
+    }
+
+    #[test]
+    fn keyframes_interpolate_step_and_clamp() {
+        let skin = arm();
+        let clip = wave();
+        assert_eq!(clip.duration, 2.0);
+        for (i, matrix) in skin.pose(None).into_iter().enumerate() {
+            assert_matrix(
+                &format!("joint {i} at its bind pose"),
+                matrix,
+                Mat4::IDENTITY,
+            );
+        }
+
+        // Halfway: a unit along +X, an eighth of a turn, the scale stepped up at one second
+        let pose = skin.pose(Some((&clip, 1.0)));
+        let shoulder = Mat4::from_translation(Vec3::X);
+        assert_matrix("shoulder halfway", pose[1], shoulder);
+        let forearm = shoulder
+            * Mat4::from_scale_rotation_translation(
+                Vec3::splat(2.0),
+                Quat::from_rotation_z(FRAC_PI_2 / 2.0),
+                Vec3::Y,
+            )
+            * Mat4::from_translation(-Vec3::Y);
+        assert_matrix("forearm halfway", pose[0], forearm);
+        // Just before a step the previous keyframe holds
+        let before_step = skin.pose(Some((&clip, 0.999)));
+        assert!((before_step[0].x_axis.length() - 1.0).abs() < 1e-3);
+        // Past either end the clip holds its first and last keyframes
+        assert_matrix(
+            "shoulder before the start",
+            skin.pose(Some((&clip, -1.0)))[1],
+            Mat4::IDENTITY,
+        );
+        assert_matrix(
+            "shoulder after the end",
+            skin.pose(Some((&clip, 5.0)))[1],
+            Mat4::from_translation(2.0 * Vec3::X),
+        );
+
+        // A vertex halfway up the forearm weighs on both joints as the shader blends them
+        let vertex = Vec3::new(0.0, 1.5, 0.0);
+        let blended = pose[0] * 0.5 + pose[1] * 0.5;
+        let expected = (forearm.transform_point3(vertex) + shoulder.transform_point3(vertex)) / 2.0;
+        let moved = blended.transform_point3(vertex);
+        assert!(
+            moved.abs_diff_eq(expected, TOLERANCE),
+            "{moved} is not {expected}"
+        );
+    }
+
+    #[test]
+    fn player_loops_reverses_holds_and_stops() {
+        let skin = arm();
+        let clip = wave();
+        let player = AnimationPlayer::new(skin.clone(), vec![clip.clone()]);
+        assert!(!player.play("run"));
+        assert_eq!(player.playing(), None);
+        assert!(player.play("wave"));
+
+        // Clones control the same player
+        let control = player.clone();
+        player.advance(2.5);
+        assert!(
+            (control.time() - 0.5).abs() < TOLERANCE,
+            "{}",
+            control.time()
+        );
+        control.set_speed(-1.0);
+        player.advance(1.0);
+        assert!((player.time() - 1.5).abs() < TOLERANCE, "{}", player.time());
+        control.set_speed(1.0);
+        control.set_looping(false);
+        player.advance(10.0);
+        assert_eq!(player.time(), 2.0);
+        assert_eq!(player.bone_matrices(), skin.pose(Some((&clip, 2.0))));
+        control.stop();
+        assert_eq!(player.bone_matrices(), skin.pose(None));
+    }
+
+    #[test]
+    fn large_palettes_fall_back_to_a_storage_buffer() {
+        // Joints, maxUniformBufferRange, expected
+        let cases = [
+            (2, 16384, PaletteBuffer::Uniform),
+            (UNIFORM_PALETTE_JOINTS, 16384, PaletteBuffer::Uniform),
+            (UNIFORM_PALETTE_JOINTS + 1, 65536, PaletteBuffer::Storage),
+            // Below the minimum Vulkan allows, still handled
+            (2, 4096, PaletteBuffer::Storage),
+        ];
+        for (joints, range, expected) in cases {
+            assert_eq!(
+                PaletteBuffer::for_joints(joints, range),
+                expected,
+                "{joints} joints in {range} bytes"
+            );
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! Surface format and capabilities queries that survive remote and virtual displays, where they
+//! sometimes fail or come back empty for a moment.
+
+use crate::error::PulsarError;
+use log::warn;
+use std::{fmt, thread, time::Duration};
+
+/// Tries of each query before the surface is reported unsupported.
+pub const SURFACE_QUERY_ATTEMPTS: u32 = 4;
+/// Wait after the first failed try, doubled after each following one.
+pub const SURFACE_QUERY_BACKOFF: Duration = Duration::from_millis(5);
+
+/// Run `query` until it returns a result `usable` accepts, sleeping between tries.
+/// The last error or unusable result is returned once the tries are spent.
+pub fn retry_query<T, E: fmt::Display>(
+    what: &str,
+    usable: impl Fn(&T) -> bool,
+    mut query: impl FnMut() -> Result<T, E>,
+) -> Result<T, E> {
+    let mut backoff = SURFACE_QUERY_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        let result = query();
+        let retry = match &result {
+            Ok(value) => !usable(value),
+            Err(error) => {
+                warn!("Surface {what} query failed, try {attempt}: {error}");
+                true
+            }
+        };
+        if !retry || attempt >= SURFACE_QUERY_ATTEMPTS {
+            return result;
+        }
+        thread::sleep(backoff);
+        backoff *= 2;
+        attempt += 1;
+    }
+}
+
+/// The format `choose` picks of the surface's list and the capabilities of the surface, each
+/// query retried on its own.
+pub fn query_surface<F, C, E: fmt::Display>(
+    query_formats: impl FnMut() -> Result<Vec<F>, E>,
+    query_capabilities: impl FnMut() -> Result<C, E>,
+    choose: impl Fn(&[F]) -> Option<F>,
+) -> Result<(F, C), PulsarError> {
+    let unsupported = |reason: String| PulsarError::SurfaceUnsupported { reason };
+    let formats = retry_query(
+        "formats",
+        |formats: &Vec<F>| !formats.is_empty(),
+        query_formats,
+    )
+    .map_err(|error| unsupported(format!("format query failed: {error}")))?;
+    let format = choose(&formats)
+        .ok_or_else(|| unsupported("the surface supports no format".to_string()))?;
+    let capabilities = retry_query("capabilities", |_| true, query_capabilities)
+        .map_err(|error| unsupported(format!("capabilities query failed: {error}")))?;
+    Ok((format, capabilities))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    /// Loader answering with `responses` in order, then with the last one.
+    fn loader<T: Clone>(
+        responses: Vec<Result<T, &'static str>>,
+    ) -> impl FnMut() -> Result<T, &'static str> {
+        let calls = Cell::new(0);
+        move || {
+            let response = responses[calls.get().min(responses.len() - 1)].clone();
+            calls.set(calls.get() + 1);
+            response
+        }
+    }
+
+    fn first(formats: &[u32]) -> Option<u32> {
+        formats.first().copied()
+    }
+
+    #[test]
+    fn transient_failures_recover_within_the_tries() {
+        let recovered = query_surface(
+            loader(vec![
+                Err("ERROR_SURFACE_LOST_KHR"),
+                Ok(vec![]),
+                Ok(vec![44]),
+            ]),
+            loader(vec![Err("ERROR_OUT_OF_HOST_MEMORY"), Ok("capabilities")]),
+            first,
+        );
+        assert_eq!(recovered.unwrap(), (44, "capabilities"));
+    }
+
+    #[test]
+    fn empty_format_list_is_unsupported() {
+        let empty = query_surface(
+            loader(vec![Ok(Vec::<u32>::new())]),
+            loader(vec![Ok(())]),
+            first,
+        );
+        assert!(matches!(empty, Err(PulsarError::SurfaceUnsupported { .. })));
+        let unchosen = query_surface(loader(vec![Ok(vec![1])]), loader(vec![Ok(())]), |_| None);
+        assert!(matches!(
+            unchosen,
+            Err(PulsarError::SurfaceUnsupported { .. })
+        ));
+    }
+
+    #[test]
+    fn no_try_past_the_bound() {
+        let tries = Cell::new(0);
+        let failing = query_surface(
+            || {
+                tries.set(tries.get() + 1);
+                Err::<Vec<u32>, _>("ERROR_INITIALIZATION_FAILED")
+            },
+            loader(vec![Ok(())]),
+            first,
+        );
+        assert!(matches!(
+            failing,
+            Err(PulsarError::SurfaceUnsupported { .. })
+        ));
+        assert_eq!(tries.get(), SURFACE_QUERY_ATTEMPTS);
+
+        let tries = Cell::new(0);
+        let capabilities = query_surface(
+            loader(vec![Ok(vec![1])]),
+            || {
+                tries.set(tries.get() + 1);
+                Err::<(), _>("ERROR_SURFACE_LOST_KHR")
+            },
+            first,
+        );
+        assert!(matches!(
+            capabilities,
+            Err(PulsarError::SurfaceUnsupported { .. })
+        ));
+        assert_eq!(tries.get(), SURFACE_QUERY_ATTEMPTS);
+    }
+}
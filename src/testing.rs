@@ -0,0 +1,295 @@
+//! Reproducible scenes for stress runs, benchmarks and regression checks. Everything is plain CPU
+//! math without trigonometry, the same seed gives bit identical scenes on every machine.
+
+use crate::{handles::MeshHandle, model::Mesh};
+use glam::{Mat4, Quat, Vec2, Vec3};
+
+/// Handle of the mesh in `index`, for checks of the queues and registries that never reach a
+/// renderer.
+pub fn mesh_handle(index: usize, generation: u32) -> MeshHandle {
+    MeshHandle::new(index, generation)
+}
+
+/// SplitMix64, small and fully specified so recorded seeds never change meaning with a dependency.
+#[derive(Debug, Clone)]
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, 1)` from the top 24 bits, exact in an f32.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+
+    /// Uniform in `0..count`, `count` must not be zero.
+    pub fn below(&mut self, count: u64) -> u64 {
+        self.next_u64() % count
+    }
+
+    /// Uniform rotation, a point of the 4D unit ball normalized. Rejecting the corners keeps it unbiased.
+    pub fn rotation(&mut self) -> Quat {
+        loop {
+            let q = glam::Vec4::new(
+                self.range(-1.0, 1.0),
+                self.range(-1.0, 1.0),
+                self.range(-1.0, 1.0),
+                self.range(-1.0, 1.0),
+            );
+            let length_squared = q.length_squared();
+            if (1e-4..=1.0).contains(&length_squared) {
+                return Quat::from_vec4(q / length_squared.sqrt());
+            }
+        }
+    }
+}
+
+/// Built-in primitive a generated object is drawn with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Primitive {
+    Cube,
+    Sphere,
+    Quad,
+    Plane,
+}
+
+impl Primitive {
+    pub const ALL: [Self; 4] = [Self::Cube, Self::Sphere, Self::Quad, Self::Plane];
+
+    /// Mesh of unit `size` around the origin, see the `Mesh` generators.
+    pub fn mesh(self, color: [f32; 4]) -> Mesh {
+        match self {
+            Self::Cube => Mesh::cube(1.0, color),
+            Self::Sphere => Mesh::uv_sphere(0.5, 16, 8, color),
+            Self::Quad => Mesh::quad(Vec2::ONE, color),
+            Self::Plane => Mesh::plane(1.0, 1.0, 3, color),
+        }
+    }
+}
+
+/// One object of a generated scene, the transform already includes its size.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratedObject {
+    pub primitive: Primitive,
+    pub transform: Mat4,
+    pub size: f32,
+    pub color: [f32; 4],
+    /// Index in the caller's material list, below [`SceneGenerator::materials`].
+    pub material: usize,
+    /// Bit per layer, at least one set, below [`SceneGenerator::layers`]. Nothing filters on
+    /// layers yet, culling and passes will.
+    pub layer_mask: u32,
+}
+
+impl GeneratedObject {
+    pub fn mesh(&self) -> Mesh {
+        let mut mesh = self.primitive.mesh(self.color);
+        mesh.transform = self.transform;
+        mesh
+    }
+}
+
+/// Closed Catmull-Rom spline through seeded waypoints, for camera flythroughs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraPath {
+    pub waypoints: Vec<Vec3>,
+    /// Point the camera keeps looking at.
+    pub target: Vec3,
+}
+
+impl CameraPath {
+    /// Position at `t`, one unit of `t` per waypoint, wrapping around.
+    pub fn position(&self, t: f32) -> Vec3 {
+        let count = self.waypoints.len();
+        match count {
+            0 => return self.target,
+            1 => return self.waypoints[0],
+            _ => {}
+        }
+        let t = t.rem_euclid(count as f32);
+        let segment = (t as usize).min(count - 1);
+        let local = t - segment as f32;
+        let point = |offset: usize| self.waypoints[(segment + count + offset - 1) % count];
+        let (p0, p1, p2, p3) = (point(0), point(1), point(2), point(3));
+        let (l2, l3) = (local * local, local * local * local);
+        0.5 * (2.0 * p1
+            + (p2 - p0) * local
+            + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * l2
+            + (3.0 * p1 - p0 - 3.0 * p2 + p3) * l3)
+    }
+
+    /// View matrix at `t`, looking at the target with `up`.
+    pub fn view(&self, t: f32, up: Vec3) -> Mat4 {
+        Mat4::look_at_rh(self.position(t), self.target, up)
+    }
+}
+
+/// Seeded source of scenes: "seed 42, 5000 objects" is the same scene everywhere.
+/// Parameters are public, changing them changes the scenes of every seed.
+#[derive(Debug, Clone)]
+pub struct SceneGenerator {
+    rng: SeededRng,
+    /// Objects are spread in a cube of this half extent around the origin.
+    pub extent: f32,
+    pub min_size: f32,
+    pub max_size: f32,
+    pub materials: usize,
+    pub layers: u32,
+}
+
+impl SceneGenerator {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: SeededRng::new(seed),
+            extent: 100.0,
+            min_size: 0.25,
+            max_size: 4.0,
+            materials: 8,
+            layers: 4,
+        }
+    }
+
+    pub fn object(&mut self) -> GeneratedObject {
+        let rng = &mut self.rng;
+        let primitive = Primitive::ALL[rng.below(Primitive::ALL.len() as u64) as usize];
+        let translation = Vec3::new(
+            rng.range(-self.extent, self.extent),
+            rng.range(-self.extent, self.extent),
+            rng.range(-self.extent, self.extent),
+        );
+        let rotation = rng.rotation();
+        let size = rng.range(self.min_size, self.max_size);
+        let color = [rng.next_f32(), rng.next_f32(), rng.next_f32(), 1.0];
+        let material = rng.below(self.materials.max(1) as u64) as usize;
+        let layers = self.layers.clamp(1, 32);
+        let all_layers = u32::MAX >> (32 - layers);
+        let layer_mask = (rng.next_u64() as u32 & all_layers).max(1);
+        GeneratedObject {
+            primitive,
+            transform: Mat4::from_scale_rotation_translation(
+                Vec3::splat(size),
+                rotation,
+                translation,
+            ),
+            size,
+            color,
+            material,
+            layer_mask,
+        }
+    }
+
+    pub fn objects(&mut self, count: usize) -> Vec<GeneratedObject> {
+        (0..count).map(|_| self.object()).collect()
+    }
+
+    /// Meshes of `count` objects, building them dominates the time for large counts.
+    pub fn meshes(&mut self, count: usize) -> Vec<Mesh> {
+        (0..count).map(|_| self.object().mesh()).collect()
+    }
+
+    /// Waypoints on a jittered ring around the scene, looking at its center.
+    pub fn camera_path(&mut self, waypoints: usize) -> CameraPath {
+        let radius = self.extent * 1.5;
+        let count = waypoints.max(1);
+        let waypoints = (0..waypoints)
+            .map(|i| {
+                // Evenly around the unit square then normalized, no trigonometry
+                let along = (i * 4 % count) as f32 / count as f32 * 2.0 - 1.0;
+                let edge = match i * 4 / count {
+                    0 => Vec2::new(along, -1.0),
+                    1 => Vec2::new(1.0, along),
+                    2 => Vec2::new(-along, 1.0),
+                    _ => Vec2::new(-1.0, -along),
+                };
+                let around = edge.normalize() * radius * self.rng.range(0.8, 1.2);
+                Vec3::new(around.x, self.rng.range(-0.3, 0.3) * radius, around.y)
+            })
+            .collect();
+        CameraPath {
+            waypoints,
+            target: Vec3::ZERO,
+        }
+    }
+}
+
+/// FNV-1a over the exact bits of the objects, to pin generated scenes against accidental changes.
+pub fn scene_hash(objects: &[GeneratedObject]) -> u64 {
+    let mut hash = 0xCBF2_9CE4_8422_2325u64;
+    let mut write = |value: u32| {
+        for byte in value.to_le_bytes() {
+            hash = (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01B3);
+        }
+    };
+    for object in objects {
+        write(object.primitive as u32);
+        for value in object.transform.to_cols_array() {
+            write(value.to_bits());
+        }
+        write(object.size.to_bits());
+        for value in object.color {
+            write(value.to_bits());
+        }
+        write(object.material as u32);
+        write(object.layer_mask);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Aggregate hashes of `SceneGenerator::new(seed).objects(count)`. Recorded benchmarks and
+    /// regression captures reference these scenes, update the pins only with a deliberate change.
+    const PINNED: [(u64, usize, u64); 3] = [
+        (0, 100, 0x2321cf61c9a8aee0),
+        (42, 5000, 0x1b78f4b068b6fa32),
+        (1234, 1000, 0x6ce0c11fe8235dce),
+    ];
+
+    #[test]
+    fn seeds_give_the_pinned_scenes() {
+        for (seed, count, expected) in PINNED {
+            let hash = scene_hash(&SceneGenerator::new(seed).objects(count));
+            assert_eq!(hash, expected, "seed {seed} with {count} objects");
+        }
+    }
+
+    #[test]
+    fn scenes_follow_their_seed_and_parameters() {
+        let first = SceneGenerator::new(7).objects(64);
+        assert_eq!(first, SceneGenerator::new(7).objects(64));
+        assert_ne!(first, SceneGenerator::new(8).objects(64));
+        let mut generator = SceneGenerator::new(7);
+        for object in generator.objects(1000) {
+            assert!(object.material < generator.materials, "{object:?}");
+            assert_ne!(object.layer_mask, 0, "{object:?}");
+            assert_eq!(object.layer_mask >> generator.layers, 0, "{object:?}");
+        }
+    }
+
+    #[test]
+    fn camera_path_loops_through_its_waypoints() {
+        let path = SceneGenerator::new(42).camera_path(8);
+        for (i, waypoint) in path.waypoints.iter().enumerate() {
+            assert!(path.position(i as f32).distance(*waypoint) < 1e-3, "{i}");
+        }
+        assert!(path.position(8.0).distance(path.position(0.0)) < 1e-3);
+    }
+}
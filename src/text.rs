@@ -0,0 +1,378 @@
+//! Text measurement and line layout, pure CPU so the UI can place things before anything is drawn.
+use glam::Vec2;
+use std::sync::Arc;
+
+/// Horizontal metrics of a font in em units, the layout scales them by the pixel size.
+pub trait FontMetrics: Send + Sync {
+    /// Advance of `c`, `None` when the font has no glyph for it.
+    fn advance(&self, c: char) -> Option<f32>;
+    /// Height above the baseline, positive.
+    fn ascender(&self) -> f32;
+    /// Depth below the baseline, negative.
+    fn descender(&self) -> f32;
+    fn line_gap(&self) -> f32;
+}
+
+/// Fonts tried in order for every character, the first one sets the line metrics.
+#[derive(Clone)]
+pub struct FontStack {
+    fonts: Vec<Arc<dyn FontMetrics>>,
+}
+
+impl FontStack {
+    pub fn new(primary: Arc<dyn FontMetrics>) -> Self {
+        Self {
+            fonts: vec![primary],
+        }
+    }
+
+    pub fn with_fallback(mut self, font: Arc<dyn FontMetrics>) -> Self {
+        self.fonts.push(font);
+        self
+    }
+
+    /// Index of the first font with a glyph for `c` and its advance. Characters no font has
+    /// take the primary font's replacement character.
+    pub fn resolve(&self, c: char) -> (usize, f32) {
+        self.fonts
+            .iter()
+            .enumerate()
+            .find_map(|(index, font)| font.advance(c).map(|advance| (index, advance)))
+            .unwrap_or_else(|| {
+                let primary = &self.fonts[0];
+                let advance = primary
+                    .advance(char::REPLACEMENT_CHARACTER)
+                    .or_else(|| primary.advance('?'))
+                    .unwrap_or(0.5);
+                (0, advance)
+            })
+    }
+
+    fn primary(&self) -> &dyn FontMetrics {
+        self.fonts[0].as_ref()
+    }
+}
+
+/// A laid out character, in pixels from the top left of the text block.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlyphRect {
+    /// Byte index of the character in the text.
+    pub index: usize,
+    pub character: char,
+    /// Font of the stack the glyph comes from.
+    pub font: usize,
+    pub line: usize,
+    pub position: Vec2,
+    /// Advance by the line height.
+    pub size: Vec2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineMetrics {
+    /// Byte range of the line, including its trailing whitespace but not the line break.
+    pub start: usize,
+    pub end: usize,
+    /// Without the trailing whitespace.
+    pub width: f32,
+    pub top: f32,
+    pub baseline: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TextMetrics {
+    /// Widest line, trailing whitespace excluded.
+    pub width: f32,
+    pub height: f32,
+    pub line_count: usize,
+    pub line_height: f32,
+    /// Pixels above the baseline, positive.
+    pub ascender: f32,
+    /// Pixels below the baseline, negative.
+    pub descender: f32,
+    /// From the top of the block to the first baseline.
+    pub baseline: f32,
+    pub lines: Vec<LineMetrics>,
+    /// Every character but line breaks.
+    pub glyphs: Vec<GlyphRect>,
+}
+
+impl TextMetrics {
+    /// Offset that centers the text vertically on `center`, ascender and descender included.
+    pub fn vertical_center_offset(&self, center: f32) -> f32 {
+        center - self.height / 2.0
+    }
+}
+
+/// Text measured once, for the UI to place and for the glyph mesh to be built from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextLayout {
+    pub text: String,
+    pub px_size: f32,
+    pub metrics: TextMetrics,
+}
+
+/// Character waiting for its line to be closed.
+#[derive(Clone, Copy)]
+struct Pending {
+    index: usize,
+    character: char,
+    font: usize,
+    advance: f32,
+}
+
+impl TextLayout {
+    /// Lines break on `\n` and, when `max_width` is set, after the last whitespace that keeps
+    /// the line within it. Whitespace hangs past the edge, words longer than a line are cut.
+    pub fn new(fonts: &FontStack, text: &str, px_size: f32, max_width: Option<f32>) -> Self {
+        let primary = fonts.primary();
+        let ascender = primary.ascender() * px_size;
+        let descender = primary.descender() * px_size;
+        let line_height = ascender - descender + primary.line_gap() * px_size;
+        let mut metrics = TextMetrics {
+            line_height,
+            ascender,
+            descender,
+            baseline: ascender,
+            ..Default::default()
+        };
+
+        let mut paragraph_start = 0;
+        for paragraph in text.split('\n') {
+            let mut pending: Vec<Pending> = Vec::new();
+            let mut line_start = paragraph_start;
+            let mut x = 0.0;
+            // Characters of `pending` before the last wrap opportunity
+            let mut break_at = None;
+            for (offset, character) in paragraph.char_indices() {
+                let (font, advance) = fonts.resolve(character);
+                let advance = advance * px_size;
+                let overflows = max_width.is_some_and(|max| x + advance > max);
+                if overflows && !character.is_whitespace() && !pending.is_empty() {
+                    let split = break_at.unwrap_or(pending.len());
+                    let rest = pending.split_off(split);
+                    let next_start = rest
+                        .first()
+                        .map_or(paragraph_start + offset, |glyph| glyph.index);
+                    close_line(&mut metrics, &pending, line_start, next_start);
+                    line_start = next_start;
+                    x = rest.iter().map(|glyph| glyph.advance).sum();
+                    pending = rest;
+                    break_at = None;
+                }
+                pending.push(Pending {
+                    index: paragraph_start + offset,
+                    character,
+                    font,
+                    advance,
+                });
+                x += advance;
+                if character.is_whitespace() {
+                    break_at = Some(pending.len());
+                }
+            }
+            let paragraph_end = paragraph_start + paragraph.len();
+            close_line(&mut metrics, &pending, line_start, paragraph_end);
+            paragraph_start = paragraph_end + 1;
+        }
+
+        metrics.line_count = metrics.lines.len();
+        metrics.height = line_height * metrics.line_count as f32;
+        metrics.width = metrics
+            .lines
+            .iter()
+            .map(|line| line.width)
+            .fold(0.0, f32::max);
+        Self {
+            text: text.to_string(),
+            px_size,
+            metrics,
+        }
+    }
+
+    /// Metrics only, when the layout itself is not kept.
+    pub fn measure(
+        fonts: &FontStack,
+        text: &str,
+        px_size: f32,
+        max_width: Option<f32>,
+    ) -> TextMetrics {
+        Self::new(fonts, text, px_size, max_width).metrics
+    }
+
+    /// Top of the caret placed before the character at byte `index`, clamped to the text.
+    pub fn caret_position(&self, index: usize) -> Vec2 {
+        let index = index.min(self.text.len());
+        let lines = &self.metrics.lines;
+        // A wrapped line ends where the next starts, the caret goes to the start of the next
+        let line = lines
+            .iter()
+            .rposition(|line| line.start <= index)
+            .unwrap_or(0);
+        let line_metrics = lines[line];
+        let x = self
+            .metrics
+            .glyphs
+            .iter()
+            .filter(|glyph| glyph.line == line && glyph.index < index)
+            .map(|glyph| glyph.size.x)
+            .sum();
+        Vec2::new(x, line_metrics.top)
+    }
+
+    /// Byte index of the caret position closest to `point`.
+    pub fn index_from_point(&self, point: Vec2) -> usize {
+        let lines = &self.metrics.lines;
+        let line =
+            ((point.y / self.metrics.line_height).floor().max(0.0) as usize).min(lines.len() - 1);
+        self.metrics
+            .glyphs
+            .iter()
+            .filter(|glyph| glyph.line == line)
+            .find(|glyph| point.x < glyph.position.x + glyph.size.x / 2.0)
+            .map_or(lines[line].end, |glyph| glyph.index)
+    }
+}
+
+fn close_line(metrics: &mut TextMetrics, pending: &[Pending], start: usize, end: usize) {
+    let line = metrics.lines.len();
+    let top = line as f32 * metrics.line_height;
+    let glyph_height = metrics.ascender - metrics.descender;
+    let mut x = 0.0;
+    let mut width = 0.0;
+    for glyph in pending {
+        metrics.glyphs.push(GlyphRect {
+            index: glyph.index,
+            character: glyph.character,
+            font: glyph.font,
+            line,
+            position: Vec2::new(x, top),
+            size: Vec2::new(glyph.advance, glyph_height),
+        });
+        x += glyph.advance;
+        if !glyph.character.is_whitespace() {
+            width = x;
+        }
+    }
+    metrics.lines.push(LineMetrics {
+        start,
+        end,
+        width,
+        top,
+        baseline: top + metrics.ascender,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PX: f32 = 10.0;
+
+    /// Half an em per ASCII character.
+    struct Latin;
+
+    impl FontMetrics for Latin {
+        fn advance(&self, c: char) -> Option<f32> {
+            c.is_ascii().then_some(0.5)
+        }
+        fn ascender(&self) -> f32 {
+            0.8
+        }
+        fn descender(&self) -> f32 {
+            -0.2
+        }
+        fn line_gap(&self) -> f32 {
+            0.2
+        }
+    }
+
+    /// Full width ideographs only.
+    struct Ideographs;
+
+    impl FontMetrics for Ideographs {
+        fn advance(&self, c: char) -> Option<f32> {
+            ('\u{4E00}'..='\u{9FFF}').contains(&c).then_some(1.0)
+        }
+        fn ascender(&self) -> f32 {
+            0.9
+        }
+        fn descender(&self) -> f32 {
+            -0.1
+        }
+        fn line_gap(&self) -> f32 {
+            0.0
+        }
+    }
+
+    fn fonts() -> FontStack {
+        FontStack::new(Arc::new(Latin)).with_fallback(Arc::new(Ideographs))
+    }
+
+    /// 5 px per character, 40 px fits 8 characters.
+    fn wrapped() -> TextLayout {
+        TextLayout::new(&fonts(), "hello big world", PX, Some(40.0))
+    }
+
+    #[test]
+    fn empty_text_has_one_line() {
+        let empty = TextLayout::measure(&fonts(), "", PX, None);
+        assert_eq!(empty.width, 0.0);
+        assert_eq!(empty.line_count, 1);
+        assert!(empty.glyphs.is_empty());
+        assert_eq!(
+            (empty.height, empty.baseline, empty.descender),
+            (12.0, 8.0, -2.0)
+        );
+    }
+
+    #[test]
+    fn wrapping_and_whitespace() {
+        let fonts = fonts();
+        let wrapped = wrapped();
+        let lines: Vec<&str> = wrapped
+            .metrics
+            .lines
+            .iter()
+            .map(|line| &wrapped.text[line.start..line.end])
+            .collect();
+        assert_eq!(lines, ["hello ", "big ", "world"]);
+        // Trailing whitespace counts towards the width
+        assert_eq!(wrapped.metrics.lines[0].width, 25.0);
+        assert_eq!(wrapped.metrics.width, 25.0);
+
+        let trailing = TextLayout::measure(&fonts, "ab   ", PX, Some(15.0));
+        assert_eq!((trailing.line_count, trailing.width), (1, 10.0));
+        // A word longer than the line is cut
+        assert_eq!(
+            TextLayout::measure(&fonts, "abcdefgh", PX, Some(15.0)).line_count,
+            3
+        );
+        let explicit = TextLayout::measure(&fonts, "a\n\nb", PX, None);
+        assert_eq!((explicit.line_count, explicit.glyphs.len()), (3, 2));
+    }
+
+    #[test]
+    fn fallback_runs_use_their_own_advance() {
+        let mixed = TextLayout::measure(&fonts(), "a中b", PX, None);
+        let fonts_used: Vec<usize> = mixed.glyphs.iter().map(|glyph| glyph.font).collect();
+        assert_eq!(fonts_used, [0, 1, 0]);
+        assert_eq!(mixed.width, 20.0);
+        assert_eq!(mixed.glyphs[2].position.x, 15.0);
+    }
+
+    #[test]
+    fn carets_and_hit_testing_agree() {
+        let wrapped = wrapped();
+        for layout in [&wrapped, &TextLayout::new(&fonts(), "a中b\nxy", PX, None)] {
+            for (index, _) in layout.text.char_indices() {
+                let caret = layout.caret_position(index);
+                let hit = layout.index_from_point(caret + Vec2::new(0.1, 1.0));
+                assert_eq!(hit, index, "caret in {:?}", layout.text);
+            }
+        }
+        assert_eq!(
+            wrapped.caret_position(wrapped.text.len()),
+            Vec2::new(25.0, 24.0)
+        );
+    }
+}
@@ -0,0 +1,380 @@
+//! Texture content updates posted by the application, copied by the render thread before the next frame.
+use crate::error::PulsarError;
+use ash::vk;
+use std::{error::Error, path::PathBuf};
+
+/// Bytes per RGBA8 texel, the format of every texture but the block compressed ones.
+pub const TEXEL_SIZE: u32 = 4;
+
+/// Texels of a texture, from the top left corner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl TextureRegion {
+    /// A whole texture of `width` by `height`.
+    pub fn full(width: u32, height: u32) -> Self {
+        Self {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        }
+    }
+
+    pub fn contains(&self, other: &Self) -> bool {
+        other.x >= self.x
+            && other.y >= self.y
+            && other.x + other.width <= self.x + self.width
+            && other.y + other.height <= self.y + self.height
+    }
+}
+
+/// RGBA8 texels written over `region`, rows `row_pitch` bytes apart in `data`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextureUpdate {
+    pub region: TextureRegion,
+    pub data: Vec<u8>,
+    pub row_pitch: u32,
+}
+
+impl TextureUpdate {
+    /// Rows tightly packed, `width * 4` bytes each.
+    pub fn new(region: TextureRegion, data: Vec<u8>) -> Result<Self, Box<dyn Error>> {
+        Self::with_row_pitch(region, data, region.width * TEXEL_SIZE)
+    }
+
+    /// Rows `row_pitch` bytes apart, whole texels and at least a row wide. The padding after the
+    /// last row may be left out.
+    pub fn with_row_pitch(
+        region: TextureRegion,
+        data: Vec<u8>,
+        row_pitch: u32,
+    ) -> Result<Self, Box<dyn Error>> {
+        if region.width == 0 || region.height == 0 {
+            return Err("Empty texture region".into());
+        }
+        if !row_pitch.is_multiple_of(TEXEL_SIZE) || row_pitch < region.width * TEXEL_SIZE {
+            return Err(format!(
+                "Row pitch of {row_pitch} bytes for rows of {} texels",
+                region.width
+            )
+            .into());
+        }
+        let update = Self {
+            region,
+            data,
+            row_pitch,
+        };
+        if update.data.len() < update.size_bytes() {
+            return Err(format!(
+                "{} bytes of texels for a {}x{} region, expected {}",
+                update.data.len(),
+                region.width,
+                region.height,
+                update.size_bytes()
+            )
+            .into());
+        }
+        Ok(update)
+    }
+
+    /// Bytes read from `data`, the last row without its padding.
+    pub fn size_bytes(&self) -> usize {
+        (self.row_pitch * (self.region.height - 1) + self.region.width * TEXEL_SIZE) as usize
+    }
+
+    /// Texels from one row to the next, as the copy expects it.
+    pub fn row_length(&self) -> u32 {
+        self.row_pitch / TEXEL_SIZE
+    }
+}
+
+/// Updates still worth copying: everything before the last update covering the whole texture
+/// would be overwritten by it. Keeps a video producer ahead of the renderer from piling up frames.
+pub fn coalesce(updates: &mut Vec<TextureUpdate>, extent: TextureRegion) {
+    if let Some(last_full) = updates
+        .iter()
+        .rposition(|update| update.region.contains(&extent))
+    {
+        updates.drain(..last_full);
+    }
+}
+
+/// How a texture is sampled. Samplers are shared between the textures asking for the same one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SamplerDesc {
+    pub mag: vk::Filter,
+    pub min: vk::Filter,
+    pub mipmap: vk::SamplerMipmapMode,
+    /// The same along every axis.
+    pub address_mode: vk::SamplerAddressMode,
+    /// Largest anisotropy, 1 or less disables it. Clamped to what the device supports, and
+    /// ignored without the `samplerAnisotropy` feature.
+    pub anisotropy: f32,
+    /// Depth comparison for shadow lookups, `None` returns the texels.
+    pub compare: Option<vk::CompareOp>,
+}
+
+impl Default for SamplerDesc {
+    /// Linear filtering between texels and mip levels, mirrored past the edges.
+    fn default() -> Self {
+        Self {
+            mag: vk::Filter::LINEAR,
+            min: vk::Filter::LINEAR,
+            mipmap: vk::SamplerMipmapMode::LINEAR,
+            address_mode: vk::SamplerAddressMode::MIRRORED_REPEAT,
+            anisotropy: 1.0,
+            compare: None,
+        }
+    }
+}
+
+impl SamplerDesc {
+    /// Nearest texel and mip level, the crisp blocks of pixel art.
+    pub fn nearest() -> Self {
+        Self {
+            mag: vk::Filter::NEAREST,
+            min: vk::Filter::NEAREST,
+            mipmap: vk::SamplerMipmapMode::NEAREST,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_address_mode(mut self, address_mode: vk::SamplerAddressMode) -> Self {
+        self.address_mode = address_mode;
+        self
+    }
+
+    pub fn with_anisotropy(mut self, anisotropy: f32) -> Self {
+        self.anisotropy = anisotropy;
+        self
+    }
+}
+
+/// Texture array of `layers` same sized layers, meshes pick theirs with `MeshUpdate::TextureLayer`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureArrayDesc {
+    pub width: u32,
+    pub height: u32,
+    /// At most the device's `maxImageArrayLayers`, 2048 on most desktop GPUs.
+    pub layers: u32,
+    pub sampler: SamplerDesc,
+}
+
+/// Faces of a cubemap, in layer order: +X, -X, +Y, -Y, +Z, -Z.
+pub const CUBE_FACES: usize = 6;
+
+/// Image files of a cubemap, decoded to RGBA8 whatever their format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CubemapSource {
+    /// One square image per face, in layer order.
+    Faces([PathBuf; CUBE_FACES]),
+    /// Every face in one image, sliced by [`slice_cube_layout`].
+    Layout(PathBuf),
+}
+
+/// Edge of the faces of `sizes`, each a face's width and height in layer order. A face that is not
+/// square or not the size of the first is a [`PulsarError::CubemapFace`].
+pub fn cube_face_size(sizes: [(u32, u32); CUBE_FACES]) -> Result<u32, PulsarError> {
+    let size = sizes[0].0;
+    for (face, &(width, height)) in sizes.iter().enumerate() {
+        if width != size || height != size || size == 0 {
+            return Err(PulsarError::CubemapFace {
+                face: face as u32,
+                width,
+                height,
+                size,
+            });
+        }
+    }
+    Ok(size)
+}
+
+/// Edge of the faces and the faces, in layer order, cut out of the RGBA8 `texels` of a `width` by `height` image laid out
+/// as a horizontal cross (4 by 3 faces, +Y above and -Y under +Z), a vertical cross (3 by 4, -Z
+/// under -Y upside down) or a strip of the faces in layer order (6 by 1 or 1 by 6). Other aspect
+/// ratios are a [`PulsarError::CubemapLayout`].
+pub fn slice_cube_layout(
+    texels: &[u8],
+    width: u32,
+    height: u32,
+) -> Result<(u32, [Vec<u8>; CUBE_FACES]), PulsarError> {
+    // Column and row of each face in the layout, and whether it is stored upside down
+    let (size, cells, flipped_face) = if 3 * width == 4 * height {
+        let cells = [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (3, 1)];
+        (height / 3, cells, None)
+    } else if 4 * width == 3 * height {
+        let cells = [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (1, 3)];
+        (width / 3, cells, Some(5))
+    } else if width == 6 * height {
+        (height, [0, 1, 2, 3, 4, 5].map(|face| (face, 0)), None)
+    } else if height == 6 * width {
+        (width, [0, 1, 2, 3, 4, 5].map(|face| (0, face)), None)
+    } else {
+        return Err(PulsarError::CubemapLayout { width, height });
+    };
+    if size == 0 || texels.len() != (width * height * TEXEL_SIZE) as usize {
+        return Err(PulsarError::CubemapLayout { width, height });
+    }
+
+    let texel = TEXEL_SIZE as usize;
+    let (edge, row_length) = (size as usize, width as usize * texel);
+    let mut face = 0;
+    let faces = cells.map(|(column, row)| {
+        let mut data = Vec::with_capacity(edge * edge * texel);
+        for y in 0..edge {
+            let start = (row * edge + y) * row_length + column * edge * texel;
+            data.extend_from_slice(&texels[start..start + edge * texel]);
+        }
+        if flipped_face == Some(face) {
+            // Upside down is the texel order reversed
+            data = data.chunks_exact(texel).rev().flatten().copied().collect();
+        }
+        face += 1;
+        data
+    });
+    Ok((size, faces))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: u32 = 256;
+    /// Rows padded to this many texels, like a decoder's output.
+    const PADDED_WIDTH: u32 = SIZE + 64;
+
+    fn band(y: u32) -> TextureUpdate {
+        let region = TextureRegion {
+            x: 0,
+            y,
+            width: SIZE,
+            height: 8,
+        };
+        TextureUpdate::new(region, vec![0; (SIZE * 8 * TEXEL_SIZE) as usize]).unwrap()
+    }
+
+    #[test]
+    fn malformed_updates_are_refused() {
+        let texture = TextureRegion::full(SIZE, SIZE);
+        assert!(TextureUpdate::new(texture, vec![0; 10]).is_err());
+        let pixels = vec![0; (SIZE * SIZE * TEXEL_SIZE) as usize];
+        assert!(TextureUpdate::with_row_pitch(texture, pixels, SIZE * TEXEL_SIZE - 1).is_err());
+    }
+
+    #[test]
+    fn padded_rows_keep_their_pitch() {
+        let pixels = vec![0; (PADDED_WIDTH * SIZE * TEXEL_SIZE) as usize];
+        let full = TextureUpdate::with_row_pitch(
+            TextureRegion::full(SIZE, SIZE),
+            pixels,
+            PADDED_WIDTH * TEXEL_SIZE,
+        )
+        .unwrap();
+        assert_eq!(full.row_length(), PADDED_WIDTH);
+    }
+
+    #[test]
+    fn full_update_drops_the_earlier_ones() {
+        let texture = TextureRegion::full(SIZE, SIZE);
+        let full =
+            TextureUpdate::new(texture, vec![0; (SIZE * SIZE * TEXEL_SIZE) as usize]).unwrap();
+        let mut queued = vec![band(8), full.clone(), band(16)];
+        coalesce(&mut queued, texture);
+        assert_eq!(queued, [full, band(16)]);
+    }
+
+    const FACE_SIZE: u32 = 8;
+
+    /// Texel `index` of `face`, telling both apart once sliced.
+    fn face_texel(face: usize, index: u32) -> [u8; 4] {
+        [
+            face as u8 * 40,
+            (index % 256) as u8,
+            (index / 256) as u8,
+            255,
+        ]
+    }
+
+    /// `columns` by `rows` faces with `cells` holding the face at each column and row, the others
+    /// black. The `flipped` face is stored upside down.
+    fn cube_layout(
+        columns: u32,
+        rows: u32,
+        cells: [(u32, u32); CUBE_FACES],
+        flipped: Option<usize>,
+    ) -> Vec<u8> {
+        let (width, height) = (columns * FACE_SIZE, rows * FACE_SIZE);
+        let mut texels = vec![0; (width * height * 4) as usize];
+        for (face, (column, row)) in cells.into_iter().enumerate() {
+            for index in 0..FACE_SIZE * FACE_SIZE {
+                let stored = if flipped == Some(face) {
+                    FACE_SIZE * FACE_SIZE - 1 - index
+                } else {
+                    index
+                };
+                let x = column * FACE_SIZE + stored % FACE_SIZE;
+                let y = row * FACE_SIZE + stored / FACE_SIZE;
+                let start = ((y * width + x) * 4) as usize;
+                texels[start..start + 4].copy_from_slice(&face_texel(face, index));
+            }
+        }
+        texels
+    }
+
+    #[test]
+    fn every_cube_layout_slices_into_its_faces() {
+        let strip = [0, 1, 2, 3, 4, 5];
+        let layouts = [
+            (
+                "horizontal cross",
+                4,
+                3,
+                [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (3, 1)],
+                None,
+            ),
+            (
+                "vertical cross",
+                3,
+                4,
+                [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (1, 3)],
+                Some(5),
+            ),
+            ("horizontal strip", 6, 1, strip.map(|face| (face, 0)), None),
+            ("vertical strip", 1, 6, strip.map(|face| (0, face)), None),
+        ];
+        for (name, columns, rows, cells, flipped) in layouts {
+            let texels = cube_layout(columns, rows, cells, flipped);
+            let (size, faces) =
+                slice_cube_layout(&texels, columns * FACE_SIZE, rows * FACE_SIZE).unwrap();
+            assert_eq!(size, FACE_SIZE, "{name}");
+            for (face, data) in faces.iter().enumerate() {
+                let expected: Vec<u8> = (0..FACE_SIZE * FACE_SIZE)
+                    .flat_map(|index| face_texel(face, index))
+                    .collect();
+                assert_eq!(*data, expected, "{name}: face {face}");
+            }
+        }
+    }
+
+    #[test]
+    fn mismatched_faces_and_unknown_layouts_are_refused() {
+        let mut sizes = [(FACE_SIZE, FACE_SIZE); CUBE_FACES];
+        sizes[3] = (FACE_SIZE, FACE_SIZE / 2);
+        let mismatch = cube_face_size(sizes);
+        assert!(
+            matches!(mismatch, Err(PulsarError::CubemapFace { face: 3, .. })),
+            "{mismatch:?}"
+        );
+        let unknown = slice_cube_layout(&[0; 5 * 4 * 4], 5, 4);
+        assert!(
+            matches!(unknown, Err(PulsarError::CubemapLayout { .. })),
+            "{unknown:?}"
+        );
+    }
+}
@@ -0,0 +1,233 @@
+//! Small previews of the window texture and of materials, rendered by the render thread a few per frame.
+
+use crate::material::Material;
+use std::sync::{Arc, Mutex};
+
+/// Thumbnails rendered per frame at most, the rest wait for the following frames.
+pub const THUMBNAILS_PER_FRAME: usize = 2;
+/// Thumbnails larger than this on a side are clamped to it.
+pub const THUMBNAIL_MAX_SIZE: u32 = 512;
+
+/// What a thumbnail shows.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ThumbnailTarget {
+    /// The window's texture, downsampled. Rendered once the texture received its first update.
+    Texture,
+    /// A lit sphere drawn with the material.
+    Material(Material),
+}
+
+/// RGBA8 pixels of a rendered thumbnail, `size` by `size`.
+#[derive(Debug, Clone)]
+pub struct ThumbnailImage {
+    pub size: u32,
+    pub rgba: Arc<Vec<u8>>,
+    /// Bumped every time the thumbnail is rendered again, upload the pixels again when it changes.
+    pub generation: u32,
+}
+
+/// Handle to a requested thumbnail, empty until the render thread renders it. Thumbnails nobody
+/// holds a handle to anymore are freed before the next frame.
+#[derive(Debug, Clone)]
+pub struct Thumbnail {
+    slot: Arc<ThumbnailSlot>,
+}
+
+#[derive(Debug)]
+struct ThumbnailSlot {
+    target: ThumbnailTarget,
+    size: u32,
+    state: Mutex<SlotState>,
+}
+
+#[derive(Debug, Default)]
+struct SlotState {
+    image: Option<ThumbnailImage>,
+    /// Rendered with a stand in, the fallback pipeline of a material still compiling.
+    provisional: bool,
+}
+
+impl Thumbnail {
+    pub fn target(&self) -> &ThumbnailTarget {
+        &self.slot.target
+    }
+
+    pub fn size(&self) -> u32 {
+        self.slot.size
+    }
+
+    /// Latest rendering, `None` before the first one.
+    pub fn image(&self) -> Option<ThumbnailImage> {
+        self.slot.state.lock().unwrap().image.clone()
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.slot.state.lock().unwrap().image.is_some()
+    }
+
+    /// Store the pixels the render thread read back.
+    pub(crate) fn complete(&self, rgba: Vec<u8>, provisional: bool) {
+        let mut state = self.slot.state.lock().unwrap();
+        let generation = state.image.as_ref().map_or(0, |image| image.generation + 1);
+        state.image = Some(ThumbnailImage {
+            size: self.slot.size,
+            rgba: Arc::new(rgba),
+            generation,
+        });
+        state.provisional = provisional;
+    }
+
+    pub(crate) fn is_provisional(&self) -> bool {
+        self.slot.state.lock().unwrap().provisional
+    }
+}
+
+/// Every live thumbnail of a window and the ones waiting to be rendered.
+#[derive(Debug, Default)]
+pub struct ThumbnailCache {
+    live: Vec<Thumbnail>,
+    /// Live thumbnails waiting to be rendered in request order, each at most once.
+    queue: Vec<Thumbnail>,
+}
+
+impl ThumbnailCache {
+    /// Handle to the thumbnail of `target` at `size`. Requesting one that is already live returns
+    /// the same handle, it is rendered once for every holder.
+    pub fn request(&mut self, target: ThumbnailTarget, size: u32) -> Thumbnail {
+        let size = size.clamp(1, THUMBNAIL_MAX_SIZE);
+        if let Some(thumbnail) = self
+            .live
+            .iter()
+            .find(|thumbnail| thumbnail.size() == size && *thumbnail.target() == target)
+        {
+            return thumbnail.clone();
+        }
+        let thumbnail = Thumbnail {
+            slot: Arc::new(ThumbnailSlot {
+                target,
+                size,
+                state: Mutex::new(SlotState::default()),
+            }),
+        };
+        self.live.push(thumbnail.clone());
+        self.queue.push(thumbnail.clone());
+        thumbnail
+    }
+
+    /// Up to [`THUMBNAILS_PER_FRAME`] thumbnails to render this frame, oldest requests first.
+    /// `ready` tells whether a target can be rendered now, the others keep their place in the queue.
+    pub fn next_batch(&mut self, ready: impl Fn(&ThumbnailTarget) -> bool) -> Vec<Thumbnail> {
+        let mut batch = Vec::new();
+        self.queue.retain(|thumbnail| {
+            if batch.len() < THUMBNAILS_PER_FRAME && ready(thumbnail.target()) {
+                batch.push(thumbnail.clone());
+                false
+            } else {
+                true
+            }
+        });
+        batch
+    }
+
+    /// Queue the live thumbnails matching `stale` to be rendered again, their current image stays
+    /// until then.
+    pub fn invalidate(&mut self, stale: impl Fn(&Thumbnail) -> bool) {
+        for thumbnail in &self.live {
+            let queued = self
+                .queue
+                .iter()
+                .any(|queued| Arc::ptr_eq(&queued.slot, &thumbnail.slot));
+            if !queued && stale(thumbnail) {
+                self.queue.push(thumbnail.clone());
+            }
+        }
+    }
+
+    /// Forget the thumbnails only the cache still holds, returns how many were freed.
+    pub fn collect_garbage(&mut self) -> usize {
+        let before = self.live.len();
+        self.queue
+            .retain(|thumbnail| Arc::strong_count(&thumbnail.slot) > 2);
+        // Queued ones were dropped above, what is left in `live` alone is unreferenced
+        self.live.retain(|thumbnail| {
+            let queued = self
+                .queue
+                .iter()
+                .any(|queued| Arc::ptr_eq(&queued.slot, &thumbnail.slot));
+            Arc::strong_count(&thumbnail.slot) > 1 + queued as usize
+        });
+        before - self.live.len()
+    }
+
+    /// Thumbnails waiting to be rendered.
+    pub fn queued(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.live.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.live.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Five materials and the texture requested three times at 64, the texture once at 128.
+    fn requests(cache: &mut ThumbnailCache) -> Vec<Thumbnail> {
+        let materials: Vec<Material> = (0..5)
+            .map(|i| Material::new(&format!("vert_{i}"), "frag"))
+            .collect();
+        let mut held = Vec::new();
+        for _ in 0..3 {
+            for material in &materials {
+                held.push(cache.request(ThumbnailTarget::Material(material.clone()), 64));
+            }
+            held.push(cache.request(ThumbnailTarget::Texture, 64));
+        }
+        held.push(cache.request(ThumbnailTarget::Texture, 128));
+        held
+    }
+
+    #[test]
+    fn requests_coalesce_and_drain_within_the_frame_budget() {
+        let mut cache = ThumbnailCache::default();
+        let _held = requests(&mut cache);
+        assert_eq!((cache.len(), cache.queued()), (7, 7));
+        let mut frames = 0;
+        while cache.queued() > 0 {
+            let batch = cache.next_batch(|_| true);
+            assert!(batch.len() <= THUMBNAILS_PER_FRAME, "{}", batch.len());
+            frames += 1;
+        }
+        assert!(frames <= 7_usize.div_ceil(THUMBNAILS_PER_FRAME), "{frames}");
+    }
+
+    #[test]
+    fn waiting_targets_keep_their_place_and_dropped_ones_are_freed() {
+        let mut cache = ThumbnailCache::default();
+        let mut held = requests(&mut cache);
+        while cache.queued() > 0 {
+            cache.next_batch(|_| true);
+        }
+
+        // A target that cannot render yet keeps its place without holding the others back
+        cache.invalidate(|_| true);
+        let waiting = cache.next_batch(|target| *target != ThumbnailTarget::Texture);
+        assert!(!waiting.is_empty());
+        assert!(waiting
+            .iter()
+            .all(|thumbnail| *thumbnail.target() != ThumbnailTarget::Texture));
+        assert_eq!(cache.queued(), 7 - waiting.len());
+
+        // Only the thumbnails the application dropped are freed
+        held.retain(|thumbnail| *thumbnail.target() == ThumbnailTarget::Texture);
+        drop(waiting);
+        assert_eq!(cache.collect_garbage(), 5);
+        assert_eq!(cache.len(), 2);
+    }
+}
@@ -0,0 +1,223 @@
+//! Simulation time of a window, advanced in fixed ticks so it can be slowed, paused and stepped
+//! for debugging while the camera and the rendering keep real time.
+//!
+//! Skinned animation and the shader background follow the simulation. Stepping `n` ticks while
+//! paused gives the same state as running `n` ticks, each tick advances by [`FIXED_TICK`] whatever
+//! the frame rate or the scale.
+
+use std::time::Duration;
+
+/// Simulation time advanced by one tick.
+pub const FIXED_TICK: Duration = Duration::from_nanos(1_000_000_000 / 60);
+/// Slowest and fastest [`TimeControl::scale`].
+pub const MIN_TIME_SCALE: f32 = 0.1;
+pub const MAX_TIME_SCALE: f32 = 4.0;
+/// Ticks run in a single frame at most, the time beyond is dropped rather than caught up.
+const MAX_TICKS_PER_FRAME: u32 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeControl {
+    /// Simulation seconds per real second, within [`MIN_TIME_SCALE`] and [`MAX_TIME_SCALE`].
+    pub scale: f32,
+    /// Frozen, only [`SimulationClock::step_once`] advances it.
+    pub paused: bool,
+}
+
+impl Default for TimeControl {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            paused: false,
+        }
+    }
+}
+
+impl TimeControl {
+    /// `scale` clamped to the supported range, for sliders.
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale.clamp(MIN_TIME_SCALE, MAX_TIME_SCALE);
+        self
+    }
+
+    pub fn with_paused(mut self, paused: bool) -> Self {
+        self.paused = paused;
+        self
+    }
+}
+
+/// Turns the real time between frames into fixed simulation ticks.
+#[derive(Debug, Clone, Default)]
+pub struct SimulationClock {
+    pub control: TimeControl,
+    /// Scaled time not yet run, less than a tick after each frame.
+    accumulator: Duration,
+    /// Ticks queued by `step_once` while paused.
+    steps: u32,
+    ticks: u64,
+}
+
+impl SimulationClock {
+    /// Ticks to run for a frame `real_delta` after the previous one.
+    pub fn advance(&mut self, real_delta: Duration) -> u32 {
+        let ticks = if self.control.paused {
+            std::mem::take(&mut self.steps)
+        } else {
+            self.accumulator += real_delta.mul_f32(self.control.scale.max(0.0));
+            let ticks = (self.accumulator.as_nanos() / FIXED_TICK.as_nanos()) as u32;
+            self.accumulator -= FIXED_TICK * ticks;
+            if ticks > MAX_TICKS_PER_FRAME {
+                self.accumulator = Duration::ZERO;
+            }
+            ticks.min(MAX_TICKS_PER_FRAME)
+        };
+        self.ticks += ticks as u64;
+        ticks
+    }
+
+    /// Queue exactly one tick for the next frame, ignored unless paused.
+    pub fn step_once(&mut self) {
+        if self.control.paused {
+            self.steps += 1;
+        }
+    }
+
+    /// Replace the scale and pause state. Resuming drops the steps not yet run.
+    pub fn set_control(&mut self, control: TimeControl) {
+        if !control.paused {
+            self.steps = 0;
+        }
+        self.control = control;
+    }
+
+    /// Ticks run since the clock started.
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /// Simulation seconds since the clock started.
+    pub fn time(&self) -> f64 {
+        FIXED_TICK.as_secs_f64() * self.ticks as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skinning::{
+        AnimationClip, AnimationPlayer, Channel, Interpolation, Joint, Keyframes, Skin,
+    };
+    use glam::{Mat4, Quat, Vec3};
+
+    const TICKS: u32 = 150;
+
+    /// A single joint sliding back and forth along +X, looping every 1.7 seconds.
+    fn slider() -> AnimationPlayer {
+        let skin = Skin {
+            joints: vec![Joint {
+                name: "root".into(),
+                parent: None,
+                origin: Mat4::IDENTITY,
+                translation: Vec3::ZERO,
+                rotation: Quat::IDENTITY,
+                scale: Vec3::ONE,
+                inverse_bind: Mat4::IDENTITY,
+            }],
+        };
+        let clip = AnimationClip::new(
+            "slide",
+            vec![Channel {
+                joint: 0,
+                interpolation: Interpolation::Linear,
+                keyframes: Keyframes::Translation(vec![
+                    (0.0, Vec3::ZERO),
+                    (0.85, 3.0 * Vec3::X),
+                    (1.7, Vec3::ZERO),
+                ]),
+            }],
+        );
+        let player = AnimationPlayer::new(skin, vec![clip]);
+        player.play("slide");
+        player.set_looping(true);
+        player
+    }
+
+    /// Advance `player` as the render thread does, one fixed tick at a time.
+    fn run(clock: &mut SimulationClock, player: &AnimationPlayer, real_delta: Duration) -> u32 {
+        let ticks = clock.advance(real_delta);
+        for _ in 0..ticks {
+            player.advance(FIXED_TICK.as_secs_f32());
+        }
+        ticks
+    }
+
+    /// Uneven frame times, as a busy frame rate gives.
+    fn jittery_frame(frame: u32) -> Duration {
+        Duration::from_micros(9_000 + (frame * 7_919) as u64 % 15_000)
+    }
+
+    #[test]
+    fn stepping_poses_a_clip_as_running_does() {
+        let running = slider();
+        let mut clock = SimulationClock::default();
+        let mut frame = 0;
+        while clock.ticks() < TICKS as u64 {
+            let ticks = run(&mut clock, &running, jittery_frame(frame).min(FIXED_TICK));
+            assert!(ticks <= 1, "frame {frame} ran {ticks} ticks");
+            frame += 1;
+        }
+        assert_eq!(clock.ticks(), TICKS as u64);
+
+        // The same count stepped one at a time while paused, frames passing in between
+        let stepped = slider();
+        let mut clock = SimulationClock::default();
+        clock.set_control(TimeControl::default().with_paused(true));
+        for step in 0..TICKS {
+            assert_eq!(run(&mut clock, &stepped, jittery_frame(step)), 0);
+            clock.step_once();
+            assert_eq!(run(&mut clock, &stepped, jittery_frame(step)), 1);
+        }
+        assert_eq!(stepped.time(), running.time());
+        assert_eq!(stepped.bone_matrices(), running.bone_matrices());
+    }
+
+    #[test]
+    fn steps_only_run_while_paused() {
+        let mut clock = SimulationClock::default();
+        clock.step_once();
+        assert_eq!(clock.advance(Duration::ZERO), 0);
+        // A step queued before resuming is dropped
+        clock.set_control(TimeControl::default().with_paused(true));
+        clock.step_once();
+        clock.set_control(TimeControl::default());
+        assert_eq!(clock.advance(Duration::ZERO), 0);
+    }
+
+    #[test]
+    fn scaled_time_runs_its_share_of_ticks() {
+        for scale in [MIN_TIME_SCALE, 0.5, 1.0, 2.0, MAX_TIME_SCALE] {
+            let mut clock = SimulationClock::default();
+            clock.set_control(TimeControl::default().with_scale(scale));
+            for _ in 0..600 {
+                clock.advance(FIXED_TICK);
+            }
+            let ticks = clock.ticks();
+            assert!(
+                (ticks as f32 - 600.0 * scale).abs() <= 1.0,
+                "{ticks} ticks at {scale}x"
+            );
+        }
+        assert_eq!(
+            TimeControl::default().with_scale(100.0).scale,
+            MAX_TIME_SCALE
+        );
+        assert_eq!(TimeControl::default().with_scale(0.0).scale, MIN_TIME_SCALE);
+    }
+
+    #[test]
+    fn stalls_are_not_caught_up_at_once() {
+        let mut clock = SimulationClock::default();
+        let ticks = clock.advance(Duration::from_secs(2));
+        assert!((1..=10).contains(&ticks), "{ticks}");
+        assert_eq!(clock.advance(FIXED_TICK), 1);
+    }
+}
@@ -0,0 +1,377 @@
+//! Memory aliasing of transient attachments.
+//!
+//! An attachment only used between a first and a last pass of a [`PassList`] is transient: the
+//! ones whose lifetimes do not overlap share device memory, placed by [`plan`] so no two living
+//! at the same time overlap in memory. A screenshot's export images are aliased over the depth
+//! and multisampled color of its scene, which are done with before the export starts.
+//!
+//! The contents of an aliased attachment are undefined when its lifetime starts, whatever the
+//! previous owner of the memory left there: its first use must clear or overwrite every texel,
+//! [`PassList::lifetimes`] refuses the lists where it does not. The pass reusing the memory must
+//! also wait for the writes of the previous owners, a memory barrier the renderer records.
+//! Nothing here needs `VK_IMAGE_CREATE_ALIAS_BIT`, which is about sharing contents.
+use crate::error::PulsarError;
+
+/// What a pass does with an attachment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// Cleared when the pass begins.
+    Clear,
+    /// Every texel written before any is read, a full screen draw or a blit covering it.
+    Overwrite,
+    /// Drawn over, the previous contents kept.
+    Load,
+    /// Sampled or copied from.
+    Read,
+}
+
+impl Access {
+    /// Whether the previous contents do not matter.
+    pub fn initializes(self) -> bool {
+        matches!(self, Self::Clear | Self::Overwrite)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pass {
+    pub name: String,
+    /// Attachments used, by index, and how.
+    pub uses: Vec<(usize, Access)>,
+}
+
+impl Pass {
+    pub fn new(name: &str, uses: &[(usize, Access)]) -> Self {
+        Self {
+            name: name.to_string(),
+            uses: uses.to_vec(),
+        }
+    }
+}
+
+/// Passes in the order they execute.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PassList {
+    pub passes: Vec<Pass>,
+}
+
+/// First and last pass using an attachment, both included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lifetime {
+    pub first: usize,
+    pub last: usize,
+}
+
+impl Lifetime {
+    pub fn overlaps(self, other: Self) -> bool {
+        self.first <= other.last && other.first <= self.last
+    }
+}
+
+impl PassList {
+    pub fn new(passes: Vec<Pass>) -> Self {
+        Self { passes }
+    }
+
+    /// Lifetime of each of the `attachments` first ones, `None` for the unused. Fails on the
+    /// first attachment whose first use reads contents it was never given.
+    pub fn lifetimes(&self, attachments: usize) -> Result<Vec<Option<Lifetime>>, PulsarError> {
+        let mut lifetimes: Vec<Option<Lifetime>> = vec![None; attachments];
+        for (index, pass) in self.passes.iter().enumerate() {
+            for &(attachment, access) in &pass.uses {
+                let Some(lifetime) = lifetimes.get_mut(attachment) else {
+                    continue;
+                };
+                match lifetime {
+                    Some(lifetime) => lifetime.last = index,
+                    None if access.initializes() => {
+                        *lifetime = Some(Lifetime {
+                            first: index,
+                            last: index,
+                        })
+                    }
+                    None => {
+                        return Err(PulsarError::TransientUninitialized {
+                            attachment,
+                            pass: pass.name.clone(),
+                        })
+                    }
+                }
+            }
+        }
+        Ok(lifetimes)
+    }
+}
+
+/// Memory requirements of a transient attachment and when it lives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransientImage {
+    pub size: u64,
+    /// Power of two, as Vulkan reports it.
+    pub alignment: u64,
+    /// Memory types the image can be bound to, bit per type index.
+    pub memory_type_bits: u32,
+    pub lifetime: Lifetime,
+}
+
+/// One device memory allocation shared by attachments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AliasBlock {
+    /// Types every attachment placed in the block accepts.
+    pub memory_type_bits: u32,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placement {
+    pub block: usize,
+    pub offset: u64,
+}
+
+/// Where each attachment lives, in the order they were given.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AliasPlan {
+    pub blocks: Vec<AliasBlock>,
+    pub placements: Vec<Placement>,
+    pub images: Vec<TransientImage>,
+}
+
+impl AliasPlan {
+    /// Bytes the attachments would take with an allocation each.
+    pub fn requested_bytes(&self) -> u64 {
+        self.images.iter().map(|image| image.size).sum()
+    }
+
+    pub fn allocated_bytes(&self) -> u64 {
+        self.blocks.iter().map(|block| block.size).sum()
+    }
+
+    pub fn bytes_saved(&self) -> u64 {
+        self.requested_bytes()
+            .saturating_sub(self.allocated_bytes())
+    }
+
+    /// Place `image` in the blocks as they are, for an attachment created once they are
+    /// allocated. `None` when no block has room over its whole lifetime.
+    pub fn place(&mut self, image: TransientImage) -> Option<Placement> {
+        let placement = (0..self.blocks.len()).find_map(|block| {
+            let offset = self.lowest_offset(block, &image)?;
+            (offset + image.size <= self.blocks[block].size).then_some(Placement { block, offset })
+        })?;
+        self.blocks[placement.block].memory_type_bits &= image.memory_type_bits;
+        self.placements.push(placement);
+        self.images.push(image);
+        Some(placement)
+    }
+
+    /// Lowest offset of `block` where `image` overlaps no attachment living at the same time,
+    /// `None` when their memory types leave none in common.
+    fn lowest_offset(&self, block: usize, image: &TransientImage) -> Option<u64> {
+        if self.blocks[block].memory_type_bits & image.memory_type_bits == 0 {
+            return None;
+        }
+        let mut taken: Vec<(u64, u64)> = self
+            .placements
+            .iter()
+            .zip(&self.images)
+            .filter(|(placement, placed)| {
+                placement.block == block && placed.lifetime.overlaps(image.lifetime)
+            })
+            .map(|(placement, placed)| (placement.offset, placement.offset + placed.size))
+            .collect();
+        taken.sort_unstable();
+        let align = |offset: u64| offset.next_multiple_of(image.alignment.max(1));
+        let mut offset = 0;
+        for (start, end) in taken {
+            if align(offset) + image.size <= start {
+                break;
+            }
+            offset = offset.max(end);
+        }
+        Some(align(offset))
+    }
+}
+
+/// Place every image, largest first, at the lowest offset free over its lifetime in the block
+/// growing the least, a new block when none shares a memory type with it.
+pub fn plan(images: &[TransientImage]) -> AliasPlan {
+    let mut order: Vec<usize> = (0..images.len()).collect();
+    order.sort_by_key(|&index| std::cmp::Reverse(images[index].size));
+    let mut plan = AliasPlan::default();
+    let mut placements = vec![None; images.len()];
+    for index in order {
+        let image = images[index];
+        let best = (0..plan.blocks.len())
+            .filter_map(|block| {
+                let offset = plan.lowest_offset(block, &image)?;
+                let growth = (offset + image.size).saturating_sub(plan.blocks[block].size);
+                Some((growth, block, offset))
+            })
+            .min();
+        let placement = match best {
+            Some((_, block, offset)) => {
+                let block_state = &mut plan.blocks[block];
+                block_state.memory_type_bits &= image.memory_type_bits;
+                block_state.size = block_state.size.max(offset + image.size);
+                Placement { block, offset }
+            }
+            None => {
+                plan.blocks.push(AliasBlock {
+                    memory_type_bits: image.memory_type_bits,
+                    size: image.size,
+                });
+                Placement {
+                    block: plan.blocks.len() - 1,
+                    offset: 0,
+                }
+            }
+        };
+        placements[index] = Some(placement);
+        plan.placements.push(placement);
+        plan.images.push(image);
+    }
+    // Back to the order given
+    plan.placements = placements.into_iter().map(Option::unwrap).collect();
+    plan.images = images.to_vec();
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::SeededRng;
+
+    const SEEDS: u64 = 500;
+    const MEMORY_TYPES: [u32; 4] = [0b0011, 0b0110, 0b1111, 0b1000];
+
+    fn image(
+        size: u64,
+        alignment: u64,
+        memory_type_bits: u32,
+        first: usize,
+        last: usize,
+    ) -> TransientImage {
+        TransientImage {
+            size,
+            alignment,
+            memory_type_bits,
+            lifetime: Lifetime { first, last },
+        }
+    }
+
+    /// Every attachment fits its block at an aligned offset of a type it accepts, and no two
+    /// living at the same time overlap in memory.
+    fn assert_valid(plan: &AliasPlan) {
+        let placed: Vec<_> = plan.placements.iter().zip(&plan.images).collect();
+        for (index, (placement, image)) in placed.iter().enumerate() {
+            let block = plan.blocks[placement.block];
+            assert_eq!(placement.offset % image.alignment, 0, "attachment {index}");
+            assert!(
+                placement.offset + image.size <= block.size,
+                "attachment {index} at {placement:?} in {block:?}"
+            );
+            assert_ne!(block.memory_type_bits, 0, "attachment {index}");
+            assert_eq!(
+                block.memory_type_bits & !image.memory_type_bits,
+                0,
+                "attachment {index} in {block:?}"
+            );
+            for (other, (other_placement, other_image)) in placed.iter().enumerate().skip(index + 1)
+            {
+                assert!(
+                    placement.block != other_placement.block
+                        || !image.lifetime.overlaps(other_image.lifetime)
+                        || placement.offset >= other_placement.offset + other_image.size
+                        || other_placement.offset >= placement.offset + image.size,
+                    "attachments {index} and {other} overlap"
+                );
+            }
+        }
+    }
+
+    /// Random pass lists whose attachments are cleared then read.
+    #[test]
+    fn random_plans_never_overlap_living_attachments() {
+        for seed in 0..SEEDS {
+            let mut rng = SeededRng::new(seed);
+            let attachments = 1 + rng.below(8) as usize;
+            let mut passes: Vec<Pass> = (0..1 + rng.below(8))
+                .map(|index| Pass::new(&format!("pass {index}"), &[]))
+                .collect();
+            for attachment in 0..attachments {
+                let first = rng.below(passes.len() as u64) as usize;
+                let last = first + rng.below((passes.len() - first) as u64) as usize;
+                passes[first].uses.push((attachment, Access::Clear));
+                for pass in &mut passes[first + 1..=last] {
+                    pass.uses.push((attachment, Access::Read));
+                }
+            }
+            let lifetimes = PassList::new(passes).lifetimes(attachments).unwrap();
+            let images: Vec<TransientImage> = lifetimes
+                .iter()
+                .map(|lifetime| {
+                    let lifetime = lifetime.expect("every attachment is used");
+                    image(
+                        256 * (1 + rng.below(16)),
+                        1 << (8 + 2 * rng.below(3)),
+                        MEMORY_TYPES[rng.below(MEMORY_TYPES.len() as u64) as usize],
+                        lifetime.first,
+                        lifetime.last,
+                    )
+                })
+                .collect();
+            let plan = plan(&images);
+            assert_valid(&plan);
+            assert_eq!(plan.images, images, "seed {seed}: placements out of order");
+        }
+    }
+
+    #[test]
+    fn attachments_share_memory_when_lifetimes_and_types_allow() {
+        // One after the other, the second reuses the memory of the first
+        let sequential = plan(&[image(4096, 256, 1, 0, 0), image(4096, 256, 1, 1, 1)]);
+        assert_eq!(sequential.blocks.len(), 1);
+        assert_eq!(sequential.bytes_saved(), 4096);
+        // Living together, side by side at an aligned offset
+        let together = plan(&[image(1000, 256, 1, 0, 1), image(500, 1024, 1, 1, 2)]);
+        assert_valid(&together);
+        assert_eq!(together.placements[1].offset, 1024);
+        assert_eq!(together.bytes_saved(), 0);
+        // No memory type in common, no sharing
+        let apart = plan(&[image(4096, 256, 0b01, 0, 0), image(4096, 256, 0b10, 1, 1)]);
+        assert_eq!(apart.blocks.len(), 2);
+        assert_eq!(apart.bytes_saved(), 0);
+    }
+
+    /// Attachments created after the blocks are allocated only take the room left.
+    #[test]
+    fn late_attachments_never_grow_the_blocks() {
+        let mut plan = plan(&[image(2048, 256, 0b11, 0, 0)]);
+        let offsets = [
+            image(1024, 256, 0b01, 1, 2),
+            image(1024, 256, 0b01, 2, 3),
+            image(1024, 256, 0b01, 2, 2),
+            image(4096, 256, 0b01, 4, 4),
+            image(1024, 256, 0b10, 4, 4),
+        ]
+        .map(|image| plan.place(image).map(|placement| placement.offset));
+        assert_eq!(offsets, [Some(0), Some(1024), None, None, None]);
+        assert_valid(&plan);
+        assert_eq!(plan.allocated_bytes(), 2048);
+        assert_eq!(plan.bytes_saved(), 2048);
+    }
+
+    #[test]
+    fn attachments_read_before_written_are_refused() {
+        let passes = PassList::new(vec![
+            Pass::new("shadow", &[(0, Access::Clear)]),
+            Pass::new("lighting", &[(0, Access::Read), (1, Access::Load)]),
+        ]);
+        match passes.lifetimes(2) {
+            Err(PulsarError::TransientUninitialized {
+                attachment: 1,
+                pass,
+            }) => assert_eq!(pass, "lighting"),
+            other => panic!("loading a fresh attachment answered {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,374 @@
+//! Bounded queue of the mesh updates posted to a window, drained by its render thread before each
+//! frame.
+//!
+//! Setters are merged as they are posted: a second update of the same kind to the same mesh
+//! replaces the first in place, so an application setting a transform thousands of times per frame
+//! queues it once. Vertices and indices merge the same way, the render thread kept only the last
+//! of each anyway. Parent links are structural, whether one is refused depends on the links
+//! applied before it: they are never merged and act as a barrier, a setter posted after a link is
+//! never merged into one posted before it on the same mesh. Updates of different meshes keep their
+//! relative order, so do the links. Draining the merged queue leaves the meshes as applying every
+//! posted update in order would.
+//!
+//! Removals and registrations travel in their own queues, applied before the updates of the same
+//! frame. An update of a removed mesh resolves to nothing, merged or not.
+use crate::{error::PulsarError, handles::MeshHandle, model::MeshUpdate};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, mem::Discriminant};
+
+/// Updates a window holds before posting waits or fails, counted after merging.
+pub const DEFAULT_CAPACITY: usize = 4096;
+
+/// What posting to a full queue does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Backpressure {
+    /// Wait for the render thread to drain the queue. Nothing is lost, the posting thread runs at
+    /// the render thread's pace.
+    #[default]
+    Block,
+    /// Refuse the update with [`PulsarError::Backpressure`], `Application::update_mesh` drops it
+    /// with a warning.
+    Reject,
+}
+
+/// What became of a posted update.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Posted {
+    Queued,
+    /// Replaced an update still queued, see the module documentation.
+    Merged,
+    /// The queue is full, nothing changed.
+    Full,
+    /// The window is closing, the update is dropped.
+    Closed,
+}
+
+/// Updates handled since the renderer started.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UpdateCounters {
+    /// Drained and applied by the render thread.
+    pub processed: u64,
+    /// Merged into an update still queued.
+    pub coalesced: u64,
+    /// Refused by a full queue under [`Backpressure::Reject`].
+    pub dropped: u64,
+}
+
+#[derive(Debug)]
+pub struct UpdateQueue {
+    updates: Vec<(MeshHandle, MeshUpdate)>,
+    /// Queued setter each later one of the same mesh and kind is merged into.
+    mergeable: HashMap<(MeshHandle, Discriminant<MeshUpdate>), usize>,
+    pub capacity: usize,
+    pub backpressure: Backpressure,
+    pub counters: UpdateCounters,
+    closed: bool,
+}
+
+impl Default for UpdateQueue {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY, Backpressure::default())
+    }
+}
+
+impl UpdateQueue {
+    /// At least one update fits whatever `capacity`.
+    pub fn new(capacity: usize, backpressure: Backpressure) -> Self {
+        Self {
+            updates: Vec::new(),
+            mergeable: HashMap::new(),
+            capacity: capacity.max(1),
+            backpressure,
+            counters: UpdateCounters::default(),
+            closed: false,
+        }
+    }
+
+    /// Whether [`Self::post`] would take `update` rather than report the queue full.
+    pub fn has_room_for(&self, mesh: MeshHandle, update: &MeshUpdate) -> bool {
+        self.closed
+            || self.updates.len() < self.capacity
+            || self
+                .mergeable
+                .contains_key(&(mesh, std::mem::discriminant(update)))
+    }
+
+    /// Queue `update`, merged into the queued setter of the same kind on `mesh` if any. A merge
+    /// never needs room, so the last value of a setter always gets through.
+    pub fn post(&mut self, mesh: MeshHandle, update: MeshUpdate) -> Posted {
+        if self.closed {
+            return Posted::Closed;
+        }
+        let kind = std::mem::discriminant(&update);
+        if let Some(&index) = self.mergeable.get(&(mesh, kind)) {
+            self.updates[index].1 = update;
+            self.counters.coalesced += 1;
+            return Posted::Merged;
+        }
+        if self.updates.len() >= self.capacity {
+            return Posted::Full;
+        }
+        if matches!(update, MeshUpdate::Parent(_)) {
+            // Setters after the link start over, the ones before it stay where they are
+            self.mergeable.retain(|&(merged, _), _| merged != mesh);
+        } else {
+            self.mergeable.insert((mesh, kind), self.updates.len());
+        }
+        self.updates.push((mesh, update));
+        Posted::Queued
+    }
+
+    /// [`Self::post`] turning a full queue into [`PulsarError::Backpressure`], counted as dropped.
+    pub fn try_post(&mut self, mesh: MeshHandle, update: MeshUpdate) -> Result<(), PulsarError> {
+        match self.post(mesh, update) {
+            Posted::Full => {
+                self.counters.dropped += 1;
+                Err(PulsarError::Backpressure {
+                    capacity: self.capacity,
+                })
+            }
+            Posted::Queued | Posted::Merged | Posted::Closed => Ok(()),
+        }
+    }
+
+    /// Every update queued, in the order to apply them, counted as processed.
+    pub fn drain(&mut self) -> Vec<(MeshHandle, MeshUpdate)> {
+        self.mergeable.clear();
+        self.counters.processed += self.updates.len() as u64;
+        std::mem::take(&mut self.updates)
+    }
+
+    pub fn len(&self) -> usize {
+        self.updates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.updates.is_empty()
+    }
+
+    /// Drop the queued updates without counting them and every later one, the window is
+    /// closing. Posts waiting for room return.
+    pub fn close(&mut self) {
+        self.mergeable.clear();
+        self.updates.clear();
+        self.closed = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        input_manager::EventStates,
+        material::{DepthBias, Material, PipelineOptions},
+        model::Mesh,
+        residency::ResidencyPriority,
+        testing::{mesh_handle, SeededRng},
+    };
+    use glam::Mat4;
+    use std::{
+        sync::{Arc, Mutex},
+        thread,
+        time::Duration,
+    };
+
+    /// Meshes of the generated scenes, handles past them are stale.
+    const MESHES: usize = 5;
+    const SEEDS: u64 = 500;
+
+    /// What the render thread keeps of every update kind.
+    #[derive(Debug, Clone, PartialEq, Default)]
+    struct MeshState {
+        transform: Mat4,
+        parent: Option<usize>,
+        layers: u32,
+        texture_layer: Option<u32>,
+        sort_bias: i32,
+        blend: bool,
+        user_id: u64,
+        pipeline_options: Option<PipelineOptions>,
+        material: Option<Material>,
+        tint: [f32; 4],
+        depth_bias: Option<DepthBias>,
+        residency: ResidencyPriority,
+        vertices: usize,
+        indices: usize,
+    }
+
+    /// Apply `update` as the render thread does, links to a missing mesh or closing a cycle
+    /// refused.
+    fn apply(scene: &mut [MeshState], mesh: MeshHandle, update: &MeshUpdate) {
+        let index = mesh.index();
+        if index >= scene.len() {
+            return;
+        }
+        match update {
+            MeshUpdate::Vertices(vertices) => scene[index].vertices = vertices.len(),
+            MeshUpdate::Indices(indices) => scene[index].indices = indices.len(),
+            MeshUpdate::Transform(transform) => scene[index].transform = *transform,
+            MeshUpdate::Parent(parent) => {
+                let parent = parent.map(MeshHandle::index);
+                let mut ancestor = parent;
+                while let Some(current) = ancestor {
+                    if current >= scene.len() || current == index {
+                        return;
+                    }
+                    ancestor = scene[current].parent;
+                }
+                scene[index].parent = parent;
+            }
+            MeshUpdate::Layers(layers) => scene[index].layers = *layers,
+            MeshUpdate::TextureLayer(layer) => scene[index].texture_layer = *layer,
+            MeshUpdate::SortBias(sort_bias) => scene[index].sort_bias = *sort_bias,
+            MeshUpdate::Blend(blend) => scene[index].blend = *blend,
+            MeshUpdate::UserId(user_id) => scene[index].user_id = *user_id,
+            MeshUpdate::PipelineOptions(options) => scene[index].pipeline_options = *options,
+            MeshUpdate::Material(material) => scene[index].material = material.clone(),
+            MeshUpdate::Tint(tint) => scene[index].tint = *tint,
+            MeshUpdate::DepthBias(depth_bias) => scene[index].depth_bias = *depth_bias,
+            MeshUpdate::Residency(priority) => scene[index].residency = *priority,
+        }
+    }
+
+    fn random_update(rng: &mut SeededRng) -> (MeshHandle, MeshUpdate) {
+        let mesh = mesh_handle(rng.below(MESHES as u64 + 1) as usize, 0);
+        let value = rng.below(4) as u32;
+        let update = match rng.below(9) {
+            0 => {
+                MeshUpdate::Vertices(Mesh::cube(1.0, [1.0; 4]).vertices[..value as usize].to_vec())
+            }
+            1 => MeshUpdate::Indices(vec![0; value as usize]),
+            2 => MeshUpdate::Transform(Mat4::from_scale(glam::Vec3::splat(value as f32))),
+            // Links are rarer, a scene mostly made of them never merges
+            3 if value < 2 => MeshUpdate::Parent(
+                (value == 0).then(|| mesh_handle(rng.below(MESHES as u64 + 1) as usize, 0)),
+            ),
+            3 | 4 => MeshUpdate::Layers(value),
+            5 => MeshUpdate::TextureLayer(value.checked_sub(1)),
+            6 => MeshUpdate::SortBias(value as i32 - 2),
+            7 if value < 2 => MeshUpdate::Blend(value == 0),
+            7 => MeshUpdate::UserId(value as u64),
+            _ => MeshUpdate::PipelineOptions((value > 0).then(|| PipelineOptions {
+                depth_compare: ash::vk::CompareOp::from_raw(value as i32),
+                ..Default::default()
+            })),
+        };
+        (mesh, update)
+    }
+
+    #[test]
+    fn merged_sequences_apply_as_posted() {
+        for seed in 0..SEEDS {
+            let mut rng = SeededRng::new(seed);
+            let updates: Vec<_> = (0..1 + rng.below(200))
+                .map(|_| random_update(&mut rng))
+                .collect();
+            let mut sequential = vec![MeshState::default(); MESHES];
+            for (mesh, update) in &updates {
+                apply(&mut sequential, *mesh, update);
+            }
+
+            let mut queue = UpdateQueue::new(usize::MAX, Backpressure::Reject);
+            for (mesh, update) in updates.iter().cloned() {
+                queue.try_post(mesh, update).unwrap();
+            }
+            let drained = queue.drain();
+            let mut coalesced = vec![MeshState::default(); MESHES];
+            for (mesh, update) in &drained {
+                apply(&mut coalesced, *mesh, update);
+            }
+            assert_eq!(coalesced, sequential, "seed {seed}");
+            let counters = queue.counters;
+            assert_eq!(counters.processed as usize, drained.len(), "seed {seed}");
+            assert_eq!(
+                counters.processed + counters.coalesced,
+                updates.len() as u64,
+                "seed {seed}"
+            );
+        }
+    }
+
+    #[test]
+    fn links_are_never_merged_nor_crossed() {
+        let (mesh, other) = (mesh_handle(0, 0), mesh_handle(1, 0));
+        let mut queue = UpdateQueue::default();
+        let posts = [
+            MeshUpdate::Transform(Mat4::IDENTITY),
+            MeshUpdate::Transform(Mat4::ZERO),
+            MeshUpdate::Parent(Some(other)),
+            MeshUpdate::Transform(Mat4::IDENTITY),
+            MeshUpdate::Parent(None),
+            MeshUpdate::Parent(None),
+        ];
+        let outcomes: Vec<Posted> = posts
+            .into_iter()
+            .map(|update| queue.post(mesh, update))
+            .collect();
+        use Posted::{Merged, Queued};
+        assert_eq!(outcomes, [Queued, Merged, Queued, Queued, Queued, Queued]);
+        let kinds: Vec<String> = queue
+            .drain()
+            .iter()
+            .map(|(_, update)| format!("{update:?}").chars().take(6).collect())
+            .collect();
+        assert_eq!(kinds, ["Transf", "Parent", "Transf", "Parent", "Parent"]);
+    }
+
+    #[test]
+    fn full_queues_reject_but_still_merge() {
+        let mut queue = UpdateQueue::new(2, Backpressure::Reject);
+        queue
+            .try_post(mesh_handle(0, 0), MeshUpdate::Layers(1))
+            .unwrap();
+        queue
+            .try_post(mesh_handle(1, 0), MeshUpdate::Layers(1))
+            .unwrap();
+        match queue.try_post(mesh_handle(2, 0), MeshUpdate::Layers(1)) {
+            Err(PulsarError::Backpressure { capacity: 2 }) => {}
+            other => panic!("a full queue answered {other:?}"),
+        }
+        // The last value of a queued setter still gets through
+        queue
+            .try_post(mesh_handle(0, 0), MeshUpdate::Layers(2))
+            .unwrap();
+        assert_eq!(queue.counters.dropped, 1);
+        assert_eq!(queue.counters.coalesced, 1);
+        assert_eq!(queue.len(), 2);
+    }
+
+    /// Blocking waits for the render thread to drain, a closing window releases it too. The
+    /// render thread is played by this one.
+    #[test]
+    fn full_queues_wait_for_the_render_thread() {
+        for closing in [false, true] {
+            let states = Arc::new(EventStates {
+                mesh_updates: Mutex::new(UpdateQueue::new(1, Backpressure::Block)),
+                ..Default::default()
+            });
+            states
+                .post_mesh_update(mesh_handle(0, 0), MeshUpdate::Blend(true))
+                .unwrap();
+            let poster = {
+                let states = states.clone();
+                thread::spawn(move || {
+                    states.post_mesh_update(mesh_handle(1, 0), MeshUpdate::Blend(true))
+                })
+            };
+            thread::sleep(Duration::from_millis(50));
+            assert!(
+                !poster.is_finished(),
+                "posting to a full queue did not wait"
+            );
+            if closing {
+                states.retire();
+            } else {
+                assert_eq!(states.take_mesh_updates().0.len(), 1);
+            }
+            poster.join().unwrap().unwrap();
+            let (drained, counters) = states.take_mesh_updates();
+            assert_eq!(drained.len(), if closing { 0 } else { 1 });
+            assert_eq!(counters.dropped, 0);
+        }
+    }
+}
@@ -1,22 +1,42 @@
 use std::sync::Arc;
 
+pub mod async_pipelines;
+pub mod background;
 pub mod command_buffers;
 pub mod command_pools;
+pub mod compute;
 #[cfg(debug_assertions)]
 pub mod debug_callback;
+pub mod deferred_deletion;
 pub mod descriptor_set;
 pub mod device;
+pub mod dof;
+pub mod export;
 pub mod fence_semaphores;
 pub mod framebuffer;
+pub mod gpu_timer;
 pub mod graphics;
 pub mod instance;
+pub mod memory_budget;
+pub mod offscreen;
 pub mod pipeline;
+pub mod pipeline_cache;
+pub mod pipeline_registry;
+pub mod readback;
 pub mod record;
+pub mod renderer;
 pub mod renderpass;
+pub mod sampler;
 pub mod surface;
 pub mod surface_resources;
 pub mod swapchain;
+pub mod texture;
+pub mod texture_array;
+pub mod texture_upload;
+pub mod transient;
 pub mod uniform;
+pub mod upload;
+pub mod viewport;
 pub mod views;
 
 // TODO check sa many things that can be made Rc instead of Arc
@@ -0,0 +1,136 @@
+use super::{device::AAADevice, pipeline::create_pipeline_for_key};
+use crate::{
+    jobs::{self, JoinToken},
+    material::Material,
+};
+use ash::vk;
+use log::{info, warn};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+/// Pipelines built on the job pool, draws use the fallback pipeline until theirs is ready. Every
+/// one derives from the fallback, which outlives them and the builds in flight.
+pub struct AAAAsyncPipelines {
+    device: Arc<AAADevice>,
+    renderpass: vk::RenderPass,
+    pipeline_layout: vk::PipelineLayout,
+    samples: vk::SampleCountFlags,
+    /// Flat vertex color pipeline, never stalls since it exists from the start. The base of the
+    /// material pipelines.
+    pub fallback: vk::Pipeline,
+    ready: HashMap<Material, vk::Pipeline>,
+    pending: HashMap<Material, JoinToken<(vk::Pipeline, Duration)>>,
+    completed: usize,
+    /// Artificial delay added to every compilation, to reproduce slow drivers.
+    pub compile_delay: Duration,
+}
+
+impl AAAAsyncPipelines {
+    pub fn new(
+        device: Arc<AAADevice>,
+        renderpass: vk::RenderPass,
+        pipeline_layout: vk::PipelineLayout,
+        samples: vk::SampleCountFlags,
+        fallback: vk::Pipeline,
+    ) -> Self {
+        Self {
+            device,
+            renderpass,
+            pipeline_layout,
+            samples,
+            fallback,
+            ready: HashMap::new(),
+            pending: HashMap::new(),
+            completed: 0,
+            compile_delay: Duration::ZERO,
+        }
+    }
+
+    /// Start building the pipeline of `material` if it is not built or building yet.
+    pub fn precompile(&mut self, material: &Material) {
+        if self.ready.contains_key(material) || self.pending.contains_key(material) {
+            return;
+        }
+        let device = self.device.clone();
+        let renderpass = self.renderpass;
+        let pipeline_layout = self.pipeline_layout;
+        let (samples, base) = (self.samples, self.fallback);
+        let delay = self.compile_delay;
+        let job_material = material.clone();
+        let token = jobs::spawn(move || {
+            std::thread::sleep(delay);
+            let build = |material: &Material| {
+                create_pipeline_for_key(
+                    &device,
+                    renderpass,
+                    pipeline_layout,
+                    material,
+                    samples,
+                    base,
+                )
+            };
+            build(&job_material).unwrap_or_else(|err| {
+                warn!("{err}, drawn without its optional stages");
+                build(&job_material.without_optional_stages())
+                    .expect("Two stage pipelines need no optional feature")
+            })
+        });
+        self.pending.insert(material.clone(), token);
+    }
+
+    /// Pipeline to draw `material` with this frame, queuing its creation on first use.
+    pub fn pipeline_for(&mut self, material: &Material) -> vk::Pipeline {
+        if let Some(&pipeline) = self.ready.get(material) {
+            return pipeline;
+        }
+        self.precompile(material);
+        self.fallback
+    }
+
+    /// Swap in the pipelines that finished building, call once per frame.
+    pub fn poll(&mut self) {
+        let finished: Vec<Material> = self
+            .pending
+            .iter()
+            .filter(|(_, token)| token.is_finished())
+            .map(|(material, _)| material.clone())
+            .collect();
+        for material in finished {
+            let token = self.pending.remove(&material).unwrap();
+            let (pipeline, elapsed) = token.join();
+            self.completed += 1;
+            info!(
+                "Pipeline {}/{} built in {elapsed:?} as a derivative, {} outstanding",
+                material.vertex_shader,
+                material.fragment_shader,
+                self.pending.len()
+            );
+            self.ready.insert(material, pipeline);
+        }
+    }
+
+    /// Whether the pipeline of `material` finished building.
+    pub fn is_ready(&self, material: &Material) -> bool {
+        self.ready.contains_key(material)
+    }
+
+    pub fn outstanding(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn completed(&self) -> usize {
+        self.completed
+    }
+
+    /// Wait for the builds in flight then destroy every pipeline but the fallback.
+    pub fn destroy(&mut self) {
+        for (_, token) in self.pending.drain() {
+            let (pipeline, _) = token.join();
+            crate::object_audit::destroyed(pipeline);
+            unsafe { self.device.ash.destroy_pipeline(pipeline, None) };
+        }
+        for (_, pipeline) in self.ready.drain() {
+            crate::object_audit::destroyed(pipeline);
+            unsafe { self.device.ash.destroy_pipeline(pipeline, None) };
+        }
+    }
+}
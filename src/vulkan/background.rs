@@ -0,0 +1,266 @@
+use super::{device::AAADevice, pipeline::create_background_pipeline};
+use crate::{
+    background::{BackgroundConstants, BackgroundMode, BackgroundShader, BACKGROUND_USER_FLOATS},
+    shaders::Shader,
+};
+use ash::vk;
+use glam::Vec2;
+use log::{info, warn};
+use std::{
+    mem,
+    time::{Duration, Instant},
+};
+
+/// How often the background's source is checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Fullscreen pass drawn first in the main render pass, with its own layout of a single push
+/// constant range read by the fragment stage.
+pub struct AAABackground {
+    layout: vk::PipelineLayout,
+    vertex_shader: vk::ShaderModule,
+    /// Of the main renderpass the pipelines are built for.
+    samples: vk::SampleCountFlags,
+    /// `None` shows the clear color.
+    pipeline: Option<vk::Pipeline>,
+    /// Built when the shader was set, the base its reloads derive from. Kept until the shader is
+    /// set again or cleared, with the reload deriving from it.
+    base: Option<vk::Pipeline>,
+    shader: Option<BackgroundShader>,
+    /// Simulation seconds since the shader was set, see `SimulationClock`.
+    time: Duration,
+    last_poll: Instant,
+    pub user: [f32; BACKGROUND_USER_FLOATS],
+}
+
+impl AAABackground {
+    pub fn new(device: &AAADevice, samples: vk::SampleCountFlags) -> Self {
+        let push_constant_range = vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            offset: 0,
+            size: mem::size_of::<BackgroundConstants>() as u32,
+        };
+        let layout_info = vk::PipelineLayoutCreateInfo::default()
+            .push_constant_ranges(std::slice::from_ref(&push_constant_range));
+        let layout = unsafe {
+            device
+                .ash
+                .create_pipeline_layout(&layout_info, None)
+                .expect("Failed to create background pipeline layout!")
+        };
+        crate::object_audit::created(layout, "background");
+        let vertex_shader =
+            Shader::from_filename("background", vk::ShaderStageFlags::VERTEX, device).module;
+        Self {
+            layout,
+            vertex_shader,
+            samples,
+            pipeline: None,
+            base: None,
+            shader: None,
+            time: Duration::ZERO,
+            last_poll: Instant::now(),
+            user: [0.0; BACKGROUND_USER_FLOATS],
+        }
+    }
+
+    /// Switch to `mode`, a shader failing to compile keeps the current background.
+    /// `draw_fence` signals once the previous frame stopped drawing the replaced pipeline.
+    pub fn set_mode(
+        &mut self,
+        device: &AAADevice,
+        renderpass: vk::RenderPass,
+        draw_fence: vk::Fence,
+        mode: BackgroundMode,
+    ) {
+        match mode {
+            BackgroundMode::Clear => {
+                self.replace(device, draw_fence, None, true);
+                self.shader = None;
+            }
+            BackgroundMode::Shader { fragment_source } => {
+                let built = BackgroundShader::load(&fragment_source)
+                    .map_err(|err| err.to_string())
+                    .and_then(|shader| {
+                        let (pipeline, _) = self.build(device, renderpass, &shader.code, None)?;
+                        Ok((shader, pipeline))
+                    });
+                match built {
+                    Ok((shader, pipeline)) => {
+                        info!("Background shader {}", fragment_source.display());
+                        self.replace(device, draw_fence, Some(pipeline), true);
+                        self.shader = Some(shader);
+                        self.time = Duration::ZERO;
+                    }
+                    Err(err) => warn!("{err}, the previous background stays"),
+                }
+            }
+        }
+    }
+
+    /// Move the shader's time `delta` forward.
+    pub fn advance(&mut self, delta: Duration) {
+        self.time += delta;
+    }
+
+    /// Rebuild the pipeline when the shader's source changed, at most every [`POLL_INTERVAL`].
+    pub fn poll(&mut self, device: &AAADevice, renderpass: vk::RenderPass, draw_fence: vk::Fence) {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return;
+        }
+        self.last_poll = Instant::now();
+        let Some(shader) = &mut self.shader else {
+            return;
+        };
+        let code = match shader.reload_if_changed() {
+            Ok(true) => shader.code.clone(),
+            Ok(false) => return,
+            Err(err) => {
+                warn!("{err}, the previous background stays");
+                return;
+            }
+        };
+        match self.build(device, renderpass, &code, self.base) {
+            Ok((pipeline, elapsed)) => {
+                info!(
+                    "Background shader reloaded, its pipeline derived from the one it was set \
+                     with in {elapsed:?}"
+                );
+                self.replace(device, draw_fence, Some(pipeline), false);
+            }
+            Err(err) => warn!("{err}, the previous background stays"),
+        }
+    }
+
+    /// Draw the background over the whole `extent`, inside the render pass before the scene.
+    /// Binds its own pipeline, viewport and scissor, the scene's are bound again after.
+    pub fn record(
+        &self,
+        device: &AAADevice,
+        command_buffer: vk::CommandBuffer,
+        extent: vk::Extent2D,
+        cursor: Option<Vec2>,
+    ) {
+        let Some(pipeline) = self.pipeline else {
+            return;
+        };
+        let constants = BackgroundConstants {
+            resolution: [extent.width as f32, extent.height as f32],
+            cursor: cursor.map_or([-1.0; 2], |cursor| cursor.to_array()),
+            time: self.time.as_secs_f32(),
+            user: self.user,
+            ..Default::default()
+        };
+        unsafe {
+            device
+                .ash
+                .cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+            device.ash.cmd_set_viewport(
+                command_buffer,
+                0,
+                &[vk::Viewport {
+                    x: 0.0,
+                    y: 0.0,
+                    width: extent.width as f32,
+                    height: extent.height as f32,
+                    min_depth: 0.0,
+                    max_depth: 1.0,
+                }],
+            );
+            device
+                .ash
+                .cmd_set_scissor(command_buffer, 0, &[extent.into()]);
+            device.ash.cmd_push_constants(
+                command_buffer,
+                self.layout,
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                constants.as_bytes(),
+            );
+            device.ash.cmd_draw(command_buffer, 3, 1, 0, 0);
+        }
+    }
+
+    fn build(
+        &self,
+        device: &AAADevice,
+        renderpass: vk::RenderPass,
+        code: &[u32],
+        base: Option<vk::Pipeline>,
+    ) -> Result<(vk::Pipeline, Duration), String> {
+        let module_info = vk::ShaderModuleCreateInfo::default().code(code);
+        let fragment_shader = unsafe { device.ash.create_shader_module(&module_info, None) }
+            .map_err(|err| format!("Background shader module: {err}"))?;
+        crate::object_audit::created(fragment_shader, "background");
+        let stages = [
+            vk::PipelineShaderStageCreateInfo::default()
+                .stage(vk::ShaderStageFlags::VERTEX)
+                .module(self.vertex_shader)
+                .name(c"main"),
+            vk::PipelineShaderStageCreateInfo::default()
+                .stage(vk::ShaderStageFlags::FRAGMENT)
+                .module(fragment_shader)
+                .name(c"main"),
+        ];
+        let pipeline = create_background_pipeline(
+            device,
+            renderpass,
+            self.layout,
+            &stages,
+            self.samples,
+            base,
+        )
+        .map_err(|err| format!("Background pipeline: {err}"));
+        crate::object_audit::destroyed(fragment_shader);
+        unsafe { device.ash.destroy_shader_module(fragment_shader, None) };
+        pipeline
+    }
+
+    /// Draw `pipeline` from now on, the base of the next reloads when `rebased`. The previous base
+    /// is destroyed with the pipeline it was set with, never before its reloads.
+    fn replace(
+        &mut self,
+        device: &AAADevice,
+        draw_fence: vk::Fence,
+        pipeline: Option<vk::Pipeline>,
+        rebased: bool,
+    ) {
+        let previous = mem::replace(&mut self.pipeline, pipeline);
+        let mut retired = Vec::new();
+        if rebased {
+            retired.extend(mem::replace(&mut self.base, pipeline));
+        }
+        // The base is drawn until the first reload, then kept for the next ones
+        retired.extend(
+            previous.filter(|previous| Some(*previous) != self.base && !retired.contains(previous)),
+        );
+        if retired.is_empty() {
+            return;
+        }
+        unsafe {
+            device
+                .ash
+                .wait_for_fences(&[draw_fence], true, u64::MAX)
+                .expect("Wait for fence failed.");
+            for pipeline in retired {
+                crate::object_audit::destroyed(pipeline);
+                device.ash.destroy_pipeline(pipeline, None);
+            }
+        }
+    }
+
+    /// The commands drawing the background must have completed.
+    pub fn destroy(&self, device: &AAADevice) {
+        unsafe {
+            let base = self.base.filter(|&base| Some(base) != self.pipeline);
+            for pipeline in self.pipeline.into_iter().chain(base) {
+                crate::object_audit::destroyed(pipeline);
+                device.ash.destroy_pipeline(pipeline, None);
+            }
+            crate::object_audit::destroyed(self.vertex_shader);
+            device.ash.destroy_shader_module(self.vertex_shader, None);
+            crate::object_audit::destroyed(self.layout);
+            device.ash.destroy_pipeline_layout(self.layout, None);
+        }
+    }
+}
@@ -17,5 +17,7 @@ pub fn create_command_pools(
             .unwrap()
     };
 
+    crate::object_audit::created(pool, "window commands");
+
     Ok(pool)
 }
@@ -0,0 +1,213 @@
+use super::{
+    descriptor_set::create_deform_set_layout, device::AAADevice, pipeline::create_compute_pipeline,
+    record::record_submit_commandbuffer,
+};
+use crate::{compute::DeformConstants, shader_reflect::ShaderInterface, shaders::Shader};
+use ash::vk;
+use log::error;
+use std::{collections::HashMap, mem};
+
+/// Pushed before every dispatch, a whole [`DeformConstants`] to the compute stage.
+const DEFORM_PUSH_CONSTANTS: vk::PushConstantRange = vk::PushConstantRange {
+    stage_flags: vk::ShaderStageFlags::COMPUTE,
+    offset: 0,
+    size: mem::size_of::<DeformConstants>() as u32,
+};
+
+/// A compute shader run over a buffer the vertex stage reads once it is written.
+#[derive(Debug, Clone, Copy)]
+pub struct ComputeDispatch<'a> {
+    pub pipeline: vk::Pipeline,
+    pub descriptor_set: vk::DescriptorSet,
+    pub constants: &'a [u8],
+    pub group_counts: [u32; 3],
+    /// Written by the shader, read as vertices by the frames after it.
+    pub written: vk::Buffer,
+}
+
+/// Compute pipelines of the deformed meshes, with a layout of their own: the deform set and a
+/// push constant range read by the compute stage. Dispatched from a dedicated command buffer on
+/// the graphics queue, submitted before the frame drawing what they write.
+pub struct AAACompute {
+    command_buffer: vk::CommandBuffer,
+    fence: vk::Fence,
+    queue: vk::Queue,
+    pub set_layout: vk::DescriptorSetLayout,
+    layout: vk::PipelineLayout,
+    /// By shader name, built on first use.
+    pipelines: HashMap<String, vk::Pipeline>,
+}
+
+impl AAACompute {
+    /// The command buffer is allocated from `pool`, of the family of `queue`, and freed with it.
+    pub fn new(device: &AAADevice, pool: vk::CommandPool, queue: vk::Queue) -> Self {
+        let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::default()
+            .command_buffer_count(1)
+            .command_pool(pool)
+            .level(vk::CommandBufferLevel::PRIMARY);
+        let fence_info = vk::FenceCreateInfo::default().flags(vk::FenceCreateFlags::SIGNALED);
+        let set_layout = create_deform_set_layout(device);
+        let set_layouts = [set_layout];
+        let layout_info = vk::PipelineLayoutCreateInfo::default()
+            .set_layouts(&set_layouts)
+            .push_constant_ranges(std::slice::from_ref(&DEFORM_PUSH_CONSTANTS));
+        unsafe {
+            let command_buffer = device
+                .ash
+                .allocate_command_buffers(&command_buffer_allocate_info)
+                .expect("Failed to allocate compute command buffer!")[0];
+            let fence = device
+                .ash
+                .create_fence(&fence_info, None)
+                .expect("Failed to create compute fence!");
+            crate::object_audit::created(fence, "compute");
+            let layout = device
+                .ash
+                .create_pipeline_layout(&layout_info, None)
+                .expect("Failed to create compute pipeline layout!");
+            crate::object_audit::created(layout, "compute");
+            Self {
+                command_buffer,
+                fence,
+                queue,
+                set_layout,
+                layout,
+                pipelines: HashMap::new(),
+            }
+        }
+    }
+
+    /// Pipeline of the compiled compute shader `shader`, built the first time it is asked for.
+    pub fn pipeline(&mut self, device: &AAADevice, shader: &str) -> vk::Pipeline {
+        if let Some(&pipeline) = self.pipelines.get(shader) {
+            return pipeline;
+        }
+        let compute_shader = Shader::from_filename(shader, vk::ShaderStageFlags::COMPUTE, device);
+        // The bindings were checked with the module, the push constants are this layout's
+        let pushed = ShaderInterface {
+            bindings: Vec::new(),
+            push_constants: compute_shader.interface.push_constants.clone(),
+        };
+        for problem in pushed.unwritten(&[], &[DEFORM_PUSH_CONSTANTS]) {
+            error!("{shader}: {problem}");
+        }
+        let pipeline = create_compute_pipeline(device, self.layout, &compute_shader);
+        unsafe {
+            crate::object_audit::destroyed(compute_shader.module);
+            device
+                .ash
+                .destroy_shader_module(compute_shader.module, None);
+        }
+        self.pipelines.insert(shader.to_string(), pipeline);
+        pipeline
+    }
+
+    /// Wait for the last dispatches, their descriptor sets and the buffers they read may change.
+    pub fn wait(&self, device: &AAADevice) {
+        unsafe {
+            device
+                .ash
+                .wait_for_fences(&[self.fence], true, u64::MAX)
+                .expect("Wait for fence failed.");
+        }
+    }
+
+    /// Record and submit `dispatches` without waiting for them. The frames submitted to the
+    /// queue after them read what they write: the barriers wait for the vertex fetches of the
+    /// earlier frames before writing, and make the writes visible to the vertex fetches after.
+    pub fn dispatch(&self, device: &AAADevice, dispatches: &[ComputeDispatch]) {
+        if dispatches.is_empty() {
+            return;
+        }
+        let barriers = |src_access, dst_access| -> Vec<vk::BufferMemoryBarrier> {
+            dispatches
+                .iter()
+                .map(|dispatch| {
+                    vk::BufferMemoryBarrier::default()
+                        .src_access_mask(src_access)
+                        .dst_access_mask(dst_access)
+                        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                        .buffer(dispatch.written)
+                        .offset(0)
+                        .size(vk::WHOLE_SIZE)
+                })
+                .collect()
+        };
+        record_submit_commandbuffer(
+            device,
+            self.command_buffer,
+            self.fence,
+            self.queue,
+            &[],
+            &[],
+            &[],
+            |device, command_buffer| unsafe {
+                // Write after read, only the execution has to wait
+                device.ash.cmd_pipeline_barrier(
+                    command_buffer,
+                    vk::PipelineStageFlags::VERTEX_INPUT,
+                    vk::PipelineStageFlags::COMPUTE_SHADER,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &barriers(vk::AccessFlags::empty(), vk::AccessFlags::SHADER_WRITE),
+                    &[],
+                );
+                for dispatch in dispatches {
+                    device.ash.cmd_bind_pipeline(
+                        command_buffer,
+                        vk::PipelineBindPoint::COMPUTE,
+                        dispatch.pipeline,
+                    );
+                    device.ash.cmd_bind_descriptor_sets(
+                        command_buffer,
+                        vk::PipelineBindPoint::COMPUTE,
+                        self.layout,
+                        0,
+                        &[dispatch.descriptor_set],
+                        &[],
+                    );
+                    device.ash.cmd_push_constants(
+                        command_buffer,
+                        self.layout,
+                        vk::ShaderStageFlags::COMPUTE,
+                        0,
+                        dispatch.constants,
+                    );
+                    let [x, y, z] = dispatch.group_counts;
+                    device.ash.cmd_dispatch(command_buffer, x, y, z);
+                }
+                device.ash.cmd_pipeline_barrier(
+                    command_buffer,
+                    vk::PipelineStageFlags::COMPUTE_SHADER,
+                    vk::PipelineStageFlags::VERTEX_INPUT,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &barriers(
+                        vk::AccessFlags::SHADER_WRITE,
+                        vk::AccessFlags::VERTEX_ATTRIBUTE_READ,
+                    ),
+                    &[],
+                );
+            },
+        );
+    }
+
+    /// The device must be idle.
+    pub fn destroy(&self, device: &AAADevice) {
+        unsafe {
+            for &pipeline in self.pipelines.values() {
+                crate::object_audit::destroyed(pipeline);
+                device.ash.destroy_pipeline(pipeline, None);
+            }
+            crate::object_audit::destroyed(self.layout);
+            device.ash.destroy_pipeline_layout(self.layout, None);
+            crate::object_audit::destroyed(self.set_layout);
+            device
+                .ash
+                .destroy_descriptor_set_layout(self.set_layout, None);
+            crate::object_audit::destroyed(self.fence);
+            device.ash.destroy_fence(self.fence, None);
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use super::device::AAADevice;
+
+type Destroy = Box<dyn FnOnce(&AAADevice) + Send>;
+
+/// Resources replaced while frames may still read them, destroyed once the last frame that could
+/// reference each has completed instead of waiting on the GPU at the swap.
+#[derive(Default)]
+pub struct AAADeferredDeletion {
+    /// Last frame referencing the resource, and how to destroy it.
+    pending: Vec<(u64, Destroy)>,
+}
+
+impl AAADeferredDeletion {
+    /// Destroy with `destroy` once frame `last_frame` has completed.
+    #[cfg_attr(not(feature = "images"), allow(dead_code))]
+    pub fn retire(&mut self, last_frame: u64, destroy: impl FnOnce(&AAADevice) + Send + 'static) {
+        self.pending.push((last_frame, Box::new(destroy)));
+    }
+
+    /// Destroy what only frames up to `completed` referenced.
+    pub fn collect(&mut self, device: &AAADevice, completed: u64) {
+        let (done, pending) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|(last_frame, _)| *last_frame <= completed);
+        self.pending = pending;
+        for (_, destroy) in done {
+            destroy(device);
+        }
+    }
+
+    /// Destroy everything, the device must be idle.
+    pub fn destroy(&mut self, device: &AAADevice) {
+        for (_, destroy) in self.pending.drain(..) {
+            destroy(device);
+        }
+    }
+}
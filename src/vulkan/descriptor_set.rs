@@ -1,27 +1,277 @@
+//! The engine's binding model, one descriptor set per update frequency:
+//!
+//! - set 0, [`GLOBAL_SET`]: per frame globals, binding 0 is the uniform buffer read by the vertex stage,
+//!   binding 1 ([`ENVIRONMENT_BINDING`]) the environment cubemap read by the fragment stage, the
+//!   flat irradiance until the application loads one, binding 2 ([`IRRADIANCE_BINDING`]) its
+//!   diffuse convolution and binding 3 ([`ENVIRONMENT_UNIFORM_BINDING`]) the intensity scaling it,
+//!   both read by the lighting shader. Binding 4 is left for a prefiltered specular cubemap
+//! - set 1, [`MATERIAL_SET`]: per material, binding 0 is the sampled texture read by the fragment
+//!   stage, and by the tessellation evaluation stage of `Material::displacement` as its heightmap
+//! - set 2, [`OBJECT_SET`]: per object, the joint matrices of a skinned mesh read by the vertex
+//!   stage, a uniform buffer at [`PALETTE_UNIFORM_BINDING`] when they fit one, a storage buffer at
+//!   [`PALETTE_STORAGE_BINDING`] otherwise. The rest of the per draw data travels in the push
+//!   constants
+//!
+//! Every pipeline is built with the same layout, so binding a lower set never disturbs the higher
+//! ones. Shaders declare `layout (set = N, binding = M)` matching [`BINDINGS`]. The layout is the
+//! union of what the stages of [`LAYOUT_SHADERS`] declare, reflected from their SPIR-V, and of
+//! [`LAYOUT_OVERRIDES`] for the descriptors written that no engine shader reads yet. A stage
+//! reading something the renderer does not write, or a descriptor written that no stage declares,
+//! fails [`engine_interface`].
+//!
+//! Compute shaders deforming meshes have a layout of their own, a single set of two storage
+//! buffers, see [`DEFORM_BINDINGS`] and [`crate::compute`]. The depth of field passes read the
+//! images they chain through a single set of their own, see [`DOF_BINDINGS`].
 use ash::vk;
 
-use super::device::AAADevice;
+use super::{device::AAADevice, pipeline::DRAW_PUSH_CONSTANTS};
+use crate::{
+    error::PulsarError,
+    shader_reflect::{ReflectedBinding, ShaderInterface},
+    shaders::Shader,
+};
 
+pub const GLOBAL_SET: u32 = 0;
+pub const MATERIAL_SET: u32 = 1;
+pub const OBJECT_SET: u32 = 2;
+pub const SET_COUNT: usize = 3;
+/// Binding of the `samplerCube` in [`GLOBAL_SET`], see `Application::load_cubemap`.
+pub const ENVIRONMENT_BINDING: u32 = 1;
+/// Bindings of the environment's irradiance `samplerCube` and of its intensity in [`GLOBAL_SET`],
+/// see [`crate::environment`].
+pub const IRRADIANCE_BINDING: u32 = 2;
+pub const ENVIRONMENT_UNIFORM_BINDING: u32 = 3;
+/// Bindings of the joint matrices in [`OBJECT_SET`], see [`crate::skinning::PaletteBuffer`].
+pub const PALETTE_STORAGE_BINDING: u32 = 0;
+pub const PALETTE_UNIFORM_BINDING: u32 = 1;
+
+/// Set, binding and type of every descriptor the renderer writes, all a shader may declare.
+pub const BINDINGS: [(u32, u32, vk::DescriptorType); 7] = [
+    (GLOBAL_SET, 0, vk::DescriptorType::UNIFORM_BUFFER),
+    (
+        GLOBAL_SET,
+        ENVIRONMENT_BINDING,
+        vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+    ),
+    (
+        GLOBAL_SET,
+        IRRADIANCE_BINDING,
+        vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+    ),
+    (
+        GLOBAL_SET,
+        ENVIRONMENT_UNIFORM_BINDING,
+        vk::DescriptorType::UNIFORM_BUFFER,
+    ),
+    (MATERIAL_SET, 0, vk::DescriptorType::COMBINED_IMAGE_SAMPLER),
+    (
+        OBJECT_SET,
+        PALETTE_STORAGE_BINDING,
+        vk::DescriptorType::STORAGE_BUFFER,
+    ),
+    (
+        OBJECT_SET,
+        PALETTE_UNIFORM_BINDING,
+        vk::DescriptorType::UNIFORM_BUFFER,
+    ),
+];
+
+/// Compiled shaders drawing with the shared layout and their stage, reflected by
+/// [`engine_interface`].
+pub const LAYOUT_SHADERS: [(&str, vk::ShaderStageFlags); 12] = [
+    ("vert", vk::ShaderStageFlags::VERTEX),
+    ("frag", vk::ShaderStageFlags::FRAGMENT),
+    ("instanced", vk::ShaderStageFlags::VERTEX),
+    ("skinned", vk::ShaderStageFlags::VERTEX),
+    ("skinned_uniform", vk::ShaderStageFlags::VERTEX),
+    ("texture_array_vert", vk::ShaderStageFlags::VERTEX),
+    ("texture_array", vk::ShaderStageFlags::FRAGMENT),
+    ("normals_vert", vk::ShaderStageFlags::VERTEX),
+    ("normals", vk::ShaderStageFlags::GEOMETRY),
+    ("displace_vert", vk::ShaderStageFlags::VERTEX),
+    ("displace_tesc", vk::ShaderStageFlags::TESSELLATION_CONTROL),
+    ("displace", vk::ShaderStageFlags::TESSELLATION_EVALUATION),
+];
+
+/// Declared in the shared layout whatever the shaders of [`LAYOUT_SHADERS`] read: the globals no
+/// engine shader samples yet, for the stages application shaders read them from. The escape
+/// hatch for descriptors reflection cannot see, such as those of shaders built at run time.
+pub const LAYOUT_OVERRIDES: [(u32, u32, vk::DescriptorType, vk::ShaderStageFlags); 2] = [
+    (
+        GLOBAL_SET,
+        0,
+        vk::DescriptorType::UNIFORM_BUFFER,
+        vk::ShaderStageFlags::VERTEX,
+    ),
+    (
+        GLOBAL_SET,
+        ENVIRONMENT_BINDING,
+        vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+        vk::ShaderStageFlags::FRAGMENT,
+    ),
+];
+
+/// Descriptors and push constants of the shared layout, see the module documentation. The push
+/// constant ranges are the ones every draw pushes, [`DRAW_PUSH_CONSTANTS`], once the shaders are
+/// checked to read within them and the device to take `max_push_constants_size` bytes of them.
+pub fn engine_interface(max_push_constants_size: u32) -> Result<ShaderInterface, PulsarError> {
+    let mismatch = |shader: &str, reason: String| PulsarError::ShaderInterface {
+        shader: shader.to_string(),
+        reason,
+    };
+    let mut interface = ShaderInterface::default();
+    for (shader, stage) in LAYOUT_SHADERS {
+        let reflected = Shader::interface_of(shader, stage)?;
+        let problems = reflected.unwritten(&BINDINGS, &DRAW_PUSH_CONSTANTS);
+        if !problems.is_empty() {
+            return Err(mismatch(shader, problems.join(", ")));
+        }
+        interface
+            .merge(&reflected)
+            .map_err(|reason| mismatch(shader, reason))?;
+    }
+    let overrides = ShaderInterface {
+        bindings: LAYOUT_OVERRIDES
+            .iter()
+            .map(
+                |&(set, binding, descriptor_type, stages)| ReflectedBinding {
+                    set,
+                    binding,
+                    descriptor_type,
+                    count: 1,
+                    stages,
+                    name: String::new(),
+                },
+            )
+            .collect(),
+        push_constants: DRAW_PUSH_CONSTANTS.to_vec(),
+    };
+    interface
+        .merge(&overrides)
+        .map_err(|reason| mismatch("LAYOUT_OVERRIDES", reason))?;
+    for (set, binding, descriptor_type) in BINDINGS {
+        if interface.binding(set, binding).is_none() {
+            return Err(mismatch(
+                "layout",
+                format!(
+                    "the renderer writes a {descriptor_type:?} at set {set} binding {binding} \
+                     no shader declares, add it to LAYOUT_OVERRIDES"
+                ),
+            ));
+        }
+    }
+    let pushed = interface
+        .push_constants
+        .iter()
+        .map(|range| range.offset + range.size)
+        .max()
+        .unwrap_or(0);
+    if pushed > max_push_constants_size {
+        return Err(mismatch(
+            "layout",
+            format!(
+                "pushes {pushed} bytes of push constants, the device takes \
+                 {max_push_constants_size}"
+            ),
+        ));
+    }
+    Ok(interface)
+}
+
+/// Bindings of the deform set, the only set of the compute pipelines: the vertices a deformed
+/// mesh was added with and the vertex buffer it is drawn from.
+pub const DEFORM_REST_BINDING: u32 = 0;
+pub const DEFORM_VERTEX_BINDING: u32 = 1;
+
+/// Set, binding, type and stages of every descriptor a compute shader may declare.
+pub const DEFORM_BINDINGS: [(u32, u32, vk::DescriptorType, vk::ShaderStageFlags); 2] = [
+    (
+        0,
+        DEFORM_REST_BINDING,
+        vk::DescriptorType::STORAGE_BUFFER,
+        vk::ShaderStageFlags::COMPUTE,
+    ),
+    (
+        0,
+        DEFORM_VERTEX_BINDING,
+        vk::DescriptorType::STORAGE_BUFFER,
+        vk::ShaderStageFlags::COMPUTE,
+    ),
+];
+
+/// Bindings of the depth of field set, the only set of the [`DOF_SHADERS`]: the copy of the
+/// scene, its depth, the circle of confusion and the two blurred fields, see `crate::dof`.
+pub const DOF_COLOR_BINDING: u32 = 0;
+pub const DOF_DEPTH_BINDING: u32 = 1;
+pub const DOF_COC_BINDING: u32 = 2;
+pub const DOF_NEAR_BINDING: u32 = 3;
+pub const DOF_FAR_BINDING: u32 = 4;
+
+/// Set, binding and type of every descriptor a depth of field shader may declare.
+pub const DOF_BINDINGS: [(u32, u32, vk::DescriptorType); 5] = [
+    (
+        0,
+        DOF_COLOR_BINDING,
+        vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+    ),
+    (
+        0,
+        DOF_DEPTH_BINDING,
+        vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+    ),
+    (
+        0,
+        DOF_COC_BINDING,
+        vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+    ),
+    (
+        0,
+        DOF_NEAR_BINDING,
+        vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+    ),
+    (
+        0,
+        DOF_FAR_BINDING,
+        vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+    ),
+];
+
+/// Compiled fragment shaders of the depth of field passes, checked against [`DOF_BINDINGS`].
+pub const DOF_SHADERS: [&str; 3] = ["dof_coc", "dof_gather", "dof_composite"];
+
+/// Material sets the pool has room for, the default material's and the texture array's.
+const MATERIAL_SETS: u32 = 2;
+
+/// The globals, allocated at startup, and the material sets, null until first used.
+#[derive(Debug, Clone, Copy)]
+pub struct AAADescriptorSets {
+    pub global: vk::DescriptorSet,
+    /// Allocated with the default texture, see `AAAResources::ensure_default_texture`.
+    pub default_material: vk::DescriptorSet,
+    /// Allocated once the application creates a texture array, see `TextureArray`.
+    pub texture_array: vk::DescriptorSet,
+}
+
+/// Pool, global set and set layouts of the shared layout, built from `interface`.
 pub fn create_descriptor_set(
     device: &AAADevice,
+    interface: &ShaderInterface,
 ) -> (
     vk::DescriptorPool,
-    Vec<vk::DescriptorSet>,
-    [vk::DescriptorSetLayout; 1],
+    AAADescriptorSets,
+    [vk::DescriptorSetLayout; SET_COUNT],
 ) {
-    let descriptor_sizes = [
-        vk::DescriptorPoolSize {
-            ty: vk::DescriptorType::UNIFORM_BUFFER,
-            descriptor_count: 1,
+    let descriptor_sizes = BINDINGS.map(|(set, _, ty)| vk::DescriptorPoolSize {
+        ty,
+        descriptor_count: if set == MATERIAL_SET {
+            MATERIAL_SETS
+        } else {
+            1
         },
-        vk::DescriptorPoolSize {
-            ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-            descriptor_count: 1,
-        },
-    ];
+    });
     let descriptor_pool_info = vk::DescriptorPoolCreateInfo::default()
         .pool_sizes(&descriptor_sizes)
-        .max_sets(1);
+        .max_sets(1 + MATERIAL_SETS);
 
     let descriptor_pool = unsafe {
         device
@@ -29,40 +279,99 @@ pub fn create_descriptor_set(
             .create_descriptor_pool(&descriptor_pool_info, None)
             .unwrap()
     };
-    let desc_layout_bindings = [
-        vk::DescriptorSetLayoutBinding {
-            descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
-            descriptor_count: 1,
-            stage_flags: vk::ShaderStageFlags::VERTEX,
-            ..Default::default()
+    crate::object_audit::created(descriptor_pool, "window descriptor pool");
+
+    let desc_set_layouts = [GLOBAL_SET, MATERIAL_SET, OBJECT_SET].map(|set| {
+        let desc_layout_bindings = interface.set_layout_bindings(set);
+        let descriptor_info =
+            vk::DescriptorSetLayoutCreateInfo::default().bindings(&desc_layout_bindings);
+        let layout = unsafe {
+            device
+                .ash
+                .create_descriptor_set_layout(&descriptor_info, None)
+                .unwrap()
+        };
+        crate::object_audit::created(layout, "descriptor set layout");
+        layout
+    });
+
+    let global = allocate_set(
+        device,
+        descriptor_pool,
+        desc_set_layouts[GLOBAL_SET as usize],
+    );
+
+    (
+        descriptor_pool,
+        AAADescriptorSets {
+            global,
+            default_material: vk::DescriptorSet::null(),
+            texture_array: vk::DescriptorSet::null(),
         },
+        desc_set_layouts,
+    )
+}
+
+/// One set of `layout` from `descriptor_pool`, which has room for every set of
+/// [`AAADescriptorSets`].
+pub fn allocate_set(
+    device: &AAADevice,
+    descriptor_pool: vk::DescriptorPool,
+    layout: vk::DescriptorSetLayout,
+) -> vk::DescriptorSet {
+    let layouts = [layout];
+    let desc_alloc_info = vk::DescriptorSetAllocateInfo::default()
+        .descriptor_pool(descriptor_pool)
+        .set_layouts(&layouts);
+    unsafe {
+        device
+            .ash
+            .allocate_descriptor_sets(&desc_alloc_info)
+            .unwrap()[0]
+    }
+}
+
+/// Layout of the deform set, [`DEFORM_BINDINGS`].
+pub fn create_deform_set_layout(device: &AAADevice) -> vk::DescriptorSetLayout {
+    let bindings = DEFORM_BINDINGS.map(|(_, binding, descriptor_type, stage_flags)| {
         vk::DescriptorSetLayoutBinding {
-            binding: 1,
-            descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            binding,
+            descriptor_type,
             descriptor_count: 1,
-            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            stage_flags,
             ..Default::default()
-        },
-    ];
-    let descriptor_info =
-        vk::DescriptorSetLayoutCreateInfo::default().bindings(&desc_layout_bindings);
-
-    let desc_set_layouts = [unsafe {
+        }
+    });
+    let descriptor_info = vk::DescriptorSetLayoutCreateInfo::default().bindings(&bindings);
+    let layout = unsafe {
         device
             .ash
             .create_descriptor_set_layout(&descriptor_info, None)
             .unwrap()
-    }];
+    };
+    crate::object_audit::created(layout, "deform set layout");
+    layout
+}
 
-    let desc_alloc_info = vk::DescriptorSetAllocateInfo::default()
-        .descriptor_pool(descriptor_pool)
-        .set_layouts(&desc_set_layouts);
-    let descriptor_sets = unsafe {
+/// Layout of the depth of field set, every image read by the fragment stage.
+pub fn create_dof_set_layout(device: &AAADevice) -> vk::DescriptorSetLayout {
+    let bindings =
+        DOF_BINDINGS.map(
+            |(_, binding, descriptor_type)| vk::DescriptorSetLayoutBinding {
+                binding,
+                descriptor_type,
+                descriptor_count: 1,
+                stage_flags: vk::ShaderStageFlags::FRAGMENT,
+                ..Default::default()
+            },
+        );
+    let descriptor_info = vk::DescriptorSetLayoutCreateInfo::default().bindings(&bindings);
+    let layout = unsafe {
         device
             .ash
-            .allocate_descriptor_sets(&desc_alloc_info)
+            .create_descriptor_set_layout(&descriptor_info, None)
             .unwrap()
     };
-
-    (descriptor_pool, descriptor_sets, desc_set_layouts)
+    crate::object_audit::created(layout, "depth of field set layout");
+    layout
 }
@@ -1,7 +1,63 @@
-use ash::{khr::swapchain, vk};
+use super::{pipeline_cache::AAAPipelineCache, sampler::AAASamplerCache};
+use crate::block_compression::BlockFormat;
+use crate::model::{MeshLimits, Vertex};
+use crate::present_wait::PresentWaitSupport;
+use ash::{
+    ext::memory_budget,
+    khr::{present_id, present_wait, swapchain},
+    prelude::VkResult,
+    vk,
+};
+use log::warn;
+use std::{ffi::CStr, mem, time::Duration};
 
 pub struct AAADevice {
     pub ash: ash::Device,
+    /// `VK_EXT_memory_budget` is enabled, see [`super::memory_budget`].
+    pub memory_budget: bool,
+    /// Meshes over these are split at registration.
+    pub mesh_limits: MeshLimits,
+    /// Line widths the pipelines may use, only 1 without the `wideLines` feature.
+    pub line_width_range: [f32; 2],
+    /// `fillModeNonSolid`, pipelines may draw their triangles as lines or points.
+    pub fill_mode_non_solid: bool,
+    /// `geometryShader`, materials may have a geometry stage, see `Material::geometry_shader`.
+    pub geometry_shader: bool,
+    /// `tessellationShader`, materials may have tessellation stages, see
+    /// `Material::tessellation`.
+    pub tessellation_shader: bool,
+    /// `maxTessellationPatchSize`, the most vertices of a patch, 0 without tessellation.
+    pub max_tessellation_patch_size: u32,
+    /// `depthBiasClamp`, a depth bias may clamp its offset, see `DepthBias::clamp`.
+    pub depth_bias_clamp: bool,
+    /// Format of the color textures, `_SRGB` unless the shaders gamma correct themselves.
+    pub texture_format: vk::Format,
+    /// Textures can blit their mip chain with linear filtering, otherwise they keep a single
+    /// level.
+    pub mipmaps: bool,
+    /// Anisotropy samplers are clamped to, 1 without the `samplerAnisotropy` feature.
+    pub max_sampler_anisotropy: f32,
+    /// `maxImageArrayLayers`, the most layers of a texture array.
+    pub max_image_array_layers: u32,
+    /// `maxImageDimensionCube`, the largest cubemap face edge.
+    #[cfg_attr(not(feature = "images"), allow(dead_code))]
+    pub max_image_dimension_cube: u32,
+    /// `maxUniformBufferRange`, the largest uniform buffer a descriptor may cover.
+    pub max_uniform_buffer_range: u32,
+    /// `maxPushConstantsSize`, the end of the furthest push constant range a layout may declare.
+    pub max_push_constants_size: u32,
+    /// Sample counts both color and depth framebuffers support, MSAA is clamped to them.
+    pub msaa_sample_counts: vk::SampleCountFlags,
+    /// Block compressed formats textures can be sampled in, none without the
+    /// `textureCompressionBC` feature.
+    pub compressed_formats: Vec<vk::Format>,
+    /// `VK_KHR_present_id` and `VK_KHR_present_wait` are enabled, see [`crate::present_wait`].
+    pub present_wait: PresentWaitSupport,
+    present_wait_loader: Option<present_wait::Device>,
+    /// Shared by the textures, see [`super::sampler::create_sampler`].
+    pub samplers: AAASamplerCache,
+    /// Every pipeline is built through it, kept across runs, see [`crate::pipeline_cache`].
+    pub pipeline_cache: AAAPipelineCache,
 }
 
 impl AAADevice {
@@ -9,36 +65,211 @@ impl AAADevice {
         instance: &ash::Instance,
         pdevice: vk::PhysicalDevice,
         queue_family_index: u32,
+        texture_format: vk::Format,
     ) -> Self {
         let priorities = [1.0];
         let queue_info = vk::DeviceQueueCreateInfo::default()
             .queue_family_index(queue_family_index)
             .queue_priorities(&priorities);
-        let device_extension_names_raw = [
+        let supported_extensions = unsafe {
+            instance
+                .enumerate_device_extension_properties(pdevice)
+                .unwrap_or_default()
+        };
+        let supported_names: Vec<&CStr> = supported_extensions
+            .iter()
+            .filter_map(|extension| extension.extension_name_as_c_str().ok())
+            .collect();
+        let memory_budget = supported_names.contains(&memory_budget::NAME);
+        let present_wait = present_wait_support(instance, pdevice, &supported_names);
+        let mut device_extension_names_raw = vec![
             swapchain::NAME.as_ptr(),
             #[cfg(any(target_os = "macos", target_os = "ios"))]
             ash::khr::portability_subset::NAME.as_ptr(),
         ];
+        if memory_budget {
+            device_extension_names_raw.push(memory_budget::NAME.as_ptr());
+        }
+        device_extension_names_raw
+            .extend(present_wait.extension_names().into_iter().map(CStr::as_ptr));
+        let supported_features = unsafe { instance.get_physical_device_features(pdevice) };
+        let wide_lines = supported_features.wide_lines;
+        let fill_mode_non_solid = supported_features.fill_mode_non_solid;
+        let sampler_anisotropy = supported_features.sampler_anisotropy;
+        let texture_compression_bc = supported_features.texture_compression_bc;
+        let geometry_shader = supported_features.geometry_shader;
+        let tessellation_shader = supported_features.tessellation_shader;
+        let depth_bias_clamp = supported_features.depth_bias_clamp;
         let features = vk::PhysicalDeviceFeatures {
             shader_clip_distance: 1,
+            wide_lines,
+            fill_mode_non_solid,
+            sampler_anisotropy,
+            texture_compression_bc,
+            geometry_shader,
+            tessellation_shader,
+            depth_bias_clamp,
             ..Default::default()
         };
-        let device_create_info = vk::DeviceCreateInfo::default()
+        let mut present_id_features =
+            vk::PhysicalDevicePresentIdFeaturesKHR::default().present_id(true);
+        let mut present_wait_features =
+            vk::PhysicalDevicePresentWaitFeaturesKHR::default().present_wait(true);
+        let mut device_create_info = vk::DeviceCreateInfo::default()
             .queue_create_infos(std::slice::from_ref(&queue_info))
             .enabled_extension_names(&device_extension_names_raw)
             .enabled_features(&features);
+        if present_wait.present_id {
+            device_create_info = device_create_info.push_next(&mut present_id_features);
+        }
+        if present_wait.present_wait {
+            device_create_info = device_create_info.push_next(&mut present_wait_features);
+        }
         let ash = unsafe {
             instance
                 .create_device(pdevice, &device_create_info, None)
                 .unwrap()
         };
 
-        Self { ash }
+        let properties = unsafe { instance.get_physical_device_properties(pdevice) };
+        let limits = properties.limits;
+        let pipeline_cache = AAAPipelineCache::load(&ash, &properties);
+        let present_wait_loader = present_wait
+            .available()
+            .then(|| present_wait::Device::new(instance, &ash));
+        Self {
+            ash,
+            memory_budget,
+            mesh_limits: mesh_limits(instance, pdevice),
+            line_width_range: if wide_lines == vk::TRUE {
+                limits.line_width_range
+            } else {
+                [1.0, 1.0]
+            },
+            fill_mode_non_solid: fill_mode_non_solid == vk::TRUE,
+            geometry_shader: geometry_shader == vk::TRUE,
+            tessellation_shader: tessellation_shader == vk::TRUE,
+            max_tessellation_patch_size: if tessellation_shader == vk::TRUE {
+                limits.max_tessellation_patch_size
+            } else {
+                0
+            },
+            depth_bias_clamp: depth_bias_clamp == vk::TRUE,
+            texture_format,
+            mipmaps: mipmaps(instance, pdevice, texture_format),
+            max_sampler_anisotropy: if sampler_anisotropy == vk::TRUE {
+                limits.max_sampler_anisotropy
+            } else {
+                1.0
+            },
+            max_image_array_layers: limits.max_image_array_layers,
+            max_image_dimension_cube: limits.max_image_dimension_cube,
+            max_uniform_buffer_range: limits.max_uniform_buffer_range,
+            max_push_constants_size: limits.max_push_constants_size,
+            msaa_sample_counts: limits.framebuffer_color_sample_counts
+                & limits.framebuffer_depth_sample_counts,
+            compressed_formats: if texture_compression_bc == vk::TRUE {
+                compressed_formats(instance, pdevice)
+            } else {
+                Vec::new()
+            },
+            present_wait,
+            present_wait_loader,
+            samplers: AAASamplerCache::default(),
+            pipeline_cache,
+        }
+    }
+
+    /// Block until the present tagged `present_id` on `swapchain` is shown, `TIMEOUT` is an
+    /// error like the others.
+    pub fn wait_for_present(
+        &self,
+        swapchain: vk::SwapchainKHR,
+        present_id: u64,
+        timeout: Duration,
+    ) -> VkResult<()> {
+        let loader = self
+            .present_wait_loader
+            .as_ref()
+            .ok_or(vk::Result::ERROR_EXTENSION_NOT_PRESENT)?;
+        unsafe { loader.wait_for_present(swapchain, present_id, timeout.as_nanos() as u64) }
+    }
+}
+
+fn present_wait_support(
+    instance: &ash::Instance,
+    pdevice: vk::PhysicalDevice,
+    supported_names: &[&CStr],
+) -> PresentWaitSupport {
+    // The features are only worth querying once the extensions are offered
+    if !supported_names.contains(&present_id::NAME) {
+        return PresentWaitSupport::default();
+    }
+    let mut present_id_features = vk::PhysicalDevicePresentIdFeaturesKHR::default();
+    let mut present_wait_features = vk::PhysicalDevicePresentWaitFeaturesKHR::default();
+    let mut features = vk::PhysicalDeviceFeatures2::default().push_next(&mut present_id_features);
+    if supported_names.contains(&present_wait::NAME) {
+        features = features.push_next(&mut present_wait_features);
+    }
+    unsafe { instance.get_physical_device_features2(pdevice, &mut features) };
+    PresentWaitSupport::detect(
+        supported_names,
+        present_id_features.present_id == vk::TRUE,
+        present_wait_features.present_wait == vk::TRUE,
+    )
+}
+
+fn mipmaps(instance: &ash::Instance, pdevice: vk::PhysicalDevice, format: vk::Format) -> bool {
+    let required = vk::FormatFeatureFlags::BLIT_SRC
+        | vk::FormatFeatureFlags::BLIT_DST
+        | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR;
+    let properties = unsafe { instance.get_physical_device_format_properties(pdevice, format) };
+    let supported = properties.optimal_tiling_features.contains(required);
+    if !supported {
+        warn!("{format:?} images cannot be blitted with linear filtering, textures keep a single mip level");
+    }
+    supported
+}
+
+fn compressed_formats(instance: &ash::Instance, pdevice: vk::PhysicalDevice) -> Vec<vk::Format> {
+    let required = vk::FormatFeatureFlags::SAMPLED_IMAGE;
+    BlockFormat::ALL
+        .iter()
+        .flat_map(|format| [format.vk_format(false), format.vk_format(true)])
+        .filter(|&format| {
+            let properties =
+                unsafe { instance.get_physical_device_format_properties(pdevice, format) };
+            properties.optimal_tiling_features.contains(required)
+        })
+        .collect()
+}
+
+fn mesh_limits(instance: &ash::Instance, pdevice: vk::PhysicalDevice) -> MeshLimits {
+    let api_version = unsafe { instance.get_physical_device_properties(pdevice) }.api_version;
+    let mut maintenance3 = vk::PhysicalDeviceMaintenance3Properties::default();
+    let mut properties = vk::PhysicalDeviceProperties2::default();
+    // The largest allocation is only reported from Vulkan 1.1 on
+    if api_version >= vk::API_VERSION_1_1 {
+        properties = properties.push_next(&mut maintenance3);
+    }
+    unsafe { instance.get_physical_device_properties2(pdevice, &mut properties) };
+    let max_index_value = properties.properties.limits.max_draw_indexed_index_value as u64;
+    let max_allocation = match maintenance3.max_memory_allocation_size {
+        0 => u64::MAX,
+        size => size,
+    };
+    MeshLimits {
+        max_vertices: (max_index_value + 1)
+            .min(max_allocation / mem::size_of::<Vertex>() as u64)
+            .min(u32::MAX as u64) as u32,
+        max_indices: (max_allocation / mem::size_of::<u32>() as u64).min(u32::MAX as u64) as u32,
     }
 }
 
 impl Drop for AAADevice {
     fn drop(&mut self) {
+        self.samplers.destroy(&self.ash);
+        self.pipeline_cache.save_and_destroy(&self.ash);
         unsafe {
             self.ash.destroy_device(None);
         }
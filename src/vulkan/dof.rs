@@ -0,0 +1,684 @@
+use super::{
+    descriptor_set::{
+        create_dof_set_layout, DOF_COC_BINDING, DOF_COLOR_BINDING, DOF_DEPTH_BINDING,
+        DOF_FAR_BINDING, DOF_NEAR_BINDING,
+    },
+    device::AAADevice,
+    offscreen::{create_image, create_view},
+    pipeline::create_background_pipeline,
+    sampler::create_sampler,
+};
+use crate::{
+    dof::{DofConstants, FAR_FIELD, NEAR_FIELD},
+    shaders::Shader,
+    texture::SamplerDesc,
+};
+use ash::vk;
+use std::mem;
+
+/// Format of the circle of confusion, signed pixels.
+const COC_FORMAT: vk::Format = vk::Format::R16_SFLOAT;
+/// Format of the near and far fields, the near field's alpha is its coverage.
+const FIELD_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+/// Depth of field of the presented image, see `crate::dof`. The scene is copied out of the
+/// presented image, its circle of confusion computed from the depth, blurred at half resolution
+/// in front of and behind the focus plane, then both fields composited back into the presented
+/// image. Built for one swapchain, destroyed with it.
+pub struct AAADepthOfField {
+    set_layout: vk::DescriptorSetLayout,
+    layout: vk::PipelineLayout,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_set: vk::DescriptorSet,
+    /// Copy of the presented image the passes sample, in the surface's format.
+    color: Target,
+    coc: Target,
+    near: Target,
+    far: Target,
+    /// Writes [`Self::coc`].
+    coc_pass: Pass,
+    /// Writes [`Self::near`] then [`Self::far`], both are compatible.
+    gather_pass: Pass,
+    near_framebuffer: vk::Framebuffer,
+    far_framebuffer: vk::Framebuffer,
+    coc_framebuffer: vk::Framebuffer,
+    /// Writes the presented image, through one framebuffer per swapchain image.
+    composite_pass: Pass,
+    composite_framebuffers: Vec<vk::Framebuffer>,
+    depth_image: vk::Image,
+    extent: vk::Extent2D,
+    half_extent: vk::Extent2D,
+}
+
+/// Image sampled by the later passes, and its view.
+struct Target {
+    image: vk::Image,
+    memory: vk::DeviceMemory,
+    view: vk::ImageView,
+}
+
+impl Target {
+    fn new(
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        format: vk::Format,
+        extent: vk::Extent2D,
+        usage: vk::ImageUsageFlags,
+    ) -> Self {
+        let (image, memory) = create_image(
+            device,
+            device_memory_properties,
+            format,
+            extent,
+            vk::SampleCountFlags::TYPE_1,
+            usage | vk::ImageUsageFlags::SAMPLED,
+        );
+        let view = create_view(device, image, format, vk::ImageAspectFlags::COLOR);
+        Self {
+            image,
+            memory,
+            view,
+        }
+    }
+
+    fn destroy(&self, device: &AAADevice) {
+        unsafe {
+            crate::object_audit::destroyed(self.view);
+            device.ash.destroy_image_view(self.view, None);
+            crate::object_audit::destroyed(self.image);
+            device.ash.destroy_image(self.image, None);
+            crate::object_audit::destroyed(self.memory);
+            device.ash.free_memory(self.memory, None);
+        }
+    }
+}
+
+/// Render pass of a single color attachment and the fullscreen pipeline drawing into it.
+struct Pass {
+    renderpass: vk::RenderPass,
+    pipeline: vk::Pipeline,
+}
+
+impl Pass {
+    fn new(
+        device: &AAADevice,
+        layout: vk::PipelineLayout,
+        vertex: &Shader,
+        fragment: &str,
+        renderpass: vk::RenderPass,
+    ) -> Self {
+        let fragment = Shader::from_filename(fragment, vk::ShaderStageFlags::FRAGMENT, device);
+        let stages = [
+            vertex.pipeline_shader_stage_create_info,
+            fragment.pipeline_shader_stage_create_info,
+        ];
+        let (pipeline, _) = create_background_pipeline(
+            device,
+            renderpass,
+            layout,
+            &stages,
+            vk::SampleCountFlags::TYPE_1,
+            None,
+        )
+        .expect("Unable to create depth of field pipeline");
+        unsafe {
+            crate::object_audit::destroyed(fragment.module);
+            device.ash.destroy_shader_module(fragment.module, None);
+        }
+        Self {
+            renderpass,
+            pipeline,
+        }
+    }
+
+    fn destroy(&self, device: &AAADevice) {
+        unsafe {
+            crate::object_audit::destroyed(self.pipeline);
+            device.ash.destroy_pipeline(self.pipeline, None);
+            crate::object_audit::destroyed(self.renderpass);
+            device.ash.destroy_render_pass(self.renderpass, None);
+        }
+    }
+}
+
+impl AAADepthOfField {
+    /// Whether a swapchain of `samples` whose images have `image_usage` can be post processed:
+    /// the depth must be single sampled to be read as a texture, the presented image copied.
+    pub fn supported(samples: vk::SampleCountFlags, image_usage: vk::ImageUsageFlags) -> bool {
+        samples == vk::SampleCountFlags::TYPE_1
+            && image_usage.contains(vk::ImageUsageFlags::TRANSFER_SRC)
+    }
+
+    /// The depth image must have the `SAMPLED` usage, see [`Self::supported`] for the swapchain.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        format: vk::Format,
+        extent: vk::Extent2D,
+        present_image_views: &[vk::ImageView],
+        depth_image: vk::Image,
+        depth_view: vk::ImageView,
+    ) -> Self {
+        let half_extent = vk::Extent2D {
+            width: extent.width.div_ceil(2).max(1),
+            height: extent.height.div_ceil(2).max(1),
+        };
+        let color = Target::new(
+            device,
+            device_memory_properties,
+            format,
+            extent,
+            vk::ImageUsageFlags::TRANSFER_DST,
+        );
+        let coc = Target::new(
+            device,
+            device_memory_properties,
+            COC_FORMAT,
+            extent,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT,
+        );
+        let [near, far] = [(); 2].map(|_| {
+            Target::new(
+                device,
+                device_memory_properties,
+                FIELD_FORMAT,
+                half_extent,
+                vk::ImageUsageFlags::COLOR_ATTACHMENT,
+            )
+        });
+
+        let set_layout = create_dof_set_layout(device);
+        let push_constant_range = vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            offset: 0,
+            size: mem::size_of::<DofConstants>() as u32,
+        };
+        let layout_info = vk::PipelineLayoutCreateInfo::default()
+            .set_layouts(std::slice::from_ref(&set_layout))
+            .push_constant_ranges(std::slice::from_ref(&push_constant_range));
+        let layout = unsafe {
+            device
+                .ash
+                .create_pipeline_layout(&layout_info, None)
+                .expect("Failed to create depth of field pipeline layout!")
+        };
+        crate::object_audit::created(layout, "depth of field");
+
+        // The background's vertex shader covers the target with one triangle
+        let vertex = Shader::from_filename("background", vk::ShaderStageFlags::VERTEX, device);
+        let coc_pass = Pass::new(
+            device,
+            layout,
+            &vertex,
+            "dof_coc",
+            create_sampled_renderpass(device, COC_FORMAT),
+        );
+        let gather_pass = Pass::new(
+            device,
+            layout,
+            &vertex,
+            "dof_gather",
+            create_sampled_renderpass(device, FIELD_FORMAT),
+        );
+        let composite_pass = Pass::new(
+            device,
+            layout,
+            &vertex,
+            "dof_composite",
+            create_composite_renderpass(device, format),
+        );
+        unsafe {
+            crate::object_audit::destroyed(vertex.module);
+            device.ash.destroy_shader_module(vertex.module, None);
+        }
+
+        let coc_framebuffer = create_framebuffer(device, coc_pass.renderpass, coc.view, extent);
+        let near_framebuffer =
+            create_framebuffer(device, gather_pass.renderpass, near.view, half_extent);
+        let far_framebuffer =
+            create_framebuffer(device, gather_pass.renderpass, far.view, half_extent);
+        let composite_framebuffers = present_image_views
+            .iter()
+            .map(|&view| create_framebuffer(device, composite_pass.renderpass, view, extent))
+            .collect();
+
+        let pool_size = vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            descriptor_count: 5,
+        };
+        let pool_info = vk::DescriptorPoolCreateInfo::default()
+            .pool_sizes(std::slice::from_ref(&pool_size))
+            .max_sets(1);
+        let descriptor_pool =
+            unsafe { device.ash.create_descriptor_pool(&pool_info, None).unwrap() };
+        crate::object_audit::created(descriptor_pool, "depth of field");
+        let allocate_info = vk::DescriptorSetAllocateInfo::default()
+            .descriptor_pool(descriptor_pool)
+            .set_layouts(std::slice::from_ref(&set_layout));
+        let descriptor_set =
+            unsafe { device.ash.allocate_descriptor_sets(&allocate_info).unwrap()[0] };
+        // Sampled between texels by the half resolution fields, never past the edges
+        let sampler = create_sampler(
+            device,
+            SamplerDesc::default().with_address_mode(vk::SamplerAddressMode::CLAMP_TO_EDGE),
+        )
+        .expect("Unable to create the depth of field sampler");
+        let image_infos = [
+            (
+                DOF_COLOR_BINDING,
+                color.view,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            ),
+            (
+                DOF_DEPTH_BINDING,
+                depth_view,
+                vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+            ),
+            (
+                DOF_COC_BINDING,
+                coc.view,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            ),
+            (
+                DOF_NEAR_BINDING,
+                near.view,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            ),
+            (
+                DOF_FAR_BINDING,
+                far.view,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            ),
+        ]
+        .map(|(binding, image_view, image_layout)| {
+            (
+                binding,
+                vk::DescriptorImageInfo {
+                    sampler,
+                    image_view,
+                    image_layout,
+                },
+            )
+        });
+        let writes = image_infos.each_ref().map(|(binding, image_info)| {
+            vk::WriteDescriptorSet::default()
+                .dst_set(descriptor_set)
+                .dst_binding(*binding)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .image_info(std::slice::from_ref(image_info))
+        });
+        unsafe { device.ash.update_descriptor_sets(&writes, &[]) };
+
+        Self {
+            set_layout,
+            layout,
+            descriptor_pool,
+            descriptor_set,
+            color,
+            coc,
+            near,
+            far,
+            coc_pass,
+            gather_pass,
+            near_framebuffer,
+            far_framebuffer,
+            coc_framebuffer,
+            composite_pass,
+            composite_framebuffers,
+            depth_image,
+            extent,
+            half_extent,
+        }
+    }
+
+    /// Blur the swapchain image `present_index`, drawn by the scene's render pass and left in
+    /// `PRESENT_SRC_KHR`, as it is again once done. The depth is read in between and left ready
+    /// for the next frame's render pass.
+    pub fn record(
+        &self,
+        device: &AAADevice,
+        command_buffer: vk::CommandBuffer,
+        present_image: vk::Image,
+        present_index: u32,
+        constants: DofConstants,
+    ) {
+        let color_range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            level_count: 1,
+            layer_count: 1,
+            ..Default::default()
+        };
+        let depth_range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::DEPTH,
+            ..color_range
+        };
+        let color_layers = vk::ImageSubresourceLayers {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            layer_count: 1,
+            ..Default::default()
+        };
+        let to_copy = [
+            vk::ImageMemoryBarrier {
+                src_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                dst_access_mask: vk::AccessFlags::TRANSFER_READ,
+                old_layout: vk::ImageLayout::PRESENT_SRC_KHR,
+                new_layout: vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                image: present_image,
+                subresource_range: color_range,
+                ..Default::default()
+            },
+            vk::ImageMemoryBarrier {
+                dst_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+                old_layout: vk::ImageLayout::UNDEFINED,
+                new_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                image: self.color.image,
+                subresource_range: color_range,
+                ..Default::default()
+            },
+            vk::ImageMemoryBarrier {
+                src_access_mask: vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                dst_access_mask: vk::AccessFlags::SHADER_READ,
+                old_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+                new_layout: vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+                image: self.depth_image,
+                subresource_range: depth_range,
+                ..Default::default()
+            },
+        ];
+        let copy = vk::ImageCopy {
+            src_subresource: color_layers,
+            dst_subresource: color_layers,
+            extent: self.extent.into(),
+            ..Default::default()
+        };
+        let to_sampled = vk::ImageMemoryBarrier {
+            src_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+            dst_access_mask: vk::AccessFlags::SHADER_READ,
+            old_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            new_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            image: self.color.image,
+            subresource_range: color_range,
+            ..Default::default()
+        };
+        let to_attachment = vk::ImageMemoryBarrier {
+            src_access_mask: vk::AccessFlags::SHADER_READ,
+            dst_access_mask: vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            old_layout: vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+            new_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            image: self.depth_image,
+            subresource_range: depth_range,
+            ..Default::default()
+        };
+        let near = DofConstants {
+            side: NEAR_FIELD,
+            ..constants
+        };
+        let far = DofConstants {
+            side: FAR_FIELD,
+            ..constants
+        };
+        unsafe {
+            device.ash.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                    | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                vk::PipelineStageFlags::TRANSFER | vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &to_copy,
+            );
+            device.ash.cmd_copy_image(
+                command_buffer,
+                present_image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                self.color.image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &[copy],
+            );
+            device.ash.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[to_sampled],
+            );
+            device.ash.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.layout,
+                0,
+                &[self.descriptor_set],
+                &[],
+            );
+        }
+        self.record_pass(
+            device,
+            command_buffer,
+            &self.coc_pass,
+            self.coc_framebuffer,
+            self.extent,
+            &constants,
+        );
+        self.record_pass(
+            device,
+            command_buffer,
+            &self.gather_pass,
+            self.near_framebuffer,
+            self.half_extent,
+            &near,
+        );
+        self.record_pass(
+            device,
+            command_buffer,
+            &self.gather_pass,
+            self.far_framebuffer,
+            self.half_extent,
+            &far,
+        );
+        self.record_pass(
+            device,
+            command_buffer,
+            &self.composite_pass,
+            self.composite_framebuffers[present_index as usize],
+            self.extent,
+            &constants,
+        );
+        unsafe {
+            device.ash.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                    | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[to_attachment],
+            );
+        }
+    }
+
+    /// One fullscreen triangle of `pass` into `framebuffer`.
+    fn record_pass(
+        &self,
+        device: &AAADevice,
+        command_buffer: vk::CommandBuffer,
+        pass: &Pass,
+        framebuffer: vk::Framebuffer,
+        extent: vk::Extent2D,
+        constants: &DofConstants,
+    ) {
+        let render_pass_begin_info = vk::RenderPassBeginInfo::default()
+            .render_pass(pass.renderpass)
+            .framebuffer(framebuffer)
+            .render_area(extent.into());
+        unsafe {
+            device.ash.cmd_begin_render_pass(
+                command_buffer,
+                &render_pass_begin_info,
+                vk::SubpassContents::INLINE,
+            );
+            device.ash.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                pass.pipeline,
+            );
+            device.ash.cmd_push_constants(
+                command_buffer,
+                self.layout,
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                constants.as_bytes(),
+            );
+            device.ash.cmd_set_viewport(
+                command_buffer,
+                0,
+                &[vk::Viewport {
+                    x: 0.0,
+                    y: 0.0,
+                    width: extent.width as f32,
+                    height: extent.height as f32,
+                    min_depth: 0.0,
+                    max_depth: 1.0,
+                }],
+            );
+            device
+                .ash
+                .cmd_set_scissor(command_buffer, 0, &[extent.into()]);
+            device.ash.cmd_draw(command_buffer, 3, 1, 0, 0);
+            device.ash.cmd_end_render_pass(command_buffer);
+        }
+    }
+
+    /// The commands blurring must have completed, the depth and presented images are not
+    /// destroyed.
+    pub fn destroy(&self, device: &AAADevice) {
+        unsafe {
+            for &framebuffer in self.composite_framebuffers.iter().chain([
+                &self.coc_framebuffer,
+                &self.near_framebuffer,
+                &self.far_framebuffer,
+            ]) {
+                crate::object_audit::destroyed(framebuffer);
+                device.ash.destroy_framebuffer(framebuffer, None);
+            }
+            crate::object_audit::destroyed(self.descriptor_pool);
+            device
+                .ash
+                .destroy_descriptor_pool(self.descriptor_pool, None);
+        }
+        for pass in [&self.coc_pass, &self.gather_pass, &self.composite_pass] {
+            pass.destroy(device);
+        }
+        for target in [&self.color, &self.coc, &self.near, &self.far] {
+            target.destroy(device);
+        }
+        unsafe {
+            crate::object_audit::destroyed(self.layout);
+            device.ash.destroy_pipeline_layout(self.layout, None);
+            crate::object_audit::destroyed(self.set_layout);
+            device
+                .ash
+                .destroy_descriptor_set_layout(self.set_layout, None);
+        }
+    }
+}
+
+fn create_framebuffer(
+    device: &AAADevice,
+    renderpass: vk::RenderPass,
+    view: vk::ImageView,
+    extent: vk::Extent2D,
+) -> vk::Framebuffer {
+    let framebuffer_create_info = vk::FramebufferCreateInfo::default()
+        .render_pass(renderpass)
+        .attachments(std::slice::from_ref(&view))
+        .width(extent.width)
+        .height(extent.height)
+        .layers(1);
+    let framebuffer = unsafe {
+        device
+            .ash
+            .create_framebuffer(&framebuffer_create_info, None)
+            .unwrap()
+    };
+    crate::object_audit::created(framebuffer, "depth of field");
+    framebuffer
+}
+
+/// Single color attachment of `format`, every pixel written, left ready to be sampled by the
+/// passes that follow.
+fn create_sampled_renderpass(device: &AAADevice, format: vk::Format) -> vk::RenderPass {
+    create_fullscreen_renderpass(
+        device,
+        format,
+        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        vk::SubpassDependency {
+            src_subpass: 0,
+            dst_subpass: vk::SUBPASS_EXTERNAL,
+            src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            src_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            dst_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
+            dst_access_mask: vk::AccessFlags::SHADER_READ,
+            ..Default::default()
+        },
+    )
+}
+
+/// The presented image written over once the scene has been copied out of it, left ready to
+/// be presented.
+fn create_composite_renderpass(device: &AAADevice, format: vk::Format) -> vk::RenderPass {
+    create_fullscreen_renderpass(
+        device,
+        format,
+        vk::ImageLayout::PRESENT_SRC_KHR,
+        vk::SubpassDependency {
+            src_subpass: vk::SUBPASS_EXTERNAL,
+            dst_subpass: 0,
+            src_stage_mask: vk::PipelineStageFlags::TRANSFER,
+            dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            ..Default::default()
+        },
+    )
+}
+
+/// Single color attachment whose previous content is discarded, the fullscreen triangle covers it.
+fn create_fullscreen_renderpass(
+    device: &AAADevice,
+    format: vk::Format,
+    final_layout: vk::ImageLayout,
+    dependency: vk::SubpassDependency,
+) -> vk::RenderPass {
+    let attachment = vk::AttachmentDescription {
+        format,
+        samples: vk::SampleCountFlags::TYPE_1,
+        load_op: vk::AttachmentLoadOp::DONT_CARE,
+        store_op: vk::AttachmentStoreOp::STORE,
+        final_layout,
+        ..Default::default()
+    };
+    let color_attachment_ref = vk::AttachmentReference {
+        attachment: 0,
+        layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+    };
+    let subpass = vk::SubpassDescription::default()
+        .color_attachments(std::slice::from_ref(&color_attachment_ref))
+        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS);
+    let renderpass_create_info = vk::RenderPassCreateInfo::default()
+        .attachments(std::slice::from_ref(&attachment))
+        .subpasses(std::slice::from_ref(&subpass))
+        .dependencies(std::slice::from_ref(&dependency));
+    let renderpass = unsafe {
+        device
+            .ash
+            .create_render_pass(&renderpass_create_info, None)
+            .unwrap()
+    };
+    crate::object_audit::created(renderpass, "depth of field");
+    renderpass
+}
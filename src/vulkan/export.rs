@@ -0,0 +1,338 @@
+use super::{
+    descriptor_set::{MATERIAL_SET, SET_COUNT},
+    device::AAADevice,
+    offscreen::{bind_dedicated, create_unbound_image, create_view, AttachmentMemory},
+    pipeline::create_background_pipeline,
+    sampler::create_sampler,
+    transient::AAATransientMemory,
+};
+use crate::{
+    color_space::{ExportColorSpace, SourceTransfer},
+    shaders::Shader,
+    texture::SamplerDesc,
+    transient::Lifetime,
+};
+use ash::vk;
+use std::mem;
+
+const COLOR_SPACES: [ExportColorSpace; 2] = [ExportColorSpace::Srgb8, ExportColorSpace::Linear16F];
+
+/// Fullscreen pass decoding an image of the surface's format for an export, see `color_space`.
+/// Reads it as the material texture of the engine's binding model, the transfer function in a
+/// fragment push constant.
+pub struct AAAExportConverter {
+    layout: vk::PipelineLayout,
+    material_layout: vk::DescriptorSetLayout,
+    /// Render pass and pipeline writing each of [`COLOR_SPACES`].
+    targets: [(vk::RenderPass, vk::Pipeline); COLOR_SPACES.len()],
+}
+
+impl AAAExportConverter {
+    pub fn new(
+        device: &AAADevice,
+        desc_set_layouts: &[vk::DescriptorSetLayout; SET_COUNT],
+    ) -> Self {
+        let push_constant_range = vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            offset: 0,
+            size: mem::size_of::<u32>() as u32,
+        };
+        let layout_info = vk::PipelineLayoutCreateInfo::default()
+            .set_layouts(desc_set_layouts)
+            .push_constant_ranges(std::slice::from_ref(&push_constant_range));
+        let layout = unsafe {
+            device
+                .ash
+                .create_pipeline_layout(&layout_info, None)
+                .expect("Failed to create export pipeline layout!")
+        };
+        crate::object_audit::created(layout, "export conversion");
+
+        // The background's vertex shader covers the target with one triangle
+        let vertex = Shader::from_filename("background", vk::ShaderStageFlags::VERTEX, device);
+        let fragment = Shader::from_filename("export", vk::ShaderStageFlags::FRAGMENT, device);
+        let stages = [
+            vertex.pipeline_shader_stage_create_info,
+            fragment.pipeline_shader_stage_create_info,
+        ];
+        let targets = COLOR_SPACES.map(|color_space| {
+            let renderpass = create_export_renderpass(device, color_space.format());
+            let (pipeline, _) = create_background_pipeline(
+                device,
+                renderpass,
+                layout,
+                &stages,
+                vk::SampleCountFlags::TYPE_1,
+                None,
+            )
+            .expect("Unable to create export pipeline");
+            (renderpass, pipeline)
+        });
+        unsafe {
+            crate::object_audit::destroyed(vertex.module);
+            device.ash.destroy_shader_module(vertex.module, None);
+            crate::object_audit::destroyed(fragment.module);
+            device.ash.destroy_shader_module(fragment.module, None);
+        }
+
+        Self {
+            layout,
+            material_layout: desc_set_layouts[MATERIAL_SET as usize],
+            targets,
+        }
+    }
+
+    fn target(&self, color_space: ExportColorSpace) -> (vk::RenderPass, vk::Pipeline) {
+        let index = COLOR_SPACES
+            .iter()
+            .position(|&known| known == color_space)
+            .expect("Every color space has a target");
+        self.targets[index]
+    }
+
+    /// The commands converting must have completed.
+    pub fn destroy(&self, device: &AAADevice) {
+        unsafe {
+            for (renderpass, pipeline) in self.targets {
+                crate::object_audit::destroyed(pipeline);
+                device.ash.destroy_pipeline(pipeline, None);
+                crate::object_audit::destroyed(renderpass);
+                device.ash.destroy_render_pass(renderpass, None);
+            }
+            crate::object_audit::destroyed(self.layout);
+            device.ash.destroy_pipeline_layout(self.layout, None);
+        }
+    }
+}
+
+/// One source image converted into an image of the export's color space, both of `extent`.
+pub struct AAAConversion {
+    pub image: vk::Image,
+    memory: AttachmentMemory,
+    view: vk::ImageView,
+    source_view: vk::ImageView,
+    framebuffer: vk::Framebuffer,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_set: vk::DescriptorSet,
+    renderpass: vk::RenderPass,
+    pipeline: vk::Pipeline,
+    layout: vk::PipelineLayout,
+    transfer: SourceTransfer,
+    extent: vk::Extent2D,
+}
+
+impl AAAConversion {
+    /// `source` must have been created in `source_format` with the `SAMPLED` usage. The image
+    /// is bound in `transient` over the lifetime given when it has room.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        converter: &AAAExportConverter,
+        source: vk::Image,
+        source_format: vk::SurfaceFormatKHR,
+        extent: vk::Extent2D,
+        color_space: ExportColorSpace,
+        transient: Option<(&mut AAATransientMemory, Lifetime)>,
+    ) -> Self {
+        let (renderpass, pipeline) = converter.target(color_space);
+        let image = create_unbound_image(
+            device,
+            color_space.format(),
+            extent,
+            vk::SampleCountFlags::TYPE_1,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
+        );
+        let memory = if transient
+            .is_some_and(|(transient, lifetime)| transient.bind(device, image, lifetime))
+        {
+            AttachmentMemory::Aliased
+        } else {
+            AttachmentMemory::Dedicated(bind_dedicated(device, device_memory_properties, image))
+        };
+        let view = create_view(
+            device,
+            image,
+            color_space.format(),
+            vk::ImageAspectFlags::COLOR,
+        );
+        let source_view = create_view(
+            device,
+            source,
+            source_format.format,
+            vk::ImageAspectFlags::COLOR,
+        );
+        let framebuffer_create_info = vk::FramebufferCreateInfo::default()
+            .render_pass(renderpass)
+            .attachments(std::slice::from_ref(&view))
+            .width(extent.width)
+            .height(extent.height)
+            .layers(1);
+        let framebuffer = unsafe {
+            device
+                .ash
+                .create_framebuffer(&framebuffer_create_info, None)
+                .unwrap()
+        };
+        crate::object_audit::created(framebuffer, "export conversion");
+
+        let pool_size = vk::DescriptorPoolSize {
+            ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            descriptor_count: 1,
+        };
+        let pool_info = vk::DescriptorPoolCreateInfo::default()
+            .pool_sizes(std::slice::from_ref(&pool_size))
+            .max_sets(1);
+        let descriptor_pool =
+            unsafe { device.ash.create_descriptor_pool(&pool_info, None).unwrap() };
+        crate::object_audit::created(descriptor_pool, "export conversion");
+        let allocate_info = vk::DescriptorSetAllocateInfo::default()
+            .descriptor_pool(descriptor_pool)
+            .set_layouts(std::slice::from_ref(&converter.material_layout));
+        let descriptor_set =
+            unsafe { device.ash.allocate_descriptor_sets(&allocate_info).unwrap()[0] };
+        // Fetched texel by texel, the sampler is only there for the binding's type
+        let image_info = vk::DescriptorImageInfo {
+            sampler: create_sampler(device, SamplerDesc::nearest())
+                .expect("Unable to create the export sampler"),
+            image_view: source_view,
+            image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        };
+        let write = vk::WriteDescriptorSet::default()
+            .dst_set(descriptor_set)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(std::slice::from_ref(&image_info));
+        unsafe { device.ash.update_descriptor_sets(&[write], &[]) };
+
+        Self {
+            image,
+            memory,
+            view,
+            source_view,
+            framebuffer,
+            descriptor_pool,
+            descriptor_set,
+            renderpass,
+            pipeline,
+            layout: converter.layout,
+            transfer: SourceTransfer::of(source_format),
+            extent,
+        }
+    }
+
+    /// Draw the source, in `SHADER_READ_ONLY_OPTIMAL`, into [`Self::image`], left in
+    /// `TRANSFER_SRC_OPTIMAL` for the copy that follows.
+    pub fn record(&self, device: &AAADevice, command_buffer: vk::CommandBuffer) {
+        let render_pass_begin_info = vk::RenderPassBeginInfo::default()
+            .render_pass(self.renderpass)
+            .framebuffer(self.framebuffer)
+            .render_area(self.extent.into());
+        unsafe {
+            device.ash.cmd_begin_render_pass(
+                command_buffer,
+                &render_pass_begin_info,
+                vk::SubpassContents::INLINE,
+            );
+            device.ash.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.pipeline,
+            );
+            device.ash.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.layout,
+                MATERIAL_SET,
+                &[self.descriptor_set],
+                &[],
+            );
+            device.ash.cmd_push_constants(
+                command_buffer,
+                self.layout,
+                vk::ShaderStageFlags::FRAGMENT,
+                0,
+                &(self.transfer as u32).to_ne_bytes(),
+            );
+            device.ash.cmd_set_viewport(
+                command_buffer,
+                0,
+                &[vk::Viewport {
+                    x: 0.0,
+                    y: 0.0,
+                    width: self.extent.width as f32,
+                    height: self.extent.height as f32,
+                    min_depth: 0.0,
+                    max_depth: 1.0,
+                }],
+            );
+            device
+                .ash
+                .cmd_set_scissor(command_buffer, 0, &[self.extent.into()]);
+            device.ash.cmd_draw(command_buffer, 3, 1, 0, 0);
+            device.ash.cmd_end_render_pass(command_buffer);
+        }
+    }
+
+    pub fn is_aliased(&self) -> bool {
+        self.memory == AttachmentMemory::Aliased
+    }
+
+    /// The commands converting must have completed, the source image is not destroyed.
+    pub fn destroy(&self, device: &AAADevice) {
+        unsafe {
+            crate::object_audit::destroyed(self.descriptor_pool);
+            device
+                .ash
+                .destroy_descriptor_pool(self.descriptor_pool, None);
+            crate::object_audit::destroyed(self.framebuffer);
+            device.ash.destroy_framebuffer(self.framebuffer, None);
+            crate::object_audit::destroyed(self.source_view);
+            device.ash.destroy_image_view(self.source_view, None);
+            crate::object_audit::destroyed(self.view);
+            device.ash.destroy_image_view(self.view, None);
+            crate::object_audit::destroyed(self.image);
+            device.ash.destroy_image(self.image, None);
+        }
+        self.memory.free(device);
+    }
+}
+
+/// Single color attachment of `format`, left ready to be copied from.
+fn create_export_renderpass(device: &AAADevice, format: vk::Format) -> vk::RenderPass {
+    let attachment = vk::AttachmentDescription {
+        format,
+        samples: vk::SampleCountFlags::TYPE_1,
+        load_op: vk::AttachmentLoadOp::DONT_CARE,
+        store_op: vk::AttachmentStoreOp::STORE,
+        final_layout: vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        ..Default::default()
+    };
+    let color_attachment_ref = vk::AttachmentReference {
+        attachment: 0,
+        layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+    };
+    let dependency = vk::SubpassDependency {
+        src_subpass: 0,
+        dst_subpass: vk::SUBPASS_EXTERNAL,
+        src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        src_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+        dst_stage_mask: vk::PipelineStageFlags::TRANSFER,
+        dst_access_mask: vk::AccessFlags::TRANSFER_READ,
+        ..Default::default()
+    };
+    let subpass = vk::SubpassDescription::default()
+        .color_attachments(std::slice::from_ref(&color_attachment_ref))
+        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS);
+    let renderpass_create_info = vk::RenderPassCreateInfo::default()
+        .attachments(std::slice::from_ref(&attachment))
+        .subpasses(std::slice::from_ref(&subpass))
+        .dependencies(std::slice::from_ref(&dependency));
+    let renderpass = unsafe {
+        device
+            .ash
+            .create_render_pass(&renderpass_create_info, None)
+            .unwrap()
+    };
+    crate::object_audit::created(renderpass, "export conversion");
+    renderpass
+}
@@ -18,6 +18,9 @@ pub fn create_fences(device: &AAADevice) -> Result<(vk::Fence, vk::Fence), Box<d
             .expect("Create fence failed.")
     };
 
+    crate::object_audit::created(draw_commands_reuse_fence, "draw commands");
+    crate::object_audit::created(setup_commands_reuse_fence, "setup commands");
+
     Ok((draw_commands_reuse_fence, setup_commands_reuse_fence))
 }
 
@@ -39,5 +42,8 @@ pub fn create_semaphores(
             .unwrap()
     };
 
+    crate::object_audit::created(present_complete_semaphore, "present complete");
+    crate::object_audit::created(rendering_complete_semaphore, "rendering complete");
+
     Ok((present_complete_semaphore, rendering_complete_semaphore))
 }
@@ -1,31 +1,38 @@
-use super::{device::AAADevice, surface::AAASurface};
+use super::{
+    device::AAADevice,
+    offscreen::{framebuffer_attachments, AAAMsaaColor},
+};
 use ash::vk;
 use std::error::Error;
 
 pub fn create_framebuffers(
     device: &AAADevice,
-    surface: &AAASurface,
+    extent: vk::Extent2D,
     present_image_views: &[vk::ImageView],
     depth_image_view: vk::ImageView,
+    msaa_color: Option<&AAAMsaaColor>,
     renderpass: vk::RenderPass,
 ) -> Result<Vec<vk::Framebuffer>, Box<dyn Error>> {
     let framebuffers: Vec<vk::Framebuffer> = present_image_views
         .iter()
         .map(|&present_image_view| {
-            let framebuffer_attachments = [present_image_view, depth_image_view];
+            let framebuffer_attachments =
+                framebuffer_attachments(present_image_view, depth_image_view, msaa_color);
             let frame_buffer_create_info = vk::FramebufferCreateInfo::default()
                 .render_pass(renderpass)
                 .attachments(&framebuffer_attachments)
-                .width(surface.capabilities.current_extent.width)
-                .height(surface.capabilities.current_extent.height)
+                .width(extent.width)
+                .height(extent.height)
                 .layers(1);
 
-            unsafe {
+            let framebuffer = unsafe {
                 device
                     .ash
                     .create_framebuffer(&frame_buffer_create_info, None)
                     .unwrap()
-            }
+            };
+            crate::object_audit::created(framebuffer, "swapchain image");
+            framebuffer
         })
         .collect();
 
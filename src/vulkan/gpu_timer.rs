@@ -0,0 +1,112 @@
+use super::device::AAADevice;
+use crate::metrics::{QueueKind, QueueSpan};
+use ash::vk;
+use std::cell::Cell;
+
+/// Timestamps around the draw command buffer, telling how long the GPU spent on a frame.
+#[derive(Debug)]
+pub struct AAAGpuTimer {
+    pool: vk::QueryPool,
+    /// Queue the command buffer is submitted to, tagging the spans read back.
+    queue: QueueKind,
+    /// Nanoseconds per timestamp tick.
+    period: f64,
+    valid_mask: u64,
+    /// Both timestamps were recorded since the pool was created.
+    written: Cell<bool>,
+}
+
+impl AAAGpuTimer {
+    /// `None` when the queue family does not support timestamps.
+    pub fn new(
+        instance: &ash::Instance,
+        device: &AAADevice,
+        physical_device: vk::PhysicalDevice,
+        queue_family_index: u32,
+        queue: QueueKind,
+    ) -> Option<Self> {
+        let valid_bits =
+            unsafe { instance.get_physical_device_queue_family_properties(physical_device) }
+                .get(queue_family_index as usize)?
+                .timestamp_valid_bits;
+        if valid_bits == 0 {
+            return None;
+        }
+        let period = unsafe { instance.get_physical_device_properties(physical_device) }
+            .limits
+            .timestamp_period as f64;
+        let pool_info = vk::QueryPoolCreateInfo::default()
+            .query_type(vk::QueryType::TIMESTAMP)
+            .query_count(2);
+        let pool = unsafe { device.ash.create_query_pool(&pool_info, None).ok()? };
+        crate::object_audit::created(pool, "GPU timer");
+        Some(Self {
+            pool,
+            queue,
+            period,
+            valid_mask: u64::MAX >> (64 - valid_bits.min(64)),
+            written: Cell::new(false),
+        })
+    }
+
+    /// First command of the frame.
+    pub fn record_start(&self, device: &AAADevice, command_buffer: vk::CommandBuffer) {
+        unsafe {
+            device
+                .ash
+                .cmd_reset_query_pool(command_buffer, self.pool, 0, 2);
+            device.ash.cmd_write_timestamp(
+                command_buffer,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                self.pool,
+                0,
+            );
+        }
+    }
+
+    /// Last command of the frame.
+    pub fn record_end(&self, device: &AAADevice, command_buffer: vk::CommandBuffer) {
+        unsafe {
+            device.ash.cmd_write_timestamp(
+                command_buffer,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                self.pool,
+                1,
+            );
+        }
+        self.written.set(true);
+    }
+
+    /// Span of the last frame recorded on the timer's queue, its command buffer's fence must be
+    /// signaled.
+    pub fn read(&self, device: &AAADevice) -> Option<QueueSpan> {
+        if !self.written.get() {
+            return None;
+        }
+        let mut timestamps = [0u64; 2];
+        unsafe {
+            device
+                .ash
+                .get_query_pool_results(
+                    self.pool,
+                    0,
+                    &mut timestamps,
+                    vk::QueryResultFlags::TYPE_64,
+                )
+                .ok()?;
+        }
+        let [start, end] = timestamps.map(|timestamp| timestamp & self.valid_mask);
+        let ticks = end.wrapping_sub(start) & self.valid_mask;
+        let start = (start as f64 * self.period) as u64;
+        Some(QueueSpan {
+            queue: self.queue,
+            start,
+            end: start + (ticks as f64 * self.period) as u64,
+        })
+    }
+
+    pub fn destroy(&self, device: &AAADevice) {
+        crate::object_audit::destroyed(self.pool);
+        unsafe { device.ash.destroy_query_pool(self.pool, None) };
+    }
+}
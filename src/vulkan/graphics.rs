@@ -1,8 +1,93 @@
-use ash::vk;
+use ash::vk::{self, Handle};
 
-use super::{device::AAADevice, surface::AAASurface, surface_resources::AAAResources, AAABase};
-use crate::{input_manager::EventStates, metrics::Metrics, model::mat4_to_bytes};
-use std::sync::{atomic::Ordering, Arc, Mutex};
+use super::{
+    background::AAABackground,
+    compute::{AAACompute, ComputeDispatch},
+    deferred_deletion::AAADeferredDeletion,
+    descriptor_set::{GLOBAL_SET, MATERIAL_SET, OBJECT_SET},
+    device::AAADevice,
+    dof::AAADepthOfField,
+    gpu_timer::AAAGpuTimer,
+    offscreen::AAAOffscreenTarget,
+    pipeline::{supported_clamp, PushConstants},
+    pipeline_registry::PipelineRegistry,
+    readback::AAAReadback,
+    renderpass,
+    surface::AAASurface,
+    surface_resources::{flat_irradiance, AAAResources},
+    swapchain::pci_ids,
+    texture::Texture,
+    texture_array::TextureArray,
+    viewport::ViewportState,
+    AAABase,
+};
+#[cfg(feature = "images")]
+use super::{surface_resources::irradiance_texture, texture::decode_cubemap};
+#[cfg(feature = "replay")]
+use crate::replay::{ReplayBuffer, ReplayFormat, ReplayFrame, REPLAY_DOWNSCALE};
+use crate::{
+    assets, batching,
+    block_compression::CompressedTexture,
+    camera::{CameraController, Ortho2DController},
+    color_space::{self, ExportColorSpace},
+    compute::{group_counts, DeformBuffer, DEFORM_GROUP_SIZE},
+    config::GraphicsConfig,
+    culling::Frustum,
+    custom_pass::{ClearTarget, CustomPassSlot, CustomPasses, FrameContext},
+    debug_lines::DebugLineBuffer,
+    display::DisplayEnvironment,
+    dof::{DofConstants, FocusMode, FocusTracker},
+    draw_order::{self, DrawKey},
+    failure::{self, Failure, FailureCategory, FAILURE_BACKGROUND},
+    flight_recorder::{Decision, RecreateReason},
+    gizmo::{Gizmo, GizmoMode, GIZMO_SCREEN_SIZE},
+    handles::MeshHandle,
+    input_manager::EventStates,
+    inset::InsetView,
+    material::{DepthBias, Material, MaterialKey},
+    metrics::{self, Metrics, QueueKind},
+    model::{Mesh, MeshSpace, MeshUpdate, RegisteredMesh, Vertex},
+    picking::{pick_mesh_ranges, MeshHit, MeshPart, Ray},
+    present_health::{
+        self, PresentDowngrade, PresentHealthMonitor, PresentModePreference, PresentSample,
+    },
+    present_wait::{self, PresentPacer, PRESENT_WAIT_STRIKES, PRESENT_WAIT_TIMEOUT},
+    renderer::{FrameOutcome, StartupContents},
+    residency::{ResidencyKey, ResidencyManager, ResidencyPriority},
+    screenshot::{
+        screenshot_passes, ScreenshotRequest, SCREENSHOT_ATTACHMENTS, SCREENSHOT_MAX_EXTENT,
+    },
+    skinning::SkinBuffer,
+    texture::SamplerDesc,
+    thumbnail::{Thumbnail, ThumbnailTarget},
+    time_control::{SimulationClock, FIXED_TICK},
+    update_queue::UpdateCounters,
+    watchdog::{RenderStage, WaitSite},
+};
+#[cfg(feature = "images")]
+use crate::{
+    environment::{Cubemap, CONVOLUTION_SIZE, IRRADIANCE_SIZE},
+    hot_reload::{FileWatcher, HOT_RELOAD_POLL_INTERVAL},
+};
+use glam::{Mat4, Vec2, Vec3};
+use log::{error, info, warn};
+#[cfg(feature = "images")]
+use std::path::PathBuf;
+use std::{
+    cell::{Cell, RefCell},
+    sync::{atomic::Ordering, Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+/// Latest vertices and indices posted for a mesh this frame.
+type PendingMeshUpdate = (MeshHandle, Option<Vec<Vertex>>, Option<Vec<u32>>);
+
+/// What the default material's texture was loaded from, loaded again from it after an eviction.
+enum TextureSource {
+    #[cfg(feature = "images")]
+    File(PathBuf, SamplerDesc),
+    Compressed(CompressedTexture, SamplerDesc),
+}
 
 pub struct AAAGraphics {
     pub device: Arc<AAADevice>,
@@ -10,220 +95,3070 @@ pub struct AAAGraphics {
     pub surface: Arc<Mutex<AAASurface>>,
     pub resources: AAAResources,
     pub event_states: Arc<EventStates>,
+    pub config: Arc<RwLock<GraphicsConfig>>,
+    #[cfg(feature = "replay")]
+    pub replay: ReplayBuffer,
+    pub pipelines: PipelineRegistry,
+    pub gizmo: Gizmo,
+    /// Mesh under the gizmo.
+    pub selected_mesh: Option<MeshHandle>,
+    mouse_was_pressed: bool,
+    /// Focus distance of the depth of field, following its target smoothly.
+    pub focus: FocusTracker,
+    /// Depth of field passes of the current swapchain, built the first frame they are enabled
+    /// and kept until the swapchain is recreated.
+    depth_of_field: Option<AAADepthOfField>,
+    /// The swapchain cannot be post processed, warned about once.
+    depth_of_field_unsupported: bool,
+    /// Display environment the current frame is laid out for.
+    pub display: DisplayEnvironment,
+    /// Why the swapchain is recreated before the next frame: acquire or present reported it out
+    /// of date or suboptimal, the present mode changed, or the window has no area yet.
+    swapchain_outdated: Option<RecreateReason>,
+    /// Kept across cycles so the counters stay monotonic.
+    pub metrics: Metrics,
+    pub residency: ResidencyManager<ResidencyKey>,
+    /// What the default material's texture can be loaded again from, `None` for the built-in
+    /// texture and once streamed into. Only then is it tracked by residency.
+    texture_source: Option<TextureSource>,
+    /// Priority of the default material's textures, the next one loaded included.
+    texture_residency: ResidencyPriority,
+    /// White texel sampled in place of the default material's texture while it is evicted,
+    /// created on the first eviction. `None` while sampled.
+    texture_placeholder: Option<Texture>,
+    /// Frame rate cap of the last frame, `None` before the first one.
+    fps_cap: Option<u32>,
+    /// Pans and zooms the 2D world camera.
+    pub ortho_2d: Ortho2DController,
+    /// Cursor position at the last frame of a middle button drag.
+    pan_anchor: Option<Vec2>,
+    /// Dollies the perspective camera toward the cursor with the scroll wheel.
+    pub camera_controller: CameraController,
+    /// Fixed ticks run by the animations and the background, scaled and paused apart from the
+    /// camera.
+    clock: SimulationClock,
+    /// Drawn with the material of a thumbnail.
+    thumbnail_sphere: RegisteredMesh,
+    /// Segments of the last `DebugLines` posted, drawn after the scene meshes.
+    debug_lines: DebugLineBuffer,
+    /// Drawn behind the scene meshes, nothing over the clear color by default.
+    background: AAABackground,
+    /// Dispatches the shaders of the deformed meshes before the frame draws them.
+    compute: AAACompute,
+    /// Application passes, borrowed mutably while recording through `&self`.
+    custom_passes: RefCell<CustomPasses>,
+    /// Descriptor set binds recorded this frame, added to the counters once it ends.
+    descriptor_binds: Cell<u64>,
+    /// Command buffers submitted to the graphics queue this frame, the same.
+    submissions: Cell<u64>,
+    /// Picture in picture views drawn after the main view, as last posted.
+    insets: Vec<InsetView>,
+    /// Times the draw command buffer, `None` when the queue has no timestamps.
+    gpu_timer: Option<AAAGpuTimer>,
+    /// Judges MAILBOX presentation, reset with every display environment.
+    present_health: PresentHealthMonitor,
+    /// PCI vendor and device ids, the key of the session's present mode downgrades.
+    pci_ids: (u32, u32),
+    /// Resolved into the present mode whenever the swapchain is recreated.
+    present_preference: PresentModePreference,
+    /// Tags presents and waits on them before sampling input, with present wait pacing on.
+    present_pacer: PresentPacer,
+    /// Quads of the failure drawn in place of the scene, and the extent they were laid out for.
+    failure_mesh: Option<(Failure, vk::Extent2D, RegisteredMesh)>,
+    /// A device failure was retried, counted as recovered once a frame presents.
+    recovering: bool,
+    /// Watches the texture file loaded last, `None` until one loads with hot reload on.
+    #[cfg(feature = "images")]
+    texture_watcher: Option<FileWatcher<image::RgbaImage>>,
+    /// Path and sampler of the watched texture file.
+    #[cfg(feature = "images")]
+    watched_texture: Option<(PathBuf, SamplerDesc)>,
+    /// Texture file decoded again, swapped in once no frame in flight samples the previous one.
+    #[cfg(feature = "images")]
+    pending_reload: Option<Texture>,
+    /// Environment cubemap, if any, and irradiance swapped in once no frame in flight samples
+    /// the previous ones.
+    pending_environment: Option<(Option<Texture>, Texture)>,
+    /// Intensity written once no frame in flight reads the previous one.
+    pending_intensity: Option<f32>,
+    /// Replaced resources, destroyed once the frames reading them completed.
+    retired: AAADeferredDeletion,
+}
+
+/// Vertical field of view and camera distance framing the unit sphere of material thumbnails.
+const THUMBNAIL_FOV_Y: f32 = std::f32::consts::FRAC_PI_4;
+const THUMBNAIL_DISTANCE: f32 = 3.0;
+
+/// Everything a render pass needs to draw the scene into a framebuffer.
+struct SceneDraw<'a> {
+    framebuffer: vk::Framebuffer,
+    extent: vk::Extent2D,
+    viewports: [vk::Viewport; 1],
+    scissors: [vk::Rect2D; 1],
+    clear_values: [vk::ClearValue; 2],
+    perspective: Mat4,
+    orthographic: Mat4,
+    world_2d: Mat4,
+    /// One per projection mesh.
+    mesh_pipelines: &'a [vk::Pipeline],
+    /// One per projection mesh, set before its draw. `None` for the material pipelines, which
+    /// bake their bias.
+    depth_biases: &'a [Option<DepthBias>],
+    /// Indices of the projection meshes in the order they draw.
+    draw_order: &'a [usize],
+    gizmo_handles: &'a [(&'a RegisteredMesh, Mat4)],
+    reference_meshes: &'a [RegisteredMesh],
+    /// Drawn for a screenshot rather than the window.
+    offscreen: bool,
 }
 
-impl AAAGraphics {
-    pub fn new(
-        base: Arc<AAABase>,
-        surface: Arc<Mutex<AAASurface>>,
-        event_states: Arc<EventStates>,
-        width: u32,
-        height: u32,
-    ) -> Self {
-        let resources = AAAResources::new(base.clone(), surface.clone(), width, height);
-        Self {
-            device: resources.device.clone(),
-            base,
-            surface,
-            resources,
-            event_states,
+/// How long the render thread waits before checking again while the window has no area.
+const ZERO_SIZED_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+impl AAAGraphics {
+    pub fn new(
+        base: Arc<AAABase>,
+        surface: Arc<Mutex<AAASurface>>,
+        event_states: Arc<EventStates>,
+        config: Arc<RwLock<GraphicsConfig>>,
+        display: DisplayEnvironment,
+    ) -> Self {
+        let (convention, demo_scene, render) = {
+            let config = config.read().unwrap();
+            (config.world_convention, config.demo_scene, config.render)
+        };
+        let mut resources = AAAResources::new(
+            base.clone(),
+            surface.clone(),
+            display.width,
+            display.height,
+            convention,
+            demo_scene,
+            render,
+            event_states.mesh_handles.clone(),
+        );
+        let contents = resources.contents();
+        debug_assert_eq!(contents, StartupContents::of(&config.read().unwrap()));
+        log::debug!(
+            "Window renderer started with {} textures and {} meshes",
+            contents.textures,
+            contents.meshes
+        );
+        #[cfg(feature = "replay")]
+        let replay = {
+            let config = config.read().unwrap();
+            ReplayBuffer::new(config.replay_budget_mb, config.replay_capture_fps)
+        };
+        let focus = FocusTracker::new(config.read().unwrap().depth_of_field.focus_distance);
+        let pipelines = PipelineRegistry::new(
+            resources.device.clone(),
+            resources.renderpass,
+            resources.pipeline_layout,
+            resources.samples,
+            resources.graphic_pipeline,
+            std::mem::take(&mut resources.mesh_pipelines),
+        );
+        // What the driver reported in use before the meshes were uploaded is left out of the
+        // budget, as are the meshes residency does not track. The gizmo handles and the
+        // reference grid, built on first use, are not counted
+        let untracked: u64 = resources
+            .orthographic_registered_meshes
+            .iter()
+            .map(|registered_mesh| registered_mesh.mesh.size_bytes())
+            .sum();
+        let budget = resources.memory_budget;
+        let mut residency = ResidencyManager::new(
+            budget
+                .budget
+                .saturating_sub(budget.usage)
+                .saturating_sub(untracked),
+        );
+        event_states
+            .decisions
+            .set_device_info(device_info(&base, surface.lock().unwrap().physical_device));
+        for (handle, registered_mesh) in resources.projection_registered_meshes.iter() {
+            residency.track(
+                ResidencyKey::Mesh(handle),
+                registered_mesh.mesh.size_bytes(),
+                registered_mesh.residency,
+            );
+        }
+        let (gpu_timer, pci_ids) = {
+            let surface = surface.lock().unwrap();
+            (
+                AAAGpuTimer::new(
+                    &base.instance,
+                    &resources.device,
+                    surface.physical_device,
+                    surface.queue_family_index,
+                    QueueKind::Graphics,
+                ),
+                pci_ids(&base.instance, surface.physical_device),
+            )
+        };
+        let present_health = PresentHealthMonitor::new(display.refresh_interval());
+        let thumbnail_sphere = Mesh::uv_sphere(1.0, 32, 16, [1.0, 1.0, 1.0, 1.0]).register(
+            &resources.device,
+            &resources.device_memory_properties,
+            Some(resources.upload_context()),
+        );
+        let background = AAABackground::new(&resources.device, resources.samples);
+        let compute = AAACompute::new(
+            &resources.device,
+            resources.pool,
+            resources.swapchain.present_queue,
+        );
+        let device_present_wait = resources.device.present_wait;
+        *event_states.raw_device.lock().unwrap() = Some(resources.device.ash.clone());
+        let mut graphics = Self {
+            device: resources.device.clone(),
+            base,
+            surface,
+            resources,
+            event_states,
+            config,
+            #[cfg(feature = "replay")]
+            replay,
+            pipelines,
+            gizmo: Gizmo::default(),
+            selected_mesh: None,
+            mouse_was_pressed: false,
+            focus,
+            depth_of_field: None,
+            depth_of_field_unsupported: false,
+            display,
+            swapchain_outdated: None,
+            metrics: Metrics::default(),
+            residency,
+            texture_source: None,
+            texture_residency: ResidencyPriority::default(),
+            texture_placeholder: None,
+            fps_cap: None,
+            ortho_2d: Ortho2DController::default(),
+            pan_anchor: None,
+            camera_controller: CameraController::default(),
+            clock: SimulationClock::default(),
+            thumbnail_sphere,
+            debug_lines: DebugLineBuffer::new(),
+            background,
+            compute,
+            custom_passes: RefCell::default(),
+            descriptor_binds: Cell::new(0),
+            submissions: Cell::new(0),
+            insets: Vec::new(),
+            gpu_timer,
+            present_health,
+            pci_ids,
+            present_preference: render.present_mode,
+            present_pacer: PresentPacer::new(device_present_wait),
+            failure_mesh: None,
+            recovering: false,
+            #[cfg(feature = "images")]
+            texture_watcher: None,
+            #[cfg(feature = "images")]
+            watched_texture: None,
+            #[cfg(feature = "images")]
+            pending_reload: None,
+            pending_environment: None,
+            pending_intensity: None,
+            retired: AAADeferredDeletion::default(),
+        };
+        graphics.update_cameras();
+        graphics.event_states.publish_present_mode(
+            render.present_mode,
+            graphics.resources.swapchain.present_mode,
+        );
+        graphics
+    }
+
+    pub fn cycle(&mut self) {
+        loop {
+            match self.render_frame() {
+                FrameOutcome::Rendered | FrameOutcome::Dropped => {}
+                // A surface staying suboptimal, rotated for one, would otherwise recreate every frame
+                FrameOutcome::NeedsRecreate(RecreateReason::Suboptimal)
+                    if !self.surface_changed() => {}
+                FrameOutcome::NeedsRecreate(reason) => {
+                    self.swapchain_outdated.get_or_insert(reason);
+                }
+                FrameOutcome::Exit => break,
+            }
+        }
+        self.event_states.heartbeat.park();
+    }
+
+    /// Whether the surface's extent or transform moved since the swapchain was created, or can no
+    /// longer be queried.
+    fn surface_changed(&self) -> bool {
+        let surface = self.surface.lock().unwrap();
+        let capabilities = unsafe {
+            self.base
+                .surface_loader
+                .get_physical_device_surface_capabilities(
+                    surface.physical_device,
+                    surface.surface_khr,
+                )
+        };
+        let window = vk::Extent2D {
+            width: self.display.width,
+            height: self.display.height,
+        };
+        capabilities.map_or(true, |capabilities| {
+            !self.resources.swapchain.fits(&capabilities, window)
+        })
+    }
+
+    /// Render and present one frame, unless the swapchain waits to be recreated or the window
+    /// is closing.
+    fn render_frame(&mut self) -> FrameOutcome {
+        if self.event_states.exiting.load(Ordering::Relaxed) {
+            return FrameOutcome::Exit;
+        }
+        let surface_locked = self.surface.clone();
+        let event_states = self.event_states.clone();
+        let heartbeat = &event_states.heartbeat;
+
+        self.wait_for_previous_present();
+        self.metrics.start_frame();
+        heartbeat.beat(self.metrics.counters.frames);
+        let config = *self.config.read().unwrap();
+
+        // MARK: display environment
+        heartbeat.enter(RenderStage::Display);
+        if let Some(preference) = self.event_states.take_present_preference() {
+            if preference != self.present_preference {
+                self.present_preference = preference;
+                self.swapchain_outdated
+                    .get_or_insert(RecreateReason::PresentModeChanged);
+            }
+        }
+        let pending_display = self.event_states.take_display_environment();
+        if pending_display.is_some() || self.swapchain_outdated.is_some() {
+            self.apply_display_environment(pending_display.unwrap_or(self.display));
+        }
+        if let Some(reason) = self.swapchain_outdated {
+            std::thread::sleep(ZERO_SIZED_POLL_INTERVAL);
+            return FrameOutcome::NeedsRecreate(reason);
+        }
+        let surface = {
+            let _waiting = heartbeat.waiting(WaitSite::SurfaceLock);
+            surface_locked.lock().unwrap()
+        };
+
+        // MARK: rotate in real time
+        // let delta = metrics.delta_start_to_start;
+        // resources.uniform *= Mat4::from_euler(glam::EulerRot::XYZ, 0.0, 0.0, delta.as_secs_f32());
+        // Self::update_uniform_buffer(
+        //     &resources.device,
+        //     resources.uniform_color_buffer_memory,
+        //     resources.uniform,
+        // );
+
+        // MARK: simulation
+        if let Some(control) = self.event_states.take_time_control() {
+            self.clock.set_control(control);
+        }
+        for _ in 0..self.event_states.take_time_steps() {
+            self.clock.step_once();
+        }
+        let ticks = self.clock.advance(
+            self.metrics
+                .simulation_delta(config.clamp_delta_after_stall),
+        );
+        self.background.advance(FIXED_TICK * ticks);
+
+        // MARK: replay
+        heartbeat.enter(RenderStage::Pipelines);
+        #[cfg(feature = "replay")]
+        {
+            self.collect_replay_frame();
+            if self.event_states.take_replay_save_request() {
+                self.replay.save(ReplayFormat::Gif);
+            }
+            self.metrics.replay_bytes = self.replay.used_bytes();
+        }
+        // MARK: pipelines
+        self.update_custom_passes();
+        self.update_background();
+        let texture_replaced = self.update_texture_file();
+        let texture_decompressed = self.update_compressed_texture();
+        let texture_reloaded = self.reload_texture_file();
+        let texture_updates = self.event_states.take_texture_updates();
+        self.update_texture_array();
+        self.update_environment();
+        for material in self.event_states.take_precompile_requests() {
+            self.pipelines.materials.precompile(&material);
+        }
+        self.pipelines.materials.poll();
+        self.metrics.pipelines_outstanding = self.pipelines.materials.outstanding();
+        self.metrics.pipelines_completed = self.pipelines.materials.completed();
+        // MARK: residency
+        heartbeat.enter(RenderStage::Residency);
+        // Taken before the additions, so whatever was posted for a mesh is applied after it is
+        // registered
+        let removals = self.event_states.take_mesh_removals();
+        let (updates, counters) = self.event_states.take_mesh_updates();
+        self.add_meshes();
+        for mesh in removals {
+            self.remove_mesh(mesh);
+        }
+        self.update_meshes(updates, counters);
+        self.update_instances();
+        self.update_skins(ticks);
+        // The cameras residency culls with
+        self.update_world_2d(self.resources.swapchain.extent);
+        self.update_dolly(self.scene_extent());
+        // Keyed every frame, a mesh's variant follows its instances, skin and blend
+        let texture_array = self.resources.texture_array.is_some();
+        for (_, registered_mesh) in self.resources.projection_registered_meshes.iter_mut() {
+            registered_mesh.resolve_material(texture_array);
+        }
+        let in_view = self.update_residency(!texture_updates.is_empty());
+        self.deform_meshes(ticks);
+        // Built now the first time a default variant is asked for, queued for a material
+        let mesh_pipelines: Vec<vk::Pipeline> = self
+            .resources
+            .projection_registered_meshes
+            .meshes()
+            .map(|registered_mesh| self.pipelines.pipeline(&registered_mesh.material))
+            .collect();
+        // The variants take theirs as dynamic state, unbiased draws set it back to 0
+        let depth_biases: Vec<Option<DepthBias>> = self
+            .resources
+            .projection_registered_meshes
+            .meshes()
+            .map(|registered_mesh| {
+                registered_mesh
+                    .material
+                    .has_dynamic_depth_bias()
+                    .then(|| registered_mesh.depth_bias.unwrap_or_default())
+            })
+            .collect();
+        let fallback_draws = self
+            .resources
+            .projection_registered_meshes
+            .meshes()
+            .zip(&mesh_pipelines)
+            .filter(|(registered_mesh, &pipeline)| {
+                matches!(registered_mesh.material, MaterialKey::Material(_))
+                    && pipeline == self.pipelines.fallback()
+            })
+            .count();
+        let camera_view = self.resources.camera.view();
+        let draw_keys: Vec<DrawKey> = self
+            .resources
+            .projection_registered_meshes
+            .meshes()
+            .zip(&mesh_pipelines)
+            .map(|(registered_mesh, &pipeline)| DrawKey {
+                sort_bias: registered_mesh.sort_bias,
+                state: pipeline.as_raw(),
+                registered: registered_mesh.registered,
+                // The 2D world is flat, its blended meshes keep their registration order
+                blend_depth: registered_mesh.material.options().is_blended().then(|| {
+                    match registered_mesh.space {
+                        MeshSpace::World => registered_mesh.view_depth(camera_view),
+                        MeshSpace::World2D => 0.0,
+                    }
+                }),
+            })
+            .collect();
+        let draw_order = draw_order::draw_order(&draw_keys, config.sort_by_material);
+        if fallback_draws > 0 {
+            self.decide(Decision::PipelineFallback {
+                draws: fallback_draws as u32,
+            });
+        }
+        let skipped_draws = in_view
+            .iter()
+            .filter_map(|&mesh| self.resources.projection_registered_meshes.get(mesh))
+            .filter(|registered_mesh| !registered_mesh.is_resident())
+            .count();
+        if skipped_draws > 0 {
+            self.decide(Decision::DrawsSkipped {
+                draws: skipped_draws as u32,
+            });
+        }
+
+        // MARK: depth of field
+        heartbeat.enter(RenderStage::Gizmo);
+        let depth_of_field = if config.depth_of_field.enabled {
+            self.update_focus(
+                self.scene_extent(),
+                self.metrics
+                    .simulation_delta(config.clamp_delta_after_stall)
+                    .as_secs_f32(),
+            );
+            self.event_states
+                .publish_focus_distance(Some(self.focus.distance));
+            let perspective = self.resources.camera.perspective();
+            let (near, far) = (perspective.near, perspective.far);
+            self.prepare_depth_of_field(&surface).then(|| {
+                DofConstants::new(
+                    &config.depth_of_field,
+                    self.focus.distance,
+                    near,
+                    far,
+                    self.scene_extent().height as f32,
+                )
+            })
+        } else {
+            self.event_states.publish_focus_distance(None);
+            None
+        };
+
+        // MARK: gizmo
+        self.gizmo.snapping = config.gizmo_snapping;
+        self.update_gizmo(self.scene_extent());
+        // MARK: failure screen
+        let failing = self.update_failure_screen(self.resources.swapchain.extent);
+
+        // MARK: hierarchy
+        let cycles = self
+            .resources
+            .projection_registered_meshes
+            .resolve_world_transforms();
+        if !cycles.is_empty() {
+            warn!("Meshes {cycles:?} close parent cycles, drawn as roots");
+        }
+        let selected_transform = self.selected_world_transform();
+
+        // MARK: lazy resources
+        // Taken now, written once the previous frame is done with the buffer
+        let debug_lines = self.event_states.take_debug_lines();
+        let samples_default_texture = !texture_updates.is_empty()
+            || !self.resources.projection_registered_meshes.is_empty()
+            || !self.resources.orthographic_registered_meshes.is_empty()
+            || selected_transform.is_some()
+            || config.reference_grid
+            || failing
+            || debug_lines.is_some()
+            || self.debug_lines.vertex_count > 0;
+        if samples_default_texture {
+            self.resources.ensure_default_texture();
+        }
+        if selected_transform.is_some() {
+            self.resources.ensure_gizmo_meshes();
+        }
+        if config.reference_grid {
+            self.resources.ensure_reference_meshes();
+        }
+
+        let gizmo_handles: Vec<(&RegisteredMesh, Mat4)> = match selected_transform {
+            Some(target) => {
+                let handle_transform = Gizmo::handle_transform(
+                    &target,
+                    self.gizmo_scale(&target, self.resources.swapchain.extent),
+                );
+                self.resources
+                    .gizmo_registered_meshes
+                    .iter()
+                    .filter(|(mode, _, _)| *mode == self.gizmo.mode)
+                    .map(|(_, _, mesh)| (mesh, handle_transform))
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+
+        let reference_meshes: &[RegisteredMesh] = if config.reference_grid {
+            &self.resources.reference_registered_meshes
+        } else {
+            &[]
+        };
+
+        #[cfg(feature = "replay")]
+        let (frame_time, capture_replay) = {
+            let frame_time = std::time::Instant::now();
+            let capture =
+                self.resources.readback.is_some() && self.replay.wants_capture(frame_time);
+            (frame_time, capture)
+        };
+        #[cfg(not(feature = "replay"))]
+        let capture_replay = false;
+
+        heartbeat.enter(RenderStage::Acquire);
+        let result = unsafe {
+            let _waiting = heartbeat.waiting(WaitSite::AcquireImage);
+            self.resources.swapchain_loader.ash.acquire_next_image(
+                self.resources.swapchain.swapchain_khr,
+                u64::MAX,
+                self.resources.present_complete_semaphore,
+                vk::Fence::null(),
+            )
+        };
+        let (present_index, acquire_suboptimal) = match result {
+            Ok((image, suboptimal)) => {
+                self.decide(Decision::Acquired { image, suboptimal });
+                (image, suboptimal)
+            }
+            Err(err) => {
+                self.decide(Decision::AcquireFailed(err));
+                self.metrics.counters.dropped_frames += 1;
+                let outcome = FrameOutcome::failed(err);
+                if outcome == FrameOutcome::Dropped {
+                    self.fail_device("Failed to acquire the next image", err);
+                    std::thread::sleep(ZERO_SIZED_POLL_INTERVAL);
+                }
+                return outcome;
+            }
+        };
+        let clear_values = [
+            vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: config.clear_color,
+                },
+            },
+            vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: 1.0,
+                    stencil: 0,
+                },
+            },
+        ];
+
+        let scene = SceneDraw {
+            framebuffer: self.resources.framebuffers[present_index as usize],
+            extent: self.resources.swapchain.extent,
+            viewports: self.resources.viewport.viewports,
+            scissors: self.resources.viewport.scissors,
+            clear_values,
+            perspective: self.resources.camera.perspective().projection_view,
+            orthographic: self.resources.camera.orthographic().projection_view,
+            world_2d: self.resources.camera.world_2d().projection_view,
+            mesh_pipelines: &mesh_pipelines,
+            depth_biases: &depth_biases,
+            draw_order: &draw_order,
+            gizmo_handles: &gizmo_handles,
+            reference_meshes,
+            offscreen: false,
+        };
+
+        heartbeat.enter(RenderStage::Record);
+        // Waited here rather than in the submit helper so the watchdog can tell
+        unsafe {
+            let _waiting = heartbeat.waiting(WaitSite::DrawFence);
+            self.device
+                .ash
+                .wait_for_fences(&[self.resources.draw_commands_reuse_fence], true, u64::MAX)
+                .expect("Wait for fence failed.");
+        }
+        // Every frame before this one has completed
+        if let Some(completed) = self.metrics.counters.frames.checked_sub(1) {
+            self.retired.collect(&self.device, completed);
+        }
+        // Of the previous frame, the closest to this acquire that has completed
+        let gpu_span = self
+            .gpu_timer
+            .as_ref()
+            .and_then(|gpu_timer| gpu_timer.read(&self.device));
+        let gpu_time = gpu_span.map(|span| span.duration());
+        self.metrics.record_queue_spans(gpu_span.as_slice());
+        if let Some(insets) = self.event_states.take_insets() {
+            self.insets = insets;
+        }
+        if let Some(lines) = debug_lines {
+            self.debug_lines.write(
+                &self.device,
+                &self.resources.device_memory_properties,
+                &lines,
+            );
+        }
+        let texture_updated = texture_replaced
+            || texture_decompressed
+            || texture_reloaded
+            || !texture_updates.is_empty();
+        self.submissions.set(self.submissions.get() + 1);
+        crate::vulkan::record::record_submit_commandbuffer(
+            &self.device,
+            self.resources.draw_command_buffer,
+            self.resources.draw_commands_reuse_fence,
+            self.resources.swapchain.present_queue,
+            &[vk::PipelineStageFlags::BOTTOM_OF_PIPE],
+            &[self.resources.present_complete_semaphore],
+            &[self.resources.rendering_complete_semaphore],
+            |device, draw_command_buffer| {
+                if let Some(gpu_timer) = &self.gpu_timer {
+                    gpu_timer.record_start(device, draw_command_buffer);
+                }
+                if let Some(uploads) = &self.resources.texture_uploads {
+                    uploads.record_default(device, draw_command_buffer);
+                    uploads.record(device, draw_command_buffer, texture_updates);
+                }
+                match &self.failure_mesh {
+                    Some((_, _, mesh)) if failing => {
+                        self.record_failure_screen(device, draw_command_buffer, &scene, mesh)
+                    }
+                    _ => {
+                        self.record_scene(device, draw_command_buffer, &scene);
+                        if let (Some(pass), Some(constants)) =
+                            (&self.depth_of_field, depth_of_field)
+                        {
+                            pass.record(
+                                device,
+                                draw_command_buffer,
+                                self.resources.present_images[present_index as usize],
+                                present_index,
+                                constants,
+                            );
+                        }
+                    }
+                }
+
+                if let Some(readback) = self.resources.readback.as_ref() {
+                    if capture_replay {
+                        readback.record(
+                            device,
+                            draw_command_buffer,
+                            self.resources.present_images[present_index as usize],
+                        );
+                    }
+                }
+                if let Some(gpu_timer) = &self.gpu_timer {
+                    gpu_timer.record_end(device, draw_command_buffer);
+                }
+            },
+        );
+        #[cfg(feature = "replay")]
+        if capture_replay {
+            self.replay.mark_captured(frame_time);
+            if let Some(readback) = self.resources.readback.as_mut() {
+                readback.captured_at = Some(frame_time);
+            }
+        }
+        let wait_semaphors = [self.resources.rendering_complete_semaphore];
+        let swapchains = [self.resources.swapchain.swapchain_khr];
+        let image_indices = [present_index];
+        let present_id = self.present_pacer.next_id();
+        let present_ids = [present_id.unwrap_or_default()];
+        let mut present_id_info = vk::PresentIdKHR::default().present_ids(&present_ids);
+        let present_info = present_wait::tag_present(
+            vk::PresentInfoKHR::default()
+                .wait_semaphores(&wait_semaphors)
+                .swapchains(&swapchains)
+                .image_indices(&image_indices),
+            present_id.map(|_| &mut present_id_info),
+        );
+
+        heartbeat.enter(RenderStage::Present);
+        let queue_present_result = unsafe {
+            let _waiting = heartbeat.waiting(WaitSite::Present);
+            self.resources
+                .swapchain_loader
+                .ash
+                .queue_present(self.resources.swapchain.present_queue, &present_info)
+        };
+
+        let present_degraded = !matches!(queue_present_result, Ok(false));
+        // Suboptimal images still present, stretched until the swapchain matches the surface
+        let outcome = FrameOutcome::presented(acquire_suboptimal, queue_present_result);
+        match queue_present_result {
+            Ok(suboptimal) => {
+                self.decide(Decision::Presented { suboptimal });
+                if let Some(present_id) = present_id {
+                    self.present_pacer.presented(present_id);
+                }
+                if self.event_states.mark_first_frame_presented() {
+                    info!("First frame presented");
+                }
+                if std::mem::take(&mut self.recovering) {
+                    self.metrics.counters.device_lost_recoveries += 1;
+                }
+            }
+            Err(err) => {
+                self.decide(Decision::PresentFailed(err));
+                self.metrics.counters.dropped_frames += 1;
+                if outcome == FrameOutcome::Dropped {
+                    self.fail_device("Failed to present", err);
+                }
+            }
+        }
+
+        // MARK: screenshots
+        heartbeat.enter(RenderStage::Screenshots);
+        for request in self.event_states.take_screenshot_requests() {
+            self.capture_screenshot(request, surface.format, &scene);
+        }
+
+        self.metrics.counters.descriptor_binds += self.descriptor_binds.take();
+        self.metrics
+            .count_submissions(QueueKind::Graphics, self.submissions.take());
+        self.metrics.counters.draw_calls += (self.resources.projection_registered_meshes.len()
+            + gizmo_handles.len()
+            + reference_meshes.len()
+            + self.resources.orthographic_registered_meshes.len()
+            + self.inset_draws()) as u64;
+        self.render_thumbnails(surface.format, texture_updated);
+        self.metrics.waits = heartbeat.take_waits();
+        self.metrics.end_frame();
+
+        // MARK: present health
+        if self.resources.swapchain.present_mode == vk::PresentModeKHR::MAILBOX {
+            let (vendor_id, device_id) = self.pci_ids;
+            if present_health::is_downgraded(vendor_id, device_id) {
+                // Another window gave up on MAILBOX for this device
+                self.swapchain_outdated
+                    .get_or_insert(RecreateReason::PresentModeDowngraded);
+            } else if let Some(downgrade) = self.present_health.record(PresentSample {
+                acquire_wait: self.metrics.waits.acquire,
+                gpu_time,
+                present_degraded,
+            }) {
+                self.downgrade_present_mode(downgrade);
+            }
+        }
+
+        // MARK: throttle
+        heartbeat.enter(RenderStage::Throttle);
+        if self.fps_cap != Some(config.fps_cap) {
+            self.fps_cap = Some(config.fps_cap);
+            self.decide(Decision::PacingChanged {
+                fps_cap: config.fps_cap,
+            });
+        }
+        if config.fps_cap > 0 {
+            let frame_budget = Duration::from_secs(1) / config.fps_cap;
+            let elapsed = self.metrics.frame_start.elapsed();
+            if elapsed < frame_budget {
+                std::thread::sleep(frame_budget - elapsed);
+            }
+        }
+        outcome
+    }
+
+    /// With present wait pacing, wait for the previous frame to reach the screen so this one
+    /// samples its input as late as the display allows.
+    fn wait_for_previous_present(&mut self) {
+        self.present_pacer
+            .set_enabled(self.config.read().unwrap().present_wait_pacing);
+        if let Some(present_id) = self.present_pacer.wait_target() {
+            let result = {
+                let _waiting = self.event_states.heartbeat.waiting(WaitSite::PresentWait);
+                self.device.wait_for_present(
+                    self.resources.swapchain.swapchain_khr,
+                    present_id,
+                    PRESENT_WAIT_TIMEOUT,
+                )
+            };
+            let (timing, fell_back) = self.present_pacer.waited(result.into(), Instant::now());
+            if let Some(timing) = timing {
+                self.metrics.record_present_timing(timing);
+            }
+            if fell_back {
+                self.decide(Decision::PresentWaitFallback {
+                    strikes: PRESENT_WAIT_STRIKES,
+                });
+            }
+        }
+        self.present_pacer.input_sampled(Instant::now());
+    }
+
+    /// Render `live` once into a transient target at the requested extent and save it.
+    /// The perspective aspect and UI layout are recomputed for this render only, the swapchain is untouched.
+    fn capture_screenshot(
+        &self,
+        request: ScreenshotRequest,
+        format: vk::SurfaceFormatKHR,
+        live: &SceneDraw,
+    ) {
+        if !request.is_valid() {
+            warn!(
+                "Ignoring {}x{} screenshot, the extent must be within 1..={SCREENSHOT_MAX_EXTENT}",
+                request.width, request.height
+            );
+            return;
+        }
+        let extent = vk::Extent2D {
+            width: request.width,
+            height: request.height,
+        };
+        // The images after the scene reuse the memory of its depth and multisampled color
+        let lifetimes =
+            screenshot_passes(color_space::needs_conversion(format, request.color_space))
+                .lifetimes(SCREENSHOT_ATTACHMENTS)
+                .expect("Screenshot passes initialize their attachments");
+        let mut target = AAAOffscreenTarget::with_lifetimes(
+            &self.device,
+            &self.resources.device_memory_properties,
+            self.resources.renderpass,
+            format.format,
+            self.resources.samples,
+            extent,
+            &lifetimes,
+        );
+        let transient = target
+            .transient
+            .as_mut()
+            .expect("Screenshot targets alias their depth");
+        let readback = AAAReadback::aliased(
+            &self.device,
+            &self.resources.device_memory_properties,
+            extent,
+            format,
+            &self.resources.export,
+            request.color_space,
+            transient,
+            &lifetimes,
+        );
+        metrics::count_aliasing(transient.attachments() as u64, transient.bytes_saved());
+
+        let camera = &self.resources.camera;
+        let perspective = Mat4::perspective_rh(
+            camera.perspective().fov_y,
+            extent.width as f32 / extent.height as f32,
+            camera.perspective().near,
+            camera.perspective().far,
+        ) * camera.view();
+        // The UI keeps its size relative to the image, as if the window had been resized
+        let ui_scale = self.display.scale_factor as f32 * extent.height as f32
+            / self.display.height.max(1) as f32;
+        let ui = camera.orthographic();
+        let orthographic = Mat4::orthographic_rh(
+            ui.left,
+            extent.width as f32 / ui_scale,
+            ui.bottom,
+            extent.height as f32 / ui_scale,
+            ui.near,
+            ui.far,
+        ) * ui.view;
+
+        let scene = SceneDraw {
+            framebuffer: target.framebuffer,
+            extent: target.extent,
+            viewports: [vk::Viewport {
+                x: 0.0,
+                y: 0.0,
+                width: extent.width as f32,
+                height: extent.height as f32,
+                min_depth: 0.0,
+                max_depth: 1.0,
+            }],
+            scissors: [extent.into()],
+            clear_values: live.clear_values,
+            perspective,
+            orthographic,
+            // Same world rectangle, the capture keeps the window's aspect ratio
+            world_2d: live.world_2d,
+            mesh_pipelines: live.mesh_pipelines,
+            depth_biases: live.depth_biases,
+            draw_order: live.draw_order,
+            // Editor overlay, not part of the picture
+            gizmo_handles: &[],
+            reference_meshes: live.reference_meshes,
+            offscreen: true,
+        };
+
+        self.submissions.set(self.submissions.get() + 1);
+        crate::vulkan::record::record_submit_commandbuffer(
+            &self.device,
+            self.resources.setup_command_buffer,
+            self.resources.setup_commands_reuse_fence,
+            self.resources.swapchain.present_queue,
+            &[],
+            &[],
+            &[],
+            |device, command_buffer| {
+                target.record_depth_transition(device, command_buffer);
+                self.resources
+                    .record_default_texture(device, command_buffer);
+                self.record_scene(device, command_buffer, &scene);
+                readback.record(device, command_buffer, target.color_image);
+            },
+        );
+        unsafe {
+            let _waiting = self.event_states.heartbeat.waiting(WaitSite::SetupFence);
+            self.device
+                .ash
+                .wait_for_fences(&[self.resources.setup_commands_reuse_fence], true, u64::MAX)
+                .expect("Wait for fence failed.");
+        }
+        let rgba = readback.read(&self.device);
+        readback.destroy(&self.device);
+        target.destroy(&self.device);
+        request.save(rgba);
+    }
+
+    /// Render the next queued thumbnails, a few per frame, after freeing the ones the application
+    /// dropped and queuing again the ones whose source changed.
+    fn render_thumbnails(&mut self, format: vk::SurfaceFormatKHR, texture_updated: bool) {
+        let batch = {
+            let mut thumbnails = self.event_states.thumbnails.lock().unwrap();
+            thumbnails.collect_garbage();
+            thumbnails.invalidate(|thumbnail| match thumbnail.target() {
+                ThumbnailTarget::Texture => texture_updated,
+                ThumbnailTarget::Material(material) => {
+                    thumbnail.is_provisional() && self.pipelines.materials.is_ready(material)
+                }
+            });
+            // Blocks cannot be blitted, the thumbnails wait for an RGBA8 texture
+            let has_texture = self
+                .resources
+                .texture_uploads
+                .as_ref()
+                .is_some_and(|uploads| uploads.has_contents() && !uploads.compressed);
+            thumbnails.next_batch(|target| has_texture || *target != ThumbnailTarget::Texture)
+        };
+        for thumbnail in batch {
+            match thumbnail.target() {
+                ThumbnailTarget::Texture => self.render_texture_thumbnail(&thumbnail),
+                ThumbnailTarget::Material(material) => {
+                    self.render_material_thumbnail(&thumbnail, material, format)
+                }
+            }
+        }
+    }
+
+    /// Downsample the window texture into `thumbnail`, stretched to its square. Only queued once
+    /// the texture has contents.
+    fn render_texture_thumbnail(&self, thumbnail: &Thumbnail) {
+        let Some(uploads) = &self.resources.texture_uploads else {
+            return;
+        };
+        let readback = AAAReadback::with_extent(
+            &self.device,
+            &self.resources.device_memory_properties,
+            uploads.extent,
+            vk::SurfaceFormatKHR {
+                format: self.device.texture_format,
+                color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+            },
+            vk::Extent2D {
+                width: thumbnail.size(),
+                height: thumbnail.size(),
+            },
+            &self.resources.export,
+            ExportColorSpace::Srgb8,
+        );
+        self.submit_and_wait(|device, command_buffer| {
+            readback.record_from(
+                device,
+                command_buffer,
+                self.resources.textures[0].image,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            );
+        });
+        thumbnail.complete(readback.read(&self.device), false);
+        readback.destroy(&self.device);
+    }
+
+    /// Draw the thumbnail sphere lit with `material` on a transparent background. Materials still
+    /// compiling are drawn with the fallback pipeline, the thumbnail is redone once theirs is ready.
+    fn render_material_thumbnail(
+        &mut self,
+        thumbnail: &Thumbnail,
+        material: &Material,
+        format: vk::SurfaceFormatKHR,
+    ) {
+        // The sphere samples the default material's texture
+        self.resources.ensure_default_texture();
+        let pipeline = if *material == Material::default() {
+            self.resources.graphic_pipeline
+        } else {
+            self.pipelines.materials.pipeline_for(material)
+        };
+        let provisional = pipeline == self.pipelines.fallback() && *material != Material::default();
+        let extent = vk::Extent2D {
+            width: thumbnail.size(),
+            height: thumbnail.size(),
+        };
+        let target = AAAOffscreenTarget::new(
+            &self.device,
+            &self.resources.device_memory_properties,
+            self.resources.renderpass,
+            format.format,
+            self.resources.samples,
+            extent,
+        );
+        let readback = AAAReadback::new(
+            &self.device,
+            &self.resources.device_memory_properties,
+            extent,
+            format,
+            1,
+            &self.resources.export,
+            ExportColorSpace::Srgb8,
+        );
+        let projection_view = Mat4::perspective_rh(THUMBNAIL_FOV_Y, 1.0, 0.1, 10.0)
+            * Mat4::look_at_rh(Vec3::new(0.0, 0.0, THUMBNAIL_DISTANCE), Vec3::ZERO, Vec3::Y);
+        let clear_values = [
+            vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: [0.0, 0.0, 0.0, 0.0],
+                },
+            },
+            vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: 1.0,
+                    stencil: 0,
+                },
+            },
+        ];
+        let render_pass_begin_info = vk::RenderPassBeginInfo::default()
+            .render_pass(self.resources.renderpass)
+            .framebuffer(target.framebuffer)
+            .render_area(extent.into())
+            .clear_values(&clear_values);
+
+        let sets = self.resources.descriptor_sets;
+        self.submit_and_wait(|device, command_buffer| {
+            target.record_depth_transition(device, command_buffer);
+            self.resources
+                .record_default_texture(device, command_buffer);
+            unsafe {
+                device.ash.cmd_begin_render_pass(
+                    command_buffer,
+                    &render_pass_begin_info,
+                    vk::SubpassContents::INLINE,
+                );
+            }
+            self.bind_descriptor_sets(
+                device,
+                command_buffer,
+                GLOBAL_SET,
+                &[sets.global, sets.default_material],
+            );
+            unsafe {
+                device.ash.cmd_bind_pipeline(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    pipeline,
+                );
+                device.ash.cmd_set_viewport(
+                    command_buffer,
+                    0,
+                    &[vk::Viewport {
+                        x: 0.0,
+                        y: 0.0,
+                        width: extent.width as f32,
+                        height: extent.height as f32,
+                        min_depth: 0.0,
+                        max_depth: 1.0,
+                    }],
+                );
+                device
+                    .ash
+                    .cmd_set_scissor(command_buffer, 0, &[extent.into()]);
+            }
+            self.draw_mesh(
+                device,
+                command_buffer,
+                &self.thumbnail_sphere,
+                PushConstants::lit(projection_view, Mat4::IDENTITY),
+            );
+            unsafe { device.ash.cmd_end_render_pass(command_buffer) };
+            readback.record(device, command_buffer, target.color_image);
+        });
+        thumbnail.complete(readback.read(&self.device), provisional);
+        readback.destroy(&self.device);
+        target.destroy(&self.device);
+    }
+
+    /// Record on the setup command buffer, submit and wait for it to complete.
+    fn submit_and_wait(&self, record: impl FnOnce(&AAADevice, vk::CommandBuffer)) {
+        self.submissions.set(self.submissions.get() + 1);
+        crate::vulkan::record::record_submit_commandbuffer(
+            &self.device,
+            self.resources.setup_command_buffer,
+            self.resources.setup_commands_reuse_fence,
+            self.resources.swapchain.present_queue,
+            &[],
+            &[],
+            &[],
+            record,
+        );
+        unsafe {
+            let _waiting = self.event_states.heartbeat.waiting(WaitSite::SetupFence);
+            self.device
+                .ash
+                .wait_for_fences(&[self.resources.setup_commands_reuse_fence], true, u64::MAX)
+                .expect("Wait for fence failed.");
+        }
+    }
+
+    /// Record the render pass drawing `scene`, shared by the swapchain and offscreen captures.
+    fn record_scene(
+        &self,
+        device: &AAADevice,
+        command_buffer: vk::CommandBuffer,
+        scene: &SceneDraw,
+    ) {
+        self.record_custom_pass(CustomPassSlot::BeforeMain, device, command_buffer, scene);
+
+        let render_pass_begin_info = vk::RenderPassBeginInfo::default()
+            .render_pass(self.resources.renderpass)
+            .framebuffer(scene.framebuffer)
+            .render_area(scene.extent.into())
+            .clear_values(&scene.clear_values);
+
+        unsafe {
+            device.ash.cmd_begin_render_pass(
+                command_buffer,
+                &render_pass_begin_info,
+                vk::SubpassContents::INLINE,
+            );
+        }
+        self.background.record(
+            device,
+            command_buffer,
+            scene.extent,
+            self.event_states.cursor_position(),
+        );
+        self.bind_default_state(device, command_buffer, scene);
+
+        self.record_meshes(device, command_buffer, scene, scene.perspective, None);
+
+        for registered_mesh in scene.reference_meshes {
+            self.draw_mesh(
+                device,
+                command_buffer,
+                registered_mesh,
+                PushConstants::unlit(scene.perspective * registered_mesh.mesh.transform),
+            );
+        }
+
+        if self.debug_lines.vertex_count > 0 {
+            unsafe {
+                device.ash.cmd_bind_pipeline(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    self.resources.line_pipeline,
+                );
+            }
+            PushConstants::unlit(scene.perspective).push(
+                device,
+                command_buffer,
+                self.resources.pipeline_layout,
+            );
+            unsafe {
+                device.ash.cmd_bind_vertex_buffers(
+                    command_buffer,
+                    0,
+                    &[self.debug_lines.buffer],
+                    &[0],
+                );
+                device
+                    .ash
+                    .cmd_draw(command_buffer, self.debug_lines.vertex_count, 1, 0, 0);
+                device.ash.cmd_bind_pipeline(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    self.resources.graphic_pipeline,
+                );
+            }
+        }
+
+        if self.record_custom_pass(CustomPassSlot::AfterMain, device, command_buffer, scene) {
+            self.bind_default_state(device, command_buffer, scene);
+        }
+
+        // Gizmo handles stay visible through the scene
+        if !scene.gizmo_handles.is_empty() {
+            self.frame_context(scene).clear_region(
+                &device.ash,
+                command_buffer,
+                scene.extent.into(),
+                ClearTarget::Depth(1.0),
+            );
+        }
+        for (registered_mesh, transform) in scene.gizmo_handles {
+            self.draw_mesh(
+                device,
+                command_buffer,
+                registered_mesh,
+                PushConstants::unlit(scene.perspective * *transform),
+            );
+        }
+
+        let viewport = &scene.viewports[0];
+        let viewport_offset = Vec2::new(viewport.x, viewport.y);
+        let viewport_size = Vec2::new(viewport.width, viewport.height);
+        if !self.insets.is_empty() {
+            for inset in &self.insets {
+                self.record_inset(device, command_buffer, scene, inset);
+            }
+            unsafe {
+                device
+                    .ash
+                    .cmd_set_viewport(command_buffer, 0, &scene.viewports);
+                device
+                    .ash
+                    .cmd_set_scissor(command_buffer, 0, &scene.scissors);
+            }
+        }
+
+        unsafe {
+            device.ash.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.resources.ui_pipeline,
+            );
+        }
+        for registered_mesh in &self.resources.orthographic_registered_meshes {
+            let pvm = scene.orthographic * registered_mesh.mesh.transform;
+            let pvm = if registered_mesh.pixel_snap {
+                crate::pixel_snap::snap_to_pixels(pvm, viewport_offset, viewport_size)
+            } else {
+                pvm
+            };
+            self.draw_mesh(
+                device,
+                command_buffer,
+                registered_mesh,
+                PushConstants::unlit(pvm),
+            );
+        }
+        unsafe {
+            device.ash.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.resources.graphic_pipeline,
+            );
+        }
+
+        self.record_custom_pass(CustomPassSlot::AfterUi, device, command_buffer, scene);
+
+        // Or draw without the index buffer
+        // device.cmd_draw(command_buffer, 3, 1, 0, 0);
+        unsafe { device.ash.cmd_end_render_pass(command_buffer) };
+    }
+
+    /// The failure's quads over its background in place of the scene, the application passes
+    /// left out.
+    fn record_failure_screen(
+        &self,
+        device: &AAADevice,
+        command_buffer: vk::CommandBuffer,
+        scene: &SceneDraw,
+        mesh: &RegisteredMesh,
+    ) {
+        let mut clear_values = scene.clear_values;
+        clear_values[0].color = vk::ClearColorValue {
+            float32: FAILURE_BACKGROUND,
+        };
+        let render_pass_begin_info = vk::RenderPassBeginInfo::default()
+            .render_pass(self.resources.renderpass)
+            .framebuffer(scene.framebuffer)
+            .render_area(scene.extent.into())
+            .clear_values(&clear_values);
+        unsafe {
+            device.ash.cmd_begin_render_pass(
+                command_buffer,
+                &render_pass_begin_info,
+                vk::SubpassContents::INLINE,
+            );
+        }
+        self.bind_default_state(device, command_buffer, scene);
+        self.draw_mesh(
+            device,
+            command_buffer,
+            mesh,
+            PushConstants::unlit(Mat4::IDENTITY),
+        );
+        unsafe { device.ash.cmd_end_render_pass(command_buffer) };
+    }
+
+    /// Projection meshes with their pipeline and material, from `projection_view`, in the
+    /// scene's draw order. The main view draws them all, an inset only the world meshes on a
+    /// layer of `layer_mask`. The default pipeline is bound again after.
+    fn record_meshes(
+        &self,
+        device: &AAADevice,
+        command_buffer: vk::CommandBuffer,
+        scene: &SceneDraw,
+        projection_view: Mat4,
+        layer_mask: Option<u32>,
+    ) {
+        let mut bound_pipeline = self.resources.graphic_pipeline;
+        let mut bound_material_set = self.resources.descriptor_sets.default_material;
+        // Unknown until the first dynamic pipeline is bound
+        let mut set_depth_bias = None;
+        let meshes: Vec<&RegisteredMesh> = self
+            .resources
+            .projection_registered_meshes
+            .meshes()
+            .collect();
+        for &index in scene.draw_order {
+            let (registered_mesh, pipeline) = (meshes[index], scene.mesh_pipelines[index]);
+            if let Some(layer_mask) = layer_mask {
+                if registered_mesh.space != MeshSpace::World
+                    || registered_mesh.layers & layer_mask == 0
+                {
+                    continue;
+                }
+            }
+            if pipeline != bound_pipeline {
+                unsafe {
+                    device.ash.cmd_bind_pipeline(
+                        command_buffer,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        pipeline,
+                    )
+                };
+                bound_pipeline = pipeline;
+                // A pipeline with a static bias overwrites the dynamic one
+                if scene.depth_biases[index].is_none() {
+                    set_depth_bias = None;
+                }
+            }
+            if let Some(depth_bias) = scene.depth_biases[index] {
+                if set_depth_bias != Some(depth_bias) {
+                    unsafe {
+                        device.ash.cmd_set_depth_bias(
+                            command_buffer,
+                            depth_bias.constant_factor,
+                            supported_clamp(device, depth_bias),
+                            depth_bias.slope_factor,
+                        )
+                    };
+                    set_depth_bias = Some(depth_bias);
+                }
+            }
+            // Rebound per run of a material only, the globals in set 0 stay bound
+            let material_set = self.material_set(registered_mesh);
+            if material_set != bound_material_set {
+                self.bind_descriptor_sets(device, command_buffer, MATERIAL_SET, &[material_set]);
+                bound_material_set = material_set;
+            }
+            if let Some(skin) = &registered_mesh.skin {
+                self.bind_descriptor_sets(
+                    device,
+                    command_buffer,
+                    OBJECT_SET,
+                    &[skin.descriptor_set],
+                );
+            }
+            let world = registered_mesh.world_transform;
+            let constants = |transform: Mat4| {
+                match registered_mesh.space {
+                    MeshSpace::World => PushConstants::lit(projection_view, world * transform),
+                    MeshSpace::World2D => PushConstants::unlit(scene.world_2d * world * transform),
+                }
+                .with_texture_layer(registered_mesh.texture_layer)
+            };
+            match batching::shared_transform(&registered_mesh.batch) {
+                Some(shared) => {
+                    self.draw_mesh(device, command_buffer, registered_mesh, constants(shared))
+                }
+                None => self.draw_batch(device, command_buffer, registered_mesh, constants),
+            }
+        }
+
+        if bound_pipeline != self.resources.graphic_pipeline {
+            unsafe {
+                device.ash.cmd_bind_pipeline(
+                    command_buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    self.resources.graphic_pipeline,
+                )
+            };
+        }
+        if bound_material_set != self.resources.descriptor_sets.default_material {
+            self.bind_descriptor_sets(
+                device,
+                command_buffer,
+                MATERIAL_SET,
+                &[self.resources.descriptor_sets.default_material],
+            );
+        }
+    }
+
+    /// `inset`'s rect cleared as asked, then its meshes from its camera. Leaves the viewport and
+    /// scissor on the rect.
+    fn record_inset(
+        &self,
+        device: &AAADevice,
+        command_buffer: vk::CommandBuffer,
+        scene: &SceneDraw,
+        inset: &InsetView,
+    ) {
+        let viewport = &scene.viewports[0];
+        let Some((corner, size)) = inset.rect.resolve(
+            Vec2::new(viewport.x, viewport.y),
+            Vec2::new(viewport.width, viewport.height),
+        ) else {
+            return;
+        };
+        let rect = vk::Rect2D {
+            offset: vk::Offset2D {
+                x: corner.x as i32,
+                y: corner.y as i32,
+            },
+            extent: vk::Extent2D {
+                width: size.x as u32,
+                height: size.y as u32,
+            },
+        };
+        let target = match (inset.background, inset.clear_depth) {
+            (Some(color), true) => Some(ClearTarget::Both { color, depth: 1.0 }),
+            (Some(color), false) => Some(ClearTarget::Color(color)),
+            (None, true) => Some(ClearTarget::Depth(1.0)),
+            (None, false) => None,
+        };
+        if let Some(target) = target {
+            self.frame_context(scene)
+                .clear_region(&device.ash, command_buffer, rect, target);
+        }
+        unsafe {
+            device.ash.cmd_set_viewport(
+                command_buffer,
+                0,
+                &[vk::Viewport {
+                    x: corner.x,
+                    y: corner.y,
+                    width: size.x,
+                    height: size.y,
+                    ..*viewport
+                }],
+            );
+            device.ash.cmd_set_scissor(command_buffer, 0, &[rect]);
+        }
+        let projection_view = inset.camera.projection_view(size.x / size.y);
+        self.record_meshes(
+            device,
+            command_buffer,
+            scene,
+            projection_view,
+            Some(inset.layer_mask),
+        );
+    }
+
+    /// Meshes drawn again by the insets, for the draw call count.
+    fn inset_draws(&self) -> usize {
+        self.insets
+            .iter()
+            .map(|inset| {
+                self.resources
+                    .projection_registered_meshes
+                    .meshes()
+                    .filter(|registered_mesh| {
+                        registered_mesh.space == MeshSpace::World
+                            && registered_mesh.layers & inset.layer_mask != 0
+                    })
+                    .count()
+            })
+            .sum()
+    }
+
+    /// Load the posted image file in place of the built-in texture, keeping the previous one when
+    /// it fails. Returns whether the texture was replaced.
+    fn update_texture_file(&mut self) -> bool {
+        let Some((path, sampler)) = self.event_states.take_texture_file() else {
+            return false;
+        };
+        #[cfg(feature = "images")]
+        match Texture::from_file(
+            &self.device,
+            &self.resources.device_memory_properties,
+            self.resources.upload_context(),
+            &path,
+            sampler,
+        ) {
+            Ok(texture) => {
+                log::info!(
+                    "Texture {} of {}x{}",
+                    path.display(),
+                    texture.extent.width,
+                    texture.extent.height
+                );
+                unsafe {
+                    let _waiting = self.event_states.heartbeat.waiting(WaitSite::DrawFence);
+                    self.device
+                        .ash
+                        .wait_for_fences(
+                            &[self.resources.draw_commands_reuse_fence],
+                            true,
+                            u64::MAX,
+                        )
+                        .expect("Wait for fence failed.");
+                }
+                self.replace_default_texture(texture);
+                self.track_default_texture(Some(TextureSource::File(path.clone(), sampler)));
+                self.watch_texture_file(path, sampler);
+                true
+            }
+            Err(err) => {
+                warn!("{err}, the previous texture stays");
+                false
+            }
+        }
+        #[cfg(not(feature = "images"))]
+        {
+            let _ = sampler;
+            warn!(
+                "Texture {} not loaded, image files need the images feature",
+                path.display()
+            );
+            false
+        }
+    }
+
+    /// Load the posted block compressed texture in place of the built-in texture, decoded when
+    /// the device cannot sample its format. Keeps the previous one when it fails, returns whether
+    /// the texture was replaced.
+    fn update_compressed_texture(&mut self) -> bool {
+        let Some((compressed, sampler)) = self.event_states.take_compressed_texture() else {
+            return false;
+        };
+        match Texture::from_compressed_or_decoded(
+            &self.device,
+            &self.resources.device_memory_properties,
+            self.resources.upload_context(),
+            &compressed,
+            sampler,
+        ) {
+            Ok(texture) => {
+                info!(
+                    "{:?} texture of {}x{}, {} levels",
+                    compressed.format,
+                    texture.extent.width,
+                    texture.extent.height,
+                    texture.mip_levels
+                );
+                unsafe {
+                    let _waiting = self.event_states.heartbeat.waiting(WaitSite::DrawFence);
+                    self.device
+                        .ash
+                        .wait_for_fences(
+                            &[self.resources.draw_commands_reuse_fence],
+                            true,
+                            u64::MAX,
+                        )
+                        .expect("Wait for fence failed.");
+                }
+                self.replace_default_texture(texture);
+                self.track_default_texture(Some(TextureSource::Compressed(compressed, sampler)));
+                // The file loaded before no longer backs the texture
+                #[cfg(feature = "images")]
+                self.unwatch_texture_file();
+                true
+            }
+            Err(err) => {
+                warn!("{err}, the previous texture stays");
+                false
+            }
+        }
+    }
+
+    /// Watch `path`, the texture file just loaded, in place of the previous one when hot reload is
+    /// on.
+    #[cfg(feature = "images")]
+    fn watch_texture_file(&mut self, path: PathBuf, sampler: SamplerDesc) {
+        self.unwatch_texture_file();
+        if !self.config.read().unwrap().texture_hot_reload {
+            return;
+        }
+        self.texture_watcher
+            .get_or_insert_with(|| {
+                FileWatcher::new(HOT_RELOAD_POLL_INTERVAL, super::texture::decode_rgba8)
+            })
+            .watch(path.clone());
+        self.watched_texture = Some((path, sampler));
+    }
+
+    /// Stop watching the texture file, its pending reload dropped.
+    #[cfg(feature = "images")]
+    fn unwatch_texture_file(&mut self) {
+        if let Some(stale) = self.pending_reload.take() {
+            stale.destroy(&self.device);
+        }
+        if let Some((previous, _)) = self.watched_texture.take() {
+            if let Some(watcher) = &self.texture_watcher {
+                watcher.unwatch(&previous);
+            }
+        }
+    }
+
+    /// Upload the watched texture file once decoded again, and swap it in when the previous frame
+    /// has completed rather than waiting for it, the replaced texture is retired. Returns whether
+    /// it was swapped in. A file failing to decode or upload keeps the current texture.
+    #[cfg(feature = "images")]
+    fn reload_texture_file(&mut self) -> bool {
+        let changes = match &self.texture_watcher {
+            Some(watcher) => watcher.changes(),
+            None => Vec::new(),
+        };
+        for (path, decoded) in changes {
+            let Some((_, sampler)) = self
+                .watched_texture
+                .as_ref()
+                .filter(|(watched, _)| *watched == path)
+            else {
+                continue;
+            };
+            let created = decoded
+                .map_err(Into::into)
+                .and_then(|pixels: image::RgbaImage| {
+                    Texture::from_rgba8(
+                        &self.device,
+                        &self.resources.device_memory_properties,
+                        self.resources.upload_context(),
+                        pixels.as_raw(),
+                        pixels.width(),
+                        pixels.height(),
+                        *sampler,
+                    )
+                });
+            match created {
+                Ok(texture) => {
+                    if let Some(stale) = self.pending_reload.replace(texture) {
+                        stale.destroy(&self.device);
+                    }
+                }
+                Err(err) => error!(
+                    "Reloading {} failed, the previous texture stays: {err}",
+                    path.display()
+                ),
+            }
+        }
+        if self.pending_reload.is_none() {
+            return false;
+        }
+        // The set is rewritten, it must not be in use by the frame in flight
+        let idle = unsafe {
+            self.device
+                .ash
+                .get_fence_status(self.resources.draw_commands_reuse_fence)
+        };
+        if idle != Ok(true) {
+            return false;
+        }
+        let Some(texture) = self.pending_reload.take() else {
+            return false;
+        };
+        // Loaded from the changed file when it is reloaded
+        if self.texture_evicted() {
+            texture.destroy(&self.device);
+            return false;
+        }
+        let size = texture.size_bytes(&self.device);
+        let swapped = self.resources.swap_default_texture(texture);
+        self.residency.resize(&ResidencyKey::DefaultTexture, size);
+        if let Some((previous, uploads)) = swapped {
+            let last_frame = self.metrics.counters.frames.saturating_sub(1);
+            self.retired.retire(last_frame, move |device| {
+                uploads.destroy(device);
+                previous.destroy(device);
+            });
+        }
+        if let Some((path, _)) = &self.watched_texture {
+            info!("Reloaded texture {}", path.display());
+        }
+        true
+    }
+
+    #[cfg(not(feature = "images"))]
+    fn reload_texture_file(&mut self) -> bool {
+        false
+    }
+
+    /// Create the requested texture array and copy the pushed layers into it. Failures are logged,
+    /// a failed array keeps the previous one.
+    fn update_texture_array(&mut self) {
+        let (desc, layers) = self.event_states.take_texture_array();
+        if desc.is_none() && layers.is_empty() {
+            return;
+        }
+        // The previous frame may still sample the array
+        unsafe {
+            let _waiting = self.event_states.heartbeat.waiting(WaitSite::DrawFence);
+            self.device
+                .ash
+                .wait_for_fences(&[self.resources.draw_commands_reuse_fence], true, u64::MAX)
+                .expect("Wait for fence failed.");
+        }
+        if let Some(desc) = desc {
+            let extent = vk::Extent2D {
+                width: desc.width,
+                height: desc.height,
+            };
+            match TextureArray::new(
+                &self.device,
+                &self.resources.device_memory_properties,
+                self.resources.upload_context(),
+                extent,
+                desc.layers,
+                desc.sampler,
+            ) {
+                Ok(texture_array) => self.resources.replace_texture_array(texture_array),
+                Err(err) => warn!("{err}, the previous texture array stays"),
+            }
+        }
+        let upload = self.resources.upload_context();
+        let Some(texture_array) = &mut self.resources.texture_array else {
+            if !layers.is_empty() {
+                warn!("{} texture layers dropped, no texture array", layers.len());
+            }
+            return;
+        };
+        for data in layers {
+            if let Err(err) = texture_array.push_layer(
+                &self.device,
+                &self.resources.device_memory_properties,
+                upload,
+                &data,
+            ) {
+                warn!("Texture layer dropped: {err}");
+            }
+        }
+    }
+
+    /// Load the posted cubemap and bake its irradiance, or the flat one when the cubemap is
+    /// cleared, keeping the previous ones when loading fails. They are swapped in with the posted
+    /// intensity once the previous frame has completed rather than waiting for it, the replaced
+    /// textures are retired.
+    fn update_environment(&mut self) {
+        if let Some(cubemap) = self.event_states.take_cubemap() {
+            let loaded = match cubemap {
+                Some((source, sampler)) => self.load_environment(&source, sampler),
+                None => flat_irradiance(
+                    &self.device,
+                    &self.resources.device_memory_properties,
+                    self.resources.upload_context(),
+                )
+                .map(|irradiance| (None, irradiance)),
+            };
+            match loaded {
+                Ok(environment) => {
+                    if let Some((cubemap, irradiance)) =
+                        self.pending_environment.replace(environment)
+                    {
+                        cubemap.inspect(|cubemap| cubemap.destroy(&self.device));
+                        irradiance.destroy(&self.device);
+                    }
+                }
+                Err(err) => warn!("{err}, the previous environment stays"),
+            }
+        }
+        if let Some(intensity) = self.event_states.take_environment_intensity() {
+            self.pending_intensity = Some(intensity);
+        }
+        if self.pending_environment.is_none() && self.pending_intensity.is_none() {
+            return;
+        }
+        // The global set is rewritten, it must not be in use by the frame in flight
+        let idle = unsafe {
+            self.device
+                .ash
+                .get_fence_status(self.resources.draw_commands_reuse_fence)
+        };
+        if idle != Ok(true) {
+            return;
+        }
+        if let Some(intensity) = self.pending_intensity.take() {
+            self.resources.write_environment_intensity(intensity);
+        }
+        if let Some((cubemap, irradiance)) = self.pending_environment.take() {
+            let (previous, previous_irradiance) =
+                self.resources.replace_environment(cubemap, irradiance);
+            let last_frame = self.metrics.counters.frames.saturating_sub(1);
+            self.retired.retire(last_frame, move |device| {
+                previous.inspect(|cubemap| cubemap.destroy(device));
+                previous_irradiance.destroy(device);
+            });
+        }
+    }
+
+    /// The cubemap of `source` and its irradiance, convolved on the CPU.
+    #[cfg(feature = "images")]
+    fn load_environment(
+        &self,
+        source: &crate::texture::CubemapSource,
+        sampler: SamplerDesc,
+    ) -> Result<(Option<Texture>, Texture), Box<dyn std::error::Error>> {
+        let (size, faces) = decode_cubemap(source)?;
+        let (device, properties) = (&self.device, &self.resources.device_memory_properties);
+        let upload = self.resources.upload_context();
+        let cubemap =
+            Texture::cubemap_from_rgba8(device, properties, upload, &faces, size, sampler)?;
+        let start = Instant::now();
+        let baked = Cubemap::from_rgba8(size, &faces)
+            .downsample(CONVOLUTION_SIZE)
+            .irradiance(IRRADIANCE_SIZE);
+        let irradiance = match irradiance_texture(device, properties, upload, &baked) {
+            Ok(irradiance) => irradiance,
+            Err(err) => {
+                cubemap.destroy(device);
+                return Err(err);
+            }
+        };
+        log::info!(
+            "Cubemap of {size}x{size} faces, irradiance baked in {:?}",
+            start.elapsed()
+        );
+        Ok((Some(cubemap), irradiance))
+    }
+
+    #[cfg(not(feature = "images"))]
+    fn load_environment(
+        &self,
+        source: &crate::texture::CubemapSource,
+        _sampler: SamplerDesc,
+    ) -> Result<(Option<Texture>, Texture), Box<dyn std::error::Error>> {
+        Err(format!("Cubemap {source:?} not loaded, image files need the images feature").into())
+    }
+
+    /// Apply the posted background and reload its shader when the source changed.
+    /// Run the retry asked for from the event loop and lay out the failure shown for `extent`,
+    /// returns whether it is drawn in place of the scene.
+    fn update_failure_screen(&mut self, extent: vk::Extent2D) -> bool {
+        let failure = {
+            let mut screen = self.event_states.failure_screen.lock().unwrap();
+            if screen.take_retry_request() {
+                // Recreating the swapchain is the engine's part, a frame presenting tells it worked
+                if screen
+                    .failure()
+                    .is_some_and(|failure| failure.category == FailureCategory::Device)
+                {
+                    self.swapchain_outdated
+                        .get_or_insert(RecreateReason::OutOfDate);
+                    self.recovering = true;
+                }
+                let recovered = screen.retry(true);
+                info!("Retried from the failure screen, recovered: {recovered}");
+            }
+            screen.failure().cloned()
+        };
+        let laid_out = self
+            .failure_mesh
+            .as_ref()
+            .is_some_and(|(shown, shown_extent, _)| {
+                Some(shown) == failure.as_ref() && *shown_extent == extent
+            });
+        if laid_out {
+            return true;
+        }
+        if let Some((_, _, mut mesh)) = self.failure_mesh.take() {
+            unsafe {
+                let _waiting = self.event_states.heartbeat.waiting(WaitSite::DrawFence);
+                self.device
+                    .ash
+                    .wait_for_fences(&[self.resources.draw_commands_reuse_fence], true, u64::MAX)
+                    .expect("Wait for fence failed.");
+            }
+            mesh.destroy(&self.device);
+        }
+        let Some(failure) = failure else {
+            return false;
+        };
+        let size = Vec2::new(extent.width as f32, extent.height as f32);
+        let mesh = failure::failure_mesh(&failure, size).register(
+            &self.device,
+            &self.resources.device_memory_properties,
+            Some(self.resources.upload_context()),
+        );
+        self.failure_mesh = Some((failure, extent, mesh));
+        true
+    }
+
+    /// Show a device failure for `err` unless one is shown already, instead of taking the render
+    /// thread down.
+    fn fail_device(&self, title: &str, err: vk::Result) {
+        let mut screen = self.event_states.failure_screen.lock().unwrap();
+        if screen
+            .failure()
+            .is_some_and(|failure| failure.category == FailureCategory::Device)
+        {
+            return;
+        }
+        error!("{title}: {err:?}");
+        screen.enter(
+            Failure::new(
+                FailureCategory::Device,
+                title,
+                format!("Vulkan returned {err:?}. Retrying recreates the swapchain."),
+            ),
+            None,
+        );
+    }
+
+    fn update_background(&mut self) {
+        let renderpass = self.resources.renderpass;
+        let draw_fence = self.resources.draw_commands_reuse_fence;
+        if let Some(mode) = self.event_states.take_background() {
+            self.background
+                .set_mode(&self.device, renderpass, draw_fence, mode);
+        }
+        if let Some(user) = self.event_states.take_background_uniforms() {
+            self.background.user = user;
+        }
+        self.background.poll(&self.device, renderpass, draw_fence);
+    }
+
+    /// Install the posted custom passes. The replaced ones are dropped once the previous frame
+    /// stopped using what they recorded.
+    fn update_custom_passes(&mut self) {
+        let changes = self.event_states.take_custom_passes();
+        if changes.is_empty() {
+            return;
+        }
+        unsafe {
+            let _waiting = self.event_states.heartbeat.waiting(WaitSite::DrawFence);
+            self.device
+                .ash
+                .wait_for_fences(&[self.resources.draw_commands_reuse_fence], true, u64::MAX)
+                .expect("Wait for fence failed.");
+        }
+        for (slot, pass) in changes {
+            self.custom_passes.get_mut().set(slot, pass);
         }
     }
 
-    pub fn cycle(&mut self) {
-        let surface = self.surface.lock().unwrap();
-        let mut metrics = Metrics::default();
-
-        while !self.event_states.exiting.load(Ordering::Relaxed) {
-            metrics.start_frame();
-
-            // MARK: throttle
-            // TEMP
-            let force_throttle = false;
-            let throttle_duration = std::time::Duration::from_millis(1);
-            if force_throttle {
-                std::thread::sleep(throttle_duration);
-            }
-
-            // MARK: rotate in real time
-            // let delta = metrics.delta_start_to_start;
-            // resources.uniform *= Mat4::from_euler(glam::EulerRot::XYZ, 0.0, 0.0, delta.as_secs_f32());
-            // Self::update_uniform_buffer(
-            //     &resources.device,
-            //     resources.uniform_color_buffer_memory,
-            //     resources.uniform,
-            // );
-
-            let result = unsafe {
-                self.resources.swapchain_loader.ash.acquire_next_image(
-                    self.resources.swapchain.swapchain_khr,
-                    u64::MAX,
-                    self.resources.present_complete_semaphore,
-                    vk::Fence::null(),
+    /// Bind `sets` from `first_set` on, see `descriptor_set` for the binding model.
+    fn bind_descriptor_sets(
+        &self,
+        device: &AAADevice,
+        command_buffer: vk::CommandBuffer,
+        first_set: u32,
+        sets: &[vk::DescriptorSet],
+    ) {
+        unsafe {
+            device.ash.cmd_bind_descriptor_sets(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.resources.pipeline_layout,
+                first_set,
+                sets,
+                &[],
+            );
+        }
+        self.descriptor_binds.set(self.descriptor_binds.get() + 1);
+    }
+
+    /// Set of `registered_mesh`'s material. Only the texture array material has descriptors of
+    /// its own, the others read the default material's texture.
+    fn material_set(&self, registered_mesh: &RegisteredMesh) -> vk::DescriptorSet {
+        let sets = self.resources.descriptor_sets;
+        match &registered_mesh.material {
+            MaterialKey::Material(material)
+                if material.is_texture_array() && self.resources.texture_array.is_some() =>
+            {
+                sets.texture_array
+            }
+            _ => sets.default_material,
+        }
+    }
+
+    /// Pipeline, descriptor sets, viewport and scissor the scene draws start from. The globals
+    /// and the default material are bound together, in one call, the globals alone while
+    /// nothing sampled the default material.
+    fn bind_default_state(
+        &self,
+        device: &AAADevice,
+        command_buffer: vk::CommandBuffer,
+        scene: &SceneDraw,
+    ) {
+        let sets = self.resources.descriptor_sets;
+        let default_sets = [sets.global, sets.default_material];
+        let bound = if sets.default_material == vk::DescriptorSet::null() {
+            &default_sets[..1]
+        } else {
+            &default_sets[..]
+        };
+        self.bind_descriptor_sets(device, command_buffer, GLOBAL_SET, bound);
+        unsafe {
+            device.ash.cmd_bind_pipeline(
+                command_buffer,
+                vk::PipelineBindPoint::GRAPHICS,
+                self.resources.graphic_pipeline,
+            );
+            device
+                .ash
+                .cmd_set_viewport(command_buffer, 0, &scene.viewports);
+            device
+                .ash
+                .cmd_set_scissor(command_buffer, 0, &scene.scissors);
+        }
+    }
+
+    /// Record the application's pass of `slot`, returns whether there was one.
+    fn record_custom_pass(
+        &self,
+        slot: CustomPassSlot,
+        device: &AAADevice,
+        command_buffer: vk::CommandBuffer,
+        scene: &SceneDraw,
+    ) -> bool {
+        let mut custom_passes = self.custom_passes.borrow_mut();
+        if !custom_passes.is_set(slot) {
+            return false;
+        }
+        let context = self.frame_context(scene);
+        if let Some(decision) = custom_passes.record(slot, &device.ash, command_buffer, &context) {
+            self.decide(decision);
+        }
+        true
+    }
+
+    /// What the custom passes are told about `scene`, its clears are shared with the renderer's.
+    fn frame_context(&self, scene: &SceneDraw) -> FrameContext {
+        FrameContext {
+            render_pass: self.resources.renderpass,
+            extent: scene.extent,
+            perspective: scene.perspective,
+            orthographic: scene.orthographic,
+            device_memory_properties: self.resources.device_memory_properties,
+            frame: self.metrics.counters.frames,
+            offscreen: scene.offscreen,
+            color_attachments: renderpass::COLOR_ATTACHMENTS,
+            depth_attachment: true,
+            samples: self.resources.samples,
+        }
+    }
+
+    fn draw_mesh(
+        &self,
+        device: &AAADevice,
+        command_buffer: vk::CommandBuffer,
+        registered_mesh: &RegisteredMesh,
+        constants: PushConstants,
+    ) {
+        // Evicted, reloaded the next time it is touched
+        if !registered_mesh.is_resident() {
+            return;
+        }
+        let instance_count = registered_mesh.instance_count();
+        if instance_count == 0 {
+            return;
+        }
+        constants.with_tint(registered_mesh.tint).push(
+            device,
+            command_buffer,
+            self.resources.pipeline_layout,
+        );
+        registered_mesh.draw_instanced(device, command_buffer, instance_count);
+    }
+
+    /// Every mesh of a batch placed on its own, with a push of its constants before each draw.
+    fn draw_batch(
+        &self,
+        device: &AAADevice,
+        command_buffer: vk::CommandBuffer,
+        registered_mesh: &RegisteredMesh,
+        constants: impl Fn(Mat4) -> PushConstants,
+    ) {
+        registered_mesh.draw_batch(device, command_buffer, |range| {
+            constants(range.transform)
+                .with_tint(registered_mesh.tint)
+                .push(device, command_buffer, self.resources.pipeline_layout);
+        });
+    }
+
+    /// Free `mesh` and drop every reference to it, handles of meshes already removed are ignored.
+    fn remove_mesh(&mut self, mesh: MeshHandle) {
+        let removed = {
+            let _waiting = self.event_states.heartbeat.waiting(WaitSite::DrawFence);
+            self.resources.unregister_mesh(mesh)
+        };
+        if !removed {
+            return;
+        }
+        self.residency.forget(&ResidencyKey::Mesh(mesh));
+        if self.selected_mesh == Some(mesh) {
+            self.selected_mesh = None;
+            self.gizmo.end_drag();
+            self.event_states.set_selection(None);
+        }
+    }
+
+    /// Register the posted meshes and batches, residency tracks them from their first frame.
+    fn add_meshes(&mut self) {
+        let meshes = self.event_states.take_mesh_additions();
+        let batches = self.event_states.take_batch_additions();
+        let skinned = self.event_states.take_skinned_additions();
+        let deformed = self.event_states.take_deformed_additions();
+        if meshes.is_empty() && batches.is_empty() && skinned.is_empty() && deformed.is_empty() {
+            return;
+        }
+        let upload = self.resources.upload_context();
+        for (handle, mesh, deformer) in deformed {
+            // Written by the shader every frame, the geometry is never shared
+            let fits = self.device.mesh_limits.fits(&mesh);
+            let registered_mesh = {
+                let _waiting = self.event_states.heartbeat.waiting(WaitSite::SetupFence);
+                mesh.register(
+                    &self.device,
+                    &self.resources.device_memory_properties,
+                    Some(upload),
                 )
             };
-            let (present_index, _) = match result {
-                Ok(result) => result,
-                Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => break,
-                Err(err) => panic!("Failed to acquire next image: {:?}", err),
+            if !fits {
+                warn!(
+                    "Mesh of {} vertices exceeds the device limits, drawn undeformed",
+                    registered_mesh.mesh.vertices.len()
+                );
+                self.track_mesh(handle, registered_mesh);
+                continue;
+            }
+            let deform = DeformBuffer::new(
+                &self.device,
+                &self.resources.device_memory_properties,
+                self.compute.set_layout,
+                deformer,
+                &registered_mesh.mesh.vertices,
+            );
+            self.track_mesh(handle, registered_mesh.with_deform(deform));
+        }
+        for (handle, mesh, player) in skinned {
+            // Posed vertices differ per mesh, the geometry is never shared
+            let registered_mesh = {
+                let _waiting = self.event_states.heartbeat.waiting(WaitSite::SetupFence);
+                mesh.register(
+                    &self.device,
+                    &self.resources.device_memory_properties,
+                    Some(upload),
+                )
             };
-            let clear_values = [
-                vk::ClearValue {
-                    color: vk::ClearColorValue {
-                        float32: [0.0, 0.0, 0.0, 0.0],
-                    },
-                },
-                vk::ClearValue {
-                    depth_stencil: vk::ClearDepthStencilValue {
-                        depth: 1.0,
-                        stencil: 0,
-                    },
-                },
-            ];
+            let skin = SkinBuffer::new(
+                &self.device,
+                &self.resources.device_memory_properties,
+                self.resources.desc_set_layouts[OBJECT_SET as usize],
+                player,
+                registered_mesh.joints_used(),
+            );
+            self.track_mesh(handle, registered_mesh.with_skin(skin));
+        }
+        for (handle, batch, space) in batches {
+            let registered_mesh = {
+                let _waiting = self.event_states.heartbeat.waiting(WaitSite::SetupFence);
+                batch
+                    .register(
+                        &self.device,
+                        &self.resources.device_memory_properties,
+                        Some(upload),
+                    )
+                    .with_space(space)
+            };
+            self.track_mesh(handle, registered_mesh);
+        }
+        for (handle, mesh, space, instances) in meshes {
+            // Meshes of the same geometry draw from a single GPU copy
+            let hash = assets::geometry_hash(&mesh);
+            let shared = self
+                .resources
+                .projection_registered_meshes
+                .find_geometry(hash, &mesh);
+            let registered_mesh = match shared {
+                Some(chunks) => {
+                    metrics::count_dedup_hit(mesh.size_bytes());
+                    mesh.share(chunks)
+                }
+                None => {
+                    let _waiting = self.event_states.heartbeat.waiting(WaitSite::SetupFence);
+                    mesh.register(
+                        &self.device,
+                        &self.resources.device_memory_properties,
+                        Some(upload),
+                    )
+                }
+            }
+            .with_space(space);
+            let registered_mesh = match instances {
+                Some(instances) => registered_mesh.with_instances(instances),
+                None => registered_mesh,
+            };
+            self.track_mesh(handle, registered_mesh);
+            self.resources
+                .projection_registered_meshes
+                .remember_geometry(hash, handle);
+        }
+    }
 
-            let render_pass_begin_info = vk::RenderPassBeginInfo::default()
-                .render_pass(self.resources.renderpass)
-                .framebuffer(self.resources.framebuffers[present_index as usize])
-                .render_area(surface.capabilities.current_extent.into())
-                .clear_values(&clear_values);
+    /// Register `registered_mesh` under `handle`, reserved when it was posted.
+    fn track_mesh(&mut self, handle: MeshHandle, registered_mesh: RegisteredMesh) {
+        let size = registered_mesh.mesh.size_bytes();
+        let priority = registered_mesh.residency;
+        self.resources
+            .projection_registered_meshes
+            .insert_at(handle, registered_mesh);
+        self.residency
+            .track(ResidencyKey::Mesh(handle), size, priority);
+    }
 
-            crate::vulkan::record::record_submit_commandbuffer(
-                &self.resources.device,
-                self.resources.draw_command_buffer,
-                self.resources.draw_commands_reuse_fence,
-                self.resources.swapchain.present_queue,
-                &[vk::PipelineStageFlags::BOTTOM_OF_PIPE],
-                &[self.resources.present_complete_semaphore],
-                &[self.resources.rendering_complete_semaphore],
-                |device, draw_command_buffer| unsafe {
-                    device.ash.cmd_begin_render_pass(
-                        draw_command_buffer,
-                        &render_pass_begin_info,
-                        vk::SubpassContents::INLINE,
-                    );
-                    device.ash.cmd_bind_descriptor_sets(
-                        draw_command_buffer,
-                        vk::PipelineBindPoint::GRAPHICS,
-                        self.resources.pipeline_layout,
-                        0,
-                        &self.resources.descriptor_sets,
-                        &[],
-                    );
-                    device.ash.cmd_bind_pipeline(
-                        draw_command_buffer,
-                        vk::PipelineBindPoint::GRAPHICS,
-                        self.resources.graphic_pipeline,
-                    );
-                    device
-                        .ash
-                        .cmd_set_viewport(draw_command_buffer, 0, &self.resources.viewports);
-                    device
-                        .ash
-                        .cmd_set_scissor(draw_command_buffer, 0, &self.resources.scissors);
+    /// Copy the instance transforms changed since the last frame into the instance buffers.
+    fn update_instances(&mut self) {
+        let changes: Vec<(MeshHandle, (u64, Vec<Mat4>))> = self
+            .resources
+            .projection_registered_meshes
+            .iter()
+            .filter_map(|(handle, registered_mesh)| {
+                let instances = registered_mesh.instances.as_ref()?;
+                Some((handle, instances.source.changes_since(instances.version)?))
+            })
+            .collect();
+        if changes.is_empty() {
+            return;
+        }
+        // The previous frame may still read the buffers
+        unsafe {
+            let _waiting = self.event_states.heartbeat.waiting(WaitSite::DrawFence);
+            self.device
+                .ash
+                .wait_for_fences(&[self.resources.draw_commands_reuse_fence], true, u64::MAX)
+                .expect("Wait for fence failed.");
+        }
+        for (mesh, (version, transforms)) in changes {
+            if let Some(instances) = self
+                .resources
+                .projection_registered_meshes
+                .get_mut(mesh)
+                .and_then(|registered_mesh| registered_mesh.instances.as_mut())
+            {
+                instances.write(
+                    &self.device,
+                    &self.resources.device_memory_properties,
+                    (version, &transforms),
+                );
+            }
+        }
+    }
 
-                    for registered_mesh in &self.resources.projection_registered_meshes {
-                        let pvm = self.resources.camera.perspective.projection_view
-                            * registered_mesh.mesh.transform;
+    /// Advance the animation players by `ticks` simulation ticks and copy their pose into the skin
+    /// buffers.
+    fn update_skins(&mut self, ticks: u32) {
+        let skins: Vec<&SkinBuffer> = self
+            .resources
+            .projection_registered_meshes
+            .meshes()
+            .filter_map(|registered_mesh| registered_mesh.skin.as_ref())
+            .collect();
+        if skins.is_empty() {
+            return;
+        }
+        // The previous frame may still read the buffers
+        unsafe {
+            let _waiting = self.event_states.heartbeat.waiting(WaitSite::DrawFence);
+            self.device
+                .ash
+                .wait_for_fences(&[self.resources.draw_commands_reuse_fence], true, u64::MAX)
+                .expect("Wait for fence failed.");
+        }
+        for skin in skins {
+            // One tick at a time, stepping while paused poses them as running does
+            for _ in 0..ticks {
+                skin.source.advance(FIXED_TICK.as_secs_f32());
+            }
+            skin.write(&self.device);
+        }
+    }
 
-                        device.ash.cmd_push_constants(
-                            draw_command_buffer,
-                            self.resources.pipeline_layout,
-                            vk::ShaderStageFlags::VERTEX,
-                            0,
-                            mat4_to_bytes(&pvm),
-                        );
-                        device.ash.cmd_bind_vertex_buffers(
-                            draw_command_buffer,
-                            0,
-                            &[registered_mesh.vertex_buffer],
-                            &[0],
-                        );
-                        device.ash.cmd_bind_index_buffer(
-                            draw_command_buffer,
-                            registered_mesh.index_buffer,
-                            0,
-                            vk::IndexType::UINT32,
-                        );
-                        device.ash.cmd_draw_indexed(
-                            draw_command_buffer,
-                            registered_mesh.mesh.indices.len() as u32,
-                            1,
-                            0,
-                            0,
-                            0,
-                        );
+    /// Copy the posted mesh contents, the last update of each kind per mesh wins.
+    fn update_meshes(&mut self, updates: Vec<(MeshHandle, MeshUpdate)>, counters: UpdateCounters) {
+        let mut pending: Vec<PendingMeshUpdate> = Vec::new();
+        self.metrics.counters.mesh_updates = counters;
+        for (mesh, update) in updates {
+            let registry = &mut self.resources.projection_registered_meshes;
+            let mut pending_index =
+                || match pending.iter().position(|(pending, _, _)| *pending == mesh) {
+                    Some(index) => index,
+                    None => {
+                        pending.push((mesh, None, None));
+                        pending.len() - 1
+                    }
+                };
+            match update {
+                MeshUpdate::Vertices(vertices) => {
+                    let index = pending_index();
+                    pending[index].1 = Some(vertices);
+                }
+                MeshUpdate::Indices(indices) => {
+                    let index = pending_index();
+                    pending[index].2 = Some(indices);
+                }
+                // Transforms and links stay on the CPU, applied in order
+                MeshUpdate::Transform(transform) => {
+                    registry.set_local_transform(mesh, transform);
+                }
+                MeshUpdate::Parent(parent) => {
+                    if !registry.set_parent(mesh, parent) {
+                        warn!("Parent {parent:?} of {mesh:?} refused, missing or closing a cycle");
+                    }
+                }
+                MeshUpdate::Layers(layers) => {
+                    if let Some(registered_mesh) = registry.get_mut(mesh) {
+                        registered_mesh.layers = layers;
+                    }
+                }
+                MeshUpdate::TextureLayer(layer) => {
+                    if let Some(registered_mesh) = registry.get_mut(mesh) {
+                        registered_mesh.assigned_material =
+                            layer.map(|_| Material::texture_array());
+                        registered_mesh.texture_layer = layer.unwrap_or_default();
+                    }
+                }
+                MeshUpdate::SortBias(sort_bias) => {
+                    if let Some(registered_mesh) = registry.get_mut(mesh) {
+                        registered_mesh.sort_bias = sort_bias;
+                    }
+                }
+                MeshUpdate::Blend(blend) => {
+                    if let Some(registered_mesh) = registry.get_mut(mesh) {
+                        registered_mesh.blend = blend;
+                    }
+                }
+                MeshUpdate::UserId(user_id) => {
+                    if let Some(registered_mesh) = registry.get_mut(mesh) {
+                        registered_mesh.mesh.user_id = user_id;
+                        if self.selected_mesh == Some(mesh) && registered_mesh.batch.is_empty() {
+                            self.event_states.set_selection(Some((mesh, user_id)));
+                        }
+                    }
+                }
+                MeshUpdate::PipelineOptions(options) => {
+                    if let Some(registered_mesh) = registry.get_mut(mesh) {
+                        registered_mesh.pipeline_options = options;
+                    }
+                }
+                MeshUpdate::Material(material) => {
+                    if let Some(registered_mesh) = registry.get_mut(mesh) {
+                        registered_mesh.assigned_material = material;
+                    }
+                }
+                MeshUpdate::Tint(tint) => {
+                    if let Some(registered_mesh) = registry.get_mut(mesh) {
+                        registered_mesh.tint = tint;
+                    }
+                }
+                MeshUpdate::DepthBias(depth_bias) => {
+                    if let Some(registered_mesh) = registry.get_mut(mesh) {
+                        registered_mesh.depth_bias = depth_bias;
+                    }
+                }
+                MeshUpdate::Residency(priority) => {
+                    if let Some(registered_mesh) = registry.get_mut(mesh) {
+                        registered_mesh.residency = priority;
+                        self.residency
+                            .set_priority(&ResidencyKey::Mesh(mesh), priority);
                     }
+                }
+            }
+        }
+        if pending.is_empty() {
+            return;
+        }
+        // The previous frame may still read the buffers
+        unsafe {
+            let _waiting = self.event_states.heartbeat.waiting(WaitSite::DrawFence);
+            self.device
+                .ash
+                .wait_for_fences(&[self.resources.draw_commands_reuse_fence], true, u64::MAX)
+                .expect("Wait for fence failed.");
+        }
+        // And the dispatches, reading the vertices the deformed meshes were added with
+        self.compute.wait(&self.device);
+        let upload = self.resources.upload_context();
+        for (mesh, vertices, indices) in pending {
+            // Removed since
+            let Some(registered_mesh) = self.resources.projection_registered_meshes.get_mut(mesh)
+            else {
+                continue;
+            };
+            let vertex_count = vertices
+                .as_ref()
+                .map_or(registered_mesh.mesh.vertices.len(), Vec::len);
+            let indices_in_range = indices
+                .as_ref()
+                .unwrap_or(&registered_mesh.mesh.indices)
+                .iter()
+                .all(|&index| (index as usize) < vertex_count);
+            if !indices_in_range {
+                warn!("Update of {mesh:?} dropped, its indices address missing vertices");
+                continue;
+            }
+            let _waiting = self.event_states.heartbeat.waiting(WaitSite::SetupFence);
+            let device_memory_properties = &self.resources.device_memory_properties;
+            if let Some(vertices) = vertices {
+                registered_mesh.update_vertices(
+                    &self.device,
+                    device_memory_properties,
+                    upload,
+                    &vertices,
+                );
+                if let Some(deform) = &mut registered_mesh.deform {
+                    deform.set_rest(&self.device, device_memory_properties, &vertices);
+                }
+            }
+            if let Some(indices) = indices {
+                registered_mesh.update_indices(
+                    &self.device,
+                    device_memory_properties,
+                    upload,
+                    &indices,
+                );
+            }
+            self.residency
+                .resize(&ResidencyKey::Mesh(mesh), registered_mesh.mesh.size_bytes());
+        }
+    }
+
+    /// Advance the deformed meshes by `ticks` simulation ticks and dispatch their shaders, the
+    /// frame draws what they write. Evicted meshes and meshes split by an update are left as
+    /// they are.
+    fn deform_meshes(&mut self, ticks: u32) {
+        let registry = &mut self.resources.projection_registered_meshes;
+        if !registry
+            .meshes()
+            .any(|registered_mesh| registered_mesh.deform.is_some())
+        {
+            return;
+        }
+        // The previous dispatches may still use the sets
+        {
+            let _waiting = self.event_states.heartbeat.waiting(WaitSite::ComputeFence);
+            self.compute.wait(&self.device);
+        }
+        let elapsed = (FIXED_TICK * ticks).as_secs_f32();
+        let mut deformed = Vec::new();
+        for (_, registered_mesh) in registry.iter_mut() {
+            let Some(deform) = &mut registered_mesh.deform else {
+                continue;
+            };
+            deform.time += elapsed;
+            let [chunk] = registered_mesh.chunks.as_slice() else {
+                continue;
+            };
+            deform.target(&self.device, chunk.vertex_buffer);
+            let pipeline = self.compute.pipeline(&self.device, &deform.deformer.shader);
+            deformed.push((
+                pipeline,
+                deform.descriptor_set,
+                deform.constants(),
+                chunk.vertex_buffer,
+            ));
+        }
+        let dispatches: Vec<ComputeDispatch> = deformed
+            .iter()
+            .map(
+                |(pipeline, descriptor_set, constants, vertex_buffer)| ComputeDispatch {
+                    pipeline: *pipeline,
+                    descriptor_set: *descriptor_set,
+                    constants: constants.as_bytes(),
+                    group_counts: group_counts(constants.vertex_count, DEFORM_GROUP_SIZE),
+                    written: *vertex_buffer,
+                },
+            )
+            .collect();
+        if !dispatches.is_empty() {
+            self.compute.dispatch(&self.device, &dispatches);
+            self.submissions.set(self.submissions.get() + 1);
+        }
+    }
 
-                    for registered_mesh in &self.resources.orthographic_registered_meshes {
-                        let pvm = self.resources.camera.orthographic.projection_view
-                            * registered_mesh.mesh.transform;
+    /// Reload the evicted meshes in view and, when one of them samples it or `texture_streamed`,
+    /// the default material's texture, then evict the least recently used streaming resources
+    /// over the budget. Returns the meshes in view.
+    fn update_residency(&mut self, texture_streamed: bool) -> Vec<MeshHandle> {
+        self.residency.next_frame();
+        if let Some(priority) = self.event_states.take_texture_residency() {
+            self.texture_residency = priority;
+            self.residency
+                .set_priority(&ResidencyKey::DefaultTexture, priority);
+        }
+        // Placed where this frame draws them, resolved again after the gizmo moved the selection
+        self.resources
+            .projection_registered_meshes
+            .resolve_world_transforms();
+        let (in_view, texture_sampled) = self.meshes_in_view();
+        let upload = self.resources.upload_context();
+        for &mesh in &in_view {
+            if !self.residency.touch(&ResidencyKey::Mesh(mesh)) {
+                continue;
+            }
+            self.decide(Decision::Reloaded(mesh));
+            if let Some(registered_mesh) = self.resources.projection_registered_meshes.get_mut(mesh)
+            {
+                let _waiting = self.event_states.heartbeat.waiting(WaitSite::SetupFence);
+                registered_mesh.reload(
+                    &self.device,
+                    &self.resources.device_memory_properties,
+                    Some(upload),
+                );
+            }
+        }
+        if (texture_sampled || texture_streamed)
+            && self.residency.touch(&ResidencyKey::DefaultTexture)
+        {
+            self.reload_default_texture();
+        }
+        // Streamed into, it no longer matches what it was loaded from. Compressed textures drop
+        // the updates
+        if texture_streamed
+            && self.texture_source.is_some()
+            && self
+                .resources
+                .texture_uploads
+                .as_ref()
+                .is_some_and(|uploads| !uploads.compressed)
+        {
+            self.track_default_texture(None);
+        }
 
-                        device.ash.cmd_push_constants(
-                            draw_command_buffer,
-                            self.resources.pipeline_layout,
-                            vk::ShaderStageFlags::VERTEX,
-                            0,
-                            mat4_to_bytes(&pvm),
-                        );
-                        device.ash.cmd_bind_vertex_buffers(
-                            draw_command_buffer,
-                            0,
-                            &[registered_mesh.vertex_buffer],
-                            &[0],
-                        );
-                        device.ash.cmd_bind_index_buffer(
-                            draw_command_buffer,
-                            registered_mesh.index_buffer,
-                            0,
-                            vk::IndexType::UINT32,
-                        );
-                        device.ash.cmd_draw_indexed(
-                            draw_command_buffer,
-                            registered_mesh.mesh.indices.len() as u32,
-                            1,
-                            0,
-                            0,
-                            0,
-                        );
+        let evicted = self.residency.evict_over_budget();
+        if !evicted.is_empty() {
+            // The previous frame may still read the buffers and sample the texture
+            unsafe {
+                let _waiting = self.event_states.heartbeat.waiting(WaitSite::DrawFence);
+                self.device
+                    .ash
+                    .wait_for_fences(&[self.resources.draw_commands_reuse_fence], true, u64::MAX)
+                    .expect("Wait for fence failed.");
+            }
+            for key in evicted {
+                match key {
+                    ResidencyKey::Mesh(mesh) => {
+                        self.decide(Decision::Evicted(mesh));
+                        if let Some(registered_mesh) =
+                            self.resources.projection_registered_meshes.get_mut(mesh)
+                        {
+                            registered_mesh.evict(&self.device);
+                        }
                     }
+                    ResidencyKey::DefaultTexture => self.evict_default_texture(),
+                }
+            }
+        }
 
-                    // Or draw without the index buffer
-                    // device.cmd_draw(draw_command_buffer, 3, 1, 0, 0);
-                    device.ash.cmd_end_render_pass(draw_command_buffer);
-                },
+        let stats = self.residency.stats();
+        self.metrics.counters.residency_evictions = stats.evictions;
+        self.metrics.counters.residency_reloads = stats.reloads;
+        self.metrics.resident_bytes = stats.resident_bytes;
+        self.metrics.memory_budget_bytes = stats.budget_bytes;
+        in_view
+    }
+
+    /// Projection meshes in view of the scene camera of their space or of an inset drawing them,
+    /// and whether one of them samples the default material's texture: the built-in shaders do,
+    /// as may the materials bound to its set.
+    fn meshes_in_view(&self) -> (Vec<MeshHandle>, bool) {
+        let camera = &self.resources.camera;
+        let perspective = Frustum::from_projection_view(camera.perspective().projection_view);
+        let world_2d = Frustum::from_projection_view(camera.world_2d().projection_view);
+        let viewport = &self.resources.viewport.viewports[0];
+        let insets: Vec<(u32, Frustum)> = self
+            .insets
+            .iter()
+            .filter_map(|inset| {
+                let (_, size) = inset.rect.resolve(
+                    Vec2::new(viewport.x, viewport.y),
+                    Vec2::new(viewport.width, viewport.height),
+                )?;
+                let projection_view = inset.camera.projection_view(size.x / size.y);
+                Some((
+                    inset.layer_mask,
+                    Frustum::from_projection_view(projection_view),
+                ))
+            })
+            .collect();
+        let default_material = self.resources.descriptor_sets.default_material;
+        let mut texture_sampled = false;
+        let in_view = self
+            .resources
+            .projection_registered_meshes
+            .iter()
+            .filter(|(_, registered_mesh)| match registered_mesh.space {
+                MeshSpace::World => {
+                    registered_mesh.in_frustum(&perspective)
+                        || insets.iter().any(|(layer_mask, frustum)| {
+                            registered_mesh.layers & layer_mask != 0
+                                && registered_mesh.in_frustum(frustum)
+                        })
+                }
+                MeshSpace::World2D => registered_mesh.in_frustum(&world_2d),
+            })
+            .map(|(mesh, registered_mesh)| {
+                texture_sampled |= self.material_set(registered_mesh) == default_material;
+                mesh
+            })
+            .collect();
+        (in_view, texture_sampled)
+    }
+
+    /// Track the default material's texture, just loaded from `source`, at its priority. `None`
+    /// keeps it resident, untracked, it could not be loaded again.
+    fn track_default_texture(&mut self, source: Option<TextureSource>) {
+        self.texture_source = source;
+        if self.texture_source.is_none() {
+            self.residency.forget(&ResidencyKey::DefaultTexture);
+            return;
+        }
+        let size = self.resources.textures[0].size_bytes(&self.device);
+        self.residency
+            .track(ResidencyKey::DefaultTexture, size, self.texture_residency);
+    }
+
+    /// Whether the placeholder is sampled in place of the default material's texture.
+    fn texture_evicted(&self) -> bool {
+        self.texture_source.is_some() && !self.residency.is_resident(&ResidencyKey::DefaultTexture)
+    }
+
+    /// Sample `texture` in place of the default material's texture. The previous one is
+    /// destroyed, or kept when it is the placeholder. The commands sampling it must have
+    /// completed.
+    fn replace_default_texture(&mut self, texture: Texture) {
+        if !self.texture_evicted() {
+            self.resources.replace_default_texture(texture);
+            return;
+        }
+        if let Some((placeholder, uploads)) = self.resources.swap_default_texture(texture) {
+            uploads.destroy(&self.device);
+            self.texture_placeholder = Some(placeholder);
+        }
+    }
+
+    /// Free the default material's texture, the placeholder sampled in its place.
+    fn evict_default_texture(&mut self) {
+        let placeholder = match self.texture_placeholder.take() {
+            Some(placeholder) => placeholder,
+            None => Texture::from_rgba8(
+                &self.device,
+                &self.resources.device_memory_properties,
+                self.resources.upload_context(),
+                &[u8::MAX; 4],
+                1,
+                1,
+                SamplerDesc::default(),
+            )
+            .expect("Failed to create the texture placeholder"),
+        };
+        self.decide(Decision::TextureEvicted);
+        if let Some((texture, uploads)) = self.resources.swap_default_texture(placeholder) {
+            uploads.destroy(&self.device);
+            texture.destroy(&self.device);
+        }
+    }
+
+    /// Load the evicted default material's texture again from its source. A source failing to
+    /// load leaves the placeholder sampled, untracked.
+    fn reload_default_texture(&mut self) {
+        let Some(source) = &self.texture_source else {
+            return;
+        };
+        let (device, device_memory_properties, upload) = (
+            &self.device,
+            &self.resources.device_memory_properties,
+            self.resources.upload_context(),
+        );
+        let loaded = {
+            let _waiting = self.event_states.heartbeat.waiting(WaitSite::SetupFence);
+            match source {
+                #[cfg(feature = "images")]
+                TextureSource::File(path, sampler) => {
+                    Texture::from_file(device, device_memory_properties, upload, path, *sampler)
+                }
+                TextureSource::Compressed(compressed, sampler) => {
+                    Texture::from_compressed_or_decoded(
+                        device,
+                        device_memory_properties,
+                        upload,
+                        compressed,
+                        *sampler,
+                    )
+                }
+            }
+        };
+        match loaded {
+            Ok(texture) => {
+                self.decide(Decision::TextureReloaded);
+                // The set is rewritten, it must not be in use by the frame in flight
+                unsafe {
+                    let _waiting = self.event_states.heartbeat.waiting(WaitSite::DrawFence);
+                    self.device
+                        .ash
+                        .wait_for_fences(
+                            &[self.resources.draw_commands_reuse_fence],
+                            true,
+                            u64::MAX,
+                        )
+                        .expect("Wait for fence failed.");
+                }
+                if let Some((placeholder, uploads)) = self.resources.swap_default_texture(texture) {
+                    uploads.destroy(&self.device);
+                    self.texture_placeholder = Some(placeholder);
+                }
+            }
+            Err(err) => {
+                error!("Reloading the evicted texture failed, it stays white: {err}");
+                self.track_default_texture(None);
+            }
+        }
+    }
+
+    /// World size of the gizmo so it keeps the same size on screen.
+    fn gizmo_scale(&self, target: &Mat4, viewport: vk::Extent2D) -> f32 {
+        let camera = &self.resources.camera;
+        crate::gizmo::screen_constant_scale(
+            camera.position(),
+            target.w_axis.truncate(),
+            camera.perspective().fov_y,
+            viewport.height as f32,
+            GIZMO_SCREEN_SIZE,
+        )
+    }
+
+    /// Select meshes under the cursor and drag the gizmo handles with the left mouse button.
+    fn update_gizmo(&mut self, viewport: vk::Extent2D) {
+        let pressed = self
+            .event_states
+            .mouse_button(winit::event::MouseButton::Left);
+        let just_pressed = pressed && !self.mouse_was_pressed;
+        self.mouse_was_pressed = pressed;
+
+        if !pressed {
+            self.gizmo.end_drag();
+            if let Some(mode) = GizmoMode::from_modifiers(self.event_states.modifiers()) {
+                self.gizmo.mode = mode;
+            }
+            return;
+        }
+        let Some(cursor) = self.scene_cursor() else {
+            return;
+        };
+        let ray = Ray::from_cursor(
+            cursor,
+            Vec2::new(viewport.width as f32, viewport.height as f32),
+            self.resources.camera.perspective().projection_view,
+        );
+
+        if just_pressed {
+            // Ctrl and Alt clicks belong to the window bindings
+            if GizmoMode::from_modifiers(self.event_states.modifiers()).is_none() {
+                return;
+            }
+            if let Some(target) = self.selected_world_transform() {
+                let scale = self.gizmo_scale(&target, viewport);
+                if self.gizmo.begin_drag(&ray, &target, scale) {
+                    return;
+                }
+            }
+            // The flat 2D world is picked first, through its own camera
+            let ray_2d = Ray::from_cursor(
+                cursor,
+                Vec2::new(viewport.width as f32, viewport.height as f32),
+                self.resources.camera.world_2d().projection_view,
             );
-            let wait_semaphors = [self.resources.rendering_complete_semaphore];
-            let swapchains = [self.resources.swapchain.swapchain_khr];
-            let image_indices = [present_index];
-            let present_info = vk::PresentInfoKHR::default()
-                .wait_semaphores(&wait_semaphors)
-                .swapchains(&swapchains)
-                .image_indices(&image_indices);
+            let hit = self
+                .pick(MeshSpace::World2D, &ray_2d)
+                .or_else(|| self.pick(MeshSpace::World, &ray))
+                .map(|(handle, hit)| (handle, hit.user_id));
+            self.selected_mesh = hit.map(|(handle, _)| handle);
+            self.event_states.set_selection(hit);
+        } else if let (Some(selected), Some(transform)) =
+            (self.selected_mesh, self.gizmo.drag(&ray))
+        {
+            if let Some(registered_mesh) = self
+                .resources
+                .projection_registered_meshes
+                .get_mut(selected)
+            {
+                registered_mesh.world_transform = transform;
+            }
+            // The gizmo moves the mesh in the world, store it under its parent
+            let registry = &mut self.resources.projection_registered_meshes;
+            let parent_world = registry.parent_world_transform(selected);
+            registry.set_local_transform(selected, parent_world.inverse() * transform);
+        }
+    }
 
-            let queue_present_result = unsafe {
-                self.resources
-                    .swapchain_loader
-                    .ash
-                    .queue_present(self.resources.swapchain.present_queue, &present_info)
-            };
+    /// Closest mesh of `space` hit by `ray`, with where it was hit.
+    fn pick(&self, space: MeshSpace, ray: &Ray) -> Option<(MeshHandle, MeshHit)> {
+        let (handles, meshes): (Vec<MeshHandle>, Vec<MeshPart>) = self
+            .resources
+            .projection_registered_meshes
+            .iter()
+            .filter(|(_, registered_mesh)| registered_mesh.space == space)
+            .flat_map(|(handle, registered_mesh)| {
+                registered_mesh.picked_parts().into_iter().map(
+                    move |(user_id, transform, indices)| {
+                        (handle, (user_id, &registered_mesh.mesh, transform, indices))
+                    },
+                )
+            })
+            .unzip();
+        let hit = pick_mesh_ranges(ray, meshes)?;
+        Some((handles[hit.index], hit))
+    }
+
+    /// Transform of the selected mesh when the gizmo can edit it, 2D world meshes have none.
+    fn selected_world_transform(&self) -> Option<Mat4> {
+        self.selected_mesh
+            .and_then(|selected| self.resources.projection_registered_meshes.get(selected))
+            .filter(|registered_mesh| registered_mesh.space == MeshSpace::World)
+            .map(|registered_mesh| registered_mesh.world_transform)
+    }
+
+    /// Pan with a middle button drag and zoom around the cursor with the wheel or a pinch. Lays
+    /// out the scene viewport in `window` first, letterboxed if the controller says so.
+    fn update_world_2d(&mut self, window: vk::Extent2D) {
+        if let Some(controller) = self.event_states.take_ortho_2d() {
+            self.ortho_2d = controller;
+        }
+        let (offset, viewport) = self
+            .ortho_2d
+            .viewport_rect(Vec2::new(window.width as f32, window.height as f32));
+        self.set_scene_viewport(offset, viewport);
+        // Also keeps the view in bounds when the window is resized
+        self.ortho_2d.resize(viewport);
+        let cursor = self.scene_cursor();
+        match cursor.filter(|_| {
+            self.event_states
+                .mouse_button(winit::event::MouseButton::Middle)
+        }) {
+            Some(cursor) => {
+                if let Some(anchor) = self.pan_anchor {
+                    self.ortho_2d.pan(cursor - anchor, viewport);
+                }
+                self.pan_anchor = Some(cursor);
+            }
+            None => self.pan_anchor = None,
+        }
+        let steps = self.event_states.take_zoom_2d();
+        if steps != 0.0 {
+            self.ortho_2d
+                .zoom_at(steps.exp(), cursor.unwrap_or(viewport * 0.5), viewport);
+        }
+        self.resources.camera.apply_2d(&self.ortho_2d, viewport);
+    }
+
+    /// Draw the scene in `size` physical pixels at `offset` of the window, the perspective and
+    /// the UI laid out in it. The bars around it keep the clear color.
+    fn set_scene_viewport(&mut self, offset: Vec2, size: Vec2) {
+        self.resources.viewport = ViewportState::at(offset, size);
+        let scale_factor = self.display.scale_factor as f32;
+        let camera = &mut self.resources.camera;
+        camera.set_aspect_ratio(size.x / size.y.max(1.0));
+        camera.set_orthographic_extent(size.x / scale_factor, size.y / scale_factor);
+    }
+
+    /// Size of the scene viewport, the window's unless letterboxed.
+    fn scene_extent(&self) -> vk::Extent2D {
+        self.resources.viewport.extent()
+    }
+
+    /// Cursor in physical pixels from the scene viewport's top left.
+    fn scene_cursor(&self) -> Option<Vec2> {
+        let viewport = &self.resources.viewport.viewports[0];
+        self.event_states
+            .cursor_position()
+            .map(|cursor| cursor - Vec2::new(viewport.x, viewport.y))
+    }
+
+    /// Dolly the perspective camera toward the world mesh or ground under the cursor by the
+    /// scroll wheel lines of the frame.
+    fn update_dolly(&mut self, viewport: vk::Extent2D) {
+        if let Some(controller) = self.event_states.take_camera_controller() {
+            self.camera_controller = controller;
+        }
+        let lines = self.event_states.take_dolly();
+        let Some(cursor) = self.scene_cursor() else {
+            return;
+        };
+        if lines == 0.0 {
+            return;
+        }
+        let ray = Ray::from_cursor(
+            cursor,
+            Vec2::new(viewport.width as f32, viewport.height as f32),
+            self.resources.camera.perspective().projection_view,
+        );
+        let hit = self.pick(MeshSpace::World, &ray).map(|(_, hit)| hit.point);
+        self.camera_controller
+            .dolly_toward_cursor(&mut self.resources.camera, lines, &ray, hit);
+    }
 
-            match queue_present_result {
-                Ok(_) => {}
-                Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => break,
-                Err(err) => panic!("Failed to present queue: {:?}", err),
+    /// Build the depth of field passes for the current swapchain, whether they can run.
+    fn prepare_depth_of_field(&mut self, surface: &AAASurface) -> bool {
+        if self.depth_of_field.is_some() {
+            return true;
+        }
+        if !AAADepthOfField::supported(self.resources.samples, self.resources.swapchain.image_usage)
+        {
+            if !std::mem::replace(&mut self.depth_of_field_unsupported, true) {
+                warn!(
+                    "Depth of field needs a single sampled swapchain that can be copied from, drawing without it"
+                );
             }
+            return false;
+        }
+        self.depth_of_field = Some(AAADepthOfField::new(
+            &self.device,
+            &self.resources.device_memory_properties,
+            surface.format.format,
+            self.resources.swapchain.extent,
+            &self.resources.present_image_views,
+            self.resources.depth_image,
+            self.resources.depth_image_view,
+        ));
+        true
+    }
 
-            metrics.end_frame();
+    /// Move the focus towards the configured distance or the mesh under the cursor.
+    fn update_focus(&mut self, viewport: vk::Extent2D, delta_seconds: f32) {
+        let dof = self.config.read().unwrap().depth_of_field;
+        let target = match dof.focus_mode {
+            FocusMode::Manual => Some(dof.focus_distance),
+            FocusMode::Cursor => self.scene_cursor().and_then(|cursor| {
+                let perspective = self.resources.camera.perspective();
+                let ray = Ray::from_cursor(
+                    cursor,
+                    Vec2::new(viewport.width as f32, viewport.height as f32),
+                    perspective.projection_view,
+                );
+                let meshes = self
+                    .resources
+                    .projection_registered_meshes
+                    .meshes()
+                    .filter(|registered_mesh| registered_mesh.space == MeshSpace::World)
+                    .flat_map(|registered_mesh| {
+                        registered_mesh.picked_parts().into_iter().map(
+                            |(user_id, transform, indices)| {
+                                (user_id, &registered_mesh.mesh, transform, indices)
+                            },
+                        )
+                    });
+                // Focus is measured along the view axis, like the depth buffer
+                pick_mesh_ranges(&ray, meshes)
+                    .map(|hit| -perspective.view.transform_point3(hit.point).z)
+            }),
+        };
+        if let Some(target) = target {
+            self.focus
+                .update(target, delta_seconds, dof.adaptation_speed);
+        }
+    }
+
+    /// Move the last recorded readback into the replay buffer once its commands completed.
+    #[cfg(feature = "replay")]
+    fn collect_replay_frame(&mut self) {
+        let Some(readback) = self.resources.readback.as_mut() else {
+            return;
+        };
+        let Some(captured_at) = readback.captured_at.take() else {
+            return;
+        };
+        unsafe {
+            let _waiting = self.event_states.heartbeat.waiting(WaitSite::DrawFence);
+            self.device
+                .ash
+                .wait_for_fences(&[self.resources.draw_commands_reuse_fence], true, u64::MAX)
+                .expect("Wait for fence failed.");
         }
+        self.replay.push(ReplayFrame {
+            width: readback.extent.width,
+            height: readback.extent.height,
+            rgba: readback.read(&self.device),
+            captured_at,
+        });
     }
 
     pub fn recreate_swapchain(&mut self, width: u32, height: u32) {
+        let previous_mode = self.resources.swapchain.present_mode;
         self.destroy_swapchain();
+        if let Some(depth_of_field) = self.depth_of_field.take() {
+            depth_of_field.destroy(&self.device);
+        }
+        self.present_pacer.reset();
 
         let mut surface = self.surface.lock().unwrap();
-        surface.recreate(&*self.base.surface_loader);
-        self.resources.recreate_viewports(width, height); // TODO sync release with drop
-        self.resources.recreate_scissors(width, height); // TODO sync release with drop
+        surface.recreate(&self.base.surface_loader);
+        self.resources.viewport = ViewportState::new(width, height);
 
         self.resources.swapchain = crate::vulkan::swapchain::AAASwapchain::new(
             &self.resources.device,
@@ -234,7 +3169,20 @@ impl AAAGraphics {
             width,
             height,
             &self.resources.swapchain_loader,
+            self.present_preference,
         );
+        let present_mode = self.resources.swapchain.present_mode;
+        if present_mode != previous_mode {
+            info!(
+                "Presenting with {present_mode:?} instead of {previous_mode:?}, {:?} preferred",
+                self.present_preference
+            );
+            // Samples of another mode say nothing of this one
+            self.present_health.reset(self.display.refresh_interval());
+            self.decide(Decision::PresentModeChanged(present_mode));
+        }
+        self.event_states
+            .publish_present_mode(self.present_preference, present_mode);
 
         // MARK: recreate_views_and_depth
         let (
@@ -243,6 +3191,7 @@ impl AAAGraphics {
             depth_image_view,
             depth_image,
             depth_image_memory,
+            msaa_color,
             device_memory_properties_new,
         ) = crate::vulkan::views::create_views_and_depth(
             &self.resources.device,
@@ -251,6 +3200,7 @@ impl AAAGraphics {
             &surface,
             &surface.physical_device,
             &self.resources.swapchain_loader,
+            self.resources.samples,
         );
 
         self.resources.present_images = present_images;
@@ -258,100 +3208,222 @@ impl AAAGraphics {
         self.resources.depth_image_view = depth_image_view;
         self.resources.depth_image = depth_image;
         self.resources.depth_image_memory = depth_image_memory;
+        self.resources.msaa_color = msaa_color;
         self.resources.device_memory_properties = device_memory_properties_new;
 
         // MARK: recreate_framebuffers
         self.resources.framebuffers = crate::vulkan::framebuffer::create_framebuffers(
             &self.resources.device,
-            &surface,
+            self.resources.swapchain.extent,
             &self.resources.present_image_views,
             depth_image_view,
+            self.resources.msaa_color.as_ref(),
             self.resources.renderpass,
         )
         .unwrap();
 
         self.resources.register_depth_image_memory();
 
-        self.resources.camera.perspective.aspect_ratio = width as f32 / height as f32;
-        self.resources.camera.orthographic.right = width as f32;
-        self.resources.camera.orthographic.top = height as f32;
-        self.resources.camera.update();
+        #[cfg(feature = "replay")]
+        {
+            self.resources.readback = AAAReadback::for_swapchain(
+                &self.resources.device,
+                &self.resources.device_memory_properties,
+                &self.resources.swapchain,
+                &surface,
+                REPLAY_DOWNSCALE,
+                &self.resources.export,
+            );
+        }
+    }
+
+    /// Apply a new display environment at once: swapchain extent, cameras and UI scale.
+    pub fn apply_display_environment(&mut self, display: DisplayEnvironment) {
+        let resized = display.width != self.display.width || display.height != self.display.height;
+        self.display = display;
+        self.present_health.reset(display.refresh_interval());
+        if display.is_zero_sized() {
+            // Minimized, recreate once the window has an area again
+            self.swapchain_outdated
+                .get_or_insert(RecreateReason::OutOfDate);
+            return;
+        }
+        if resized || self.swapchain_outdated.is_some() {
+            self.recreate_swapchain(display.width, display.height);
+            self.decide(Decision::SwapchainRecreated {
+                reason: match self.swapchain_outdated {
+                    _ if resized => RecreateReason::Resized,
+                    Some(reason) => reason,
+                    None => RecreateReason::OutOfDate,
+                },
+                width: display.width,
+                height: display.height,
+            });
+            self.metrics.counters.swapchain_recreations += 1;
+            self.swapchain_outdated = None;
+        }
+        self.update_cameras();
+    }
+
+    /// Present with FIFO on this device for the rest of the session, from the next frame on.
+    fn downgrade_present_mode(&mut self, downgrade: PresentDowngrade) {
+        let (vendor_id, device_id) = self.pci_ids;
+        warn!(
+            "MAILBOX presentation starves device {vendor_id:#06x}:{device_id:#06x}, falling back to FIFO: {downgrade}"
+        );
+        present_health::remember_downgrade(vendor_id, device_id);
+        self.decide(Decision::PresentModeDowngraded {
+            slow_frames: downgrade.slow_frames,
+        });
+        self.event_states.report_present_downgrade(downgrade);
+        self.swapchain_outdated
+            .get_or_insert(RecreateReason::PresentModeDowngraded);
+    }
+
+    /// Note `decision` in the flight recorder, for the post-mortem dumps.
+    fn decide(&self, decision: Decision) {
+        self.event_states
+            .decisions
+            .record(self.metrics.counters.frames, decision);
+    }
+
+    /// Perspective follows the extent, the UI is laid out in logical pixels.
+    fn update_cameras(&mut self) {
+        let (logical_width, logical_height) = self.display.logical_size();
+        let camera = &mut self.resources.camera;
+        camera.set_aspect_ratio(self.display.width as f32 / self.display.height as f32);
+        camera.set_orthographic_extent(logical_width, logical_height);
     }
 
     pub fn destroy_swapchain(&self) {
         unsafe {
-            self.resources.device.ash.device_wait_idle().unwrap();
+            {
+                let _waiting = self.event_states.heartbeat.waiting(WaitSite::DeviceIdle);
+                self.resources.device.ash.device_wait_idle().unwrap();
+            }
 
             for &framebuffer in self.resources.framebuffers.iter() {
+                crate::object_audit::destroyed(framebuffer);
                 self.resources
                     .device
                     .ash
                     .destroy_framebuffer(framebuffer, None);
             }
             for &image_view in self.resources.present_image_views.iter() {
+                crate::object_audit::destroyed(image_view);
                 self.resources
                     .device
                     .ash
                     .destroy_image_view(image_view, None);
             }
+            crate::object_audit::destroyed(self.resources.swapchain.swapchain_khr);
             self.resources
                 .swapchain_loader
                 .ash
                 .destroy_swapchain(self.resources.swapchain.swapchain_khr, None);
 
+            crate::object_audit::destroyed(self.resources.depth_image_memory);
             self.resources
                 .device
                 .ash
                 .free_memory(self.resources.depth_image_memory, None);
+            crate::object_audit::destroyed(self.resources.depth_image_view);
             self.resources
                 .device
                 .ash
                 .destroy_image_view(self.resources.depth_image_view, None);
+            crate::object_audit::destroyed(self.resources.depth_image);
             self.resources
                 .device
                 .ash
                 .destroy_image(self.resources.depth_image, None);
+            if let Some(msaa_color) = self.resources.msaa_color.as_ref() {
+                msaa_color.destroy(&self.resources.device);
+            }
+
+            if let Some(readback) = self.resources.readback.as_ref() {
+                readback.destroy(&self.resources.device);
+            }
         }
     }
 }
 
 impl Drop for AAAGraphics {
     fn drop(&mut self) {
+        // Applications lose the device before it is destroyed
+        self.event_states.raw_device.lock().unwrap().take();
         self.destroy_swapchain();
+        if let Some(depth_of_field) = self.depth_of_field.take() {
+            depth_of_field.destroy(&self.device);
+        }
+        // The device is idle and still alive for what the passes own
+        *self.custom_passes.get_mut() = CustomPasses::default();
+        self.pipelines.destroy();
+        self.thumbnail_sphere.destroy(&self.device);
+        self.retired.destroy(&self.device);
+        #[cfg(feature = "images")]
+        if let Some(texture) = self.pending_reload.take() {
+            texture.destroy(&self.device);
+        }
+        if let Some((cubemap, irradiance)) = self.pending_environment.take() {
+            cubemap.inspect(|cubemap| cubemap.destroy(&self.device));
+            irradiance.destroy(&self.device);
+        }
+        if let Some(placeholder) = self.texture_placeholder.take() {
+            placeholder.destroy(&self.device);
+        }
+        if let Some((_, _, mesh)) = &mut self.failure_mesh {
+            mesh.destroy(&self.device);
+        }
+        self.debug_lines.destroy(&self.device);
+        self.background.destroy(&self.device);
+        self.compute.destroy(&self.device);
+        self.resources.export.destroy(&self.device);
+        if let Some(gpu_timer) = &self.gpu_timer {
+            gpu_timer.destroy(&self.device);
+        }
 
         unsafe {
-            for &pipeline in self.resources.graphics_pipelines.iter() {
+            for &pipeline in &self.resources.graphics_pipelines {
+                crate::object_audit::destroyed(pipeline);
                 self.resources.device.ash.destroy_pipeline(pipeline, None);
             }
 
+            crate::object_audit::destroyed(self.resources.pipeline_layout);
             self.resources
                 .device
                 .ash
                 .destroy_pipeline_layout(self.resources.pipeline_layout, None);
 
+            crate::object_audit::destroyed(self.resources.renderpass);
             self.resources
                 .device
                 .ash
                 .destroy_render_pass(self.resources.renderpass, None);
 
+            crate::object_audit::destroyed(self.resources.present_complete_semaphore);
             self.resources
                 .device
                 .ash
                 .destroy_semaphore(self.resources.present_complete_semaphore, None);
+            crate::object_audit::destroyed(self.resources.rendering_complete_semaphore);
             self.resources
                 .device
                 .ash
                 .destroy_semaphore(self.resources.rendering_complete_semaphore, None);
 
+            crate::object_audit::destroyed(self.resources.draw_commands_reuse_fence);
             self.resources
                 .device
                 .ash
                 .destroy_fence(self.resources.draw_commands_reuse_fence, None);
+            crate::object_audit::destroyed(self.resources.setup_commands_reuse_fence);
             self.resources
                 .device
                 .ash
                 .destroy_fence(self.resources.setup_commands_reuse_fence, None);
 
+            crate::object_audit::destroyed(self.resources.pool);
             self.resources
                 .device
                 .ash
@@ -359,3 +3431,23 @@ impl Drop for AAAGraphics {
         }
     }
 }
+
+/// Name, type and versions of the physical device, for the diagnostics.
+fn device_info(base: &AAABase, physical_device: vk::PhysicalDevice) -> String {
+    let properties = unsafe {
+        base.instance
+            .get_physical_device_properties(physical_device)
+    };
+    let name = properties
+        .device_name_as_c_str()
+        .map_or("unnamed".into(), |name| name.to_string_lossy());
+    format!(
+        "{name} ({:?}), Vulkan {}.{}.{}, driver {:#x}, vendor {:#06x}",
+        properties.device_type,
+        vk::api_version_major(properties.api_version),
+        vk::api_version_minor(properties.api_version),
+        vk::api_version_patch(properties.api_version),
+        properties.driver_version,
+        properties.vendor_id,
+    )
+}
@@ -13,7 +13,7 @@ pub fn create_instance(
             .application_version(0)
             .engine_name(app_name)
             .engine_version(0)
-            .api_version(vk::make_api_version(0, 1, 0, 0));
+            .api_version(vk::make_api_version(0, 1, 1, 0));
         let mut extension_names =
             ash_window::enumerate_required_extensions(display_handle.as_raw())
                 .unwrap()
@@ -26,9 +26,7 @@ pub fn create_instance(
             extension_names.push(ash::khr::get_physical_device_properties2::NAME.as_ptr());
         }
         #[cfg(debug_assertions)]
-        let layer_names = [ffi::CStr::from_bytes_with_nul_unchecked(
-            b"VK_LAYER_KHRONOS_validation\0",
-        )];
+        let layer_names = [c"VK_LAYER_KHRONOS_validation"];
         #[cfg(not(debug_assertions))]
         let layer_names: Vec<ffi::CString> = Vec::new();
         let layers_names_raw: Vec<*const c_char> = layer_names
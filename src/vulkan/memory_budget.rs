@@ -0,0 +1,60 @@
+use super::device::AAADevice;
+use ash::vk;
+
+/// Share of the device local heaps left to the driver and other applications
+/// when `VK_EXT_memory_budget` is unavailable.
+const HEAP_MARGIN: f64 = 0.2;
+
+#[derive(Debug, Clone, Copy)]
+pub struct AAAMemoryBudget {
+    /// Device local bytes this process may use.
+    pub budget: u64,
+    /// Device local bytes this process uses, as reported by the driver. Zero without the extension.
+    pub usage: u64,
+}
+
+impl AAAMemoryBudget {
+    /// Budget of the device local heaps, from `VK_EXT_memory_budget` when enabled,
+    /// else their sizes minus a margin.
+    pub fn query(
+        instance: &ash::Instance,
+        device: &AAADevice,
+        physical_device: vk::PhysicalDevice,
+    ) -> Self {
+        let mut budget_properties = vk::PhysicalDeviceMemoryBudgetPropertiesEXT::default();
+        let mut properties = vk::PhysicalDeviceMemoryProperties2::default();
+        if device.memory_budget {
+            properties = properties.push_next(&mut budget_properties);
+        }
+        unsafe {
+            instance.get_physical_device_memory_properties2(physical_device, &mut properties)
+        };
+        let memory_properties = properties.memory_properties;
+
+        let device_local = (0..memory_properties.memory_heap_count as usize).filter(|&heap| {
+            memory_properties.memory_heaps[heap]
+                .flags
+                .contains(vk::MemoryHeapFlags::DEVICE_LOCAL)
+        });
+        if device.memory_budget {
+            device_local.fold(
+                Self {
+                    budget: 0,
+                    usage: 0,
+                },
+                |total, heap| Self {
+                    budget: total.budget + budget_properties.heap_budget[heap],
+                    usage: total.usage + budget_properties.heap_usage[heap],
+                },
+            )
+        } else {
+            let size: u64 = device_local
+                .map(|heap| memory_properties.memory_heaps[heap].size)
+                .sum();
+            Self {
+                budget: (size as f64 * (1.0 - HEAP_MARGIN)) as u64,
+                usage: 0,
+            }
+        }
+    }
+}
@@ -0,0 +1,388 @@
+use super::{device::AAADevice, transient::AAATransientMemory, views::find_memorytype_index};
+use crate::{
+    screenshot::{SCREENSHOT_DEPTH, SCREENSHOT_MSAA_COLOR},
+    transient::Lifetime,
+};
+use ash::vk;
+
+/// Memory behind an attachment, freed with it unless shared by transient attachments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttachmentMemory {
+    Dedicated(vk::DeviceMemory),
+    /// Bound in an [`AAATransientMemory`], freed with it.
+    Aliased,
+}
+
+impl AttachmentMemory {
+    pub fn free(self, device: &AAADevice) {
+        if let Self::Dedicated(memory) = self {
+            crate::object_audit::destroyed(memory);
+            unsafe { device.ash.free_memory(memory, None) };
+        }
+    }
+}
+
+/// Color and depth target outside of the swapchain, compatible with the main renderpass.
+pub struct AAAOffscreenTarget {
+    pub color_image: vk::Image,
+    pub color_memory: vk::DeviceMemory,
+    pub color_view: vk::ImageView,
+    pub depth_image: vk::Image,
+    pub depth_memory: AttachmentMemory,
+    pub depth_view: vk::ImageView,
+    /// Drawn into and resolved into the color image, with MSAA.
+    pub msaa_color: Option<AAAMsaaColor>,
+    /// Shared by the depth and multisampled color of an aliased target, and whatever is bound in
+    /// it after them.
+    pub transient: Option<AAATransientMemory>,
+    pub framebuffer: vk::Framebuffer,
+    pub extent: vk::Extent2D,
+}
+
+impl AAAOffscreenTarget {
+    /// `color_format` and `samples` must be the swapchain's for the framebuffer to match
+    /// `renderpass`.
+    pub fn new(
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        renderpass: vk::RenderPass,
+        color_format: vk::Format,
+        samples: vk::SampleCountFlags,
+        extent: vk::Extent2D,
+    ) -> Self {
+        Self::with_lifetimes(
+            device,
+            device_memory_properties,
+            renderpass,
+            color_format,
+            samples,
+            extent,
+            &[],
+        )
+    }
+
+    /// Target whose depth and multisampled color share [`Self::transient`] over their
+    /// `lifetimes`, indexed as [`crate::screenshot::screenshot_passes`]. The images used after
+    /// the scene are bound in it too when they fit, see [`AAATransientMemory::bind`].
+    pub fn with_lifetimes(
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        renderpass: vk::RenderPass,
+        color_format: vk::Format,
+        samples: vk::SampleCountFlags,
+        extent: vk::Extent2D,
+        lifetimes: &[Option<Lifetime>],
+    ) -> Self {
+        let (color_image, color_memory) = create_image(
+            device,
+            device_memory_properties,
+            color_format,
+            extent,
+            vk::SampleCountFlags::TYPE_1,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
+        );
+        let color_view = create_view(
+            device,
+            color_image,
+            color_format,
+            vk::ImageAspectFlags::COLOR,
+        );
+        let depth_image = create_unbound_image(
+            device,
+            vk::Format::D16_UNORM,
+            extent,
+            samples,
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+        );
+        let msaa_image = AAAMsaaColor::create_unbound(device, color_format, extent, samples);
+
+        let depth_lifetime = lifetimes.get(SCREENSHOT_DEPTH).copied().flatten();
+        let msaa_lifetime = lifetimes.get(SCREENSHOT_MSAA_COLOR).copied().flatten();
+        let aliased: Vec<(vk::Image, Lifetime)> = [
+            depth_lifetime.map(|lifetime| (depth_image, lifetime)),
+            msaa_image.zip(msaa_lifetime),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        let transient = (!aliased.is_empty())
+            .then(|| AAATransientMemory::new(device, device_memory_properties, &aliased));
+        let memory = |image: vk::Image, lifetime: Option<Lifetime>| match lifetime {
+            Some(_) => AttachmentMemory::Aliased,
+            None => {
+                AttachmentMemory::Dedicated(bind_dedicated(device, device_memory_properties, image))
+            }
+        };
+
+        let depth_memory = memory(depth_image, depth_lifetime);
+        let depth_view = create_view(
+            device,
+            depth_image,
+            vk::Format::D16_UNORM,
+            vk::ImageAspectFlags::DEPTH,
+        );
+        let msaa_color = msaa_image.map(|image| {
+            AAAMsaaColor::with_image(device, image, memory(image, msaa_lifetime), color_format)
+        });
+        let attachments = framebuffer_attachments(color_view, depth_view, msaa_color.as_ref());
+        let framebuffer_create_info = vk::FramebufferCreateInfo::default()
+            .render_pass(renderpass)
+            .attachments(&attachments)
+            .width(extent.width)
+            .height(extent.height)
+            .layers(1);
+        let framebuffer = unsafe {
+            device
+                .ash
+                .create_framebuffer(&framebuffer_create_info, None)
+                .unwrap()
+        };
+        crate::object_audit::created(framebuffer, "offscreen target");
+
+        Self {
+            color_image,
+            color_memory,
+            color_view,
+            depth_image,
+            depth_memory,
+            depth_view,
+            msaa_color,
+            transient,
+            framebuffer,
+            extent,
+        }
+    }
+
+    /// The renderpass expects the depth attachment ready, record before beginning it.
+    pub fn record_depth_transition(&self, device: &AAADevice, command_buffer: vk::CommandBuffer) {
+        let barrier = vk::ImageMemoryBarrier::default()
+            .image(self.depth_image)
+            .dst_access_mask(
+                vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                    | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            )
+            .old_layout(vk::ImageLayout::UNDEFINED)
+            .new_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+            .subresource_range(
+                vk::ImageSubresourceRange::default()
+                    .aspect_mask(vk::ImageAspectFlags::DEPTH)
+                    .layer_count(1)
+                    .level_count(1),
+            );
+        unsafe {
+            device.ash.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[barrier],
+            );
+        }
+    }
+
+    /// The commands using the target must have completed, and the images bound in
+    /// [`Self::transient`] after the target's been destroyed.
+    pub fn destroy(&self, device: &AAADevice) {
+        unsafe {
+            crate::object_audit::destroyed(self.framebuffer);
+            device.ash.destroy_framebuffer(self.framebuffer, None);
+        }
+        if let Some(msaa_color) = &self.msaa_color {
+            msaa_color.destroy(device);
+        }
+        unsafe {
+            crate::object_audit::destroyed(self.depth_view);
+            device.ash.destroy_image_view(self.depth_view, None);
+            crate::object_audit::destroyed(self.depth_image);
+            device.ash.destroy_image(self.depth_image, None);
+        }
+        self.depth_memory.free(device);
+        unsafe {
+            crate::object_audit::destroyed(self.color_view);
+            device.ash.destroy_image_view(self.color_view, None);
+            crate::object_audit::destroyed(self.color_image);
+            device.ash.destroy_image(self.color_image, None);
+            crate::object_audit::destroyed(self.color_memory);
+            device.ash.free_memory(self.color_memory, None);
+        }
+        if let Some(transient) = &self.transient {
+            transient.destroy(device);
+        }
+    }
+}
+
+/// Multisampled color attachment the subpass draws into, resolved into the single sample image
+/// of the framebuffer at the end of the pass.
+pub struct AAAMsaaColor {
+    pub image: vk::Image,
+    pub memory: AttachmentMemory,
+    pub view: vk::ImageView,
+}
+
+impl AAAMsaaColor {
+    /// `None` for a single sample, the pass then draws straight into the framebuffer's image.
+    pub fn new(
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        format: vk::Format,
+        extent: vk::Extent2D,
+        samples: vk::SampleCountFlags,
+    ) -> Option<Self> {
+        let image = Self::create_unbound(device, format, extent, samples)?;
+        let memory = bind_dedicated(device, device_memory_properties, image);
+        Some(Self::with_image(
+            device,
+            image,
+            AttachmentMemory::Dedicated(memory),
+            format,
+        ))
+    }
+
+    fn create_unbound(
+        device: &AAADevice,
+        format: vk::Format,
+        extent: vk::Extent2D,
+        samples: vk::SampleCountFlags,
+    ) -> Option<vk::Image> {
+        if samples == vk::SampleCountFlags::TYPE_1 {
+            return None;
+        }
+        // Never read outside of the pass, tiled GPUs may keep it in tile memory
+        Some(create_unbound_image(
+            device,
+            format,
+            extent,
+            samples,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT,
+        ))
+    }
+
+    /// `image` must be bound to `memory` already.
+    fn with_image(
+        device: &AAADevice,
+        image: vk::Image,
+        memory: AttachmentMemory,
+        format: vk::Format,
+    ) -> Self {
+        let view = create_view(device, image, format, vk::ImageAspectFlags::COLOR);
+        Self {
+            image,
+            memory,
+            view,
+        }
+    }
+
+    /// The commands drawing into it must have completed.
+    pub fn destroy(&self, device: &AAADevice) {
+        unsafe {
+            crate::object_audit::destroyed(self.view);
+            device.ash.destroy_image_view(self.view, None);
+            crate::object_audit::destroyed(self.image);
+            device.ash.destroy_image(self.image, None);
+        }
+        self.memory.free(device);
+    }
+}
+
+/// Attachments in the order of the main renderpass: the color drawn into, depth, then the
+/// resolve target with MSAA.
+pub fn framebuffer_attachments(
+    color_view: vk::ImageView,
+    depth_view: vk::ImageView,
+    msaa_color: Option<&AAAMsaaColor>,
+) -> Vec<vk::ImageView> {
+    match msaa_color {
+        Some(msaa_color) => vec![msaa_color.view, depth_view, color_view],
+        None => vec![color_view, depth_view],
+    }
+}
+
+#[track_caller]
+pub fn create_image(
+    device: &AAADevice,
+    device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    format: vk::Format,
+    extent: vk::Extent2D,
+    samples: vk::SampleCountFlags,
+    usage: vk::ImageUsageFlags,
+) -> (vk::Image, vk::DeviceMemory) {
+    let image = create_unbound_image(device, format, extent, samples, usage);
+    let memory = bind_dedicated(device, device_memory_properties, image);
+    (image, memory)
+}
+
+/// Image without memory yet, for [`bind_dedicated`] or an [`AAATransientMemory`].
+#[track_caller]
+pub fn create_unbound_image(
+    device: &AAADevice,
+    format: vk::Format,
+    extent: vk::Extent2D,
+    samples: vk::SampleCountFlags,
+    usage: vk::ImageUsageFlags,
+) -> vk::Image {
+    let image_create_info = vk::ImageCreateInfo::default()
+        .image_type(vk::ImageType::TYPE_2D)
+        .format(format)
+        .extent(extent.into())
+        .mip_levels(1)
+        .array_layers(1)
+        .samples(samples)
+        .tiling(vk::ImageTiling::OPTIMAL)
+        .usage(usage)
+        .sharing_mode(vk::SharingMode::EXCLUSIVE);
+    let image = unsafe { device.ash.create_image(&image_create_info, None).unwrap() };
+    crate::object_audit::created(image, "offscreen attachment");
+    image
+}
+
+/// Allocate device local memory for `image` alone and bind it.
+#[track_caller]
+pub fn bind_dedicated(
+    device: &AAADevice,
+    device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    image: vk::Image,
+) -> vk::DeviceMemory {
+    let memory_req = unsafe { device.ash.get_image_memory_requirements(image) };
+    let memory_index = find_memorytype_index(
+        &memory_req,
+        device_memory_properties,
+        vk::MemoryPropertyFlags::DEVICE_LOCAL,
+    )
+    .expect("Unable to find suitable memory index for offscreen image.");
+    let allocate_info = vk::MemoryAllocateInfo::default()
+        .allocation_size(memory_req.size)
+        .memory_type_index(memory_index);
+    let memory = unsafe { device.ash.allocate_memory(&allocate_info, None).unwrap() };
+    crate::object_audit::created(memory, "offscreen attachment");
+    unsafe {
+        device
+            .ash
+            .bind_image_memory(image, memory, 0)
+            .expect("Unable to bind offscreen image memory")
+    };
+    memory
+}
+
+#[track_caller]
+pub fn create_view(
+    device: &AAADevice,
+    image: vk::Image,
+    format: vk::Format,
+    aspect_mask: vk::ImageAspectFlags,
+) -> vk::ImageView {
+    let view_info = vk::ImageViewCreateInfo::default()
+        .view_type(vk::ImageViewType::TYPE_2D)
+        .format(format)
+        .subresource_range(
+            vk::ImageSubresourceRange::default()
+                .aspect_mask(aspect_mask)
+                .level_count(1)
+                .layer_count(1),
+        )
+        .image(image);
+    let view = unsafe { device.ash.create_image_view(&view_info, None).unwrap() };
+    crate::object_audit::created(view, "offscreen attachment");
+    view
+}
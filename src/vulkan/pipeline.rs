@@ -1,44 +1,217 @@
-use super::{device::AAADevice, surface::AAASurface};
-use crate::{model::Vertex, shaders::Shader};
+use super::{descriptor_set::SET_COUNT, device::AAADevice};
+use crate::{
+    debug_lines::DEBUG_LINE_WIDTH,
+    error::PulsarError,
+    instancing::InstanceBuffer,
+    material::{BlendMode, DepthBias, Material, PipelineOptions},
+    model::Vertex,
+    shader_reflect::ShaderInterface,
+    shaders::Shader,
+};
 use ash::vk;
-use glam::Mat4;
-use std::mem;
+use glam::{Mat4, Vec4};
+use log::info;
+use std::{collections::HashMap, mem, time::Duration};
 
-fn create_pipeline_layout(
-    device: &AAADevice,
-    desc_set_layouts: [vk::DescriptorSetLayout; 1],
-) -> vk::PipelineLayout {
-    let push_constant_range = vk::PushConstantRange {
+/// Pushed before every draw, the transforms to the vertex stage and the tint to the fragment
+/// stage, see [`DRAW_PUSH_CONSTANTS`].
+#[repr(C)]
+pub(crate) struct PushConstants {
+    pub pvm: Mat4,
+    /// First three columns of the inverse transpose of the model matrix for the normals, zero for
+    /// unlit draws. Their last row is free, see [`PushConstants::with_texture_layer`].
+    pub normal: [Vec4; 3],
+    /// Multiplies the color of every fragment, white leaves it as it is.
+    pub tint: Vec4,
+}
+
+/// Bytes of [`PushConstants`] read by the vertex stage, the tint follows them.
+const VERTEX_PUSH_CONSTANTS_SIZE: u32 = mem::offset_of!(PushConstants, tint) as u32;
+
+impl PushConstants {
+    /// Shaded by the directional light, `transform` places the mesh in the world.
+    pub fn lit(projection_view: Mat4, transform: Mat4) -> Self {
+        let normal = transform.inverse().transpose();
+        let normal = if normal.is_finite() {
+            normal
+        } else {
+            transform
+        };
+        Self {
+            pvm: projection_view * transform,
+            normal: [normal.x_axis, normal.y_axis, normal.z_axis],
+            tint: Vec4::ONE,
+        }
+    }
+
+    /// Drawn with its vertex colors as they are, UI and overlays.
+    pub fn unlit(pvm: Mat4) -> Self {
+        Self {
+            pvm,
+            normal: [Vec4::ZERO; 3],
+            tint: Vec4::ONE,
+        }
+    }
+
+    /// Sample `layer` of the texture array, in the last row of `normal` which the shaders drop
+    /// with `mat3(normal)`. Keeps the constants within the 128 bytes every device supports.
+    pub fn with_texture_layer(mut self, layer: u32) -> Self {
+        self.normal[2].w = layer as f32;
+        self
+    }
+
+    pub fn with_tint(mut self, tint: [f32; 4]) -> Self {
+        self.tint = Vec4::from_array(tint);
+        self
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            std::slice::from_raw_parts(self as *const Self as *const u8, mem::size_of::<Self>())
+        }
+    }
+
+    /// Push both ranges of [`DRAW_PUSH_CONSTANTS`] for the draws recorded next with `layout`.
+    pub fn push(
+        &self,
+        device: &AAADevice,
+        command_buffer: vk::CommandBuffer,
+        layout: vk::PipelineLayout,
+    ) {
+        let (vertex, fragment) = self
+            .as_bytes()
+            .split_at(VERTEX_PUSH_CONSTANTS_SIZE as usize);
+        unsafe {
+            device.ash.cmd_push_constants(
+                command_buffer,
+                layout,
+                vk::ShaderStageFlags::VERTEX,
+                0,
+                vertex,
+            );
+            device.ash.cmd_push_constants(
+                command_buffer,
+                layout,
+                vk::ShaderStageFlags::FRAGMENT,
+                VERTEX_PUSH_CONSTANTS_SIZE,
+                fragment,
+            );
+        }
+    }
+}
+
+/// Push constants of every draw, the transforms of a [`PushConstants`] to the vertex stage then
+/// its tint to the fragment stage.
+pub(crate) const DRAW_PUSH_CONSTANTS: [vk::PushConstantRange; 2] = [
+    vk::PushConstantRange {
         stage_flags: vk::ShaderStageFlags::VERTEX,
         offset: 0,
-        size: std::mem::size_of::<Mat4>() as u32,
-    };
+        size: VERTEX_PUSH_CONSTANTS_SIZE,
+    },
+    vk::PushConstantRange {
+        stage_flags: vk::ShaderStageFlags::FRAGMENT,
+        offset: VERTEX_PUSH_CONSTANTS_SIZE,
+        size: mem::size_of::<PushConstants>() as u32 - VERTEX_PUSH_CONSTANTS_SIZE,
+    },
+];
 
-    let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo {
-        set_layout_count: desc_set_layouts.len() as u32,
-        p_set_layouts: desc_set_layouts.as_ptr(),
-        push_constant_range_count: 1,
-        p_push_constant_ranges: &push_constant_range,
-        ..Default::default()
-    };
+/// Shared by every pipeline, with a layout per set of the binding model in `descriptor_set` and
+/// the push constants of `interface`.
+fn create_pipeline_layout(
+    device: &AAADevice,
+    desc_set_layouts: &[vk::DescriptorSetLayout],
+    interface: &ShaderInterface,
+) -> vk::PipelineLayout {
+    debug_assert_eq!(desc_set_layouts.len(), SET_COUNT);
+    let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo::default()
+        .set_layouts(desc_set_layouts)
+        .push_constant_ranges(&interface.push_constants);
 
-    unsafe {
+    let layout = unsafe {
         device
             .ash
             .create_pipeline_layout(&pipeline_layout_create_info, None)
             .expect("Failed to create pipeline layout!")
+    };
+    crate::object_audit::created(layout, "shared pipeline layout");
+    layout
+}
+
+/// Default material pipeline of a projection mesh without a material, built once per
+/// [`PipelineOptions`] the meshes ask for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MeshPipeline {
+    Triangles,
+    /// Instance transforms applied by the vertex shader first.
+    Instanced,
+    /// Joint matrices blended first, read from a storage buffer.
+    Skinned,
+    /// Joint matrices read from a uniform buffer, see [`crate::skinning::PaletteBuffer`].
+    SkinnedUniform,
+    /// Blended by alpha, see `RegisteredMesh::blend`.
+    Blended,
+}
+
+impl MeshPipeline {
+    pub const ALL: [Self; 5] = [
+        Self::Triangles,
+        Self::Instanced,
+        Self::Skinned,
+        Self::SkinnedUniform,
+        Self::Blended,
+    ];
+
+    /// Vertex shader, every variant shares the default fragment shader.
+    fn vertex_shader(self) -> &'static str {
+        match self {
+            Self::Triangles | Self::Blended => "vert",
+            Self::Instanced => "instanced",
+            Self::Skinned => "skinned",
+            Self::SkinnedUniform => "skinned_uniform",
+        }
+    }
+
+    fn kind(self) -> PipelineKind {
+        match self {
+            Self::Triangles | Self::Blended => PipelineKind::Triangles,
+            Self::Instanced => PipelineKind::Instanced,
+            Self::Skinned | Self::SkinnedUniform => PipelineKind::Skinned,
+        }
+    }
+
+    /// `options` of the mesh, blended by alpha for the `Blended` variant, never drawing patches.
+    pub fn options(self, options: PipelineOptions) -> PipelineOptions {
+        // The default material has no tessellation stages to draw patches with
+        let options = PipelineOptions {
+            patch_control_points: 0,
+            ..options
+        };
+        match self {
+            Self::Blended => PipelineOptions {
+                blend: BlendMode::Alpha,
+                ..options
+            },
+            _ => options,
+        }
     }
 }
 
+/// Pipelines of the projection meshes without a material, by variant and options.
+pub type MeshPipelines = HashMap<(MeshPipeline, PipelineOptions), vk::Pipeline>;
+
+/// The unculled pipeline overlays and the 2D world draw with, the UI's ignoring depth, the debug
+/// lines' and every mesh variant built with `options`.
+type DefaultPipelines = (vk::Pipeline, vk::Pipeline, vk::Pipeline, MeshPipelines);
+
 pub fn create_pipeline(
     device: &AAADevice,
-    surface: &AAASurface,
     renderpass: vk::RenderPass,
-    desc_set_layouts: [vk::DescriptorSetLayout; 1],
+    samples: vk::SampleCountFlags,
+    desc_set_layouts: &[vk::DescriptorSetLayout],
+    interface: &ShaderInterface,
+    options: PipelineOptions,
 ) -> (
-    vk::Pipeline,
-    [vk::Viewport; 1],
-    [vk::Rect2D; 1],
+    DefaultPipelines,
     Vec<vk::Pipeline>,
     vk::PipelineLayout,
     vk::ShaderModule,
@@ -52,14 +225,365 @@ pub fn create_pipeline(
         frag_shader.pipeline_shader_stage_create_info,
     ];
 
-    let pipeline_layout = create_pipeline_layout(&device, desc_set_layouts);
+    let pipeline_layout = create_pipeline_layout(device, desc_set_layouts, interface);
+
+    let mut phases = Vec::new();
+    // Grid lines, gizmo handles and the UI are seen from either side. The base every other
+    // pipeline of the window derives from, the mesh variants and the materials alike
+    let (graphic_pipeline, elapsed) = create_graphics_pipeline(
+        device,
+        renderpass,
+        pipeline_layout,
+        &shader_stage_create_infos,
+        samples,
+        PipelineKind::Triangles,
+        PipelineOptions::unculled(),
+        DepthBiasState::Off,
+        None,
+    );
+    phases.push(("base", elapsed));
+    // Drawn in order over the scene, the covers never fight with what is in front of them
+    let (ui_pipeline, elapsed) = create_graphics_pipeline(
+        device,
+        renderpass,
+        pipeline_layout,
+        &shader_stage_create_infos,
+        samples,
+        PipelineKind::Triangles,
+        PipelineOptions::ui(),
+        DepthBiasState::Off,
+        Some(graphic_pipeline),
+    );
+    phases.push(("derivative", elapsed));
+    let line_width = DEBUG_LINE_WIDTH.clamp(device.line_width_range[0], device.line_width_range[1]);
+    let (line_pipeline, elapsed) = create_graphics_pipeline(
+        device,
+        renderpass,
+        pipeline_layout,
+        &shader_stage_create_infos,
+        samples,
+        PipelineKind::Lines { width: line_width },
+        PipelineOptions::unculled(),
+        DepthBiasState::Off,
+        Some(graphic_pipeline),
+    );
+    phases.push(("derivative", elapsed));
+    let mesh_pipelines = MeshPipeline::ALL
+        .into_iter()
+        .map(|variant| {
+            let (pipeline, elapsed) = create_mesh_pipeline(
+                device,
+                renderpass,
+                pipeline_layout,
+                samples,
+                variant,
+                options,
+                graphic_pipeline,
+            );
+            phases.push(("derivative", elapsed));
+            ((variant, options), pipeline)
+        })
+        .collect();
+    let graphics_pipelines = vec![graphic_pipeline, ui_pipeline, line_pipeline];
+    log_derivatives(&phases);
+
+    (
+        (graphic_pipeline, ui_pipeline, line_pipeline, mesh_pipelines),
+        graphics_pipelines,
+        pipeline_layout,
+        vertex_shader.module,
+        frag_shader.module,
+    )
+}
+
+/// How long the driver took to create the base pipeline against its derivatives, as `"base"` and
+/// `"derivative"` phases. A driver taking advantage of the derivatives builds them
+/// faster, the others as fast.
+fn log_derivatives(phases: &[(&'static str, Duration)]) {
+    let total = |label| -> (Duration, u32) {
+        phases
+            .iter()
+            .filter(|(phase, _)| *phase == label)
+            .fold((Duration::ZERO, 0), |(sum, count), (_, elapsed)| {
+                (sum + *elapsed, count + 1)
+            })
+    };
+    let ((base, _), (derivatives, count)) = (total("base"), total("derivative"));
+    info!(
+        "Base pipeline built in {base:?}, its {count} derivatives in {:?} each",
+        derivatives.checked_div(count).unwrap_or_default()
+    );
+}
+
+/// Build `variant` rasterized with `options` as a derivative of `base`, the shader modules only
+/// live for the creation. Returns how long `vkCreateGraphicsPipelines` took with it.
+pub fn create_mesh_pipeline(
+    device: &AAADevice,
+    renderpass: vk::RenderPass,
+    pipeline_layout: vk::PipelineLayout,
+    samples: vk::SampleCountFlags,
+    variant: MeshPipeline,
+    options: PipelineOptions,
+    base: vk::Pipeline,
+) -> (vk::Pipeline, Duration) {
+    let vertex_shader = Shader::from_filename(
+        variant.vertex_shader(),
+        vk::ShaderStageFlags::VERTEX,
+        device,
+    );
+    let frag_shader = Shader::from_filename("frag", vk::ShaderStageFlags::FRAGMENT, device);
+    let (pipeline, elapsed) = create_graphics_pipeline(
+        device,
+        renderpass,
+        pipeline_layout,
+        &[
+            vertex_shader.pipeline_shader_stage_create_info,
+            frag_shader.pipeline_shader_stage_create_info,
+        ],
+        samples,
+        variant.kind(),
+        variant.options(options),
+        DepthBiasState::Dynamic,
+        Some(base),
+    );
+    unsafe {
+        crate::object_audit::destroyed(vertex_shader.module);
+        device.ash.destroy_shader_module(vertex_shader.module, None);
+        crate::object_audit::destroyed(frag_shader.module);
+        device.ash.destroy_shader_module(frag_shader.module, None);
+    }
+    (pipeline, elapsed)
+}
+
+/// Whether `device` can build the optional stages of `material`, and its patches match them.
+fn check_optional_stages(device: &AAADevice, material: &Material) -> Result<(), PulsarError> {
+    if let Some(shader) = &material.geometry_shader {
+        if !device.geometry_shader {
+            return Err(PulsarError::GeometryShaderUnsupported {
+                shader: shader.clone(),
+            });
+        }
+    }
+    let patch_control_points = material.options.patch_control_points;
+    let (shader, reason) = match &material.tessellation {
+        None if patch_control_points == 0 => return Ok(()),
+        None => (
+            &material.vertex_shader,
+            format!("patches of {patch_control_points} vertices without tessellation shaders"),
+        ),
+        Some(_) if !device.tessellation_shader => (
+            &material.vertex_shader,
+            "needs the tessellationShader feature, which the device lacks".to_string(),
+        ),
+        Some(tessellation) if patch_control_points == 0 => (
+            &tessellation.control,
+            "draws no patches, set PipelineOptions::patch_control_points".to_string(),
+        ),
+        Some(tessellation) if patch_control_points > device.max_tessellation_patch_size => (
+            &tessellation.control,
+            format!(
+                "patches of {patch_control_points} vertices, the device takes at most {}",
+                device.max_tessellation_patch_size
+            ),
+        ),
+        Some(_) => return Ok(()),
+    };
+    Err(PulsarError::Tessellation {
+        shader: shader.clone(),
+        reason,
+    })
+}
+
+/// Build the pipeline of `material` as a derivative of `base`, the shader modules only live for
+/// the creation. Safe to call from any thread sharing the device, `base` must outlive the call.
+/// A geometry or tessellation stage on a device without its feature fails,
+/// [`Material::without_optional_stages`] still builds. Returns how long `vkCreateGraphicsPipelines`
+/// took with it.
+pub fn create_pipeline_for_key(
+    device: &AAADevice,
+    renderpass: vk::RenderPass,
+    pipeline_layout: vk::PipelineLayout,
+    material: &Material,
+    samples: vk::SampleCountFlags,
+    base: vk::Pipeline,
+) -> Result<(vk::Pipeline, Duration), PulsarError> {
+    check_optional_stages(device, material)?;
+    let tessellation_shaders = material.tessellation.as_ref().map(|tessellation| {
+        [
+            (
+                &tessellation.control,
+                vk::ShaderStageFlags::TESSELLATION_CONTROL,
+            ),
+            (
+                &tessellation.evaluation,
+                vk::ShaderStageFlags::TESSELLATION_EVALUATION,
+            ),
+        ]
+    });
+    // In pipeline order
+    let shaders: Vec<Shader> = [(&material.vertex_shader, vk::ShaderStageFlags::VERTEX)]
+        .into_iter()
+        .chain(tessellation_shaders.into_iter().flatten())
+        .chain(
+            material
+                .geometry_shader
+                .iter()
+                .map(|shader| (shader, vk::ShaderStageFlags::GEOMETRY)),
+        )
+        .chain([(&material.fragment_shader, vk::ShaderStageFlags::FRAGMENT)])
+        .map(|(shader, stage)| Shader::from_filename(shader, stage, device))
+        .collect();
+    let map_entries: Vec<_> = material
+        .specialization
+        .iter()
+        .enumerate()
+        .map(|(index, &(constant_id, _))| vk::SpecializationMapEntry {
+            constant_id,
+            offset: (index * mem::size_of::<u32>()) as u32,
+            size: mem::size_of::<u32>(),
+        })
+        .collect();
+    let data: Vec<u8> = material
+        .specialization
+        .iter()
+        .flat_map(|&(_, value)| value.to_ne_bytes())
+        .collect();
+    let specialization_info = vk::SpecializationInfo::default()
+        .map_entries(&map_entries)
+        .data(&data);
+    let stages: Vec<_> = shaders
+        .iter()
+        .map(|shader| {
+            let stage = shader.pipeline_shader_stage_create_info;
+            if map_entries.is_empty() {
+                stage
+            } else {
+                stage.specialization_info(&specialization_info)
+            }
+        })
+        .collect();
+    let (pipeline, elapsed) = create_graphics_pipeline(
+        device,
+        renderpass,
+        pipeline_layout,
+        &stages,
+        samples,
+        PipelineKind::Triangles,
+        material.options,
+        material
+            .depth_bias
+            .map_or(DepthBiasState::Off, DepthBiasState::Fixed),
+        Some(base),
+    );
+    for shader in shaders {
+        unsafe {
+            crate::object_audit::destroyed(shader.module);
+            device.ash.destroy_shader_module(shader.module, None);
+        }
+    }
+    Ok((pipeline, elapsed))
+}
 
-    let vertex_input_binding_descriptions = [vk::VertexInputBindingDescription {
+/// Fullscreen triangle of a background, from `stages` drawing without vertex buffers nor depth.
+/// Fails rather than panics, a broken background shader keeps the previous one. A reloaded shader
+/// derives from the pipeline the shader was set with, its `base`. Returns how long
+/// `vkCreateGraphicsPipelines` took with it.
+pub fn create_background_pipeline(
+    device: &AAADevice,
+    renderpass: vk::RenderPass,
+    pipeline_layout: vk::PipelineLayout,
+    stages: &[vk::PipelineShaderStageCreateInfo],
+    samples: vk::SampleCountFlags,
+    base: Option<vk::Pipeline>,
+) -> Result<(vk::Pipeline, Duration), vk::Result> {
+    try_create_graphics_pipeline(
+        device,
+        renderpass,
+        pipeline_layout,
+        stages,
+        samples,
+        PipelineKind::Background,
+        PipelineOptions::unculled(),
+        DepthBiasState::Off,
+        base,
+    )
+}
+
+/// What a pipeline built by [`create_graphics_pipeline`] draws.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PipelineKind {
+    Triangles,
+    /// Triangles reading a `Mat4` per instance from binding 1, as four vec4 columns at locations 5 to 8.
+    Instanced,
+    /// Triangles reading the vertex joints and weights at locations 5 and 6, the joint matrices
+    /// from the object set.
+    Skinned,
+    /// Pairs of vertices as segments, `width` within the device's line width range.
+    Lines {
+        width: f32,
+    },
+    /// A triangle generated by the vertex shader, behind the scene without touching the depth.
+    Background,
+}
+
+/// Rasterizer depth bias of a pipeline built by [`create_graphics_pipeline`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DepthBiasState {
+    Off,
+    /// Baked in the pipeline, a material's [`DepthBias`].
+    Fixed(DepthBias),
+    /// Set with `cmd_set_depth_bias` before each draw, a mesh's `RegisteredMesh::depth_bias`.
+    Dynamic,
+}
+
+/// Fixed function state shared by every pipeline drawing `Vertex` meshes, a derivative of `base`
+/// when there is one, with the time `vkCreateGraphicsPipelines` took alone.
+#[allow(clippy::too_many_arguments)]
+#[track_caller]
+fn create_graphics_pipeline(
+    device: &AAADevice,
+    renderpass: vk::RenderPass,
+    pipeline_layout: vk::PipelineLayout,
+    shader_stage_create_infos: &[vk::PipelineShaderStageCreateInfo],
+    samples: vk::SampleCountFlags,
+    kind: PipelineKind,
+    options: PipelineOptions,
+    depth_bias: DepthBiasState,
+    base: Option<vk::Pipeline>,
+) -> (vk::Pipeline, Duration) {
+    try_create_graphics_pipeline(
+        device,
+        renderpass,
+        pipeline_layout,
+        shader_stage_create_infos,
+        samples,
+        kind,
+        options,
+        depth_bias,
+        base,
+    )
+    .expect("Unable to create graphics pipeline")
+}
+
+#[allow(clippy::too_many_arguments)]
+#[track_caller]
+fn try_create_graphics_pipeline(
+    device: &AAADevice,
+    renderpass: vk::RenderPass,
+    pipeline_layout: vk::PipelineLayout,
+    shader_stage_create_infos: &[vk::PipelineShaderStageCreateInfo],
+    samples: vk::SampleCountFlags,
+    kind: PipelineKind,
+    options: PipelineOptions,
+    depth_bias_state: DepthBiasState,
+    base: Option<vk::Pipeline>,
+) -> Result<(vk::Pipeline, Duration), vk::Result> {
+    let mut vertex_input_binding_descriptions = vec![vk::VertexInputBindingDescription {
         binding: 0,
         stride: mem::size_of::<Vertex>() as u32,
         input_rate: vk::VertexInputRate::VERTEX,
     }];
-    let vertex_input_attribute_descriptions = [
+    let mut vertex_input_attribute_descriptions = vec![
         vk::VertexInputAttributeDescription {
             location: 0,
             binding: 0,
@@ -78,73 +602,158 @@ pub fn create_pipeline(
             format: vk::Format::R32G32B32A32_SFLOAT,
             offset: mem::offset_of!(Vertex, color) as u32,
         },
+        vk::VertexInputAttributeDescription {
+            location: 3,
+            binding: 0,
+            format: vk::Format::R32G32B32_SFLOAT,
+            offset: mem::offset_of!(Vertex, normal) as u32,
+        },
+        vk::VertexInputAttributeDescription {
+            location: 4,
+            binding: 0,
+            format: vk::Format::R32G32B32A32_SFLOAT,
+            offset: mem::offset_of!(Vertex, tangent) as u32,
+        },
     ];
+    if kind == PipelineKind::Instanced {
+        vertex_input_binding_descriptions.push(vk::VertexInputBindingDescription {
+            binding: 1,
+            stride: InstanceBuffer::STRIDE,
+            input_rate: vk::VertexInputRate::INSTANCE,
+        });
+        vertex_input_attribute_descriptions.extend((0..4).map(|column| {
+            vk::VertexInputAttributeDescription {
+                location: 5 + column,
+                binding: 1,
+                format: vk::Format::R32G32B32A32_SFLOAT,
+                offset: column * mem::size_of::<[f32; 4]>() as u32,
+            }
+        }));
+    }
+
+    if kind == PipelineKind::Skinned {
+        vertex_input_attribute_descriptions.extend([
+            vk::VertexInputAttributeDescription {
+                location: 5,
+                binding: 0,
+                format: vk::Format::R16G16B16A16_UINT,
+                offset: mem::offset_of!(Vertex, joints) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                location: 6,
+                binding: 0,
+                format: vk::Format::R32G32B32A32_SFLOAT,
+                offset: mem::offset_of!(Vertex, weights) as u32,
+            },
+        ]);
+    }
+
+    if kind == PipelineKind::Background {
+        vertex_input_binding_descriptions.clear();
+        vertex_input_attribute_descriptions.clear();
+    }
 
     let vertex_input_state_info = vk::PipelineVertexInputStateCreateInfo::default()
         .vertex_attribute_descriptions(&vertex_input_attribute_descriptions)
         .vertex_binding_descriptions(&vertex_input_binding_descriptions);
+    let (topology, line_width) = match kind {
+        PipelineKind::Lines { width } => (vk::PrimitiveTopology::LINE_LIST, width),
+        _ if options.patch_control_points > 0 => (vk::PrimitiveTopology::PATCH_LIST, 1.0),
+        _ => (options.topology, 1.0),
+    };
     let vertex_input_assembly_state_info = vk::PipelineInputAssemblyStateCreateInfo {
-        topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+        topology,
         ..Default::default()
     };
 
-    let viewports = [vk::Viewport {
-        x: 0.0,
-        y: 0.0,
-        width: surface.capabilities.current_extent.width as f32,
-        height: surface.capabilities.current_extent.height as f32,
-        min_depth: 0.0,
-        max_depth: 1.0,
-    }];
-    let scissors = [surface.capabilities.current_extent.into()];
+    let tessellation_state_info = vk::PipelineTessellationStateCreateInfo::default()
+        .patch_control_points(options.patch_control_points);
+
+    // Viewport and scissor are dynamic, set with every command buffer from a `ViewportState`
     let viewport_state_info = vk::PipelineViewportStateCreateInfo::default()
-        .scissors(&scissors)
-        .viewports(&viewports);
+        .viewport_count(1)
+        .scissor_count(1);
 
-    let rasterization_info = vk::PipelineRasterizationStateCreateInfo {
-        front_face: vk::FrontFace::COUNTER_CLOCKWISE,
-        line_width: 1.0,
-        polygon_mode: vk::PolygonMode::FILL,
-        ..Default::default()
+    // The dynamic bias is left at 0 until a draw sets it
+    let depth_bias = match depth_bias_state {
+        DepthBiasState::Fixed(depth_bias) => depth_bias,
+        DepthBiasState::Off | DepthBiasState::Dynamic => DepthBias::default(),
     };
-    let multisample_state_info = vk::PipelineMultisampleStateCreateInfo::default()
-        .rasterization_samples(vk::SampleCountFlags::TYPE_1);
-    let noop_stencil_state = vk::StencilOpState {
-        fail_op: vk::StencilOp::KEEP,
-        pass_op: vk::StencilOp::KEEP,
-        depth_fail_op: vk::StencilOp::KEEP,
-        compare_op: vk::CompareOp::ALWAYS,
-        ..Default::default()
+    let polygon_mode = if device.fill_mode_non_solid {
+        options.polygon_mode
+    } else {
+        vk::PolygonMode::FILL
     };
-    let depth_state_info = vk::PipelineDepthStencilStateCreateInfo {
-        depth_test_enable: 1,
-        depth_write_enable: 1,
-        depth_compare_op: vk::CompareOp::LESS_OR_EQUAL,
-        front: noop_stencil_state,
-        back: noop_stencil_state,
-        max_depth_bounds: 1.0,
+    let rasterization_info = vk::PipelineRasterizationStateCreateInfo {
+        cull_mode: options.cull_mode,
+        front_face: options.front_face,
+        line_width,
+        polygon_mode,
+        depth_bias_enable: (depth_bias_state != DepthBiasState::Off).into(),
+        depth_bias_constant_factor: depth_bias.constant_factor,
+        depth_bias_clamp: supported_clamp(device, depth_bias),
+        depth_bias_slope_factor: depth_bias.slope_factor,
         ..Default::default()
     };
-    let color_blend_attachment_states = [vk::PipelineColorBlendAttachmentState {
-        blend_enable: 0,
-        src_color_blend_factor: vk::BlendFactor::SRC_COLOR,
-        dst_color_blend_factor: vk::BlendFactor::ONE_MINUS_DST_COLOR,
-        color_blend_op: vk::BlendOp::ADD,
-        src_alpha_blend_factor: vk::BlendFactor::ZERO,
-        dst_alpha_blend_factor: vk::BlendFactor::ZERO,
-        alpha_blend_op: vk::BlendOp::ADD,
-        color_write_mask: vk::ColorComponentFlags::RGBA,
+    let multisample_state_info =
+        vk::PipelineMultisampleStateCreateInfo::default().rasterization_samples(samples);
+    let depth_options = match kind {
+        PipelineKind::Background => PipelineOptions {
+            depth_test: false,
+            ..options
+        },
+        _ => options,
+    };
+    let depth_state_info = depth_options.depth_stencil_state();
+    // Blended meshes draw back to front after the opaque ones, none writes the depth to hide those
+    // behind it, see `PipelineOptions::depth_stencil_state`
+    let color_blend_attachment_states = [match options.blend {
+        // The alpha accumulates as coverage, what a transparent window is composited with
+        BlendMode::Alpha => vk::PipelineColorBlendAttachmentState {
+            blend_enable: vk::TRUE,
+            src_color_blend_factor: vk::BlendFactor::SRC_ALPHA,
+            dst_color_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+            color_blend_op: vk::BlendOp::ADD,
+            src_alpha_blend_factor: vk::BlendFactor::ONE,
+            dst_alpha_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+            alpha_blend_op: vk::BlendOp::ADD,
+            color_write_mask: vk::ColorComponentFlags::RGBA,
+        },
+        // The alpha is left as it is, a glow adds no coverage
+        BlendMode::Additive => vk::PipelineColorBlendAttachmentState {
+            blend_enable: vk::TRUE,
+            src_color_blend_factor: vk::BlendFactor::SRC_ALPHA,
+            dst_color_blend_factor: vk::BlendFactor::ONE,
+            color_blend_op: vk::BlendOp::ADD,
+            src_alpha_blend_factor: vk::BlendFactor::ZERO,
+            dst_alpha_blend_factor: vk::BlendFactor::ONE,
+            alpha_blend_op: vk::BlendOp::ADD,
+            color_write_mask: vk::ColorComponentFlags::RGBA,
+        },
+        BlendMode::Opaque => vk::PipelineColorBlendAttachmentState {
+            blend_enable: 0,
+            src_color_blend_factor: vk::BlendFactor::SRC_COLOR,
+            dst_color_blend_factor: vk::BlendFactor::ONE_MINUS_DST_COLOR,
+            color_blend_op: vk::BlendOp::ADD,
+            src_alpha_blend_factor: vk::BlendFactor::ZERO,
+            dst_alpha_blend_factor: vk::BlendFactor::ZERO,
+            alpha_blend_op: vk::BlendOp::ADD,
+            color_write_mask: vk::ColorComponentFlags::RGBA,
+        },
     }];
     let color_blend_state = vk::PipelineColorBlendStateCreateInfo::default()
         .logic_op(vk::LogicOp::CLEAR)
         .attachments(&color_blend_attachment_states);
 
-    let dynamic_state = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+    let mut dynamic_state = vec![vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+    if depth_bias_state == DepthBiasState::Dynamic {
+        dynamic_state.push(vk::DynamicState::DEPTH_BIAS);
+    }
     let dynamic_state_info =
         vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_state);
 
-    let graphic_pipeline_info = vk::GraphicsPipelineCreateInfo::default()
-        .stages(&shader_stage_create_infos)
+    let mut graphic_pipeline_info = vk::GraphicsPipelineCreateInfo::default()
+        .stages(shader_stage_create_infos)
         .vertex_input_state(&vertex_input_state_info)
         .input_assembly_state(&vertex_input_assembly_state_info)
         .viewport_state(&viewport_state_info)
@@ -155,23 +764,62 @@ pub fn create_pipeline(
         .dynamic_state(&dynamic_state_info)
         .layout(pipeline_layout)
         .render_pass(renderpass);
+    if options.patch_control_points > 0 {
+        graphic_pipeline_info = graphic_pipeline_info.tessellation_state(&tessellation_state_info);
+    }
+    // Every pipeline may be the base of others, whose creation the driver may speed up from what
+    // it knows of theirs. Their owners keep each base alive as long as its derivatives
+    graphic_pipeline_info = match base {
+        Some(base) => graphic_pipeline_info
+            .flags(vk::PipelineCreateFlags::ALLOW_DERIVATIVES | vk::PipelineCreateFlags::DERIVATIVE)
+            .base_pipeline_handle(base)
+            .base_pipeline_index(-1),
+        None => graphic_pipeline_info.flags(vk::PipelineCreateFlags::ALLOW_DERIVATIVES),
+    };
 
-    let graphics_pipelines = unsafe {
+    // The shader modules and the state above are left out, the derivatives only speed this up
+    let mut phases = Vec::new();
+    let graphics_pipelines = crate::stopwatch!(phases, "create", unsafe {
         device
             .ash
-            .create_graphics_pipelines(vk::PipelineCache::null(), &[graphic_pipeline_info], None)
-            .expect("Unable to create graphics pipeline")
-    };
+            .create_graphics_pipelines(device.pipeline_cache.handle, &[graphic_pipeline_info], None)
+            .map_err(|(_, err)| err)?
+    });
+    crate::object_audit::created(graphics_pipelines[0], "graphics pipeline");
 
-    let graphic_pipeline = graphics_pipelines[0];
+    Ok((graphics_pipelines[0], phases[0].1))
+}
 
-    (
-        graphic_pipeline,
-        viewports,
-        scissors,
-        graphics_pipelines,
-        pipeline_layout,
-        vertex_shader.module,
-        frag_shader.module,
-    )
+/// `depth_bias`'s clamp, 0 on a device which cannot clamp.
+pub fn supported_clamp(device: &AAADevice, depth_bias: DepthBias) -> f32 {
+    if device.depth_bias_clamp {
+        depth_bias.clamp
+    } else {
+        0.0
+    }
+}
+
+/// Compute pipeline of `shader`'s compute stage, see `AAACompute`. The module may be destroyed
+/// once it returns.
+#[track_caller]
+pub fn create_compute_pipeline(
+    device: &AAADevice,
+    pipeline_layout: vk::PipelineLayout,
+    shader: &Shader,
+) -> vk::Pipeline {
+    debug_assert_eq!(
+        shader.pipeline_shader_stage_create_info.stage,
+        vk::ShaderStageFlags::COMPUTE
+    );
+    let compute_pipeline_info = vk::ComputePipelineCreateInfo::default()
+        .stage(shader.pipeline_shader_stage_create_info)
+        .layout(pipeline_layout);
+    let compute_pipelines = unsafe {
+        device
+            .ash
+            .create_compute_pipelines(device.pipeline_cache.handle, &[compute_pipeline_info], None)
+            .expect("Failed to create compute pipeline!")
+    };
+    crate::object_audit::created(compute_pipelines[0], "compute pipeline");
+    compute_pipelines[0]
 }
@@ -0,0 +1,101 @@
+use crate::pipeline_cache::{cache_path, PipelineCacheHeader};
+use ash::vk::{self, Handle};
+use log::{info, warn};
+use std::{fs, io, path::Path, path::PathBuf};
+
+/// Pipeline cache every pipeline of the device is built through, read from the previous run and
+/// written back when the device is destroyed.
+pub struct AAAPipelineCache {
+    pub handle: vk::PipelineCache,
+    path: PathBuf,
+    /// Bytes reused from the file, 0 when the pipelines compile from scratch.
+    loaded: usize,
+}
+
+impl AAAPipelineCache {
+    /// Seeded with the file of this device when its header matches, empty otherwise.
+    pub fn load(device: &ash::Device, properties: &vk::PhysicalDeviceProperties) -> Self {
+        let header = PipelineCacheHeader {
+            vendor_id: properties.vendor_id,
+            device_id: properties.device_id,
+            uuid: properties.pipeline_cache_uuid,
+        };
+        let path = cache_path(header.vendor_id, header.device_id);
+        let initial_data = match fs::read(&path) {
+            Ok(data) => match header.validate(&data) {
+                Ok(()) => data,
+                Err(err) => {
+                    warn!("Ignoring {}: {err}", path.display());
+                    Vec::new()
+                }
+            },
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                info!(
+                    "No pipeline cache at {}, pipelines compile from scratch",
+                    path.display()
+                );
+                Vec::new()
+            }
+            Err(err) => {
+                warn!("Failed to read {}: {err}", path.display());
+                Vec::new()
+            }
+        };
+        let create_info = vk::PipelineCacheCreateInfo::default().initial_data(&initial_data);
+        let (handle, loaded) = match unsafe { device.create_pipeline_cache(&create_info, None) } {
+            Ok(handle) => (handle, initial_data.len()),
+            Err(err) => {
+                warn!("The driver refused the pipeline cache ({err:?}), starting from empty");
+                let empty = vk::PipelineCacheCreateInfo::default();
+                // A null cache still builds pipelines, only without reuse
+                let handle = unsafe { device.create_pipeline_cache(&empty, None) };
+                (handle.unwrap_or_default(), 0)
+            }
+        };
+        crate::object_audit::created(handle, "pipeline cache");
+        if loaded > 0 {
+            info!(
+                "Pipeline cache hit: {loaded} bytes reused from {}",
+                path.display()
+            );
+        }
+        Self {
+            handle,
+            path,
+            loaded,
+        }
+    }
+
+    /// Write the cache for the next run, then destroy it. The pipelines built through it stay
+    /// valid.
+    pub fn save_and_destroy(&self, device: &ash::Device) {
+        if self.handle.is_null() {
+            return;
+        }
+        match unsafe { device.get_pipeline_cache_data(self.handle) } {
+            Ok(data) => match write_replacing(&self.path, &data, self.handle.as_raw()) {
+                Ok(()) => info!(
+                    "Pipeline cache saved: {} bytes to {}, {} reused at startup",
+                    data.len(),
+                    self.path.display(),
+                    self.loaded
+                ),
+                Err(err) => warn!("Failed to write {}: {err}", self.path.display()),
+            },
+            Err(err) => warn!("Failed to read back the pipeline cache: {err:?}"),
+        }
+        crate::object_audit::destroyed(self.handle);
+        unsafe { device.destroy_pipeline_cache(self.handle, None) };
+    }
+}
+
+/// Written aside then renamed over `path`, a crash or another window saving at the same time
+/// never leaves half a cache.
+fn write_replacing(path: &Path, data: &[u8], unique: u64) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let partial = path.with_extension(format!("{}_{unique:x}.partial", std::process::id()));
+    fs::write(&partial, data)?;
+    fs::rename(&partial, path)
+}
@@ -0,0 +1,102 @@
+use super::{
+    async_pipelines::AAAAsyncPipelines,
+    device::AAADevice,
+    pipeline::{create_mesh_pipeline, MeshPipelines},
+};
+use crate::material::MaterialKey;
+use ash::vk;
+use log::info;
+use std::{collections::HashMap, sync::Arc};
+
+/// Every pipeline the projection meshes draw with, by [`MaterialKey`], built the first time a
+/// mesh asks for it and kept until the window closes. The default material's variants are built
+/// on the render thread, the application's materials on the job pool, drawn with the fallback
+/// until theirs is ready. All share the one pipeline layout and derive from the base pipeline.
+pub struct PipelineRegistry {
+    device: Arc<AAADevice>,
+    renderpass: vk::RenderPass,
+    pipeline_layout: vk::PipelineLayout,
+    samples: vk::SampleCountFlags,
+    /// The default material's variants, those of the default options built at startup.
+    defaults: HashMap<MaterialKey, vk::Pipeline>,
+    /// The application's materials.
+    pub materials: AAAAsyncPipelines,
+}
+
+impl PipelineRegistry {
+    /// Registry deriving from `base`, the fallback of the materials, holding the `startup`
+    /// variants.
+    pub fn new(
+        device: Arc<AAADevice>,
+        renderpass: vk::RenderPass,
+        pipeline_layout: vk::PipelineLayout,
+        samples: vk::SampleCountFlags,
+        base: vk::Pipeline,
+        startup: MeshPipelines,
+    ) -> Self {
+        let defaults = startup
+            .into_iter()
+            .map(|((variant, options), pipeline)| {
+                (MaterialKey::Default(variant, options), pipeline)
+            })
+            .collect();
+        let materials =
+            AAAAsyncPipelines::new(device.clone(), renderpass, pipeline_layout, samples, base);
+        Self {
+            device,
+            renderpass,
+            pipeline_layout,
+            samples,
+            defaults,
+            materials,
+        }
+    }
+
+    /// Pipeline to draw `key` with this frame. A default variant is built now the first time,
+    /// a material is queued and drawn with [`Self::fallback`] until ready.
+    pub fn pipeline(&mut self, key: &MaterialKey) -> vk::Pipeline {
+        let (variant, options) = match key {
+            MaterialKey::Default(variant, options) => (*variant, *options),
+            MaterialKey::Material(material) => return self.materials.pipeline_for(material),
+        };
+        if let Some(&pipeline) = self.defaults.get(key) {
+            return pipeline;
+        }
+        let (pipeline, elapsed) = create_mesh_pipeline(
+            &self.device,
+            self.renderpass,
+            self.pipeline_layout,
+            self.samples,
+            variant,
+            options,
+            self.materials.fallback,
+        );
+        info!(
+            "Built the {variant:?} pipeline for {options:?} in {elapsed:?}, derived from the \
+             default pipeline"
+        );
+        self.defaults.insert(key.clone(), pipeline);
+        pipeline
+    }
+
+    /// Drawn in place of the materials still building.
+    pub fn fallback(&self) -> vk::Pipeline {
+        self.materials.fallback
+    }
+
+    /// Wait for the builds in flight then destroy every cached pipeline but the fallback, which
+    /// the window owns.
+    pub fn destroy(&mut self) {
+        self.materials.destroy();
+        for (_, pipeline) in self.defaults.drain() {
+            crate::object_audit::destroyed(pipeline);
+            unsafe { self.device.ash.destroy_pipeline(pipeline, None) };
+        }
+    }
+}
+
+impl Drop for PipelineRegistry {
+    fn drop(&mut self) {
+        self.destroy();
+    }
+}
@@ -0,0 +1,488 @@
+use super::{
+    device::AAADevice,
+    export::{AAAConversion, AAAExportConverter},
+    offscreen::{bind_dedicated, create_unbound_image, AttachmentMemory},
+    surface::AAASurface,
+    swapchain::AAASwapchain,
+    transient::AAATransientMemory,
+    views::find_memorytype_index,
+};
+use crate::{
+    color_space::{self, ExportColorSpace},
+    screenshot::{SCREENSHOT_EXPORT, SCREENSHOT_READBACK},
+    transient::Lifetime,
+};
+use ash::vk;
+use std::time::Instant;
+
+/// Downscaled copy of a presented image, blitted on the GPU and read back through a host visible buffer.
+/// Sources other than 8 bit sRGB, or exports other than [`ExportColorSpace::Srgb8`], are blitted
+/// in their own format then decoded by the export convert pass before the copy.
+pub struct AAAReadback {
+    pub image: vk::Image,
+    pub image_memory: AttachmentMemory,
+    pub buffer: vk::Buffer,
+    pub buffer_memory: vk::DeviceMemory,
+    pub buffer_size: vk::DeviceSize,
+    pub extent: vk::Extent2D,
+    pub source_extent: vk::Extent2D,
+    conversion: Option<AAAConversion>,
+    /// Whether an image is bound in memory the render before the blit used, see
+    /// [`crate::transient`].
+    aliased: bool,
+    /// Set when a copy was recorded and not read yet.
+    #[cfg_attr(not(feature = "replay"), allow(dead_code))]
+    pub captured_at: Option<Instant>,
+}
+
+impl AAAReadback {
+    pub fn new(
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        source_extent: vk::Extent2D,
+        source_format: vk::SurfaceFormatKHR,
+        downscale: u32,
+        converter: &AAAExportConverter,
+        color_space: ExportColorSpace,
+    ) -> Self {
+        let extent = vk::Extent2D {
+            width: (source_extent.width / downscale).max(1),
+            height: (source_extent.height / downscale).max(1),
+        };
+        Self::with_extent(
+            device,
+            device_memory_properties,
+            source_extent,
+            source_format,
+            extent,
+            converter,
+            color_space,
+        )
+    }
+
+    /// Readback scaling `source_extent` to any `extent`, the aspect ratio is not kept.
+    pub fn with_extent(
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        source_extent: vk::Extent2D,
+        source_format: vk::SurfaceFormatKHR,
+        extent: vk::Extent2D,
+        converter: &AAAExportConverter,
+        color_space: ExportColorSpace,
+    ) -> Self {
+        Self::build(
+            device,
+            device_memory_properties,
+            source_extent,
+            source_format,
+            extent,
+            converter,
+            color_space,
+            None,
+            &[],
+        )
+    }
+
+    /// Full size readback of a screenshot whose images are bound in `transient` when they fit,
+    /// over their `lifetimes` indexed as [`crate::screenshot::screenshot_passes`].
+    #[allow(clippy::too_many_arguments)]
+    pub fn aliased(
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        source_extent: vk::Extent2D,
+        source_format: vk::SurfaceFormatKHR,
+        converter: &AAAExportConverter,
+        color_space: ExportColorSpace,
+        transient: &mut AAATransientMemory,
+        lifetimes: &[Option<Lifetime>],
+    ) -> Self {
+        Self::build(
+            device,
+            device_memory_properties,
+            source_extent,
+            source_format,
+            source_extent,
+            converter,
+            color_space,
+            Some(transient),
+            lifetimes,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn build(
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        source_extent: vk::Extent2D,
+        source_format: vk::SurfaceFormatKHR,
+        extent: vk::Extent2D,
+        converter: &AAAExportConverter,
+        color_space: ExportColorSpace,
+        mut transient: Option<&mut AAATransientMemory>,
+        lifetimes: &[Option<Lifetime>],
+    ) -> Self {
+        let convert = color_space::needs_conversion(source_format, color_space);
+        // Blits convert between formats, keep the transfer function of the source. The convert
+        // pass decodes any other source from a blit in its own format.
+        let (format, usage) = match source_format.format {
+            _ if convert => (source_format.format, vk::ImageUsageFlags::SAMPLED),
+            vk::Format::B8G8R8A8_SRGB | vk::Format::R8G8B8A8_SRGB => {
+                (vk::Format::R8G8B8A8_SRGB, vk::ImageUsageFlags::TRANSFER_SRC)
+            }
+            _ => (
+                vk::Format::R8G8B8A8_UNORM,
+                vk::ImageUsageFlags::TRANSFER_SRC,
+            ),
+        };
+
+        let image = create_unbound_image(
+            device,
+            format,
+            extent,
+            vk::SampleCountFlags::TYPE_1,
+            vk::ImageUsageFlags::TRANSFER_DST | usage,
+        );
+        let lifetime = |attachment: usize| lifetimes.get(attachment).copied().flatten();
+        let image_memory = if transient
+            .as_deref_mut()
+            .zip(lifetime(SCREENSHOT_READBACK))
+            .is_some_and(|(transient, lifetime)| transient.bind(device, image, lifetime))
+        {
+            AttachmentMemory::Aliased
+        } else {
+            AttachmentMemory::Dedicated(bind_dedicated(device, device_memory_properties, image))
+        };
+
+        let buffer_size =
+            (extent.width * extent.height) as vk::DeviceSize * color_space.texel_size() as u64;
+        let buffer_info = vk::BufferCreateInfo {
+            size: buffer_size,
+            usage: vk::BufferUsageFlags::TRANSFER_DST,
+            sharing_mode: vk::SharingMode::EXCLUSIVE,
+            ..Default::default()
+        };
+        let buffer = unsafe { device.ash.create_buffer(&buffer_info, None).unwrap() };
+        crate::object_audit::created(buffer, "readback buffer");
+        let buffer_memory_req = unsafe { device.ash.get_buffer_memory_requirements(buffer) };
+        let buffer_memory_index = find_memorytype_index(
+            &buffer_memory_req,
+            device_memory_properties,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )
+        .expect("Unable to find suitable memorytype for the readback buffer.");
+        let buffer_allocate_info = vk::MemoryAllocateInfo {
+            allocation_size: buffer_memory_req.size,
+            memory_type_index: buffer_memory_index,
+            ..Default::default()
+        };
+        let buffer_memory = unsafe {
+            device
+                .ash
+                .allocate_memory(&buffer_allocate_info, None)
+                .unwrap()
+        };
+        crate::object_audit::created(buffer_memory, "readback buffer");
+        unsafe {
+            device
+                .ash
+                .bind_buffer_memory(buffer, buffer_memory, 0)
+                .unwrap()
+        };
+
+        let conversion = convert.then(|| {
+            AAAConversion::new(
+                device,
+                device_memory_properties,
+                converter,
+                image,
+                source_format,
+                extent,
+                color_space,
+                transient.zip(lifetime(SCREENSHOT_EXPORT)),
+            )
+        });
+        let aliased = image_memory == AttachmentMemory::Aliased
+            || conversion.as_ref().is_some_and(AAAConversion::is_aliased);
+
+        Self {
+            image,
+            image_memory,
+            buffer,
+            buffer_memory,
+            buffer_size,
+            extent,
+            source_extent,
+            conversion,
+            aliased,
+            captured_at: None,
+        }
+    }
+
+    /// Readback matching the current swapchain, if its images can be used as a transfer source.
+    #[cfg_attr(not(feature = "replay"), allow(dead_code))]
+    pub fn for_swapchain(
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        swapchain: &AAASwapchain,
+        surface: &AAASurface,
+        downscale: u32,
+        converter: &AAAExportConverter,
+    ) -> Option<Self> {
+        if !swapchain
+            .image_usage
+            .contains(vk::ImageUsageFlags::TRANSFER_SRC)
+        {
+            return None;
+        }
+        Some(Self::new(
+            device,
+            device_memory_properties,
+            swapchain.extent,
+            surface.format,
+            downscale,
+            converter,
+            ExportColorSpace::Srgb8,
+        ))
+    }
+
+    /// Record the downscale blit, the conversion if any, and the copy into the host buffer.
+    /// Must be recorded after the render pass, while `source` is in `PRESENT_SRC_KHR`.
+    pub fn record(&self, device: &AAADevice, command_buffer: vk::CommandBuffer, source: vk::Image) {
+        self.record_from(
+            device,
+            command_buffer,
+            source,
+            vk::ImageLayout::PRESENT_SRC_KHR,
+        );
+    }
+
+    /// [`Self::record`] from a `source` left in `source_layout`, either a presentable image written
+    /// by a render pass or a sampled texture written by a copy.
+    pub fn record_from(
+        &self,
+        device: &AAADevice,
+        command_buffer: vk::CommandBuffer,
+        source: vk::Image,
+        source_layout: vk::ImageLayout,
+    ) {
+        let (source_stage, source_access) = match source_layout {
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL => (
+                vk::PipelineStageFlags::TRANSFER | vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::AccessFlags::TRANSFER_WRITE,
+            ),
+            _ => (
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            ),
+        };
+        let color_range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            level_count: 1,
+            layer_count: 1,
+            ..Default::default()
+        };
+        let color_layers = vk::ImageSubresourceLayers {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            layer_count: 1,
+            ..Default::default()
+        };
+
+        let to_transfer = [
+            vk::ImageMemoryBarrier {
+                src_access_mask: source_access,
+                dst_access_mask: vk::AccessFlags::TRANSFER_READ,
+                old_layout: source_layout,
+                new_layout: vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                image: source,
+                subresource_range: color_range,
+                ..Default::default()
+            },
+            vk::ImageMemoryBarrier {
+                dst_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+                old_layout: vk::ImageLayout::UNDEFINED,
+                new_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                image: self.image,
+                subresource_range: color_range,
+                ..Default::default()
+            },
+        ];
+
+        let blit = vk::ImageBlit {
+            src_subresource: color_layers,
+            src_offsets: [
+                vk::Offset3D::default(),
+                vk::Offset3D {
+                    x: self.source_extent.width as i32,
+                    y: self.source_extent.height as i32,
+                    z: 1,
+                },
+            ],
+            dst_subresource: color_layers,
+            dst_offsets: [
+                vk::Offset3D::default(),
+                vk::Offset3D {
+                    x: self.extent.width as i32,
+                    y: self.extent.height as i32,
+                    z: 1,
+                },
+            ],
+        };
+
+        let blit_done = [
+            vk::ImageMemoryBarrier {
+                src_access_mask: vk::AccessFlags::TRANSFER_READ,
+                old_layout: vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                new_layout: source_layout,
+                image: source,
+                subresource_range: color_range,
+                ..Default::default()
+            },
+            // Sampled by the convert pass, or copied as is
+            match self.conversion {
+                Some(_) => vk::ImageMemoryBarrier {
+                    src_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+                    dst_access_mask: vk::AccessFlags::SHADER_READ,
+                    old_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    new_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    image: self.image,
+                    subresource_range: color_range,
+                    ..Default::default()
+                },
+                None => vk::ImageMemoryBarrier {
+                    src_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+                    dst_access_mask: vk::AccessFlags::TRANSFER_READ,
+                    old_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    new_layout: vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    image: self.image,
+                    subresource_range: color_range,
+                    ..Default::default()
+                },
+            },
+        ];
+
+        let copy_region = vk::BufferImageCopy::default()
+            .image_subresource(color_layers)
+            .image_extent(self.extent.into());
+
+        let host_read = vk::BufferMemoryBarrier {
+            src_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+            dst_access_mask: vk::AccessFlags::HOST_READ,
+            buffer: self.buffer,
+            size: vk::WHOLE_SIZE,
+            ..Default::default()
+        };
+
+        unsafe {
+            if self.aliased {
+                // The blit and the conversion write memory the render used for its depth or
+                // multisampled color
+                let reuse = vk::MemoryBarrier {
+                    src_access_mask: vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE
+                        | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                    dst_access_mask: vk::AccessFlags::TRANSFER_WRITE
+                        | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                    ..Default::default()
+                };
+                device.ash.cmd_pipeline_barrier(
+                    command_buffer,
+                    vk::PipelineStageFlags::LATE_FRAGMENT_TESTS
+                        | vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                    vk::PipelineStageFlags::TRANSFER
+                        | vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                    vk::DependencyFlags::empty(),
+                    &[reuse],
+                    &[],
+                    &[],
+                );
+            }
+            device.ash.cmd_pipeline_barrier(
+                command_buffer,
+                source_stage,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &to_transfer,
+            );
+            device.ash.cmd_blit_image(
+                command_buffer,
+                source,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                self.image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &[blit],
+                vk::Filter::LINEAR,
+            );
+            device.ash.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::TRANSFER
+                    | vk::PipelineStageFlags::FRAGMENT_SHADER
+                    | vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &blit_done,
+            );
+            let copied = match &self.conversion {
+                Some(conversion) => {
+                    conversion.record(device, command_buffer);
+                    conversion.image
+                }
+                None => self.image,
+            };
+            device.ash.cmd_copy_image_to_buffer(
+                command_buffer,
+                copied,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                self.buffer,
+                &[copy_region],
+            );
+            device.ash.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::HOST,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[host_read],
+                &[],
+            );
+        }
+    }
+
+    /// Copy the texels out of the host buffer, RGBA8 or RGBA16F after a conversion to
+    /// [`ExportColorSpace::Linear16F`]. The command buffer that recorded the copy must have
+    /// completed.
+    pub fn read(&self, device: &AAADevice) -> Vec<u8> {
+        let mut rgba = vec![0u8; self.buffer_size as usize];
+        unsafe {
+            let ptr = device
+                .ash
+                .map_memory(
+                    self.buffer_memory,
+                    0,
+                    self.buffer_size,
+                    vk::MemoryMapFlags::empty(),
+                )
+                .unwrap();
+            std::ptr::copy_nonoverlapping(ptr as *const u8, rgba.as_mut_ptr(), rgba.len());
+            device.ash.unmap_memory(self.buffer_memory);
+        }
+        rgba
+    }
+
+    pub fn destroy(&self, device: &AAADevice) {
+        if let Some(conversion) = &self.conversion {
+            conversion.destroy(device);
+        }
+        unsafe {
+            crate::object_audit::destroyed(self.buffer);
+            device.ash.destroy_buffer(self.buffer, None);
+            crate::object_audit::destroyed(self.buffer_memory);
+            device.ash.free_memory(self.buffer_memory, None);
+            crate::object_audit::destroyed(self.image);
+            device.ash.destroy_image(self.image, None);
+        }
+        self.image_memory.free(device);
+    }
+}
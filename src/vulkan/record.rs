@@ -4,6 +4,7 @@ use ash::vk;
 /// Helper function for submitting command buffers. Immediately waits for the fence before the command buffer
 /// is executed. That way we can delay the waiting for the fences by 1 frame which is good for performance.
 /// Make sure to create the fence in a signaled state on the first use.
+#[allow(clippy::too_many_arguments)]
 pub fn record_submit_commandbuffer<F: FnOnce(&AAADevice, vk::CommandBuffer)>(
     device: &AAADevice,
     command_buffer: vk::CommandBuffer,
@@ -0,0 +1,194 @@
+#[cfg(debug_assertions)]
+use super::debug_callback::DebugUtils;
+use super::{graphics::AAAGraphics, surface::AAASurface, AAABase};
+use crate::{
+    color_space,
+    config::GraphicsConfig,
+    display::DisplayEnvironment,
+    flight_recorder::DumpReason,
+    input_manager::EventStates,
+    renderer::{RendererFactory, WindowRenderer},
+    shaders::Shader,
+};
+use ash::{
+    vk::{self, PhysicalDevice},
+    Entry,
+};
+use rwh_06::HasDisplayHandle;
+use std::{
+    error::Error,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Mutex, RwLock},
+    thread,
+};
+use winit::{event_loop::EventLoop, window::Window};
+
+/// Vulkan instance shared by every window, creates a surface and a render thread per window.
+pub struct AAARendererFactory {
+    #[cfg(debug_assertions)]
+    _debug_utils: DebugUtils,
+    pub base: Arc<AAABase>,
+    pub physical_device_list: Vec<PhysicalDevice>,
+    pub graphics_config: Arc<RwLock<GraphicsConfig>>,
+    /// Surface formats in order of preference, `None` for the ones matching the gamma workflow.
+    pub surface_formats: Option<Vec<vk::SurfaceFormatKHR>>,
+}
+
+impl AAARendererFactory {
+    pub fn new<T>(
+        event_loop: &EventLoop<T>,
+        graphics_config: Arc<RwLock<GraphicsConfig>>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut phases = Vec::new();
+        // The shaders are only read when the first window creates its pipelines
+        #[cfg(debug_assertions)]
+        let shaders = thread::spawn(Shader::compile_shaders);
+
+        let entry = Entry::linked();
+
+        let instance = crate::stopwatch!(
+            phases,
+            "instance",
+            crate::vulkan::instance::create_instance(&entry, event_loop.display_handle().unwrap())
+        )?;
+
+        let surface_loader = ash::khr::surface::Instance::new(&entry, &instance);
+
+        #[cfg(debug_assertions)]
+        let _debug_utils = DebugUtils::new(&entry, &instance)?;
+
+        let physical_device_list = crate::stopwatch!(phases, "physical devices", unsafe {
+            instance
+                .enumerate_physical_devices()
+                .expect("Physical device error")
+        });
+
+        #[cfg(debug_assertions)]
+        crate::stopwatch!(phases, "shader compilation wait", {
+            if let Err(panic) = shaders.join() {
+                panic::resume_unwind(panic);
+            }
+        });
+        crate::metrics::record_startup_phases("Renderer factory", phases);
+
+        let base = AAABase {
+            entry,
+            instance: Arc::new(instance),
+            surface_loader: Arc::new(surface_loader),
+        };
+
+        Ok(Self {
+            #[cfg(debug_assertions)]
+            _debug_utils,
+            base: Arc::new(base),
+            physical_device_list,
+            graphics_config,
+            surface_formats: None,
+        })
+    }
+
+    pub fn with_surface_formats(
+        mut self,
+        surface_formats: Option<Vec<vk::SurfaceFormatKHR>>,
+    ) -> Self {
+        self.surface_formats = surface_formats;
+        self
+    }
+}
+
+impl RendererFactory for AAARendererFactory {
+    fn create_surface_renderer(
+        &mut self,
+        window: &Arc<Window>,
+        event_states: Arc<EventStates>,
+        display: DisplayEnvironment,
+    ) -> Result<Box<dyn WindowRenderer>, Box<dyn Error>> {
+        let shader_gamma = self.graphics_config.read().unwrap().shader_gamma;
+        let preferred_formats = self
+            .surface_formats
+            .clone()
+            .unwrap_or_else(|| color_space::preferred_surface_formats(shader_gamma).to_vec());
+        let surface = AAASurface::new(
+            &self.base,
+            window,
+            &self.physical_device_list,
+            shader_gamma,
+            preferred_formats,
+        )?;
+        let surface = Arc::new(Mutex::new(surface));
+        let graphics = AAAGraphics::new(
+            self.base.clone(),
+            surface.clone(),
+            event_states.clone(),
+            self.graphics_config.clone(),
+            display,
+        );
+        Ok(Box::new(AAAWindowRenderer {
+            base: self.base.clone(),
+            surface,
+            graphics: Some(Arc::new(Mutex::new(graphics))),
+            render_handle: None,
+            event_states,
+        }))
+    }
+}
+
+/// Owns the graphics of a window and the thread rendering them.
+pub struct AAAWindowRenderer {
+    base: Arc<AAABase>,
+    surface: Arc<Mutex<AAASurface>>,
+    graphics: Option<Arc<Mutex<AAAGraphics>>>,
+    render_handle: Option<thread::JoinHandle<()>>,
+    event_states: Arc<EventStates>,
+}
+
+impl WindowRenderer for AAAWindowRenderer {
+    fn resize(&mut self, display: DisplayEnvironment) {
+        self.event_states.post_display_environment(display);
+    }
+
+    fn render(&mut self) {
+        if self.render_handle.is_some() {
+            return;
+        }
+        self.event_states.opening();
+        let graphics_locked = self.graphics.clone().unwrap();
+        let event_states = self.event_states.clone();
+        self.render_handle = Some(thread::spawn(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                let mut graphics = graphics_locked.lock().unwrap();
+                graphics.cycle();
+            }));
+            if let Err(panic) = result {
+                let message = panic
+                    .downcast_ref::<&str>()
+                    .map(|message| message.to_string())
+                    .or_else(|| panic.downcast_ref::<String>().cloned())
+                    .unwrap_or_default();
+                event_states.dump_decisions(&DumpReason::Panicked(message));
+                panic::resume_unwind(panic);
+            }
+        }));
+    }
+
+    fn shutdown(&mut self) {
+        self.event_states.exiting();
+        if let Some(handle) = self.render_handle.take() {
+            handle.join().unwrap();
+        }
+    }
+}
+
+impl Drop for AAAWindowRenderer {
+    fn drop(&mut self) {
+        self.shutdown();
+        self.graphics = None;
+        let surface_guard = self.surface.lock().unwrap();
+        crate::object_audit::destroyed(surface_guard.surface_khr);
+        unsafe {
+            self.base
+                .surface_loader
+                .destroy_surface(surface_guard.surface_khr, None)
+        };
+    }
+}
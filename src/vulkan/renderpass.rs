@@ -2,32 +2,61 @@ use super::{device::AAADevice, surface::AAASurface};
 use ash::vk;
 use std::error::Error;
 
+/// Color attachments of the main subpass, a depth attachment follows them.
+pub const COLOR_ATTACHMENTS: u32 = 1;
+/// With MSAA, the single sample image the color attachment resolves into follows the depth.
+pub const RESOLVE_ATTACHMENT: u32 = 2;
+
+/// Draws into the framebuffer's image, or with more than one sample into a multisampled color
+/// attachment resolved into it at the end of the subpass.
 pub fn create_renderpass(
     surface: &AAASurface,
     device: &AAADevice,
+    samples: vk::SampleCountFlags,
 ) -> Result<vk::RenderPass, Box<dyn Error>> {
-    let renderpass_attachments = [
-        vk::AttachmentDescription {
-            format: surface.format.format,
-            samples: vk::SampleCountFlags::TYPE_1,
-            load_op: vk::AttachmentLoadOp::CLEAR,
-            store_op: vk::AttachmentStoreOp::STORE,
-            final_layout: vk::ImageLayout::PRESENT_SRC_KHR,
-            ..Default::default()
-        },
-        vk::AttachmentDescription {
-            format: vk::Format::D16_UNORM,
-            samples: vk::SampleCountFlags::TYPE_1,
-            load_op: vk::AttachmentLoadOp::CLEAR,
-            initial_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
-            final_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
-            ..Default::default()
-        },
-    ];
+    let presented = vk::AttachmentDescription {
+        format: surface.format.format,
+        samples: vk::SampleCountFlags::TYPE_1,
+        load_op: vk::AttachmentLoadOp::CLEAR,
+        store_op: vk::AttachmentStoreOp::STORE,
+        final_layout: vk::ImageLayout::PRESENT_SRC_KHR,
+        ..Default::default()
+    };
+    let depth = vk::AttachmentDescription {
+        format: vk::Format::D16_UNORM,
+        samples,
+        load_op: vk::AttachmentLoadOp::CLEAR,
+        initial_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+        final_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+        ..Default::default()
+    };
+    let multisampled = samples != vk::SampleCountFlags::TYPE_1;
+    let renderpass_attachments: Vec<vk::AttachmentDescription> = if multisampled {
+        vec![
+            // Only the resolved samples outlive the pass
+            vk::AttachmentDescription {
+                samples,
+                store_op: vk::AttachmentStoreOp::DONT_CARE,
+                final_layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                ..presented
+            },
+            depth,
+            vk::AttachmentDescription {
+                load_op: vk::AttachmentLoadOp::DONT_CARE,
+                ..presented
+            },
+        ]
+    } else {
+        vec![presented, depth]
+    };
     let color_attachment_refs = [vk::AttachmentReference {
         attachment: 0,
         layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
     }];
+    let resolve_attachment_refs = [vk::AttachmentReference {
+        attachment: RESOLVE_ATTACHMENT,
+        layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+    }];
     let depth_attachment_ref = vk::AttachmentReference {
         attachment: 1,
         layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
@@ -41,10 +70,13 @@ pub fn create_renderpass(
         ..Default::default()
     }];
 
-    let subpass = vk::SubpassDescription::default()
+    let mut subpass = vk::SubpassDescription::default()
         .color_attachments(&color_attachment_refs)
         .depth_stencil_attachment(&depth_attachment_ref)
         .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS);
+    if multisampled {
+        subpass = subpass.resolve_attachments(&resolve_attachment_refs);
+    }
 
     let renderpass_create_info = vk::RenderPassCreateInfo::default()
         .attachments(&renderpass_attachments)
@@ -57,6 +89,7 @@ pub fn create_renderpass(
             .create_render_pass(&renderpass_create_info, None)
             .unwrap()
     };
+    crate::object_audit::created(renderpass, "window render pass");
 
     Ok(renderpass)
 }
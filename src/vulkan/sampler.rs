@@ -0,0 +1,50 @@
+use super::device::AAADevice;
+use crate::texture::SamplerDesc;
+use ash::vk;
+use std::sync::Mutex;
+
+/// Samplers created so far, one per distinct [`SamplerDesc`], destroyed with the device.
+#[derive(Default)]
+pub struct AAASamplerCache {
+    samplers: Mutex<Vec<(SamplerDesc, vk::Sampler)>>,
+}
+
+impl AAASamplerCache {
+    /// The commands sampling with the samplers must have completed.
+    pub fn destroy(&self, device: &ash::Device) {
+        for (_, sampler) in self.samplers.lock().unwrap().drain(..) {
+            crate::object_audit::destroyed(sampler);
+            unsafe { device.destroy_sampler(sampler, None) };
+        }
+    }
+}
+
+/// Sampler matching `desc`, the same handle for every identical request. Owned by the device, not
+/// to be destroyed by the caller.
+pub fn create_sampler(device: &AAADevice, desc: SamplerDesc) -> Result<vk::Sampler, vk::Result> {
+    let mut samplers = device.samplers.samplers.lock().unwrap();
+    if let Some(&(_, sampler)) = samplers.iter().find(|(cached, _)| *cached == desc) {
+        return Ok(sampler);
+    }
+    let anisotropy = desc.anisotropy.min(device.max_sampler_anisotropy);
+    let sampler_info = vk::SamplerCreateInfo {
+        mag_filter: desc.mag,
+        min_filter: desc.min,
+        mipmap_mode: desc.mipmap,
+        address_mode_u: desc.address_mode,
+        address_mode_v: desc.address_mode,
+        address_mode_w: desc.address_mode,
+        anisotropy_enable: (anisotropy > 1.0).into(),
+        max_anisotropy: anisotropy.max(1.0),
+        compare_enable: desc.compare.is_some().into(),
+        compare_op: desc.compare.unwrap_or(vk::CompareOp::NEVER),
+        border_color: vk::BorderColor::FLOAT_OPAQUE_WHITE,
+        // Every level of the view, whatever the texture's mip count
+        max_lod: vk::LOD_CLAMP_NONE,
+        ..Default::default()
+    };
+    let sampler = unsafe { device.ash.create_sampler(&sampler_info, None)? };
+    crate::object_audit::created(sampler, "sampler");
+    samplers.push((desc, sampler));
+    Ok(sampler)
+}
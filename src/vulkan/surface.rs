@@ -1,8 +1,9 @@
-use super::{device::AAADevice, surface_resources::AAAResources, AAABase};
-use ash::{khr::surface, util::Align, vk};
-use glam::Mat4;
+use super::AAABase;
+use crate::{color_space, error::PulsarError, surface_support::query_surface};
+use ash::{khr::surface, vk};
+use log::warn;
 use rwh_06::{HasDisplayHandle, HasWindowHandle};
-use std::{error::Error, mem, sync::Arc};
+use std::{error::Error, sync::Arc};
 
 pub struct AAASurface {
     pub surface_khr: vk::SurfaceKHR,
@@ -10,14 +11,21 @@ pub struct AAASurface {
     pub capabilities: vk::SurfaceCapabilitiesKHR,
     pub physical_device: vk::PhysicalDevice,
     pub queue_family_index: u32,
-    pub resources: Option<AAAResources>,
+    /// Shaders encode their own gamma, see `GraphicsConfig::shader_gamma`.
+    pub shader_gamma: bool,
+    /// Formats taken in this order over the surface's first, again whenever it is queried.
+    pub preferred_formats: Vec<vk::SurfaceFormatKHR>,
 }
 
 impl AAASurface {
+    /// Presents in the first of `preferred_formats` the surface offers, see
+    /// `color_space::preferred_surface_formats`, otherwise in its first format.
     pub fn new(
         renderer: &Arc<AAABase>,
         window: &winit::window::Window,
         physical_device_list: &[ash::vk::PhysicalDevice],
+        shader_gamma: bool,
+        preferred_formats: Vec<vk::SurfaceFormatKHR>,
     ) -> Result<Self, Box<dyn Error>> {
         let surface_khr = unsafe {
             ash_window::create_surface(
@@ -26,11 +34,48 @@ impl AAASurface {
                 window.display_handle()?.as_raw(),
                 window.window_handle()?.as_raw(),
                 None,
-            )
-            .unwrap()
+            )?
         };
+        crate::object_audit::created(surface_khr, "window surface");
 
-        let (physical_device, queue_family_index) = physical_device_list
+        let supported = Self::find_queue(renderer, physical_device_list, surface_khr).and_then(
+            |(physical_device, queue_family_index)| {
+                let (format, capabilities) = Self::query(
+                    &renderer.surface_loader,
+                    physical_device,
+                    surface_khr,
+                    &preferred_formats,
+                )?;
+                if !preferred_formats.contains(&format) {
+                    warn!(
+                        "The surface offers none of the preferred formats, presenting {format:?}"
+                    );
+                }
+                Ok(Self {
+                    surface_khr,
+                    format,
+                    capabilities,
+                    physical_device,
+                    queue_family_index,
+                    shader_gamma,
+                    preferred_formats,
+                })
+            },
+        );
+        if supported.is_err() {
+            crate::object_audit::destroyed(surface_khr);
+            unsafe { renderer.surface_loader.destroy_surface(surface_khr, None) };
+        }
+        Ok(supported?)
+    }
+
+    /// First device with a queue family that draws and presents to the surface.
+    fn find_queue(
+        renderer: &AAABase,
+        physical_device_list: &[vk::PhysicalDevice],
+        surface_khr: vk::SurfaceKHR,
+    ) -> Result<(vk::PhysicalDevice, u32), PulsarError> {
+        physical_device_list
             .iter()
             .find_map(|physical_device| unsafe {
                 renderer
@@ -39,93 +84,68 @@ impl AAASurface {
                     .iter()
                     .enumerate()
                     .find_map(|(index, info)| {
-                        let supports_graphic_and_surface =
-                            info.queue_flags.contains(vk::QueueFlags::GRAPHICS)
-                                && renderer
-                                    .surface_loader
-                                    .get_physical_device_surface_support(
-                                        *physical_device,
-                                        index as u32,
-                                        surface_khr,
-                                    )
-                                    .unwrap();
+                        // A failed support query counts as unsupported, another family may do.
+                        // Compute too, the deformed meshes are dispatched on the same queue
+                        let supports_graphic_and_surface = info
+                            .queue_flags
+                            .contains(vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE)
+                            && renderer
+                                .surface_loader
+                                .get_physical_device_surface_support(
+                                    *physical_device,
+                                    index as u32,
+                                    surface_khr,
+                                )
+                                .unwrap_or(false);
                         if supports_graphic_and_surface {
-                            Some((*physical_device, index))
+                            Some((*physical_device, index as u32))
                         } else {
                             None
                         }
                     })
             })
-            .expect("Couldn't find suitable device.");
-        let queue_family_index = queue_family_index as u32;
-
-        let format = unsafe {
-            renderer
-                .surface_loader
-                .get_physical_device_surface_formats(physical_device, surface_khr)
-                .unwrap()[0]
-        };
-
-        let capabilities = unsafe {
-            renderer
-                .surface_loader
-                .get_physical_device_surface_capabilities(physical_device, surface_khr)
-                .unwrap()
-        };
-
-        Ok(Self {
-            surface_khr,
-            format,
-            capabilities,
-            physical_device,
-            queue_family_index,
-            resources: None,
-        })
+            .ok_or_else(|| PulsarError::SurfaceUnsupported {
+                reason: "no device presents to the surface".to_string(),
+            })
     }
 
-    pub fn recreate(&mut self, surface_loader: &surface::Instance) {
-        self.format = unsafe {
-            surface_loader
-                .get_physical_device_surface_formats(self.physical_device, self.surface_khr)
-                .unwrap()[0]
-        };
-
-        self.capabilities = unsafe {
-            surface_loader
-                .get_physical_device_surface_capabilities(self.physical_device, self.surface_khr)
-                .unwrap()
-        };
+    fn query(
+        surface_loader: &surface::Instance,
+        physical_device: vk::PhysicalDevice,
+        surface_khr: vk::SurfaceKHR,
+        preferred_formats: &[vk::SurfaceFormatKHR],
+    ) -> Result<(vk::SurfaceFormatKHR, vk::SurfaceCapabilitiesKHR), PulsarError> {
+        query_surface(
+            || unsafe {
+                surface_loader.get_physical_device_surface_formats(physical_device, surface_khr)
+            },
+            || unsafe {
+                surface_loader
+                    .get_physical_device_surface_capabilities(physical_device, surface_khr)
+            },
+            |available| color_space::choose_surface_format(available, preferred_formats),
+        )
     }
 
-    // pub fn update(&self, uniform: Mat4) {
-    //     self.uniform *= Mat4::from_euler(glam::EulerRot::XYZ, 0.0, 0.0, 5); // TODO reinplement delta time
-    //     self.update_uniform_buffer(&self.device, self.uniform_buffer_memory, self.uniform);
-    // }
-
-    fn update_uniform_buffer(
-        device: &AAADevice,
-        uniform_buffer_memory: vk::DeviceMemory,
-        new_transform: Mat4,
-    ) {
-        unsafe {
-            let uniform_ptr = device
-                .ash
-                .map_memory(
-                    uniform_buffer_memory,
-                    0,
-                    mem::size_of::<Mat4>() as u64,
-                    vk::MemoryMapFlags::empty(),
-                )
-                .unwrap();
-
-            let mut uniform_aligned_slice = Align::new(
-                uniform_ptr,
-                mem::align_of::<Mat4>() as u64,
-                mem::size_of::<Mat4>() as u64,
-            );
+    /// Format of the color textures sampled on this surface's device.
+    pub fn texture_format(&self) -> vk::Format {
+        color_space::texture_format(self.shader_gamma)
+    }
 
-            uniform_aligned_slice.copy_from_slice(&[new_transform]);
-            device.ash.unmap_memory(uniform_buffer_memory);
+    /// Query the surface again for a new swapchain. A failure keeps the last known good format and
+    /// capabilities, resize storms on remote displays fail some queries and recover on the next one.
+    pub fn recreate(&mut self, surface_loader: &surface::Instance) {
+        match Self::query(
+            surface_loader,
+            self.physical_device,
+            self.surface_khr,
+            &self.preferred_formats,
+        ) {
+            Ok((format, capabilities)) => {
+                self.format = format;
+                self.capabilities = capabilities;
+            }
+            Err(error) => warn!("{error}, keeping the previous surface format and capabilities"),
         }
     }
 }
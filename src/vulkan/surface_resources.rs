@@ -1,25 +1,47 @@
 use super::{
+    descriptor_set::{
+        allocate_set, AAADescriptorSets, ENVIRONMENT_BINDING, ENVIRONMENT_UNIFORM_BINDING,
+        IRRADIANCE_BINDING, MATERIAL_SET, SET_COUNT,
+    },
     device::AAADevice,
+    export::AAAExportConverter,
+    memory_budget::AAAMemoryBudget,
+    offscreen::AAAMsaaColor,
+    pipeline::MeshPipelines,
+    readback::AAAReadback,
     record::record_submit_commandbuffer,
     surface::AAASurface,
     swapchain::{AAASwapchain, AAASwapchainLoader},
-    views::find_memorytype_index,
+    texture::Texture,
+    texture_array::TextureArray,
+    texture_upload::AAATextureUploads,
+    upload::{create_filled_host_buffer, write_mapped, AAAOwnedBuffer, AAAUploadContext},
+    viewport::ViewportState,
     AAABase,
 };
 use crate::{
     camera::{Camera, OrthographicProjection, PerspectiveProjection},
-    model::{Mesh, RegisteredMesh, Vertex},
+    color_space,
+    config::RenderSettings,
+    environment::{Cubemap, FLAT_AMBIENT},
+    gizmo::{Gizmo, GizmoAxis, GizmoMode},
+    handles::MeshHandle,
+    material::PipelineOptions,
+    model::{Mesh, MeshHandles, MeshRegistry, RegisteredMesh, Vertex},
+    renderer::StartupContents,
+    texture::SamplerDesc,
+    world::WorldConvention,
 };
-use ash::{
-    util::Align,
-    vk::{self, DescriptorSetLayout},
-};
-use glam::{Mat4, Vec3};
+use ash::vk::{self, DescriptorSetLayout};
+use glam::{Mat4, Vec3, Vec4};
 use std::{
     mem,
     sync::{Arc, Mutex},
 };
 
+/// Grid lines on each side of the origin, one world unit apart.
+const REFERENCE_GRID_HALF_EXTENT: i32 = 10;
+
 pub struct AAAResources {
     pub device: Arc<AAADevice>, // TEMP should be in super, everything uses it
 
@@ -29,6 +51,11 @@ pub struct AAAResources {
     pub depth_image: vk::Image,
     pub depth_image_view: vk::ImageView,
     pub depth_image_memory: vk::DeviceMemory,
+    /// Samples per pixel of the color and depth attachments, `render.msaa_samples` clamped to
+    /// the device.
+    pub samples: vk::SampleCountFlags,
+    /// Drawn into and resolved into the presented image, with more than one sample.
+    pub msaa_color: Option<AAAMsaaColor>,
 
     pub present_images: Vec<vk::Image>,
     pub present_image_views: Vec<vk::ImageView>,
@@ -42,15 +69,23 @@ pub struct AAAResources {
     pub vertex_shader_module: vk::ShaderModule,
     pub fragment_shader_module: vk::ShaderModule,
 
-    pub image_buffer_memory: vk::DeviceMemory,
-    pub image_buffer: vk::Buffer,
-    pub texture_memory: vk::DeviceMemory,
-    pub tex_image_view: vk::ImageView,
-    pub texture_image: vk::Image,
-
-    pub desc_set_layouts: [DescriptorSetLayout; 1],
+    /// The default material samples the first, the built-in texture. Empty until a frame
+    /// samples it, see [`Self::ensure_default_texture`].
+    pub textures: Vec<Texture>,
+    /// Streams the application's updates into the built-in texture, made with it.
+    pub texture_uploads: Option<AAATextureUploads>,
+    /// Sampled by the meshes drawn with `Material::texture_array`, once the application made one.
+    pub texture_array: Option<TextureArray>,
+    /// Cubemap in the global set's [`ENVIRONMENT_BINDING`], once the application loaded one.
+    pub environment: Option<Texture>,
+    /// Diffuse convolution of the environment in the global set's [`IRRADIANCE_BINDING`], also in
+    /// its [`ENVIRONMENT_BINDING`] while no cubemap is loaded.
+    pub irradiance: Texture,
+    /// Intensity of the irradiance in `x`, at [`ENVIRONMENT_UNIFORM_BINDING`].
+    pub environment_uniform: AAAOwnedBuffer,
+
+    pub desc_set_layouts: [DescriptorSetLayout; SET_COUNT],
     pub descriptor_pool: vk::DescriptorPool,
-    pub texture_sampler: vk::Sampler,
 
     pub uniform_color_buffer_memory: vk::DeviceMemory,
     pub uniform_color_buffer: vk::Buffer,
@@ -63,49 +98,95 @@ pub struct AAAResources {
     pub swapchain: AAASwapchain,
 
     pub framebuffers: Vec<vk::Framebuffer>,
-    pub viewports: [vk::Viewport; 1],
-    pub scissors: [vk::Rect2D; 1],
-
-    pub descriptor_sets: Vec<vk::DescriptorSet>,
+    pub readback: Option<AAAReadback>,
+    /// Decodes the presented images for the readbacks of other formats than 8 bit sRGB.
+    pub export: AAAExportConverter,
+    /// The window's until letterboxed, see `AAAGraphics::set_scene_viewport`.
+    pub viewport: ViewportState,
+
+    pub descriptor_sets: AAADescriptorSets,
+    /// Unculled, the state overlays and custom passes start from.
     pub graphic_pipeline: vk::Pipeline,
-
-    pub projection_registered_meshes: Vec<RegisteredMesh>,
+    /// Unculled without depth test nor write, the orthographic meshes draw in order over the
+    /// scene.
+    pub ui_pipeline: vk::Pipeline,
+    /// Default material of the projection meshes, the variants of the default options built
+    /// at startup. Handed over to the window's `PipelineRegistry`, which builds the others on
+    /// first use.
+    pub mesh_pipelines: MeshPipelines,
+    /// `LINE_LIST` topology, draws the debug lines.
+    pub line_pipeline: vk::Pipeline,
+
+    pub projection_registered_meshes: MeshRegistry,
     pub orthographic_registered_meshes: Vec<RegisteredMesh>,
+    /// Handles of every gizmo mode, drawn over the selected mesh. Built when a mesh is first
+    /// selected, see [`Self::ensure_gizmo_meshes`].
+    pub gizmo_registered_meshes: Vec<(GizmoMode, GizmoAxis, RegisteredMesh)>,
+    /// Ground grid and world axes, drawn when `GraphicsConfig::reference_grid` is set. Built
+    /// the first time it is, see [`Self::ensure_reference_meshes`].
+    pub reference_registered_meshes: Vec<RegisteredMesh>,
     pub device_memory_properties: vk::PhysicalDeviceMemoryProperties,
+    /// Device local memory of the window, queried before the meshes were uploaded.
+    pub memory_budget: AAAMemoryBudget,
 
-    pub uniform: Mat4,
     pub camera: Camera,
 }
 
 impl AAAResources {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         base: Arc<AAABase>,
         surface: Arc<Mutex<AAASurface>>,
         width: u32,
         height: u32,
+        convention: WorldConvention,
+        demo_scene: bool,
+        render: RenderSettings,
+        mesh_handles: Arc<MeshHandles>,
     ) -> Self {
+        let mut phases = Vec::new();
         let surface = surface.lock().unwrap();
 
-        let device = AAADevice::new(
-            &base.instance,
-            surface.physical_device,
-            surface.queue_family_index,
+        let device = crate::stopwatch!(
+            phases,
+            "device",
+            AAADevice::new(
+                &base.instance,
+                surface.physical_device,
+                surface.queue_family_index,
+                surface.texture_format(),
+            )
         );
 
         let swapchain_loader = AAASwapchainLoader::new(&base, &device);
+        let samples = render.sample_count(device.msaa_sample_counts);
+        if samples.as_raw() != render.msaa_samples {
+            log::warn!(
+                "{} MSAA samples asked for, rendering with {}",
+                render.msaa_samples,
+                samples.as_raw()
+            );
+        }
 
-        // TODO get from os window api for linux and possibly more
-        // let size = surface.capabilities.current_extent; // TODO VERIFY THERE S NOT MORE ELSEWHERE
-
-        let swapchain = AAASwapchain::new(
-            &device,
-            &base,
-            &surface,
-            surface.physical_device,
-            surface.queue_family_index,
-            width,
-            height,
-            &swapchain_loader,
+        let swapchain = crate::stopwatch!(
+            phases,
+            "swapchain",
+            AAASwapchain::new(
+                &device,
+                &base,
+                &surface,
+                surface.physical_device,
+                surface.queue_family_index,
+                width,
+                height,
+                &swapchain_loader,
+                render.present_mode,
+            )
+        );
+        log::info!(
+            "Presenting with {:?}, {:?} preferred",
+            swapchain.present_mode,
+            render.present_mode
         );
 
         let (draw_commands_reuse_fence, setup_commands_reuse_fence) =
@@ -117,6 +198,7 @@ impl AAAResources {
             depth_image_view,
             depth_image,
             depth_image_memory,
+            msaa_color,
             device_memory_properties,
         ) = crate::vulkan::views::create_views_and_depth(
             &device,
@@ -125,40 +207,67 @@ impl AAAResources {
             &surface,
             &surface.physical_device,
             &swapchain_loader,
+            samples,
         );
 
         let (present_complete_semaphore, rendering_complete_semaphore) =
             crate::vulkan::fence_semaphores::create_semaphores(&device).unwrap();
 
-        let renderpass = crate::vulkan::renderpass::create_renderpass(&surface, &device).unwrap();
+        let renderpass =
+            crate::vulkan::renderpass::create_renderpass(&surface, &device, samples).unwrap();
 
+        // Reflected from the compiled shaders, a mismatch with what the renderer writes is a bug
+        let interface =
+            crate::vulkan::descriptor_set::engine_interface(device.max_push_constants_size)
+                .unwrap_or_else(|err| panic!("{err}"));
         let (descriptor_pool, descriptor_sets, desc_set_layouts) =
-            crate::vulkan::descriptor_set::create_descriptor_set(&device);
+            crate::vulkan::descriptor_set::create_descriptor_set(&device, &interface);
 
         let (
-            graphic_pipeline,
-            viewports,
-            scissors,
+            (graphic_pipeline, ui_pipeline, line_pipeline, mesh_pipelines),
             graphics_pipelines,
             pipeline_layout,
             vertex_shader_module,
             fragment_shader_module,
-        ) = crate::vulkan::pipeline::create_pipeline(
-            &device,
-            &surface,
-            renderpass,
-            desc_set_layouts,
+        ) = crate::stopwatch!(
+            phases,
+            "pipelines",
+            crate::vulkan::pipeline::create_pipeline(
+                &device,
+                renderpass,
+                samples,
+                &desc_set_layouts,
+                &interface,
+                PipelineOptions::default(),
+            )
         );
 
+        let viewport = ViewportState::new(swapchain.extent.width, swapchain.extent.height);
         let framebuffers = crate::vulkan::framebuffer::create_framebuffers(
             &device,
-            &surface,
+            swapchain.extent,
             &present_image_views,
             depth_image_view,
+            msaa_color.as_ref(),
             renderpass,
         )
         .unwrap();
 
+        let export = AAAExportConverter::new(&device, &desc_set_layouts);
+
+        // Only the replay buffer reads the presented images back
+        #[cfg(feature = "replay")]
+        let readback = AAAReadback::for_swapchain(
+            &device,
+            &device_memory_properties,
+            &swapchain,
+            &surface,
+            crate::replay::REPLAY_DOWNSCALE,
+            &export,
+        );
+        #[cfg(not(feature = "replay"))]
+        let readback = None;
+
         let pool =
             crate::vulkan::command_pools::create_command_pools(&device, surface.queue_family_index)
                 .unwrap();
@@ -204,6 +313,12 @@ impl AAAResources {
             },
         );
 
+        let upload = AAAUploadContext {
+            command_buffer: setup_command_buffer,
+            fence: setup_commands_reuse_fence,
+            queue: swapchain.present_queue,
+        };
+
         // MARK: UNIFORM BUFFER
         let uniform = Mat4::IDENTITY;
         // TEMP: rotate UBO transfrom by 25% of PI
@@ -217,226 +332,15 @@ impl AAAResources {
                 uniform,
             );
 
-        // MARK: IMAGE
-        let image = image::load_from_memory(include_bytes!("../../assets/img/picture.png"))
-            .unwrap()
-            .to_rgba8();
-        let (width, height) = image.dimensions();
-        let image_extent = vk::Extent2D { width, height };
-        let image_data = image.into_raw();
-        let image_buffer_info = vk::BufferCreateInfo {
-            size: (mem::size_of::<u8>() * image_data.len()) as u64,
-            usage: vk::BufferUsageFlags::TRANSFER_SRC,
-            sharing_mode: vk::SharingMode::EXCLUSIVE,
-            ..Default::default()
-        };
-        let image_buffer = unsafe { device.ash.create_buffer(&image_buffer_info, None).unwrap() };
-        let image_buffer_memory_req =
-            unsafe { device.ash.get_buffer_memory_requirements(image_buffer) };
-        let image_buffer_memory_index = find_memorytype_index(
-            &image_buffer_memory_req,
-            &device_memory_properties,
-            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-        )
-        .expect("Unable to find suitable memorytype for the image buffer.");
-
-        let image_buffer_allocate_info = vk::MemoryAllocateInfo {
-            allocation_size: image_buffer_memory_req.size,
-            memory_type_index: image_buffer_memory_index,
-            ..Default::default()
-        };
-        let image_buffer_memory = unsafe {
-            device
-                .ash
-                .allocate_memory(&image_buffer_allocate_info, None)
-                .unwrap()
-        };
-        let image_ptr = unsafe {
-            device
-                .ash
-                .map_memory(
-                    image_buffer_memory,
-                    0,
-                    image_buffer_memory_req.size,
-                    vk::MemoryMapFlags::empty(),
-                )
-                .unwrap()
-        };
-        let mut image_slice = unsafe {
-            Align::new(
-                image_ptr,
-                mem::align_of::<u8>() as u64,
-                image_buffer_memory_req.size,
-            )
-        };
-        image_slice.copy_from_slice(&image_data);
-        unsafe {
-            device.ash.unmap_memory(image_buffer_memory);
-            device
-                .ash
-                .bind_buffer_memory(image_buffer, image_buffer_memory, 0)
-                .unwrap();
-        }
-
-        // MARK: TEXTURE
-        let texture_create_info = vk::ImageCreateInfo {
-            image_type: vk::ImageType::TYPE_2D,
-            format: vk::Format::R8G8B8A8_UNORM,
-            extent: image_extent.into(),
-            mip_levels: 1,
-            array_layers: 1,
-            samples: vk::SampleCountFlags::TYPE_1,
-            tiling: vk::ImageTiling::OPTIMAL,
-            usage: vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
-            sharing_mode: vk::SharingMode::EXCLUSIVE,
-            ..Default::default()
-        };
-        let texture_image = unsafe { device.ash.create_image(&texture_create_info, None).unwrap() };
-        let texture_memory_req = unsafe { device.ash.get_image_memory_requirements(texture_image) };
-        let texture_memory_index = find_memorytype_index(
-            &texture_memory_req,
+        // MARK: ENVIRONMENT
+        let irradiance = flat_irradiance(&device, &device_memory_properties, upload)
+            .expect("Failed to create the flat irradiance");
+        let environment_uniform = create_filled_host_buffer(
+            &device,
             &device_memory_properties,
-            vk::MemoryPropertyFlags::DEVICE_LOCAL,
-        )
-        .expect("Unable to find suitable memory index for depth image.");
-
-        let texture_allocate_info = vk::MemoryAllocateInfo {
-            allocation_size: texture_memory_req.size,
-            memory_type_index: texture_memory_index,
-            ..Default::default()
-        };
-        let texture_memory = unsafe {
-            device
-                .ash
-                .allocate_memory(&texture_allocate_info, None)
-                .unwrap()
-        };
-        unsafe {
-            device
-                .ash
-                .bind_image_memory(texture_image, texture_memory, 0)
-                .expect("Unable to bind depth image memory")
-        };
-
-        // MARK: REC TEXTURE
-        // record_submit_commandbuffer(
-        //     &device,
-        //     setup_command_buffer,
-        //     setup_commands_reuse_fence,
-        //     swapchain.present_queue,
-        //     &[],
-        //     &[],
-        //     &[],
-        //     |device, texture_command_buffer| {
-        //         let texture_barrier = vk::ImageMemoryBarrier {
-        //             dst_access_mask: vk::AccessFlags::TRANSFER_WRITE,
-        //             new_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-        //             image: texture_image,
-        //             subresource_range: vk::ImageSubresourceRange {
-        //                 aspect_mask: vk::ImageAspectFlags::COLOR,
-        //                 level_count: 1,
-        //                 layer_count: 1,
-        //                 ..Default::default()
-        //             },
-        //             ..Default::default()
-        //         };
-        //         unsafe {
-        //             device.ash.cmd_pipeline_barrier(
-        //                 texture_command_buffer,
-        //                 vk::PipelineStageFlags::BOTTOM_OF_PIPE,
-        //                 vk::PipelineStageFlags::TRANSFER,
-        //                 vk::DependencyFlags::empty(),
-        //                 &[],
-        //                 &[],
-        //                 &[texture_barrier],
-        //             )
-        //         };
-        //         let buffer_copy_regions = vk::BufferImageCopy::default()
-        //             .image_subresource(
-        //                 vk::ImageSubresourceLayers::default()
-        //                     .aspect_mask(vk::ImageAspectFlags::COLOR)
-        //                     .layer_count(1),
-        //             )
-        //             .image_extent(image_extent.into());
-
-        //         unsafe {
-        //             device.ash.cmd_copy_buffer_to_image(
-        //                 texture_command_buffer,
-        //                 image_buffer,
-        //                 texture_image,
-        //                 vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-        //                 &[buffer_copy_regions],
-        //             )
-        //         };
-        //         let texture_barrier_end = vk::ImageMemoryBarrier {
-        //             src_access_mask: vk::AccessFlags::TRANSFER_WRITE,
-        //             dst_access_mask: vk::AccessFlags::SHADER_READ,
-        //             old_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-        //             new_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-        //             image: texture_image,
-        //             subresource_range: vk::ImageSubresourceRange {
-        //                 aspect_mask: vk::ImageAspectFlags::COLOR,
-        //                 level_count: 1,
-        //                 layer_count: 1,
-        //                 ..Default::default()
-        //             },
-        //             ..Default::default()
-        //         };
-        //         unsafe {
-        //             device.ash.cmd_pipeline_barrier(
-        //                 texture_command_buffer,
-        //                 vk::PipelineStageFlags::TRANSFER,
-        //                 vk::PipelineStageFlags::FRAGMENT_SHADER,
-        //                 vk::DependencyFlags::empty(),
-        //                 &[],
-        //                 &[],
-        //                 &[texture_barrier_end],
-        //             )
-        //         };
-        //     },
-        // );
-
-        // MARK: SAMPLER
-        let sampler_info = vk::SamplerCreateInfo {
-            mag_filter: vk::Filter::LINEAR,
-            min_filter: vk::Filter::LINEAR,
-            mipmap_mode: vk::SamplerMipmapMode::LINEAR,
-            address_mode_u: vk::SamplerAddressMode::MIRRORED_REPEAT,
-            address_mode_v: vk::SamplerAddressMode::MIRRORED_REPEAT,
-            address_mode_w: vk::SamplerAddressMode::MIRRORED_REPEAT,
-            max_anisotropy: 1.0,
-            border_color: vk::BorderColor::FLOAT_OPAQUE_WHITE,
-            compare_op: vk::CompareOp::NEVER,
-            ..Default::default()
-        };
-
-        let texture_sampler = unsafe { device.ash.create_sampler(&sampler_info, None).unwrap() };
-
-        // MARK: TEXTURE VIEW
-        let tex_image_view_info = vk::ImageViewCreateInfo {
-            view_type: vk::ImageViewType::TYPE_2D,
-            format: texture_create_info.format,
-            components: vk::ComponentMapping {
-                r: vk::ComponentSwizzle::R,
-                g: vk::ComponentSwizzle::G,
-                b: vk::ComponentSwizzle::B,
-                a: vk::ComponentSwizzle::A,
-            },
-            subresource_range: vk::ImageSubresourceRange {
-                aspect_mask: vk::ImageAspectFlags::COLOR,
-                level_count: 1,
-                layer_count: 1,
-                ..Default::default()
-            },
-            image: texture_image,
-            ..Default::default()
-        };
-        let tex_image_view = unsafe {
-            device
-                .ash
-                .create_image_view(&tex_image_view_info, None)
-                .unwrap()
-        };
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
+            &[Vec4::X],
+        );
 
         let uniform_color_buffer_descriptor = vk::DescriptorBufferInfo {
             buffer: uniform_color_buffer,
@@ -444,33 +348,54 @@ impl AAAResources {
             range: mem::size_of_val(&uniform) as u64,
         };
 
-        let tex_descriptor = vk::DescriptorImageInfo {
-            image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-            image_view: tex_image_view,
-            sampler: texture_sampler,
+        let irradiance_descriptor = irradiance.descriptor();
+        let environment_uniform_descriptor = vk::DescriptorBufferInfo {
+            buffer: environment_uniform.buffer,
+            offset: 0,
+            range: environment_uniform.size,
         };
 
         let write_desc_sets = [
             vk::WriteDescriptorSet {
-                dst_set: descriptor_sets[0],
+                dst_set: descriptor_sets.global,
                 descriptor_count: 1,
                 descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
                 p_buffer_info: &uniform_color_buffer_descriptor,
                 ..Default::default()
             },
             vk::WriteDescriptorSet {
-                dst_set: descriptor_sets[0],
-                dst_binding: 1,
+                dst_set: descriptor_sets.global,
+                dst_binding: ENVIRONMENT_BINDING,
                 descriptor_count: 1,
                 descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-                p_image_info: &tex_descriptor,
+                p_image_info: &irradiance_descriptor,
+                ..Default::default()
+            },
+            vk::WriteDescriptorSet {
+                dst_set: descriptor_sets.global,
+                dst_binding: IRRADIANCE_BINDING,
+                descriptor_count: 1,
+                descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                p_image_info: &irradiance_descriptor,
+                ..Default::default()
+            },
+            vk::WriteDescriptorSet {
+                dst_set: descriptor_sets.global,
+                dst_binding: ENVIRONMENT_UNIFORM_BINDING,
+                descriptor_count: 1,
+                descriptor_type: vk::DescriptorType::UNIFORM_BUFFER,
+                p_buffer_info: &environment_uniform_descriptor,
                 ..Default::default()
             },
         ];
         unsafe { device.ash.update_descriptor_sets(&write_desc_sets, &[]) };
 
+        // The meshes are counted by residency, queried before any is uploaded
+        let memory_budget =
+            AAAMemoryBudget::query(&base.instance, &device, surface.physical_device);
+
         // MARK: MESHES
-        let mut projection_registered_meshes = Vec::new();
+        let mut projection_registered_meshes = MeshRegistry::new(mesh_handles);
         let mut orthographic_registered_meshes = Vec::new();
 
         // use rand::Rng;
@@ -546,113 +471,190 @@ impl AAAResources {
         // let registered_square = square.register(&device, &device_memory_properties);
         // registered_meshes.push(registered_square);
 
-        // MARK: UI COVER
-        let ui_width = 350.0;
-        let ui_height = 600.0;
-        let ui_vertices = vec![
-            Vertex {
-                pos: [0.0, 0.0, 0.0, 1.0],
-                uv: [0.0, 0.0],
-                color: [1.0, 1.0, 1.0, 1.0],
-            },
-            Vertex {
-                pos: [ui_width, 0.0, 0.0, 1.0],
-                uv: [1.0, 0.0],
-                color: [1.0, 1.0, 1.0, 1.0],
-            },
-            Vertex {
-                pos: [ui_width, ui_height, 0.0, 1.0],
-                uv: [1.0, 1.0],
-                color: [1.0, 1.0, 1.0, 1.0],
-            },
-            Vertex {
-                pos: [0.0, ui_height, 0.0, 1.0],
-                uv: [0.0, 1.0],
-                color: [1.0, 1.0, 1.0, 1.0],
-            },
-        ];
-        let ui_indices = vec![0u32, 1, 2, 2, 3, 0];
-        let ui_cover = Mesh {
-            vertices: ui_vertices,
-            indices: ui_indices,
-            transform: Mat4::IDENTITY,
-        };
-        let registered_ui_cover = ui_cover.register(&device, &device_memory_properties);
-        orthographic_registered_meshes.push(registered_ui_cover);
-
-        // MARK: LEFT_SCREEN_COVER
-        // let left_cover_color = [
-        //     0.08627450980392157,
-        //     0.08627450980392157,
-        //     0.13333333333333333,
-        //     1.0,
-        // ];
-        let left_cover_color = [1.0, 1.0, 0.0, 1.0];
-        let left_cover = Mesh {
-            vertices: vec![
+        let meshes_start = std::time::Instant::now();
+        if demo_scene {
+            // MARK: UI COVER
+            let ui_width = 350.0;
+            let ui_height = 600.0;
+            let ui_vertices = vec![
                 Vertex {
-                    pos: [-1.0, -1.0, 0.0, 1.0],
+                    pos: [0.0, 0.0, 0.0, 1.0],
                     uv: [0.0, 0.0],
-                    color: left_cover_color,
-                },
-                Vertex {
-                    pos: [-1.0, 1.0, 0.0, 1.0],
-                    uv: [0.0, 1.0],
-                    color: left_cover_color,
+                    color: [1.0, 1.0, 1.0, 1.0],
+                    normal: [0.0, 0.0, 1.0],
+                    tangent: [1.0, 0.0, 0.0, 1.0],
+                    joints: [0; 4],
+                    weights: [0.0; 4],
                 },
                 Vertex {
-                    pos: [0.0, 1.0, 0.0, 1.0],
-                    uv: [1.0, 1.0],
-                    color: left_cover_color,
-                },
-                Vertex {
-                    pos: [0.0, -1.0, 0.0, 1.0],
+                    pos: [ui_width, 0.0, 0.0, 1.0],
                     uv: [1.0, 0.0],
-                    color: left_cover_color,
+                    color: [1.0, 1.0, 1.0, 1.0],
+                    normal: [0.0, 0.0, 1.0],
+                    tangent: [1.0, 0.0, 0.0, 1.0],
+                    joints: [0; 4],
+                    weights: [0.0; 4],
                 },
-            ],
-            indices: vec![0u32, 1, 2, 2, 3, 0],
-            transform: Mat4::from_translation(glam::Vec3::new(0.0, 0.2, 0.0)),
-        };
-        let registered_square = left_cover.register(&device, &device_memory_properties);
-        projection_registered_meshes.push(registered_square);
-
-        // MARK: RIGHT_SCREEN_COVER
-        // let right_cover_color = [
-        //     0.13333333333333333,
-        //     0.13333333333333333,
-        //     0.21176470588235294,
-        //     1.0,
-        // ];
-        let right_cover_color = [0.0, 1.0, 1.0, 1.0];
-        let right_cover = Mesh {
-            vertices: vec![
                 Vertex {
-                    pos: [0.0, -1.0, 0.0, 1.0],
-                    uv: [0.0, 0.0],
-                    color: right_cover_color,
-                },
-                Vertex {
-                    pos: [0.0, 1.0, 0.0, 1.0],
-                    uv: [0.0, 1.0],
-                    color: right_cover_color,
-                },
-                Vertex {
-                    pos: [1.0, 1.0, 0.0, 1.0],
+                    pos: [ui_width, ui_height, 0.0, 1.0],
                     uv: [1.0, 1.0],
-                    color: right_cover_color,
+                    color: [1.0, 1.0, 1.0, 1.0],
+                    normal: [0.0, 0.0, 1.0],
+                    tangent: [1.0, 0.0, 0.0, 1.0],
+                    joints: [0; 4],
+                    weights: [0.0; 4],
                 },
                 Vertex {
-                    pos: [1.0, -1.0, 0.0, 1.0],
-                    uv: [1.0, 0.0],
-                    color: right_cover_color,
+                    pos: [0.0, ui_height, 0.0, 1.0],
+                    uv: [0.0, 1.0],
+                    color: [1.0, 1.0, 1.0, 1.0],
+                    normal: [0.0, 0.0, 1.0],
+                    tangent: [1.0, 0.0, 0.0, 1.0],
+                    joints: [0; 4],
+                    weights: [0.0; 4],
                 },
-            ],
-            indices: vec![0u32, 1, 2, 2, 3, 0],
-            transform: Mat4::from_translation(glam::Vec3::new(0.0, -0.2, 0.0)),
-        };
-        let registered_square = right_cover.register(&device, &device_memory_properties);
-        projection_registered_meshes.push(registered_square);
+            ];
+            let ui_indices = vec![0u32, 1, 2, 2, 3, 0];
+            let ui_cover = Mesh {
+                vertices: ui_vertices,
+                indices: ui_indices,
+                transform: Mat4::IDENTITY,
+                user_id: 0,
+            };
+            let registered_ui_cover =
+                ui_cover.register(&device, &device_memory_properties, Some(upload));
+            orthographic_registered_meshes.push(registered_ui_cover);
+
+            // Authored in sRGB, made linear so an `_SRGB` swapchain shows them the same
+            let cover_color = |color| {
+                if surface.shader_gamma {
+                    color
+                } else {
+                    color_space::srgb_color_to_linear(color)
+                }
+            };
+
+            // MARK: LEFT_SCREEN_COVER
+            // let left_cover_color = [
+            //     0.08627450980392157,
+            //     0.08627450980392157,
+            //     0.13333333333333333,
+            //     1.0,
+            // ];
+            let left_cover_color = cover_color([1.0, 1.0, 0.0, 1.0]);
+            let left_cover = Mesh {
+                vertices: vec![
+                    Vertex {
+                        pos: [-1.0, -1.0, 0.0, 1.0],
+                        uv: [0.0, 0.0],
+                        color: left_cover_color,
+                        normal: [0.0, 0.0, 1.0],
+                        tangent: [1.0, 0.0, 0.0, 1.0],
+                        joints: [0; 4],
+                        weights: [0.0; 4],
+                    },
+                    Vertex {
+                        pos: [-1.0, 1.0, 0.0, 1.0],
+                        uv: [0.0, 1.0],
+                        color: left_cover_color,
+                        normal: [0.0, 0.0, 1.0],
+                        tangent: [1.0, 0.0, 0.0, 1.0],
+                        joints: [0; 4],
+                        weights: [0.0; 4],
+                    },
+                    Vertex {
+                        pos: [0.0, 1.0, 0.0, 1.0],
+                        uv: [1.0, 1.0],
+                        color: left_cover_color,
+                        normal: [0.0, 0.0, 1.0],
+                        tangent: [1.0, 0.0, 0.0, 1.0],
+                        joints: [0; 4],
+                        weights: [0.0; 4],
+                    },
+                    Vertex {
+                        pos: [0.0, -1.0, 0.0, 1.0],
+                        uv: [1.0, 0.0],
+                        color: left_cover_color,
+                        normal: [0.0, 0.0, 1.0],
+                        tangent: [1.0, 0.0, 0.0, 1.0],
+                        joints: [0; 4],
+                        weights: [0.0; 4],
+                    },
+                ],
+                // Counter-clockwise facing the camera, see `PipelineOptions`
+                indices: vec![0u32, 3, 2, 2, 1, 0],
+                transform: Mat4::from_translation(glam::Vec3::new(0.0, 0.2, 0.0)),
+                user_id: 1,
+            };
+            let registered_square = left_cover
+                .register(&device, &device_memory_properties, Some(upload))
+                .with_blend(true);
+            projection_registered_meshes.insert(registered_square);
+
+            // MARK: RIGHT_SCREEN_COVER
+            // let right_cover_color = [
+            //     0.13333333333333333,
+            //     0.13333333333333333,
+            //     0.21176470588235294,
+            //     1.0,
+            // ];
+            let right_cover_color = cover_color([0.0, 1.0, 1.0, 1.0]);
+            let right_cover = Mesh {
+                vertices: vec![
+                    Vertex {
+                        pos: [0.0, -1.0, 0.0, 1.0],
+                        uv: [0.0, 0.0],
+                        color: right_cover_color,
+                        normal: [0.0, 0.0, 1.0],
+                        tangent: [1.0, 0.0, 0.0, 1.0],
+                        joints: [0; 4],
+                        weights: [0.0; 4],
+                    },
+                    Vertex {
+                        pos: [0.0, 1.0, 0.0, 1.0],
+                        uv: [0.0, 1.0],
+                        color: right_cover_color,
+                        normal: [0.0, 0.0, 1.0],
+                        tangent: [1.0, 0.0, 0.0, 1.0],
+                        joints: [0; 4],
+                        weights: [0.0; 4],
+                    },
+                    Vertex {
+                        pos: [1.0, 1.0, 0.0, 1.0],
+                        uv: [1.0, 1.0],
+                        color: right_cover_color,
+                        normal: [0.0, 0.0, 1.0],
+                        tangent: [1.0, 0.0, 0.0, 1.0],
+                        joints: [0; 4],
+                        weights: [0.0; 4],
+                    },
+                    Vertex {
+                        pos: [1.0, -1.0, 0.0, 1.0],
+                        uv: [1.0, 0.0],
+                        color: right_cover_color,
+                        normal: [0.0, 0.0, 1.0],
+                        tangent: [1.0, 0.0, 0.0, 1.0],
+                        joints: [0; 4],
+                        weights: [0.0; 4],
+                    },
+                ],
+                indices: vec![0u32, 3, 2, 2, 1, 0],
+                transform: Mat4::from_translation(glam::Vec3::new(0.0, -0.2, 0.0)),
+                user_id: 2,
+            };
+            let registered_square = right_cover
+                .register(&device, &device_memory_properties, Some(upload))
+                .with_blend(true);
+            projection_registered_meshes.insert(registered_square);
+        }
+
+        // The demo scene is authored Y up
+        let demo_conversion = WorldConvention::YUp.conversion_to(convention);
+        for (_, registered_mesh) in projection_registered_meshes.iter_mut() {
+            registered_mesh.mesh.transform = demo_conversion * registered_mesh.mesh.transform;
+        }
+
+        phases.push(("meshes", meshes_start.elapsed()));
 
         // MARK: Cameras
         let ui_projection = OrthographicProjection::new(
@@ -669,15 +671,16 @@ impl AAAResources {
             width as f32 / height as f32,
             0.01,
             1000.0,
-            Mat4::from_translation(glam::Vec3::new(0.0, 0.0, -4.0)),
+            Mat4::IDENTITY,
         );
         let camera = Camera::new(
-            Vec3::new(0.0, 0.0, 4.0),
+            demo_conversion.transform_point3(Vec3::new(0.0, 0.0, 4.0)),
+            convention,
             ui_projection,
             worldspace_projection,
         );
 
-        Self {
+        let mut resources = Self {
             device: Arc::new(device),
 
             draw_command_buffer,
@@ -686,6 +689,8 @@ impl AAAResources {
             depth_image,
             depth_image_view,
             depth_image_memory,
+            samples,
+            msaa_color,
 
             present_images,
             present_image_views,
@@ -699,15 +704,15 @@ impl AAAResources {
             vertex_shader_module,
             fragment_shader_module,
 
-            image_buffer_memory,
-            image_buffer,
-            texture_memory,
-            tex_image_view,
-            texture_image,
+            textures: Vec::new(),
+            texture_uploads: None,
+            texture_array: None,
+            environment: None,
+            irradiance,
+            environment_uniform,
 
             desc_set_layouts,
             descriptor_pool,
-            texture_sampler,
 
             uniform_color_buffer_memory,
             uniform_color_buffer,
@@ -720,40 +725,279 @@ impl AAAResources {
             swapchain,
 
             framebuffers,
-            viewports,
-            scissors,
+            readback,
+            export,
+            viewport,
 
             descriptor_sets,
             graphic_pipeline,
+            ui_pipeline,
+            mesh_pipelines,
+            line_pipeline,
 
             projection_registered_meshes,
             orthographic_registered_meshes,
+            gizmo_registered_meshes: Vec::new(),
+            reference_registered_meshes: Vec::new(),
 
             device_memory_properties,
+            memory_budget,
 
-            uniform,
             camera,
+        };
+
+        // MARK: TEXTURE
+        // The covers sample the bundled picture, an empty scene makes no texture until a frame
+        // samples it
+        if demo_scene {
+            let texture_start = std::time::Instant::now();
+            resources.ensure_default_texture();
+            #[cfg(feature = "images")]
+            {
+                let pixels = default_picture_pixels();
+                let mut staging = crate::vulkan::upload::AAAStagingBuffer::new(
+                    &resources.device,
+                    &resources.device_memory_properties,
+                    pixels.len() as u64,
+                );
+                staging.write(0, &pixels);
+                if let Some(uploads) = &resources.texture_uploads {
+                    uploads.upload_initial(&resources.device, upload, staging);
+                }
+            }
+            phases.push(("texture", texture_start.elapsed()));
+        }
+        crate::metrics::record_startup_phases("Window renderer", phases);
+
+        resources
+    }
+
+    /// Textures and meshes created so far, at startup the demo scene's alone, see
+    /// [`StartupContents::of`].
+    pub fn contents(&self) -> StartupContents {
+        StartupContents {
+            textures: self.textures.len(),
+            meshes: self.projection_registered_meshes.len()
+                + self.orthographic_registered_meshes.len()
+                + self.gizmo_registered_meshes.len()
+                + self.reference_registered_meshes.len(),
+        }
+    }
+
+    /// Create the built-in texture, filled white by the next frame, with its uploads and the
+    /// default material's set, unless it exists. Called before a frame samples it.
+    pub fn ensure_default_texture(&mut self) {
+        if !self.textures.is_empty() {
+            return;
+        }
+        let texture = Texture::blank(
+            &self.device,
+            &self.device_memory_properties,
+            default_picture_extent(),
+            SamplerDesc::default(),
+        )
+        .expect("Failed to create the default texture");
+        let uploads = AAATextureUploads::new(
+            &self.device,
+            &self.device_memory_properties,
+            texture.image,
+            texture.extent,
+            texture.mip_levels,
+        );
+        self.write_default_material(&texture);
+        self.textures.push(texture);
+        self.texture_uploads = Some(uploads);
+    }
+
+    /// Record the first fill of the built-in texture, if it exists, see
+    /// [`AAATextureUploads::record_default`].
+    pub fn record_default_texture(&self, device: &AAADevice, command_buffer: vk::CommandBuffer) {
+        if let Some(uploads) = &self.texture_uploads {
+            uploads.record_default(device, command_buffer);
+        }
+    }
+
+    /// Build the gizmo handles of every mode, unless they exist. Waits for the uploads.
+    pub fn ensure_gizmo_meshes(&mut self) {
+        if !self.gizmo_registered_meshes.is_empty() {
+            return;
+        }
+        let upload = self.upload_context();
+        for mode in [GizmoMode::Translate, GizmoMode::Rotate, GizmoMode::Scale] {
+            for axis in GizmoAxis::ALL {
+                let registered_handle = Gizmo::handle_mesh(mode, axis).register(
+                    &self.device,
+                    &self.device_memory_properties,
+                    Some(upload),
+                );
+                self.gizmo_registered_meshes
+                    .push((mode, axis, registered_handle));
+            }
+        }
+    }
+
+    /// Build the ground grid of the camera's convention and the world axes, unless they exist.
+    /// Waits for the uploads.
+    pub fn ensure_reference_meshes(&mut self) {
+        if !self.reference_registered_meshes.is_empty() {
+            return;
         }
+        let upload = self.upload_context();
+        let convention = self.camera.convention();
+        self.reference_registered_meshes = [
+            crate::world::reference_grid(convention, REFERENCE_GRID_HALF_EXTENT, 1.0),
+            crate::world::axis_gizmo(1.0),
+        ]
+        .map(|mesh| mesh.register(&self.device, &self.device_memory_properties, Some(upload)))
+        .into();
     }
 
-    // TODO reuse at creation and recreation
-    pub fn recreate_viewports(&mut self, width: u32, height: u32) {
-        self.viewports = [vk::Viewport {
-            x: 0.0,
-            y: 0.0,
-            width: width as f32,
-            height: height as f32,
-            min_depth: 0.0,
-            max_depth: 1.0,
-        }];
+    /// Point the default material's set at `texture`, allocating the set the first time.
+    fn write_default_material(&mut self, texture: &Texture) {
+        if self.descriptor_sets.default_material == vk::DescriptorSet::null() {
+            self.descriptor_sets.default_material = allocate_set(
+                &self.device,
+                self.descriptor_pool,
+                self.desc_set_layouts[MATERIAL_SET as usize],
+            );
+        }
+        let descriptor = texture.descriptor();
+        let write = vk::WriteDescriptorSet {
+            dst_set: self.descriptor_sets.default_material,
+            descriptor_count: 1,
+            descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            p_image_info: &descriptor,
+            ..Default::default()
+        };
+        unsafe { self.device.ash.update_descriptor_sets(&[write], &[]) };
     }
 
-    // TODO reuse at creation and recreation
-    pub fn recreate_scissors(&mut self, width: u32, height: u32) {
-        self.scissors = [vk::Rect2D {
-            offset: vk::Offset2D { x: 0, y: 0 },
-            extent: vk::Extent2D { width, height },
-        }];
+    /// Destroy the buffers of `handle` once the last submitted frame is done with them.
+    /// Returns `false` when the mesh was already removed.
+    pub fn unregister_mesh(&mut self, handle: MeshHandle) -> bool {
+        let Some(mut registered_mesh) = self.projection_registered_meshes.remove(handle) else {
+            return false;
+        };
+        unsafe {
+            self.device
+                .ash
+                .wait_for_fences(&[self.draw_commands_reuse_fence], true, u64::MAX)
+                .expect("Wait for fence failed.");
+        }
+        registered_mesh.destroy(&self.device);
+        true
+    }
+
+    /// Sample `texture`, filled, in place of the built-in texture and stream the updates into it.
+    /// The commands sampling the previous one must have completed.
+    pub fn replace_default_texture(&mut self, texture: Texture) {
+        if let Some((texture, uploads)) = self.swap_default_texture(texture) {
+            uploads.destroy(&self.device);
+            texture.destroy(&self.device);
+        }
+    }
+
+    /// Sample `texture` in place of the built-in texture like [`Self::replace_default_texture`],
+    /// returns the previous texture and its uploads for the caller to destroy once no frame
+    /// reads them, `None` when there was none yet. The set must not be in use by pending
+    /// commands.
+    pub fn swap_default_texture(
+        &mut self,
+        texture: Texture,
+    ) -> Option<(Texture, AAATextureUploads)> {
+        let mut uploads = AAATextureUploads::new(
+            &self.device,
+            &self.device_memory_properties,
+            texture.image,
+            texture.extent,
+            texture.mip_levels,
+        );
+        uploads.compressed = texture.format != self.device.texture_format;
+        uploads.filled();
+        self.write_default_material(&texture);
+        let previous_uploads = self.texture_uploads.replace(uploads);
+        if self.textures.is_empty() {
+            self.textures.push(texture);
+            return None;
+        }
+        Some((
+            mem::replace(&mut self.textures[0], texture),
+            previous_uploads.expect("The built-in texture is made with its uploads"),
+        ))
+    }
+
+    /// Sample `texture_array` through the texture array set in place of the previous array. The
+    /// commands sampling the previous one must have completed.
+    pub fn replace_texture_array(&mut self, texture_array: TextureArray) {
+        if self.descriptor_sets.texture_array == vk::DescriptorSet::null() {
+            self.descriptor_sets.texture_array = allocate_set(
+                &self.device,
+                self.descriptor_pool,
+                self.desc_set_layouts[MATERIAL_SET as usize],
+            );
+        }
+        let descriptor = texture_array.descriptor();
+        let write = vk::WriteDescriptorSet {
+            dst_set: self.descriptor_sets.texture_array,
+            descriptor_count: 1,
+            descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            p_image_info: &descriptor,
+            ..Default::default()
+        };
+        unsafe { self.device.ash.update_descriptor_sets(&[write], &[]) };
+        if let Some(previous) = self.texture_array.replace(texture_array) {
+            previous.destroy(&self.device);
+        }
+    }
+
+    /// Sample `cubemap` through the global set's [`ENVIRONMENT_BINDING`] and `irradiance` through
+    /// its [`IRRADIANCE_BINDING`] in place of the previous ones, returned for the caller to
+    /// destroy once the frames sampling them completed. Without a cubemap the environment binding
+    /// samples `irradiance`. The set must not be in use by a pending frame.
+    pub fn replace_environment(
+        &mut self,
+        cubemap: Option<Texture>,
+        irradiance: Texture,
+    ) -> (Option<Texture>, Texture) {
+        let environment = cubemap.as_ref().unwrap_or(&irradiance).descriptor();
+        let irradiance_descriptor = irradiance.descriptor();
+        let writes = [
+            (ENVIRONMENT_BINDING, &environment),
+            (IRRADIANCE_BINDING, &irradiance_descriptor),
+        ]
+        .map(|(dst_binding, descriptor)| vk::WriteDescriptorSet {
+            dst_set: self.descriptor_sets.global,
+            dst_binding,
+            descriptor_count: 1,
+            descriptor_type: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            p_image_info: descriptor,
+            ..Default::default()
+        });
+        unsafe { self.device.ash.update_descriptor_sets(&writes, &[]) };
+        (
+            mem::replace(&mut self.environment, cubemap),
+            mem::replace(&mut self.irradiance, irradiance),
+        )
+    }
+
+    /// Scale the irradiance by `intensity`. No pending frame may read the uniform.
+    pub fn write_environment_intensity(&self, intensity: f32) {
+        let uniform = &self.environment_uniform;
+        write_mapped(
+            &self.device,
+            uniform.memory,
+            uniform.size,
+            &[Vec4::new(intensity, 0.0, 0.0, 0.0)],
+        );
+    }
+
+    /// Mesh uploads share the setup command buffer.
+    pub fn upload_context(&self) -> AAAUploadContext {
+        AAAUploadContext {
+            command_buffer: self.setup_command_buffer,
+            fence: self.setup_commands_reuse_fence,
+            queue: self.swapchain.present_queue,
+        }
     }
 
     // TODO on creation also register the depth image memory instead of code dupe
@@ -803,52 +1047,135 @@ impl Drop for AAAResources {
         unsafe {
             self.device.ash.device_wait_idle().unwrap();
 
+            crate::object_audit::destroyed(self.vertex_shader_module);
             self.device
                 .ash
                 .destroy_shader_module(self.vertex_shader_module, None);
+            crate::object_audit::destroyed(self.fragment_shader_module);
             self.device
                 .ash
                 .destroy_shader_module(self.fragment_shader_module, None);
 
-            self.device.ash.free_memory(self.image_buffer_memory, None);
-            self.device.ash.destroy_buffer(self.image_buffer, None);
-            self.device.ash.free_memory(self.texture_memory, None);
+            for texture in &self.textures {
+                texture.destroy(&self.device);
+            }
+            if let Some(uploads) = &self.texture_uploads {
+                uploads.destroy(&self.device);
+            }
+            if let Some(texture_array) = &self.texture_array {
+                texture_array.destroy(&self.device);
+            }
+            if let Some(environment) = &self.environment {
+                environment.destroy(&self.device);
+            }
+            self.irradiance.destroy(&self.device);
+            crate::object_audit::destroyed(self.environment_uniform.memory);
             self.device
                 .ash
-                .destroy_image_view(self.tex_image_view, None);
-            self.device.ash.destroy_image(self.texture_image, None);
-
-            for registered_mesh in self.projection_registered_meshes.iter() {
-                self.device
-                    .ash
-                    .free_memory(registered_mesh.index_buffer_memory, None);
-                self.device
-                    .ash
-                    .destroy_buffer(registered_mesh.index_buffer, None);
-                self.device
-                    .ash
-                    .free_memory(registered_mesh.vertex_buffer_memory, None);
-                self.device
-                    .ash
-                    .destroy_buffer(registered_mesh.vertex_buffer, None);
+                .free_memory(self.environment_uniform.memory, None);
+            crate::object_audit::destroyed(self.environment_uniform.buffer);
+            self.device
+                .ash
+                .destroy_buffer(self.environment_uniform.buffer, None);
+
+            for registered_mesh in self
+                .projection_registered_meshes
+                .iter_mut()
+                .map(|(_, mesh)| mesh)
+                .chain(self.orthographic_registered_meshes.iter_mut())
+                .chain(
+                    self.gizmo_registered_meshes
+                        .iter_mut()
+                        .map(|(_, _, mesh)| mesh),
+                )
+                .chain(self.reference_registered_meshes.iter_mut())
+            {
+                registered_mesh.destroy(&self.device);
             }
 
             for &descriptor_set_layout in self.desc_set_layouts.iter() {
+                crate::object_audit::destroyed(descriptor_set_layout);
                 self.device
                     .ash
                     .destroy_descriptor_set_layout(descriptor_set_layout, None);
             }
+            crate::object_audit::destroyed(self.descriptor_pool);
             self.device
                 .ash
                 .destroy_descriptor_pool(self.descriptor_pool, None);
-            self.device.ash.destroy_sampler(self.texture_sampler, None);
 
+            crate::object_audit::destroyed(self.uniform_color_buffer_memory);
             self.device
                 .ash
                 .free_memory(self.uniform_color_buffer_memory, None);
+            crate::object_audit::destroyed(self.uniform_color_buffer);
             self.device
                 .ash
                 .destroy_buffer(self.uniform_color_buffer, None);
         }
     }
 }
+
+/// Irradiance of an environment of [`FLAT_AMBIENT`] everywhere, lighting as the former constant
+/// ambient did. Waits for the copies.
+pub fn flat_irradiance(
+    device: &AAADevice,
+    device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    upload: AAAUploadContext,
+) -> Result<Texture, Box<dyn std::error::Error>> {
+    irradiance_texture(
+        device,
+        device_memory_properties,
+        upload,
+        &Cubemap::from_fn(1, |_| Vec3::splat(FLAT_AMBIENT)),
+    )
+}
+
+/// Upload `irradiance` in the device's texture format, sampled as the linear irradiance.
+pub fn irradiance_texture(
+    device: &AAADevice,
+    device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    upload: AAAUploadContext,
+    irradiance: &Cubemap,
+) -> Result<Texture, Box<dyn std::error::Error>> {
+    let srgb = device.texture_format == vk::Format::R8G8B8A8_SRGB;
+    Texture::cubemap_from_rgba8(
+        device,
+        device_memory_properties,
+        upload,
+        &irradiance.to_rgba8(srgb),
+        irradiance.size,
+        SamplerDesc::default(),
+    )
+}
+
+/// Size of the built-in texture, the bundled picture's. Only its header is read, the picture is
+/// decoded for the demo scene alone, otherwise the first frame fills the texture white.
+#[cfg(feature = "images")]
+fn default_picture_extent() -> vk::Extent2D {
+    let picture = std::io::Cursor::new(include_bytes!("../../assets/img/picture.png"));
+    let (width, height) = image::ImageReader::new(picture)
+        .with_guessed_format()
+        .unwrap()
+        .into_dimensions()
+        .unwrap();
+    vk::Extent2D { width, height }
+}
+
+/// The bundled picture as RGBA8 rows, sized as [`default_picture_extent`].
+#[cfg(feature = "images")]
+fn default_picture_pixels() -> Vec<u8> {
+    image::load_from_memory(include_bytes!("../../assets/img/picture.png"))
+        .expect("The bundled picture is a valid PNG")
+        .to_rgba8()
+        .into_raw()
+}
+
+/// Without an image decoder the built-in texture keeps the picture's size.
+#[cfg(not(feature = "images"))]
+fn default_picture_extent() -> vk::Extent2D {
+    vk::Extent2D {
+        width: 1024,
+        height: 1024,
+    }
+}
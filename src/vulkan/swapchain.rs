@@ -1,4 +1,5 @@
 use super::{device::AAADevice, surface::AAASurface, AAABase};
+use crate::present_health::{self, PresentModePreference};
 use ash::{khr::swapchain, vk};
 
 pub struct AAASwapchainLoader {
@@ -15,11 +16,19 @@ impl AAASwapchainLoader {
 pub struct AAASwapchain {
     pub swapchain_khr: vk::SwapchainKHR,
     pub _desired_image_count: u32,
-    pub _present_mode: vk::PresentModeKHR,
+    /// Resolved from the preference the swapchain was created with.
+    pub present_mode: vk::PresentModeKHR,
     pub present_queue: vk::Queue,
+    pub image_usage: vk::ImageUsageFlags,
+    /// Of the swapchain images, what the framebuffers, depth and viewport are sized to. See
+    /// [`image_extent`].
+    pub extent: vk::Extent2D,
+    /// Transform the surface reported when the swapchain was created.
+    pub surface_transform: vk::SurfaceTransformFlagsKHR,
 }
 
 impl AAASwapchain {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         device: &AAADevice,
         base: &AAABase,
@@ -29,17 +38,18 @@ impl AAASwapchain {
         width: u32,
         height: u32,
         swapchain_loader: &AAASwapchainLoader,
+        preference: PresentModePreference,
     ) -> Self {
         let present_modes = unsafe {
             base.surface_loader
                 .get_physical_device_surface_present_modes(pdevice, surface.surface_khr)
                 .unwrap()
         };
-        let present_mode = present_modes
-            .iter()
-            .cloned()
-            .find(|&mode| mode == vk::PresentModeKHR::MAILBOX)
-            .unwrap_or(vk::PresentModeKHR::FIFO);
+        let (vendor_id, device_id) = pci_ids(&base.instance, pdevice);
+        let present_mode = preference.resolve(
+            &present_modes,
+            present_health::is_downgraded(vendor_id, device_id),
+        );
 
         let present_queue = unsafe { device.ash.get_device_queue(queue_family_index, 0) };
 
@@ -49,10 +59,7 @@ impl AAASwapchain {
         {
             desired_image_count = surface.capabilities.max_image_count;
         }
-        let surface_resolution = match surface.capabilities.current_extent.width {
-            u32::MAX => vk::Extent2D { width, height },
-            _ => surface.capabilities.current_extent,
-        };
+        let extent = image_extent(&surface.capabilities, vk::Extent2D { width, height });
         let pre_transform = if surface
             .capabilities
             .supported_transforms
@@ -63,13 +70,23 @@ impl AAASwapchain {
             surface.capabilities.current_transform
         };
 
+        // Transfer source lets the replay buffer blit presented images
+        let mut image_usage = vk::ImageUsageFlags::COLOR_ATTACHMENT;
+        if surface
+            .capabilities
+            .supported_usage_flags
+            .contains(vk::ImageUsageFlags::TRANSFER_SRC)
+        {
+            image_usage |= vk::ImageUsageFlags::TRANSFER_SRC;
+        }
+
         let swapchain_create_info = vk::SwapchainCreateInfoKHR::default()
             .surface(surface.surface_khr)
             .min_image_count(desired_image_count)
             .image_color_space(surface.format.color_space)
             .image_format(surface.format.format)
-            .image_extent(surface_resolution)
-            .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
+            .image_extent(extent)
+            .image_usage(image_usage)
             .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
             .pre_transform(pre_transform)
             .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
@@ -83,12 +100,94 @@ impl AAASwapchain {
                 .create_swapchain(&swapchain_create_info, None)
                 .unwrap()
         };
+        crate::object_audit::created(swapchain, "window swapchain");
 
         AAASwapchain {
             swapchain_khr: swapchain,
             _desired_image_count: desired_image_count,
-            _present_mode: present_mode,
+            present_mode,
             present_queue,
+            image_usage,
+            extent,
+            surface_transform: surface.capabilities.current_transform,
+        }
+    }
+
+    /// Whether `capabilities` are those of the surface the swapchain was created for, in a window
+    /// of `window` physical pixels. A suboptimal swapchain that fits is kept, recreating it would
+    /// not change anything.
+    pub fn fits(&self, capabilities: &vk::SurfaceCapabilitiesKHR, window: vk::Extent2D) -> bool {
+        image_extent(capabilities, window) == self.extent
+            && capabilities.current_transform == self.surface_transform
+    }
+}
+
+/// Extent of the swapchain images on a surface reporting `capabilities`: its current extent, or
+/// the window's inner size `window` within the surface's limits where the surface lets the
+/// swapchain decide, its current extent being the special value `0xFFFFFFFF` (Wayland).
+pub fn image_extent(
+    capabilities: &vk::SurfaceCapabilitiesKHR,
+    window: vk::Extent2D,
+) -> vk::Extent2D {
+    if capabilities.current_extent.width != u32::MAX {
+        return capabilities.current_extent;
+    }
+    let (min, max) = (capabilities.min_image_extent, capabilities.max_image_extent);
+    vk::Extent2D {
+        width: window.width.clamp(min.width, max.width.max(min.width)),
+        height: window.height.clamp(min.height, max.height.max(min.height)),
+    }
+}
+
+/// PCI vendor and device ids, identifying the device across windows.
+pub fn pci_ids(instance: &ash::Instance, pdevice: vk::PhysicalDevice) -> (u32, u32) {
+    let properties = unsafe { instance.get_physical_device_properties(pdevice) };
+    (properties.vendor_id, properties.device_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capabilities(current_extent: vk::Extent2D) -> vk::SurfaceCapabilitiesKHR {
+        vk::SurfaceCapabilitiesKHR {
+            current_extent,
+            min_image_extent: vk::Extent2D {
+                width: 1,
+                height: 1,
+            },
+            max_image_extent: vk::Extent2D {
+                width: 4096,
+                height: 4096,
+            },
+            ..Default::default()
         }
     }
+
+    #[test]
+    fn the_window_sizes_the_images_where_the_surface_does_not() {
+        let window = vk::Extent2D {
+            width: 800,
+            height: 600,
+        };
+        let reported = vk::Extent2D {
+            width: 1024,
+            height: 768,
+        };
+        assert_eq!(image_extent(&capabilities(reported), window), reported);
+        let undefined = vk::Extent2D {
+            width: u32::MAX,
+            height: u32::MAX,
+        };
+        assert_eq!(image_extent(&capabilities(undefined), window), window);
+        let huge = vk::Extent2D {
+            width: 10_000,
+            height: 600,
+        };
+        assert_eq!(
+            image_extent(&capabilities(undefined), huge).width,
+            4096,
+            "Past the surface's limits"
+        );
+    }
 }
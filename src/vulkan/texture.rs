@@ -0,0 +1,495 @@
+use super::{
+    device::AAADevice,
+    sampler::create_sampler,
+    texture_upload::{mip_levels, upload_levels, upload_whole},
+    upload::{AAAStagingBuffer, AAAUploadContext},
+    views::find_memorytype_index,
+};
+use crate::{
+    block_compression::CompressedTexture,
+    error::PulsarError,
+    texture::{SamplerDesc, CUBE_FACES, TEXEL_SIZE},
+};
+use ash::vk;
+use log::warn;
+use std::error::Error;
+
+/// Sampled RGBA8 image, in the device's `texture_format`, with its memory and view, freed together by [`Texture::destroy`].
+/// Block compressed textures keep the `format` of their blocks.
+pub struct Texture {
+    pub image: vk::Image,
+    pub format: vk::Format,
+    pub memory: vk::DeviceMemory,
+    pub view: vk::ImageView,
+    /// Shared with the textures sampled alike, owned by the device's sampler cache.
+    pub sampler: vk::Sampler,
+    pub extent: vk::Extent2D,
+    /// Down to 1x1, or 1 when the device cannot blit the chain, see `AAADevice::mipmaps`.
+    pub mip_levels: u32,
+    /// Array layers of the image, 1 unless viewed as a `TYPE_2D_ARRAY`, see `TextureArray`, or a
+    /// `CUBE` of 6.
+    pub layers: u32,
+}
+
+impl Texture {
+    /// Texture of `extent` with undefined contents, filled by a copy before it is first sampled.
+    /// Has a full mip chain when the device can generate it.
+    pub fn blank(
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        extent: vk::Extent2D,
+        sampler: SamplerDesc,
+    ) -> Result<Self, Box<dyn Error>> {
+        let view_type = vk::ImageViewType::TYPE_2D;
+        Self::create(
+            device,
+            device_memory_properties,
+            extent,
+            1,
+            view_type,
+            sampler,
+        )
+    }
+
+    /// Like [`Self::blank`] with `layers` array layers of `extent`, viewed as a `TYPE_2D_ARRAY`
+    /// whatever their count.
+    pub fn blank_array(
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        extent: vk::Extent2D,
+        layers: u32,
+        sampler: SamplerDesc,
+    ) -> Result<Self, Box<dyn Error>> {
+        let view_type = vk::ImageViewType::TYPE_2D_ARRAY;
+        Self::create(
+            device,
+            device_memory_properties,
+            extent,
+            layers,
+            view_type,
+            sampler,
+        )
+    }
+
+    /// Cubemap of 6 square faces of `size`, viewed as a `CUBE`. Larger than the device's
+    /// `maxImageDimensionCube` is an error.
+    pub fn blank_cube(
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        size: u32,
+        sampler: SamplerDesc,
+    ) -> Result<Self, Box<dyn Error>> {
+        if size == 0 || size > device.max_image_dimension_cube {
+            return Err(format!(
+                "Cubemap faces of {size}x{size}, at most {}",
+                device.max_image_dimension_cube
+            )
+            .into());
+        }
+        let extent = vk::Extent2D {
+            width: size,
+            height: size,
+        };
+        let view_type = vk::ImageViewType::CUBE;
+        Self::create(
+            device,
+            device_memory_properties,
+            extent,
+            CUBE_FACES as u32,
+            view_type,
+            sampler,
+        )
+    }
+
+    fn create(
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        extent: vk::Extent2D,
+        layers: u32,
+        view_type: vk::ImageViewType,
+        sampler: SamplerDesc,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mip_levels = if device.mipmaps {
+            mip_levels(extent)
+        } else {
+            1
+        };
+        // Source and destination of the mip blits, read back by the texture thumbnails
+        let usage = vk::ImageUsageFlags::TRANSFER_DST
+            | vk::ImageUsageFlags::TRANSFER_SRC
+            | vk::ImageUsageFlags::SAMPLED;
+        Self::allocate(
+            device,
+            device_memory_properties,
+            device.texture_format,
+            extent,
+            mip_levels,
+            layers,
+            view_type,
+            usage,
+            sampler,
+        )
+    }
+
+    /// Image of `format` with its memory, view and sampler.
+    #[allow(clippy::too_many_arguments)]
+    fn allocate(
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        format: vk::Format,
+        extent: vk::Extent2D,
+        mip_levels: u32,
+        layers: u32,
+        view_type: vk::ImageViewType,
+        usage: vk::ImageUsageFlags,
+        sampler: SamplerDesc,
+    ) -> Result<Self, Box<dyn Error>> {
+        let flags = if view_type == vk::ImageViewType::CUBE {
+            vk::ImageCreateFlags::CUBE_COMPATIBLE
+        } else {
+            vk::ImageCreateFlags::empty()
+        };
+        let image_info = vk::ImageCreateInfo {
+            flags,
+            image_type: vk::ImageType::TYPE_2D,
+            format,
+            extent: extent.into(),
+            mip_levels,
+            array_layers: layers,
+            samples: vk::SampleCountFlags::TYPE_1,
+            tiling: vk::ImageTiling::OPTIMAL,
+            usage,
+            sharing_mode: vk::SharingMode::EXCLUSIVE,
+            ..Default::default()
+        };
+        let image = unsafe { device.ash.create_image(&image_info, None)? };
+        crate::object_audit::created(image, "texture");
+        // Whatever was created is destroyed when a later step fails
+        let mut texture = Self {
+            image,
+            format,
+            memory: vk::DeviceMemory::null(),
+            view: vk::ImageView::null(),
+            sampler: vk::Sampler::null(),
+            extent,
+            mip_levels,
+            layers,
+        };
+        if let Err(err) = texture.bind_view_sampler(
+            device,
+            device_memory_properties,
+            &image_info,
+            view_type,
+            sampler,
+        ) {
+            texture.destroy(device);
+            return Err(err);
+        }
+        Ok(texture)
+    }
+
+    /// Texture of `width` by `height` RGBA8 texels, rows tightly packed from the top left. Waits
+    /// for the copy.
+    pub fn from_rgba8(
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        upload: AAAUploadContext,
+        data: &[u8],
+        width: u32,
+        height: u32,
+        sampler: SamplerDesc,
+    ) -> Result<Self, Box<dyn Error>> {
+        let expected = width as usize * height as usize * TEXEL_SIZE as usize;
+        if width == 0 || height == 0 || data.len() != expected {
+            return Err(format!(
+                "{} bytes of texels for a {width}x{height} texture, expected {expected}",
+                data.len()
+            )
+            .into());
+        }
+        let extent = vk::Extent2D { width, height };
+        let texture = Self::blank(device, device_memory_properties, extent, sampler)?;
+        let mut staging =
+            AAAStagingBuffer::new(device, device_memory_properties, data.len() as u64);
+        staging.write(0, data);
+        upload_whole(
+            device,
+            upload,
+            texture.image,
+            extent,
+            texture.mip_levels,
+            0,
+            vk::ImageLayout::UNDEFINED,
+            staging,
+        );
+        Ok(texture)
+    }
+
+    /// Texture of the blocks of `compressed` as they are, in the `_SRGB` format when the device's
+    /// `texture_format` is. Only the levels given are copied, the blocks cannot be blitted into
+    /// more. A format the device cannot sample is a
+    /// [`PulsarError::UnsupportedTextureFormat`]. Waits for the copy.
+    pub fn from_compressed(
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        upload: AAAUploadContext,
+        compressed: &CompressedTexture,
+        sampler: SamplerDesc,
+    ) -> Result<Self, Box<dyn Error>> {
+        let srgb = device.texture_format == vk::Format::R8G8B8A8_SRGB;
+        let format = compressed.format.vk_format(srgb);
+        if !device.compressed_formats.contains(&format) {
+            return Err(PulsarError::UnsupportedTextureFormat {
+                format: compressed.format,
+            }
+            .into());
+        }
+        let extent = vk::Extent2D {
+            width: compressed.width,
+            height: compressed.height,
+        };
+        let mip_levels = compressed.levels().len() as u32;
+        let texture = Self::allocate(
+            device,
+            device_memory_properties,
+            format,
+            extent,
+            mip_levels,
+            1,
+            vk::ImageViewType::TYPE_2D,
+            vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
+            sampler,
+        )?;
+        let data = compressed.data();
+        let mut staging =
+            AAAStagingBuffer::new(device, device_memory_properties, data.len() as u64);
+        staging.write(0, &data);
+        upload_levels(
+            device,
+            upload,
+            texture.image,
+            mip_levels,
+            &compressed.copy_regions(),
+            staging,
+        );
+        Ok(texture)
+    }
+
+    /// [`Self::from_compressed`], or its first level decoded to RGBA8 with the chain generated
+    /// from it when the device cannot sample the format.
+    pub fn from_compressed_or_decoded(
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        upload: AAAUploadContext,
+        compressed: &CompressedTexture,
+        sampler: SamplerDesc,
+    ) -> Result<Self, Box<dyn Error>> {
+        match Self::from_compressed(
+            device,
+            device_memory_properties,
+            upload,
+            compressed,
+            sampler,
+        ) {
+            Err(err)
+                if matches!(
+                    err.downcast_ref(),
+                    Some(PulsarError::UnsupportedTextureFormat { .. })
+                ) =>
+            {
+                warn!("{err}, decoding it to RGBA8");
+                Self::from_rgba8(
+                    device,
+                    device_memory_properties,
+                    upload,
+                    &compressed.decode_rgba8(),
+                    compressed.width,
+                    compressed.height,
+                    sampler,
+                )
+            }
+            result => result,
+        }
+    }
+
+    /// Decode the image at `path`, converted to RGBA8 whatever its format. Failures are a
+    /// [`PulsarError::TextureLoad`](crate::error::PulsarError::TextureLoad).
+    #[cfg(feature = "images")]
+    pub fn from_file(
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        upload: AAAUploadContext,
+        path: &std::path::Path,
+        sampler: SamplerDesc,
+    ) -> Result<Self, Box<dyn Error>> {
+        let error = |reason: String| PulsarError::TextureLoad {
+            path: path.to_path_buf(),
+            reason,
+        };
+        let pixels = decode_rgba8(path)?;
+        let (width, height) = pixels.dimensions();
+        Self::from_rgba8(
+            device,
+            device_memory_properties,
+            upload,
+            pixels.as_raw(),
+            width,
+            height,
+            sampler,
+        )
+        .map_err(|err| error(err.to_string()).into())
+    }
+
+    /// Cubemap of `faces`, RGBA8 texels of `size` by `size` in layer order: +X, -X, +Y, -Y, +Z,
+    /// -Z. Waits for the copies.
+    pub fn cubemap_from_rgba8(
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        upload: AAAUploadContext,
+        faces: &[Vec<u8>; CUBE_FACES],
+        size: u32,
+        sampler: SamplerDesc,
+    ) -> Result<Self, Box<dyn Error>> {
+        let expected = size as usize * size as usize * TEXEL_SIZE as usize;
+        if let Some(face) = faces.iter().position(|data| data.len() != expected) {
+            return Err(format!(
+                "{} bytes of texels for face {face} of a {size}x{size} cubemap, expected {expected}",
+                faces[face].len()
+            )
+            .into());
+        }
+        let texture = Self::blank_cube(device, device_memory_properties, size, sampler)?;
+        for (layer, data) in faces.iter().enumerate() {
+            let mut staging =
+                AAAStagingBuffer::new(device, device_memory_properties, data.len() as u64);
+            staging.write(0, data);
+            upload_whole(
+                device,
+                upload,
+                texture.image,
+                texture.extent,
+                texture.mip_levels,
+                layer as u32,
+                vk::ImageLayout::UNDEFINED,
+                staging,
+            );
+        }
+        Ok(texture)
+    }
+
+    /// How shaders sample the texture once filled.
+    pub fn descriptor(&self) -> vk::DescriptorImageInfo {
+        vk::DescriptorImageInfo {
+            image_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            image_view: self.view,
+            sampler: self.sampler,
+        }
+    }
+
+    fn bind_view_sampler(
+        &mut self,
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        image_info: &vk::ImageCreateInfo,
+        view_type: vk::ImageViewType,
+        sampler: SamplerDesc,
+    ) -> Result<(), Box<dyn Error>> {
+        let memory_req = unsafe { device.ash.get_image_memory_requirements(self.image) };
+        let memory_index = find_memorytype_index(
+            &memory_req,
+            device_memory_properties,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )
+        .ok_or("No device local memory for the texture")?;
+        let allocate_info = vk::MemoryAllocateInfo {
+            allocation_size: memory_req.size,
+            memory_type_index: memory_index,
+            ..Default::default()
+        };
+        unsafe {
+            self.memory = device.ash.allocate_memory(&allocate_info, None)?;
+            crate::object_audit::created(self.memory, "texture");
+            device.ash.bind_image_memory(self.image, self.memory, 0)?;
+        }
+
+        self.sampler = create_sampler(device, sampler)?;
+
+        let view_info = vk::ImageViewCreateInfo {
+            view_type,
+            format: image_info.format,
+            components: vk::ComponentMapping {
+                r: vk::ComponentSwizzle::R,
+                g: vk::ComponentSwizzle::G,
+                b: vk::ComponentSwizzle::B,
+                a: vk::ComponentSwizzle::A,
+            },
+            subresource_range: vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                level_count: self.mip_levels,
+                layer_count: self.layers,
+                ..Default::default()
+            },
+            image: self.image,
+            ..Default::default()
+        };
+        self.view = unsafe { device.ash.create_image_view(&view_info, None)? };
+        crate::object_audit::created(self.view, "texture");
+        Ok(())
+    }
+
+    /// Bytes of its memory, what residency counts it for.
+    pub fn size_bytes(&self, device: &AAADevice) -> u64 {
+        unsafe { device.ash.get_image_memory_requirements(self.image) }.size
+    }
+
+    /// The commands sampling the texture must have completed. Null handles are skipped, the
+    /// sampler stays with the device.
+    pub fn destroy(&self, device: &AAADevice) {
+        unsafe {
+            crate::object_audit::destroyed(self.view);
+            device.ash.destroy_image_view(self.view, None);
+            crate::object_audit::destroyed(self.image);
+            device.ash.destroy_image(self.image, None);
+            crate::object_audit::destroyed(self.memory);
+            device.ash.free_memory(self.memory, None);
+        }
+    }
+}
+
+/// Decode the faces of `source` to RGBA8, in layer order, with their size. Faces that fail to
+/// decode are a [`PulsarError::TextureLoad`], faces not all square of one size a
+/// [`PulsarError::CubemapFace`], images of no known layout a [`PulsarError::CubemapLayout`].
+#[cfg(feature = "images")]
+pub fn decode_cubemap(
+    source: &crate::texture::CubemapSource,
+) -> Result<(u32, [Vec<u8>; CUBE_FACES]), PulsarError> {
+    use crate::texture::{cube_face_size, slice_cube_layout, CubemapSource};
+
+    match source {
+        CubemapSource::Faces(paths) => {
+            let mut decoded = Vec::with_capacity(CUBE_FACES);
+            for path in paths {
+                decoded.push(decode_rgba8(path)?);
+            }
+            let size = cube_face_size(std::array::from_fn(|face| decoded[face].dimensions()))?;
+            let faces = std::array::from_fn(|face| std::mem::take(&mut decoded[face]).into_raw());
+            Ok((size, faces))
+        }
+        CubemapSource::Layout(path) => {
+            let pixels = decode_rgba8(path)?;
+            let (width, height) = pixels.dimensions();
+            slice_cube_layout(pixels.as_raw(), width, height)
+        }
+    }
+}
+
+/// The image at `path` as RGBA8, a [`PulsarError::TextureLoad`] when it cannot be decoded.
+#[cfg(feature = "images")]
+pub fn decode_rgba8(path: &std::path::Path) -> Result<image::RgbaImage, PulsarError> {
+    Ok(image::open(path)
+        .map_err(|err| PulsarError::TextureLoad {
+            path: path.to_path_buf(),
+            reason: err.to_string(),
+        })?
+        .to_rgba8())
+}
@@ -0,0 +1,165 @@
+use super::{
+    device::AAADevice,
+    record::record_submit_commandbuffer,
+    texture::Texture,
+    texture_upload::upload_whole,
+    upload::{AAAStagingBuffer, AAAUploadContext},
+};
+use crate::{
+    error::PulsarError,
+    texture::{SamplerDesc, TEXEL_SIZE},
+};
+use ash::vk;
+use std::error::Error;
+
+/// Same sized RGBA8 layers sampled through one `sampler2DArray` descriptor, each mesh picking its
+/// layer. Layers are filled in the order they are pushed, the ones not pushed yet are white.
+pub struct TextureArray {
+    pub texture: Texture,
+    /// Layers pushed so far, the next one goes at this index.
+    pub pushed: u32,
+}
+
+impl TextureArray {
+    /// `layers` white layers of `extent`, more than the device's `maxImageArrayLayers` is a
+    /// [`PulsarError::TextureArrayLayers`]. Waits for the fill.
+    pub fn new(
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        upload: AAAUploadContext,
+        extent: vk::Extent2D,
+        layers: u32,
+        sampler: SamplerDesc,
+    ) -> Result<Self, Box<dyn Error>> {
+        if layers == 0 || layers > device.max_image_array_layers {
+            return Err(PulsarError::TextureArrayLayers {
+                requested: layers,
+                max: device.max_image_array_layers,
+            }
+            .into());
+        }
+        if extent.width == 0 || extent.height == 0 {
+            return Err(format!("Empty {}x{} texture array", extent.width, extent.height).into());
+        }
+        let texture =
+            Texture::blank_array(device, device_memory_properties, extent, layers, sampler)?;
+        clear_white(device, upload, &texture);
+        Ok(Self { texture, pushed: 0 })
+    }
+
+    /// Copy `data`, the layer's RGBA8 texels from the top left, into the next layer and return
+    /// its index. Pushing past the array's layers is a [`PulsarError::TextureArrayLayers`]. The
+    /// commands sampling the array must have completed, waits for the copy.
+    pub fn push_layer(
+        &mut self,
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        upload: AAAUploadContext,
+        data: &[u8],
+    ) -> Result<u32, Box<dyn Error>> {
+        let layer = self.pushed;
+        if layer >= self.texture.layers {
+            return Err(PulsarError::TextureArrayLayers {
+                requested: layer + 1,
+                max: self.texture.layers,
+            }
+            .into());
+        }
+        let vk::Extent2D { width, height } = self.texture.extent;
+        let expected = (width * height * TEXEL_SIZE) as usize;
+        if data.len() != expected {
+            return Err(format!(
+                "{} bytes of texels for a {width}x{height} layer, expected {expected}",
+                data.len()
+            )
+            .into());
+        }
+        let mut staging =
+            AAAStagingBuffer::new(device, device_memory_properties, data.len() as u64);
+        staging.write(0, data);
+        upload_whole(
+            device,
+            upload,
+            self.texture.image,
+            self.texture.extent,
+            self.texture.mip_levels,
+            layer,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            staging,
+        );
+        self.pushed += 1;
+        Ok(layer)
+    }
+
+    pub fn descriptor(&self) -> vk::DescriptorImageInfo {
+        self.texture.descriptor()
+    }
+
+    pub fn destroy(&self, device: &AAADevice) {
+        self.texture.destroy(device);
+    }
+}
+
+/// Every level of every layer white and `SHADER_READ_ONLY_OPTIMAL`, so the whole view is defined
+/// before the first layer is pushed.
+fn clear_white(device: &AAADevice, upload: AAAUploadContext, texture: &Texture) {
+    let subresource_range = vk::ImageSubresourceRange::default()
+        .aspect_mask(vk::ImageAspectFlags::COLOR)
+        .level_count(texture.mip_levels)
+        .layer_count(texture.layers);
+    let to_transfer = vk::ImageMemoryBarrier::default()
+        .image(texture.image)
+        .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+        .old_layout(vk::ImageLayout::UNDEFINED)
+        .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+        .subresource_range(subresource_range);
+    let to_shader = vk::ImageMemoryBarrier::default()
+        .image(texture.image)
+        .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+        .dst_access_mask(vk::AccessFlags::SHADER_READ)
+        .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+        .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+        .subresource_range(subresource_range);
+    record_submit_commandbuffer(
+        device,
+        upload.command_buffer,
+        upload.fence,
+        upload.queue,
+        &[],
+        &[],
+        &[],
+        |device, command_buffer| unsafe {
+            device.ash.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[to_transfer],
+            );
+            device.ash.cmd_clear_color_image(
+                command_buffer,
+                texture.image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &vk::ClearColorValue { float32: [1.0; 4] },
+                &[subresource_range],
+            );
+            device.ash.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[to_shader],
+            );
+        },
+    );
+    unsafe {
+        device
+            .ash
+            .wait_for_fences(&[upload.fence], true, u64::MAX)
+            .expect("Wait for fence failed.");
+    }
+}
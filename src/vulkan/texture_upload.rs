@@ -0,0 +1,547 @@
+use super::{
+    device::AAADevice,
+    record::record_submit_commandbuffer,
+    upload::{create_empty_buffer, AAAOwnedBuffer, AAAStagingBuffer, AAAUploadContext},
+};
+use crate::texture::{coalesce, TextureRegion, TextureUpdate, TEXEL_SIZE};
+use ash::vk;
+use log::warn;
+use std::{cell::Cell, ffi::c_void};
+
+/// Staging slots, one per frame that may still be copying plus the one being written.
+const SLOTS: u64 = 2;
+/// A slot holds this many whole textures, a full frame and the dirty rects posted after it.
+const SLOT_TEXTURES: u64 = 2;
+/// `bufferOffset` of a copy to a color image must be a multiple of the texel size.
+const OFFSET_ALIGNMENT: u64 = TEXEL_SIZE as u64;
+
+/// Persistently mapped staging ring the texture updates are copied from, on the frame's command
+/// buffer before the render pass. The frame is recorded after the draw fence signaled so the
+/// previous frame no longer samples the texture, the barrier orders the copy after those reads.
+pub struct AAATextureUploads {
+    pub buffer: vk::Buffer,
+    pub memory: vk::DeviceMemory,
+    ptr: *mut c_void,
+    slot_size: u64,
+    slot: Cell<u64>,
+    pub image: vk::Image,
+    pub extent: vk::Extent2D,
+    /// Levels of the image, regenerated from the first after every update.
+    pub mip_levels: u32,
+    /// Block compressed image, the RGBA8 updates cannot be copied into it and are dropped.
+    pub compressed: bool,
+    /// `UNDEFINED` until the first frame fills the texture white or records an update.
+    layout: Cell<vk::ImageLayout>,
+    updated: Cell<bool>,
+}
+
+// The mapping is only written by the render thread owning the resources
+unsafe impl Send for AAATextureUploads {}
+
+impl AAATextureUploads {
+    pub fn new(
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        image: vk::Image,
+        extent: vk::Extent2D,
+        mip_levels: u32,
+    ) -> Self {
+        let slot_size = (extent.width * extent.height * TEXEL_SIZE) as u64 * SLOT_TEXTURES;
+        let AAAOwnedBuffer { buffer, memory, .. } = create_empty_buffer(
+            device,
+            device_memory_properties,
+            slot_size * SLOTS,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        );
+        let ptr = unsafe {
+            device
+                .ash
+                .map_memory(memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())
+                .unwrap()
+        };
+        Self {
+            buffer,
+            memory,
+            ptr,
+            slot_size,
+            slot: Cell::new(0),
+            image,
+            extent,
+            mip_levels,
+            compressed: false,
+            layout: Cell::new(vk::ImageLayout::UNDEFINED),
+            updated: Cell::new(false),
+        }
+    }
+
+    /// Clear the texture white on the first frame, so meshes sample a defined texture before the
+    /// application posts one without anything staged at startup. Records nothing afterwards.
+    pub fn record_default(&self, device: &AAADevice, command_buffer: vk::CommandBuffer) {
+        if self.layout.get() != vk::ImageLayout::UNDEFINED {
+            return;
+        }
+        // Every level is white, no chain to generate
+        let subresource_range = vk::ImageSubresourceRange::default()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .level_count(self.mip_levels)
+            .layer_count(1);
+        let to_transfer = vk::ImageMemoryBarrier::default()
+            .image(self.image)
+            .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .old_layout(vk::ImageLayout::UNDEFINED)
+            .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+            .subresource_range(subresource_range);
+        let to_shader = vk::ImageMemoryBarrier::default()
+            .image(self.image)
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::SHADER_READ)
+            .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+            .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .subresource_range(subresource_range);
+        unsafe {
+            device.ash.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[to_transfer],
+            );
+            device.ash.cmd_clear_color_image(
+                command_buffer,
+                self.image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &vk::ClearColorValue { float32: [1.0; 4] },
+                &[subresource_range],
+            );
+            device.ash.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[to_shader],
+            );
+        }
+        self.layout.set(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+    }
+
+    /// Fill the whole texture from `staging`, see [`upload_whole`]. The first frame samples it as
+    /// is, without the white fill.
+    #[cfg(feature = "images")]
+    pub fn upload_initial(
+        &self,
+        device: &AAADevice,
+        upload: AAAUploadContext,
+        staging: AAAStagingBuffer,
+    ) {
+        upload_whole(
+            device,
+            upload,
+            self.image,
+            self.extent,
+            self.mip_levels,
+            0,
+            self.layout.get(),
+            staging,
+        );
+        self.filled();
+    }
+
+    /// The image was filled outside the ring and left `SHADER_READ_ONLY_OPTIMAL`.
+    pub fn filled(&self) {
+        self.layout.set(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+        self.updated.set(true);
+    }
+
+    /// Write `updates` into the next slot and record their copies, returns how many were recorded.
+    /// Updates outside the texture or past the slot capacity are dropped with a warning.
+    pub fn record(
+        &self,
+        device: &AAADevice,
+        command_buffer: vk::CommandBuffer,
+        mut updates: Vec<TextureUpdate>,
+    ) -> usize {
+        if self.compressed {
+            if !updates.is_empty() {
+                warn!(
+                    "{} texture updates dropped, the texture is block compressed",
+                    updates.len()
+                );
+            }
+            return 0;
+        }
+        let texture = TextureRegion::full(self.extent.width, self.extent.height);
+        coalesce(&mut updates, texture);
+        let slot_start = self.slot.get() * self.slot_size;
+        let mut offset = 0;
+        let mut regions = Vec::new();
+        for update in &updates {
+            if !texture.contains(&update.region) {
+                warn!(
+                    "Texture update {:?} outside of the {}x{} texture",
+                    update.region, self.extent.width, self.extent.height
+                );
+                continue;
+            }
+            let size = update.size_bytes() as u64;
+            if offset + size > self.slot_size {
+                warn!(
+                    "Texture update {:?} dropped, {} bytes already staged this frame",
+                    update.region, offset
+                );
+                continue;
+            }
+            unsafe {
+                std::ptr::copy_nonoverlapping(
+                    update.data.as_ptr(),
+                    self.ptr.cast::<u8>().add((slot_start + offset) as usize),
+                    size as usize,
+                );
+            }
+            regions.push(
+                vk::BufferImageCopy::default()
+                    .buffer_offset(slot_start + offset)
+                    .buffer_row_length(update.row_length())
+                    .image_subresource(
+                        vk::ImageSubresourceLayers::default()
+                            .aspect_mask(vk::ImageAspectFlags::COLOR)
+                            .layer_count(1),
+                    )
+                    .image_offset(vk::Offset3D {
+                        x: update.region.x as i32,
+                        y: update.region.y as i32,
+                        z: 0,
+                    })
+                    .image_extent(vk::Extent3D {
+                        width: update.region.width,
+                        height: update.region.height,
+                        depth: 1,
+                    }),
+            );
+            offset = (offset + size).next_multiple_of(OFFSET_ALIGNMENT);
+        }
+        if regions.is_empty() {
+            return 0;
+        }
+        self.slot.set((self.slot.get() + 1) % SLOTS);
+
+        // Every level is written, the first by the copies and the others from it
+        let subresource_range = vk::ImageSubresourceRange::default()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .level_count(self.mip_levels)
+            .layer_count(1);
+        let to_transfer = vk::ImageMemoryBarrier::default()
+            .image(self.image)
+            .src_access_mask(vk::AccessFlags::SHADER_READ)
+            .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .old_layout(self.layout.get())
+            .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+            .subresource_range(subresource_range);
+        unsafe {
+            device.ash.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[to_transfer],
+            );
+            device.ash.cmd_copy_buffer_to_image(
+                command_buffer,
+                self.buffer,
+                self.image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &regions,
+            );
+        }
+        record_mipmaps(
+            device,
+            command_buffer,
+            self.image,
+            self.extent,
+            self.mip_levels,
+            0,
+        );
+        self.layout.set(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+        self.updated.set(true);
+        regions.len()
+    }
+
+    /// Whether the texture was uploaded or updated, the white fill of the first frame does not count.
+    pub fn has_contents(&self) -> bool {
+        self.updated.get()
+    }
+
+    pub fn destroy(&self, device: &AAADevice) {
+        unsafe {
+            device.ash.unmap_memory(self.memory);
+            crate::object_audit::destroyed(self.memory);
+            device.ash.free_memory(self.memory, None);
+            crate::object_audit::destroyed(self.buffer);
+            device.ash.destroy_buffer(self.buffer, None);
+        }
+    }
+}
+
+/// Copy `staging` over the first level of `image`'s array `layer` and generate the others from it,
+/// on the setup command buffer. Waits for the copy then frees `staging`. The layer is left
+/// `SHADER_READ_ONLY_OPTIMAL`.
+#[allow(clippy::too_many_arguments)]
+pub fn upload_whole(
+    device: &AAADevice,
+    upload: AAAUploadContext,
+    image: vk::Image,
+    extent: vk::Extent2D,
+    mip_levels: u32,
+    layer: u32,
+    layout: vk::ImageLayout,
+    staging: AAAStagingBuffer,
+) {
+    debug_assert_eq!(
+        staging.size,
+        (extent.width * extent.height * TEXEL_SIZE) as u64
+    );
+    let subresource_range = vk::ImageSubresourceRange::default()
+        .aspect_mask(vk::ImageAspectFlags::COLOR)
+        .level_count(mip_levels)
+        .base_array_layer(layer)
+        .layer_count(1);
+    let to_transfer = vk::ImageMemoryBarrier::default()
+        .image(image)
+        .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+        .old_layout(layout)
+        .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+        .subresource_range(subresource_range);
+    let region = vk::BufferImageCopy::default()
+        .image_subresource(
+            vk::ImageSubresourceLayers::default()
+                .aspect_mask(vk::ImageAspectFlags::COLOR)
+                .base_array_layer(layer)
+                .layer_count(1),
+        )
+        .image_extent(extent.into());
+    record_submit_commandbuffer(
+        device,
+        upload.command_buffer,
+        upload.fence,
+        upload.queue,
+        &[],
+        &[],
+        &[],
+        |device, command_buffer| {
+            unsafe {
+                device.ash.cmd_pipeline_barrier(
+                    command_buffer,
+                    vk::PipelineStageFlags::TOP_OF_PIPE,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &[],
+                    &[to_transfer],
+                );
+                device.ash.cmd_copy_buffer_to_image(
+                    command_buffer,
+                    staging.buffer,
+                    image,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &[region],
+                );
+            }
+            record_mipmaps(device, command_buffer, image, extent, mip_levels, layer);
+        },
+    );
+    // The staging buffer is only needed until the copy completed
+    unsafe {
+        device
+            .ash
+            .wait_for_fences(&[upload.fence], true, u64::MAX)
+            .expect("Wait for fence failed.");
+    }
+    staging.destroy(device);
+}
+
+/// Copy every level of `image` out of `staging` with `regions`, one per level, on the setup
+/// command buffer. Nothing is generated, the levels are left `SHADER_READ_ONLY_OPTIMAL`. Waits for
+/// the copy then frees `staging`.
+pub fn upload_levels(
+    device: &AAADevice,
+    upload: AAAUploadContext,
+    image: vk::Image,
+    mip_levels: u32,
+    regions: &[vk::BufferImageCopy],
+    staging: AAAStagingBuffer,
+) {
+    let subresource_range = vk::ImageSubresourceRange::default()
+        .aspect_mask(vk::ImageAspectFlags::COLOR)
+        .level_count(mip_levels)
+        .layer_count(1);
+    let to_transfer = vk::ImageMemoryBarrier::default()
+        .image(image)
+        .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+        .old_layout(vk::ImageLayout::UNDEFINED)
+        .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+        .subresource_range(subresource_range);
+    let to_shader = vk::ImageMemoryBarrier::default()
+        .image(image)
+        .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+        .dst_access_mask(vk::AccessFlags::SHADER_READ)
+        .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+        .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+        .subresource_range(subresource_range);
+    record_submit_commandbuffer(
+        device,
+        upload.command_buffer,
+        upload.fence,
+        upload.queue,
+        &[],
+        &[],
+        &[],
+        |device, command_buffer| unsafe {
+            device.ash.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[to_transfer],
+            );
+            device.ash.cmd_copy_buffer_to_image(
+                command_buffer,
+                staging.buffer,
+                image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                regions,
+            );
+            device.ash.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[to_shader],
+            );
+        },
+    );
+    unsafe {
+        device
+            .ash
+            .wait_for_fences(&[upload.fence], true, u64::MAX)
+            .expect("Wait for fence failed.");
+    }
+    staging.destroy(device);
+}
+
+/// Levels of a full mip chain down to 1x1 for `extent`.
+pub fn mip_levels(extent: vk::Extent2D) -> u32 {
+    u32::BITS - extent.width.max(extent.height).max(1).leading_zeros()
+}
+
+/// Halve each level of array `layer` into the next with linear blits, each level read once the
+/// previous blit wrote it. Every level must be `TRANSFER_DST_OPTIMAL` with the first written, all
+/// are left `SHADER_READ_ONLY_OPTIMAL` for the fragment shader.
+pub fn record_mipmaps(
+    device: &AAADevice,
+    command_buffer: vk::CommandBuffer,
+    image: vk::Image,
+    extent: vk::Extent2D,
+    mip_levels: u32,
+    layer: u32,
+) {
+    let level = |level: u32| {
+        vk::ImageSubresourceRange::default()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .base_mip_level(level)
+            .level_count(1)
+            .base_array_layer(layer)
+            .layer_count(1)
+    };
+    let layers = |level: u32| {
+        vk::ImageSubresourceLayers::default()
+            .aspect_mask(vk::ImageAspectFlags::COLOR)
+            .mip_level(level)
+            .base_array_layer(layer)
+            .layer_count(1)
+    };
+    let corner = |size: u32| vk::Offset3D {
+        x: (extent.width >> size).max(1) as i32,
+        y: (extent.height >> size).max(1) as i32,
+        z: 1,
+    };
+    for target in 1..mip_levels {
+        let source = target - 1;
+        let to_source = vk::ImageMemoryBarrier::default()
+            .image(image)
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+            .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+            .new_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+            .subresource_range(level(source));
+        let blit = vk::ImageBlit::default()
+            .src_subresource(layers(source))
+            .src_offsets([vk::Offset3D::default(), corner(source)])
+            .dst_subresource(layers(target))
+            .dst_offsets([vk::Offset3D::default(), corner(target)]);
+        let to_shader = vk::ImageMemoryBarrier::default()
+            .image(image)
+            .src_access_mask(vk::AccessFlags::TRANSFER_READ)
+            .dst_access_mask(vk::AccessFlags::SHADER_READ)
+            .old_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+            .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .subresource_range(level(source));
+        unsafe {
+            device.ash.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[to_source],
+            );
+            device.ash.cmd_blit_image(
+                command_buffer,
+                image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &[blit],
+                vk::Filter::LINEAR,
+            );
+            device.ash.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[to_shader],
+            );
+        }
+    }
+    // The last level was only written
+    let to_shader = vk::ImageMemoryBarrier::default()
+        .image(image)
+        .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+        .dst_access_mask(vk::AccessFlags::SHADER_READ)
+        .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+        .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+        .subresource_range(level(mip_levels - 1));
+    unsafe {
+        device.ash.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::FRAGMENT_SHADER,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[to_shader],
+        );
+    }
+}
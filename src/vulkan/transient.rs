@@ -0,0 +1,106 @@
+use super::{device::AAADevice, views::find_memorytype_index};
+use crate::transient::{self, AliasPlan, Lifetime, TransientImage};
+use ash::vk;
+
+/// Device memory shared by the transient attachments of one submission, see
+/// [`crate::transient`]. Their images are destroyed by their owners, before [`Self::destroy`].
+pub struct AAATransientMemory {
+    blocks: Vec<vk::DeviceMemory>,
+    plan: AliasPlan,
+}
+
+impl AAATransientMemory {
+    /// Allocate the blocks `images` are aliased in and bind each. The images must not be bound
+    /// yet, their views are created after.
+    pub fn new(
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        images: &[(vk::Image, Lifetime)],
+    ) -> Self {
+        let requirements: Vec<TransientImage> = images
+            .iter()
+            .map(|&(image, lifetime)| {
+                let memory_req = unsafe { device.ash.get_image_memory_requirements(image) };
+                TransientImage {
+                    size: memory_req.size,
+                    alignment: memory_req.alignment,
+                    memory_type_bits: memory_req.memory_type_bits,
+                    lifetime,
+                }
+            })
+            .collect();
+        let mut plan = transient::plan(&requirements);
+        let blocks = plan
+            .blocks
+            .iter_mut()
+            .map(|block| {
+                let memory_req = vk::MemoryRequirements {
+                    size: block.size,
+                    alignment: 1,
+                    memory_type_bits: block.memory_type_bits,
+                };
+                let memory_index = find_memorytype_index(
+                    &memory_req,
+                    device_memory_properties,
+                    vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                )
+                .expect("Unable to find suitable memory index for transient attachments.");
+                // Attachments placed later must accept the type chosen
+                block.memory_type_bits = 1 << memory_index;
+                let allocate_info = vk::MemoryAllocateInfo::default()
+                    .allocation_size(block.size)
+                    .memory_type_index(memory_index);
+                let memory = unsafe { device.ash.allocate_memory(&allocate_info, None).unwrap() };
+                crate::object_audit::created(memory, "transient attachments");
+                memory
+            })
+            .collect::<Vec<_>>();
+        for (&(image, _), placement) in images.iter().zip(&plan.placements) {
+            unsafe {
+                device
+                    .ash
+                    .bind_image_memory(image, blocks[placement.block], placement.offset)
+                    .expect("Unable to bind transient attachment memory")
+            };
+        }
+        Self { blocks, plan }
+    }
+
+    /// Bind `image` in a block as allocated if one has room over its `lifetime`. `false` when
+    /// none has, the image then needs memory of its own.
+    pub fn bind(&mut self, device: &AAADevice, image: vk::Image, lifetime: Lifetime) -> bool {
+        let memory_req = unsafe { device.ash.get_image_memory_requirements(image) };
+        let Some(placement) = self.plan.place(TransientImage {
+            size: memory_req.size,
+            alignment: memory_req.alignment,
+            memory_type_bits: memory_req.memory_type_bits,
+            lifetime,
+        }) else {
+            return false;
+        };
+        unsafe {
+            device
+                .ash
+                .bind_image_memory(image, self.blocks[placement.block], placement.offset)
+                .expect("Unable to bind transient attachment memory")
+        };
+        true
+    }
+
+    pub fn attachments(&self) -> usize {
+        self.plan.images.len()
+    }
+
+    /// Bytes the attachments would have allocated on their own over the blocks' size.
+    pub fn bytes_saved(&self) -> u64 {
+        self.plan.bytes_saved()
+    }
+
+    /// The images bound in the blocks must have been destroyed.
+    pub fn destroy(&self, device: &AAADevice) {
+        for &memory in &self.blocks {
+            crate::object_audit::destroyed(memory);
+            unsafe { device.ash.free_memory(memory, None) };
+        }
+    }
+}
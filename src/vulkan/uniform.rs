@@ -1,62 +1,17 @@
-use super::{device::AAADevice, views::find_memorytype_index};
-use ash::{util::Align, vk};
+use super::{device::AAADevice, upload::create_filled_host_buffer};
+use ash::vk;
 use glam::Mat4;
-use std::mem;
 
 pub fn create_uniform_buffer(
     device: &AAADevice,
     device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
     uniform: Mat4,
 ) -> (vk::Buffer, vk::DeviceMemory) {
-    let uniform_buffer_info = vk::BufferCreateInfo {
-        size: mem::size_of_val(&uniform) as u64,
-        usage: vk::BufferUsageFlags::UNIFORM_BUFFER,
-        sharing_mode: vk::SharingMode::EXCLUSIVE,
-        ..Default::default()
-    };
-    unsafe {
-        let uniform_buffer = device
-            .ash
-            .create_buffer(&uniform_buffer_info, None)
-            .unwrap();
-        let uniform_buffer_memory_req = device.ash.get_buffer_memory_requirements(uniform_buffer);
-        let uniform_buffer_memory_index = find_memorytype_index(
-            &uniform_buffer_memory_req,
-            device_memory_properties,
-            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-        )
-        .expect("Unable to find suitable memorytype for the vertex buffer.");
-
-        let uniform_buffer_allocate_info = vk::MemoryAllocateInfo {
-            allocation_size: uniform_buffer_memory_req.size,
-            memory_type_index: uniform_buffer_memory_index,
-            ..Default::default()
-        };
-        let uniform_buffer_memory = device
-            .ash
-            .allocate_memory(&uniform_buffer_allocate_info, None)
-            .unwrap();
-        let uniform_ptr = device
-            .ash
-            .map_memory(
-                uniform_buffer_memory,
-                0,
-                uniform_buffer_memory_req.size,
-                vk::MemoryMapFlags::empty(),
-            )
-            .unwrap();
-        let mut uniform_aligned_slice = Align::new(
-            uniform_ptr,
-            mem::align_of::<Mat4>() as u64,
-            uniform_buffer_memory_req.size,
-        );
-        uniform_aligned_slice.copy_from_slice(&[uniform]);
-        device.ash.unmap_memory(uniform_buffer_memory);
-        device
-            .ash
-            .bind_buffer_memory(uniform_buffer, uniform_buffer_memory, 0)
-            .unwrap();
-
-        (uniform_buffer, uniform_buffer_memory)
-    }
+    let uniform_buffer = create_filled_host_buffer(
+        device,
+        device_memory_properties,
+        vk::BufferUsageFlags::UNIFORM_BUFFER,
+        &[uniform],
+    );
+    (uniform_buffer.buffer, uniform_buffer.memory)
 }
@@ -0,0 +1,349 @@
+use super::{device::AAADevice, record::record_submit_commandbuffer, views::find_memorytype_index};
+use crate::metrics;
+use ash::vk;
+use bytemuck::Pod;
+use std::{any::type_name, ffi::c_void, mem};
+
+/// `minMemoryMapAlignment` every implementation guarantees, mappings start aligned to it.
+const MIN_MEMORY_MAP_ALIGNMENT: usize = 64;
+
+/// Command buffer, fence and queue the copies into device local memory are submitted with.
+#[derive(Debug, Clone, Copy)]
+pub struct AAAUploadContext {
+    pub command_buffer: vk::CommandBuffer,
+    pub fence: vk::Fence,
+    pub queue: vk::Queue,
+}
+
+impl AAAUploadContext {
+    /// Whether some device local memory is not host visible. Integrated GPUs share a single heap,
+    /// staging only adds a copy there.
+    pub fn worthwhile(device_memory_properties: &vk::PhysicalDeviceMemoryProperties) -> bool {
+        device_memory_properties.memory_types[..device_memory_properties.memory_type_count as _]
+            .iter()
+            .any(|memory_type| {
+                memory_type
+                    .property_flags
+                    .contains(vk::MemoryPropertyFlags::DEVICE_LOCAL)
+                    && !memory_type
+                        .property_flags
+                        .contains(vk::MemoryPropertyFlags::HOST_VISIBLE)
+            })
+    }
+
+    /// Copy from `staging` into the destination buffers, wait for the copies then free `staging`.
+    /// The destinations are made visible to `dst_access` from `dst_stage` on.
+    pub fn copy(
+        &self,
+        device: &AAADevice,
+        staging: AAAStagingBuffer,
+        copies: &[(vk::Buffer, vk::BufferCopy)],
+        dst_stage: vk::PipelineStageFlags,
+        dst_access: vk::AccessFlags,
+    ) {
+        record_submit_commandbuffer(
+            device,
+            self.command_buffer,
+            self.fence,
+            self.queue,
+            &[],
+            &[],
+            &[],
+            |device, command_buffer| unsafe {
+                for &(buffer, region) in copies {
+                    device
+                        .ash
+                        .cmd_copy_buffer(command_buffer, staging.buffer, buffer, &[region]);
+                }
+                let barrier = vk::MemoryBarrier::default()
+                    .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                    .dst_access_mask(dst_access);
+                device.ash.cmd_pipeline_barrier(
+                    command_buffer,
+                    vk::PipelineStageFlags::TRANSFER,
+                    dst_stage,
+                    vk::DependencyFlags::empty(),
+                    &[barrier],
+                    &[],
+                    &[],
+                );
+            },
+        );
+        unsafe {
+            device
+                .ash
+                .wait_for_fences(&[self.fence], true, u64::MAX)
+                .expect("Wait for fence failed.");
+        }
+        staging.destroy(device);
+    }
+}
+
+/// Host visible copy source, mapped for its whole lifetime.
+pub struct AAAStagingBuffer {
+    pub buffer: vk::Buffer,
+    pub memory: vk::DeviceMemory,
+    pub ptr: *mut c_void,
+    pub size: vk::DeviceSize,
+}
+
+impl AAAStagingBuffer {
+    pub fn new(
+        device: &AAADevice,
+        device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+        size: vk::DeviceSize,
+    ) -> Self {
+        let AAAOwnedBuffer { buffer, memory, .. } = create_empty_buffer(
+            device,
+            device_memory_properties,
+            size,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        );
+        let ptr = unsafe {
+            device
+                .ash
+                .map_memory(memory, 0, size, vk::MemoryMapFlags::empty())
+                .unwrap()
+        };
+        Self {
+            buffer,
+            memory,
+            ptr,
+            size,
+        }
+    }
+
+    pub fn write(&mut self, offset: vk::DeviceSize, bytes: &[u8]) {
+        assert!(offset + bytes.len() as u64 <= self.size);
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                bytes.as_ptr(),
+                self.ptr.cast::<u8>().add(offset as usize),
+                bytes.len(),
+            );
+        }
+    }
+
+    pub fn destroy(self, device: &AAADevice) {
+        unsafe {
+            device.ash.unmap_memory(self.memory);
+            crate::object_audit::destroyed(self.memory);
+            device.ash.free_memory(self.memory, None);
+            crate::object_audit::destroyed(self.buffer);
+            device.ash.destroy_buffer(self.buffer, None);
+        }
+    }
+}
+
+/// Buffer and the allocation bound to it, freed together.
+#[derive(Debug)]
+pub struct AAAOwnedBuffer {
+    pub buffer: vk::Buffer,
+    pub memory: vk::DeviceMemory,
+    /// Size the buffer was created with.
+    pub size: vk::DeviceSize,
+}
+
+/// Buffer bound to a fresh allocation of `memory_flags` memory, left unwritten. Vulkan has no
+/// empty buffers, a `size` of 0 creates a 1 byte one.
+#[track_caller]
+pub fn create_empty_buffer(
+    device: &AAADevice,
+    device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    size: vk::DeviceSize,
+    usage: vk::BufferUsageFlags,
+    memory_flags: vk::MemoryPropertyFlags,
+) -> AAAOwnedBuffer {
+    let size = buffer_size(size);
+    let buffer_info = vk::BufferCreateInfo::default()
+        .size(size)
+        .usage(usage)
+        .sharing_mode(vk::SharingMode::EXCLUSIVE);
+    unsafe {
+        let buffer = device.ash.create_buffer(&buffer_info, None).unwrap();
+        crate::object_audit::created(buffer, "buffer");
+        let memory_req = device.ash.get_buffer_memory_requirements(buffer);
+        debug_assert!(memory_req.size >= size);
+        let memory_index =
+            find_memorytype_index(&memory_req, device_memory_properties, memory_flags)
+                .expect("Unable to find suitable memorytype for the buffer.");
+        let allocate_info = vk::MemoryAllocateInfo::default()
+            .allocation_size(memory_req.size)
+            .memory_type_index(memory_index);
+        let memory = device.ash.allocate_memory(&allocate_info, None).unwrap();
+        crate::object_audit::created(memory, "buffer");
+        metrics::count_buffer_allocation();
+        // A fresh allocation starts at offset 0, which satisfies any alignment requirement
+        device.ash.bind_buffer_memory(buffer, memory, 0).unwrap();
+        AAAOwnedBuffer {
+            buffer,
+            memory,
+            size,
+        }
+    }
+}
+
+/// Size a buffer asked for `size` bytes is created with, at least 1 byte.
+fn buffer_size(size: vk::DeviceSize) -> vk::DeviceSize {
+    size.max(1)
+}
+
+/// Host visible and coherent buffer holding `data`, readable by the device once returned.
+#[track_caller]
+pub fn create_filled_host_buffer<T: Pod>(
+    device: &AAADevice,
+    device_memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    usage: vk::BufferUsageFlags,
+    data: &[T],
+) -> AAAOwnedBuffer {
+    let owned = create_empty_buffer(
+        device,
+        device_memory_properties,
+        mem::size_of_val(data) as u64,
+        usage,
+        vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+    );
+    write_mapped(device, owned.memory, owned.size, data);
+    owned
+}
+
+/// Copy `data` to the start of `memory` through a temporary mapping.
+///
+/// `memory` is host visible and coherent, `capacity` bytes long, not mapped already and not read
+/// by any pending command. Mappings start at offset 0 of the allocation, aligned to at least
+/// `minMemoryMapAlignment` (64 bytes), so any `T` up to that alignment is copied in place.
+pub fn write_mapped<T: Pod>(
+    device: &AAADevice,
+    memory: vk::DeviceMemory,
+    capacity: vk::DeviceSize,
+    data: &[T],
+) {
+    let Some(size) = mapped_write_size(data, capacity) else {
+        return;
+    };
+    unsafe {
+        let ptr = device
+            .ash
+            .map_memory(memory, 0, size, vk::MemoryMapFlags::empty())
+            .unwrap();
+        let bytes: &[u8] = bytemuck::cast_slice(data);
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr.cast(), bytes.len());
+        device.ash.unmap_memory(memory);
+    }
+}
+
+/// Bytes [`write_mapped`] maps for `data`, `None` when there is nothing to write. `data` must
+/// fit `capacity` and `T` the mapping's alignment.
+fn mapped_write_size<T: Pod>(data: &[T], capacity: vk::DeviceSize) -> Option<vk::DeviceSize> {
+    debug_assert!(
+        mem::align_of::<T>() <= MIN_MEMORY_MAP_ALIGNMENT,
+        "Mapping misaligned for {}",
+        type_name::<T>()
+    );
+    let size = mem::size_of_val(data) as u64;
+    debug_assert!(
+        size <= capacity,
+        "{size} bytes of {} written to {capacity}",
+        type_name::<T>()
+    );
+    (size > 0).then_some(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Mat4;
+
+    /// Memory types of a discrete GPU: device local only, host visible and coherent, then both.
+    fn discrete_memory() -> vk::PhysicalDeviceMemoryProperties {
+        let mut properties = vk::PhysicalDeviceMemoryProperties {
+            memory_type_count: 3,
+            ..Default::default()
+        };
+        for (memory_type, property_flags) in properties.memory_types.iter_mut().zip([
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL
+                | vk::MemoryPropertyFlags::HOST_VISIBLE
+                | vk::MemoryPropertyFlags::HOST_COHERENT,
+        ]) {
+            memory_type.property_flags = property_flags;
+        }
+        properties
+    }
+
+    fn requirements(memory_type_bits: u32) -> vk::MemoryRequirements {
+        vk::MemoryRequirements {
+            size: 256,
+            alignment: 64,
+            memory_type_bits,
+        }
+    }
+
+    /// Aligned past what a mapping guarantees.
+    #[derive(Clone, Copy)]
+    #[repr(C, align(128))]
+    struct OverAligned([u8; 128]);
+
+    // SAFETY: plain bytes, the size is a multiple of the alignment so there is no padding
+    unsafe impl bytemuck::Zeroable for OverAligned {}
+    unsafe impl Pod for OverAligned {}
+
+    #[test]
+    fn empty_buffers_are_one_byte() {
+        assert_eq!(buffer_size(0), 1);
+        assert_eq!(buffer_size(1), 1);
+        assert_eq!(buffer_size(256), 256);
+    }
+
+    #[test]
+    fn mapped_writes_skip_empty_data() {
+        assert_eq!(mapped_write_size::<Mat4>(&[], 0), None);
+        assert_eq!(mapped_write_size::<u8>(&[], 64), None);
+        assert_eq!(mapped_write_size(&[Mat4::IDENTITY; 2], 128), Some(128));
+        assert_eq!(mapped_write_size(&[1u8, 2, 3], 4), Some(3));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "written to 63")]
+    fn mapped_writes_fit_the_capacity() {
+        mapped_write_size(&[Mat4::IDENTITY], 63);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Mapping misaligned")]
+    fn mapped_writes_refuse_alignments_past_the_mapping() {
+        mapped_write_size(&[OverAligned([0; 128])], 128);
+    }
+
+    #[test]
+    fn host_buffers_take_the_first_allowed_host_coherent_type() {
+        let properties = discrete_memory();
+        let host = vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT;
+        assert_eq!(
+            find_memorytype_index(&requirements(0b111), &properties, host),
+            Some(1)
+        );
+        // The buffer rules out the second type
+        assert_eq!(
+            find_memorytype_index(&requirements(0b101), &properties, host),
+            Some(2)
+        );
+        assert_eq!(
+            find_memorytype_index(&requirements(0b001), &properties, host),
+            None
+        );
+        // Types past the count are never taken
+        assert_eq!(
+            find_memorytype_index(
+                &requirements(1 << 5),
+                &properties,
+                vk::MemoryPropertyFlags::empty()
+            ),
+            None
+        );
+    }
+}
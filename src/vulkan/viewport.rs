@@ -0,0 +1,45 @@
+use ash::vk;
+use glam::Vec2;
+
+/// The one viewport and scissor of the frame. Both are dynamic states of every pipeline, set
+/// from here when recording, so pipelines never depend on the size of what they draw to.
+#[derive(Debug, Clone, Copy)]
+pub struct ViewportState {
+    pub viewports: [vk::Viewport; 1],
+    pub scissors: [vk::Rect2D; 1],
+}
+
+impl ViewportState {
+    /// Covering a `width` by `height` target, at creation and on every resize.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self::at(Vec2::ZERO, Vec2::new(width as f32, height as f32))
+    }
+
+    /// `size` physical pixels at `offset` of the target, the scissor truncated to whole pixels.
+    pub fn at(offset: Vec2, size: Vec2) -> Self {
+        Self {
+            viewports: [vk::Viewport {
+                x: offset.x,
+                y: offset.y,
+                width: size.x,
+                height: size.y,
+                min_depth: 0.0,
+                max_depth: 1.0,
+            }],
+            scissors: [vk::Rect2D {
+                offset: vk::Offset2D {
+                    x: offset.x as i32,
+                    y: offset.y as i32,
+                },
+                extent: vk::Extent2D {
+                    width: size.x as u32,
+                    height: size.y as u32,
+                },
+            }],
+        }
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.scissors[0].extent
+    }
+}
@@ -1,5 +1,6 @@
 use super::{
     device::AAADevice,
+    offscreen::AAAMsaaColor,
     surface::AAASurface,
     swapchain::{AAASwapchain, AAASwapchainLoader},
     AAABase,
@@ -28,12 +29,14 @@ pub fn create_views_and_depth(
     surface: &AAASurface,
     physical_device: &vk::PhysicalDevice,
     swapchain_loader: &AAASwapchainLoader,
+    samples: vk::SampleCountFlags,
 ) -> (
     Vec<ash::vk::Image>,
     Vec<vk::ImageView>,
     vk::ImageView,
     vk::Image,
     vk::DeviceMemory,
+    Option<AAAMsaaColor>,
     vk::PhysicalDeviceMemoryProperties,
 ) {
     let present_images = unsafe {
@@ -62,12 +65,14 @@ pub fn create_views_and_depth(
                     layer_count: 1,
                 })
                 .image(image);
-            unsafe {
+            let view = unsafe {
                 device
                     .ash
                     .create_image_view(&create_view_info, None)
                     .unwrap()
-            }
+            };
+            crate::object_audit::created(view, "swapchain image");
+            view
         })
         .collect();
     let device_memory_properties = unsafe {
@@ -78,12 +83,13 @@ pub fn create_views_and_depth(
     let depth_image_create_info = vk::ImageCreateInfo::default()
         .image_type(vk::ImageType::TYPE_2D)
         .format(vk::Format::D16_UNORM)
-        .extent(surface.capabilities.current_extent.into())
+        .extent(swapchain.extent.into())
         .mip_levels(1)
         .array_layers(1)
-        .samples(vk::SampleCountFlags::TYPE_1)
+        .samples(samples)
         .tiling(vk::ImageTiling::OPTIMAL)
-        .usage(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT)
+        // Sampled by the depth of field passes after the scene
+        .usage(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED)
         .sharing_mode(vk::SharingMode::EXCLUSIVE);
 
     let depth_image = unsafe {
@@ -92,6 +98,7 @@ pub fn create_views_and_depth(
             .create_image(&depth_image_create_info, None)
             .unwrap()
     };
+    crate::object_audit::created(depth_image, "window depth");
     let depth_image_memory_req = unsafe { device.ash.get_image_memory_requirements(depth_image) };
     let depth_image_memory_index = find_memorytype_index(
         &depth_image_memory_req,
@@ -110,6 +117,7 @@ pub fn create_views_and_depth(
             .allocate_memory(&depth_image_allocate_info, None)
             .unwrap()
     };
+    crate::object_audit::created(depth_image_memory, "window depth");
 
     unsafe {
         device
@@ -135,6 +143,15 @@ pub fn create_views_and_depth(
             .create_image_view(&depth_image_view_info, None)
             .unwrap()
     };
+    crate::object_audit::created(depth_image_view, "window depth");
+
+    let msaa_color = AAAMsaaColor::new(
+        device,
+        &device_memory_properties,
+        surface.format.format,
+        swapchain.extent,
+        samples,
+    );
 
     (
         present_images,
@@ -142,6 +159,7 @@ pub fn create_views_and_depth(
         depth_image_view,
         depth_image,
         depth_image_memory,
+        msaa_color,
         device_memory_properties,
     )
 }
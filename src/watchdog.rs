@@ -0,0 +1,339 @@
+//! Detects a render thread that stopped making progress, from the event loop thread.
+use log::error;
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, AtomicU8, Ordering},
+    time::{Duration, Instant},
+};
+
+/// Set to any value to disable the watchdog, when pausing the render thread in a debugger.
+pub const WATCHDOG_DISABLE_ENV: &str = "PULSAR_NO_WATCHDOG";
+/// How often the event loop wakes up to check the heartbeats.
+pub const WATCHDOG_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Blocking call the render thread is in, if any.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum WaitSite {
+    None,
+    SurfaceLock,
+    AcquireImage,
+    DrawFence,
+    SetupFence,
+    Present,
+    DeviceIdle,
+    /// The previous frame reaching the screen, see [`crate::present_wait`].
+    PresentWait,
+    /// The previous dispatches of the deformed meshes, see [`crate::compute`].
+    ComputeFence,
+}
+
+/// Last part of the frame the render thread entered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum RenderStage {
+    FrameStart,
+    Display,
+    Pipelines,
+    Residency,
+    Gizmo,
+    Acquire,
+    Record,
+    Present,
+    Screenshots,
+    Throttle,
+}
+
+impl WaitSite {
+    const ALL: [Self; 9] = [
+        Self::None,
+        Self::SurfaceLock,
+        Self::AcquireImage,
+        Self::DrawFence,
+        Self::SetupFence,
+        Self::Present,
+        Self::DeviceIdle,
+        Self::PresentWait,
+        Self::ComputeFence,
+    ];
+}
+
+impl RenderStage {
+    const ALL: [Self; 10] = [
+        Self::FrameStart,
+        Self::Display,
+        Self::Pipelines,
+        Self::Residency,
+        Self::Gizmo,
+        Self::Acquire,
+        Self::Record,
+        Self::Present,
+        Self::Screenshots,
+        Self::Throttle,
+    ];
+}
+
+/// Time the render thread spent blocked during a frame, by what it waited on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameWaits {
+    pub acquire: Duration,
+    /// Draw and setup fences, the GPU finishing the engine's own work.
+    pub fences: Duration,
+    pub present: Duration,
+    /// Present wait pacing holding the frame back on purpose, before it starts.
+    pub pacing: Duration,
+}
+
+impl FrameWaits {
+    /// Acquire and present, where the compositor holds the frame.
+    pub fn compositor(&self) -> Duration {
+        self.acquire + self.present
+    }
+}
+
+/// Written by the render thread every frame, read by the watchdog.
+#[derive(Debug)]
+pub struct Heartbeat {
+    epoch: Instant,
+    /// Milliseconds since `epoch` plus one, zero while the render thread is not running.
+    last_beat: AtomicU64,
+    frame: AtomicU64,
+    stage: AtomicU8,
+    wait_site: AtomicU8,
+    /// Nanoseconds spent in each site since the last [`Self::take_waits`], by [`WaitSite`].
+    waited: [AtomicU64; WaitSite::ALL.len()],
+    /// Nanoseconds slept on entering each site, to fake a stalled compositor or GPU.
+    #[cfg(debug_assertions)]
+    injected: [AtomicU64; WaitSite::ALL.len()],
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Self {
+            epoch: Instant::now(),
+            last_beat: AtomicU64::new(0),
+            frame: AtomicU64::new(0),
+            stage: AtomicU8::new(RenderStage::FrameStart as u8),
+            wait_site: AtomicU8::new(WaitSite::None as u8),
+            waited: Default::default(),
+            #[cfg(debug_assertions)]
+            injected: Default::default(),
+        }
+    }
+}
+
+impl Heartbeat {
+    pub fn beat(&self, frame: u64) {
+        let now = self.epoch.elapsed().as_millis() as u64 + 1;
+        self.frame.store(frame, Ordering::Relaxed);
+        self.stage
+            .store(RenderStage::FrameStart as u8, Ordering::Relaxed);
+        self.last_beat.store(now, Ordering::Release);
+    }
+
+    /// The render thread stopped on purpose, silence is expected until the next beat.
+    pub fn park(&self) {
+        self.last_beat.store(0, Ordering::Release);
+    }
+
+    pub fn enter(&self, stage: RenderStage) {
+        self.stage.store(stage as u8, Ordering::Relaxed);
+    }
+
+    /// Tags the blocking call until the guard drops, its duration is added to the frame's waits.
+    pub fn waiting(&self, site: WaitSite) -> WaitGuard<'_> {
+        self.wait_site.store(site as u8, Ordering::Relaxed);
+        let guard = WaitGuard {
+            heartbeat: self,
+            site,
+            start: Instant::now(),
+        };
+        #[cfg(debug_assertions)]
+        match self.injected[site as usize].load(Ordering::Relaxed) {
+            0 => {}
+            nanos => std::thread::sleep(Duration::from_nanos(nanos)),
+        }
+        guard
+    }
+
+    /// Make every wait at `site` last at least `delay` longer, zero removes it. Debug builds only,
+    /// for checking how stalls are attributed.
+    #[cfg(debug_assertions)]
+    pub fn inject_delay(&self, site: WaitSite, delay: Duration) {
+        self.injected[site as usize].store(delay.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Waits since the last call, by what was waited on.
+    pub fn take_waits(&self) -> FrameWaits {
+        let take = |site: WaitSite| {
+            Duration::from_nanos(self.waited[site as usize].swap(0, Ordering::Relaxed))
+        };
+        FrameWaits {
+            acquire: take(WaitSite::AcquireImage),
+            fences: take(WaitSite::DrawFence)
+                + take(WaitSite::SetupFence)
+                + take(WaitSite::ComputeFence),
+            present: take(WaitSite::Present),
+            pacing: take(WaitSite::PresentWait),
+        }
+    }
+
+    /// Time since the last beat, `None` while parked.
+    pub fn silence(&self) -> Option<Duration> {
+        match self.last_beat.load(Ordering::Acquire) {
+            0 => None,
+            beat => Some(
+                self.epoch
+                    .elapsed()
+                    .saturating_sub(Duration::from_millis(beat - 1)),
+            ),
+        }
+    }
+
+    pub fn report(&self) -> Option<StallReport> {
+        Some(StallReport {
+            silence: self.silence()?,
+            frame: self.frame.load(Ordering::Relaxed),
+            stage: RenderStage::ALL[self.stage.load(Ordering::Relaxed) as usize],
+            wait_site: WaitSite::ALL[self.wait_site.load(Ordering::Relaxed) as usize],
+        })
+    }
+}
+
+pub struct WaitGuard<'a> {
+    heartbeat: &'a Heartbeat,
+    site: WaitSite,
+    start: Instant,
+}
+
+impl Drop for WaitGuard<'_> {
+    fn drop(&mut self) {
+        self.heartbeat.waited[self.site as usize]
+            .fetch_add(self.start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        self.heartbeat
+            .wait_site
+            .store(WaitSite::None as u8, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StallReport {
+    pub silence: Duration,
+    /// Frame the render thread was on.
+    pub frame: u64,
+    pub stage: RenderStage,
+    pub wait_site: WaitSite,
+}
+
+impl fmt::Display for StallReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "no frame for {:.1}s, stuck in frame {} at {:?}",
+            self.silence.as_secs_f64(),
+            self.frame,
+            self.stage
+        )?;
+        match self.wait_site {
+            WaitSite::None => write!(f, ", not in a blocking call"),
+            site => write!(f, ", waiting on {site:?}"),
+        }
+    }
+}
+
+/// Reports each stall once, again only after the render thread recovered.
+#[derive(Debug)]
+pub struct Watchdog {
+    threshold: Option<Duration>,
+    stalled: bool,
+}
+
+impl Watchdog {
+    /// `threshold` of zero or the [`WATCHDOG_DISABLE_ENV`] variable disable it.
+    pub fn new(threshold: Duration) -> Self {
+        let disabled = threshold.is_zero() || std::env::var_os(WATCHDOG_DISABLE_ENV).is_some();
+        Self {
+            threshold: (!disabled).then_some(threshold),
+            stalled: false,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.threshold.is_some()
+    }
+
+    pub fn check(&mut self, heartbeat: &Heartbeat) -> Option<StallReport> {
+        let threshold = self.threshold?;
+        let report = heartbeat.report();
+        let stalled = report.is_some_and(|report| report.silence >= threshold);
+        let newly_stalled = stalled && !self.stalled;
+        self.stalled = stalled;
+        let report = report.filter(|_| newly_stalled)?;
+        error!("Render thread stalled, {report}");
+        Some(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    const THRESHOLD: Duration = Duration::from_millis(50);
+
+    #[test]
+    fn stall_is_reported_once_with_its_wait_site() {
+        let heartbeat = Heartbeat::default();
+        let mut watchdog = Watchdog::new(THRESHOLD);
+        // Not started yet
+        thread::sleep(THRESHOLD);
+        assert_eq!(watchdog.check(&heartbeat), None);
+
+        heartbeat.beat(3);
+        assert_eq!(watchdog.check(&heartbeat), None);
+        heartbeat.enter(RenderStage::Record);
+        let waiting = heartbeat.waiting(WaitSite::DrawFence);
+        thread::sleep(THRESHOLD * 2);
+        let report = watchdog.check(&heartbeat).unwrap();
+        assert_eq!(report.frame, 3);
+        assert_eq!(report.stage, RenderStage::Record);
+        assert_eq!(report.wait_site, WaitSite::DrawFence);
+        assert!(report.silence >= THRESHOLD);
+        assert_eq!(watchdog.check(&heartbeat), None);
+        drop(waiting);
+
+        // Recovered, then stalled again outside of a blocking call
+        heartbeat.beat(4);
+        assert_eq!(watchdog.check(&heartbeat), None);
+        thread::sleep(THRESHOLD * 2);
+        let report = watchdog.check(&heartbeat).unwrap();
+        assert_eq!(report.wait_site, WaitSite::None);
+        assert_eq!(report.stage, RenderStage::FrameStart);
+
+        heartbeat.park();
+        assert_eq!(watchdog.check(&heartbeat), None);
+    }
+
+    #[test]
+    fn zero_threshold_disables() {
+        let heartbeat = Heartbeat::default();
+        heartbeat.beat(0);
+        let mut watchdog = Watchdog::new(Duration::ZERO);
+        assert!(!watchdog.enabled());
+        assert_eq!(watchdog.check(&heartbeat), None);
+    }
+
+    #[test]
+    fn waits_are_summed_by_site() {
+        let heartbeat = Heartbeat::default();
+        for site in [WaitSite::DrawFence, WaitSite::SetupFence, WaitSite::Present] {
+            let _waiting = heartbeat.waiting(site);
+            thread::sleep(Duration::from_millis(5));
+        }
+        let waits = heartbeat.take_waits();
+        assert!(waits.fences >= Duration::from_millis(10));
+        assert!(waits.present >= Duration::from_millis(5));
+        assert_eq!(waits.acquire, Duration::ZERO);
+        assert_eq!(heartbeat.take_waits().fences, Duration::ZERO);
+    }
+}
@@ -0,0 +1,1155 @@
+use crate::app::UserEvent;
+use crate::background::{BackgroundMode, BACKGROUND_USER_FLOATS};
+use crate::batching::MeshBatch;
+use crate::block_compression::CompressedTexture;
+use crate::camera::{
+    CameraController, Ortho2DController, ORTHO_2D_PIXELS_PER_LINE, ORTHO_2D_ZOOM_PER_LINE,
+};
+use crate::color_space::ExportColorSpace;
+use crate::compute::VertexDeformer;
+use crate::config::WindowConfig;
+use crate::custom_pass::{CustomPass, CustomPassSlot};
+use crate::debug_lines::DebugLines;
+use crate::display::DisplayEnvironment;
+use crate::error::PulsarError;
+use crate::failure::{Failure, FailureKey, RetryCallback};
+use crate::handles::MeshHandle;
+use crate::input_manager::EventStates;
+use crate::inset::InsetView;
+use crate::instancing::Instances;
+use crate::loader::LoadTarget;
+use crate::material::Material;
+use crate::model::{Mesh, MeshSpace, MeshUpdate};
+use crate::present_health::{PresentDowngrade, PresentModePreference};
+use crate::renderer::RendererFactory;
+use crate::residency::ResidencyPriority;
+use crate::screenshot::ScreenshotRequest;
+use crate::skinning::AnimationPlayer;
+use crate::texture::{CubemapSource, SamplerDesc, TextureArrayDesc, TextureUpdate};
+use crate::thumbnail::{Thumbnail, ThumbnailTarget};
+use crate::time_control::TimeControl;
+use crate::update_queue::UpdateQueue;
+use crate::watchdog::{StallReport, Watchdog, WATCHDOG_POLL_INTERVAL};
+use crate::window_state::WindowState;
+use ash::vk;
+use log::{error, info, warn};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Debug;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use winit::application::ApplicationHandler;
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::event::{DeviceEvent, DeviceId, Ime, MouseButton, MouseScrollDelta, WindowEvent};
+use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
+use winit::keyboard::{Key, ModifiersState};
+use winit::window::{CustomCursor, Icon, Window, WindowId};
+
+/// Resolution multiplier of the screenshot binding.
+const SCREENSHOT_SCALE: u32 = 2;
+/// Simulation time scales of the slow motion and fast forward bindings.
+const SLOW_MOTION_SCALE: f32 = 0.25;
+const FAST_FORWARD_SCALE: f32 = 4.0;
+/// How long a window created hidden waits for its first frame before it is shown anyway, a
+/// window without area or a renderer that never presents would stay hidden for good.
+pub const FIRST_FRAME_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Windows, cursors, key and mouse bindings. Rendering is delegated to the `RendererFactory`.
+pub struct WindowManager {
+    pub custom_cursors: Vec<CustomCursor>,
+    icon: Option<Icon>,
+    windows: HashMap<WindowId, WindowState>,
+    pub window_config: WindowConfig,
+    factory: Box<dyn RendererFactory>,
+    /// Why the first window could not be opened, until the application takes it.
+    startup_error: Option<Box<dyn Error>>,
+}
+
+impl WindowManager {
+    pub fn new<T>(
+        event_loop: &EventLoop<T>,
+        window_config: WindowConfig,
+        factory: Box<dyn RendererFactory>,
+    ) -> Self {
+        // You'll have to choose an icon size at your own discretion. On Windows, you still have to account
+        //  for screen scaling. Here we use 32px, since it seems to work well enough in most cases.
+        // Be careful about going too high, or you'll be bitten by the low-quality downscaling built into the
+        // WM.
+        let icon = builtin_icon();
+
+        // info!("Loading cursor assets");
+        let custom_cursors = builtin_cursors(event_loop);
+
+        Self {
+            custom_cursors,
+            icon,
+            windows: Default::default(),
+            window_config,
+            factory,
+            startup_error: None,
+        }
+    }
+
+    /// Error that kept the first window from opening, a `PulsarError` when the display cannot be
+    /// rendered to. Switch to another factory with [`Self::set_renderer_factory`] and open a window
+    /// again, otherwise the event loop exits with no window left.
+    pub fn take_startup_error(&mut self) -> Option<Box<dyn Error>> {
+        self.startup_error.take()
+    }
+
+    /// Renderers of the windows opened from now on, the open ones keep theirs.
+    pub fn set_renderer_factory(&mut self, factory: Box<dyn RendererFactory>) {
+        self.factory = factory;
+    }
+
+    /// Build the pipeline of `material` in the background on every window, before its first draw.
+    pub fn precompile(&self, material: &Material) {
+        for window_state in self.windows.values() {
+            window_state.precompile(material.clone());
+        }
+    }
+
+    /// User id of the mesh selected in `window_id`.
+    pub fn selected_user_id(&self, window_id: WindowId) -> Option<u64> {
+        self.windows
+            .get(&window_id)
+            .and_then(WindowState::selected_user_id)
+    }
+
+    /// Mesh selected in `window_id`.
+    pub fn selected_mesh(&self, window_id: WindowId) -> Option<MeshHandle> {
+        self.windows
+            .get(&window_id)
+            .and_then(WindowState::selected_mesh)
+    }
+
+    /// Copy `update` into `window_id`'s texture before its next frame.
+    pub fn update_texture(&self, window_id: WindowId, update: TextureUpdate) {
+        if let Some(window) = self.windows.get(&window_id) {
+            window.update_texture(update);
+        }
+    }
+
+    pub fn load_texture(&self, window_id: WindowId, path: PathBuf, sampler: SamplerDesc) {
+        if let Some(window) = self.windows.get(&window_id) {
+            window.load_texture(path, sampler);
+        }
+    }
+
+    pub fn load_compressed_texture(
+        &self,
+        window_id: WindowId,
+        texture: CompressedTexture,
+        sampler: SamplerDesc,
+    ) {
+        if let Some(window) = self.windows.get(&window_id) {
+            window.load_compressed_texture(texture, sampler);
+        }
+    }
+
+    pub fn create_texture_array(&self, window_id: WindowId, desc: TextureArrayDesc) {
+        if let Some(window) = self.windows.get(&window_id) {
+            window.create_texture_array(desc);
+        }
+    }
+
+    pub fn push_texture_layer(&self, window_id: WindowId, data: Vec<u8>) {
+        if let Some(window) = self.windows.get(&window_id) {
+            window.push_texture_layer(data);
+        }
+    }
+
+    pub fn load_cubemap(&self, window_id: WindowId, source: CubemapSource, sampler: SamplerDesc) {
+        if let Some(window) = self.windows.get(&window_id) {
+            window.load_cubemap(source, sampler);
+        }
+    }
+
+    pub fn clear_cubemap(&self, window_id: WindowId) {
+        if let Some(window) = self.windows.get(&window_id) {
+            window.clear_cubemap();
+        }
+    }
+
+    pub fn set_texture_residency(&self, window_id: WindowId, priority: ResidencyPriority) {
+        if let Some(window) = self.windows.get(&window_id) {
+            window.set_texture_residency(priority);
+        }
+    }
+
+    pub fn set_environment_intensity(&self, window_id: WindowId, intensity: f32) {
+        if let Some(window) = self.windows.get(&window_id) {
+            window.set_environment_intensity(intensity);
+        }
+    }
+
+    pub fn show_failure(
+        &self,
+        window_id: WindowId,
+        failure: Failure,
+        retry: Option<RetryCallback>,
+    ) {
+        if let Some(window) = self.windows.get(&window_id) {
+            window.show_failure(failure, retry);
+        }
+    }
+
+    pub fn clear_failure(&self, window_id: WindowId) {
+        if let Some(window) = self.windows.get(&window_id) {
+            window.clear_failure();
+        }
+    }
+
+    pub fn request_thumbnail(
+        &self,
+        window_id: WindowId,
+        target: ThumbnailTarget,
+        size: u32,
+    ) -> Option<Thumbnail> {
+        self.windows
+            .get(&window_id)
+            .map(|window| window.request_thumbnail(target, size))
+    }
+
+    pub fn window_ids(&self) -> impl Iterator<Item = WindowId> + '_ {
+        self.windows.keys().copied()
+    }
+
+    /// Register `mesh` in `window_id` before its next frame, `None` when the window is gone.
+    pub fn add_mesh(
+        &self,
+        window_id: WindowId,
+        mesh: Mesh,
+        space: MeshSpace,
+        instances: Option<Instances>,
+    ) -> Option<MeshHandle> {
+        self.windows
+            .get(&window_id)
+            .map(|window| window.add_mesh(mesh, space, instances))
+    }
+
+    pub fn add_skinned_mesh(
+        &self,
+        window_id: WindowId,
+        mesh: Mesh,
+        player: AnimationPlayer,
+    ) -> Option<MeshHandle> {
+        self.windows
+            .get(&window_id)
+            .map(|window| window.add_skinned_mesh(mesh, player))
+    }
+
+    pub fn add_deformed_mesh(
+        &self,
+        window_id: WindowId,
+        mesh: Mesh,
+        deformer: VertexDeformer,
+    ) -> Option<MeshHandle> {
+        self.windows
+            .get(&window_id)
+            .map(|window| window.add_deformed_mesh(mesh, deformer))
+    }
+
+    /// `None` when the window is gone.
+    pub fn load_target(&self, window_id: WindowId) -> Option<LoadTarget> {
+        self.windows.get(&window_id).map(WindowState::load_target)
+    }
+
+    pub fn add_mesh_batch(
+        &self,
+        window_id: WindowId,
+        batch: MeshBatch,
+        space: MeshSpace,
+    ) -> Option<MeshHandle> {
+        self.windows
+            .get(&window_id)
+            .map(|window| window.add_mesh_batch(batch, space))
+    }
+
+    pub fn set_ortho_2d(&self, window_id: WindowId, controller: Ortho2DController) {
+        if let Some(window) = self.windows.get(&window_id) {
+            window.set_ortho_2d(controller);
+        }
+    }
+
+    pub fn set_camera_controller(&self, window_id: WindowId, controller: CameraController) {
+        if let Some(window) = self.windows.get(&window_id) {
+            window.set_camera_controller(controller);
+        }
+    }
+
+    pub fn time_control(&self, window_id: WindowId) -> Option<TimeControl> {
+        self.windows.get(&window_id).map(WindowState::time_control)
+    }
+
+    pub fn set_time_control(&mut self, window_id: WindowId, control: TimeControl) {
+        if let Some(window) = self.windows.get_mut(&window_id) {
+            window.set_time_control(control);
+        }
+    }
+
+    pub fn present_mode(
+        &self,
+        window_id: WindowId,
+    ) -> Option<(PresentModePreference, vk::PresentModeKHR)> {
+        self.windows.get(&window_id)?.present_mode()
+    }
+
+    pub fn focus_distance(&self, window_id: WindowId) -> Option<f32> {
+        self.windows.get(&window_id)?.focus_distance()
+    }
+
+    pub fn set_title(&self, window_id: WindowId, title: &str) {
+        if let Some(window) = self.windows.get(&window_id) {
+            window.window.set_title(title);
+        }
+    }
+
+    pub fn set_present_preference(
+        &mut self,
+        window_id: WindowId,
+        preference: PresentModePreference,
+    ) {
+        if let Some(window) = self.windows.get_mut(&window_id) {
+            window.set_present_preference(preference);
+        }
+    }
+
+    pub fn step_once(&self, window_id: WindowId) {
+        if let Some(window) = self.windows.get(&window_id) {
+            window.step_once();
+        }
+    }
+
+    pub fn set_custom_pass(
+        &self,
+        window_id: WindowId,
+        slot: CustomPassSlot,
+        pass: Option<CustomPass>,
+    ) {
+        if let Some(window) = self.windows.get(&window_id) {
+            window.set_custom_pass(slot, pass);
+        }
+    }
+
+    pub(crate) fn raw_device(&self, window_id: WindowId) -> Option<ash::Device> {
+        let window = self.windows.get(&window_id)?;
+        window.event_states.raw_device.lock().unwrap().clone()
+    }
+
+    pub fn set_debug_lines(&self, window_id: WindowId, lines: DebugLines) {
+        if let Some(window) = self.windows.get(&window_id) {
+            window.set_debug_lines(lines);
+        }
+    }
+
+    pub fn set_background(&self, window_id: WindowId, mode: BackgroundMode) {
+        if let Some(window) = self.windows.get(&window_id) {
+            window.set_background(mode);
+        }
+    }
+
+    pub fn set_background_uniforms(
+        &self,
+        window_id: WindowId,
+        user: [f32; BACKGROUND_USER_FLOATS],
+    ) {
+        if let Some(window) = self.windows.get(&window_id) {
+            window.set_background_uniforms(user);
+        }
+    }
+
+    pub fn set_insets(&self, window_id: WindowId, insets: Vec<InsetView>) {
+        if let Some(window) = self.windows.get(&window_id) {
+            window.set_insets(insets);
+        }
+    }
+
+    /// Copy `update` into `mesh` of `window_id` before its next frame.
+    /// Queue `update` for `window_id`'s render thread, nothing to do when the window is gone.
+    pub fn update_mesh(
+        &self,
+        window_id: WindowId,
+        mesh: MeshHandle,
+        update: MeshUpdate,
+    ) -> Result<(), PulsarError> {
+        match self.windows.get(&window_id) {
+            Some(window) => window.update_mesh(mesh, update),
+            None => Ok(()),
+        }
+    }
+
+    /// Free `mesh` from `window_id` before its next frame.
+    pub fn remove_mesh(&self, window_id: WindowId, mesh: MeshHandle) {
+        if let Some(window) = self.windows.get(&window_id) {
+            window.remove_mesh(mesh);
+        }
+    }
+
+    /// Render `window_id`'s scene offscreen at `extent` and save it to `path` in `color_space`.
+    pub fn capture_screenshot_at(
+        &self,
+        window_id: WindowId,
+        extent: PhysicalSize<u32>,
+        path: impl Into<std::path::PathBuf>,
+        color_space: ExportColorSpace,
+    ) {
+        if let Some(window) = self.windows.get(&window_id) {
+            window.capture_screenshot(ScreenshotRequest::new(
+                extent.width,
+                extent.height,
+                path,
+                color_space,
+            ));
+        }
+    }
+
+    /// Windows whose render thread just stalled, each stall is reported once.
+    pub fn stalled_render_threads(&mut self) -> Vec<(WindowId, StallReport)> {
+        self.windows
+            .iter_mut()
+            .filter_map(|(&window_id, window_state)| {
+                window_state
+                    .check_render_thread()
+                    .map(|report| (window_id, report))
+            })
+            .collect()
+    }
+
+    /// Windows whose first frame was just presented.
+    pub fn first_frames_presented(&mut self) -> Vec<WindowId> {
+        self.windows
+            .iter_mut()
+            .filter_map(|(&window_id, window_state)| {
+                window_state.check_first_frame().then_some(window_id)
+            })
+            .collect()
+    }
+
+    /// Whether `window_id` presented a frame yet, `false` once it is closed.
+    pub fn first_frame_presented(&self, window_id: WindowId) -> bool {
+        self.windows
+            .get(&window_id)
+            .is_some_and(|window| window.first_frame_presented())
+    }
+
+    /// Windows whose presentation just fell back to FIFO.
+    pub fn present_mode_downgrades(&self) -> Vec<(WindowId, PresentDowngrade)> {
+        self.windows
+            .iter()
+            .filter_map(|(&window_id, window_state)| {
+                window_state
+                    .check_present_mode()
+                    .map(|report| (window_id, report))
+            })
+            .collect()
+    }
+
+    pub fn window_count(&self) -> usize {
+        self.windows.len()
+    }
+
+    /// Stop rendering to `window_id` and drop it, returns `false` when it was already closed.
+    pub fn close_window(&mut self, window_id: WindowId) -> bool {
+        let Some(mut window_state) = self.windows.remove(&window_id) else {
+            return false;
+        };
+        window_state.close();
+        true
+    }
+
+    /// Stop rendering and drop every window.
+    pub fn close_all(&mut self) {
+        for window_state in self.windows.values_mut() {
+            window_state.close();
+        }
+        self.windows.clear();
+    }
+
+    pub fn create_window(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        _tab_id: Option<String>,
+    ) -> Result<WindowId, Box<dyn Error>> {
+        // TODO read-out activation token.
+
+        let hidden = self.window_config.hidden_until_first_frame;
+        #[allow(unused_mut)]
+        let mut window_attributes = Window::default_attributes()
+            .with_visible(!hidden)
+            .with_title(&self.window_config.title)
+            .with_transparent(self.window_config.transparent)
+            .with_window_icon(self.icon.clone())
+            .with_inner_size(PhysicalSize::new(
+                self.window_config.width,
+                self.window_config.height,
+            ));
+
+        let window = Arc::new(event_loop.create_window(window_attributes)?);
+        let event_states = Arc::new(EventStates {
+            mesh_updates: Mutex::new(UpdateQueue::new(
+                self.window_config.update_queue_capacity,
+                self.window_config.update_backpressure,
+            )),
+            ..Default::default()
+        });
+        let display = DisplayEnvironment::from_window(&window);
+        let renderer =
+            self.factory
+                .create_surface_renderer(&window, event_states.clone(), display)?;
+
+        let window_state = WindowState::new(
+            window,
+            renderer,
+            event_states,
+            display,
+            self.custom_cursors.len(),
+            Watchdog::new(Duration::from_secs_f32(
+                self.window_config.stall_threshold_secs.max(0.0),
+            )),
+            hidden,
+        );
+        let window_id = window_state.window.id();
+        self.windows.insert(window_id, window_state);
+        Ok(window_id)
+    }
+
+    fn handle_action(&mut self, event_loop: &ActiveEventLoop, window_id: WindowId, action: Action) {
+        // let cursor_position = self.cursor_position;
+        let window = self.windows.get_mut(&window_id).unwrap();
+        // info!("Executing action: {action:?}");
+        match action {
+            Action::CloseWindow => {
+                self.close_window(window_id);
+            }
+            Action::CreateNewWindow => match self.create_window(event_loop, None) {
+                Ok(window_id) => self.windows.get_mut(&window_id).unwrap().start_rendering(),
+                Err(error) => error!("Failed to create a new window: {error}"),
+            },
+            Action::ToggleResizeIncrements => window.toggle_resize_increments(),
+            Action::ToggleCursorVisibility => window.toggle_cursor_visibility(),
+            Action::ToggleResizable => window.toggle_resizable(),
+            Action::ToggleDecorations => window.toggle_decorations(),
+            Action::ToggleFullscreen => window.toggle_fullscreen(),
+            Action::ToggleMaximize => window.toggle_maximize(),
+            Action::ToggleImeInput => window.toggle_ime(),
+            Action::Minimize => window.minimize(),
+            Action::NextCursor => window.next_cursor(),
+            Action::NextCustomCursor => window.next_custom_cursor(&self.custom_cursors),
+            Action::CycleCursorGrab => window.cycle_cursor_grab(),
+            Action::DragWindow => window.drag_window(),
+            Action::DragResizeWindow => window.drag_resize_window(),
+            Action::ShowWindowMenu => window.show_menu(),
+            Action::PrintHelp => self.print_help(),
+            Action::RequestResize => window.swap_dimensions(),
+            Action::SaveReplay => window.save_replay(),
+            Action::DumpDecisions => window.dump_decisions(),
+            Action::TogglePause => window.toggle_pause(),
+            Action::StepOnce => window.step_once(),
+            Action::SlowMotion => window.set_time_scale(SLOW_MOTION_SCALE),
+            Action::NormalSpeed => window.set_time_scale(1.0),
+            Action::FastForward => window.set_time_scale(FAST_FORWARD_SCALE),
+            Action::CycleVsync => window.cycle_present_mode(),
+            Action::Screenshot => {
+                let size = window.window.inner_size();
+                window.capture_screenshot(ScreenshotRequest::timestamped(
+                    size.width * SCREENSHOT_SCALE,
+                    size.height * SCREENSHOT_SCALE,
+                    ExportColorSpace::Srgb8,
+                ));
+            }
+        }
+    }
+
+    fn dump_monitors(&self, event_loop: &ActiveEventLoop) {
+        // info!("Monitors information");
+        let primary_monitor = event_loop.primary_monitor();
+        for monitor in event_loop.available_monitors() {
+            let intro = if primary_monitor.as_ref() == Some(&monitor) {
+                "Primary monitor"
+            } else {
+                "Monitor"
+            };
+
+            if let Some(name) = monitor.name() {
+                info!("{intro}: {name}");
+            } else {
+                info!("{intro}: [no name]");
+            }
+
+            let PhysicalSize { width, height } = monitor.size();
+            info!(
+                "  Current mode: {width}x{height}{}",
+                if let Some(m_hz) = monitor.refresh_rate_millihertz() {
+                    format!(" @ {}.{} Hz", m_hz / 1000, m_hz % 1000)
+                } else {
+                    String::new()
+                }
+            );
+
+            let PhysicalPosition { x, y } = monitor.position();
+            info!("  Position: {x},{y}");
+
+            info!("  Scale factor: {}", monitor.scale_factor());
+
+            info!("  Available modes (width x height x bit-depth):");
+            for mode in monitor.video_modes() {
+                let PhysicalSize { width, height } = mode.size();
+                let bits = mode.bit_depth();
+                let m_hz = mode.refresh_rate_millihertz();
+                info!(
+                    "    {width}x{height}x{bits} @ {}.{} Hz",
+                    m_hz / 1000,
+                    m_hz % 1000
+                );
+            }
+        }
+    }
+
+    /// Process the key binding.
+    fn process_key_binding(key: &str, mods: &ModifiersState) -> Option<Action> {
+        KEY_BINDINGS.iter().find_map(|binding| {
+            binding
+                .is_triggered_by(&key, mods)
+                .then_some(binding.action)
+        })
+    }
+
+    /// Process mouse binding.
+    fn process_mouse_binding(button: MouseButton, mods: &ModifiersState) -> Option<Action> {
+        MOUSE_BINDINGS.iter().find_map(|binding| {
+            binding
+                .is_triggered_by(&button, mods)
+                .then_some(binding.action)
+        })
+    }
+
+    fn print_help(&self) {
+        info!("Keyboard bindings:");
+        for binding in KEY_BINDINGS {
+            info!(
+                "{}{:<10} - {} ({})",
+                modifiers_to_string(binding.mods),
+                binding.trigger,
+                binding.action,
+                binding.action.help(),
+            );
+        }
+        info!("Mouse bindings:");
+        for binding in MOUSE_BINDINGS {
+            info!(
+                "{}{:<10} - {} ({})",
+                modifiers_to_string(binding.mods),
+                mouse_button_to_string(binding.trigger),
+                binding.action,
+                binding.action.help(),
+            );
+        }
+    }
+}
+
+impl ApplicationHandler<UserEvent> for WindowManager {
+    fn user_event(&mut self, _event_loop: &ActiveEventLoop, event: UserEvent) {
+        info!("User event: {event:?}");
+        if let UserEvent::FirstFramePresented(window_id) = event {
+            if let Some(window) = self.windows.get_mut(&window_id) {
+                window.reveal();
+            }
+        }
+    }
+
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: WindowEvent,
+    ) {
+        let window_state = match self.windows.get_mut(&window_id) {
+            Some(window) => window,
+            None => return,
+        };
+
+        match event {
+            WindowEvent::Resized(size) => {
+                window_state.resize(size);
+            }
+            WindowEvent::Focused(focused) => {
+                if focused {
+                    info!("Window={window_id:?} focused");
+                } else {
+                    info!("Window={window_id:?} unfocused");
+                }
+            }
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                info!("Window={window_id:?} changed scale to {scale_factor}");
+                window_state.scale_factor_changed(scale_factor);
+            }
+            WindowEvent::Moved(position) => {
+                window_state.moved(position);
+            }
+            WindowEvent::ThemeChanged(theme) => {
+                info!("Theme changed to {theme:?}");
+                window_state.set_theme(theme);
+            }
+            WindowEvent::RedrawRequested => {}
+            WindowEvent::Occluded(occluded) => {
+                window_state.set_occluded(occluded);
+            }
+            WindowEvent::CloseRequested => {
+                info!("Closing Window={window_id:?}");
+                self.close_window(window_id);
+            }
+            WindowEvent::Destroyed => {
+                // Some X11 window managers destroy the window without asking first
+                warn!("Window={window_id:?} destroyed without a close request");
+                self.close_window(window_id);
+            }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                window_state.modifiers = modifiers.state();
+                window_state
+                    .event_states
+                    .set_modifiers(window_state.modifiers);
+                info!("Modifiers changed to {:?}", window_state.modifiers);
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                // Scrolling up zooms in the 2D world and dollies the camera toward the cursor,
+                // touchpads in pixels count in the same lines as wheels
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => y,
+                    MouseScrollDelta::PixelDelta(px) => px.y as f32 / ORTHO_2D_PIXELS_PER_LINE,
+                };
+                window_state
+                    .event_states
+                    .add_zoom_2d(lines * ORTHO_2D_ZOOM_PER_LINE);
+                window_state.event_states.add_dolly(lines);
+            }
+            WindowEvent::KeyboardInput {
+                event,
+                is_synthetic: false,
+                ..
+            } => {
+                let mods = window_state.modifiers;
+
+                // The failure screen takes every key while shown
+                if event.state.is_pressed() {
+                    match window_state.failure_key(&event.logical_key) {
+                        Some(Some(FailureKey::Exit)) => {
+                            info!("Closing Window={window_id:?} from its failure screen");
+                            self.close_window(window_id);
+                            return;
+                        }
+                        Some(_) => return,
+                        None => (),
+                    }
+                }
+
+                // Dispatch actions only on press.
+                if event.state.is_pressed() {
+                    let action = if let Key::Character(ch) = event.logical_key.as_ref() {
+                        Self::process_key_binding(&ch.to_uppercase(), &mods)
+                    } else {
+                        None
+                    };
+
+                    if let Some(action) = action {
+                        self.handle_action(event_loop, window_id, action);
+                    }
+                }
+            }
+            WindowEvent::MouseInput { button, state, .. } => {
+                window_state
+                    .event_states
+                    .set_mouse_button(button, state.is_pressed());
+                let mods = window_state.modifiers;
+                if let Some(action) = state
+                    .is_pressed()
+                    .then(|| Self::process_mouse_binding(button, &mods))
+                    .flatten()
+                {
+                    self.handle_action(event_loop, window_id, action);
+                }
+            }
+            WindowEvent::CursorLeft { .. } => {
+                // info!("Cursor left Window={window_id:?}");
+                window_state.cursor_left();
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                // info!("Moved cursor to {position:?}");
+                window_state.cursor_moved(position);
+            }
+            WindowEvent::ActivationTokenDone { token: _token, .. } => {}
+            WindowEvent::Ime(event) => match event {
+                Ime::Enabled => {} // info!("IME enabled for Window={window_id:?}"),
+                Ime::Preedit(text, caret_pos) => {
+                    info!("Preedit: {}, with caret at {:?}", text, caret_pos);
+                }
+                Ime::Commit(text) => {
+                    info!("Committed: {}", text);
+                }
+                Ime::Disabled => info!("IME disabled for Window={window_id:?}"),
+            },
+            WindowEvent::PinchGesture { delta, .. } => {
+                window_state
+                    .event_states
+                    .add_zoom_2d((1.0 + delta as f32).max(f32::EPSILON).ln());
+                window_state.zoom += delta;
+                let zoom = window_state.zoom;
+                if delta > 0.0 {
+                    info!("Zoomed in {delta:.5} (now: {zoom:.5})");
+                } else {
+                    info!("Zoomed out {delta:.5} (now: {zoom:.5})");
+                }
+            }
+            WindowEvent::RotationGesture { delta, .. } => {
+                window_state.rotated += delta;
+                let rotated = window_state.rotated;
+                if delta > 0.0 {
+                    info!("Rotated counterclockwise {delta:.5} (now: {rotated:.5})");
+                } else {
+                    info!("Rotated clockwise {delta:.5} (now: {rotated:.5})");
+                }
+            }
+            WindowEvent::PanGesture { delta, phase, .. } => {
+                window_state.panned.x += delta.x;
+                window_state.panned.y += delta.y;
+                info!(
+                    "Panned ({delta:?})) (now: {:?}), {phase:?}",
+                    window_state.panned
+                );
+            }
+            WindowEvent::DoubleTapGesture { .. } => {
+                info!("Smart zoom");
+            }
+            WindowEvent::TouchpadPressure { .. }
+            | WindowEvent::HoveredFileCancelled
+            | WindowEvent::KeyboardInput { .. }
+            | WindowEvent::CursorEntered { .. }
+            | WindowEvent::AxisMotion { .. }
+            | WindowEvent::DroppedFile(_)
+            | WindowEvent::HoveredFile(_)
+            | WindowEvent::Touch(_) => (),
+        }
+    }
+
+    fn device_event(
+        &mut self,
+        _event_loop: &ActiveEventLoop,
+        _device_id: DeviceId,
+        _event: DeviceEvent,
+    ) {
+        // info!("Device {device_id:?} event: {event:?}");
+    }
+
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        info!("Resumed the event loop");
+        self.dump_monitors(event_loop);
+
+        let window_id = match self.create_window(event_loop, None) {
+            Ok(window_id) => window_id,
+            Err(error) => {
+                error!("Failed to create the initial window: {error}");
+                self.startup_error = Some(error);
+                return;
+            }
+        };
+
+        let window_state = self.windows.get_mut(&window_id).unwrap();
+        window_state.start_rendering();
+        self.print_help();
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        for window in self.windows.values_mut() {
+            window.reveal();
+        }
+        let hidden = self.windows.values().any(WindowState::hidden);
+        if self.windows.is_empty() {
+            // info!("No windows left, exiting...");
+            event_loop.exit();
+        } else if self.window_config.stall_threshold_secs > 0.0 || hidden {
+            // Wake up even without events, a stalled render thread produces none and a hidden
+            // window none until it is shown
+            event_loop.set_control_flow(ControlFlow::WaitUntil(
+                Instant::now() + WATCHDOG_POLL_INTERVAL,
+            ));
+        }
+    }
+
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        //
+    }
+}
+
+struct Binding<T: Eq> {
+    trigger: T,
+    mods: ModifiersState,
+    action: Action,
+}
+
+impl<T: Eq> Binding<T> {
+    const fn new(trigger: T, mods: ModifiersState, action: Action) -> Self {
+        Self {
+            trigger,
+            mods,
+            action,
+        }
+    }
+
+    fn is_triggered_by(&self, trigger: &T, mods: &ModifiersState) -> bool {
+        &self.trigger == trigger && &self.mods == mods
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    CloseWindow,
+    ToggleCursorVisibility,
+    CreateNewWindow,
+    ToggleResizeIncrements,
+    ToggleImeInput,
+    ToggleDecorations,
+    ToggleResizable,
+    ToggleFullscreen,
+    ToggleMaximize,
+    Minimize,
+    NextCursor,
+    NextCustomCursor,
+    CycleCursorGrab,
+    PrintHelp,
+    DragWindow,
+    DragResizeWindow,
+    ShowWindowMenu,
+    RequestResize,
+    SaveReplay,
+    DumpDecisions,
+    Screenshot,
+    TogglePause,
+    CycleVsync,
+    StepOnce,
+    SlowMotion,
+    NormalSpeed,
+    FastForward,
+}
+
+impl Action {
+    fn help(&self) -> &'static str {
+        match self {
+            Action::CloseWindow => "Close window",
+            Action::ToggleCursorVisibility => "Hide cursor",
+            Action::CreateNewWindow => "Create new window",
+            Action::ToggleImeInput => "Toggle IME input",
+            Action::ToggleDecorations => "Toggle decorations",
+            Action::ToggleResizable => "Toggle window resizable state",
+            Action::ToggleFullscreen => "Toggle fullscreen",
+            Action::ToggleMaximize => "Maximize",
+            Action::Minimize => "Minimize",
+            Action::ToggleResizeIncrements => "Use resize increments when resizing window",
+            Action::NextCursor => "Advance the cursor to the next value",
+            Action::NextCustomCursor => "Advance custom cursor to the next value",
+            Action::CycleCursorGrab => "Cycle through cursor grab mode",
+            Action::PrintHelp => "Print help",
+            Action::DragWindow => "Start window drag",
+            Action::DragResizeWindow => "Start window drag-resize",
+            Action::ShowWindowMenu => "Show window menu",
+            Action::RequestResize => "Request a resize",
+            Action::SaveReplay => "Save the last seconds as a GIF",
+            Action::DumpDecisions => {
+                "Save the recent render decisions to the diagnostics directory"
+            }
+            Action::Screenshot => "Save a screenshot at twice the window resolution",
+            Action::TogglePause => "Pause or resume the simulation",
+            Action::CycleVsync => "Switch to the next present mode preference",
+            Action::StepOnce => "Run a single simulation tick while paused",
+            Action::SlowMotion => "Run the simulation at a quarter speed",
+            Action::NormalSpeed => "Run the simulation in real time",
+            Action::FastForward => "Run the simulation four times faster",
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&self, f)
+    }
+}
+
+#[cfg(feature = "images")]
+fn builtin_icon() -> Option<Icon> {
+    Some(load_icon(include_bytes!("../assets/img/icon.png")))
+}
+
+/// Without an image decoder windows keep the platform icon.
+#[cfg(not(feature = "images"))]
+fn builtin_icon() -> Option<Icon> {
+    None
+}
+
+#[cfg(feature = "images")]
+fn builtin_cursors<T>(event_loop: &EventLoop<T>) -> Vec<CustomCursor> {
+    [
+        include_bytes!("../assets/img/cross.png").as_slice(),
+        include_bytes!("../assets/img/cross2.png"),
+        include_bytes!("../assets/img/gradient.png"),
+    ]
+    .into_iter()
+    .map(|bytes| event_loop.create_custom_cursor(decode_cursor(bytes)))
+    .collect()
+}
+
+/// Without an image decoder only the named cursors are available.
+#[cfg(not(feature = "images"))]
+fn builtin_cursors<T>(_event_loop: &EventLoop<T>) -> Vec<CustomCursor> {
+    Vec::new()
+}
+
+#[cfg(feature = "images")]
+fn decode_cursor(bytes: &[u8]) -> winit::window::CustomCursorSource {
+    let img = image::load_from_memory(bytes).unwrap().to_rgba8();
+    let samples = img.into_flat_samples();
+    let (_, w, h) = samples.extents();
+    let (w, h) = (w as u16, h as u16);
+    CustomCursor::from_rgba(samples.samples, w, h, w / 2, h / 2).unwrap()
+}
+
+#[cfg(feature = "images")]
+fn load_icon(bytes: &[u8]) -> Icon {
+    let (icon_rgba, icon_width, icon_height) = {
+        let image = image::load_from_memory(bytes).unwrap().into_rgba8();
+        let (width, height) = image.dimensions();
+        let rgba = image.into_raw();
+        (rgba, width, height)
+    };
+    Icon::from_rgba(icon_rgba, icon_width, icon_height).expect("Failed to open icon")
+}
+
+fn modifiers_to_string(mods: ModifiersState) -> String {
+    [
+        (ModifiersState::SUPER, "Super+"),
+        (ModifiersState::ALT, "Alt+"),
+        (ModifiersState::CONTROL, "Ctrl+"),
+        (ModifiersState::SHIFT, "Shift+"),
+    ]
+    .iter()
+    .filter(|(modifier, _)| mods.contains(*modifier))
+    .map(|(_, desc)| *desc)
+    .collect::<String>()
+}
+
+fn mouse_button_to_string(button: MouseButton) -> &'static str {
+    match button {
+        MouseButton::Left => "LMB",
+        MouseButton::Right => "RMB",
+        MouseButton::Middle => "MMB",
+        MouseButton::Back => "Back",
+        MouseButton::Forward => "Forward",
+        MouseButton::Other(_) => "Other",
+    }
+}
+
+const KEY_BINDINGS: &[Binding<&'static str>] = &[
+    Binding::new("Q", ModifiersState::CONTROL, Action::CloseWindow),
+    Binding::new("H", ModifiersState::CONTROL, Action::PrintHelp),
+    Binding::new("F", ModifiersState::CONTROL, Action::ToggleFullscreen),
+    Binding::new("D", ModifiersState::CONTROL, Action::ToggleDecorations),
+    Binding::new("I", ModifiersState::CONTROL, Action::ToggleImeInput),
+    Binding::new("L", ModifiersState::CONTROL, Action::CycleCursorGrab),
+    Binding::new("P", ModifiersState::CONTROL, Action::ToggleResizeIncrements),
+    Binding::new("R", ModifiersState::CONTROL, Action::ToggleResizable),
+    Binding::new("R", ModifiersState::ALT, Action::RequestResize),
+    // M.
+    Binding::new("M", ModifiersState::CONTROL, Action::ToggleMaximize),
+    Binding::new("M", ModifiersState::ALT, Action::Minimize),
+    // N.
+    Binding::new("N", ModifiersState::CONTROL, Action::CreateNewWindow),
+    // C.
+    Binding::new("C", ModifiersState::CONTROL, Action::NextCursor),
+    Binding::new("C", ModifiersState::ALT, Action::NextCustomCursor),
+    Binding::new("Z", ModifiersState::CONTROL, Action::ToggleCursorVisibility),
+    Binding::new("S", ModifiersState::CONTROL, Action::SaveReplay),
+    Binding::new("D", ModifiersState::ALT, Action::DumpDecisions),
+    Binding::new("P", ModifiersState::ALT, Action::Screenshot),
+    Binding::new("V", ModifiersState::ALT, Action::CycleVsync),
+    // Simulation time.
+    Binding::new("T", ModifiersState::ALT, Action::TogglePause),
+    Binding::new("S", ModifiersState::ALT, Action::StepOnce),
+    Binding::new("1", ModifiersState::ALT, Action::SlowMotion),
+    Binding::new("2", ModifiersState::ALT, Action::NormalSpeed),
+    Binding::new("3", ModifiersState::ALT, Action::FastForward),
+];
+
+const MOUSE_BINDINGS: &[Binding<MouseButton>] = &[
+    Binding::new(
+        MouseButton::Left,
+        ModifiersState::ALT,
+        Action::DragResizeWindow,
+    ),
+    Binding::new(
+        MouseButton::Left,
+        ModifiersState::CONTROL,
+        Action::DragWindow,
+    ),
+    Binding::new(
+        MouseButton::Right,
+        ModifiersState::CONTROL,
+        Action::ShowWindowMenu,
+    ),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bindings_resolve_with_their_modifiers() {
+        assert_eq!(
+            WindowManager::process_key_binding("Q", &ModifiersState::CONTROL),
+            Some(Action::CloseWindow)
+        );
+        assert_eq!(
+            WindowManager::process_key_binding("R", &ModifiersState::ALT),
+            Some(Action::RequestResize)
+        );
+        assert_eq!(
+            WindowManager::process_key_binding("Q", &ModifiersState::empty()),
+            None
+        );
+        assert_eq!(
+            WindowManager::process_mouse_binding(MouseButton::Left, &ModifiersState::CONTROL),
+            Some(Action::DragWindow)
+        );
+        assert_eq!(
+            WindowManager::process_mouse_binding(MouseButton::Middle, &ModifiersState::CONTROL),
+            None
+        );
+    }
+
+    #[test]
+    fn no_binding_is_shadowed() {
+        for (index, binding) in KEY_BINDINGS.iter().enumerate() {
+            assert!(
+                !KEY_BINDINGS[..index]
+                    .iter()
+                    .any(|other| other.is_triggered_by(&binding.trigger, &binding.mods)),
+                "{} {:?} is bound twice",
+                binding.trigger,
+                binding.mods
+            );
+        }
+        for (index, binding) in MOUSE_BINDINGS.iter().enumerate() {
+            assert!(!MOUSE_BINDINGS[..index]
+                .iter()
+                .any(|other| other.is_triggered_by(&binding.trigger, &binding.mods)));
+        }
+    }
+
+    #[test]
+    fn first_frame_is_marked_once() {
+        let event_states = EventStates::default();
+        assert!(!event_states.first_frame_presented());
+        assert!(event_states.mark_first_frame_presented());
+        assert!(!event_states.mark_first_frame_presented());
+        assert!(event_states.first_frame_presented());
+    }
+}
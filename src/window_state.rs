@@ -1,19 +1,41 @@
 use crate::{
-    app::Application,
+    background::{BackgroundMode, BACKGROUND_USER_FLOATS},
+    batching::MeshBatch,
+    block_compression::CompressedTexture,
+    camera::{CameraController, Ortho2DController},
+    compute::VertexDeformer,
+    custom_pass::{CustomPass, CustomPassSlot},
+    debug_lines::DebugLines,
+    display::{DisplayEnvironment, MonitorBounds},
+    error::PulsarError,
+    failure::{Failure, FailureKey, RetryCallback},
+    flight_recorder::DumpReason,
+    handles::MeshHandle,
     input_manager::EventStates,
-    vulkan::{graphics::AAAGraphics, surface::AAASurface, AAABase},
+    inset::InsetView,
+    instancing::Instances,
+    loader::LoadTarget,
+    material::Material,
+    model::{Mesh, MeshSpace, MeshUpdate},
+    present_health::{PresentDowngrade, PresentModePreference},
+    renderer::WindowRenderer,
+    residency::ResidencyPriority,
+    screenshot::ScreenshotRequest,
+    skinning::AnimationPlayer,
+    texture::{CubemapSource, SamplerDesc, TextureArrayDesc, TextureUpdate},
+    thumbnail::{Thumbnail, ThumbnailTarget},
+    time_control::TimeControl,
+    watchdog::{StallReport, Watchdog},
+    window_manager::FIRST_FRAME_TIMEOUT,
 };
+use ash::vk;
 use cursor_icon::CursorIcon;
-use log::info;
-use std::{
-    error::Error,
-    mem,
-    sync::{Arc, Mutex},
-    thread,
-};
+use glam::Vec2;
+use log::{info, warn};
+use std::{mem, path::PathBuf, sync::Arc, time::Instant};
 use winit::{
     dpi::{LogicalSize, PhysicalPosition, PhysicalSize},
-    keyboard::ModifiersState,
+    keyboard::{Key, ModifiersState},
     window::{Cursor, CursorGrabMode, CustomCursor, Fullscreen, ResizeDirection, Theme, Window},
 };
 
@@ -85,21 +107,34 @@ pub struct WindowState {
     cursor_hidden: bool,
 
     // Render
-    // // TODO Remove pub
-    pub renderer: Arc<AAABase>,
-    pub surface: Arc<Mutex<AAASurface>>,
-
-    pub graphics: Option<Arc<Mutex<AAAGraphics>>>,
-
-    pub render_handle: Option<thread::JoinHandle<()>>,
+    renderer: Box<dyn WindowRenderer>,
 
     pub event_states: Arc<EventStates>,
+    /// Display environment as last reported by winit, the render thread applies it on its next frame.
+    pub display: DisplayEnvironment,
+    /// Scale and pause state last posted to the render thread's simulation clock.
+    time_control: TimeControl,
+    /// Present mode preference last posted to the render thread, `None` while the window keeps
+    /// the configured one.
+    present_preference: Option<PresentModePreference>,
+    /// Monitor the window was on when its refresh rate was last read.
+    monitor_bounds: Option<MonitorBounds>,
+    watchdog: Watchdog,
+    /// Created hidden at this instant, shown once its first frame is presented.
+    hidden_since: Option<Instant>,
+    first_frame_reported: bool,
 }
 
 impl WindowState {
-    pub fn new(app: &Application, window: Window) -> Result<Self, Box<dyn Error>> {
-        let window = Arc::new(window);
-
+    pub fn new(
+        window: Arc<Window>,
+        renderer: Box<dyn WindowRenderer>,
+        event_states: Arc<EventStates>,
+        display: DisplayEnvironment,
+        custom_cursor_count: usize,
+        watchdog: Watchdog,
+        hidden: bool,
+    ) -> Self {
         let theme = window.theme().unwrap_or(Theme::Dark);
         info!("Theme: {theme:?}");
         let named_idx = 0;
@@ -109,14 +144,16 @@ impl WindowState {
         let ime = true;
         window.set_ime_allowed(ime);
 
-        let renderer = app.renderer.clone();
+        let monitor_bounds = window
+            .current_monitor()
+            .map(|monitor| MonitorBounds::from_monitor(&monitor));
 
-        let surface =
-            crate::vulkan::surface::AAASurface::new(&renderer, &window, &app.physical_device_list)
-                .unwrap();
-
-        Ok(Self {
-            custom_idx: app.custom_cursors.len() - 1,
+        Self {
+            display,
+            time_control: TimeControl::default(),
+            present_preference: None,
+            monitor_bounds,
+            custom_idx: custom_cursor_count - 1,
             cursor_grab: CursorGrabMode::None,
             named_idx,
             window,
@@ -130,11 +167,11 @@ impl WindowState {
             panned: Default::default(),
             zoom: Default::default(),
             renderer,
-            surface: Arc::new(Mutex::new(surface)),
-            render_handle: Default::default(),
-            event_states: Default::default(),
-            graphics: Default::default(),
-        })
+            event_states,
+            watchdog,
+            hidden_since: hidden.then(Instant::now),
+            first_frame_reported: false,
+        }
     }
 
     pub fn toggle_ime(&mut self) {
@@ -152,6 +189,8 @@ impl WindowState {
 
     pub fn cursor_moved(&mut self, position: PhysicalPosition<f64>) {
         self.cursor_position = Some(position);
+        self.event_states
+            .set_cursor_position(Some(Vec2::new(position.x as f32, position.y as f32)));
         if self.ime {
             self.window
                 .set_ime_cursor_area(position, PhysicalSize::new(20, 20));
@@ -160,6 +199,7 @@ impl WindowState {
 
     pub fn cursor_left(&mut self) {
         self.cursor_position = None;
+        self.event_states.set_cursor_position(None);
     }
 
     /// Toggle maximized.
@@ -247,27 +287,339 @@ impl WindowState {
     }
 
     pub fn next_custom_cursor(&mut self, custom_cursors: &[CustomCursor]) {
+        if custom_cursors.is_empty() {
+            return;
+        }
         self.custom_idx = (self.custom_idx + 1) % custom_cursors.len();
         let cursor = Cursor::Custom(custom_cursors[self.custom_idx].clone());
         self.window.set_cursor(cursor);
     }
 
     pub fn resize(&mut self, size: PhysicalSize<u32>) {
-        self.render_thread_close_join();
+        self.display.width = size.width;
+        self.display.height = size.height;
+        self.renderer.resize(self.display);
+    }
 
-        let width = size.width;
-        let height = size.height;
+    pub fn scale_factor_changed(&mut self, scale_factor: f64) {
+        self.display = self.display.rescaled(scale_factor);
+        self.refresh_monitor();
+        self.renderer.resize(self.display);
+    }
 
-        if width == 0 || height == 0 {
+    /// The window may have moved to a monitor with another refresh rate. The monitor is only
+    /// queried again once the window's center leaves the last one, dragging sends many moves.
+    pub fn moved(&mut self, position: PhysicalPosition<i32>) {
+        if self
+            .monitor_bounds
+            .is_some_and(|bounds| !bounds.left_by(position, &self.display))
+        {
             return;
         }
+        if self.refresh_monitor() {
+            self.renderer.resize(self.display);
+        }
+    }
+
+    fn refresh_monitor(&mut self) -> bool {
+        let monitor = self.window.current_monitor();
+        self.monitor_bounds = monitor.as_ref().map(MonitorBounds::from_monitor);
+        let refresh_rate_millihertz = monitor.and_then(|monitor| monitor.refresh_rate_millihertz());
+        let changed = refresh_rate_millihertz != self.display.refresh_rate_millihertz;
+        self.display.refresh_rate_millihertz = refresh_rate_millihertz;
+        changed
+    }
+
+    /// Ask the render thread to save the replay buffer.
+    pub fn save_replay(&self) {
+        if cfg!(feature = "replay") {
+            self.event_states.request_replay_save();
+        } else {
+            warn!("Built without the replay feature, nothing was recorded");
+        }
+    }
+
+    /// Render the scene at `request`'s extent, whatever the window size, after the next frame.
+    pub fn capture_screenshot(&self, request: ScreenshotRequest) {
+        self.event_states.request_screenshot(request);
+    }
+
+    /// Hint that `material` will be drawn soon, its pipeline is built in the background.
+    pub fn precompile(&self, material: Material) {
+        self.event_states.request_precompile(material);
+    }
+
+    /// User id of the mesh selected in this window.
+    pub fn selected_user_id(&self) -> Option<u64> {
+        self.event_states.selection().map(|(_, user_id)| user_id)
+    }
+
+    /// Reports a render thread that stopped producing frames, once per stall.
+    pub fn check_render_thread(&mut self) -> Option<StallReport> {
+        let report = self.watchdog.check(&self.event_states.heartbeat)?;
+        self.event_states
+            .dump_decisions(&DumpReason::Stalled(report));
+        Some(report)
+    }
+
+    /// Reports that the render thread presented its first frame, once.
+    pub fn check_first_frame(&mut self) -> bool {
+        if self.first_frame_reported || !self.event_states.first_frame_presented() {
+            return false;
+        }
+        self.first_frame_reported = true;
+        true
+    }
+
+    pub fn first_frame_presented(&self) -> bool {
+        self.event_states.first_frame_presented()
+    }
+
+    /// Whether the window is still hidden waiting for its first frame.
+    pub fn hidden(&self) -> bool {
+        self.hidden_since.is_some()
+    }
+
+    /// Show the window created hidden, once its first frame was presented or it waited longer
+    /// than [`FIRST_FRAME_TIMEOUT`]. Returns whether it was shown.
+    pub fn reveal(&mut self) -> bool {
+        let Some(hidden_since) = self.hidden_since else {
+            return false;
+        };
+        if !self.event_states.first_frame_presented() {
+            if hidden_since.elapsed() < FIRST_FRAME_TIMEOUT {
+                return false;
+            }
+            warn!(
+                "No frame presented {FIRST_FRAME_TIMEOUT:?} after creating Window={:?}, showing it anyway",
+                self.window.id()
+            );
+        }
+        self.hidden_since = None;
+        self.window.set_visible(true);
+        true
+    }
+
+    /// Reports that the render thread gave up on MAILBOX, once.
+    pub fn check_present_mode(&self) -> Option<PresentDowngrade> {
+        self.event_states.take_present_downgrade()
+    }
+
+    /// Save the render thread's recent decisions to the diagnostics directory.
+    pub fn dump_decisions(&self) {
+        self.event_states.dump_decisions(&DumpReason::Requested);
+    }
+
+    pub fn selected_mesh(&self) -> Option<MeshHandle> {
+        self.event_states.selection().map(|(mesh, _)| mesh)
+    }
+
+    /// Copy `update` into the texture before the next frame.
+    pub fn update_texture(&self, update: TextureUpdate) {
+        self.event_states.post_texture_update(update);
+    }
+
+    /// Replace the texture with the image at `path`, sampled as `sampler`, before the next frame.
+    pub fn load_texture(&self, path: PathBuf, sampler: SamplerDesc) {
+        self.event_states.request_texture_file(path, sampler);
+    }
+
+    /// Replace the texture with the blocks of `texture`, sampled as `sampler`, before the next
+    /// frame.
+    pub fn load_compressed_texture(&self, texture: CompressedTexture, sampler: SamplerDesc) {
+        self.event_states
+            .request_compressed_texture(texture, sampler);
+    }
+
+    /// Replace the texture array before the next frame, without its layers.
+    pub fn create_texture_array(&self, desc: TextureArrayDesc) {
+        self.event_states.request_texture_array(desc);
+    }
+
+    /// Copy `data` into the texture array's next layer before the next frame.
+    pub fn push_texture_layer(&self, data: Vec<u8>) {
+        self.event_states.push_texture_layer(data);
+    }
+
+    /// Replace the environment cubemap with the faces of `source` before the next frame.
+    pub fn load_cubemap(&self, source: CubemapSource, sampler: SamplerDesc) {
+        self.event_states.request_cubemap(Some((source, sampler)));
+    }
+
+    /// Go back to the flat environment before the next frame.
+    pub fn clear_cubemap(&self) {
+        self.event_states.request_cubemap(None);
+    }
+
+    pub fn set_texture_residency(&self, priority: ResidencyPriority) {
+        self.event_states.set_texture_residency(priority);
+    }
+
+    pub fn set_environment_intensity(&self, intensity: f32) {
+        self.event_states.set_environment_intensity(intensity);
+    }
+
+    /// Draw `failure` in place of the scene from the next frame.
+    pub fn show_failure(&self, failure: Failure, retry: Option<RetryCallback>) {
+        warn!("Showing failure {:?}: {}", failure.category, failure.title);
+        self.event_states
+            .failure_screen
+            .lock()
+            .unwrap()
+            .enter(failure, retry);
+    }
+
+    pub fn clear_failure(&self) {
+        self.event_states.failure_screen.lock().unwrap().leave();
+    }
+
+    /// What `key` does on the failure screen, `None` when no failure is shown. Every key is taken
+    /// by the screen while it is.
+    pub fn failure_key(&self, key: &Key) -> Option<Option<FailureKey>> {
+        let mut screen = self.event_states.failure_screen.lock().unwrap();
+        screen.failure()?;
+        let action = FailureKey::from_key(key);
+        if action == Some(FailureKey::Retry) {
+            screen.request_retry();
+        }
+        Some(action)
+    }
+
+    /// Thumbnail of `target`, rendered over the next frames.
+    pub fn request_thumbnail(&self, target: ThumbnailTarget, size: u32) -> Thumbnail {
+        self.event_states.request_thumbnail(target, size)
+    }
+
+    /// Register `mesh` before the next frame, drawn with the camera of `space`.
+    pub fn add_mesh(
+        &self,
+        mesh: Mesh,
+        space: MeshSpace,
+        instances: Option<Instances>,
+    ) -> MeshHandle {
+        self.event_states
+            .request_mesh_addition(mesh, space, instances)
+    }
+
+    /// Current renderer generation, for meshes loaded in the background.
+    pub fn load_target(&self) -> LoadTarget {
+        LoadTarget::new(&self.event_states)
+    }
+
+    pub fn add_skinned_mesh(&self, mesh: Mesh, player: AnimationPlayer) -> MeshHandle {
+        self.event_states.request_skinned_addition(mesh, player)
+    }
 
-        let graphics_locked = self.graphics.clone().unwrap();
-        let mut graphics_lock = graphics_locked.lock().unwrap();
-        graphics_lock.recreate_swapchain(width, height);
-        drop(graphics_lock);
+    pub fn add_deformed_mesh(&self, mesh: Mesh, deformer: VertexDeformer) -> MeshHandle {
+        self.event_states.request_deformed_addition(mesh, deformer)
+    }
+
+    pub fn add_mesh_batch(&self, batch: MeshBatch, space: MeshSpace) -> MeshHandle {
+        self.event_states.request_batch_addition(batch, space)
+    }
+
+    /// Replace the 2D world controller, its view included.
+    pub fn set_ortho_2d(&self, controller: Ortho2DController) {
+        self.event_states.set_ortho_2d(controller);
+    }
+
+    /// Replace how the scroll wheel moves the perspective camera.
+    pub fn set_camera_controller(&self, controller: CameraController) {
+        self.event_states.set_camera_controller(controller);
+    }
+
+    pub fn time_control(&self) -> TimeControl {
+        self.time_control
+    }
+
+    /// Slow, speed up or pause the simulation from the next frame on, see [`TimeControl`].
+    pub fn set_time_control(&mut self, control: TimeControl) {
+        self.time_control = control;
+        self.event_states.set_time_control(control);
+    }
+
+    pub fn toggle_pause(&mut self) {
+        let control = self.time_control.with_paused(!self.time_control.paused);
+        info!(
+            "Simulation {}",
+            if control.paused { "paused" } else { "resumed" }
+        );
+        self.set_time_control(control);
+    }
+
+    /// Preference of the current swapchain and the mode it presents with, `None` until the
+    /// render thread created one.
+    pub fn present_mode(&self) -> Option<(PresentModePreference, vk::PresentModeKHR)> {
+        self.event_states.present_mode()
+    }
 
-        self.spawn_render_thread_and_render();
+    /// Distance the depth of field is focused at, `None` while it is disabled.
+    pub fn focus_distance(&self) -> Option<f32> {
+        self.event_states.focus_distance()
+    }
+
+    /// Recreate the swapchain with `preference` before the next frame.
+    pub fn set_present_preference(&mut self, preference: PresentModePreference) {
+        self.present_preference = Some(preference);
+        self.event_states.set_present_preference(preference);
+    }
+
+    /// Switch to the next [`PresentModePreference`], from the one asked for last.
+    pub fn cycle_present_mode(&mut self) {
+        let current = self
+            .present_preference
+            .or_else(|| self.present_mode().map(|(preference, _)| preference))
+            .unwrap_or_default();
+        let preference = current.next();
+        info!("Presenting with {preference:?} preferred");
+        self.set_present_preference(preference);
+    }
+
+    /// Run a single simulation tick, only while paused.
+    pub fn step_once(&self) {
+        if self.time_control.paused {
+            self.event_states.request_time_step();
+        }
+    }
+
+    pub fn set_time_scale(&mut self, scale: f32) {
+        let control = self.time_control.with_scale(scale);
+        info!("Simulation at {}x", control.scale);
+        self.set_time_control(control);
+    }
+
+    /// Record `pass` at `slot` from the next frame on, `None` removes the slot's pass.
+    pub fn set_custom_pass(&self, slot: CustomPassSlot, pass: Option<CustomPass>) {
+        self.event_states.set_custom_pass(slot, pass);
+    }
+
+    /// Draw `lines` from the next frame on, in place of the previous ones.
+    pub fn set_debug_lines(&self, lines: DebugLines) {
+        self.event_states.set_debug_lines(lines);
+    }
+
+    /// Draw `mode` behind the scene from the next frame on.
+    pub fn set_background(&self, mode: BackgroundMode) {
+        self.event_states.set_background(mode);
+    }
+
+    pub fn set_background_uniforms(&self, user: [f32; BACKGROUND_USER_FLOATS]) {
+        self.event_states.set_background_uniforms(user);
+    }
+
+    /// Draw `insets` from the next frame on, in place of the previous ones.
+    pub fn set_insets(&self, insets: Vec<InsetView>) {
+        self.event_states.set_insets(insets);
+    }
+
+    /// Mark `mesh` dirty with new contents, copied before the next frame.
+    pub fn update_mesh(&self, mesh: MeshHandle, update: MeshUpdate) -> Result<(), PulsarError> {
+        self.event_states.post_mesh_update(mesh, update)
+    }
+
+    /// Free `mesh` before the next frame, once the frames drawing it are done.
+    pub fn remove_mesh(&self, mesh: MeshHandle) {
+        self.event_states.request_mesh_removal(mesh);
     }
 
     pub fn set_theme(&mut self, theme: Theme) {
@@ -345,46 +697,17 @@ impl WindowState {
         }
     }
 
-    pub fn create_renderer(&mut self) {
-        let renderer = self.renderer.clone();
-        let event_states = self.event_states.clone();
-        let width = self.window.inner_size().width;
-        let height = self.window.inner_size().height;
-        let graphics = {
-            let surface_locked = self.surface.clone();
-            AAAGraphics::new(renderer, surface_locked, event_states, width, height)
-        };
-        self.graphics = Some(Arc::new(Mutex::new(graphics)));
-
-        self.spawn_render_thread_and_render();
+    pub fn start_rendering(&mut self) {
+        self.renderer.render();
     }
 
-    pub fn render_thread_close_join(&mut self) {
-        self.event_states.exiting();
-        if let Some(handle) = self.render_handle.take() {
-            handle.join().unwrap();
-        }
+    pub fn stop_rendering(&mut self) {
+        self.renderer.shutdown();
     }
 
-    pub fn spawn_render_thread_and_render(&mut self) {
-        self.event_states.opening();
-        let graphics_locked = self.graphics.clone().unwrap();
-        self.render_handle = Some(thread::spawn(move || {
-            let mut graphics = graphics_locked.lock().unwrap();
-            graphics.cycle();
-        }));
-    }
-}
-
-impl Drop for WindowState {
-    fn drop(&mut self) {
-        self.graphics = None;
-        let surface_guard = self.surface.lock().unwrap();
-        unsafe {
-            // TODO move on its own struct
-            self.renderer
-                .surface_loader
-                .destroy_surface(surface_guard.surface_khr, None)
-        };
+    /// Stop rendering for good, the loads still running for the window are cancelled.
+    pub fn close(&mut self) {
+        self.event_states.retire();
+        self.stop_rendering();
     }
 }
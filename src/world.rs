@@ -0,0 +1,276 @@
+use crate::model::{Mesh, Scene, Vertex};
+use glam::{Mat4, Vec3};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+/// Line width of the reference grid, in world units.
+const GRID_LINE_WIDTH: f32 = 0.01;
+const GRID_COLOR: [f32; 4] = [0.4, 0.4, 0.4, 1.0];
+/// Every n-th line of the grid is highlighted.
+const GRID_MAJOR_EVERY: i32 = 5;
+const GRID_MAJOR_COLOR: [f32; 4] = [0.6, 0.6, 0.6, 1.0];
+const AXIS_THICKNESS: f32 = 0.02;
+
+/// Which world axis points up. Right handed either way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WorldConvention {
+    #[default]
+    YUp,
+    ZUp,
+}
+
+impl WorldConvention {
+    pub fn up(self) -> Vec3 {
+        match self {
+            Self::YUp => Vec3::Y,
+            Self::ZUp => Vec3::Z,
+        }
+    }
+
+    /// Direction a default camera looks at.
+    pub fn forward(self) -> Vec3 {
+        match self {
+            Self::YUp => Vec3::NEG_Z,
+            Self::ZUp => Vec3::Y,
+        }
+    }
+
+    pub fn right(self) -> Vec3 {
+        Vec3::X
+    }
+
+    /// Two axes spanning the ground plane.
+    pub fn ground_axes(self) -> (Vec3, Vec3) {
+        (self.right(), -self.forward())
+    }
+
+    /// Maps positions authored in `self` to `target`, a quarter turn around X when they differ.
+    pub fn conversion_to(self, target: Self) -> Mat4 {
+        match (self, target) {
+            (Self::YUp, Self::ZUp) => Mat4::from_rotation_x(std::f32::consts::FRAC_PI_2),
+            (Self::ZUp, Self::YUp) => Mat4::from_rotation_x(-std::f32::consts::FRAC_PI_2),
+            _ => Mat4::IDENTITY,
+        }
+    }
+
+    /// View matrix looking from `eye` at `target` with this convention's up.
+    pub fn look_at(self, eye: Vec3, target: Vec3) -> Mat4 {
+        let up = self.up();
+        let direction = (target - eye).normalize_or_zero();
+        // Looking straight up or down, any horizontal up keeps the basis valid
+        let up = if direction.cross(up).length_squared() < 1e-6 {
+            -self.forward()
+        } else {
+            up
+        };
+        Mat4::look_at_rh(eye, target, up)
+    }
+
+    /// Warns when `authored` differs from `self`, returns the transform to apply if `convert`.
+    pub fn reconcile(self, authored: Self, convert: bool) -> Option<Mat4> {
+        if authored == self {
+            return None;
+        }
+        warn!(
+            "Scene authored {authored:?} loaded in a {self:?} world{}",
+            if convert { ", converting" } else { "" }
+        );
+        convert.then(|| authored.conversion_to(self))
+    }
+}
+
+impl Mesh {
+    /// Move the vertices from the `from` convention to `to`, normals and tangents included.
+    pub fn convert(&mut self, from: WorldConvention, to: WorldConvention) {
+        if from == to {
+            return;
+        }
+        let conversion = from.conversion_to(to);
+        for vertex in &mut self.vertices {
+            let [x, y, z, w] = vertex.pos;
+            let pos = conversion.transform_point3(Vec3::new(x, y, z));
+            vertex.pos = [pos.x, pos.y, pos.z, w];
+            vertex.normal = conversion
+                .transform_vector3(Vec3::from(vertex.normal))
+                .to_array();
+            let [tx, ty, tz, handedness] = vertex.tangent;
+            let tangent = conversion.transform_vector3(Vec3::new(tx, ty, tz));
+            vertex.tangent = [tangent.x, tangent.y, tangent.z, handedness];
+        }
+    }
+}
+
+impl Scene {
+    /// Bring a loaded scene into the `current` convention, warning when it was authored in another one.
+    pub fn conform(&mut self, current: WorldConvention, convert: bool) {
+        let Some(conversion) = current.reconcile(self.convention, convert) else {
+            return;
+        };
+        for mesh in self.models.iter_mut().flat_map(|model| &mut model.meshes) {
+            mesh.transform = conversion * mesh.transform;
+        }
+        self.convention = current;
+    }
+}
+
+/// Grid on the ground plane, `half_extent` lines on each side of the origin.
+pub fn reference_grid(convention: WorldConvention, half_extent: i32, spacing: f32) -> Mesh {
+    let (u, v) = convention.ground_axes();
+    let normal = convention.up();
+    let length = half_extent as f32 * spacing;
+    let mut mesh = Mesh {
+        vertices: Vec::new(),
+        indices: Vec::new(),
+        transform: Mat4::IDENTITY,
+        user_id: 0,
+    };
+    for i in -half_extent..=half_extent {
+        let color = if i % GRID_MAJOR_EVERY == 0 {
+            GRID_MAJOR_COLOR
+        } else {
+            GRID_COLOR
+        };
+        let offset = i as f32 * spacing;
+        for (along, across) in [(u, v), (v, u)] {
+            let center = across * offset;
+            let half_width = across * GRID_LINE_WIDTH / 2.0;
+            push_quad(
+                &mut mesh,
+                [
+                    center - along * length - half_width,
+                    center + along * length - half_width,
+                    center + along * length + half_width,
+                    center - along * length + half_width,
+                ],
+                normal,
+                color,
+            );
+        }
+    }
+    mesh
+}
+
+/// Red, green and blue bars along +X, +Y and +Z.
+pub fn axis_gizmo(length: f32) -> Mesh {
+    let mut mesh = Mesh {
+        vertices: Vec::new(),
+        indices: Vec::new(),
+        transform: Mat4::IDENTITY,
+        user_id: 0,
+    };
+    let axes = [
+        (Vec3::X, [1.0, 0.0, 0.0, 1.0]),
+        (Vec3::Y, [0.0, 1.0, 0.0, 1.0]),
+        (Vec3::Z, [0.0, 0.0, 1.0, 1.0]),
+    ];
+    for (axis, color) in axes {
+        let side = axis.any_orthonormal_vector() * AXIS_THICKNESS / 2.0;
+        let other = axis.cross(side);
+        let tip = axis * length;
+        for (face, width) in [(side, other), (-side, other), (other, side), (-other, side)] {
+            push_quad(
+                &mut mesh,
+                [
+                    face - width,
+                    tip + face - width,
+                    tip + face + width,
+                    face + width,
+                ],
+                face.normalize(),
+                color,
+            );
+        }
+    }
+    mesh
+}
+
+fn push_quad(mesh: &mut Mesh, corners: [Vec3; 4], normal: Vec3, color: [f32; 4]) {
+    let offset = mesh.vertices.len() as u32;
+    let tangent = (corners[1] - corners[0]).normalize_or_zero();
+    let uvs = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+    for (corner, uv) in corners.into_iter().zip(uvs) {
+        mesh.vertices.push(Vertex {
+            pos: [corner.x, corner.y, corner.z, 1.0],
+            uv,
+            color,
+            normal: normal.to_array(),
+            tangent: tangent.extend(1.0).to_array(),
+            joints: [0; 4],
+            weights: [0.0; 4],
+        });
+    }
+    mesh.indices.extend([
+        offset,
+        offset + 1,
+        offset + 2,
+        offset,
+        offset + 2,
+        offset + 3,
+    ]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Model;
+
+    const TOLERANCE: f32 = 1e-5;
+    const CONVENTIONS: [WorldConvention; 2] = [WorldConvention::YUp, WorldConvention::ZUp];
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!(a.abs_diff_eq(b, TOLERANCE), "{a} is not {b}");
+    }
+
+    #[test]
+    fn conversions_map_the_axes_and_round_trip() {
+        for from in CONVENTIONS {
+            for to in CONVENTIONS {
+                let conversion = from.conversion_to(to);
+                assert_close(conversion.transform_vector3(from.up()), to.up());
+                assert_close(conversion.transform_vector3(from.forward()), to.forward());
+                let round_trip = to.conversion_to(from) * conversion;
+                assert!(
+                    round_trip.abs_diff_eq(Mat4::IDENTITY, TOLERANCE),
+                    "{from:?} to {to:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn cameras_stay_upright() {
+        for convention in CONVENTIONS {
+            // A camera behind the origin sees the convention's up as screen up
+            let eye = -convention.forward() * 4.0;
+            let view = convention.look_at(eye, Vec3::ZERO);
+            assert_close(view.transform_vector3(convention.up()), Vec3::Y);
+            assert_close(view.transform_point3(Vec3::ZERO), Vec3::new(0.0, 0.0, -4.0));
+            // Straight down stays a valid basis
+            let top_down = convention.look_at(convention.up() * 4.0, Vec3::ZERO);
+            assert!(top_down.is_finite(), "{convention:?}");
+        }
+    }
+
+    #[test]
+    fn conformed_scene_is_moved_to_the_current_convention() {
+        let mut scene = Scene {
+            models: vec![Model {
+                meshes: vec![Mesh {
+                    vertices: Vec::new(),
+                    indices: Vec::new(),
+                    transform: Mat4::from_translation(Vec3::Y),
+                    user_id: 0,
+                }],
+                texture: None,
+            }],
+            convention: WorldConvention::YUp,
+            textures: Vec::new(),
+        };
+        scene.conform(WorldConvention::ZUp, true);
+        assert_eq!(scene.convention, WorldConvention::ZUp);
+        assert_close(
+            scene.models[0].meshes[0].transform.w_axis.truncate(),
+            Vec3::Z,
+        );
+    }
+}
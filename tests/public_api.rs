@@ -0,0 +1,7 @@
+//! The public API boundary: Vulkan objects and the registries holding them stay inside the crate,
+//! handles cannot be forged and the raw device is only reached through `unsafe`.
+
+#[test]
+fn vulkan_objects_stay_inside_the_crate() {
+    trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+}
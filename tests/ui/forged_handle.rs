@@ -0,0 +1,3 @@
+fn main() {
+    let _ = pulsar::MeshHandle::new(0, 0);
+}
@@ -0,0 +1,8 @@
+use pulsar::app::Application;
+use winit::window::WindowId;
+
+fn device(app: &Application, window_id: WindowId) -> Option<ash::Device> {
+    app.raw_device(window_id)
+}
+
+fn main() {}
@@ -0,0 +1,3 @@
+use pulsar::model::RegisteredMesh;
+
+fn main() {}
@@ -0,0 +1,3 @@
+use pulsar::vulkan::graphics::AAAGraphics;
+
+fn main() {}